@@ -0,0 +1,12 @@
+//! Public API surface extraction and comparison.
+//!
+//! [`ApiSurface`] models what other assemblies can see of an assembly: its
+//! public and protected types and members, their signatures, attributes and
+//! generic constraints. [`ApiSurface::diff`] compares two versions and
+//! classifies each change as breaking or not, with text and JSON reports.
+
+mod diff;
+mod surface;
+
+pub use diff::*;
+pub use surface::*;
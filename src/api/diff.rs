@@ -0,0 +1,653 @@
+//! Comparing two API surfaces and classifying the changes.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde_json::{Value, json};
+
+use super::surface::{
+    ApiAttribute, ApiForwardedType, ApiGenericParam, ApiMember, ApiSurface, ApiType, ApiTypeKind,
+    ApiVariance,
+};
+
+const OBSOLETE_ATTRIBUTE: &str = "System.ObsoleteAttribute";
+
+/// What changed between two versions of a type or member.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApiChangeKind {
+    TypeAdded,
+    TypeRemoved,
+    /// The type moved to another assembly, which the new version forwards
+    /// it to.
+    TypeForwarded,
+    TypeKindChanged,
+    VisibilityChanged,
+    SealedAdded,
+    SealedRemoved,
+    AbstractAdded,
+    AbstractRemoved,
+    BaseTypeChanged,
+    InterfaceAdded,
+    InterfaceRemoved,
+    GenericParameterChanged,
+    MemberAdded,
+    MemberRemoved,
+    /// The field, return, property or event type changed.
+    MemberTypeChanged,
+    StaticChanged,
+    VirtualAdded,
+    VirtualRemoved,
+    ReadOnlyAdded,
+    ReadOnlyRemoved,
+    ConstantValueChanged,
+    AccessorAdded,
+    AccessorRemoved,
+    AttributeAdded,
+    AttributeRemoved,
+}
+
+impl ApiChangeKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ApiChangeKind::TypeAdded => "TypeAdded",
+            ApiChangeKind::TypeRemoved => "TypeRemoved",
+            ApiChangeKind::TypeForwarded => "TypeForwarded",
+            ApiChangeKind::TypeKindChanged => "TypeKindChanged",
+            ApiChangeKind::VisibilityChanged => "VisibilityChanged",
+            ApiChangeKind::SealedAdded => "SealedAdded",
+            ApiChangeKind::SealedRemoved => "SealedRemoved",
+            ApiChangeKind::AbstractAdded => "AbstractAdded",
+            ApiChangeKind::AbstractRemoved => "AbstractRemoved",
+            ApiChangeKind::BaseTypeChanged => "BaseTypeChanged",
+            ApiChangeKind::InterfaceAdded => "InterfaceAdded",
+            ApiChangeKind::InterfaceRemoved => "InterfaceRemoved",
+            ApiChangeKind::GenericParameterChanged => "GenericParameterChanged",
+            ApiChangeKind::MemberAdded => "MemberAdded",
+            ApiChangeKind::MemberRemoved => "MemberRemoved",
+            ApiChangeKind::MemberTypeChanged => "MemberTypeChanged",
+            ApiChangeKind::StaticChanged => "StaticChanged",
+            ApiChangeKind::VirtualAdded => "VirtualAdded",
+            ApiChangeKind::VirtualRemoved => "VirtualRemoved",
+            ApiChangeKind::ReadOnlyAdded => "ReadOnlyAdded",
+            ApiChangeKind::ReadOnlyRemoved => "ReadOnlyRemoved",
+            ApiChangeKind::ConstantValueChanged => "ConstantValueChanged",
+            ApiChangeKind::AccessorAdded => "AccessorAdded",
+            ApiChangeKind::AccessorRemoved => "AccessorRemoved",
+            ApiChangeKind::AttributeAdded => "AttributeAdded",
+            ApiChangeKind::AttributeRemoved => "AttributeRemoved",
+        }
+    }
+}
+
+/// One difference between two API surfaces.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ApiChange {
+    pub kind: ApiChangeKind,
+    /// Whether code compiled against the old version can break against the
+    /// new one, at compile time or at run time.
+    pub breaking: bool,
+    pub type_name: String,
+    /// The member, as `kind signature`, for member changes.
+    pub member: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for ApiChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let marker = if self.breaking { "BREAKING" } else { "ok" };
+        write!(f, "[{marker}] {}", self.type_name)?;
+        if let Some(member) = &self.member {
+            write!(f, " :: {member}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// The changes from one API surface to another.
+///
+/// ```
+/// use mscoree::{ApiSurface, MetadataBuilder, MetadataReader};
+///
+/// let reader = MetadataReader::from_bytes(MetadataBuilder::new().to_bytes()?)?;
+/// let surface = reader.api_surface()?;
+/// let diff = surface.diff(&surface);
+/// assert!(!diff.is_breaking());
+/// assert_eq!(diff.to_json()["changes"].as_array().map(Vec::len), Some(0));
+/// # Ok::<(), mscoree::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiDiff {
+    /// The display names of the compared assemblies, if they have manifests.
+    pub old_assembly: Option<String>,
+    pub new_assembly: Option<String>,
+    /// The changes, grouped by type in name order.
+    pub changes: Vec<ApiChange>,
+}
+
+impl ApiDiff {
+    /// Returns `true` if any change is breaking.
+    pub fn is_breaking(&self) -> bool {
+        self.changes.iter().any(|change| change.breaking)
+    }
+
+    /// Returns the breaking changes.
+    pub fn breaking_changes(&self) -> impl Iterator<Item = &ApiChange> {
+        self.changes.iter().filter(|change| change.breaking)
+    }
+
+    /// Returns the report as JSON:
+    /// `{"oldAssembly", "newAssembly", "breaking", "changes": [...]}`.
+    pub fn to_json(&self) -> Value {
+        let changes: Vec<Value> = self
+            .changes
+            .iter()
+            .map(|change| {
+                json!({
+                    "kind": change.kind.name(),
+                    "breaking": change.breaking,
+                    "type": change.type_name,
+                    "member": change.member,
+                    "message": change.message,
+                })
+            })
+            .collect();
+        json!({
+            "oldAssembly": self.old_assembly,
+            "newAssembly": self.new_assembly,
+            "breaking": self.is_breaking(),
+            "changes": changes,
+        })
+    }
+}
+
+/// Writes the text report: a summary line followed by one line per change.
+impl fmt::Display for ApiDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let (Some(old), Some(new)) = (&self.old_assembly, &self.new_assembly) {
+            writeln!(f, "{old} -> {new}")?;
+        }
+        let breaking = self.breaking_changes().count();
+        writeln!(f, "{} change(s), {breaking} breaking", self.changes.len())?;
+        for change in &self.changes {
+            writeln!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+impl ApiSurface {
+    /// Compares this (older) surface with `newer`.
+    ///
+    /// A type that moved to another assembly is not removed as long as the
+    /// newer version forwards it there.
+    pub fn diff(&self, newer: &ApiSurface) -> ApiDiff {
+        let old_types: BTreeMap<&str, &ApiType> =
+            self.types.iter().map(|ty| (ty.name.as_str(), ty)).collect();
+        let new_types: BTreeMap<&str, &ApiType> = newer
+            .types
+            .iter()
+            .map(|ty| (ty.name.as_str(), ty))
+            .collect();
+        let old_forwarded: BTreeMap<&str, &ApiForwardedType> = self
+            .forwarded_types
+            .iter()
+            .map(|ty| (ty.name.as_str(), ty))
+            .collect();
+        let new_forwarded: BTreeMap<&str, &ApiForwardedType> = newer
+            .forwarded_types
+            .iter()
+            .map(|ty| (ty.name.as_str(), ty))
+            .collect();
+        let mut changes = Changes::default();
+        for (name, old) in &old_types {
+            match (new_types.get(name), new_forwarded.get(name)) {
+                (Some(new), _) => changes.compare_types(old, new),
+                (None, Some(forwarded)) => changes.push_type(
+                    ApiChangeKind::TypeForwarded,
+                    false,
+                    name,
+                    format!(
+                        "{} {name} was moved to {}",
+                        old.kind.name(),
+                        forwarded.assembly
+                    ),
+                ),
+                (None, None) => changes.push_type(
+                    ApiChangeKind::TypeRemoved,
+                    true,
+                    name,
+                    format!("{} {name} was removed", old.kind.name()),
+                ),
+            }
+        }
+        for (name, old) in &old_forwarded {
+            match (new_types.contains_key(name), new_forwarded.get(name)) {
+                (false, Some(new)) if new.assembly != old.assembly => changes.push_type(
+                    ApiChangeKind::TypeForwarded,
+                    false,
+                    name,
+                    format!(
+                        "forwarded type {name} was moved from {} to {}",
+                        old.assembly, new.assembly
+                    ),
+                ),
+                (false, None) => changes.push_type(
+                    ApiChangeKind::TypeRemoved,
+                    true,
+                    name,
+                    format!("forwarded type {name} was removed"),
+                ),
+                _ => {}
+            }
+        }
+        for (name, new) in &new_types {
+            // A type that moves back is still the same type.
+            if !old_types.contains_key(name) && !old_forwarded.contains_key(name) {
+                changes.push_type(
+                    ApiChangeKind::TypeAdded,
+                    false,
+                    name,
+                    format!("{} {name} was added", new.kind.name()),
+                );
+            }
+        }
+        for name in new_forwarded.keys() {
+            if !old_types.contains_key(name) && !old_forwarded.contains_key(name) {
+                changes.push_type(
+                    ApiChangeKind::TypeAdded,
+                    false,
+                    name,
+                    format!("forwarded type {name} was added"),
+                );
+            }
+        }
+        changes.0.sort_by(|a, b| a.type_name.cmp(&b.type_name));
+        ApiDiff {
+            old_assembly: self.assembly.as_ref().map(ToString::to_string),
+            new_assembly: newer.assembly.as_ref().map(ToString::to_string),
+            changes: changes.0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Changes(Vec<ApiChange>);
+
+impl Changes {
+    fn push_type(&mut self, kind: ApiChangeKind, breaking: bool, ty: &str, message: String) {
+        self.0.push(ApiChange {
+            kind,
+            breaking,
+            type_name: ty.to_string(),
+            member: None,
+            message,
+        });
+    }
+
+    fn push_member(
+        &mut self,
+        kind: ApiChangeKind,
+        breaking: bool,
+        ty: &str,
+        member: &ApiMember,
+        message: String,
+    ) {
+        self.0.push(ApiChange {
+            kind,
+            breaking,
+            type_name: ty.to_string(),
+            member: Some(member.to_string()),
+            message,
+        });
+    }
+
+    fn compare_types(&mut self, old: &ApiType, new: &ApiType) {
+        let name = old.name.as_str();
+        if old.kind != new.kind {
+            self.push_type(
+                ApiChangeKind::TypeKindChanged,
+                true,
+                name,
+                format!("changed from {} to {}", old.kind.name(), new.kind.name()),
+            );
+            // Members of a type of another kind are not comparable.
+            return;
+        }
+        if old.visibility != new.visibility {
+            self.push_type(
+                ApiChangeKind::VisibilityChanged,
+                new.visibility < old.visibility,
+                name,
+                format!(
+                    "visibility changed from {} to {}",
+                    old.visibility.name(),
+                    new.visibility.name()
+                ),
+            );
+        }
+        // Structs, enums and delegates are always sealed.
+        if old.kind == ApiTypeKind::Class {
+            if !old.is_sealed && new.is_sealed {
+                self.push_type(
+                    ApiChangeKind::SealedAdded,
+                    true,
+                    name,
+                    "type was sealed".into(),
+                );
+            } else if old.is_sealed && !new.is_sealed {
+                self.push_type(
+                    ApiChangeKind::SealedRemoved,
+                    false,
+                    name,
+                    "type is no longer sealed".into(),
+                );
+            }
+            if !old.is_abstract && new.is_abstract {
+                self.push_type(
+                    ApiChangeKind::AbstractAdded,
+                    true,
+                    name,
+                    "type was made abstract".into(),
+                );
+            } else if old.is_abstract && !new.is_abstract {
+                self.push_type(
+                    ApiChangeKind::AbstractRemoved,
+                    false,
+                    name,
+                    "type is no longer abstract".into(),
+                );
+            }
+        }
+        if old.base_type != new.base_type {
+            self.push_type(
+                ApiChangeKind::BaseTypeChanged,
+                true,
+                name,
+                format!(
+                    "base type changed from {} to {}",
+                    old.base_type.as_deref().unwrap_or("(none)"),
+                    new.base_type.as_deref().unwrap_or("(none)")
+                ),
+            );
+        }
+        for interface in &old.interfaces {
+            if !new.interfaces.contains(interface) {
+                self.push_type(
+                    ApiChangeKind::InterfaceRemoved,
+                    true,
+                    name,
+                    format!("no longer implements {interface}"),
+                );
+            }
+        }
+        for interface in &new.interfaces {
+            if !old.interfaces.contains(interface) {
+                // New members of an interface break its implementers, but
+                // implementing another interface breaks no caller.
+                self.push_type(
+                    ApiChangeKind::InterfaceAdded,
+                    false,
+                    name,
+                    format!("implements {interface}"),
+                );
+            }
+        }
+        for (breaking, message) in generic_param_changes(&old.generic_params, &new.generic_params) {
+            self.push_type(
+                ApiChangeKind::GenericParameterChanged,
+                breaking,
+                name,
+                message,
+            );
+        }
+        for (kind, breaking, message) in attribute_changes(&old.attributes, &new.attributes) {
+            self.push_type(kind, breaking, name, message);
+        }
+        self.compare_members(old, new);
+    }
+
+    fn compare_members(&mut self, old_type: &ApiType, new_type: &ApiType) {
+        let name = old_type.name.as_str();
+        let old: BTreeMap<_, &ApiMember> = old_type
+            .members
+            .iter()
+            .map(|member| ((member.kind, member.signature.as_str()), member))
+            .collect();
+        let new: BTreeMap<_, &ApiMember> = new_type
+            .members
+            .iter()
+            .map(|member| ((member.kind, member.signature.as_str()), member))
+            .collect();
+        for (key, old_member) in &old {
+            match new.get(key) {
+                Some(new_member) => self.compare_member(name, old_member, new_member),
+                None => self.push_member(
+                    ApiChangeKind::MemberRemoved,
+                    true,
+                    name,
+                    old_member,
+                    "member was removed".into(),
+                ),
+            }
+        }
+        for (key, new_member) in &new {
+            if old.contains_key(key) {
+                continue;
+            }
+            // Implementers of an interface or derived types of an abstract
+            // class must now provide the member.
+            let breaking = new_member.is_abstract && !new_type.is_sealed;
+            let message = if breaking {
+                "abstract member was added".to_string()
+            } else {
+                "member was added".to_string()
+            };
+            self.push_member(
+                ApiChangeKind::MemberAdded,
+                breaking,
+                name,
+                new_member,
+                message,
+            );
+        }
+    }
+
+    fn compare_member(&mut self, ty: &str, old: &ApiMember, new: &ApiMember) {
+        if old.type_name != new.type_name {
+            self.push_member(
+                ApiChangeKind::MemberTypeChanged,
+                true,
+                ty,
+                old,
+                format!("type changed from {} to {}", old.type_name, new.type_name),
+            );
+        }
+        if old.visibility != new.visibility {
+            self.push_member(
+                ApiChangeKind::VisibilityChanged,
+                new.visibility < old.visibility,
+                ty,
+                old,
+                format!(
+                    "visibility changed from {} to {}",
+                    old.visibility.name(),
+                    new.visibility.name()
+                ),
+            );
+        }
+        if old.is_static != new.is_static {
+            let message = if new.is_static {
+                "member was made static"
+            } else {
+                "member is no longer static"
+            };
+            self.push_member(ApiChangeKind::StaticChanged, true, ty, old, message.into());
+        }
+        let old_overridable = old.is_virtual || old.is_abstract;
+        let new_overridable = new.is_virtual || new.is_abstract;
+        if old_overridable && !new_overridable {
+            let (kind, message) = if new.is_sealed {
+                (ApiChangeKind::SealedAdded, "member was sealed")
+            } else {
+                (ApiChangeKind::VirtualRemoved, "member is no longer virtual")
+            };
+            self.push_member(kind, true, ty, old, message.into());
+        } else if !old_overridable && new_overridable {
+            let (kind, message) = if old.is_sealed {
+                (ApiChangeKind::SealedRemoved, "member is no longer sealed")
+            } else {
+                (ApiChangeKind::VirtualAdded, "member was made virtual")
+            };
+            self.push_member(kind, false, ty, old, message.into());
+        }
+        if !old.is_abstract && new.is_abstract {
+            self.push_member(
+                ApiChangeKind::AbstractAdded,
+                true,
+                ty,
+                old,
+                "member was made abstract".into(),
+            );
+        } else if old.is_abstract && !new.is_abstract {
+            self.push_member(
+                ApiChangeKind::AbstractRemoved,
+                false,
+                ty,
+                old,
+                "member is no longer abstract".into(),
+            );
+        }
+        if !old.is_read_only && new.is_read_only {
+            self.push_member(
+                ApiChangeKind::ReadOnlyAdded,
+                true,
+                ty,
+                old,
+                "field was made readonly".into(),
+            );
+        } else if old.is_read_only && !new.is_read_only {
+            self.push_member(
+                ApiChangeKind::ReadOnlyRemoved,
+                false,
+                ty,
+                old,
+                "field is no longer readonly".into(),
+            );
+        }
+        if old.value != new.value {
+            // Callers compiled the old value into their own code.
+            self.push_member(
+                ApiChangeKind::ConstantValueChanged,
+                true,
+                ty,
+                old,
+                format!(
+                    "value changed from {} to {}",
+                    old.value.as_deref().unwrap_or("(none)"),
+                    new.value.as_deref().unwrap_or("(none)")
+                ),
+            );
+        }
+        for accessor in &old.accessors {
+            if !new.accessors.contains(accessor) {
+                self.push_member(
+                    ApiChangeKind::AccessorRemoved,
+                    true,
+                    ty,
+                    old,
+                    format!("{accessor} accessor was removed"),
+                );
+            }
+        }
+        for accessor in &new.accessors {
+            if !old.accessors.contains(accessor) {
+                self.push_member(
+                    ApiChangeKind::AccessorAdded,
+                    false,
+                    ty,
+                    old,
+                    format!("{accessor} accessor was added"),
+                );
+            }
+        }
+        for (breaking, message) in generic_param_changes(&old.generic_params, &new.generic_params) {
+            self.push_member(
+                ApiChangeKind::GenericParameterChanged,
+                breaking,
+                ty,
+                old,
+                message,
+            );
+        }
+        for (kind, breaking, message) in attribute_changes(&old.attributes, &new.attributes) {
+            self.push_member(kind, breaking, ty, old, message);
+        }
+    }
+}
+
+/// Compares generic parameters by position; their count is part of the
+/// name or signature, so it never differs here.
+fn generic_param_changes(old: &[ApiGenericParam], new: &[ApiGenericParam]) -> Vec<(bool, String)> {
+    let mut changes = Vec::new();
+    for (old, new) in old.iter().zip(new) {
+        for constraint in &new.constraints {
+            if !old.constraints.contains(constraint) {
+                changes.push((
+                    true,
+                    format!("constraint {constraint} was added to {}", new.name),
+                ));
+            }
+        }
+        for constraint in &old.constraints {
+            if !new.constraints.contains(constraint) {
+                changes.push((
+                    false,
+                    format!("constraint {constraint} was removed from {}", new.name),
+                ));
+            }
+        }
+        if old.variance != new.variance {
+            changes.push((
+                old.variance != ApiVariance::Invariant,
+                format!(
+                    "variance of {} changed from {:?} to {:?}",
+                    new.name, old.variance, new.variance
+                ),
+            ));
+        }
+    }
+    changes
+}
+
+fn attribute_changes(
+    old: &[ApiAttribute],
+    new: &[ApiAttribute],
+) -> Vec<(ApiChangeKind, bool, String)> {
+    let mut changes = Vec::new();
+    for attribute in old {
+        if !new.contains(attribute) {
+            changes.push((
+                ApiChangeKind::AttributeRemoved,
+                false,
+                format!("attribute {attribute} was removed"),
+            ));
+        }
+    }
+    for attribute in new {
+        if !old.contains(attribute) {
+            changes.push((
+                ApiChangeKind::AttributeAdded,
+                is_obsolete_error(attribute),
+                format!("attribute {attribute} was added"),
+            ));
+        }
+    }
+    changes
+}
+
+/// Returns `true` for `[Obsolete(message, true)]`, which turns every use
+/// into a compile error.
+fn is_obsolete_error(attribute: &ApiAttribute) -> bool {
+    attribute.type_name == OBSOLETE_ATTRIBUTE
+        && attribute.arguments.get(1).is_some_and(|arg| arg == "true")
+}
@@ -0,0 +1,888 @@
+//! The API model: the types and members of an assembly visible outside it.
+
+use std::fmt;
+
+use crate::assembly_identity::AssemblyIdentity;
+use crate::error::{Error, Result};
+use crate::reader::{
+    AssemblyRefRow, CustomAttributeArgument, EventRow, ExportedTypeRow, FieldRow,
+    GenericParamConstraintRow, GenericParamRow, InterfaceImplRow, MetadataReader, MethodDefRow,
+    MethodSemanticsRow, MethodSig, PropertyRow, PropertySig, TYPE_ATTRIBUTE_INTERFACE, TableId,
+    TypeDefRow, TypeSig, TypeSpecRow, parse_field_sig, token_rid,
+};
+
+const TYPE_FORWARDED_TO_ATTRIBUTE: &str =
+    "System.Runtime.CompilerServices.TypeForwardedToAttribute";
+
+const TYPE_ATTRIBUTE_VISIBILITY_MASK: u32 = 0x0000_0007;
+const TYPE_ATTRIBUTE_PUBLIC: u32 = 0x0000_0001;
+const TYPE_ATTRIBUTE_NESTED_PUBLIC: u32 = 0x0000_0002;
+const TYPE_ATTRIBUTE_NESTED_FAMILY: u32 = 0x0000_0004;
+const TYPE_ATTRIBUTE_NESTED_FAM_OR_ASSEM: u32 = 0x0000_0007;
+const TYPE_ATTRIBUTE_ABSTRACT: u32 = 0x0000_0080;
+const TYPE_ATTRIBUTE_SEALED: u32 = 0x0000_0100;
+
+/// `MethodAttributes.MemberAccessMask` and `FieldAttributes.FieldAccessMask`.
+const MEMBER_ACCESS_MASK: u16 = 0x0007;
+const MEMBER_ACCESS_FAMILY: u16 = 0x0004;
+const MEMBER_ACCESS_FAM_OR_ASSEM: u16 = 0x0005;
+const MEMBER_ACCESS_PUBLIC: u16 = 0x0006;
+const MEMBER_STATIC: u16 = 0x0010;
+
+const FIELD_ATTRIBUTE_INIT_ONLY: u16 = 0x0020;
+const FIELD_ATTRIBUTE_LITERAL: u16 = 0x0040;
+
+const METHOD_ATTRIBUTE_FINAL: u16 = 0x0020;
+const METHOD_ATTRIBUTE_VIRTUAL: u16 = 0x0040;
+const METHOD_ATTRIBUTE_ABSTRACT: u16 = 0x0400;
+
+const METHOD_SEMANTICS_SETTER: u16 = 0x0001;
+const METHOD_SEMANTICS_GETTER: u16 = 0x0002;
+const METHOD_SEMANTICS_OTHER: u16 = 0x0004;
+const METHOD_SEMANTICS_ADD_ON: u16 = 0x0008;
+const METHOD_SEMANTICS_REMOVE_ON: u16 = 0x0010;
+const METHOD_SEMANTICS_FIRE: u16 = 0x0020;
+
+const GENERIC_PARAM_VARIANCE_MASK: u16 = 0x0003;
+const GENERIC_PARAM_COVARIANT: u16 = 0x0001;
+const GENERIC_PARAM_CONTRAVARIANT: u16 = 0x0002;
+const GENERIC_PARAM_REFERENCE_TYPE: u16 = 0x0004;
+const GENERIC_PARAM_NOT_NULLABLE_VALUE_TYPE: u16 = 0x0008;
+const GENERIC_PARAM_DEFAULT_CONSTRUCTOR: u16 = 0x0010;
+
+/// Column of `GenericParam.Owner`.
+const GENERIC_PARAM_OWNER: usize = 2;
+/// Column of `GenericParamConstraint.Owner`.
+const GENERIC_PARAM_CONSTRAINT_OWNER: usize = 0;
+/// Column of `InterfaceImpl.Class`.
+const INTERFACE_IMPL_CLASS: usize = 0;
+/// Column of `MethodSemantics.Association`.
+const METHOD_SEMANTICS_ASSOCIATION: usize = 2;
+
+/// How far outside its assembly a type or member can be seen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ApiVisibility {
+    /// Visible to derived types only (`protected` or `protected internal`).
+    Protected,
+    Public,
+}
+
+impl ApiVisibility {
+    pub fn name(self) -> &'static str {
+        match self {
+            ApiVisibility::Protected => "protected",
+            ApiVisibility::Public => "public",
+        }
+    }
+}
+
+/// The kind of a type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApiTypeKind {
+    Class,
+    Interface,
+    Struct,
+    Enum,
+    Delegate,
+}
+
+impl ApiTypeKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ApiTypeKind::Class => "class",
+            ApiTypeKind::Interface => "interface",
+            ApiTypeKind::Struct => "struct",
+            ApiTypeKind::Enum => "enum",
+            ApiTypeKind::Delegate => "delegate",
+        }
+    }
+}
+
+/// The kind of a member.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ApiMemberKind {
+    Field,
+    Constructor,
+    Method,
+    Property,
+    Event,
+}
+
+impl ApiMemberKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ApiMemberKind::Field => "field",
+            ApiMemberKind::Constructor => "constructor",
+            ApiMemberKind::Method => "method",
+            ApiMemberKind::Property => "property",
+            ApiMemberKind::Event => "event",
+        }
+    }
+}
+
+/// The variance of a generic parameter of an interface or delegate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApiVariance {
+    Invariant,
+    /// `out T`.
+    Covariant,
+    /// `in T`.
+    Contravariant,
+}
+
+/// A custom attribute applied to a type or member.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ApiAttribute {
+    pub type_name: String,
+    /// The constructor arguments followed by the named arguments
+    /// (`Name = value`), in C# notation.
+    pub arguments: Vec<String>,
+}
+
+impl fmt::Display for ApiAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.type_name)?;
+        if !self.arguments.is_empty() {
+            write!(f, "({})", self.arguments.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A generic parameter with its constraints
+/// (`IMetaDataImport2::GetGenericParamConstraintProps`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ApiGenericParam {
+    pub name: String,
+    pub variance: ApiVariance,
+    /// The special constraints (`class`, `struct`, `new()`) followed by the
+    /// constraint types, sorted.
+    pub constraints: Vec<String>,
+}
+
+/// A visible field, method, property or event.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ApiMember {
+    pub kind: ApiMemberKind,
+    pub name: String,
+    /// The name and parameter types that identify the member within its
+    /// type, e.g. ``Parse`1(System.String,!!0&)`` or `Item[System.Int32]`.
+    pub signature: String,
+    /// The field, return, property or event type.
+    pub type_name: String,
+    pub visibility: ApiVisibility,
+    pub is_static: bool,
+    /// Whether derived types can override the member (`virtual` and not
+    /// `sealed`).
+    pub is_virtual: bool,
+    pub is_abstract: bool,
+    /// Whether an override is `sealed`.
+    pub is_sealed: bool,
+    /// Whether a field is `readonly`.
+    pub is_read_only: bool,
+    /// The value of a constant field.
+    pub value: Option<String>,
+    /// The visible accessors of a property or event (`get`, `set`, `add`,
+    /// `remove`, `raise`).
+    pub accessors: Vec<String>,
+    pub generic_params: Vec<ApiGenericParam>,
+    pub attributes: Vec<ApiAttribute>,
+}
+
+impl fmt::Display for ApiMember {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.kind.name(), self.signature)
+    }
+}
+
+/// A visible type and its visible members.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ApiType {
+    /// The full name, with nested types separated by `+`.
+    pub name: String,
+    pub kind: ApiTypeKind,
+    pub visibility: ApiVisibility,
+    pub is_abstract: bool,
+    pub is_sealed: bool,
+    pub base_type: Option<String>,
+    /// The directly implemented visible interfaces, sorted.
+    pub interfaces: Vec<String>,
+    pub generic_params: Vec<ApiGenericParam>,
+    pub attributes: Vec<ApiAttribute>,
+    /// The members, sorted by kind and signature.
+    pub members: Vec<ApiMember>,
+}
+
+impl ApiType {
+    /// Returns `true` for a `static` class.
+    pub fn is_static(&self) -> bool {
+        self.kind == ApiTypeKind::Class && self.is_abstract && self.is_sealed
+    }
+
+    /// Returns the member with the given kind and signature.
+    pub fn member(&self, kind: ApiMemberKind, signature: &str) -> Option<&ApiMember> {
+        self.members
+            .iter()
+            .find(|member| member.kind == kind && member.signature == signature)
+    }
+}
+
+/// A type the assembly forwards to another assembly, with an
+/// `ExportedType` row or a `[TypeForwardedTo]` attribute.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ApiForwardedType {
+    /// The full name, with nested types separated by `+`.
+    pub name: String,
+    /// The simple name of the assembly that defines the type.
+    pub assembly: String,
+}
+
+/// The public API of an assembly.
+///
+/// Signatures name types by their full names, with generic parameters
+/// written positionally (`!0` for a type's, `!!0` for a method's), so that
+/// surfaces of different builds compare equal when the API is unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiSurface {
+    pub assembly: Option<AssemblyIdentity>,
+    /// The visible types, sorted by name.
+    pub types: Vec<ApiType>,
+    /// The types forwarded to other assemblies, sorted by name.
+    pub forwarded_types: Vec<ApiForwardedType>,
+}
+
+impl ApiSurface {
+    /// Returns the type with the given full name.
+    pub fn find_type(&self, name: &str) -> Option<&ApiType> {
+        self.types.iter().find(|ty| ty.name == name)
+    }
+
+    /// Returns the forwarder of the type with the given full name.
+    pub fn find_forwarded_type(&self, name: &str) -> Option<&ApiForwardedType> {
+        self.forwarded_types.iter().find(|ty| ty.name == name)
+    }
+}
+
+impl MetadataReader {
+    /// Extracts the public API of the assembly: every type reachable from
+    /// outside it, with its public and protected members, and the types it
+    /// forwards to other assemblies.
+    pub fn api_surface(&self) -> Result<ApiSurface> {
+        let mut types = Vec::new();
+        for rid in 1..=self.row_count(TableId::TypeDef) {
+            if let Some(visibility) = self.api_type_visibility(rid)? {
+                types.push(self.api_type(rid, visibility)?);
+            }
+        }
+        types.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(ApiSurface {
+            assembly: self.assembly_identity()?,
+            types,
+            forwarded_types: self.api_forwarded_types()?,
+        })
+    }
+
+    /// Returns the type forwarders of the assembly. Compilers turn
+    /// `[TypeForwardedTo]` into `ExportedType` rows, but hand-written
+    /// metadata may carry only the attribute.
+    fn api_forwarded_types(&self) -> Result<Vec<ApiForwardedType>> {
+        let mut forwarded = Vec::new();
+        for rid in 1..=self.row_count(TableId::ExportedType) {
+            if let Some(ty) = self.api_exported_type_forwarder(rid)? {
+                forwarded.push(ty);
+            }
+        }
+        if self.row_count(TableId::Assembly) != 0 {
+            let assembly = TableId::Assembly.token(1);
+            for rid in self.find_custom_attributes(assembly, TYPE_FORWARDED_TO_ATTRIBUTE)? {
+                let value = self.custom_attribute_value(rid)?;
+                if let Some(CustomAttributeArgument::Type(Some(qualified))) =
+                    value.fixed_args.first()
+                {
+                    let mut parts = qualified.split(',').map(str::trim);
+                    forwarded.push(ApiForwardedType {
+                        name: parts.next().unwrap_or_default().to_string(),
+                        assembly: parts.next().unwrap_or_default().to_string(),
+                    });
+                }
+            }
+        }
+        forwarded.sort_by(|a, b| a.name.cmp(&b.name));
+        forwarded.dedup_by(|a, b| a.name == b.name);
+        Ok(forwarded)
+    }
+
+    /// Returns the forwarder an `ExportedType` row describes, or `None` for
+    /// a type exported from another module of the assembly.
+    fn api_exported_type_forwarder(&self, rid: u32) -> Result<Option<ApiForwardedType>> {
+        let mut name = String::new();
+        let mut current = rid;
+        for _ in 0..self.row_count(TableId::ExportedType) {
+            let row = self.row::<ExportedTypeRow>(current)?;
+            let (namespace, own) = (self.string(row.namespace)?, self.string(row.name)?);
+            let own = if namespace.is_empty() {
+                own.to_string()
+            } else {
+                format!("{namespace}.{own}")
+            };
+            name = if name.is_empty() {
+                own
+            } else {
+                format!("{own}+{name}")
+            };
+            match TableId::from_token(row.implementation) {
+                Some((TableId::AssemblyRef, scope)) => {
+                    let assembly = self.string(self.row::<AssemblyRefRow>(scope)?.name)?;
+                    return Ok(Some(ApiForwardedType {
+                        name,
+                        assembly: assembly.to_string(),
+                    }));
+                }
+                Some((TableId::ExportedType, outer)) => current = outer,
+                _ => return Ok(None),
+            }
+        }
+        Err(Error::BadMetadata(format!(
+            "exported type {:#010x} is nested in itself",
+            TableId::ExportedType.token(rid)
+        )))
+    }
+
+    /// Returns how visible a type is outside the assembly, taking the
+    /// enclosing types into account.
+    fn api_type_visibility(&self, rid: u32) -> Result<Option<ApiVisibility>> {
+        let mut visibility = ApiVisibility::Public;
+        let mut current = rid;
+        for _ in 0..self.row_count(TableId::TypeDef) {
+            let flags = self.row::<TypeDefRow>(current)?.flags;
+            let own = match flags & TYPE_ATTRIBUTE_VISIBILITY_MASK {
+                TYPE_ATTRIBUTE_PUBLIC | TYPE_ATTRIBUTE_NESTED_PUBLIC => ApiVisibility::Public,
+                TYPE_ATTRIBUTE_NESTED_FAMILY | TYPE_ATTRIBUTE_NESTED_FAM_OR_ASSEM => {
+                    ApiVisibility::Protected
+                }
+                _ => return Ok(None),
+            };
+            visibility = visibility.min(own);
+            if flags & TYPE_ATTRIBUTE_VISIBILITY_MASK == TYPE_ATTRIBUTE_PUBLIC {
+                return Ok(Some(visibility));
+            }
+            match self.enclosing_type(current)? {
+                // Nothing outside the assembly derives from a sealed type.
+                Some(outer)
+                    if own == ApiVisibility::Protected
+                        && self.row::<TypeDefRow>(outer)?.flags & TYPE_ATTRIBUTE_SEALED != 0 =>
+                {
+                    return Ok(None);
+                }
+                Some(outer) => current = outer,
+                None => return Ok(None),
+            }
+        }
+        Err(Error::BadMetadata(format!(
+            "type {:#010x} is nested in itself",
+            TableId::TypeDef.token(rid)
+        )))
+    }
+
+    fn api_type(&self, rid: u32, visibility: ApiVisibility) -> Result<ApiType> {
+        let token = TableId::TypeDef.token(rid);
+        let row = self.row::<TypeDefRow>(rid)?;
+        let base_type = if token_rid(row.extends) == 0 {
+            None
+        } else {
+            Some(self.api_type_token_name(row.extends)?)
+        };
+        let kind = if row.flags & TYPE_ATTRIBUTE_INTERFACE != 0 {
+            ApiTypeKind::Interface
+        } else {
+            match base_type.as_deref() {
+                Some("System.Enum") => ApiTypeKind::Enum,
+                Some("System.ValueType") => ApiTypeKind::Struct,
+                Some("System.MulticastDelegate") => ApiTypeKind::Delegate,
+                _ => ApiTypeKind::Class,
+            }
+        };
+        let mut interfaces = Vec::new();
+        for impl_rid in self.find_rows(TableId::InterfaceImpl, INTERFACE_IMPL_CLASS, rid)? {
+            let interface = self.row::<InterfaceImplRow>(impl_rid)?.interface;
+            if self.is_api_visible_type(interface)? {
+                interfaces.push(self.api_type_token_name(interface)?);
+            }
+        }
+        interfaces.sort();
+
+        let mut members = Vec::new();
+        let sealed = row.flags & TYPE_ATTRIBUTE_SEALED != 0;
+        let accessors = self.api_accessors(rid)?;
+        for field in self.type_def_fields(rid)? {
+            if let Some(member) = self.api_field(field, sealed)? {
+                members.push(member);
+            }
+        }
+        for method in self.type_def_methods(rid)? {
+            if accessors.contains(&method) {
+                continue;
+            }
+            if let Some(member) = self.api_method(method, sealed)? {
+                members.push(member);
+            }
+        }
+        for property in self.type_def_properties(rid)? {
+            if let Some(member) = self.api_property(property, sealed)? {
+                members.push(member);
+            }
+        }
+        for event in self.type_def_events(rid)? {
+            if let Some(member) = self.api_event(event, sealed)? {
+                members.push(member);
+            }
+        }
+        members.sort_by(|a, b| (a.kind, &a.signature).cmp(&(b.kind, &b.signature)));
+
+        Ok(ApiType {
+            name: self.type_full_name(token)?,
+            kind,
+            visibility,
+            is_abstract: row.flags & TYPE_ATTRIBUTE_ABSTRACT != 0 && kind != ApiTypeKind::Interface,
+            is_sealed: sealed,
+            base_type,
+            interfaces,
+            generic_params: self.api_generic_params(token)?,
+            attributes: self.api_attributes(token)?,
+            members,
+        })
+    }
+
+    /// Returns the `MethodDef` rids of the property and event accessors of a
+    /// type.
+    fn api_accessors(&self, type_rid: u32) -> Result<Vec<u32>> {
+        let mut owners: Vec<u32> = self
+            .type_def_properties(type_rid)?
+            .into_iter()
+            .map(|rid| TableId::Property.token(rid))
+            .collect();
+        owners.extend(
+            self.type_def_events(type_rid)?
+                .into_iter()
+                .map(|rid| TableId::Event.token(rid)),
+        );
+        let mut accessors = Vec::new();
+        for owner in owners {
+            for (_, method) in self.api_semantics(owner)? {
+                accessors.push(method);
+            }
+        }
+        Ok(accessors)
+    }
+
+    /// Returns the `(Semantics, MethodDef rid)` pairs of a property or
+    /// event.
+    fn api_semantics(&self, owner: u32) -> Result<Vec<(u16, u32)>> {
+        self.find_rows(
+            TableId::MethodSemantics,
+            METHOD_SEMANTICS_ASSOCIATION,
+            owner,
+        )?
+        .into_iter()
+        .map(|rid| {
+            let row = self.row::<MethodSemanticsRow>(rid)?;
+            Ok((row.semantics, row.method))
+        })
+        .collect()
+    }
+
+    fn api_field(&self, rid: u32, sealed: bool) -> Result<Option<ApiMember>> {
+        let row = self.row::<FieldRow>(rid)?;
+        let Some(visibility) = member_visibility(row.flags, sealed) else {
+            return Ok(None);
+        };
+        let token = TableId::Field.token(rid);
+        let name = self.string(row.name)?.to_string();
+        let ty = parse_field_sig(self.blob(row.signature)?)?;
+        let value = if row.flags & FIELD_ATTRIBUTE_LITERAL != 0 {
+            self.constant(token)?.map(|value| value.to_string())
+        } else {
+            None
+        };
+        Ok(Some(ApiMember {
+            kind: ApiMemberKind::Field,
+            signature: name.clone(),
+            name,
+            type_name: self.api_type_name(&ty)?,
+            visibility,
+            is_static: row.flags & MEMBER_STATIC != 0,
+            is_virtual: false,
+            is_abstract: false,
+            is_sealed: false,
+            is_read_only: row.flags & FIELD_ATTRIBUTE_INIT_ONLY != 0,
+            value,
+            accessors: Vec::new(),
+            generic_params: Vec::new(),
+            attributes: self.api_attributes(token)?,
+        }))
+    }
+
+    fn api_method(&self, rid: u32, sealed: bool) -> Result<Option<ApiMember>> {
+        let row = self.row::<MethodDefRow>(rid)?;
+        let Some(visibility) = member_visibility(row.flags, sealed) else {
+            return Ok(None);
+        };
+        let token = TableId::MethodDef.token(rid);
+        let name = self.string(row.name)?.to_string();
+        if name == ".cctor" {
+            return Ok(None);
+        }
+        let sig = MethodSig::parse(self.blob(row.signature)?)?;
+        let params = self.api_type_names(&sig.params)?;
+        let type_name = self.api_type_name(&sig.return_type)?;
+        let mut signature = name.clone();
+        if sig.generic_param_count != 0 {
+            signature.push_str(&format!("`{}", sig.generic_param_count));
+        }
+        signature.push_str(&format!("({})", params.join(",")));
+        // Conversion operators are overloaded on their return type only.
+        if matches!(name.as_str(), "op_Implicit" | "op_Explicit") {
+            signature.push_str(&format!(":{type_name}"));
+        }
+        let is_virtual = row.flags & METHOD_ATTRIBUTE_VIRTUAL != 0;
+        let is_final = row.flags & METHOD_ATTRIBUTE_FINAL != 0;
+        Ok(Some(ApiMember {
+            kind: if name == ".ctor" {
+                ApiMemberKind::Constructor
+            } else {
+                ApiMemberKind::Method
+            },
+            name,
+            signature,
+            type_name,
+            visibility,
+            is_static: row.flags & MEMBER_STATIC != 0,
+            is_virtual: is_virtual && !is_final,
+            is_abstract: row.flags & METHOD_ATTRIBUTE_ABSTRACT != 0,
+            is_sealed: is_virtual && is_final,
+            is_read_only: false,
+            value: None,
+            accessors: Vec::new(),
+            generic_params: self.api_generic_params(token)?,
+            attributes: self.api_attributes(token)?,
+        }))
+    }
+
+    fn api_property(&self, rid: u32, sealed: bool) -> Result<Option<ApiMember>> {
+        let row = self.row::<PropertyRow>(rid)?;
+        let token = TableId::Property.token(rid);
+        let name = self.string(row.name)?.to_string();
+        let sig = PropertySig::parse(self.blob(row.signature)?)?;
+        let mut signature = name.clone();
+        if !sig.params.is_empty() {
+            signature.push_str(&format!(
+                "[{}]",
+                self.api_type_names(&sig.params)?.join(",")
+            ));
+        }
+        let accessors = [
+            (METHOD_SEMANTICS_GETTER, "get"),
+            (METHOD_SEMANTICS_SETTER, "set"),
+        ];
+        self.api_accessor_member(
+            token,
+            ApiMemberKind::Property,
+            name,
+            signature,
+            self.api_type_name(&sig.ty)?,
+            &accessors,
+            sealed,
+        )
+    }
+
+    fn api_event(&self, rid: u32, sealed: bool) -> Result<Option<ApiMember>> {
+        let row = self.row::<EventRow>(rid)?;
+        let token = TableId::Event.token(rid);
+        let name = self.string(row.name)?.to_string();
+        let accessors = [
+            (METHOD_SEMANTICS_ADD_ON, "add"),
+            (METHOD_SEMANTICS_REMOVE_ON, "remove"),
+            (METHOD_SEMANTICS_FIRE, "raise"),
+        ];
+        self.api_accessor_member(
+            token,
+            ApiMemberKind::Event,
+            name.clone(),
+            name,
+            self.api_type_token_name(row.event_type)?,
+            &accessors,
+            sealed,
+        )
+    }
+
+    /// Builds a property or event from its visible accessors; the most
+    /// visible accessor decides its visibility and modifiers.
+    fn api_accessor_member(
+        &self,
+        token: u32,
+        kind: ApiMemberKind,
+        name: String,
+        signature: String,
+        type_name: String,
+        accessor_names: &[(u16, &str)],
+        sealed: bool,
+    ) -> Result<Option<ApiMember>> {
+        let mut visible = Vec::new();
+        for (semantics, method) in self.api_semantics(token)? {
+            if semantics & METHOD_SEMANTICS_OTHER != 0 {
+                continue;
+            }
+            if let Some(accessor) = self.api_method(method, sealed)?
+                && let Some((_, accessor_name)) = accessor_names
+                    .iter()
+                    .find(|(flag, _)| semantics & flag != 0)
+            {
+                visible.push((*accessor_name, accessor));
+            }
+        }
+        let Some(primary) = visible
+            .iter()
+            .max_by_key(|(_, accessor)| accessor.visibility)
+            .map(|(_, accessor)| accessor.clone())
+        else {
+            return Ok(None);
+        };
+        let mut accessors: Vec<String> = accessor_names
+            .iter()
+            .filter(|(_, accessor_name)| visible.iter().any(|(name, _)| name == accessor_name))
+            .map(|(_, accessor_name)| accessor_name.to_string())
+            .collect();
+        accessors.dedup();
+        Ok(Some(ApiMember {
+            kind,
+            name,
+            signature,
+            type_name,
+            visibility: primary.visibility,
+            is_static: primary.is_static,
+            is_virtual: primary.is_virtual,
+            is_abstract: primary.is_abstract,
+            is_sealed: primary.is_sealed,
+            is_read_only: false,
+            value: None,
+            accessors,
+            generic_params: Vec::new(),
+            attributes: self.api_attributes(token)?,
+        }))
+    }
+
+    /// Returns the generic parameters of a `TypeDef` or `MethodDef`, in
+    /// order.
+    fn api_generic_params(&self, owner: u32) -> Result<Vec<ApiGenericParam>> {
+        let mut rows = Vec::new();
+        for rid in self.find_rows(TableId::GenericParam, GENERIC_PARAM_OWNER, owner)? {
+            rows.push((rid, self.row::<GenericParamRow>(rid)?));
+        }
+        rows.sort_by_key(|(_, row)| row.number);
+        let mut params = Vec::new();
+        for (rid, row) in rows {
+            let mut constraints = Vec::new();
+            if row.flags & GENERIC_PARAM_REFERENCE_TYPE != 0 {
+                constraints.push("class".to_string());
+            }
+            if row.flags & GENERIC_PARAM_NOT_NULLABLE_VALUE_TYPE != 0 {
+                constraints.push("struct".to_string());
+            }
+            if row.flags & GENERIC_PARAM_DEFAULT_CONSTRUCTOR != 0 {
+                constraints.push("new()".to_string());
+            }
+            let mut types = Vec::new();
+            for constraint in self.find_rows(
+                TableId::GenericParamConstraint,
+                GENERIC_PARAM_CONSTRAINT_OWNER,
+                rid,
+            )? {
+                let constraint = self.row::<GenericParamConstraintRow>(constraint)?;
+                types.push(self.api_type_token_name(constraint.constraint)?);
+            }
+            types.sort();
+            constraints.extend(types);
+            params.push(ApiGenericParam {
+                name: self.string(row.name)?.to_string(),
+                variance: match row.flags & GENERIC_PARAM_VARIANCE_MASK {
+                    GENERIC_PARAM_COVARIANT => ApiVariance::Covariant,
+                    GENERIC_PARAM_CONTRAVARIANT => ApiVariance::Contravariant,
+                    _ => ApiVariance::Invariant,
+                },
+                constraints,
+            });
+        }
+        Ok(params)
+    }
+
+    /// Returns the custom attributes of an entity, sorted.
+    fn api_attributes(&self, token: u32) -> Result<Vec<ApiAttribute>> {
+        let mut attributes = Vec::new();
+        for rid in self.custom_attributes(token)? {
+            let type_name = self.custom_attribute_type_name(rid)?;
+            let value = self.custom_attribute_value(rid)?;
+            let mut arguments: Vec<String> = value.fixed_args.iter().map(argument_text).collect();
+            arguments.extend(
+                value
+                    .named_args
+                    .iter()
+                    .map(|arg| format!("{} = {}", arg.name, argument_text(&arg.value))),
+            );
+            attributes.push(ApiAttribute {
+                type_name,
+                arguments,
+            });
+        }
+        attributes.sort_by(|a, b| (&a.type_name, &a.arguments).cmp(&(&b.type_name, &b.arguments)));
+        Ok(attributes)
+    }
+
+    /// Returns `false` for `TypeDef`s that are not visible outside the
+    /// assembly; referenced types are assumed to be visible.
+    fn is_api_visible_type(&self, token: u32) -> Result<bool> {
+        match TableId::from_token(token) {
+            Some((TableId::TypeDef, rid)) => Ok(self.api_type_visibility(rid)?.is_some()),
+            _ => Ok(true),
+        }
+    }
+
+    /// Returns the name of a `TypeDef`, `TypeRef` or `TypeSpec`.
+    fn api_type_token_name(&self, token: u32) -> Result<String> {
+        match TableId::from_token(token) {
+            Some((TableId::TypeSpec, rid)) => {
+                let blob = self.blob(self.row::<TypeSpecRow>(rid)?.signature)?;
+                self.api_type_name(&TypeSig::parse(blob)?)
+            }
+            _ => self.type_full_name(token),
+        }
+    }
+
+    fn api_type_names(&self, types: &[TypeSig]) -> Result<Vec<String>> {
+        types.iter().map(|ty| self.api_type_name(ty)).collect()
+    }
+
+    /// Returns the name of a signature type, in the notation of
+    /// [`TypeInst`](crate::TypeInst).
+    fn api_type_name(&self, ty: &TypeSig) -> Result<String> {
+        let primitive = match ty {
+            TypeSig::Void => "System.Void",
+            TypeSig::Boolean => "System.Boolean",
+            TypeSig::Char => "System.Char",
+            TypeSig::I1 => "System.SByte",
+            TypeSig::U1 => "System.Byte",
+            TypeSig::I2 => "System.Int16",
+            TypeSig::U2 => "System.UInt16",
+            TypeSig::I4 => "System.Int32",
+            TypeSig::U4 => "System.UInt32",
+            TypeSig::I8 => "System.Int64",
+            TypeSig::U8 => "System.UInt64",
+            TypeSig::R4 => "System.Single",
+            TypeSig::R8 => "System.Double",
+            TypeSig::I => "System.IntPtr",
+            TypeSig::U => "System.UIntPtr",
+            TypeSig::String => "System.String",
+            TypeSig::Object => "System.Object",
+            TypeSig::TypedByRef => "System.TypedReference",
+            _ => "",
+        };
+        if !primitive.is_empty() {
+            return Ok(primitive.to_string());
+        }
+        Ok(match ty {
+            TypeSig::Class(token) | TypeSig::ValueType(token) => {
+                self.api_type_token_name(*token)?
+            }
+            TypeSig::GenericInst {
+                definition, args, ..
+            } => format!(
+                "{}<{}>",
+                self.api_type_token_name(*definition)?,
+                self.api_type_names(args)?.join(",")
+            ),
+            TypeSig::Ptr(ty) => format!("{}*", self.api_type_name(ty)?),
+            TypeSig::ByRef(ty) => format!("{}&", self.api_type_name(ty)?),
+            TypeSig::SzArray(ty) => format!("{}[]", self.api_type_name(ty)?),
+            TypeSig::Array(ty, shape) if shape.rank <= 1 => {
+                format!("{}[*]", self.api_type_name(ty)?)
+            }
+            TypeSig::Array(ty, shape) => format!(
+                "{}[{}]",
+                self.api_type_name(ty)?,
+                ",".repeat(shape.rank as usize - 1)
+            ),
+            TypeSig::Var(n) => format!("!{n}"),
+            TypeSig::MVar(n) => format!("!!{n}"),
+            TypeSig::FnPtr(sig) => format!(
+                "method {} *({})",
+                self.api_type_name(&sig.return_type)?,
+                self.api_type_names(&sig.params)?.join(",")
+            ),
+            // `modreq`s are part of the signature the compiler binds to
+            // (`in` parameters, `init` accessors); `modopt`s are not.
+            TypeSig::Modified {
+                required: true,
+                modifier,
+                ty,
+            } => format!(
+                "{} modreq({})",
+                self.api_type_name(ty)?,
+                self.api_type_token_name(*modifier)?
+            ),
+            TypeSig::Modified { ty, .. } | TypeSig::Pinned(ty) => self.api_type_name(ty)?,
+            _ => {
+                return Err(Error::BadSignature(format!(
+                    "unexpected type {ty:?} in a member signature"
+                )));
+            }
+        })
+    }
+}
+
+/// Returns the visibility of a member of a type that is `sealed` or not;
+/// protected members of sealed types are out of reach.
+fn member_visibility(flags: u16, sealed: bool) -> Option<ApiVisibility> {
+    match flags & MEMBER_ACCESS_MASK {
+        MEMBER_ACCESS_PUBLIC => Some(ApiVisibility::Public),
+        MEMBER_ACCESS_FAMILY | MEMBER_ACCESS_FAM_OR_ASSEM if !sealed => {
+            Some(ApiVisibility::Protected)
+        }
+        _ => None,
+    }
+}
+
+/// Formats a custom attribute argument in C# notation.
+fn argument_text(arg: &CustomAttributeArgument) -> String {
+    match arg {
+        CustomAttributeArgument::Boolean(value) => value.to_string(),
+        CustomAttributeArgument::Char(value) => match char::from_u32(u32::from(*value)) {
+            Some(c) => format!("{c:?}"),
+            None => format!("'\\u{value:04x}'"),
+        },
+        CustomAttributeArgument::I1(value) => value.to_string(),
+        CustomAttributeArgument::U1(value) => value.to_string(),
+        CustomAttributeArgument::I2(value) => value.to_string(),
+        CustomAttributeArgument::U2(value) => value.to_string(),
+        CustomAttributeArgument::I4(value) => value.to_string(),
+        CustomAttributeArgument::U4(value) => value.to_string(),
+        CustomAttributeArgument::I8(value) => value.to_string(),
+        CustomAttributeArgument::U8(value) => value.to_string(),
+        CustomAttributeArgument::R4(value) => value.to_string(),
+        CustomAttributeArgument::R8(value) => value.to_string(),
+        CustomAttributeArgument::String(Some(value)) => format!("{value:?}"),
+        CustomAttributeArgument::Type(Some(name)) => format!("typeof({name})"),
+        CustomAttributeArgument::String(None)
+        | CustomAttributeArgument::Type(None)
+        | CustomAttributeArgument::Array(None) => "null".to_string(),
+        CustomAttributeArgument::Enum { type_name, value } => {
+            format!("({type_name}){}", argument_text(value))
+        }
+        CustomAttributeArgument::Array(Some(values)) => format!(
+            "new[] {{ {} }}",
+            values
+                .iter()
+                .map(argument_text)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
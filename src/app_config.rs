@@ -0,0 +1,587 @@
+//! .NET Framework application configuration files.
+//!
+//! Parses the `<startup>` and `<runtime>` sections of `app.config`,
+//! `machine.config` and publisher policy files, and evaluates them the way
+//! fusion and the shim do: [`BindingPolicy`] applies `<bindingRedirect>`
+//! entries to an assembly reference and [`requested_runtime`] picks the
+//! runtime version like `ICLRMetaHostPolicy::GetRequestedRuntime`.
+
+use std::path::Path;
+
+use roxmltree::{Document, Node};
+
+use crate::assembly_identity::{AssemblyIdentity, AssemblyPublicKey, AssemblyVersion};
+use crate::error::{Error, Result};
+
+/// A `<supportedRuntime>` entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SupportedRuntime {
+    /// The runtime version, e.g. `v4.0` or `v2.0.50727`.
+    pub version: String,
+    /// The target framework, e.g. `.NETFramework,Version=v4.7.2`.
+    pub sku: Option<String>,
+}
+
+/// A `<bindingRedirect>` entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BindingRedirect {
+    /// The lowest version redirected.
+    pub old_min: AssemblyVersion,
+    /// The highest version redirected.
+    pub old_max: AssemblyVersion,
+    pub new_version: AssemblyVersion,
+}
+
+impl BindingRedirect {
+    /// Returns `true` if `version` lies in the redirected range.
+    pub fn matches(&self, version: AssemblyVersion) -> bool {
+        (self.old_min.parts()..=self.old_max.parts()).contains(&version.parts())
+    }
+}
+
+/// A `<codeBase>` entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBase {
+    pub version: AssemblyVersion,
+    /// The location of the assembly, a URL or a path.
+    pub href: String,
+}
+
+/// A `<dependentAssembly>` entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependentAssembly {
+    /// The assembly the entry applies to, from `<assemblyIdentity>`.
+    pub identity: AssemblyIdentity,
+    pub binding_redirects: Vec<BindingRedirect>,
+    pub code_bases: Vec<CodeBase>,
+    /// `<publisherPolicy apply="...">` for this assembly.
+    pub publisher_policy: Option<bool>,
+    /// The `appliesTo` runtime version of the enclosing `<assemblyBinding>`.
+    pub applies_to: Option<String>,
+}
+
+impl DependentAssembly {
+    /// Returns `true` if the entry applies to `reference` on the runtime
+    /// `runtime_version`. A missing culture selects the neutral culture.
+    pub fn matches(&self, reference: &AssemblyIdentity, runtime_version: Option<&str>) -> bool {
+        if let (Some(applies_to), Some(runtime)) = (&self.applies_to, runtime_version)
+            && !runtime_matches(applies_to, runtime)
+        {
+            return false;
+        }
+        let culture = |identity: &AssemblyIdentity| identity.culture.clone().unwrap_or_default();
+        self.identity.name.eq_ignore_ascii_case(&reference.name)
+            && self.identity.public_key_token() == reference.public_key_token()
+            && culture(&self.identity).eq_ignore_ascii_case(&culture(reference))
+            && match (
+                self.identity.processor_architecture,
+                reference.processor_architecture,
+            ) {
+                (Some(expected), Some(actual)) => expected == actual,
+                _ => true,
+            }
+    }
+}
+
+/// The parsed `<startup>` and `<runtime>` sections of a configuration file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppConfig {
+    /// The `<supportedRuntime>` entries in order of preference.
+    pub supported_runtimes: Vec<SupportedRuntime>,
+    /// The legacy `<requiredRuntime>` version.
+    pub required_runtime: Option<String>,
+    /// `useLegacyV2RuntimeActivationPolicy` of `<startup>`.
+    pub use_legacy_v2_runtime_activation_policy: bool,
+    /// The `<probing privatePath>` value, a semicolon-separated list.
+    pub private_path: Option<String>,
+    /// The global `<publisherPolicy apply>` setting.
+    pub publisher_policy: bool,
+    pub dependent_assemblies: Vec<DependentAssembly>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            supported_runtimes: Vec::new(),
+            required_runtime: None,
+            use_legacy_v2_runtime_activation_policy: false,
+            private_path: None,
+            publisher_policy: true,
+            dependent_assemblies: Vec::new(),
+        }
+    }
+}
+
+impl AppConfig {
+    /// Reads and parses the configuration file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<AppConfig> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses configuration XML. Sections other than `<startup>` and
+    /// `<runtime>` are ignored.
+    pub fn parse(xml: &str) -> Result<AppConfig> {
+        let document = Document::parse(xml.trim_start_matches('\u{feff}'))
+            .map_err(|e| Error::BadConfig(format!("invalid configuration XML: {e}")))?;
+        let root = document.root_element();
+        if root.tag_name().name() != "configuration" {
+            return Err(Error::BadConfig(
+                "root element is not <configuration>".into(),
+            ));
+        }
+
+        let mut config = AppConfig::default();
+        for section in root.children().filter(Node::is_element) {
+            match section.tag_name().name() {
+                "startup" => parse_startup(section, &mut config)?,
+                "runtime" => {
+                    for binding in children(section, "assemblyBinding") {
+                        parse_assembly_binding(binding, &mut config)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+
+    /// Returns the `<dependentAssembly>` entries that apply to `reference`.
+    pub fn dependent_assemblies_for<'a>(
+        &'a self,
+        reference: &'a AssemblyIdentity,
+        runtime_version: Option<&'a str>,
+    ) -> impl Iterator<Item = &'a DependentAssembly> + 'a {
+        self.dependent_assemblies
+            .iter()
+            .filter(move |entry| entry.matches(reference, runtime_version))
+    }
+
+    /// Returns the version `reference` is redirected to, if any redirect
+    /// covers its version.
+    pub fn redirect(
+        &self,
+        reference: &AssemblyIdentity,
+        runtime_version: Option<&str>,
+    ) -> Option<AssemblyVersion> {
+        let version = reference.version?;
+        self.dependent_assemblies_for(reference, runtime_version)
+            .flat_map(|entry| &entry.binding_redirects)
+            .find(|redirect| redirect.matches(version))
+            .map(|redirect| redirect.new_version)
+    }
+
+    /// Returns `true` if publisher policy applies to `reference`.
+    pub fn applies_publisher_policy(
+        &self,
+        reference: &AssemblyIdentity,
+        runtime_version: Option<&str>,
+    ) -> bool {
+        self.dependent_assemblies_for(reference, runtime_version)
+            .find_map(|entry| entry.publisher_policy)
+            .unwrap_or(self.publisher_policy)
+    }
+
+    /// Returns the `<codeBase>` location for `reference`'s version.
+    pub fn code_base(
+        &self,
+        reference: &AssemblyIdentity,
+        runtime_version: Option<&str>,
+    ) -> Option<&str> {
+        let version = reference.version?;
+        self.dependent_assemblies
+            .iter()
+            .filter(|entry| entry.matches(reference, runtime_version))
+            .flat_map(|entry| &entry.code_bases)
+            .find(|code_base| code_base.version.parts() == version.parts())
+            .map(|code_base| code_base.href.as_str())
+    }
+}
+
+fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn required<'a>(node: Node<'a, '_>, attribute: &str) -> Result<&'a str> {
+    node.attribute(attribute).ok_or_else(|| {
+        Error::BadConfig(format!(
+            "<{}> has no {attribute} attribute",
+            node.tag_name().name()
+        ))
+    })
+}
+
+fn parse_version(node: Node, attribute: &str) -> Result<AssemblyVersion> {
+    required(node, attribute)?.trim().parse().map_err(|_| {
+        Error::BadConfig(format!(
+            "invalid {attribute} {:?}",
+            node.attribute(attribute)
+        ))
+    })
+}
+
+/// Parses `apply="yes|no"`.
+fn parse_apply(node: Node) -> Option<bool> {
+    node.attribute("apply")
+        .map(|apply| !apply.trim().eq_ignore_ascii_case("no"))
+}
+
+fn parse_startup(startup: Node, config: &mut AppConfig) -> Result<()> {
+    config.use_legacy_v2_runtime_activation_policy = startup
+        .attribute("useLegacyV2RuntimeActivationPolicy")
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case("true"));
+    for entry in children(startup, "supportedRuntime") {
+        config.supported_runtimes.push(SupportedRuntime {
+            version: required(entry, "version")?.trim().to_string(),
+            sku: entry.attribute("sku").map(str::to_string),
+        });
+    }
+    if let Some(entry) = children(startup, "requiredRuntime").next() {
+        config.required_runtime = Some(required(entry, "version")?.trim().to_string());
+    }
+    Ok(())
+}
+
+fn parse_assembly_binding(binding: Node, config: &mut AppConfig) -> Result<()> {
+    let applies_to = binding.attribute("appliesTo").map(str::to_string);
+    for element in binding.children().filter(Node::is_element) {
+        match element.tag_name().name() {
+            "probing" => config.private_path = element.attribute("privatePath").map(str::to_string),
+            "publisherPolicy" => config.publisher_policy = parse_apply(element).unwrap_or(true),
+            "dependentAssembly" => config
+                .dependent_assemblies
+                .push(parse_dependent_assembly(element, applies_to.clone())?),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn parse_dependent_assembly(
+    element: Node,
+    applies_to: Option<String>,
+) -> Result<DependentAssembly> {
+    let identity_node = children(element, "assemblyIdentity")
+        .next()
+        .ok_or_else(|| Error::BadConfig("<dependentAssembly> has no <assemblyIdentity>".into()))?;
+    let mut identity = AssemblyIdentity::new(required(identity_node, "name")?.trim());
+    identity.culture = identity_node.attribute("culture").map(|culture| {
+        if culture.eq_ignore_ascii_case("neutral") {
+            String::new()
+        } else {
+            culture.to_string()
+        }
+    });
+    identity.public_key = match identity_node.attribute("publicKeyToken") {
+        Some(token) if !token.is_empty() && !token.eq_ignore_ascii_case("null") => {
+            Some(AssemblyPublicKey::Token(token.trim().parse().map_err(
+                |_| Error::BadConfig(format!("invalid publicKeyToken {token:?}")),
+            )?))
+        }
+        _ => Some(AssemblyPublicKey::None),
+    };
+    identity.processor_architecture = identity_node
+        .attribute("processorArchitecture")
+        .map(str::parse)
+        .transpose()?;
+
+    let mut entry = DependentAssembly {
+        identity,
+        binding_redirects: Vec::new(),
+        code_bases: Vec::new(),
+        publisher_policy: None,
+        applies_to,
+    };
+    for child in element.children().filter(Node::is_element) {
+        match child.tag_name().name() {
+            "bindingRedirect" => {
+                let old = required(child, "oldVersion")?;
+                let parse = |s: &str| {
+                    s.trim()
+                        .parse::<AssemblyVersion>()
+                        .map_err(|_| Error::BadConfig(format!("invalid oldVersion {old:?}")))
+                };
+                let (old_min, old_max) = match old.split_once('-') {
+                    Some((min, max)) => (parse(min)?, parse(max)?),
+                    None => (parse(old)?, parse(old)?),
+                };
+                entry.binding_redirects.push(BindingRedirect {
+                    old_min,
+                    old_max,
+                    new_version: parse_version(child, "newVersion")?,
+                });
+            }
+            "codeBase" => entry.code_bases.push(CodeBase {
+                version: parse_version(child, "version")?,
+                href: required(child, "href")?.to_string(),
+            }),
+            "publisherPolicy" => entry.publisher_policy = parse_apply(child),
+            _ => {}
+        }
+    }
+    Ok(entry)
+}
+
+/// A level of version policy, in the order fusion applies them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PolicyLevel {
+    /// The application configuration file.
+    Application,
+    /// A publisher policy assembly from the GAC.
+    Publisher,
+    /// `machine.config` (administrator policy).
+    Machine,
+}
+
+/// A version change made by one policy level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppliedRedirect {
+    pub level: PolicyLevel,
+    pub from: AssemblyVersion,
+    pub to: AssemblyVersion,
+}
+
+/// The outcome of [`BindingPolicy::apply`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyResult {
+    /// The reference after all redirects.
+    pub identity: AssemblyIdentity,
+    /// The redirects that changed the version, in order.
+    pub redirects: Vec<AppliedRedirect>,
+    /// A `<codeBase>` for the final version from the application or
+    /// machine configuration.
+    pub code_base: Option<String>,
+}
+
+/// Version policy for strong-named assembly references: application
+/// configuration, then publisher policy unless the application disables
+/// it, then machine configuration.
+///
+/// ```
+/// use mscoree::{AppConfig, AssemblyIdentity, BindingPolicy};
+///
+/// let config = AppConfig::parse(r#"
+///     <configuration>
+///       <runtime>
+///         <assemblyBinding xmlns="urn:schemas-microsoft-com:asm.v1">
+///           <dependentAssembly>
+///             <assemblyIdentity name="Newtonsoft.Json" publicKeyToken="30ad4fe6b2a6aeed" culture="neutral" />
+///             <bindingRedirect oldVersion="0.0.0.0-13.0.0.0" newVersion="13.0.0.0" />
+///           </dependentAssembly>
+///         </assemblyBinding>
+///       </runtime>
+///     </configuration>"#).unwrap();
+///
+/// let reference: AssemblyIdentity =
+///     "Newtonsoft.Json, Version=6.0.0.0, Culture=neutral, PublicKeyToken=30ad4fe6b2a6aeed"
+///         .parse()
+///         .unwrap();
+/// let result = BindingPolicy::new().with_app_config(config).apply(&reference);
+/// assert_eq!(result.identity.version.unwrap().to_string(), "13.0.0.0");
+/// assert_eq!(result.redirects.len(), 1);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BindingPolicy {
+    app: Option<AppConfig>,
+    publisher: Vec<AppConfig>,
+    machine: Option<AppConfig>,
+    runtime_version: Option<String>,
+}
+
+impl BindingPolicy {
+    /// Creates a policy without any configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the application configuration file.
+    pub fn with_app_config(mut self, config: AppConfig) -> Self {
+        self.app = Some(config);
+        self
+    }
+
+    /// Adds the configuration of a publisher policy assembly
+    /// (`policy.<major>.<minor>.<name>`).
+    pub fn with_publisher_policy(mut self, config: AppConfig) -> Self {
+        self.publisher.push(config);
+        self
+    }
+
+    /// Sets `machine.config`.
+    pub fn with_machine_config(mut self, config: AppConfig) -> Self {
+        self.machine = Some(config);
+        self
+    }
+
+    /// Sets the runtime version, e.g. `v4.0.30319`, used to filter
+    /// `<assemblyBinding appliesTo>` sections.
+    pub fn with_runtime_version(mut self, version: impl Into<String>) -> Self {
+        self.runtime_version = Some(version.into());
+        self
+    }
+
+    /// Applies the policy to `reference`. References without a strong name
+    /// or version are returned unchanged, as fusion ignores policy for them.
+    pub fn apply(&self, reference: &AssemblyIdentity) -> PolicyResult {
+        let runtime = self.runtime_version.as_deref();
+        let mut result = PolicyResult {
+            identity: reference.clone(),
+            redirects: Vec::new(),
+            code_base: None,
+        };
+        if !reference.is_strong_named() || reference.version.is_none() {
+            return result;
+        }
+
+        let redirect = |level: PolicyLevel, config: &AppConfig, result: &mut PolicyResult| {
+            if let Some(to) = config.redirect(&result.identity, runtime) {
+                let from = result.identity.version.unwrap_or_default();
+                if from.parts() != to.parts() {
+                    result.redirects.push(AppliedRedirect { level, from, to });
+                }
+                result.identity.version = Some(to);
+            }
+        };
+
+        if let Some(app) = &self.app {
+            redirect(PolicyLevel::Application, app, &mut result);
+        }
+        let publisher_policy = self
+            .app
+            .as_ref()
+            .is_none_or(|app| app.applies_publisher_policy(&result.identity, runtime));
+        if publisher_policy {
+            for policy in &self.publisher {
+                redirect(PolicyLevel::Publisher, policy, &mut result);
+            }
+        }
+        if let Some(machine) = &self.machine {
+            redirect(PolicyLevel::Machine, machine, &mut result);
+        }
+
+        result.code_base = [&self.app, &self.machine]
+            .into_iter()
+            .flatten()
+            .find_map(|config| config.code_base(&result.identity, runtime))
+            .map(str::to_string);
+        result
+    }
+}
+
+/// Where the runtime chosen by [`requested_runtime`] came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RuntimeSource {
+    /// A `<supportedRuntime>` or `<requiredRuntime>` entry.
+    ConfigFile,
+    /// The runtime version in the image's metadata header.
+    Image,
+    /// The latest installed runtime, chosen because the requested one is
+    /// missing and upgrade policy applies (`METAHOST_POLICY_APPLY_UPGRADE_POLICY`).
+    UpgradePolicy,
+}
+
+/// The runtime selected by [`requested_runtime`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestedRuntime {
+    /// The installed runtime version, e.g. `v4.0.30319`.
+    pub version: String,
+    pub source: RuntimeSource,
+    /// The `sku` of the chosen `<supportedRuntime>` entry.
+    pub sku: Option<String>,
+    /// `useLegacyV2RuntimeActivationPolicy` of the configuration.
+    pub use_legacy_v2_runtime_activation_policy: bool,
+}
+
+/// Picks the runtime the shim would load, like
+/// `ICLRMetaHostPolicy::GetRequestedRuntime`.
+///
+/// The first `<supportedRuntime>` entry that is installed wins; if none is,
+/// `<requiredRuntime>` and then `image_version` (the metadata version string
+/// of the executable) are tried. If nothing matches and
+/// `apply_upgrade_policy` is set, the latest installed runtime is used.
+///
+/// ```
+/// use mscoree::{AppConfig, RuntimeSource, requested_runtime};
+///
+/// let config = AppConfig::parse(r#"
+///     <configuration>
+///       <startup useLegacyV2RuntimeActivationPolicy="true">
+///         <supportedRuntime version="v4.0" sku=".NETFramework,Version=v4.8" />
+///         <supportedRuntime version="v2.0.50727" />
+///       </startup>
+///     </configuration>"#).unwrap();
+/// let installed = ["v2.0.50727", "v4.0.30319"];
+///
+/// let runtime = requested_runtime(Some(&config), Some("v2.0.50727"), &installed, false).unwrap();
+/// assert_eq!(runtime.version, "v4.0.30319");
+/// assert_eq!(runtime.source, RuntimeSource::ConfigFile);
+/// assert!(runtime.use_legacy_v2_runtime_activation_policy);
+///
+/// let runtime = requested_runtime(None, Some("v1.1.4322"), &installed, true).unwrap();
+/// assert_eq!((runtime.version.as_str(), runtime.source), ("v4.0.30319", RuntimeSource::UpgradePolicy));
+/// ```
+pub fn requested_runtime(
+    config: Option<&AppConfig>,
+    image_version: Option<&str>,
+    installed: &[&str],
+    apply_upgrade_policy: bool,
+) -> Option<RequestedRuntime> {
+    let legacy = config.is_some_and(|config| config.use_legacy_v2_runtime_activation_policy);
+    let select = |requested: &str| {
+        installed
+            .iter()
+            .copied()
+            .filter(|version| runtime_matches(requested, version))
+            .max_by_key(|version| runtime_version_parts(version))
+    };
+    let found = |version: &str, source, sku: Option<&String>| RequestedRuntime {
+        version: version.to_string(),
+        source,
+        sku: sku.cloned(),
+        use_legacy_v2_runtime_activation_policy: legacy,
+    };
+
+    if let Some(config) = config {
+        let supported = config.supported_runtimes.iter().find_map(|entry| {
+            select(&entry.version)
+                .map(|version| found(version, RuntimeSource::ConfigFile, entry.sku.as_ref()))
+        });
+        if supported.is_some() {
+            return supported;
+        }
+        if let Some(version) = config.required_runtime.as_deref().and_then(select) {
+            return Some(found(version, RuntimeSource::ConfigFile, None));
+        }
+    }
+    if let Some(version) = image_version.and_then(select) {
+        return Some(found(version, RuntimeSource::Image, None));
+    }
+    if apply_upgrade_policy {
+        let latest = installed
+            .iter()
+            .copied()
+            .max_by_key(|version| runtime_version_parts(version))?;
+        return Some(found(latest, RuntimeSource::UpgradePolicy, None));
+    }
+    None
+}
+
+/// Returns `true` if the runtime version `installed` satisfies `requested`,
+/// where a partial version such as `v4.0` matches `v4.0.30319`.
+fn runtime_matches(requested: &str, installed: &str) -> bool {
+    let requested = runtime_version_parts(requested);
+    let installed = runtime_version_parts(installed);
+    !requested.is_empty() && installed.starts_with(&requested)
+}
+
+fn runtime_version_parts(version: &str) -> Vec<u32> {
+    let version = version.trim();
+    let version = version.strip_prefix(['v', 'V']).unwrap_or(version);
+    version
+        .split('.')
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
@@ -0,0 +1,697 @@
+//! Assembly identities and display names.
+//!
+//! A pure-Rust counterpart of `IAssemblyName`: parsing and formatting of
+//! display names such as
+//! `mscorlib, Version=4.0.0.0, Culture=neutral, PublicKeyToken=b77a5c561934e089`
+//! and the comparison semantics of `IAssemblyName::IsEqual`.
+
+use std::fmt;
+use std::ops::BitOr;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::reader::{AssemblyRefRow, AssemblyRow, MetadataReader, TableId};
+use crate::strong_name::{AF_PUBLIC_KEY, PublicKeyToken, decode_hex};
+
+/// `AssemblyFlags.Retargetable`.
+pub const AF_RETARGETABLE: u32 = 0x0100;
+/// `AssemblyFlags.PA_Mask`: processor architecture bits.
+pub const AF_PA_MASK: u32 = 0x0070;
+/// `AssemblyFlags.PA_Specified`: the processor architecture is set.
+pub const AF_PA_SPECIFIED: u32 = 0x0080;
+/// `AssemblyFlags.ContentType_Mask`.
+pub const AF_CONTENT_TYPE_MASK: u32 = 0x0E00;
+/// `AssemblyFlags.ContentType_WindowsRuntime`.
+pub const AF_CONTENT_TYPE_WINDOWS_RUNTIME: u32 = 0x0200;
+
+/// A four-part assembly version. Build and revision may be left unspecified
+/// in partial display names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct AssemblyVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub build: Option<u16>,
+    pub revision: Option<u16>,
+}
+
+impl AssemblyVersion {
+    /// Creates a fully specified version.
+    pub fn new(major: u16, minor: u16, build: u16, revision: u16) -> Self {
+        AssemblyVersion {
+            major,
+            minor,
+            build: Some(build),
+            revision: Some(revision),
+        }
+    }
+
+    /// Returns the four components, with unspecified ones as 0.
+    pub fn parts(&self) -> [u16; 4] {
+        [
+            self.major,
+            self.minor,
+            self.build.unwrap_or(0),
+            self.revision.unwrap_or(0),
+        ]
+    }
+}
+
+impl fmt::Display for AssemblyVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)?;
+        if let Some(build) = self.build {
+            write!(f, ".{build}")?;
+            if let Some(revision) = self.revision {
+                write!(f, ".{revision}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for AssemblyVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<AssemblyVersion> {
+        let invalid = || Error::BadAssemblyName(format!("invalid version {s:?}"));
+        let parts = s
+            .split('.')
+            .map(|part| part.trim().parse::<u16>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>>>()?;
+        if !(2..=4).contains(&parts.len()) {
+            return Err(invalid());
+        }
+        Ok(AssemblyVersion {
+            major: parts[0],
+            minor: parts[1],
+            build: parts.get(2).copied(),
+            revision: parts.get(3).copied(),
+        })
+    }
+}
+
+/// Processor architecture of an assembly (`PEKIND`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProcessorArchitecture {
+    None,
+    Msil,
+    X86,
+    Ia64,
+    Amd64,
+    Arm,
+    Arm64,
+}
+
+impl ProcessorArchitecture {
+    /// Returns the display-name spelling, e.g. `MSIL` or `AMD64`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ProcessorArchitecture::None => "None",
+            ProcessorArchitecture::Msil => "MSIL",
+            ProcessorArchitecture::X86 => "x86",
+            ProcessorArchitecture::Ia64 => "IA64",
+            ProcessorArchitecture::Amd64 => "AMD64",
+            ProcessorArchitecture::Arm => "ARM",
+            ProcessorArchitecture::Arm64 => "ARM64",
+        }
+    }
+
+    /// Decodes the `PA_*` bits of `AssemblyFlags`, if `PA_Specified` is set.
+    pub fn from_assembly_flags(flags: u32) -> Option<ProcessorArchitecture> {
+        if flags & AF_PA_SPECIFIED == 0 {
+            return None;
+        }
+        Some(match (flags & AF_PA_MASK) >> 4 {
+            1 => ProcessorArchitecture::Msil,
+            2 => ProcessorArchitecture::X86,
+            3 => ProcessorArchitecture::Ia64,
+            4 => ProcessorArchitecture::Amd64,
+            5 => ProcessorArchitecture::Arm,
+            6 => ProcessorArchitecture::Arm64,
+            _ => ProcessorArchitecture::None,
+        })
+    }
+}
+
+impl FromStr for ProcessorArchitecture {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ProcessorArchitecture> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "none" => ProcessorArchitecture::None,
+            "msil" => ProcessorArchitecture::Msil,
+            "x86" => ProcessorArchitecture::X86,
+            "ia64" => ProcessorArchitecture::Ia64,
+            "amd64" => ProcessorArchitecture::Amd64,
+            "arm" => ProcessorArchitecture::Arm,
+            "arm64" => ProcessorArchitecture::Arm64,
+            _ => {
+                return Err(Error::BadAssemblyName(format!(
+                    "unknown processor architecture {s:?}"
+                )));
+            }
+        })
+    }
+}
+
+/// Content type of an assembly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AssemblyContentType {
+    Default,
+    WindowsRuntime,
+}
+
+/// The strong-name part of an identity.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AssemblyPublicKey {
+    /// Explicitly not strong-named (`PublicKeyToken=null`).
+    None,
+    /// A full public key in `PublicKeyBlob` format.
+    PublicKey(Vec<u8>),
+    /// A public key token.
+    Token(PublicKeyToken),
+}
+
+impl AssemblyPublicKey {
+    /// Returns the public key token, computing it from a full key.
+    pub fn token(&self) -> Option<PublicKeyToken> {
+        match self {
+            AssemblyPublicKey::None => None,
+            AssemblyPublicKey::PublicKey(key) => {
+                Some(PublicKeyToken::from_public_key_unchecked(key))
+            }
+            AssemblyPublicKey::Token(token) => Some(*token),
+        }
+    }
+}
+
+/// Comparison flags of `IAssemblyName::IsEqual` (`ASM_CMP_FLAGS`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AssemblyCompareFlags(pub u32);
+
+impl AssemblyCompareFlags {
+    pub const NAME: AssemblyCompareFlags = AssemblyCompareFlags(0x1);
+    pub const MAJOR_VERSION: AssemblyCompareFlags = AssemblyCompareFlags(0x2);
+    pub const MINOR_VERSION: AssemblyCompareFlags = AssemblyCompareFlags(0x4);
+    pub const BUILD_NUMBER: AssemblyCompareFlags = AssemblyCompareFlags(0x8);
+    pub const REVISION_NUMBER: AssemblyCompareFlags = AssemblyCompareFlags(0x10);
+    pub const VERSION: AssemblyCompareFlags = AssemblyCompareFlags(0x1E);
+    pub const PUBLIC_KEY_TOKEN: AssemblyCompareFlags = AssemblyCompareFlags(0x20);
+    pub const CULTURE: AssemblyCompareFlags = AssemblyCompareFlags(0x40);
+    pub const CUSTOM: AssemblyCompareFlags = AssemblyCompareFlags(0x80);
+    /// Compare every property the first name specifies, treating it as a
+    /// (possibly partial) reference.
+    pub const DEFAULT: AssemblyCompareFlags = AssemblyCompareFlags(0x100);
+    pub const RETARGET: AssemblyCompareFlags = AssemblyCompareFlags(0x200);
+    pub const ARCHITECTURE: AssemblyCompareFlags = AssemblyCompareFlags(0x400);
+    pub const CONFIG_MASK: AssemblyCompareFlags = AssemblyCompareFlags(0x800);
+    pub const MVID: AssemblyCompareFlags = AssemblyCompareFlags(0x1000);
+    pub const SIGNATURE: AssemblyCompareFlags = AssemblyCompareFlags(0x2000);
+    /// Content type; an extension not defined by `ASM_CMP_FLAGS`.
+    pub const CONTENT_TYPE: AssemblyCompareFlags = AssemblyCompareFlags(0x4000);
+    /// Name, version, public key token and culture.
+    pub const IL_ALL: AssemblyCompareFlags = AssemblyCompareFlags(0x7F);
+    /// Name, public key token and culture.
+    pub const IL_NO_VERSION: AssemblyCompareFlags = AssemblyCompareFlags(0x61);
+
+    /// Returns `true` if all bits of `other` are set.
+    pub fn contains(self, other: AssemblyCompareFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for AssemblyCompareFlags {
+    type Output = AssemblyCompareFlags;
+
+    fn bitor(self, rhs: AssemblyCompareFlags) -> AssemblyCompareFlags {
+        AssemblyCompareFlags(self.0 | rhs.0)
+    }
+}
+
+/// An assembly identity: the properties that make up a display name.
+///
+/// Unset properties (`None`) are unspecified, as in a partial name.
+///
+/// ```
+/// use mscoree::{AssemblyCompareFlags, AssemblyIdentity};
+///
+/// let name: AssemblyIdentity =
+///     "mscorlib, Version=4.0.0.0, Culture=neutral, PublicKeyToken=b77a5c561934e089"
+///         .parse()
+///         .unwrap();
+/// assert_eq!(name.name, "mscorlib");
+/// assert_eq!(
+///     name.to_string(),
+///     "mscorlib, Version=4.0.0.0, Culture=neutral, PublicKeyToken=b77a5c561934e089"
+/// );
+///
+/// let partial: AssemblyIdentity = "MSCORLIB, Culture=neutral".parse().unwrap();
+/// assert!(partial.is_equal(&name, AssemblyCompareFlags::DEFAULT));
+/// assert!(!partial.is_equal(&name, AssemblyCompareFlags::IL_ALL));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct AssemblyIdentity {
+    pub name: String,
+    pub version: Option<AssemblyVersion>,
+    /// The culture; `Some("")` is the neutral culture.
+    pub culture: Option<String>,
+    pub public_key: Option<AssemblyPublicKey>,
+    pub processor_architecture: Option<ProcessorArchitecture>,
+    pub retargetable: Option<bool>,
+    pub content_type: Option<AssemblyContentType>,
+}
+
+impl AssemblyIdentity {
+    /// Creates an identity with only a simple name.
+    pub fn new(name: impl Into<String>) -> Self {
+        AssemblyIdentity {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Builds a fully specified identity from the values returned by
+    /// `IMetaDataAssemblyImport::GetAssemblyProps` or `GetAssemblyRefProps`.
+    ///
+    /// `public_key_or_token` is a full key if `flags` has `AF_PUBLIC_KEY`,
+    /// otherwise a token (or empty).
+    pub fn from_props(
+        name: &str,
+        version: [u16; 4],
+        culture: &str,
+        public_key_or_token: &[u8],
+        flags: u32,
+    ) -> Result<Self> {
+        let public_key =
+            if public_key_or_token.is_empty() {
+                AssemblyPublicKey::None
+            } else if flags & AF_PUBLIC_KEY != 0 {
+                AssemblyPublicKey::PublicKey(public_key_or_token.to_vec())
+            } else {
+                AssemblyPublicKey::Token(PublicKeyToken(public_key_or_token.try_into().map_err(
+                    |_| Error::BadAssemblyName("public key token is not 8 bytes".into()),
+                )?))
+            };
+        let content_type = if flags & AF_CONTENT_TYPE_MASK == AF_CONTENT_TYPE_WINDOWS_RUNTIME {
+            AssemblyContentType::WindowsRuntime
+        } else {
+            AssemblyContentType::Default
+        };
+        Ok(AssemblyIdentity {
+            name: name.to_string(),
+            version: Some(AssemblyVersion::new(
+                version[0], version[1], version[2], version[3],
+            )),
+            culture: Some(normalize_culture(culture)),
+            public_key: Some(public_key),
+            processor_architecture: ProcessorArchitecture::from_assembly_flags(flags),
+            retargetable: Some(flags & AF_RETARGETABLE != 0),
+            content_type: Some(content_type),
+        })
+    }
+
+    /// Returns the public key token, computing it from a full key.
+    pub fn public_key_token(&self) -> Option<PublicKeyToken> {
+        self.public_key.as_ref()?.token()
+    }
+
+    /// Returns `true` if the identity carries a public key or token.
+    pub fn is_strong_named(&self) -> bool {
+        self.public_key_token().is_some()
+    }
+
+    /// Compares two identities with `IAssemblyName::IsEqual` semantics.
+    ///
+    /// With [`AssemblyCompareFlags::DEFAULT`] `self` is treated as a partial
+    /// reference: only the properties it specifies are compared. With
+    /// explicit flags the selected properties must match exactly, where an
+    /// unspecified property only matches another unspecified one. Names and
+    /// cultures compare case-insensitively.
+    pub fn is_equal(&self, other: &AssemblyIdentity, flags: AssemblyCompareFlags) -> bool {
+        let flags = if flags.contains(AssemblyCompareFlags::DEFAULT) {
+            self.specified_flags()
+        } else {
+            flags
+        };
+        let has = |flag| flags.contains(flag);
+
+        if has(AssemblyCompareFlags::NAME) && !self.name.eq_ignore_ascii_case(&other.name) {
+            return false;
+        }
+        let versions = (self.version, other.version);
+        let component = |get: fn(&AssemblyVersion) -> Option<u16>| match versions {
+            (Some(a), Some(b)) => get(&a) == get(&b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        if (has(AssemblyCompareFlags::MAJOR_VERSION) && !component(|v| Some(v.major)))
+            || (has(AssemblyCompareFlags::MINOR_VERSION) && !component(|v| Some(v.minor)))
+            || (has(AssemblyCompareFlags::BUILD_NUMBER) && !component(|v| v.build))
+            || (has(AssemblyCompareFlags::REVISION_NUMBER) && !component(|v| v.revision))
+        {
+            return false;
+        }
+        if has(AssemblyCompareFlags::PUBLIC_KEY_TOKEN) {
+            let a = self.public_key.as_ref().map(AssemblyPublicKey::token);
+            let b = other.public_key.as_ref().map(AssemblyPublicKey::token);
+            if a != b {
+                return false;
+            }
+        }
+        if has(AssemblyCompareFlags::CULTURE) {
+            let equal = match (&self.culture, &other.culture) {
+                (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+                (a, b) => a.is_none() && b.is_none(),
+            };
+            if !equal {
+                return false;
+            }
+        }
+        if has(AssemblyCompareFlags::RETARGET)
+            && self.retargetable.unwrap_or(false) != other.retargetable.unwrap_or(false)
+        {
+            return false;
+        }
+        if has(AssemblyCompareFlags::ARCHITECTURE)
+            && self.processor_architecture != other.processor_architecture
+        {
+            return false;
+        }
+        if has(AssemblyCompareFlags::CONTENT_TYPE)
+            && self.content_type.unwrap_or(AssemblyContentType::Default)
+                != other.content_type.unwrap_or(AssemblyContentType::Default)
+        {
+            return false;
+        }
+        true
+    }
+
+    /// Returns the comparison flags for the properties this name specifies.
+    fn specified_flags(&self) -> AssemblyCompareFlags {
+        let mut flags = AssemblyCompareFlags::NAME;
+        if let Some(version) = self.version {
+            flags =
+                flags | AssemblyCompareFlags::MAJOR_VERSION | AssemblyCompareFlags::MINOR_VERSION;
+            if version.build.is_some() {
+                flags = flags | AssemblyCompareFlags::BUILD_NUMBER;
+            }
+            if version.revision.is_some() {
+                flags = flags | AssemblyCompareFlags::REVISION_NUMBER;
+            }
+        }
+        if self.public_key.is_some() {
+            flags = flags | AssemblyCompareFlags::PUBLIC_KEY_TOKEN;
+        }
+        if self.culture.is_some() {
+            flags = flags | AssemblyCompareFlags::CULTURE;
+        }
+        if self.retargetable.is_some() {
+            flags = flags | AssemblyCompareFlags::RETARGET;
+        }
+        if self.processor_architecture.is_some() {
+            flags = flags | AssemblyCompareFlags::ARCHITECTURE;
+        }
+        if self.content_type.is_some() {
+            flags = flags | AssemblyCompareFlags::CONTENT_TYPE;
+        }
+        flags
+    }
+}
+
+impl FromStr for AssemblyIdentity {
+    type Err = Error;
+
+    /// Parses a display name. Property names are case-insensitive, values
+    /// may be quoted and `\` escapes the next character.
+    fn from_str(s: &str) -> Result<AssemblyIdentity> {
+        let mut parts = split_display_name(s)?.into_iter();
+        let (name, value) = parts.next().unwrap_or_default();
+        if value.is_some() || name.is_empty() {
+            return Err(Error::BadAssemblyName(format!(
+                "display name {s:?} does not start with a simple name"
+            )));
+        }
+        let mut identity = AssemblyIdentity::new(name);
+        let mut seen = Vec::new();
+        for (key, value) in parts {
+            let value = value
+                .ok_or_else(|| Error::BadAssemblyName(format!("property {key:?} has no value")))?;
+            let lower = key.to_ascii_lowercase();
+            if seen.contains(&lower) {
+                return Err(Error::BadAssemblyName(format!(
+                    "property {key:?} is specified twice"
+                )));
+            }
+            match lower.as_str() {
+                "version" => identity.version = Some(value.parse()?),
+                "culture" => identity.culture = Some(normalize_culture(&value)),
+                "publickeytoken" => {
+                    if identity.public_key.is_some() {
+                        return Err(Error::BadAssemblyName(
+                            "both PublicKey and PublicKeyToken are specified".into(),
+                        ));
+                    }
+                    identity.public_key = Some(if value.eq_ignore_ascii_case("null") {
+                        AssemblyPublicKey::None
+                    } else {
+                        AssemblyPublicKey::Token(value.parse().map_err(|_| {
+                            Error::BadAssemblyName(format!("invalid public key token {value:?}"))
+                        })?)
+                    });
+                }
+                "publickey" => {
+                    if identity.public_key.is_some() {
+                        return Err(Error::BadAssemblyName(
+                            "both PublicKey and PublicKeyToken are specified".into(),
+                        ));
+                    }
+                    identity.public_key = Some(if value.eq_ignore_ascii_case("null") {
+                        AssemblyPublicKey::None
+                    } else {
+                        AssemblyPublicKey::PublicKey(decode_hex(&value).ok_or_else(|| {
+                            Error::BadAssemblyName(format!("invalid public key {value:?}"))
+                        })?)
+                    });
+                }
+                "processorarchitecture" => identity.processor_architecture = Some(value.parse()?),
+                "retargetable" => {
+                    identity.retargetable = Some(match value.to_ascii_lowercase().as_str() {
+                        "yes" => true,
+                        "no" => false,
+                        _ => {
+                            return Err(Error::BadAssemblyName(format!(
+                                "invalid Retargetable value {value:?}"
+                            )));
+                        }
+                    })
+                }
+                "contenttype" => {
+                    identity.content_type = Some(if value.eq_ignore_ascii_case("WindowsRuntime") {
+                        AssemblyContentType::WindowsRuntime
+                    } else if value.eq_ignore_ascii_case("Default") {
+                        AssemblyContentType::Default
+                    } else {
+                        return Err(Error::BadAssemblyName(format!(
+                            "invalid ContentType value {value:?}"
+                        )));
+                    })
+                }
+                // Unknown properties are ignored, as the runtime does.
+                _ => {}
+            }
+            seen.push(lower);
+        }
+        Ok(identity)
+    }
+}
+
+impl fmt::Display for AssemblyIdentity {
+    /// Formats the display name. Properties are written in the runtime's
+    /// order; defaulted `Retargetable=No` and `ContentType=Default` are
+    /// omitted. Full public keys are shown as their token unless the
+    /// alternate form (`{:#}`) is requested.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&escape(&self.name))?;
+        if let Some(version) = &self.version {
+            write!(f, ", Version={version}")?;
+        }
+        if let Some(culture) = &self.culture {
+            let culture = if culture.is_empty() {
+                "neutral"
+            } else {
+                culture
+            };
+            write!(f, ", Culture={}", escape(culture))?;
+        }
+        match &self.public_key {
+            Some(AssemblyPublicKey::None) => f.write_str(", PublicKeyToken=null")?,
+            Some(AssemblyPublicKey::Token(token)) => write!(f, ", PublicKeyToken={token}")?,
+            Some(AssemblyPublicKey::PublicKey(key)) if !f.alternate() => write!(
+                f,
+                ", PublicKeyToken={}",
+                PublicKeyToken::from_public_key_unchecked(key)
+            )?,
+            Some(AssemblyPublicKey::PublicKey(key)) => {
+                f.write_str(", PublicKey=")?;
+                for byte in key {
+                    write!(f, "{byte:02x}")?;
+                }
+            }
+            None => {}
+        }
+        if let Some(architecture) = self.processor_architecture {
+            write!(f, ", ProcessorArchitecture={}", architecture.name())?;
+        }
+        if self.retargetable == Some(true) {
+            f.write_str(", Retargetable=Yes")?;
+        }
+        if self.content_type == Some(AssemblyContentType::WindowsRuntime) {
+            f.write_str(", ContentType=WindowsRuntime")?;
+        }
+        Ok(())
+    }
+}
+
+impl MetadataReader {
+    /// Returns the identity of the assembly manifest, if the metadata has one.
+    pub fn assembly_identity(&self) -> Result<Option<AssemblyIdentity>> {
+        if self.row_count(TableId::Assembly) == 0 {
+            return Ok(None);
+        }
+        let row = self.row::<AssemblyRow>(1)?;
+        let public_key = self.blob(row.public_key)?;
+        let flags = if public_key.is_empty() {
+            row.flags
+        } else {
+            row.flags | AF_PUBLIC_KEY
+        };
+        AssemblyIdentity::from_props(
+            self.string(row.name)?,
+            [
+                row.major_version,
+                row.minor_version,
+                row.build_number,
+                row.revision_number,
+            ],
+            self.string(row.culture)?,
+            public_key,
+            flags,
+        )
+        .map(Some)
+    }
+
+    /// Returns the identity referenced by `AssemblyRef` row `rid`.
+    pub fn assembly_ref_identity(&self, rid: u32) -> Result<AssemblyIdentity> {
+        let row = self.row::<AssemblyRefRow>(rid)?;
+        AssemblyIdentity::from_props(
+            self.string(row.name)?,
+            [
+                row.major_version,
+                row.minor_version,
+                row.build_number,
+                row.revision_number,
+            ],
+            self.string(row.culture)?,
+            self.blob(row.public_key_or_token)?,
+            row.flags,
+        )
+    }
+}
+
+fn normalize_culture(culture: &str) -> String {
+    if culture.eq_ignore_ascii_case("neutral") {
+        String::new()
+    } else {
+        culture.to_string()
+    }
+}
+
+/// Splits a display name into `key[=value]` parts, unescaping and unquoting.
+fn split_display_name(s: &str) -> Result<Vec<(String, Option<String>)>> {
+    let mut parts = Vec::new();
+    let mut chars = s.chars().peekable();
+    loop {
+        let key = read_token(&mut chars, s)?;
+        let value = if chars.peek() == Some(&'=') {
+            chars.next();
+            Some(read_token(&mut chars, s)?)
+        } else {
+            None
+        };
+        parts.push((key, value));
+        match chars.next() {
+            Some(',') => {}
+            None => break,
+            Some(c) => {
+                return Err(Error::BadAssemblyName(format!(
+                    "unexpected {c:?} in display name {s:?}"
+                )));
+            }
+        }
+    }
+    Ok(parts)
+}
+
+/// Reads a possibly quoted token up to an unescaped `,` or `=`.
+fn read_token(chars: &mut std::iter::Peekable<std::str::Chars<'_>>, s: &str) -> Result<String> {
+    let unterminated = || Error::BadAssemblyName(format!("unterminated escape or quote in {s:?}"));
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+    let quote = chars.next_if(|&c| c == '"' || c == '\'');
+    let mut token = String::new();
+    loop {
+        match chars.peek().copied() {
+            None if quote.is_some() => return Err(unterminated()),
+            None => break,
+            Some(c) if Some(c) == quote => {
+                chars.next();
+                break;
+            }
+            Some(',' | '=') if quote.is_none() => break,
+            Some('\\') => {
+                chars.next();
+                token.push(match chars.next().ok_or_else(unterminated)? {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    c => c,
+                });
+            }
+            Some(c) => {
+                chars.next();
+                token.push(c);
+            }
+        }
+    }
+    if quote.is_some() {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        Ok(token)
+    } else {
+        Ok(token.trim_end().to_string())
+    }
+}
+
+/// Escapes a display-name value; values with surrounding whitespace are
+/// quoted so it is preserved.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ',' | '=' | '"' | '\'' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    if value.starts_with(char::is_whitespace) || value.ends_with(char::is_whitespace) {
+        format!("\"{escaped}\"")
+    } else {
+        escaped
+    }
+}
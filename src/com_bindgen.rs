@@ -0,0 +1,819 @@
+//! Generation of Rust COM bindings from the COM interfaces an assembly
+//! declares.
+//!
+//! Interfaces marked `[ComImport]` (or exported with `[InterfaceType]`) and
+//! carrying a `[Guid]` are turned into `#[interface("…")]` trait
+//! definitions in the style of this crate's own bindings. Managed parameter
+//! types are mapped to their unmanaged representation following the default
+//! COM interop marshalling rules and any `[MarshalAs]` descriptors.
+//!
+//! Every trait derives from `IUnknown`. The `IDispatch` and `IInspectable`
+//! slots of dual and Windows Runtime interfaces are declared in the trait
+//! itself, the way `IMetaDataDispenserEx` repeats the methods of
+//! `IMetaDataDispenser`, since `#[interface]` cannot derive from them.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use crate::error::{Error, Result};
+use crate::reader::{
+    AssemblyRow, FieldMarshalRow, METHOD_ATTRIBUTE_STATIC, METHOD_IMPL_PRESERVE_SIG,
+    MetadataReader, MethodDefRow, MethodSig, ModuleRow, NativeType, PARAM_ATTRIBUTE_IN,
+    PARAM_ATTRIBUTE_OUT, ParamRow, TYPE_ATTRIBUTE_IMPORT, TYPE_ATTRIBUTE_INTERFACE, TableId,
+    TypeDefRow, TypeSig, token_rid,
+};
+use crate::type_system::{ModuleId, Workspace};
+
+const GUID_ATTRIBUTE: &str = "System.Runtime.InteropServices.GuidAttribute";
+const INTERFACE_TYPE_ATTRIBUTE: &str = "System.Runtime.InteropServices.InterfaceTypeAttribute";
+
+/// The name `tlbimp` gives the `[out, retval]` parameter of a method whose
+/// `HRESULT` is hidden.
+const RETVAL_PARAM_NAME: &str = "pRetVal";
+
+/// Lines longer than this are wrapped one parameter per line, as rustfmt
+/// does.
+const MAX_WIDTH: usize = 100;
+
+/// `System.Runtime.InteropServices.ComInterfaceType`: the interface a COM
+/// interface derives from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ComInterfaceType {
+    /// Derives from `IDispatch` and exposes its members in the vtable too.
+    #[default]
+    InterfaceIsDual,
+    /// Derives from `IUnknown`.
+    InterfaceIsIUnknown,
+    /// A dispinterface: members are only reachable through
+    /// `IDispatch::Invoke`.
+    InterfaceIsIDispatch,
+    /// Derives from `IInspectable` (Windows Runtime).
+    InterfaceIsIInspectable,
+}
+
+impl ComInterfaceType {
+    /// Returns the type with the given `ComInterfaceType` value.
+    pub fn from_i32(value: i32) -> Option<ComInterfaceType> {
+        Some(match value {
+            0 => ComInterfaceType::InterfaceIsDual,
+            1 => ComInterfaceType::InterfaceIsIUnknown,
+            2 => ComInterfaceType::InterfaceIsIDispatch,
+            3 => ComInterfaceType::InterfaceIsIInspectable,
+            _ => return None,
+        })
+    }
+
+    /// Returns the name of the interface the COM interface derives from.
+    pub fn base_interface(self) -> &'static str {
+        match self {
+            ComInterfaceType::InterfaceIsIUnknown => "IUnknown",
+            ComInterfaceType::InterfaceIsDual | ComInterfaceType::InterfaceIsIDispatch => {
+                "IDispatch"
+            }
+            ComInterfaceType::InterfaceIsIInspectable => "IInspectable",
+        }
+    }
+
+    /// Returns the slots the base interface adds to those of `IUnknown`,
+    /// as trait items.
+    fn inherited_methods(self) -> &'static [&'static str] {
+        match self {
+            ComInterfaceType::InterfaceIsIUnknown => &[],
+            ComInterfaceType::InterfaceIsDual | ComInterfaceType::InterfaceIsIDispatch => &[
+                "pub unsafe fn GetTypeInfoCount(&self, pctinfo: *mut u32) -> HRESULT;",
+                "pub unsafe fn GetTypeInfo(&self, iTInfo: u32, lcid: u32, ppTInfo: *mut *mut c_void) -> HRESULT;",
+                "pub unsafe fn GetIDsOfNames(\n        &self,\n        riid: *const GUID,\n        rgszNames: *const *const u16,\n        cNames: u32,\n        lcid: u32,\n        rgDispId: *mut i32,\n    ) -> HRESULT;",
+                "pub unsafe fn Invoke(\n        &self,\n        dispIdMember: i32,\n        riid: *const GUID,\n        lcid: u32,\n        wFlags: u16,\n        pDispParams: *mut c_void,\n        pVarResult: *mut c_void,\n        pExcepInfo: *mut c_void,\n        puArgErr: *mut u32,\n    ) -> HRESULT;",
+            ],
+            ComInterfaceType::InterfaceIsIInspectable => &[
+                "pub unsafe fn GetIids(&self, iidCount: *mut u32, iids: *mut *mut GUID) -> HRESULT;",
+                "pub unsafe fn GetRuntimeClassName(&self, className: *mut *mut c_void) -> HRESULT;",
+                "pub unsafe fn GetTrustLevel(&self, trustLevel: *mut i32) -> HRESULT;",
+            ],
+        }
+    }
+}
+
+/// A parameter of a generated COM method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComParam {
+    /// The Rust identifier of the parameter.
+    pub name: String,
+    /// The Rust type of the parameter.
+    pub ty: String,
+}
+
+/// A vtable method of a generated COM interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComMethod {
+    /// The `MethodDef` token.
+    pub token: u32,
+    /// The Rust name of the method; overloads get a `_2`, `_3`… suffix as
+    /// in type libraries exported by `tlbexp`.
+    pub name: String,
+    pub params: Vec<ComParam>,
+    /// The Rust return type, or `None` for `void`. Methods without
+    /// `[PreserveSig]` return `HRESULT` and pass their managed return value
+    /// through a trailing `pRetVal` pointer.
+    pub return_type: Option<String>,
+}
+
+/// A COM interface declared by an assembly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComInterface {
+    /// The `TypeDef` token.
+    pub token: u32,
+    /// The Rust name of the generated trait.
+    pub name: String,
+    /// The full managed name (`Ns.Outer+IFoo`).
+    pub full_name: String,
+    /// The interface id in registry format without braces, upper case.
+    pub iid: String,
+    pub interface_type: ComInterfaceType,
+    /// The vtable methods after those of the base interface, in vtable
+    /// order. Empty for dispinterfaces.
+    pub methods: Vec<ComMethod>,
+}
+
+/// The COM interfaces of an assembly, ready to be written as a Rust source
+/// file with `Display`.
+///
+/// ```no_run
+/// use mscoree::PeImage;
+///
+/// let image = PeImage::open("Interop.Contoso.dll")?;
+/// let bindings = image.metadata()?.com_bindings()?;
+/// std::fs::write("contoso.rs", bindings.to_string())?;
+/// for warning in &bindings.warnings {
+///     eprintln!("warning: {warning}");
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ComBindings {
+    /// The name of the assembly (or module) the interfaces come from.
+    pub source: String,
+    pub interfaces: Vec<ComInterface>,
+    /// The items the generated code imports, as full paths.
+    pub imports: BTreeSet<String>,
+    /// Value types (structures) used by the interfaces that the including
+    /// module must define, by Rust name.
+    pub external_types: BTreeSet<String>,
+    /// Members whose types could not be mapped and were emitted as
+    /// `*mut c_void`.
+    pub warnings: Vec<String>,
+}
+
+impl MetadataReader {
+    /// Collects the COM interfaces of the module and maps their methods to
+    /// Rust signatures.
+    ///
+    /// An interface qualifies if it carries a `[Guid]` and is either
+    /// `[ComImport]` or has an explicit `[InterfaceType]`. Generic
+    /// interfaces cannot be used from COM and are skipped with a warning.
+    ///
+    /// Enums from other assemblies cannot be told apart from structs here;
+    /// use [`Workspace::com_bindings`] to map them to their underlying type.
+    pub fn com_bindings(&self) -> Result<ComBindings> {
+        self.com_bindings_in(None)
+    }
+
+    fn com_bindings_in(&self, workspace: Option<(&Workspace, ModuleId)>) -> Result<ComBindings> {
+        let mut candidates = Vec::new();
+        for rid in 1..=self.row_count(TableId::TypeDef) {
+            let row = self.row::<TypeDefRow>(rid)?;
+            if row.flags & TYPE_ATTRIBUTE_INTERFACE == 0 {
+                continue;
+            }
+            let token = TableId::TypeDef.token(rid);
+            let Some(iid) = self.com_guid(token)? else {
+                continue;
+            };
+            let interface_type = self.com_interface_type(token)?;
+            if row.flags & TYPE_ATTRIBUTE_IMPORT == 0 && interface_type.is_none() {
+                continue;
+            }
+            candidates.push((token, iid, interface_type.unwrap_or_default()));
+        }
+
+        let mut generator = Generator {
+            reader: self,
+            workspace,
+            names: HashMap::new(),
+            bindings: ComBindings {
+                source: self.com_source_name()?,
+                ..ComBindings::default()
+            },
+        };
+        generator.assign_names(&candidates)?;
+        for (token, iid, interface_type) in candidates {
+            if let Some(interface) = generator.interface(token, iid, interface_type)? {
+                generator.bindings.interfaces.push(interface);
+            }
+        }
+        Ok(generator.bindings)
+    }
+
+    fn com_guid(&self, token: u32) -> Result<Option<String>> {
+        let Some(&rid) = self.find_custom_attributes(token, GUID_ATTRIBUTE)?.first() else {
+            return Ok(None);
+        };
+        let value = self.custom_attribute_value(rid)?;
+        let text = value
+            .fixed_args
+            .first()
+            .and_then(|arg| arg.as_str())
+            .unwrap_or_default();
+        let iid = text
+            .trim_start_matches('{')
+            .trim_end_matches('}')
+            .to_ascii_uppercase();
+        let groups: Vec<&str> = iid.split('-').collect();
+        let valid = groups.iter().map(|g| g.len()).eq([8, 4, 4, 4, 12])
+            && groups
+                .iter()
+                .all(|g| g.bytes().all(|b| b.is_ascii_hexdigit()));
+        if !valid {
+            return Err(Error::BadMetadata(format!(
+                "{} has an invalid GuidAttribute {text:?}",
+                self.type_full_name(token)?
+            )));
+        }
+        Ok(Some(iid))
+    }
+
+    fn com_interface_type(&self, token: u32) -> Result<Option<ComInterfaceType>> {
+        use crate::reader::CustomAttributeArgument as Arg;
+
+        let Some(&rid) = self
+            .find_custom_attributes(token, INTERFACE_TYPE_ATTRIBUTE)?
+            .first()
+        else {
+            return Ok(None);
+        };
+        let value = self.custom_attribute_value(rid)?;
+        let raw = match value.fixed_args.first() {
+            Some(Arg::Enum { value, .. }) => match **value {
+                Arg::I4(v) => Some(v),
+                Arg::I2(v) => Some(v.into()),
+                _ => None,
+            },
+            // The legacy constructor takes the value as a short.
+            Some(Arg::I2(v)) => Some((*v).into()),
+            _ => None,
+        };
+        raw.and_then(ComInterfaceType::from_i32)
+            .map(Some)
+            .ok_or_else(|| {
+                Error::BadMetadata(format!(
+                    "{} has an invalid InterfaceTypeAttribute",
+                    self.type_full_name(token).unwrap_or_default()
+                ))
+            })
+    }
+
+    fn com_source_name(&self) -> Result<String> {
+        if self.row_count(TableId::Assembly) != 0 {
+            let row = self.row::<AssemblyRow>(1)?;
+            return Ok(self.string(row.name)?.to_string());
+        }
+        if self.row_count(TableId::Module) != 0 {
+            let row = self.row::<ModuleRow>(1)?;
+            return Ok(self.string(row.name)?.to_string());
+        }
+        Ok(String::new())
+    }
+}
+
+/// How data flows through the pointers a type maps to.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    /// A parameter marked `[In]` only: its pointers are `*const`.
+    In,
+    InOut,
+    /// The target of a `ref` or `out` parameter, which the callee may
+    /// replace.
+    Referent,
+}
+
+struct Generator<'a> {
+    reader: &'a MetadataReader,
+    /// The workspace the module is loaded in, to resolve referenced types.
+    workspace: Option<(&'a Workspace, ModuleId)>,
+    /// Trait names of the interfaces being generated, by `TypeDef` token.
+    names: HashMap<u32, String>,
+    bindings: ComBindings,
+}
+
+impl Generator<'_> {
+    /// Names traits after the simple interface names, falling back to the
+    /// full name where two interfaces share one.
+    fn assign_names(&mut self, candidates: &[(u32, String, ComInterfaceType)]) -> Result<()> {
+        let mut simple = HashMap::new();
+        for &(token, ..) in candidates {
+            let (_, name) = self.reader.type_namespace_and_name(token)?;
+            *simple.entry(identifier(name)).or_insert(0) += 1;
+        }
+        for &(token, ..) in candidates {
+            let (_, name) = self.reader.type_namespace_and_name(token)?;
+            let name = identifier(name);
+            let name = if simple[&name] > 1 {
+                identifier(&self.reader.type_full_name(token)?)
+            } else {
+                name
+            };
+            self.names.insert(token, name);
+        }
+        Ok(())
+    }
+
+    fn interface(
+        &mut self,
+        token: u32,
+        iid: String,
+        interface_type: ComInterfaceType,
+    ) -> Result<Option<ComInterface>> {
+        let full_name = self.reader.type_full_name(token)?;
+        if !self
+            .reader
+            .find_rows(TableId::GenericParam, 2, token)?
+            .is_empty()
+        {
+            self.bindings.warnings.push(format!(
+                "{full_name}: generic interfaces cannot be used from COM"
+            ));
+            return Ok(None);
+        }
+        self.import("windows::core::IUnknown");
+        self.import("windows::core::interface");
+        if interface_type != ComInterfaceType::InterfaceIsIUnknown {
+            self.import("windows::core::GUID");
+            self.import("windows::core::HRESULT");
+            self.import("std::ffi::c_void");
+        }
+
+        let mut methods = Vec::new();
+        if interface_type != ComInterfaceType::InterfaceIsIDispatch {
+            let mut overloads: HashMap<String, u32> = HashMap::new();
+            for method in self.reader.type_def_methods(token_rid(token))? {
+                let row = self.reader.row::<MethodDefRow>(method)?;
+                if row.flags & METHOD_ATTRIBUTE_STATIC != 0 {
+                    continue;
+                }
+                let managed = self.reader.string(row.name)?;
+                let count = overloads.entry(managed.to_string()).or_insert(0);
+                *count += 1;
+                let name = match *count {
+                    1 => identifier(managed),
+                    n => format!("{}_{n}", identifier(managed)),
+                };
+                let context = format!("{full_name}.{managed}");
+                methods.push(self.method(method, &row, name, &context)?);
+            }
+        }
+
+        Ok(Some(ComInterface {
+            token,
+            name: self.names[&token].clone(),
+            full_name,
+            iid,
+            interface_type,
+            methods,
+        }))
+    }
+
+    fn method(
+        &mut self,
+        rid: u32,
+        row: &MethodDefRow,
+        name: String,
+        context: &str,
+    ) -> Result<ComMethod> {
+        let sig = MethodSig::parse(self.reader.blob(row.signature)?)?;
+        let mut names = vec![None; sig.params.len() + 1];
+        let mut flags = vec![0u16; sig.params.len() + 1];
+        let mut marshal = vec![None; sig.params.len() + 1];
+        for param in self.reader.method_params(rid)? {
+            let param_row = self.reader.row::<ParamRow>(param)?;
+            let seq = usize::from(param_row.sequence);
+            if seq >= names.len() {
+                continue;
+            }
+            let param_name = self.reader.string(param_row.name)?;
+            names[seq] = (!param_name.is_empty()).then(|| param_name.to_string());
+            flags[seq] = param_row.flags;
+            marshal[seq] = self.marshal(TableId::Param.token(param))?;
+        }
+
+        let mut params = Vec::with_capacity(sig.params.len() + 1);
+        for (i, ty) in sig.params.iter().enumerate() {
+            let name = match &names[i + 1] {
+                Some(name) => identifier(name),
+                None => format!("param{}", i + 1),
+            };
+            let direction = if flags[i + 1] & (PARAM_ATTRIBUTE_IN | PARAM_ATTRIBUTE_OUT)
+                == PARAM_ATTRIBUTE_IN
+            {
+                Direction::In
+            } else {
+                Direction::InOut
+            };
+            let what = format!("{context}: parameter {name}");
+            let ty = self.map(ty, marshal[i + 1].as_ref(), direction, &what)?;
+            params.push(ComParam { name, ty });
+        }
+
+        let returns_void = matches!(sig.return_type.strip_modifiers(), TypeSig::Void);
+        let what = format!("{context}: return value");
+        let return_type = if row.impl_flags & METHOD_IMPL_PRESERVE_SIG != 0 {
+            if returns_void {
+                None
+            } else {
+                Some(self.map(
+                    &sig.return_type,
+                    marshal[0].as_ref(),
+                    Direction::InOut,
+                    &what,
+                )?)
+            }
+        } else {
+            if !returns_void {
+                let ty = self.map(
+                    &sig.return_type,
+                    marshal[0].as_ref(),
+                    Direction::Referent,
+                    &what,
+                )?;
+                params.push(ComParam {
+                    name: RETVAL_PARAM_NAME.to_string(),
+                    ty: format!("*mut {ty}"),
+                });
+            }
+            self.import("windows::core::HRESULT");
+            Some("HRESULT".to_string())
+        };
+        Ok(ComMethod {
+            token: TableId::MethodDef.token(rid),
+            name,
+            params,
+            return_type,
+        })
+    }
+
+    fn marshal(&self, token: u32) -> Result<Option<NativeType>> {
+        match self.reader.find_row(TableId::FieldMarshal, 0, token)? {
+            Some(rid) => {
+                let row = self.reader.row::<FieldMarshalRow>(rid)?;
+                NativeType::parse(self.reader.blob(row.native_type)?).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Maps a managed type to its unmanaged Rust type. `marshal` applies to
+    /// the type itself or, for a `ref` parameter, to the referenced type.
+    fn map(
+        &mut self,
+        sig: &TypeSig,
+        marshal: Option<&NativeType>,
+        direction: Direction,
+        what: &str,
+    ) -> Result<String> {
+        let ptr = match direction {
+            Direction::In => "*const",
+            Direction::InOut | Direction::Referent => "*mut",
+        };
+        // Strings are immutable, so only a string the callee hands back
+        // is writable.
+        let string_ptr = match direction {
+            Direction::Referent => "*mut",
+            Direction::In | Direction::InOut => "*const",
+        };
+        let ty = match sig.strip_modifiers() {
+            TypeSig::Void => self.c_void(),
+            TypeSig::Boolean => match marshal {
+                Some(NativeType::Bool) => self.use_type("windows::core::BOOL"),
+                Some(NativeType::U1) => "u8".into(),
+                Some(NativeType::I1) => "i8".into(),
+                _ => self.use_type("windows::Win32::Foundation::VARIANT_BOOL"),
+            },
+            TypeSig::Char => match marshal {
+                Some(NativeType::U1 | NativeType::I1) => "u8".into(),
+                _ => "u16".into(),
+            },
+            TypeSig::I1 => "i8".into(),
+            TypeSig::U1 => "u8".into(),
+            TypeSig::I2 => "i16".into(),
+            TypeSig::U2 => "u16".into(),
+            TypeSig::I4 => "i32".into(),
+            TypeSig::U4 => "u32".into(),
+            TypeSig::I8 => "i64".into(),
+            TypeSig::U8 => "u64".into(),
+            TypeSig::R4 => "f32".into(),
+            TypeSig::R8 => "f64".into(),
+            TypeSig::I => "isize".into(),
+            TypeSig::U => "usize".into(),
+            TypeSig::String => match marshal {
+                Some(NativeType::LPWStr | NativeType::LPTStr) => format!("{string_ptr} u16"),
+                Some(NativeType::LPStr | NativeType::LPUTF8Str) => format!("{string_ptr} u8"),
+                Some(NativeType::HString) => self.use_type("windows::core::HSTRING"),
+                _ => self.use_type("windows::core::BSTR"),
+            },
+            TypeSig::Object => match marshal {
+                Some(NativeType::IUnknown { .. } | NativeType::Interface { .. }) => {
+                    format!("*mut {}", self.use_type("windows::core::IUnknown"))
+                }
+                Some(NativeType::IDispatch { .. }) => format!(
+                    "*mut {}",
+                    self.use_type("windows::Win32::System::Com::IDispatch")
+                ),
+                Some(NativeType::IInspectable) => {
+                    format!("*mut {}", self.use_type("windows::core::IInspectable"))
+                }
+                Some(NativeType::AsAny) => format!("*mut {}", self.c_void()),
+                _ => self.use_type("windows::Win32::System::Variant::VARIANT"),
+            },
+            TypeSig::Class(token) => self.map_class(*token, marshal)?,
+            TypeSig::ValueType(token) => self.map_value_type(*token)?,
+            TypeSig::Ptr(inner) => {
+                format!("*mut {}", self.map(inner, None, Direction::InOut, what)?)
+            }
+            TypeSig::ByRef(inner) => {
+                format!(
+                    "{ptr} {}",
+                    self.map(inner, marshal, Direction::Referent, what)?
+                )
+            }
+            TypeSig::SzArray(element) | TypeSig::Array(element, _) => match marshal {
+                Some(
+                    NativeType::LPArray { array_sub_type, .. }
+                    | NativeType::ByValArray { array_sub_type, .. },
+                ) => {
+                    let element =
+                        self.map(element, array_sub_type.as_deref(), Direction::InOut, what)?;
+                    format!("{ptr} {element}")
+                }
+                _ => format!(
+                    "*mut {}",
+                    self.use_type("windows::Win32::System::Com::SAFEARRAY")
+                ),
+            },
+            TypeSig::FnPtr(_) => format!("*const {}", self.c_void()),
+            other => {
+                self.bindings
+                    .warnings
+                    .push(format!("{what} has unsupported type {other:?}"));
+                format!("*mut {}", self.c_void())
+            }
+        };
+        Ok(ty)
+    }
+
+    fn map_class(&mut self, token: u32, marshal: Option<&NativeType>) -> Result<String> {
+        if let Some(name) = self.names.get(&token) {
+            return Ok(format!("*mut {name}"));
+        }
+        let full_name = self.reader.type_full_name(token).unwrap_or_default();
+        Ok(match (full_name.as_str(), marshal) {
+            ("System.Text.StringBuilder", Some(NativeType::LPStr | NativeType::LPUTF8Str)) => {
+                "*mut u8".into()
+            }
+            ("System.Text.StringBuilder", _) => "*mut u16".into(),
+            ("System.Array", _) => format!(
+                "*mut {}",
+                self.use_type("windows::Win32::System::Com::SAFEARRAY")
+            ),
+            (_, Some(NativeType::FunctionPtr)) => format!("*const {}", self.c_void()),
+            (_, Some(NativeType::IDispatch { .. })) => format!(
+                "*mut {}",
+                self.use_type("windows::Win32::System::Com::IDispatch")
+            ),
+            (_, Some(NativeType::IInspectable)) => {
+                format!("*mut {}", self.use_type("windows::core::IInspectable"))
+            }
+            // Other classes are passed as their class interface or, for
+            // interfaces not generated here, as a plain interface pointer.
+            _ => format!("*mut {}", self.use_type("windows::core::IUnknown")),
+        })
+    }
+
+    fn map_value_type(&mut self, token: u32) -> Result<String> {
+        let full_name = self.reader.type_full_name(token)?;
+        Ok(match full_name.as_str() {
+            "System.Guid" => self.use_type("windows::core::GUID"),
+            "System.Decimal" => self.use_type("windows::Win32::Foundation::DECIMAL"),
+            // DateTime is marshalled as an OLE Automation DATE.
+            "System.DateTime" => "f64".into(),
+            "System.IntPtr" => "isize".into(),
+            "System.UIntPtr" => "usize".into(),
+            _ => {
+                if let Some(element_type) = self.enum_underlying_type(token)? {
+                    return Ok(primitive_name(element_type).into());
+                }
+                let (_, name) = self.reader.type_namespace_and_name(token)?;
+                let name = identifier(name);
+                self.bindings.external_types.insert(name.clone());
+                name
+            }
+        })
+    }
+
+    /// Returns the underlying type of an enum defined in this module or, with
+    /// a workspace, in a referenced assembly.
+    fn enum_underlying_type(&self, token: u32) -> Result<Option<u8>> {
+        let (reader, token) = match (TableId::from_token(token), self.workspace) {
+            (Some((TableId::TypeDef, _)), _) => (self.reader, token),
+            (Some((TableId::TypeRef, _)), Some((workspace, module))) => {
+                match workspace.resolve_type(module, token)? {
+                    Some(ty) => (&workspace.module(ty.module).metadata, ty.token),
+                    None => return Ok(None),
+                }
+            }
+            _ => return Ok(None),
+        };
+        let extends = reader.row::<TypeDefRow>(token_rid(token))?.extends;
+        if extends & 0x00FF_FFFF == 0 || reader.type_full_name(extends)? != "System.Enum" {
+            return Ok(None);
+        }
+        reader.enum_underlying_type(token).map(Some)
+    }
+
+    fn c_void(&mut self) -> String {
+        self.use_type("std::ffi::c_void")
+    }
+
+    fn use_type(&mut self, path: &str) -> String {
+        self.import(path);
+        path.rsplit("::").next().unwrap_or(path).to_string()
+    }
+
+    fn import(&mut self, path: &str) {
+        self.bindings.imports.insert(path.to_string());
+    }
+}
+
+fn primitive_name(element_type: u8) -> &'static str {
+    match TypeSig::from_primitive_element_type(element_type) {
+        Some(TypeSig::Boolean) => "u8",
+        Some(TypeSig::Char) => "u16",
+        Some(TypeSig::I1) => "i8",
+        Some(TypeSig::U1) => "u8",
+        Some(TypeSig::I2) => "i16",
+        Some(TypeSig::U2) => "u16",
+        Some(TypeSig::U4) => "u32",
+        Some(TypeSig::I8) => "i64",
+        Some(TypeSig::U8) => "u64",
+        Some(TypeSig::I) => "isize",
+        Some(TypeSig::U) => "usize",
+        _ => "i32",
+    }
+}
+
+/// Turns a managed name into a Rust identifier.
+fn identifier(name: &str) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    match ident.as_str() {
+        "self" | "Self" | "super" | "crate" => ident.push('_'),
+        "as" | "async" | "await" | "box" | "break" | "const" | "continue" | "dyn" | "else"
+        | "enum" | "extern" | "false" | "fn" | "for" | "gen" | "if" | "impl" | "in" | "let"
+        | "loop" | "match" | "mod" | "move" | "mut" | "pub" | "ref" | "return" | "static"
+        | "struct" | "trait" | "true" | "type" | "unsafe" | "use" | "where" | "while" => {
+            ident.insert_str(0, "r#")
+        }
+        _ => {}
+    }
+    ident
+}
+
+impl Workspace {
+    /// Collects the COM interfaces of `module` like
+    /// [`MetadataReader::com_bindings`], resolving enums defined in the
+    /// other loaded assemblies to their underlying type.
+    pub fn com_bindings(&self, module: ModuleId) -> Result<ComBindings> {
+        self.module(module)
+            .metadata
+            .com_bindings_in(Some((self, module)))
+    }
+}
+
+impl fmt::Display for ComMethod {
+    /// Formats the method as a trait item, wrapped like rustfmt would.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ret = match &self.return_type {
+            Some(ty) => format!(" -> {ty}"),
+            None => String::new(),
+        };
+        let params: String = self
+            .params
+            .iter()
+            .map(|p| format!(", {}: {}", p.name, p.ty))
+            .collect();
+        let line = format!("    pub unsafe fn {}(&self{params}){ret};", self.name);
+        if line.len() <= MAX_WIDTH {
+            return writeln!(f, "{line}");
+        }
+        writeln!(f, "    pub unsafe fn {}(", self.name)?;
+        writeln!(f, "        &self,")?;
+        for param in &self.params {
+            writeln!(f, "        {}: {},", param.name, param.ty)?;
+        }
+        writeln!(f, "    ){ret};")
+    }
+}
+
+impl fmt::Display for ComInterface {
+    /// Formats the interface as an `#[interface]` trait definition.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "/// {} - `{}`.", self.name, self.full_name)?;
+        if self.interface_type == ComInterfaceType::InterfaceIsIDispatch {
+            writeln!(f, "///")?;
+            writeln!(
+                f,
+                "/// A dispinterface: its members are called through `IDispatch::Invoke`."
+            )?;
+        }
+        writeln!(f, "#[interface(\"{}\")]", self.iid)?;
+        let inherited = self.interface_type.inherited_methods();
+        if inherited.is_empty() && self.methods.is_empty() {
+            return writeln!(f, "pub unsafe trait {}: IUnknown {{}}", self.name);
+        }
+        writeln!(f, "pub unsafe trait {}: IUnknown {{", self.name)?;
+        if !inherited.is_empty() {
+            writeln!(f, "    // {} methods", self.interface_type.base_interface())?;
+            for method in inherited {
+                writeln!(f, "    {method}")?;
+            }
+            if !self.methods.is_empty() {
+                writeln!(f)?;
+                writeln!(f, "    // {} methods", self.name)?;
+            }
+        }
+        for method in &self.methods {
+            write!(f, "{method}")?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for ComBindings {
+    /// Writes a Rust source file in the layout of `src/interfaces/*.rs`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "//! COM interfaces of the `{}` assembly.", self.source)?;
+        writeln!(f, "//!")?;
+        writeln!(f, "//! Generated from the assembly metadata.")?;
+
+        // Group the imports by module; `#[interface]` needs the vtable of
+        // `IUnknown` in scope.
+        let mut modules: Vec<(&str, BTreeSet<String>)> = Vec::new();
+        for path in &self.imports {
+            let (module, item) = path.rsplit_once("::").unwrap_or(("", path));
+            let items = match modules.iter_mut().find(|(m, _)| *m == module) {
+                Some((_, items)) => items,
+                None => {
+                    modules.push((module, BTreeSet::new()));
+                    &mut modules.last_mut().unwrap().1
+                }
+            };
+            if path == "windows::core::IUnknown" && !self.interfaces.is_empty() {
+                items.insert("IUnknown_Vtbl".to_string());
+            }
+            items.insert(item.to_string());
+        }
+        if !modules.is_empty() {
+            writeln!(f)?;
+        }
+        for (module, items) in &modules {
+            let items: Vec<&str> = items.iter().map(String::as_str).collect();
+            match items.as_slice() {
+                [item] => writeln!(f, "use {module}::{item};")?,
+                _ => writeln!(f, "use {module}::{{{}}};", items.join(", "))?,
+            }
+        }
+        if !self.external_types.is_empty() {
+            writeln!(f)?;
+            let names: Vec<&str> = self.external_types.iter().map(String::as_str).collect();
+            writeln!(
+                f,
+                "// Structures to define alongside these interfaces: {}.",
+                names.join(", ")
+            )?;
+        }
+        for interface in &self.interfaces {
+            writeln!(f)?;
+            write!(f, "{interface}")?;
+        }
+        Ok(())
+    }
+}
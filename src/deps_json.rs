@@ -0,0 +1,310 @@
+//! `*.deps.json` dependency manifests of .NET Core applications.
+
+use std::path::Path;
+
+use serde_json::{Map, Value};
+
+use crate::error::{Error, Result};
+
+/// A parsed `*.deps.json` file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DepsFile {
+    /// The target the application runs on, e.g. `.NETCoreApp,Version=v8.0`
+    /// or `.NETCoreApp,Version=v8.0/linux-x64` for self-contained apps.
+    pub runtime_target: String,
+    /// The targets, each listing the libraries resolved for it.
+    pub targets: Vec<DepsTarget>,
+    /// Library metadata shared by all targets.
+    pub libraries: Vec<DepsLibrary>,
+    /// The RID fallback graph of self-contained apps, e.g.
+    /// `linux-x64 -> [linux, unix-x64, unix, any, base]`.
+    pub runtimes: Vec<(String, Vec<String>)>,
+}
+
+/// A target section, e.g. `.NETCoreApp,Version=v8.0`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DepsTarget {
+    pub name: String,
+    pub libraries: Vec<DepsTargetLibrary>,
+}
+
+/// A library as resolved for a target, with its assets.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DepsTargetLibrary {
+    pub name: String,
+    pub version: String,
+    /// Dependencies as `(name, version)` pairs.
+    pub dependencies: Vec<(String, String)>,
+    /// Managed assemblies loaded on every platform.
+    pub runtime: Vec<DepsAsset>,
+    /// Native libraries loaded on every platform.
+    pub native: Vec<DepsAsset>,
+    /// Satellite resource assemblies.
+    pub resources: Vec<DepsAsset>,
+    /// Platform-specific managed and native assets.
+    pub runtime_targets: Vec<DepsAsset>,
+}
+
+/// Kind of a library asset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DepsAssetType {
+    Runtime,
+    Native,
+    Resource,
+}
+
+/// A file belonging to a library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepsAsset {
+    /// Path relative to the library root, with `/` separators.
+    pub path: String,
+    pub asset_type: DepsAssetType,
+    /// The runtime identifier the asset is specific to, from `runtimeTargets`.
+    pub rid: Option<String>,
+    /// The culture of a resource assembly.
+    pub locale: Option<String>,
+    pub assembly_version: Option<String>,
+    pub file_version: Option<String>,
+}
+
+impl DepsAsset {
+    /// Returns the file name of the asset.
+    pub fn file_name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+}
+
+/// Library metadata from the `libraries` section.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DepsLibrary {
+    pub name: String,
+    pub version: String,
+    /// `project`, `package` or `reference`.
+    pub kind: String,
+    pub serviceable: bool,
+    pub sha512: Option<String>,
+    /// Location of a package below a probing directory, e.g.
+    /// `newtonsoft.json/13.0.3`.
+    pub path: Option<String>,
+    pub hash_path: Option<String>,
+}
+
+impl DepsTargetLibrary {
+    /// Returns the assets of `asset_type` used on a platform with the RID
+    /// fallback chain `rids`, as the host selects them: the assets of the
+    /// first RID in the chain that has any in `runtimeTargets`, otherwise
+    /// the RID-agnostic ones.
+    pub fn assets_for_rid(&self, asset_type: DepsAssetType, rids: &[String]) -> Vec<&DepsAsset> {
+        let specific = rids.iter().find_map(|rid| {
+            let assets: Vec<_> = self
+                .runtime_targets
+                .iter()
+                .filter(|asset| asset.asset_type == asset_type && asset.rid.as_ref() == Some(rid))
+                .collect();
+            (!assets.is_empty()).then_some(assets)
+        });
+        specific.unwrap_or_else(|| match asset_type {
+            DepsAssetType::Runtime => self.runtime.iter().collect(),
+            DepsAssetType::Native => self.native.iter().collect(),
+            DepsAssetType::Resource => self.resources.iter().collect(),
+        })
+    }
+}
+
+impl DepsLibrary {
+    /// Returns `true` for NuGet packages, whose assets live below a probing
+    /// directory unless the app was published.
+    pub fn is_package(&self) -> bool {
+        self.kind.eq_ignore_ascii_case("package")
+    }
+}
+
+impl DepsFile {
+    /// Reads and parses the deps file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<DepsFile> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses deps file JSON.
+    ///
+    /// ```
+    /// use mscoree::DepsFile;
+    ///
+    /// let deps = DepsFile::parse(r#"{
+    ///     "runtimeTarget": { "name": ".NETCoreApp,Version=v8.0" },
+    ///     "targets": { ".NETCoreApp,Version=v8.0": {
+    ///         "Newtonsoft.Json/13.0.3": {
+    ///             "runtime": { "lib/net6.0/Newtonsoft.Json.dll": { "assemblyVersion": "13.0.0.0" } }
+    ///         }
+    ///     } },
+    ///     "libraries": { "Newtonsoft.Json/13.0.3": {
+    ///         "type": "package", "serviceable": true, "path": "newtonsoft.json/13.0.3"
+    ///     } }
+    /// }"#).unwrap();
+    /// let library = &deps.runtime_target().unwrap().libraries[0];
+    /// assert_eq!(library.runtime[0].file_name(), "Newtonsoft.Json.dll");
+    /// assert!(deps.library("Newtonsoft.Json", "13.0.3").unwrap().is_package());
+    /// ```
+    pub fn parse(json: &str) -> Result<DepsFile> {
+        let root: Value = serde_json::from_str(json)
+            .map_err(|e| Error::BadConfig(format!("invalid deps.json: {e}")))?;
+        let root = object(&root, "deps.json")?;
+
+        let runtime_target = match root.get("runtimeTarget") {
+            Some(Value::String(name)) => name.clone(),
+            Some(Value::Object(target)) => string(target, "name").unwrap_or_default(),
+            _ => String::new(),
+        };
+
+        let mut targets = Vec::new();
+        if let Some(section) = root.get("targets") {
+            for (name, libraries) in object(section, "targets")? {
+                let mut target = DepsTarget {
+                    name: name.clone(),
+                    libraries: Vec::new(),
+                };
+                for (id, library) in object(libraries, name)? {
+                    target.libraries.push(parse_target_library(id, library)?);
+                }
+                targets.push(target);
+            }
+        }
+
+        let mut libraries = Vec::new();
+        if let Some(section) = root.get("libraries") {
+            for (id, library) in object(section, "libraries")? {
+                let (name, version) = split_library_id(id)?;
+                let library = object(library, id)?;
+                libraries.push(DepsLibrary {
+                    name,
+                    version,
+                    kind: string(library, "type").unwrap_or_default(),
+                    serviceable: library
+                        .get("serviceable")
+                        .and_then(Value::as_bool)
+                        .unwrap_or(false),
+                    sha512: string(library, "sha512").filter(|s| !s.is_empty()),
+                    path: string(library, "path"),
+                    hash_path: string(library, "hashPath"),
+                });
+            }
+        }
+
+        let mut runtimes = Vec::new();
+        if let Some(section) = root.get("runtimes") {
+            for (rid, fallbacks) in object(section, "runtimes")? {
+                let fallbacks = fallbacks
+                    .as_array()
+                    .map(|list| {
+                        list.iter()
+                            .filter_map(Value::as_str)
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                runtimes.push((rid.clone(), fallbacks));
+            }
+        }
+
+        Ok(DepsFile {
+            runtime_target,
+            targets,
+            libraries,
+            runtimes,
+        })
+    }
+
+    /// Returns the target named by `runtimeTarget`, or the first target.
+    pub fn runtime_target(&self) -> Option<&DepsTarget> {
+        self.targets
+            .iter()
+            .find(|target| target.name == self.runtime_target)
+            .or_else(|| self.targets.first())
+    }
+
+    /// Returns `rid` followed by its fallbacks from the `runtimes` section,
+    /// e.g. `linux-x64, linux, unix-x64, unix, any, base`.
+    pub fn rid_fallbacks(&self, rid: &str) -> Vec<String> {
+        let mut rids = vec![rid.to_string()];
+        if let Some((_, fallbacks)) = self.runtimes.iter().find(|(name, _)| name == rid) {
+            rids.extend(fallbacks.iter().cloned());
+        } else if rid != "any" {
+            rids.push("any".to_string());
+        }
+        rids
+    }
+
+    /// Returns the `libraries` entry for a library.
+    pub fn library(&self, name: &str, version: &str) -> Option<&DepsLibrary> {
+        self.libraries
+            .iter()
+            .find(|library| library.name.eq_ignore_ascii_case(name) && library.version == version)
+    }
+}
+
+fn parse_target_library(id: &str, value: &Value) -> Result<DepsTargetLibrary> {
+    let (name, version) = split_library_id(id)?;
+    let entry = object(value, id)?;
+    let mut library = DepsTargetLibrary {
+        name,
+        version,
+        ..Default::default()
+    };
+    if let Some(Value::Object(dependencies)) = entry.get("dependencies") {
+        library.dependencies = dependencies
+            .iter()
+            .map(|(name, version)| (name.clone(), version.as_str().unwrap_or("").to_string()))
+            .collect();
+    }
+    library.runtime = parse_assets(entry, "runtime", DepsAssetType::Runtime)?;
+    library.native = parse_assets(entry, "native", DepsAssetType::Native)?;
+    library.resources = parse_assets(entry, "resources", DepsAssetType::Resource)?;
+    library.runtime_targets = parse_assets(entry, "runtimeTargets", DepsAssetType::Runtime)?;
+    Ok(library)
+}
+
+fn parse_assets(
+    entry: &Map<String, Value>,
+    key: &str,
+    asset_type: DepsAssetType,
+) -> Result<Vec<DepsAsset>> {
+    let Some(section) = entry.get(key) else {
+        return Ok(Vec::new());
+    };
+    let mut assets = Vec::new();
+    for (path, properties) in object(section, key)? {
+        let properties = properties.as_object();
+        let property = |name: &str| properties.and_then(|p| string(p, name));
+        let asset_type = match property("assetType").as_deref() {
+            Some("native") => DepsAssetType::Native,
+            Some("resources") => DepsAssetType::Resource,
+            Some(_) => DepsAssetType::Runtime,
+            None => asset_type,
+        };
+        assets.push(DepsAsset {
+            path: path.clone(),
+            asset_type,
+            rid: property("rid"),
+            locale: property("locale"),
+            assembly_version: property("assemblyVersion"),
+            file_version: property("fileVersion"),
+        });
+    }
+    Ok(assets)
+}
+
+fn split_library_id(id: &str) -> Result<(String, String)> {
+    id.split_once('/')
+        .map(|(name, version)| (name.to_string(), version.to_string()))
+        .ok_or_else(|| Error::BadConfig(format!("library id {id:?} is not name/version")))
+}
+
+pub(crate) fn object<'a>(value: &'a Value, what: &str) -> Result<&'a Map<String, Value>> {
+    value
+        .as_object()
+        .ok_or_else(|| Error::BadConfig(format!("{what} is not a JSON object")))
+}
+
+pub(crate) fn string(map: &Map<String, Value>, key: &str) -> Option<String> {
+    map.get(key).and_then(Value::as_str).map(str::to_string)
+}
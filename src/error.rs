@@ -0,0 +1,63 @@
+//! Error type for the pure-Rust image, metadata and strong-name support.
+
+use std::fmt;
+
+/// Errors produced while reading PE images and CLI metadata.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// An I/O error occurred while reading a file.
+    Io(std::io::Error),
+    /// The PE/COFF image is malformed or truncated.
+    BadImageFormat(String),
+    /// The image is a valid PE file but has no CLI header.
+    NotManaged,
+    /// The CLI metadata is malformed or truncated.
+    BadMetadata(String),
+    /// A signature or custom attribute blob is malformed.
+    BadSignature(String),
+    /// A strong-name key, token or signature is malformed.
+    BadStrongName(String),
+    /// An assembly display name is malformed.
+    BadAssemblyName(String),
+    /// A configuration file such as `*.deps.json` is malformed.
+    BadConfig(String),
+    /// A name index file is malformed or has an unsupported version.
+    BadIndex(String),
+    /// A method body or the IL in it is malformed.
+    BadMethodBody(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::BadImageFormat(msg) => write!(f, "bad image format: {msg}"),
+            Error::NotManaged => f.write_str("image does not contain a CLI header"),
+            Error::BadMetadata(msg) => write!(f, "bad metadata: {msg}"),
+            Error::BadSignature(msg) => write!(f, "bad signature: {msg}"),
+            Error::BadStrongName(msg) => write!(f, "bad strong name: {msg}"),
+            Error::BadAssemblyName(msg) => write!(f, "bad assembly name: {msg}"),
+            Error::BadConfig(msg) => write!(f, "bad configuration: {msg}"),
+            Error::BadIndex(msg) => write!(f, "bad name index: {msg}"),
+            Error::BadMethodBody(msg) => write!(f, "bad method body: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+pub(crate) type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,257 @@
+//! Offline enumeration of the Global Assembly Cache.
+//!
+//! A pure-Rust reader of the GAC directory layout, for Windows images,
+//! containers and copies of `C:\Windows` examined on any platform where
+//! `IAssemblyCache` and `IAssemblyEnum` are not available.
+//!
+//! Assemblies are stored as
+//! `<cache>\<Name>\<Version>_<Culture>_<Token>\<Name>.dll`, with a `v4.0_`
+//! prefix on the last directory in the .NET 4 GAC
+//! (`Microsoft.NET\assembly`).
+
+use std::path::{Path, PathBuf};
+
+use crate::assembly_identity::{
+    AssemblyCompareFlags, AssemblyIdentity, AssemblyPublicKey, ProcessorArchitecture,
+};
+use crate::error::Result;
+
+/// A cache directory of the GAC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GacCache {
+    /// `GAC`: .NET 1.x assemblies without a processor architecture.
+    Legacy,
+    /// `GAC_MSIL`: architecture-neutral assemblies.
+    Msil,
+    /// `GAC_32`: 32-bit assemblies.
+    X86,
+    /// `GAC_64`: 64-bit assemblies.
+    X64,
+}
+
+impl GacCache {
+    /// All caches, in the order fusion searches them on a 64-bit system.
+    pub const ALL: [GacCache; 4] = [
+        GacCache::X64,
+        GacCache::X86,
+        GacCache::Msil,
+        GacCache::Legacy,
+    ];
+
+    /// Returns the directory name, e.g. `GAC_MSIL`.
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            GacCache::Legacy => "GAC",
+            GacCache::Msil => "GAC_MSIL",
+            GacCache::X86 => "GAC_32",
+            GacCache::X64 => "GAC_64",
+        }
+    }
+
+    /// Returns the processor architecture of assemblies in the cache.
+    /// `GAC_64` is reported as AMD64.
+    pub fn processor_architecture(self) -> Option<ProcessorArchitecture> {
+        match self {
+            GacCache::Legacy => None,
+            GacCache::Msil => Some(ProcessorArchitecture::Msil),
+            GacCache::X86 => Some(ProcessorArchitecture::X86),
+            GacCache::X64 => Some(ProcessorArchitecture::Amd64),
+        }
+    }
+}
+
+/// The runtime generation a GAC belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GacRuntime {
+    /// The CLR 2.0 GAC in `Windows\assembly`, also used by .NET 1.x.
+    V2,
+    /// The CLR 4 GAC in `Windows\Microsoft.NET\assembly`.
+    V4,
+}
+
+/// An assembly found in the GAC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GacEntry {
+    /// The identity encoded in the directory layout.
+    pub identity: AssemblyIdentity,
+    /// The assembly file.
+    pub path: PathBuf,
+    pub cache: GacCache,
+    pub runtime: GacRuntime,
+}
+
+/// A GAC rooted at an arbitrary path.
+///
+/// The root may be a Windows directory, containing `assembly` and
+/// `Microsoft.NET\assembly`, or a single GAC directory containing the
+/// `GAC_*` caches. Directory names are matched case-insensitively, so
+/// copies on case-sensitive file systems work too.
+#[derive(Debug, Clone)]
+pub struct OfflineGac {
+    root: PathBuf,
+}
+
+impl OfflineGac {
+    /// Creates a reader for the GAC at `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        OfflineGac { root: root.into() }
+    }
+
+    /// Returns the root directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Lists every assembly in the GAC, sorted by name, version and culture.
+    pub fn enumerate(&self) -> Result<Vec<GacEntry>> {
+        self.collect(|_| true)
+    }
+
+    /// Lists the assemblies with simple name `name`.
+    pub fn find_by_name(&self, name: &str) -> Result<Vec<GacEntry>> {
+        self.collect(|dir| dir.eq_ignore_ascii_case(name))
+    }
+
+    /// Lists the assemblies matching `reference`, treated as a partial
+    /// name: only the properties it specifies are compared.
+    pub fn find(&self, reference: &AssemblyIdentity) -> Result<Vec<GacEntry>> {
+        let mut entries = self.find_by_name(&reference.name)?;
+        entries.retain(|entry| reference.is_equal(&entry.identity, AssemblyCompareFlags::DEFAULT));
+        Ok(entries)
+    }
+
+    /// Returns the cache directories below the root.
+    fn caches(&self) -> Result<Vec<(PathBuf, GacCache)>> {
+        let mut bases = vec![self.root.clone()];
+        if let Some(assembly) = child_dir(&self.root, "assembly")? {
+            bases.push(assembly);
+        }
+        if let Some(framework) = child_dir(&self.root, "Microsoft.NET")?
+            && let Some(assembly) = child_dir(&framework, "assembly")?
+        {
+            bases.push(assembly);
+        }
+
+        let mut caches = Vec::new();
+        for base in bases {
+            for cache in GacCache::ALL {
+                if let Some(dir) = child_dir(&base, cache.dir_name())? {
+                    caches.push((dir, cache));
+                }
+            }
+        }
+        Ok(caches)
+    }
+
+    fn collect(&self, mut filter: impl FnMut(&str) -> bool) -> Result<Vec<GacEntry>> {
+        let mut entries = Vec::new();
+        for (cache_dir, cache) in self.caches()? {
+            for name_dir in std::fs::read_dir(&cache_dir)? {
+                let name_dir = name_dir?;
+                let name = name_dir.file_name().to_string_lossy().into_owned();
+                if !name_dir.file_type()?.is_dir() || !filter(&name) {
+                    continue;
+                }
+                for version_dir in std::fs::read_dir(name_dir.path())? {
+                    let version_dir = version_dir?;
+                    if !version_dir.file_type()?.is_dir() {
+                        continue;
+                    }
+                    let dir_name = version_dir.file_name().to_string_lossy().into_owned();
+                    let Some((identity, runtime)) = parse_dir_name(&name, &dir_name, cache) else {
+                        continue;
+                    };
+                    if let Some(path) = assembly_file(&version_dir.path(), &name)? {
+                        entries.push(GacEntry {
+                            identity,
+                            path,
+                            cache,
+                            runtime,
+                        });
+                    }
+                }
+            }
+        }
+        entries.sort_by(|a, b| {
+            let key = |entry: &GacEntry| {
+                (
+                    entry.identity.name.to_ascii_lowercase(),
+                    entry.identity.version.map(|v| v.parts()),
+                    entry.identity.culture.clone(),
+                )
+            };
+            key(a).cmp(&key(b)).then_with(|| a.path.cmp(&b.path))
+        });
+        Ok(entries)
+    }
+}
+
+/// Parses `[v4.0_]<Version>_<Culture>_<Token>`.
+fn parse_dir_name(
+    name: &str,
+    dir: &str,
+    cache: GacCache,
+) -> Option<(AssemblyIdentity, GacRuntime)> {
+    let (runtime, rest) = match dir.strip_prefix("v4.0_") {
+        Some(rest) => (GacRuntime::V4, rest),
+        None => (GacRuntime::V2, dir),
+    };
+    let mut parts = rest.splitn(3, '_');
+    let version = parts.next()?.parse().ok()?;
+    let culture = parts.next()?;
+    let token = parts.next()?;
+    let public_key = if token.is_empty() {
+        AssemblyPublicKey::None
+    } else {
+        AssemblyPublicKey::Token(token.parse().ok()?)
+    };
+
+    let mut identity = AssemblyIdentity::new(name);
+    identity.version = Some(version);
+    identity.culture = Some(if culture.eq_ignore_ascii_case("neutral") {
+        String::new()
+    } else {
+        culture.to_string()
+    });
+    identity.public_key = Some(public_key);
+    identity.processor_architecture = cache.processor_architecture();
+    Some((identity, runtime))
+}
+
+/// Finds `<name>.dll` or `<name>.exe` in `dir`, ignoring case.
+fn assembly_file(dir: &Path, name: &str) -> Result<Option<PathBuf>> {
+    for extension in ["dll", "exe"] {
+        let file = format!("{name}.{extension}");
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file()
+                && entry
+                    .file_name()
+                    .to_string_lossy()
+                    .eq_ignore_ascii_case(&file)
+            {
+                return Ok(Some(entry.path()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Finds the subdirectory `name` of `dir`, ignoring case.
+fn child_dir(dir: &Path, name: &str) -> Result<Option<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir()
+            && entry
+                .file_name()
+                .to_string_lossy()
+                .eq_ignore_ascii_case(name)
+        {
+            return Ok(Some(entry.path()));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,15 @@
+//! CIL method bodies and static IL weaving.
+//!
+//! [`MethodBody`] decodes a method's IL into instructions whose branches
+//! refer to labels rather than offsets, so code can be inserted and removed
+//! freely before the body is encoded again. [`AssemblyWeaver`] applies a
+//! [`MethodWeaver`] to the methods of an assembly on disk and writes the
+//! result back out, with its debug information updated to match.
+
+mod body;
+mod opcode;
+mod weave;
+
+pub use body::*;
+pub use opcode::*;
+pub use weave::*;
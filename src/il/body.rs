@@ -0,0 +1,834 @@
+//! Method bodies (ECMA-335 II.25.4): decoding IL into instructions with
+//! symbolic branch targets, editing it and encoding it back.
+
+use std::collections::HashMap;
+
+use super::opcode::{FlowControl, OpCode, OperandType};
+use crate::error::{Error, Result};
+use crate::reader::{Cursor, MetadataReader, MethodDefRow, PeImage};
+
+const CORILMETHOD_TINY_FORMAT: u8 = 0x02;
+const CORILMETHOD_FAT_FORMAT: u16 = 0x0003;
+const CORILMETHOD_FORMAT_MASK: u8 = 0x03;
+const CORILMETHOD_MORE_SECTS: u16 = 0x0008;
+const CORILMETHOD_INIT_LOCALS: u16 = 0x0010;
+/// Size of a fat header in 4-byte units, stored in its top 4 bits.
+const FAT_HEADER_DWORDS: u16 = 3;
+
+const CORILMETHOD_SECT_EH_TABLE: u8 = 0x01;
+const CORILMETHOD_SECT_FAT_FORMAT: u8 = 0x40;
+const CORILMETHOD_SECT_MORE_SECTS: u8 = 0x80;
+
+const COR_ILEXCEPTION_CLAUSE_EXCEPTION: u32 = 0x0000;
+const COR_ILEXCEPTION_CLAUSE_FILTER: u32 = 0x0001;
+const COR_ILEXCEPTION_CLAUSE_FINALLY: u32 = 0x0002;
+const COR_ILEXCEPTION_CLAUSE_FAULT: u32 = 0x0004;
+
+/// A position in a method body that branches and exception handlers refer
+/// to.
+///
+/// Every instruction has a label. [`MethodBody::parse`] gives the n-th
+/// instruction of the original body `Label(n)`; new instructions get fresh
+/// labels from [`MethodBody::new_label`], so labels stay valid however the
+/// instruction list is edited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Label(pub u32);
+
+impl Label {
+    /// The end of the body, just past the last instruction.
+    pub const END: Label = Label(u32::MAX);
+}
+
+/// The inline operand of an instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    None,
+    /// `ShortInlineI`.
+    Int8(i8),
+    /// `InlineI`.
+    Int32(i32),
+    /// `InlineI8`.
+    Int64(i64),
+    /// `ShortInlineR`.
+    Float32(f32),
+    /// `InlineR`.
+    Float64(f64),
+    /// A metadata token: `InlineMethod`, `InlineField`, `InlineType`,
+    /// `InlineTok`, `InlineSig`, or an `mdString` token for `ldstr`.
+    Token(u32),
+    /// An argument or local index: `ShortInlineVar` or `InlineVar`.
+    Variable(u16),
+    /// A branch target: `ShortInlineBrTarget` or `InlineBrTarget`.
+    Target(Label),
+    /// The targets of a `switch`.
+    Switch(Vec<Label>),
+}
+
+/// A decoded instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub label: Label,
+    pub opcode: OpCode,
+    pub operand: Operand,
+    /// The IL offset of the instruction in the body it was parsed from, or
+    /// `None` for an instruction added since.
+    pub original_offset: Option<u32>,
+}
+
+/// The kind of an exception handling clause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionHandlerKind {
+    /// A typed catch handler for the given `TypeDefOrRef` token.
+    Catch(u32),
+    /// A filtered handler whose filter block starts at the label.
+    Filter(Label),
+    Finally,
+    Fault,
+}
+
+/// An exception handling clause. Ranges are half-open: the end labels name
+/// the first instruction after the block, or [`Label::END`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExceptionHandler {
+    pub kind: ExceptionHandlerKind,
+    pub try_start: Label,
+    pub try_end: Label,
+    pub handler_start: Label,
+    pub handler_end: Label,
+}
+
+/// A decoded method body.
+///
+/// ```
+/// use mscoree::{Label, MethodBody, OpCode, Operand};
+///
+/// // ldarg.0; brtrue.s +1; ret; ldc.i4.1; pop; ret
+/// let mut body = MethodBody::parse(&[0x1E, 0x02, 0x2D, 0x01, 0x2A, 0x17, 0x26, 0x2A])?;
+/// assert_eq!(body.instructions[1].operand, Operand::Target(Label(3)));
+///
+/// // Insert a call before the first instruction; the branch still lands on
+/// // `ldc.i4.1`.
+/// let call = body.instruction(OpCode::CALL, Operand::Token(0x0A00_0001));
+/// body.instructions.insert(0, call);
+/// body.max_stack = body.compute_max_stack(false, |_, _| Ok((0, 0)))?;
+/// let bytes = body.to_bytes()?;
+/// assert_eq!(
+///     bytes,
+///     [0x32, 0x28, 0x01, 0x00, 0x00, 0x0A, 0x02, 0x2D, 0x01, 0x2A, 0x17, 0x26, 0x2A],
+/// );
+/// # Ok::<(), mscoree::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodBody {
+    pub max_stack: u16,
+    /// Whether locals are zero-initialized (`CorILMethod_InitLocals`).
+    pub init_locals: bool,
+    /// The `StandAloneSig` token of the local variable signature, or 0.
+    pub local_var_sig: u32,
+    pub instructions: Vec<Instruction>,
+    pub exception_handlers: Vec<ExceptionHandler>,
+    next_label: u32,
+}
+
+impl Default for MethodBody {
+    fn default() -> Self {
+        Self {
+            max_stack: 8,
+            init_locals: false,
+            local_var_sig: 0,
+            instructions: Vec::new(),
+            exception_handlers: Vec::new(),
+            next_label: 0,
+        }
+    }
+}
+
+impl MethodBody {
+    /// Creates an empty body.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decodes a method body: its header, IL and exception handling
+    /// sections. Trailing bytes after the last section are ignored.
+    pub fn parse(data: &[u8]) -> Result<MethodBody> {
+        let header = parse_header(data)?;
+        let code = &data[header.header_len..header.header_len + header.code_size];
+        let mut body = MethodBody {
+            max_stack: header.max_stack,
+            init_locals: header.flags & CORILMETHOD_INIT_LOCALS != 0,
+            local_var_sig: header.local_var_sig,
+            ..Default::default()
+        };
+
+        // First pass: decode, keeping branch targets as offsets.
+        let mut decoded = Vec::new();
+        let mut cur = Cursor::new(code);
+        while !cur.is_empty() {
+            let offset = cur.position() as u32;
+            let first = cur.u8()?;
+            let opcode = if first == 0xFE {
+                OpCode(0xFE00 | cur.u8()? as u16)
+            } else {
+                OpCode(first as u16)
+            };
+            if !opcode.is_valid() {
+                return Err(Error::BadMethodBody(format!(
+                    "invalid opcode {:#x} at IL offset {offset:#x}",
+                    opcode.0
+                )));
+            }
+            let operand = match opcode.operand_type() {
+                OperandType::InlineNone => RawOperand::Plain(Operand::None),
+                OperandType::ShortInlineI => RawOperand::Plain(Operand::Int8(cur.u8()? as i8)),
+                OperandType::InlineI => RawOperand::Plain(Operand::Int32(cur.u32()? as i32)),
+                OperandType::InlineI8 => RawOperand::Plain(Operand::Int64(cur.u64()? as i64)),
+                OperandType::ShortInlineR => {
+                    RawOperand::Plain(Operand::Float32(f32::from_bits(cur.u32()?)))
+                }
+                OperandType::InlineR => {
+                    RawOperand::Plain(Operand::Float64(f64::from_bits(cur.u64()?)))
+                }
+                OperandType::ShortInlineVar => {
+                    RawOperand::Plain(Operand::Variable(cur.u8()? as u16))
+                }
+                OperandType::InlineVar => RawOperand::Plain(Operand::Variable(cur.u16()?)),
+                OperandType::ShortInlineBrTarget => {
+                    let delta = cur.u8()? as i8 as i64;
+                    RawOperand::Target(cur.position() as i64 + delta)
+                }
+                OperandType::InlineBrTarget => {
+                    let delta = cur.u32()? as i32 as i64;
+                    RawOperand::Target(cur.position() as i64 + delta)
+                }
+                OperandType::InlineSwitch => {
+                    let count = cur.u32()? as usize;
+                    if count > cur.remaining() / 4 {
+                        return Err(Error::BadMethodBody(format!(
+                            "switch at IL offset {offset:#x} has more targets than the body holds"
+                        )));
+                    }
+                    let deltas = (0..count)
+                        .map(|_| cur.u32().map(|d| d as i32 as i64))
+                        .collect::<Result<Vec<_>>>()?;
+                    let base = cur.position() as i64;
+                    RawOperand::Switch(deltas.into_iter().map(|d| base + d).collect())
+                }
+                OperandType::InlineMethod
+                | OperandType::InlineField
+                | OperandType::InlineType
+                | OperandType::InlineTok
+                | OperandType::InlineString
+                | OperandType::InlineSig => RawOperand::Plain(Operand::Token(cur.u32()?)),
+            };
+            decoded.push((offset, opcode, operand));
+        }
+
+        let labels: HashMap<u32, Label> = decoded
+            .iter()
+            .enumerate()
+            .map(|(index, (offset, _, _))| (*offset, Label(index as u32)))
+            .collect();
+        let code_size = code.len() as u32;
+        let label_at = |offset: i64| -> Result<Label> {
+            if offset == code_size as i64 {
+                return Ok(Label::END);
+            }
+            u32::try_from(offset)
+                .ok()
+                .and_then(|offset| labels.get(&offset).copied())
+                .ok_or_else(|| {
+                    Error::BadMethodBody(format!(
+                        "IL offset {offset:#x} is not the start of an instruction"
+                    ))
+                })
+        };
+        for (index, (offset, opcode, operand)) in decoded.into_iter().enumerate() {
+            let operand = match operand {
+                RawOperand::Plain(operand) => operand,
+                RawOperand::Target(target) => Operand::Target(label_at(target)?),
+                RawOperand::Switch(targets) => {
+                    Operand::Switch(targets.into_iter().map(label_at).collect::<Result<_>>()?)
+                }
+            };
+            body.instructions.push(Instruction {
+                label: Label(index as u32),
+                opcode,
+                operand,
+                original_offset: Some(offset),
+            });
+        }
+        body.next_label = body.instructions.len() as u32;
+
+        if header.flags & CORILMETHOD_MORE_SECTS != 0 {
+            let mut pos = (header.header_len + header.code_size).next_multiple_of(4);
+            loop {
+                let mut cur = Cursor::at(data, pos);
+                let kind = cur.u8()?;
+                let is_fat = kind & CORILMETHOD_SECT_FAT_FORMAT != 0;
+                let size = if is_fat {
+                    let bytes = cur.bytes(3)?;
+                    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]) as usize
+                } else {
+                    let size = cur.u8()? as usize;
+                    cur.skip(2)?;
+                    size
+                };
+                if size < 4 {
+                    return Err(Error::BadMethodBody(format!(
+                        "method data section at {pos:#x} is {size} bytes long"
+                    )));
+                }
+                if kind & CORILMETHOD_SECT_EH_TABLE != 0 {
+                    let clause_size = if is_fat { 24 } else { 12 };
+                    for _ in 0..(size - 4) / clause_size {
+                        let read = |cur: &mut Cursor, wide: bool| -> Result<u32> {
+                            if wide {
+                                cur.u32()
+                            } else {
+                                cur.u16().map(u32::from)
+                            }
+                        };
+                        let flags = read(&mut cur, is_fat)?;
+                        let try_offset = read(&mut cur, is_fat)?;
+                        let try_length = if is_fat { cur.u32()? } else { cur.u8()? as u32 };
+                        let handler_offset = read(&mut cur, is_fat)?;
+                        let handler_length = if is_fat { cur.u32()? } else { cur.u8()? as u32 };
+                        let class_or_filter = cur.u32()?;
+                        let kind = match flags & 0x7 {
+                            COR_ILEXCEPTION_CLAUSE_EXCEPTION => {
+                                ExceptionHandlerKind::Catch(class_or_filter)
+                            }
+                            COR_ILEXCEPTION_CLAUSE_FILTER => {
+                                ExceptionHandlerKind::Filter(label_at(class_or_filter as i64)?)
+                            }
+                            COR_ILEXCEPTION_CLAUSE_FINALLY => ExceptionHandlerKind::Finally,
+                            COR_ILEXCEPTION_CLAUSE_FAULT => ExceptionHandlerKind::Fault,
+                            other => {
+                                return Err(Error::BadMethodBody(format!(
+                                    "unknown exception clause kind {other:#x}"
+                                )));
+                            }
+                        };
+                        body.exception_handlers.push(ExceptionHandler {
+                            kind,
+                            try_start: label_at(try_offset as i64)?,
+                            try_end: label_at(try_offset as i64 + try_length as i64)?,
+                            handler_start: label_at(handler_offset as i64)?,
+                            handler_end: label_at(handler_offset as i64 + handler_length as i64)?,
+                        });
+                    }
+                }
+                pos = (pos + size).next_multiple_of(4);
+                if kind & CORILMETHOD_SECT_MORE_SECTS == 0 {
+                    break;
+                }
+            }
+        }
+        Ok(body)
+    }
+
+    /// Returns a fresh label for a new instruction.
+    pub fn new_label(&mut self) -> Label {
+        let label = Label(self.next_label);
+        self.next_label += 1;
+        label
+    }
+
+    /// Creates a new instruction with a fresh label, to insert into
+    /// [`instructions`](Self::instructions).
+    pub fn instruction(&mut self, opcode: OpCode, operand: Operand) -> Instruction {
+        Instruction {
+            label: self.new_label(),
+            opcode,
+            operand,
+            original_offset: None,
+        }
+    }
+
+    /// Returns the index of the instruction with `label`.
+    pub fn position(&self, label: Label) -> Option<usize> {
+        self.instructions.iter().position(|i| i.label == label)
+    }
+
+    /// Makes every branch and `switch` that targets `from` target `to`
+    /// instead. Exception handler boundaries are left alone.
+    pub fn redirect_branches(&mut self, from: Label, to: Label) {
+        for instruction in &mut self.instructions {
+            match &mut instruction.operand {
+                Operand::Target(target) if *target == from => *target = to,
+                Operand::Switch(targets) => {
+                    for target in targets.iter_mut().filter(|t| **t == from) {
+                        *target = to;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the IL offset each instruction will have when the body is
+    /// encoded, followed by the code size.
+    ///
+    /// Short branches whose target is out of range are widened to their
+    /// long forms; the offsets account for that.
+    pub fn instruction_offsets(&self) -> Result<Vec<u32>> {
+        Ok(self.layout()?.1)
+    }
+
+    /// Encodes the body: a tiny header when the body allows one and a fat
+    /// header otherwise, the IL, and the exception handling section.
+    ///
+    /// [`max_stack`](Self::max_stack) is written as it is; see
+    /// [`compute_max_stack`](Self::compute_max_stack).
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let (opcodes, offsets) = self.layout()?;
+        let labels = self.label_offsets(&offsets);
+        let offset_of = |label: Label| -> Result<u32> {
+            labels.get(&label).copied().ok_or_else(|| {
+                Error::BadMethodBody(format!("label {} is not in the body", label.0))
+            })
+        };
+
+        let mut code = Vec::with_capacity(*offsets.last().unwrap_or(&0) as usize);
+        for ((instruction, &opcode), &offset) in
+            self.instructions.iter().zip(&opcodes).zip(&offsets)
+        {
+            if opcode.size() == 2 {
+                code.push(0xFE);
+            }
+            code.push(opcode.0 as u8);
+            let mismatch = || {
+                Error::BadMethodBody(format!(
+                    "{opcode} at IL offset {offset:#x} cannot take operand {:?}",
+                    instruction.operand
+                ))
+            };
+            let next = offset + instruction_size(opcode, &instruction.operand) as u32;
+            match (opcode.operand_type(), &instruction.operand) {
+                (OperandType::InlineNone, Operand::None) => {}
+                (OperandType::ShortInlineI, &Operand::Int8(value)) => code.push(value as u8),
+                (OperandType::InlineI, &Operand::Int32(value)) => {
+                    code.extend_from_slice(&value.to_le_bytes())
+                }
+                (OperandType::InlineI8, &Operand::Int64(value)) => {
+                    code.extend_from_slice(&value.to_le_bytes())
+                }
+                (OperandType::ShortInlineR, &Operand::Float32(value)) => {
+                    code.extend_from_slice(&value.to_le_bytes())
+                }
+                (OperandType::InlineR, &Operand::Float64(value)) => {
+                    code.extend_from_slice(&value.to_le_bytes())
+                }
+                (OperandType::ShortInlineVar, &Operand::Variable(index)) => {
+                    code.push(u8::try_from(index).map_err(|_| mismatch())?)
+                }
+                (OperandType::InlineVar, &Operand::Variable(index)) => {
+                    code.extend_from_slice(&index.to_le_bytes())
+                }
+                (OperandType::ShortInlineBrTarget, &Operand::Target(target)) => {
+                    let delta = offset_of(target)? as i64 - next as i64;
+                    code.push(i8::try_from(delta).map_err(|_| mismatch())? as u8);
+                }
+                (OperandType::InlineBrTarget, &Operand::Target(target)) => {
+                    let delta = offset_of(target)? as i64 - next as i64;
+                    code.extend_from_slice(&(delta as i32).to_le_bytes());
+                }
+                (OperandType::InlineSwitch, Operand::Switch(targets)) => {
+                    code.extend_from_slice(&(targets.len() as u32).to_le_bytes());
+                    for &target in targets {
+                        let delta = offset_of(target)? as i64 - next as i64;
+                        code.extend_from_slice(&(delta as i32).to_le_bytes());
+                    }
+                }
+                (
+                    OperandType::InlineMethod
+                    | OperandType::InlineField
+                    | OperandType::InlineType
+                    | OperandType::InlineTok
+                    | OperandType::InlineString
+                    | OperandType::InlineSig,
+                    &Operand::Token(token),
+                ) => code.extend_from_slice(&token.to_le_bytes()),
+                _ => return Err(mismatch()),
+            }
+        }
+
+        let mut clauses = Vec::with_capacity(self.exception_handlers.len());
+        for handler in &self.exception_handlers {
+            let try_offset = offset_of(handler.try_start)?;
+            let handler_offset = offset_of(handler.handler_start)?;
+            let length = |start: u32, end: Label| -> Result<u32> {
+                offset_of(end)?.checked_sub(start).ok_or_else(|| {
+                    Error::BadMethodBody(format!(
+                        "exception handler block at IL offset {start:#x} ends before it starts"
+                    ))
+                })
+            };
+            let (flags, extra) = match handler.kind {
+                ExceptionHandlerKind::Catch(token) => (COR_ILEXCEPTION_CLAUSE_EXCEPTION, token),
+                ExceptionHandlerKind::Filter(filter) => {
+                    (COR_ILEXCEPTION_CLAUSE_FILTER, offset_of(filter)?)
+                }
+                ExceptionHandlerKind::Finally => (COR_ILEXCEPTION_CLAUSE_FINALLY, 0),
+                ExceptionHandlerKind::Fault => (COR_ILEXCEPTION_CLAUSE_FAULT, 0),
+            };
+            clauses.push([
+                flags,
+                try_offset,
+                length(try_offset, handler.try_end)?,
+                handler_offset,
+                length(handler_offset, handler.handler_end)?,
+                extra,
+            ]);
+        }
+
+        let mut out = Vec::new();
+        let is_tiny = code.len() < 64
+            && self.max_stack <= 8
+            && self.local_var_sig == 0
+            && !self.init_locals
+            && clauses.is_empty();
+        if is_tiny {
+            out.push(((code.len() as u8) << 2) | CORILMETHOD_TINY_FORMAT);
+            out.extend_from_slice(&code);
+            return Ok(out);
+        }
+        let mut flags = CORILMETHOD_FAT_FORMAT | (FAT_HEADER_DWORDS << 12);
+        if self.init_locals {
+            flags |= CORILMETHOD_INIT_LOCALS;
+        }
+        if !clauses.is_empty() {
+            flags |= CORILMETHOD_MORE_SECTS;
+        }
+        out.extend_from_slice(&flags.to_le_bytes());
+        out.extend_from_slice(&self.max_stack.to_le_bytes());
+        out.extend_from_slice(&(code.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.local_var_sig.to_le_bytes());
+        out.extend_from_slice(&code);
+        if clauses.is_empty() {
+            return Ok(out);
+        }
+
+        out.resize(out.len().next_multiple_of(4), 0);
+        let small_size = 4 + 12 * clauses.len();
+        let fits_small = small_size <= 0xFF
+            && clauses
+                .iter()
+                .all(|c| c[1] <= 0xFFFF && c[2] <= 0xFF && c[3] <= 0xFFFF && c[4] <= 0xFF);
+        if fits_small {
+            out.push(CORILMETHOD_SECT_EH_TABLE);
+            out.push(small_size as u8);
+            out.extend_from_slice(&[0, 0]);
+            for [
+                flags,
+                try_offset,
+                try_length,
+                handler_offset,
+                handler_length,
+                extra,
+            ] in clauses
+            {
+                out.extend_from_slice(&(flags as u16).to_le_bytes());
+                out.extend_from_slice(&(try_offset as u16).to_le_bytes());
+                out.push(try_length as u8);
+                out.extend_from_slice(&(handler_offset as u16).to_le_bytes());
+                out.push(handler_length as u8);
+                out.extend_from_slice(&extra.to_le_bytes());
+            }
+        } else {
+            let size = 4 + 24 * clauses.len() as u32;
+            out.push(CORILMETHOD_SECT_EH_TABLE | CORILMETHOD_SECT_FAT_FORMAT);
+            out.extend_from_slice(&size.to_le_bytes()[..3]);
+            for clause in clauses {
+                for value in clause {
+                    out.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Computes the maximum evaluation stack depth of the body by following
+    /// its control flow.
+    ///
+    /// `returns_value` says whether `ret` pops a value. `call_effect` gives
+    /// the number of stack slots a `call`, `callvirt`, `newobj` or `calli`
+    /// with the given token pops and pushes, which depends on its
+    /// signature.
+    pub fn compute_max_stack(
+        &self,
+        returns_value: bool,
+        mut call_effect: impl FnMut(OpCode, u32) -> Result<(u32, u32)>,
+    ) -> Result<u16> {
+        let mut indices: HashMap<Label, usize> = self
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(index, i)| (i.label, index))
+            .collect();
+        indices.insert(Label::END, self.instructions.len());
+        let index_of = |label: Label| -> Result<usize> {
+            indices.get(&label).copied().ok_or_else(|| {
+                Error::BadMethodBody(format!("label {} is not in the body", label.0))
+            })
+        };
+
+        let mut depths: Vec<Option<u32>> = vec![None; self.instructions.len() + 1];
+        let mut pending = vec![(0usize, 0u32)];
+        for handler in &self.exception_handlers {
+            let depth = match handler.kind {
+                ExceptionHandlerKind::Catch(_) => 1,
+                ExceptionHandlerKind::Filter(filter) => {
+                    pending.push((index_of(filter)?, 1));
+                    1
+                }
+                ExceptionHandlerKind::Finally | ExceptionHandlerKind::Fault => 0,
+            };
+            pending.push((index_of(handler.handler_start)?, depth));
+        }
+
+        let mut max = 0u32;
+        while let Some((index, depth)) = pending.pop() {
+            let Some(instruction) = self.instructions.get(index) else {
+                continue;
+            };
+            if depths[index].is_some() {
+                continue;
+            }
+            depths[index] = Some(depth);
+            let opcode = instruction.opcode;
+            let (pops, pushes) = match (opcode.pops(), opcode.pushes()) {
+                (Some(pops), Some(pushes)) => (pops, pushes),
+                _ if opcode == OpCode::RET => (returns_value as u32, 0),
+                _ => {
+                    let Operand::Token(token) = instruction.operand else {
+                        return Err(Error::BadMethodBody(format!(
+                            "{opcode} does not have a token operand"
+                        )));
+                    };
+                    call_effect(opcode, token)?
+                }
+            };
+            let depth = depth.checked_sub(pops).ok_or_else(|| {
+                Error::BadMethodBody(format!(
+                    "{opcode} (instruction {index}) pops {pops} values from a stack of {depth}"
+                ))
+            })? + pushes;
+            max = max.max(depth);
+
+            match opcode.flow_control() {
+                FlowControl::Return | FlowControl::Throw => {}
+                _ if opcode == OpCode::JMP => {}
+                FlowControl::Branch => {
+                    let Operand::Target(target) = instruction.operand else {
+                        continue;
+                    };
+                    // `leave` empties the evaluation stack.
+                    let is_leave = matches!(opcode, OpCode::LEAVE | OpCode::LEAVE_S);
+                    pending.push((index_of(target)?, if is_leave { 0 } else { depth }));
+                }
+                FlowControl::CondBranch => {
+                    match &instruction.operand {
+                        Operand::Target(target) => pending.push((index_of(*target)?, depth)),
+                        Operand::Switch(targets) => {
+                            for &target in targets {
+                                pending.push((index_of(target)?, depth));
+                            }
+                        }
+                        _ => {}
+                    }
+                    pending.push((index + 1, depth));
+                }
+                _ => pending.push((index + 1, depth)),
+            }
+        }
+        u16::try_from(max).map_err(|_| {
+            Error::BadMethodBody(format!("maximum stack depth {max} does not fit in 16 bits"))
+        })
+    }
+
+    /// Chooses the final opcode of each instruction, widening short
+    /// branches that cannot reach their targets, and returns the opcodes
+    /// with the instruction offsets and code size.
+    fn layout(&self) -> Result<(Vec<OpCode>, Vec<u32>)> {
+        let mut opcodes: Vec<OpCode> = self.instructions.iter().map(|i| i.opcode).collect();
+        loop {
+            let mut offsets = Vec::with_capacity(opcodes.len() + 1);
+            let mut offset = 0u32;
+            for (instruction, &opcode) in self.instructions.iter().zip(&opcodes) {
+                offsets.push(offset);
+                offset += instruction_size(opcode, &instruction.operand) as u32;
+            }
+            offsets.push(offset);
+
+            let labels = self.label_offsets(&offsets);
+            let mut widened = false;
+            for (index, instruction) in self.instructions.iter().enumerate() {
+                let opcode = opcodes[index];
+                if opcode.operand_type() != OperandType::ShortInlineBrTarget {
+                    continue;
+                }
+                let Operand::Target(target) = instruction.operand else {
+                    continue;
+                };
+                let Some(&target) = labels.get(&target) else {
+                    continue;
+                };
+                let delta = target as i64 - offsets[index + 1] as i64;
+                if i8::try_from(delta).is_err() {
+                    opcodes[index] = opcode.long_form();
+                    widened = true;
+                }
+            }
+            if !widened {
+                return Ok((opcodes, offsets));
+            }
+        }
+    }
+
+    fn label_offsets(&self, offsets: &[u32]) -> HashMap<Label, u32> {
+        let mut labels: HashMap<Label, u32> = self
+            .instructions
+            .iter()
+            .zip(offsets)
+            .map(|(i, &offset)| (i.label, offset))
+            .collect();
+        labels.insert(Label::END, *offsets.last().unwrap_or(&0));
+        labels
+    }
+}
+
+enum RawOperand {
+    Plain(Operand),
+    Target(i64),
+    Switch(Vec<i64>),
+}
+
+struct BodyHeader {
+    flags: u16,
+    header_len: usize,
+    code_size: usize,
+    max_stack: u16,
+    local_var_sig: u32,
+}
+
+fn parse_header(data: &[u8]) -> Result<BodyHeader> {
+    let mut cur = Cursor::new(data);
+    let first = cur.peek_u8()?;
+    let header = match first & CORILMETHOD_FORMAT_MASK {
+        CORILMETHOD_TINY_FORMAT => BodyHeader {
+            flags: 0,
+            header_len: 1,
+            code_size: (first >> 2) as usize,
+            max_stack: 8,
+            local_var_sig: 0,
+        },
+        0x03 => {
+            let flags = cur.u16()?;
+            let header_len = (flags >> 12) as usize * 4;
+            let max_stack = cur.u16()?;
+            let code_size = cur.u32()? as usize;
+            let local_var_sig = cur.u32()?;
+            if header_len < 12 {
+                return Err(Error::BadMethodBody(format!(
+                    "fat method header is {header_len} bytes long"
+                )));
+            }
+            BodyHeader {
+                flags: flags & 0x0FFF,
+                header_len,
+                code_size,
+                max_stack,
+                local_var_sig,
+            }
+        }
+        _ => {
+            return Err(Error::BadMethodBody(format!(
+                "invalid method header byte {first:#04x}"
+            )));
+        }
+    };
+    if data.len() < header.header_len + header.code_size {
+        return Err(Error::BadMethodBody(format!(
+            "method body of {} bytes is truncated",
+            header.header_len + header.code_size
+        )));
+    }
+    Ok(header)
+}
+
+/// Returns the code size of the method body at the start of `data`.
+pub(crate) fn method_code_size(data: &[u8]) -> Result<u32> {
+    Ok(parse_header(data)?.code_size as u32)
+}
+
+/// Returns the length of the method body at the start of `data`: its
+/// header, code and extra data sections.
+pub(crate) fn method_body_len(data: &[u8]) -> Result<usize> {
+    let header = parse_header(data)?;
+    let mut len = header.header_len + header.code_size;
+    if header.flags & CORILMETHOD_MORE_SECTS != 0 {
+        loop {
+            len = len.next_multiple_of(4);
+            let mut cur = Cursor::at(data, len);
+            let kind = cur.u8()?;
+            let size = if kind & CORILMETHOD_SECT_FAT_FORMAT != 0 {
+                let bytes = cur.bytes(3)?;
+                u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]) as usize
+            } else {
+                cur.u8()? as usize
+            };
+            if size < 4 || data.len() < len + size {
+                return Err(Error::BadMethodBody(format!(
+                    "method data section at {len:#x} is malformed"
+                )));
+            }
+            len += size;
+            if kind & CORILMETHOD_SECT_MORE_SECTS == 0 {
+                break;
+            }
+        }
+    }
+    Ok(len)
+}
+
+fn instruction_size(opcode: OpCode, operand: &Operand) -> usize {
+    let operand_size = match (opcode.operand_type(), operand) {
+        (OperandType::InlineSwitch, Operand::Switch(targets)) => 4 + 4 * targets.len(),
+        (ty, _) => ty.size(),
+    };
+    opcode.size() + operand_size
+}
+
+impl PeImage {
+    /// Returns the raw bytes of a method's body: header, IL and extra data
+    /// sections. Returns `None` for methods without a body.
+    pub fn method_body_bytes(
+        &self,
+        metadata: &MetadataReader,
+        method_rid: u32,
+    ) -> Result<Option<&[u8]>> {
+        let rva = metadata.row::<MethodDefRow>(method_rid)?.rva;
+        if rva == 0 {
+            return Ok(None);
+        }
+        let outside =
+            || Error::BadImageFormat(format!("method body at RVA {rva:#x} is outside the image"));
+        let start = self.rva_to_offset(rva).ok_or_else(outside)?;
+        let data = self.data().get(start..).ok_or_else(outside)?;
+        let len = method_body_len(data)?;
+        Ok(Some(&data[..len]))
+    }
+
+    /// Decodes a method's body. Returns `None` for methods without a body.
+    pub fn method_body(
+        &self,
+        metadata: &MetadataReader,
+        method_rid: u32,
+    ) -> Result<Option<MethodBody>> {
+        self.method_body_bytes(metadata, method_rid)?
+            .map(MethodBody::parse)
+            .transpose()
+    }
+}
@@ -0,0 +1,411 @@
+//! The CIL instruction set (ECMA-335 Partition III).
+
+use std::fmt;
+
+/// The kind of inline operand that follows an opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperandType {
+    InlineNone,
+    /// A signed 8-bit integer (an unsigned one for `unaligned.` and `no.`).
+    ShortInlineI,
+    InlineI,
+    InlineI8,
+    ShortInlineR,
+    InlineR,
+    ShortInlineBrTarget,
+    InlineBrTarget,
+    InlineSwitch,
+    InlineMethod,
+    InlineField,
+    InlineType,
+    /// A `TypeDef`, `TypeRef`, `TypeSpec`, `MethodDef`, `MemberRef` or
+    /// `MethodSpec` token, for `ldtoken`.
+    InlineTok,
+    InlineString,
+    InlineSig,
+    ShortInlineVar,
+    InlineVar,
+}
+
+impl OperandType {
+    /// Returns the size of the operand in bytes; for `InlineSwitch`, the
+    /// size of the target count only.
+    pub fn size(self) -> usize {
+        match self {
+            OperandType::InlineNone => 0,
+            OperandType::ShortInlineI
+            | OperandType::ShortInlineBrTarget
+            | OperandType::ShortInlineVar => 1,
+            OperandType::InlineVar => 2,
+            OperandType::InlineI8 | OperandType::InlineR => 8,
+            _ => 4,
+        }
+    }
+}
+
+/// How an instruction affects control flow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FlowControl {
+    /// Execution continues with the next instruction.
+    Next,
+    /// `break`.
+    Break,
+    /// A call; execution continues with the next instruction.
+    Call,
+    /// An unconditional branch, including `leave`.
+    Branch,
+    /// A conditional branch or `switch`.
+    CondBranch,
+    /// `ret`, `endfinally` or `endfilter`.
+    Return,
+    /// `throw` or `rethrow`.
+    Throw,
+    /// A prefix that modifies the next instruction.
+    Meta,
+}
+
+/// A CIL opcode. Two-byte opcodes are stored as `0xFE00 | second byte`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OpCode(pub u16);
+
+#[derive(Debug)]
+struct OpCodeInfo {
+    value: u16,
+    name: &'static str,
+    operand: OperandType,
+    flow: FlowControl,
+    /// Stack slots popped, or -1 if it depends on a signature.
+    pops: i8,
+    /// Stack slots pushed, or -1 if it depends on a signature.
+    pushes: i8,
+}
+
+macro_rules! opcodes {
+    ($($konst:ident = $value:literal, $name:literal, $operand:ident, $flow:ident, $pops:literal, $pushes:literal;)*) => {
+        impl OpCode {
+            $(
+                #[doc = concat!("`", $name, "`")]
+                pub const $konst: OpCode = OpCode($value);
+            )*
+        }
+
+        /// Sorted by value.
+        static OPCODES: &[OpCodeInfo] = &[
+            $(OpCodeInfo {
+                value: $value,
+                name: $name,
+                operand: OperandType::$operand,
+                flow: FlowControl::$flow,
+                pops: $pops,
+                pushes: $pushes,
+            },)*
+        ];
+    };
+}
+
+opcodes! {
+    NOP = 0x00, "nop", InlineNone, Next, 0, 0;
+    BREAK = 0x01, "break", InlineNone, Break, 0, 0;
+    LDARG_0 = 0x02, "ldarg.0", InlineNone, Next, 0, 1;
+    LDARG_1 = 0x03, "ldarg.1", InlineNone, Next, 0, 1;
+    LDARG_2 = 0x04, "ldarg.2", InlineNone, Next, 0, 1;
+    LDARG_3 = 0x05, "ldarg.3", InlineNone, Next, 0, 1;
+    LDLOC_0 = 0x06, "ldloc.0", InlineNone, Next, 0, 1;
+    LDLOC_1 = 0x07, "ldloc.1", InlineNone, Next, 0, 1;
+    LDLOC_2 = 0x08, "ldloc.2", InlineNone, Next, 0, 1;
+    LDLOC_3 = 0x09, "ldloc.3", InlineNone, Next, 0, 1;
+    STLOC_0 = 0x0A, "stloc.0", InlineNone, Next, 1, 0;
+    STLOC_1 = 0x0B, "stloc.1", InlineNone, Next, 1, 0;
+    STLOC_2 = 0x0C, "stloc.2", InlineNone, Next, 1, 0;
+    STLOC_3 = 0x0D, "stloc.3", InlineNone, Next, 1, 0;
+    LDARG_S = 0x0E, "ldarg.s", ShortInlineVar, Next, 0, 1;
+    LDARGA_S = 0x0F, "ldarga.s", ShortInlineVar, Next, 0, 1;
+    STARG_S = 0x10, "starg.s", ShortInlineVar, Next, 1, 0;
+    LDLOC_S = 0x11, "ldloc.s", ShortInlineVar, Next, 0, 1;
+    LDLOCA_S = 0x12, "ldloca.s", ShortInlineVar, Next, 0, 1;
+    STLOC_S = 0x13, "stloc.s", ShortInlineVar, Next, 1, 0;
+    LDNULL = 0x14, "ldnull", InlineNone, Next, 0, 1;
+    LDC_I4_M1 = 0x15, "ldc.i4.m1", InlineNone, Next, 0, 1;
+    LDC_I4_0 = 0x16, "ldc.i4.0", InlineNone, Next, 0, 1;
+    LDC_I4_1 = 0x17, "ldc.i4.1", InlineNone, Next, 0, 1;
+    LDC_I4_2 = 0x18, "ldc.i4.2", InlineNone, Next, 0, 1;
+    LDC_I4_3 = 0x19, "ldc.i4.3", InlineNone, Next, 0, 1;
+    LDC_I4_4 = 0x1A, "ldc.i4.4", InlineNone, Next, 0, 1;
+    LDC_I4_5 = 0x1B, "ldc.i4.5", InlineNone, Next, 0, 1;
+    LDC_I4_6 = 0x1C, "ldc.i4.6", InlineNone, Next, 0, 1;
+    LDC_I4_7 = 0x1D, "ldc.i4.7", InlineNone, Next, 0, 1;
+    LDC_I4_8 = 0x1E, "ldc.i4.8", InlineNone, Next, 0, 1;
+    LDC_I4_S = 0x1F, "ldc.i4.s", ShortInlineI, Next, 0, 1;
+    LDC_I4 = 0x20, "ldc.i4", InlineI, Next, 0, 1;
+    LDC_I8 = 0x21, "ldc.i8", InlineI8, Next, 0, 1;
+    LDC_R4 = 0x22, "ldc.r4", ShortInlineR, Next, 0, 1;
+    LDC_R8 = 0x23, "ldc.r8", InlineR, Next, 0, 1;
+    DUP = 0x25, "dup", InlineNone, Next, 1, 2;
+    POP = 0x26, "pop", InlineNone, Next, 1, 0;
+    JMP = 0x27, "jmp", InlineMethod, Call, 0, 0;
+    CALL = 0x28, "call", InlineMethod, Call, -1, -1;
+    CALLI = 0x29, "calli", InlineSig, Call, -1, -1;
+    RET = 0x2A, "ret", InlineNone, Return, -1, 0;
+    BR_S = 0x2B, "br.s", ShortInlineBrTarget, Branch, 0, 0;
+    BRFALSE_S = 0x2C, "brfalse.s", ShortInlineBrTarget, CondBranch, 1, 0;
+    BRTRUE_S = 0x2D, "brtrue.s", ShortInlineBrTarget, CondBranch, 1, 0;
+    BEQ_S = 0x2E, "beq.s", ShortInlineBrTarget, CondBranch, 2, 0;
+    BGE_S = 0x2F, "bge.s", ShortInlineBrTarget, CondBranch, 2, 0;
+    BGT_S = 0x30, "bgt.s", ShortInlineBrTarget, CondBranch, 2, 0;
+    BLE_S = 0x31, "ble.s", ShortInlineBrTarget, CondBranch, 2, 0;
+    BLT_S = 0x32, "blt.s", ShortInlineBrTarget, CondBranch, 2, 0;
+    BNE_UN_S = 0x33, "bne.un.s", ShortInlineBrTarget, CondBranch, 2, 0;
+    BGE_UN_S = 0x34, "bge.un.s", ShortInlineBrTarget, CondBranch, 2, 0;
+    BGT_UN_S = 0x35, "bgt.un.s", ShortInlineBrTarget, CondBranch, 2, 0;
+    BLE_UN_S = 0x36, "ble.un.s", ShortInlineBrTarget, CondBranch, 2, 0;
+    BLT_UN_S = 0x37, "blt.un.s", ShortInlineBrTarget, CondBranch, 2, 0;
+    BR = 0x38, "br", InlineBrTarget, Branch, 0, 0;
+    BRFALSE = 0x39, "brfalse", InlineBrTarget, CondBranch, 1, 0;
+    BRTRUE = 0x3A, "brtrue", InlineBrTarget, CondBranch, 1, 0;
+    BEQ = 0x3B, "beq", InlineBrTarget, CondBranch, 2, 0;
+    BGE = 0x3C, "bge", InlineBrTarget, CondBranch, 2, 0;
+    BGT = 0x3D, "bgt", InlineBrTarget, CondBranch, 2, 0;
+    BLE = 0x3E, "ble", InlineBrTarget, CondBranch, 2, 0;
+    BLT = 0x3F, "blt", InlineBrTarget, CondBranch, 2, 0;
+    BNE_UN = 0x40, "bne.un", InlineBrTarget, CondBranch, 2, 0;
+    BGE_UN = 0x41, "bge.un", InlineBrTarget, CondBranch, 2, 0;
+    BGT_UN = 0x42, "bgt.un", InlineBrTarget, CondBranch, 2, 0;
+    BLE_UN = 0x43, "ble.un", InlineBrTarget, CondBranch, 2, 0;
+    BLT_UN = 0x44, "blt.un", InlineBrTarget, CondBranch, 2, 0;
+    SWITCH = 0x45, "switch", InlineSwitch, CondBranch, 1, 0;
+    LDIND_I1 = 0x46, "ldind.i1", InlineNone, Next, 1, 1;
+    LDIND_U1 = 0x47, "ldind.u1", InlineNone, Next, 1, 1;
+    LDIND_I2 = 0x48, "ldind.i2", InlineNone, Next, 1, 1;
+    LDIND_U2 = 0x49, "ldind.u2", InlineNone, Next, 1, 1;
+    LDIND_I4 = 0x4A, "ldind.i4", InlineNone, Next, 1, 1;
+    LDIND_U4 = 0x4B, "ldind.u4", InlineNone, Next, 1, 1;
+    LDIND_I8 = 0x4C, "ldind.i8", InlineNone, Next, 1, 1;
+    LDIND_I = 0x4D, "ldind.i", InlineNone, Next, 1, 1;
+    LDIND_R4 = 0x4E, "ldind.r4", InlineNone, Next, 1, 1;
+    LDIND_R8 = 0x4F, "ldind.r8", InlineNone, Next, 1, 1;
+    LDIND_REF = 0x50, "ldind.ref", InlineNone, Next, 1, 1;
+    STIND_REF = 0x51, "stind.ref", InlineNone, Next, 2, 0;
+    STIND_I1 = 0x52, "stind.i1", InlineNone, Next, 2, 0;
+    STIND_I2 = 0x53, "stind.i2", InlineNone, Next, 2, 0;
+    STIND_I4 = 0x54, "stind.i4", InlineNone, Next, 2, 0;
+    STIND_I8 = 0x55, "stind.i8", InlineNone, Next, 2, 0;
+    STIND_R4 = 0x56, "stind.r4", InlineNone, Next, 2, 0;
+    STIND_R8 = 0x57, "stind.r8", InlineNone, Next, 2, 0;
+    ADD = 0x58, "add", InlineNone, Next, 2, 1;
+    SUB = 0x59, "sub", InlineNone, Next, 2, 1;
+    MUL = 0x5A, "mul", InlineNone, Next, 2, 1;
+    DIV = 0x5B, "div", InlineNone, Next, 2, 1;
+    DIV_UN = 0x5C, "div.un", InlineNone, Next, 2, 1;
+    REM = 0x5D, "rem", InlineNone, Next, 2, 1;
+    REM_UN = 0x5E, "rem.un", InlineNone, Next, 2, 1;
+    AND = 0x5F, "and", InlineNone, Next, 2, 1;
+    OR = 0x60, "or", InlineNone, Next, 2, 1;
+    XOR = 0x61, "xor", InlineNone, Next, 2, 1;
+    SHL = 0x62, "shl", InlineNone, Next, 2, 1;
+    SHR = 0x63, "shr", InlineNone, Next, 2, 1;
+    SHR_UN = 0x64, "shr.un", InlineNone, Next, 2, 1;
+    NEG = 0x65, "neg", InlineNone, Next, 1, 1;
+    NOT = 0x66, "not", InlineNone, Next, 1, 1;
+    CONV_I1 = 0x67, "conv.i1", InlineNone, Next, 1, 1;
+    CONV_I2 = 0x68, "conv.i2", InlineNone, Next, 1, 1;
+    CONV_I4 = 0x69, "conv.i4", InlineNone, Next, 1, 1;
+    CONV_I8 = 0x6A, "conv.i8", InlineNone, Next, 1, 1;
+    CONV_R4 = 0x6B, "conv.r4", InlineNone, Next, 1, 1;
+    CONV_R8 = 0x6C, "conv.r8", InlineNone, Next, 1, 1;
+    CONV_U4 = 0x6D, "conv.u4", InlineNone, Next, 1, 1;
+    CONV_U8 = 0x6E, "conv.u8", InlineNone, Next, 1, 1;
+    CALLVIRT = 0x6F, "callvirt", InlineMethod, Call, -1, -1;
+    CPOBJ = 0x70, "cpobj", InlineType, Next, 2, 0;
+    LDOBJ = 0x71, "ldobj", InlineType, Next, 1, 1;
+    LDSTR = 0x72, "ldstr", InlineString, Next, 0, 1;
+    NEWOBJ = 0x73, "newobj", InlineMethod, Call, -1, 1;
+    CASTCLASS = 0x74, "castclass", InlineType, Next, 1, 1;
+    ISINST = 0x75, "isinst", InlineType, Next, 1, 1;
+    CONV_R_UN = 0x76, "conv.r.un", InlineNone, Next, 1, 1;
+    UNBOX = 0x79, "unbox", InlineType, Next, 1, 1;
+    THROW = 0x7A, "throw", InlineNone, Throw, 1, 0;
+    LDFLD = 0x7B, "ldfld", InlineField, Next, 1, 1;
+    LDFLDA = 0x7C, "ldflda", InlineField, Next, 1, 1;
+    STFLD = 0x7D, "stfld", InlineField, Next, 2, 0;
+    LDSFLD = 0x7E, "ldsfld", InlineField, Next, 0, 1;
+    LDSFLDA = 0x7F, "ldsflda", InlineField, Next, 0, 1;
+    STSFLD = 0x80, "stsfld", InlineField, Next, 1, 0;
+    STOBJ = 0x81, "stobj", InlineType, Next, 2, 0;
+    CONV_OVF_I1_UN = 0x82, "conv.ovf.i1.un", InlineNone, Next, 1, 1;
+    CONV_OVF_I2_UN = 0x83, "conv.ovf.i2.un", InlineNone, Next, 1, 1;
+    CONV_OVF_I4_UN = 0x84, "conv.ovf.i4.un", InlineNone, Next, 1, 1;
+    CONV_OVF_I8_UN = 0x85, "conv.ovf.i8.un", InlineNone, Next, 1, 1;
+    CONV_OVF_U1_UN = 0x86, "conv.ovf.u1.un", InlineNone, Next, 1, 1;
+    CONV_OVF_U2_UN = 0x87, "conv.ovf.u2.un", InlineNone, Next, 1, 1;
+    CONV_OVF_U4_UN = 0x88, "conv.ovf.u4.un", InlineNone, Next, 1, 1;
+    CONV_OVF_U8_UN = 0x89, "conv.ovf.u8.un", InlineNone, Next, 1, 1;
+    CONV_OVF_I_UN = 0x8A, "conv.ovf.i.un", InlineNone, Next, 1, 1;
+    CONV_OVF_U_UN = 0x8B, "conv.ovf.u.un", InlineNone, Next, 1, 1;
+    BOX = 0x8C, "box", InlineType, Next, 1, 1;
+    NEWARR = 0x8D, "newarr", InlineType, Next, 1, 1;
+    LDLEN = 0x8E, "ldlen", InlineNone, Next, 1, 1;
+    LDELEMA = 0x8F, "ldelema", InlineType, Next, 2, 1;
+    LDELEM_I1 = 0x90, "ldelem.i1", InlineNone, Next, 2, 1;
+    LDELEM_U1 = 0x91, "ldelem.u1", InlineNone, Next, 2, 1;
+    LDELEM_I2 = 0x92, "ldelem.i2", InlineNone, Next, 2, 1;
+    LDELEM_U2 = 0x93, "ldelem.u2", InlineNone, Next, 2, 1;
+    LDELEM_I4 = 0x94, "ldelem.i4", InlineNone, Next, 2, 1;
+    LDELEM_U4 = 0x95, "ldelem.u4", InlineNone, Next, 2, 1;
+    LDELEM_I8 = 0x96, "ldelem.i8", InlineNone, Next, 2, 1;
+    LDELEM_I = 0x97, "ldelem.i", InlineNone, Next, 2, 1;
+    LDELEM_R4 = 0x98, "ldelem.r4", InlineNone, Next, 2, 1;
+    LDELEM_R8 = 0x99, "ldelem.r8", InlineNone, Next, 2, 1;
+    LDELEM_REF = 0x9A, "ldelem.ref", InlineNone, Next, 2, 1;
+    STELEM_I = 0x9B, "stelem.i", InlineNone, Next, 3, 0;
+    STELEM_I1 = 0x9C, "stelem.i1", InlineNone, Next, 3, 0;
+    STELEM_I2 = 0x9D, "stelem.i2", InlineNone, Next, 3, 0;
+    STELEM_I4 = 0x9E, "stelem.i4", InlineNone, Next, 3, 0;
+    STELEM_I8 = 0x9F, "stelem.i8", InlineNone, Next, 3, 0;
+    STELEM_R4 = 0xA0, "stelem.r4", InlineNone, Next, 3, 0;
+    STELEM_R8 = 0xA1, "stelem.r8", InlineNone, Next, 3, 0;
+    STELEM_REF = 0xA2, "stelem.ref", InlineNone, Next, 3, 0;
+    LDELEM = 0xA3, "ldelem", InlineType, Next, 2, 1;
+    STELEM = 0xA4, "stelem", InlineType, Next, 3, 0;
+    UNBOX_ANY = 0xA5, "unbox.any", InlineType, Next, 1, 1;
+    CONV_OVF_I1 = 0xB3, "conv.ovf.i1", InlineNone, Next, 1, 1;
+    CONV_OVF_U1 = 0xB4, "conv.ovf.u1", InlineNone, Next, 1, 1;
+    CONV_OVF_I2 = 0xB5, "conv.ovf.i2", InlineNone, Next, 1, 1;
+    CONV_OVF_U2 = 0xB6, "conv.ovf.u2", InlineNone, Next, 1, 1;
+    CONV_OVF_I4 = 0xB7, "conv.ovf.i4", InlineNone, Next, 1, 1;
+    CONV_OVF_U4 = 0xB8, "conv.ovf.u4", InlineNone, Next, 1, 1;
+    CONV_OVF_I8 = 0xB9, "conv.ovf.i8", InlineNone, Next, 1, 1;
+    CONV_OVF_U8 = 0xBA, "conv.ovf.u8", InlineNone, Next, 1, 1;
+    REFANYVAL = 0xC2, "refanyval", InlineType, Next, 1, 1;
+    CKFINITE = 0xC3, "ckfinite", InlineNone, Next, 1, 1;
+    MKREFANY = 0xC6, "mkrefany", InlineType, Next, 1, 1;
+    LDTOKEN = 0xD0, "ldtoken", InlineTok, Next, 0, 1;
+    CONV_U2 = 0xD1, "conv.u2", InlineNone, Next, 1, 1;
+    CONV_U1 = 0xD2, "conv.u1", InlineNone, Next, 1, 1;
+    CONV_I = 0xD3, "conv.i", InlineNone, Next, 1, 1;
+    CONV_OVF_I = 0xD4, "conv.ovf.i", InlineNone, Next, 1, 1;
+    CONV_OVF_U = 0xD5, "conv.ovf.u", InlineNone, Next, 1, 1;
+    ADD_OVF = 0xD6, "add.ovf", InlineNone, Next, 2, 1;
+    ADD_OVF_UN = 0xD7, "add.ovf.un", InlineNone, Next, 2, 1;
+    MUL_OVF = 0xD8, "mul.ovf", InlineNone, Next, 2, 1;
+    MUL_OVF_UN = 0xD9, "mul.ovf.un", InlineNone, Next, 2, 1;
+    SUB_OVF = 0xDA, "sub.ovf", InlineNone, Next, 2, 1;
+    SUB_OVF_UN = 0xDB, "sub.ovf.un", InlineNone, Next, 2, 1;
+    ENDFINALLY = 0xDC, "endfinally", InlineNone, Return, 0, 0;
+    LEAVE = 0xDD, "leave", InlineBrTarget, Branch, 0, 0;
+    LEAVE_S = 0xDE, "leave.s", ShortInlineBrTarget, Branch, 0, 0;
+    STIND_I = 0xDF, "stind.i", InlineNone, Next, 2, 0;
+    CONV_U = 0xE0, "conv.u", InlineNone, Next, 1, 1;
+    ARGLIST = 0xFE00, "arglist", InlineNone, Next, 0, 1;
+    CEQ = 0xFE01, "ceq", InlineNone, Next, 2, 1;
+    CGT = 0xFE02, "cgt", InlineNone, Next, 2, 1;
+    CGT_UN = 0xFE03, "cgt.un", InlineNone, Next, 2, 1;
+    CLT = 0xFE04, "clt", InlineNone, Next, 2, 1;
+    CLT_UN = 0xFE05, "clt.un", InlineNone, Next, 2, 1;
+    LDFTN = 0xFE06, "ldftn", InlineMethod, Next, 0, 1;
+    LDVIRTFTN = 0xFE07, "ldvirtftn", InlineMethod, Next, 1, 1;
+    LDARG = 0xFE09, "ldarg", InlineVar, Next, 0, 1;
+    LDARGA = 0xFE0A, "ldarga", InlineVar, Next, 0, 1;
+    STARG = 0xFE0B, "starg", InlineVar, Next, 1, 0;
+    LDLOC = 0xFE0C, "ldloc", InlineVar, Next, 0, 1;
+    LDLOCA = 0xFE0D, "ldloca", InlineVar, Next, 0, 1;
+    STLOC = 0xFE0E, "stloc", InlineVar, Next, 1, 0;
+    LOCALLOC = 0xFE0F, "localloc", InlineNone, Next, 1, 1;
+    ENDFILTER = 0xFE11, "endfilter", InlineNone, Return, 1, 0;
+    UNALIGNED = 0xFE12, "unaligned.", ShortInlineI, Meta, 0, 0;
+    VOLATILE = 0xFE13, "volatile.", InlineNone, Meta, 0, 0;
+    TAIL = 0xFE14, "tail.", InlineNone, Meta, 0, 0;
+    INITOBJ = 0xFE15, "initobj", InlineType, Next, 1, 0;
+    CONSTRAINED = 0xFE16, "constrained.", InlineType, Meta, 0, 0;
+    CPBLK = 0xFE17, "cpblk", InlineNone, Next, 3, 0;
+    INITBLK = 0xFE18, "initblk", InlineNone, Next, 3, 0;
+    NO = 0xFE19, "no.", ShortInlineI, Meta, 0, 0;
+    RETHROW = 0xFE1A, "rethrow", InlineNone, Throw, 0, 0;
+    SIZEOF = 0xFE1C, "sizeof", InlineType, Next, 0, 1;
+    REFANYTYPE = 0xFE1D, "refanytype", InlineNone, Next, 1, 1;
+    READONLY = 0xFE1E, "readonly.", InlineNone, Meta, 0, 0;
+}
+
+impl OpCode {
+    fn info(self) -> Option<&'static OpCodeInfo> {
+        OPCODES
+            .binary_search_by_key(&self.0, |info| info.value)
+            .ok()
+            .map(|index| &OPCODES[index])
+    }
+
+    /// Returns `true` if this is an opcode of the instruction set.
+    pub fn is_valid(self) -> bool {
+        self.info().is_some()
+    }
+
+    /// Returns the opcode's mnemonic, e.g. `ldc.i4.s`.
+    pub fn name(self) -> Option<&'static str> {
+        self.info().map(|info| info.name)
+    }
+
+    /// Returns the kind of operand that follows the opcode.
+    pub fn operand_type(self) -> OperandType {
+        self.info()
+            .map_or(OperandType::InlineNone, |info| info.operand)
+    }
+
+    /// Returns how the instruction affects control flow.
+    pub fn flow_control(self) -> FlowControl {
+        self.info().map_or(FlowControl::Next, |info| info.flow)
+    }
+
+    /// Returns the number of stack slots the instruction pops, or `None` if
+    /// that depends on a signature (calls and `ret`).
+    pub fn pops(self) -> Option<u32> {
+        self.info().and_then(|info| u32::try_from(info.pops).ok())
+    }
+
+    /// Returns the number of stack slots the instruction pushes, or `None`
+    /// if that depends on a signature (calls).
+    pub fn pushes(self) -> Option<u32> {
+        self.info().and_then(|info| u32::try_from(info.pushes).ok())
+    }
+
+    /// Returns the size of the opcode itself: 1 or 2 bytes.
+    pub fn size(self) -> usize {
+        if self.0 >> 8 == 0xFE { 2 } else { 1 }
+    }
+
+    /// Returns the long form of a short branch (`br.s` → `br`), or the
+    /// opcode itself.
+    pub fn long_form(self) -> OpCode {
+        match self {
+            OpCode(0x2B..=0x37) => OpCode(self.0 + 0x0D),
+            OpCode::LEAVE_S => OpCode::LEAVE,
+            _ => self,
+        }
+    }
+
+    /// Returns the short form of a branch (`br` → `br.s`), or the opcode
+    /// itself.
+    pub fn short_form(self) -> OpCode {
+        match self {
+            OpCode(0x38..=0x44) => OpCode(self.0 - 0x0D),
+            OpCode::LEAVE => OpCode::LEAVE_S,
+            _ => self,
+        }
+    }
+}
+
+impl fmt::Debug for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "OpCode({name})"),
+            None => write!(f, "OpCode({:#x})", self.0),
+        }
+    }
+}
+
+impl fmt::Display for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "<invalid {:#x}>", self.0),
+        }
+    }
+}
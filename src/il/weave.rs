@@ -0,0 +1,742 @@
+//! Static IL weaving: rewriting the method bodies of an assembly on disk.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use super::body::{MethodBody, method_code_size};
+use super::opcode::OpCode;
+use crate::assembly_identity::{AssemblyContentType, AssemblyIdentity, AssemblyPublicKey};
+use crate::error::{Error, Result};
+use crate::reader::{
+    AssemblyRefRow, COMIMAGE_FLAGS_ILONLY, COMIMAGE_FLAGS_NATIVE_ENTRYPOINT, Cursor,
+    IMAGE_CEE_CS_CALLCONV_EXPLICITTHIS, IMAGE_DEBUG_TYPE_EMBEDDED_PORTABLE_PDB,
+    IMAGE_DEBUG_TYPE_PDBCHECKSUM, LocalScopeRow, ManifestResourceRow, MemberRefRow, MetadataReader,
+    MethodDefRow, MethodSequencePoints, MethodSig, PeImage, SequencePoint, StandAloneSigRow,
+    TableId, TypeRefRow, TypeSig, TypeSpecRow, token_rid,
+};
+use crate::strong_name::{StrongNameKeyPair, is_ecma_public_key};
+use crate::writer::{MetadataBuilder, PeBuilder, write_compressed_u32};
+
+/// `MethodImplAttributes.CodeTypeMask`; 0 is IL.
+const METHOD_IMPL_CODE_TYPE_MASK: u16 = 0x0003;
+/// `AssemblyFlags.PublicKey`.
+const ASSEMBLY_FLAGS_PUBLIC_KEY: u32 = 0x0001;
+/// `AssemblyFlags.Retargetable`.
+const ASSEMBLY_FLAGS_RETARGETABLE: u32 = 0x0100;
+/// `AssemblyFlags.ContentType` value for Windows Runtime assemblies.
+const ASSEMBLY_FLAGS_WINDOWS_RUNTIME: u32 = 0x0200;
+/// The first Portable PDB table; the tables below it are the type system's.
+const FIRST_PDB_TABLE: u8 = TableId::Document as u8;
+/// Token type of `#US` heap entries.
+const MD_STRING: u32 = 0x7000_0000;
+const LOCAL_SIG: u8 = 0x07;
+
+/// Rewrites the bodies of the methods an [`AssemblyWeaver`] offers it.
+///
+/// ```
+/// use mscoree::{
+///     AssemblyIdentity, Error, MethodBody, MethodWeaver, OpCode, Operand, WeaveContext,
+/// };
+///
+/// /// Calls `static void Tracing.Trace.Enter()` at the start of every method.
+/// struct Tracer;
+///
+/// impl MethodWeaver for Tracer {
+///     fn weave(
+///         &mut self,
+///         context: &mut WeaveContext<'_>,
+///         _method: u32,
+///         body: &mut MethodBody,
+///     ) -> Result<(), Error> {
+///         let scope = context.assembly_ref(&AssemblyIdentity::new("Tracing"))?;
+///         let class = context.type_ref(scope, "Tracing", "Trace");
+///         let enter = context.member_ref(class, "Enter", &[0x00, 0x00, 0x01]);
+///         let call = body.instruction(OpCode::CALL, Operand::Token(enter));
+///         body.instructions.insert(0, call);
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait MethodWeaver {
+    /// Returns `true` if `method`, a `MethodDef` rid, should be woven. Only
+    /// methods with IL bodies are offered. Selects every method by default.
+    fn select(&mut self, metadata: &MetadataReader, method: u32) -> bool {
+        let _ = (metadata, method);
+        true
+    }
+
+    /// Rewrites the body of `method`. Tokens the new code needs come from
+    /// `context`; `max_stack` is recomputed when the assembly is written.
+    fn weave(
+        &mut self,
+        context: &mut WeaveContext<'_>,
+        method: u32,
+        body: &mut MethodBody,
+    ) -> Result<()>;
+}
+
+/// Gives a [`MethodWeaver`] the assembly's metadata and adds the references
+/// its code needs. Each `*_ref`-style method returns an existing row when
+/// there is one, so asking twice gives the same token.
+pub struct WeaveContext<'a> {
+    metadata: &'a MetadataReader,
+    builder: &'a mut MetadataBuilder,
+}
+
+impl<'a> WeaveContext<'a> {
+    /// Returns the assembly's metadata as it was read. Rows added while
+    /// weaving are only in [`builder`](Self::builder).
+    pub fn metadata(&self) -> &'a MetadataReader {
+        self.metadata
+    }
+
+    /// Returns the metadata being built, for changes the other methods do
+    /// not cover.
+    pub fn builder(&mut self) -> &mut MetadataBuilder {
+        self.builder
+    }
+
+    /// Returns the `AssemblyRef` token for `identity`, adding a row if no
+    /// reference has its simple name.
+    pub fn assembly_ref(&mut self, identity: &AssemblyIdentity) -> Result<u32> {
+        for rid in 1..=self.builder.row_count(TableId::AssemblyRef) {
+            let row = self.builder.row::<AssemblyRefRow>(rid)?;
+            if heap_string(self.builder.strings_heap(), row.name)
+                .eq_ignore_ascii_case(identity.name.as_bytes())
+            {
+                return Ok(TableId::AssemblyRef.token(rid));
+            }
+        }
+        let version = identity.version.unwrap_or_default();
+        let mut flags = 0;
+        let public_key_or_token = match &identity.public_key {
+            Some(AssemblyPublicKey::PublicKey(key)) => {
+                flags |= ASSEMBLY_FLAGS_PUBLIC_KEY;
+                self.builder.blob(key)
+            }
+            Some(AssemblyPublicKey::Token(token)) => self.builder.blob(token.as_bytes()),
+            Some(AssemblyPublicKey::None) | None => 0,
+        };
+        if identity.retargetable == Some(true) {
+            flags |= ASSEMBLY_FLAGS_RETARGETABLE;
+        }
+        if identity.content_type == Some(AssemblyContentType::WindowsRuntime) {
+            flags |= ASSEMBLY_FLAGS_WINDOWS_RUNTIME;
+        }
+        let culture = match identity.culture.as_deref() {
+            None | Some("neutral") => 0,
+            Some(culture) => self.builder.string(culture),
+        };
+        let row = AssemblyRefRow {
+            major_version: version.major,
+            minor_version: version.minor,
+            build_number: version.build.unwrap_or(0),
+            revision_number: version.revision.unwrap_or(0),
+            flags,
+            public_key_or_token,
+            name: self.builder.string(&identity.name),
+            culture,
+            hash_value: 0,
+        };
+        Ok(TableId::AssemblyRef.token(self.builder.add(&row)))
+    }
+
+    /// Returns a `TypeRef` token for a type in `resolution_scope`: an
+    /// `AssemblyRef`, `ModuleRef`, `Module` or, for a nested type, the
+    /// enclosing `TypeRef` token.
+    pub fn type_ref(&mut self, resolution_scope: u32, namespace: &str, name: &str) -> u32 {
+        for rid in 1..=self.builder.row_count(TableId::TypeRef) {
+            let Ok(row) = self.builder.row::<TypeRefRow>(rid) else {
+                continue;
+            };
+            let strings = self.builder.strings_heap();
+            if row.resolution_scope == resolution_scope
+                && heap_string(strings, row.name) == name.as_bytes()
+                && heap_string(strings, row.namespace) == namespace.as_bytes()
+            {
+                return TableId::TypeRef.token(rid);
+            }
+        }
+        let row = TypeRefRow {
+            resolution_scope,
+            name: self.builder.string(name),
+            namespace: self.builder.string(namespace),
+        };
+        TableId::TypeRef.token(self.builder.add(&row))
+    }
+
+    /// Returns a `MemberRef` token for a method or field of `class` (a
+    /// `TypeRef`, `TypeSpec`, `TypeDef`, `ModuleRef` or `MethodDef` token)
+    /// with the given signature blob.
+    pub fn member_ref(&mut self, class: u32, name: &str, signature: &[u8]) -> u32 {
+        for rid in 1..=self.builder.row_count(TableId::MemberRef) {
+            let Ok(row) = self.builder.row::<MemberRefRow>(rid) else {
+                continue;
+            };
+            if row.class == class
+                && heap_string(self.builder.strings_heap(), row.name) == name.as_bytes()
+                && self.builder.blob_at(row.signature).ok() == Some(signature)
+            {
+                return TableId::MemberRef.token(rid);
+            }
+        }
+        let row = MemberRefRow {
+            class,
+            name: self.builder.string(name),
+            signature: self.builder.blob(signature),
+        };
+        TableId::MemberRef.token(self.builder.add(&row))
+    }
+
+    /// Returns a `TypeSpec` token for a type signature blob, such as a
+    /// generic instantiation.
+    pub fn type_spec(&mut self, signature: &[u8]) -> u32 {
+        let signature = self.builder.blob(signature);
+        let existing = (1..=self.builder.row_count(TableId::TypeSpec)).find(|&rid| {
+            self.builder
+                .row::<TypeSpecRow>(rid)
+                .is_ok_and(|row| row.signature == signature)
+        });
+        let rid = existing.unwrap_or_else(|| self.builder.add(&TypeSpecRow { signature }));
+        TableId::TypeSpec.token(rid)
+    }
+
+    /// Returns a `StandAloneSig` token for a local variable signature or a
+    /// `calli` method signature.
+    pub fn standalone_sig(&mut self, signature: &[u8]) -> u32 {
+        let signature = self.builder.blob(signature);
+        let existing = (1..=self.builder.row_count(TableId::StandAloneSig)).find(|&rid| {
+            self.builder
+                .row::<StandAloneSigRow>(rid)
+                .is_ok_and(|row| row.signature == signature)
+        });
+        let rid = existing.unwrap_or_else(|| self.builder.add(&StandAloneSigRow { signature }));
+        TableId::StandAloneSig.token(rid)
+    }
+
+    /// Returns the `mdString` token of a string literal, for `ldstr`.
+    pub fn user_string(&mut self, value: &str) -> u32 {
+        MD_STRING | self.builder.user_string(value)
+    }
+
+    /// Adds a local variable of type `ty`, an encoded type signature, to
+    /// `body` and returns its index. The body gets a new local signature;
+    /// `init_locals` is left as it is.
+    pub fn add_local(&mut self, body: &mut MethodBody, ty: &[u8]) -> Result<u16> {
+        let (count, types) = match body.local_var_sig {
+            0 => (0, Vec::new()),
+            token => {
+                let rid = match TableId::from_token(token) {
+                    Some((TableId::StandAloneSig, rid)) => rid,
+                    _ => {
+                        return Err(Error::BadMethodBody(format!(
+                            "local signature token {token:#010x} is not a StandAloneSig"
+                        )));
+                    }
+                };
+                let row = self.builder.row::<StandAloneSigRow>(rid)?;
+                let blob = self.builder.blob_at(row.signature)?;
+                let mut cur = Cursor::new(blob);
+                if cur.u8()? != LOCAL_SIG {
+                    return Err(Error::BadSignature(format!(
+                        "signature {token:#010x} is not a local variable signature"
+                    )));
+                }
+                (cur.compressed_u32()?, cur.rest().to_vec())
+            }
+        };
+        let index = u16::try_from(count)
+            .ok()
+            .filter(|&index| index < u16::MAX - 1)
+            .ok_or_else(|| Error::BadMethodBody("too many local variables".into()))?;
+        let mut signature = vec![LOCAL_SIG];
+        write_compressed_u32(&mut signature, count + 1);
+        signature.extend_from_slice(&types);
+        signature.extend_from_slice(ty);
+        body.local_var_sig = self.standalone_sig(&signature);
+        Ok(index)
+    }
+}
+
+/// The files an [`AssemblyWeaver`] writes.
+#[derive(Debug, Clone)]
+pub struct WovenAssembly {
+    /// The assembly image.
+    pub image: Vec<u8>,
+    /// The Portable PDB, if one was given to the weaver.
+    pub pdb: Option<Vec<u8>>,
+}
+
+/// Weaves IL into the methods of an IL-only assembly and writes it back.
+///
+/// The weaver reads the assembly once; each [`weave`](Self::weave) pass
+/// offers its methods to a [`MethodWeaver`], and [`write`](Self::write)
+/// produces the new image. Methods that were not woven keep their bodies
+/// byte for byte, as do field data, managed resources and Win32 resources.
+///
+/// With a Portable PDB from [`set_pdb`](Self::set_pdb), the PDB is written
+/// out too: sequence points and scopes of woven methods follow their
+/// instructions to the new offsets, and inserted code is marked hidden so
+/// that debuggers step over it. Embedded PDBs are dropped, as the weaver
+/// cannot decompress them.
+///
+/// The image is re-signed when a key is set with
+/// [`set_strong_name_key`](Self::set_strong_name_key). Otherwise its
+/// strong-name signature area is kept but empty, as for a delay-signed
+/// assembly.
+pub struct AssemblyWeaver {
+    image: PeImage,
+    metadata: MetadataReader,
+    builder: MetadataBuilder,
+    bodies: BTreeMap<u32, MethodBody>,
+    pdb: Option<MetadataReader>,
+    key: Option<StrongNameKeyPair>,
+}
+
+impl AssemblyWeaver {
+    /// Creates a weaver for an IL-only image.
+    pub fn new(image: PeImage) -> Result<Self> {
+        let cor = image.cor_header().ok_or(Error::NotManaged)?;
+        if cor.flags & COMIMAGE_FLAGS_ILONLY == 0
+            || cor.flags & COMIMAGE_FLAGS_NATIVE_ENTRYPOINT != 0
+        {
+            return Err(Error::BadImageFormat(
+                "only IL-only images can be woven".into(),
+            ));
+        }
+        let metadata = image.metadata()?;
+        let builder = MetadataBuilder::from_reader(&metadata)?;
+        Ok(Self {
+            image,
+            metadata,
+            builder,
+            bodies: BTreeMap::new(),
+            pdb: None,
+            key: None,
+        })
+    }
+
+    /// Opens the image at `path` for weaving.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(PeImage::open(path)?)
+    }
+
+    /// Returns the image's metadata as it was read.
+    pub fn metadata(&self) -> &MetadataReader {
+        &self.metadata
+    }
+
+    /// Sets the Portable PDB of the image, to be updated and written along
+    /// with it. The PDB must match the image's CodeView record, if it has
+    /// one.
+    pub fn set_pdb(&mut self, pdb: Vec<u8>) -> Result<()> {
+        let pdb = MetadataReader::from_bytes(pdb)?;
+        let stream = pdb
+            .pdb_stream()
+            .ok_or_else(|| Error::BadMetadata("PDB has no #Pdb stream".into()))?;
+        if let Some(codeview) = self.image.codeview()?
+            && codeview.is_portable
+            && codeview.portable_pdb_id() != stream.id
+        {
+            return Err(Error::BadMetadata(
+                "PDB id does not match the image's CodeView record".into(),
+            ));
+        }
+        self.pdb = Some(pdb);
+        Ok(())
+    }
+
+    /// Sets the key to strong-name sign the woven assembly with. An
+    /// assembly with a different public key takes the key's.
+    pub fn set_strong_name_key(&mut self, key: Option<StrongNameKeyPair>) {
+        self.key = key;
+    }
+
+    /// Offers every method with an IL body to `weaver` and returns how many
+    /// it wove. Methods woven by an earlier pass are offered with their
+    /// woven bodies.
+    pub fn weave(&mut self, weaver: &mut dyn MethodWeaver) -> Result<usize> {
+        let mut woven = 0;
+        for rid in 1..=self.metadata.row_count(TableId::MethodDef) {
+            let row = self.metadata.row::<MethodDefRow>(rid)?;
+            if row.rva == 0 || row.impl_flags & METHOD_IMPL_CODE_TYPE_MASK != 0 {
+                continue;
+            }
+            if !weaver.select(&self.metadata, rid) {
+                continue;
+            }
+            let mut body = match self.bodies.remove(&rid) {
+                Some(body) => body,
+                None => self
+                    .image
+                    .method_body(&self.metadata, rid)?
+                    .expect("methods with an RVA have a body"),
+            };
+            let mut context = WeaveContext {
+                metadata: &self.metadata,
+                builder: &mut self.builder,
+            };
+            weaver.weave(&mut context, rid, &mut body)?;
+            self.bodies.insert(rid, body);
+            woven += 1;
+        }
+        Ok(woven)
+    }
+
+    /// Writes the woven assembly and, if one was set, its PDB.
+    pub fn write(&self) -> Result<WovenAssembly> {
+        let image = &self.image;
+        let metadata = &self.metadata;
+        let cor = image.cor_header().ok_or(Error::NotManaged)?;
+        let mut builder = self.builder.clone();
+        let mut pe = PeBuilder::from_image(image)?;
+        pe.set_entry_point(cor.entry_point);
+
+        // Method bodies. Bodies several methods share are copied once.
+        let mut copied = HashMap::new();
+        let mut layouts = BTreeMap::new();
+        for rid in 1..=builder.row_count(TableId::MethodDef) {
+            let rva = builder.row::<MethodDefRow>(rid)?.rva;
+            if rva == 0 {
+                continue;
+            }
+            let new_rva = match self.bodies.get(&rid) {
+                Some(body) => {
+                    let mut body = body.clone();
+                    let signature = MethodSig::parse(
+                        builder.blob_at(builder.row::<MethodDefRow>(rid)?.signature)?,
+                    )?;
+                    let returns_value =
+                        !matches!(signature.return_type.strip_modifiers(), TypeSig::Void);
+                    let max_stack = body.compute_max_stack(returns_value, |opcode, token| {
+                        call_stack_effect(&builder, opcode, token)
+                    })?;
+                    body.max_stack = body.max_stack.max(max_stack);
+                    let original = image
+                        .method_body_bytes(metadata, rid)?
+                        .expect("methods with an RVA have a body");
+                    layouts.insert(
+                        rid,
+                        WovenLayout {
+                            original_code_size: method_code_size(original)?,
+                            offsets: body.instruction_offsets()?,
+                            original_offsets: body
+                                .instructions
+                                .iter()
+                                .map(|i| i.original_offset)
+                                .collect(),
+                            local_var_sig: body.local_var_sig,
+                        },
+                    );
+                    pe.add_method_body(&body.to_bytes()?)
+                }
+                None => match copied.get(&rva) {
+                    Some(&new_rva) => new_rva,
+                    None => {
+                        let bytes = image
+                            .method_body_bytes(metadata, rid)?
+                            .expect("methods with an RVA have a body");
+                        let new_rva = pe.add_method_body(bytes);
+                        copied.insert(rva, new_rva);
+                        new_rva
+                    }
+                },
+            };
+            builder.set_column(TableId::MethodDef, rid, 0, new_rva)?;
+        }
+
+        for rid in 1..=builder.row_count(TableId::FieldRva) {
+            let field = builder.row_values(TableId::FieldRva, rid)?[1];
+            let data = image.field_data(metadata, field)?.ok_or_else(|| {
+                Error::BadMetadata(format!(
+                    "size of the data of field {:#010x} is unknown",
+                    TableId::Field.token(field)
+                ))
+            })?;
+            let rva = pe.add_field_data(data);
+            builder.set_column(TableId::FieldRva, rid, 0, rva)?;
+        }
+
+        let mut resources = HashMap::new();
+        for rid in 1..=builder.row_count(TableId::ManifestResource) {
+            let row = builder.row::<ManifestResourceRow>(rid)?;
+            if token_rid(row.implementation) != 0 {
+                continue;
+            }
+            let offset = match resources.get(&row.offset) {
+                Some(&offset) => offset,
+                None => {
+                    let offset = pe.add_resource(image.managed_resource(row.offset)?);
+                    resources.insert(row.offset, offset);
+                    offset
+                }
+            };
+            builder.set_column(TableId::ManifestResource, rid, 0, offset)?;
+        }
+
+        match &self.key {
+            Some(key) => {
+                if builder.row_count(TableId::Assembly) == 0 {
+                    return Err(Error::BadStrongName(
+                        "image has no assembly manifest to sign".into(),
+                    ));
+                }
+                let public_key = key.public_key_blob();
+                let current = metadata.assembly_public_key()?;
+                let keeps_key = current == Some(&public_key[..])
+                    || current.is_some_and(is_ecma_public_key)
+                    || metadata.assembly_signature_key()?.is_some();
+                if !keeps_key {
+                    let blob = builder.blob(&public_key);
+                    let flags = builder.row_values(TableId::Assembly, 1)?[5];
+                    builder.set_column(TableId::Assembly, 1, 6, blob)?;
+                    builder.set_column(
+                        TableId::Assembly,
+                        1,
+                        5,
+                        flags | ASSEMBLY_FLAGS_PUBLIC_KEY,
+                    )?;
+                }
+                pe.set_strong_name_signature_size(key.signature_len() as u32);
+            }
+            None => pe.set_strong_name_signature_size(
+                image
+                    .strong_name_signature_range()
+                    .map_or(0, |range| range.len() as u32),
+            ),
+        }
+        builder.sort_tables()?;
+
+        let pdb = match &self.pdb {
+            Some(pdb) => Some(rewrite_pdb(pdb, &builder, &layouts)?),
+            None => None,
+        };
+        for entry in image.debug_directory()? {
+            let data = image.debug_data(&entry).ok_or_else(|| {
+                Error::BadImageFormat("debug directory data is outside the image".into())
+            })?;
+            match entry.kind {
+                IMAGE_DEBUG_TYPE_EMBEDDED_PORTABLE_PDB => continue,
+                IMAGE_DEBUG_TYPE_PDBCHECKSUM if pdb.is_some() => {
+                    let (pdb, id_offset) = pdb.as_ref().expect("checked above");
+                    match pdb_checksum(data, pdb, *id_offset) {
+                        Some(checksum) => pe.add_debug_entry(entry, &checksum),
+                        None => continue,
+                    }
+                }
+                _ => pe.add_debug_entry(entry, data),
+            }
+        }
+
+        pe.set_metadata(builder.to_bytes()?);
+        let mut bytes = pe.to_bytes()?;
+        if let Some(key) = &self.key {
+            bytes = PeImage::from_bytes(bytes)?.strong_name_sign(key)?;
+        }
+        Ok(WovenAssembly {
+            image: bytes,
+            pdb: pdb.map(|(pdb, _)| pdb),
+        })
+    }
+}
+
+/// Where the instructions of a woven method ended up.
+struct WovenLayout {
+    original_code_size: u32,
+    /// New offset of each instruction, then the new code size.
+    offsets: Vec<u32>,
+    original_offsets: Vec<Option<u32>>,
+    local_var_sig: u32,
+}
+
+impl WovenLayout {
+    /// Maps an offset of the original code to the new code: the new offset
+    /// of the first surviving instruction at or after it.
+    fn map_offset(&self, offset: u32) -> u32 {
+        let code_size = *self.offsets.last().unwrap_or(&0);
+        if offset >= self.original_code_size {
+            return code_size;
+        }
+        self.original_offsets
+            .iter()
+            .zip(&self.offsets)
+            .filter_map(|(original, &new)| original.filter(|&o| o >= offset).map(|o| (o, new)))
+            .min()
+            .map_or(code_size, |(_, new)| new)
+    }
+
+    /// Moves sequence points to the new offsets. Inserted code gets a
+    /// hidden point, and original code after it resumes the point that
+    /// covered it.
+    fn map_sequence_points(&self, points: &[SequencePoint]) -> Vec<SequencePoint> {
+        let Some(first) = points.first() else {
+            return Vec::new();
+        };
+        #[derive(PartialEq)]
+        enum Current {
+            None,
+            Hidden,
+            Point(usize),
+        }
+        let mut current = Current::None;
+        let mut document = first.document;
+        let mut mapped = Vec::new();
+        for (original, &offset) in self.original_offsets.iter().zip(&self.offsets) {
+            match original {
+                Some(original) => {
+                    let Some(index) = points.iter().rposition(|p| p.offset <= *original) else {
+                        continue;
+                    };
+                    if current != Current::Point(index) {
+                        document = points[index].document;
+                        mapped.push(SequencePoint {
+                            offset,
+                            ..points[index]
+                        });
+                        current = Current::Point(index);
+                    }
+                }
+                None => {
+                    if current != Current::Hidden {
+                        mapped.push(SequencePoint::hidden(document, offset));
+                        current = Current::Hidden;
+                    }
+                }
+            }
+        }
+        mapped
+    }
+}
+
+/// Returns the stack slots a call instruction pops and pushes.
+fn call_stack_effect(builder: &MetadataBuilder, opcode: OpCode, token: u32) -> Result<(u32, u32)> {
+    let signature_of = |token: u32| -> Result<&[u8]> {
+        let (table, rid) =
+            TableId::from_token(token).ok_or_else(|| bad_call_token(opcode, token))?;
+        let blob = match table {
+            TableId::MethodDef => builder.row::<MethodDefRow>(rid)?.signature,
+            TableId::MemberRef => builder.row::<MemberRefRow>(rid)?.signature,
+            TableId::StandAloneSig => builder.row::<StandAloneSigRow>(rid)?.signature,
+            TableId::MethodSpec => {
+                let method = builder.row_values(TableId::MethodSpec, rid)?[0];
+                return match TableId::from_token(method) {
+                    Some((TableId::MethodDef, rid)) => {
+                        builder.blob_at(builder.row::<MethodDefRow>(rid)?.signature)
+                    }
+                    Some((TableId::MemberRef, rid)) => {
+                        builder.blob_at(builder.row::<MemberRefRow>(rid)?.signature)
+                    }
+                    _ => Err(bad_call_token(opcode, token)),
+                };
+            }
+            _ => return Err(bad_call_token(opcode, token)),
+        };
+        builder.blob_at(blob)
+    };
+    let signature = MethodSig::parse(signature_of(token)?)?;
+    let explicit_this = signature.calling_convention & IMAGE_CEE_CS_CALLCONV_EXPLICITTHIS != 0;
+    let this = (signature.has_this() && !explicit_this) as u32;
+    let params = signature.params.len() as u32;
+    let returns = !matches!(signature.return_type.strip_modifiers(), TypeSig::Void) as u32;
+    Ok(match opcode {
+        OpCode::NEWOBJ => (params, 1),
+        OpCode::CALLI => (params + this + 1, returns),
+        _ => (params + this, returns),
+    })
+}
+
+fn bad_call_token(opcode: OpCode, token: u32) -> Error {
+    Error::BadMethodBody(format!(
+        "{opcode} has an invalid method token {token:#010x}"
+    ))
+}
+
+/// Returns the NUL-terminated string at `index` of a `#Strings` heap.
+fn heap_string(heap: &[u8], index: u32) -> &[u8] {
+    let rest = heap.get(index as usize..).unwrap_or(&[]);
+    let len = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+    &rest[..len]
+}
+
+/// Updates a Portable PDB for the woven image and returns it with the
+/// offset of its id.
+fn rewrite_pdb(
+    pdb: &MetadataReader,
+    image: &MetadataBuilder,
+    layouts: &BTreeMap<u32, WovenLayout>,
+) -> Result<(Vec<u8>, usize)> {
+    let mut builder = MetadataBuilder::from_reader(pdb)?;
+    for (&rid, layout) in layouts {
+        if rid > builder.row_count(TableId::MethodDebugInformation) {
+            continue;
+        }
+        let original = pdb.sequence_points(rid)?;
+        if !original.points.is_empty() {
+            let points = MethodSequencePoints {
+                local_signature: match TableId::from_token(layout.local_var_sig) {
+                    Some((TableId::StandAloneSig, rid)) => rid,
+                    _ => 0,
+                },
+                points: layout.map_sequence_points(&original.points),
+            };
+            let (document, blob) = points.to_blob()?;
+            let blob = builder.blob(&blob);
+            builder.set_row(TableId::MethodDebugInformation, rid, vec![document, blob])?;
+        }
+    }
+    for rid in 1..=builder.row_count(TableId::LocalScope) {
+        let row = builder.row::<LocalScopeRow>(rid)?;
+        let Some(layout) = layouts.get(&row.method) else {
+            continue;
+        };
+        let start = match row.start_offset {
+            0 => 0,
+            start => layout.map_offset(start),
+        };
+        let end = layout.map_offset(row.start_offset.saturating_add(row.length));
+        builder.set_column(TableId::LocalScope, rid, 4, start)?;
+        builder.set_column(TableId::LocalScope, rid, 5, end.saturating_sub(start))?;
+    }
+
+    let mut stream = pdb.pdb_stream().cloned().expect("checked by set_pdb");
+    stream.referenced_type_system_tables = 0;
+    stream.type_system_table_rows = [0; 64];
+    for &table in TableId::ALL {
+        let rows = image.row_count(table);
+        if (table as u8) < FIRST_PDB_TABLE && rows != 0 {
+            stream.referenced_type_system_tables |= 1u64 << table as u8;
+            stream.type_system_table_rows[table as usize] = rows;
+        }
+    }
+    builder.set_pdb_stream(Some(stream));
+
+    let bytes = builder.to_bytes()?;
+    let written = MetadataReader::from_bytes(bytes)?;
+    let id_offset = written
+        .streams()
+        .iter()
+        .find(|s| s.name == "#Pdb")
+        .map(|s| s.offset as usize)
+        .expect("the builder writes a #Pdb stream");
+    Ok((written.data().to_vec(), id_offset))
+}
+
+/// Recomputes a `PdbChecksum` debug entry for `pdb`: the hash, with the
+/// entry's algorithm, of the PDB with its id zeroed. Returns `None` for
+/// an unknown algorithm.
+fn pdb_checksum(entry: &[u8], pdb: &[u8], id_offset: usize) -> Option<Vec<u8>> {
+    let name_len = entry.iter().position(|&b| b == 0)?;
+    let mut zeroed = pdb.to_vec();
+    zeroed.get_mut(id_offset..id_offset + 20)?.fill(0);
+    let hash = match &entry[..name_len] {
+        b"SHA256" => Sha256::digest(&zeroed).to_vec(),
+        b"SHA384" => Sha384::digest(&zeroed).to_vec(),
+        b"SHA512" => Sha512::digest(&zeroed).to_vec(),
+        _ => return None,
+    };
+    let mut out = entry[..=name_len].to_vec();
+    out.extend_from_slice(&hash);
+    Some(out)
+}
@@ -5,6 +5,7 @@ mod clr_data;
 mod clr_data_target;
 mod clr_debugging;
 mod clr_metahost;
+mod clr_metahost_policy;
 mod clr_runtime_host;
 mod clr_runtime_info;
 mod clr_strong_name;
@@ -47,6 +48,7 @@ pub use clr_data::*;
 pub use clr_data_target::*;
 pub use clr_debugging::*;
 pub use clr_metahost::*;
+pub use clr_metahost_policy::*;
 pub use clr_runtime_host::*;
 pub use clr_runtime_info::*;
 pub use clr_strong_name::*;
@@ -0,0 +1,207 @@
+//! ICLRMetaHostPolicy interface definition.
+
+use std::ops::BitOr;
+use std::path::Path;
+
+use windows::Win32::Foundation::ERROR_INSUFFICIENT_BUFFER;
+use windows::Win32::System::Com::IStream;
+use windows::core::{
+    GUID, HRESULT, HSTRING, IUnknown, IUnknown_Vtbl, Interface, PCWSTR, PWSTR, Result, interface,
+};
+
+use super::clr_runtime_info::ICLRRuntimeInfo;
+
+/// Policy flags for `ICLRMetaHostPolicy::GetRequestedRuntime`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct METAHOST_POLICY_FLAGS(pub u32);
+
+impl METAHOST_POLICY_FLAGS {
+    /// Bind to the runtime the binary was built for (the default).
+    pub const METAHOST_POLICY_HIGHCOMPAT: Self = Self(0x00);
+    /// Use the latest installed runtime if the requested one is missing.
+    pub const METAHOST_POLICY_APPLY_UPGRADE_POLICY: Self = Self(0x08);
+    /// Apply the policy used when the binary is launched as an executable.
+    pub const METAHOST_POLICY_EMULATE_EXE_LAUNCH: Self = Self(0x10);
+    /// Show an error dialog if no runtime can be found.
+    pub const METAHOST_POLICY_SHOW_ERROR_DIALOG: Self = Self(0x20);
+    /// Use the process executable instead of `pwzBinary`.
+    pub const METAHOST_POLICY_USE_PROCESS_IMAGE_PATH: Self = Self(0x40);
+    /// Check that the `sku` of `<supportedRuntime>` is installed.
+    pub const METAHOST_POLICY_ENSURE_SKU_SUPPORTED: Self = Self(0x80);
+    /// Ignore the process error mode when showing the error dialog.
+    pub const METAHOST_POLICY_IGNORE_ERROR_MODE: Self = Self(0x1000);
+
+    /// Returns `true` if all flags in `other` are set.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for METAHOST_POLICY_FLAGS {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Configuration flags returned by `ICLRMetaHostPolicy::GetRequestedRuntime`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct METAHOST_CONFIG_FLAGS(pub u32);
+
+impl METAHOST_CONFIG_FLAGS {
+    /// Mask of the `useLegacyV2RuntimeActivationPolicy` bits.
+    pub const METAHOST_CONFIG_FLAGS_LEGACY_V2_ACTIVATION_POLICY_MASK: Self = Self(0x3);
+    /// `useLegacyV2RuntimeActivationPolicy` is not set.
+    pub const METAHOST_CONFIG_FLAGS_LEGACY_V2_ACTIVATION_POLICY_UNSET: Self = Self(0x0);
+    /// `useLegacyV2RuntimeActivationPolicy="true"`.
+    pub const METAHOST_CONFIG_FLAGS_LEGACY_V2_ACTIVATION_POLICY_TRUE: Self = Self(0x1);
+    /// `useLegacyV2RuntimeActivationPolicy="false"`.
+    pub const METAHOST_CONFIG_FLAGS_LEGACY_V2_ACTIVATION_POLICY_FALSE: Self = Self(0x2);
+
+    /// Returns the `useLegacyV2RuntimeActivationPolicy` setting, if the
+    /// configuration file specifies it.
+    pub fn legacy_v2_activation_policy(self) -> Option<bool> {
+        match self.0 & Self::METAHOST_CONFIG_FLAGS_LEGACY_V2_ACTIVATION_POLICY_MASK.0 {
+            0x1 => Some(true),
+            0x2 => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// ICLRMetaHostPolicy interface for policy-based runtime selection (.NET 4.0+).
+///
+/// Obtain an instance using `CLRCreateInstance` with `CLSID_CLRMetaHostPolicy`.
+#[interface("E2190695-77B2-492E-8E14-C4B3A7FDD593")]
+pub unsafe trait ICLRMetaHostPolicy: IUnknown {
+    /// Selects the runtime for a binary based on its metadata, its
+    /// configuration file and the policy flags.
+    ///
+    /// # Arguments
+    ///
+    /// * `dwPolicyFlags` - A combination of `METAHOST_POLICY_FLAGS`
+    /// * `pwzBinary` - Path to the assembly, or null
+    /// * `pCfgStream` - An `IStream` over the configuration file, or null
+    /// * `pwzVersion` - On input a version hint, on output the selected version
+    /// * `pcchVersion` - Size of `pwzVersion` in characters
+    /// * `pwzImageVersion` - Receives the runtime version in the binary's metadata
+    /// * `pcchImageVersion` - Size of `pwzImageVersion` in characters
+    /// * `pdwConfigFlags` - Receives `METAHOST_CONFIG_FLAGS`
+    /// * `riid` - The IID of the interface to return (typically IID_ICLRRuntimeInfo)
+    /// * `ppRuntime` - Receives the requested interface
+    pub unsafe fn GetRequestedRuntime(
+        &self,
+        dwPolicyFlags: METAHOST_POLICY_FLAGS,
+        pwzBinary: PCWSTR,
+        pCfgStream: *mut core::ffi::c_void,
+        pwzVersion: PWSTR,
+        pcchVersion: *mut u32,
+        pwzImageVersion: PWSTR,
+        pcchImageVersion: *mut u32,
+        pdwConfigFlags: *mut METAHOST_CONFIG_FLAGS,
+        riid: *const GUID,
+        ppRuntime: *mut *mut core::ffi::c_void,
+    ) -> HRESULT;
+}
+
+/// The runtime selected by [`ICLRMetaHostPolicy::requested_runtime`].
+#[derive(Debug, Clone)]
+pub struct RequestedRuntimeInfo {
+    pub runtime: ICLRRuntimeInfo,
+    /// The selected runtime version, e.g. `v4.0.30319`.
+    pub version: String,
+    /// The runtime version in the binary's metadata, if a binary was given.
+    pub image_version: String,
+    pub config_flags: METAHOST_CONFIG_FLAGS,
+}
+
+impl ICLRMetaHostPolicy {
+    /// Selects the runtime for `binary`, like `GetRequestedRuntime`.
+    ///
+    /// `config` is an `IStream` over the application configuration file and
+    /// `version` an optional version hint such as `v4.0.30319`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use mscoree::{
+    ///     CLRCreateInstance, CLSID_CLRMetaHostPolicy, ICLRMetaHostPolicy, METAHOST_POLICY_FLAGS,
+    /// };
+    ///
+    /// let policy: ICLRMetaHostPolicy = unsafe { CLRCreateInstance(&CLSID_CLRMetaHostPolicy)? };
+    /// let runtime = policy.requested_runtime(
+    ///     METAHOST_POLICY_FLAGS::METAHOST_POLICY_HIGHCOMPAT,
+    ///     Some("app.exe".as_ref()),
+    ///     None,
+    ///     None,
+    /// )?;
+    /// println!("{} (built for {})", runtime.version, runtime.image_version);
+    /// # Ok::<(), windows::core::Error>(())
+    /// ```
+    pub fn requested_runtime(
+        &self,
+        flags: METAHOST_POLICY_FLAGS,
+        binary: Option<&Path>,
+        config: Option<&IStream>,
+        version: Option<&str>,
+    ) -> Result<RequestedRuntimeInfo> {
+        let binary = binary.map(HSTRING::from);
+        let binary = binary
+            .as_ref()
+            .map_or(PCWSTR::null(), |binary| PCWSTR(binary.as_ptr()));
+        let config = config.map_or(core::ptr::null_mut(), Interface::as_raw);
+
+        let mut version_buffer: Vec<u16> = version.unwrap_or("").encode_utf16().collect();
+        version_buffer.resize(version_buffer.len().max(MAX_VERSION_LEN) + 1, 0);
+        let mut image_buffer = vec![0u16; MAX_VERSION_LEN + 1];
+        loop {
+            let mut version_len = version_buffer.len() as u32;
+            let mut image_len = image_buffer.len() as u32;
+            let mut config_flags = METAHOST_CONFIG_FLAGS::default();
+            let mut runtime = core::ptr::null_mut();
+            let hr = unsafe {
+                self.GetRequestedRuntime(
+                    flags,
+                    binary,
+                    config,
+                    PWSTR(version_buffer.as_mut_ptr()),
+                    &mut version_len,
+                    PWSTR(image_buffer.as_mut_ptr()),
+                    &mut image_len,
+                    &mut config_flags,
+                    &ICLRRuntimeInfo::IID,
+                    &mut runtime,
+                )
+            };
+            if hr == ERROR_INSUFFICIENT_BUFFER.to_hresult() {
+                let (version_size, image_size) = (version_buffer.len(), image_buffer.len());
+                let hint = String::from_utf16_lossy(&version_buffer[..nul(&version_buffer)]);
+                version_buffer = hint.encode_utf16().collect();
+                let version_size_needed = (version_len as usize).max(version_buffer.len() + 1);
+                version_buffer.resize(version_size.max(version_size_needed), 0);
+                image_buffer.resize(image_size.max(image_len as usize), 0);
+                // Retry only if a buffer grew; otherwise report the error
+                // rather than asking again for the same sizes.
+                if version_buffer.len() > version_size || image_buffer.len() > image_size {
+                    continue;
+                }
+            }
+            hr.ok()?;
+            return Ok(RequestedRuntimeInfo {
+                runtime: unsafe { ICLRRuntimeInfo::from_raw(runtime) },
+                version: String::from_utf16_lossy(&version_buffer[..nul(&version_buffer)]),
+                image_version: String::from_utf16_lossy(&image_buffer[..nul(&image_buffer)]),
+                config_flags,
+            });
+        }
+    }
+}
+
+/// Initial buffer size for version strings, in characters.
+const MAX_VERSION_LEN: usize = 64;
+
+/// Returns the length of a NUL-terminated UTF-16 buffer.
+fn nul(buffer: &[u16]) -> usize {
+    buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len())
+}
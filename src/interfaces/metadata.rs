@@ -161,4 +161,80 @@ pub unsafe trait IMetaDataTables: IUnknown {
         piKey: *mut u32,
         ppName: *mut *const u8,
     ) -> HRESULT;
+
+    /// Get a column's offset, size, type and name.
+    pub unsafe fn GetColumnInfo(
+        &self,
+        ixTbl: u32,
+        ixCol: u32,
+        poCol: *mut u32,
+        pcbCol: *mut u32,
+        pType: *mut u32,
+        ppName: *mut *const u8,
+    ) -> HRESULT;
+
+    /// Get the token types a coded token kind can encode.
+    pub unsafe fn GetCodedTokenInfo(
+        &self,
+        ixCdTkn: u32,
+        pcTokens: *mut u32,
+        ppTokens: *mut *const u32,
+        ppName: *mut *const u8,
+    ) -> HRESULT;
+
+    /// Get a pointer to a row's raw data.
+    pub unsafe fn GetRow(&self, ixTbl: u32, rid: u32, ppRow: *mut *const c_void) -> HRESULT;
+
+    /// Get a column value, with coded indices decoded into tokens.
+    pub unsafe fn GetColumn(&self, ixTbl: u32, ixCol: u32, rid: u32, pVal: *mut u32) -> HRESULT;
+
+    /// Get a UTF-8 string from the string heap.
+    pub unsafe fn GetString(&self, ixString: u32, ppString: *mut *const u8) -> HRESULT;
+
+    /// Get a blob from the blob heap.
+    pub unsafe fn GetBlob(
+        &self,
+        ixBlob: u32,
+        pcbData: *mut u32,
+        ppData: *mut *const c_void,
+    ) -> HRESULT;
+
+    /// Get a GUID from the GUID heap.
+    pub unsafe fn GetGuid(&self, ixGuid: u32, ppGUID: *mut *const GUID) -> HRESULT;
+
+    /// Get a UTF-16 string from the user string heap.
+    pub unsafe fn GetUserString(
+        &self,
+        ixUserString: u32,
+        pcbData: *mut u32,
+        ppData: *mut *const c_void,
+    ) -> HRESULT;
+
+    /// Get the index of the string after `ixString`. Returns S_FALSE at the end of the heap.
+    pub unsafe fn GetNextString(&self, ixString: u32, pNext: *mut u32) -> HRESULT;
+
+    /// Get the index of the blob after `ixBlob`. Returns S_FALSE at the end of the heap.
+    pub unsafe fn GetNextBlob(&self, ixBlob: u32, pNext: *mut u32) -> HRESULT;
+
+    /// Get the index of the GUID after `ixGuid`. Returns S_FALSE at the end of the heap.
+    pub unsafe fn GetNextGuid(&self, ixGuid: u32, pNext: *mut u32) -> HRESULT;
+
+    /// Get the index of the user string after `ixUserString`. Returns S_FALSE at the end of the heap.
+    pub unsafe fn GetNextUserString(&self, ixUserString: u32, pNext: *mut u32) -> HRESULT;
+}
+
+/// IMetaDataTables2 - Adds access to the metadata blob and its streams.
+#[interface("BADB5F70-58DA-43A9-A1C6-D74819F19B15")]
+pub unsafe trait IMetaDataTables2: IMetaDataTables {
+    /// Get a pointer to the whole metadata blob and its size.
+    pub unsafe fn GetMetaDataStorage(&self, ppvMd: *mut *const c_void, pcbMd: *mut u32) -> HRESULT;
+
+    /// Get the name, data and size of a metadata stream. Returns S_FALSE if `ix` is past the last stream.
+    pub unsafe fn GetMetaDataStreamInfo(
+        &self,
+        ix: u32,
+        ppchName: *mut *const u8,
+        ppv: *mut *const c_void,
+        pcb: *mut u32,
+    ) -> HRESULT;
 }
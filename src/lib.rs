@@ -14,13 +14,19 @@
 //! ## Key Interfaces
 //!
 //! - [`ICLRMetaHost`] - Entry point for CLR hosting (.NET 4.0+)
+//! - [`ICLRMetaHostPolicy`] - Policy-based runtime selection for a binary (.NET 4.0+)
 //! - [`ICLRRuntimeInfo`] - Information about a specific CLR version
 //! - [`ICLRRuntimeHost`] - Runtime hosting interface (.NET 2.0+)
 //! - [`ICorRuntimeHost`] - Legacy runtime hosting interface (.NET 1.x)
 //!
+//! The COM bindings are only available on Windows. The pure-Rust image and
+//! metadata readers ([`PeImage`], [`MetadataReader`]) work on every platform.
+//!
 //! ## Example
 //!
 //! ```no_run
+//! # #[cfg(windows)]
+//! # fn main() -> windows::core::Result<()> {
 //! use mscoree::{CLRCreateInstance, CLSID_CLRMetaHost, ICLRMetaHost, IID_ICLRRuntimeInfo};
 //! use std::ptr::null_mut;
 //!
@@ -33,13 +39,56 @@
 //!     let mut runtime_info = null_mut();
 //!     meta_host.GetRuntime(version, &IID_ICLRRuntimeInfo, &mut runtime_info);
 //! }
-//! # Ok::<(), windows::core::Error>(())
+//! # Ok(())
+//! # }
+//! # #[cfg(not(windows))]
+//! # fn main() {}
 //! ```
 
+#[cfg(windows)]
 mod functions;
+#[cfg(windows)]
 mod guids;
+#[cfg(windows)]
 mod interfaces;
 
+mod api;
+mod app_config;
+mod assembly_identity;
+mod com_bindgen;
+mod deps_json;
+mod error;
+mod gac;
+mod il;
+mod merge;
+mod name_index;
+mod reader;
+mod resolver;
+mod runtime_config;
+mod strong_name;
+mod type_system;
+mod writer;
+
+#[cfg(windows)]
 pub use functions::*;
+#[cfg(windows)]
 pub use guids::*;
+#[cfg(windows)]
 pub use interfaces::*;
+
+pub use api::*;
+pub use app_config::*;
+pub use assembly_identity::*;
+pub use com_bindgen::*;
+pub use deps_json::*;
+pub use error::*;
+pub use gac::*;
+pub use il::*;
+pub use merge::*;
+pub use name_index::*;
+pub use reader::*;
+pub use resolver::*;
+pub use runtime_config::*;
+pub use strong_name::*;
+pub use type_system::*;
+pub use writer::*;
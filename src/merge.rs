@@ -0,0 +1,14 @@
+//! Assembly merging.
+//!
+//! [`AssemblyMerger`] combines several assemblies into one, as ILMerge
+//! does: references between them become references to the merged
+//! definitions, clashing types are renamed or internalized, and a
+//! [`MergeReport`] lists what changed and the conflicts that could not be
+//! resolved automatically.
+
+mod merger;
+mod report;
+mod type_name;
+
+pub use merger::*;
+pub use report::*;
@@ -0,0 +1,1810 @@
+//! Merging the metadata, code and resources of several assemblies into one.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+use super::report::{MergeConflictKind, MergeReport, RenamedType};
+use super::type_name::rewrite_type_name;
+use crate::assembly_identity::AssemblyIdentity;
+use crate::error::{Error, Result};
+use crate::il::{ExceptionHandlerKind, Operand};
+use crate::reader::{
+    AssemblyRefRow, AssemblyRow, COMIMAGE_FLAGS_ILONLY, COMIMAGE_FLAGS_NATIVE_ENTRYPOINT,
+    COMIMAGE_FLAGS_TRACKDEBUGDATA, ColumnType, Cursor, CustomAttributeRow, EventMapRow, EventRow,
+    ExportedTypeRow, FieldRow, FieldRvaRow, FileRow, IMAGE_CEE_CS_CALLCONV_FIELD,
+    IMAGE_CEE_CS_CALLCONV_MASK, Machine, ManifestResourceRow, MemberRefRow, MetadataReader,
+    MethodDefRow, MethodSpecRow, ModuleRefRow, ModuleRow, ParamRow, PeImage, PropertyMapRow,
+    PropertyRow, StandAloneSigRow, TOKEN_TYPE_STRING, TableId, TypeDefRow, TypeRefRow, TypeSpecRow,
+    token_rid,
+};
+use crate::strong_name::{StrongNameKeyPair, is_ecma_public_key};
+use crate::writer::{
+    MetadataBuilder, PeBuilder, SIGNATURE_COLUMNS, remap_signature, write_compressed_u32,
+};
+
+const TYPE_ATTRIBUTE_VISIBILITY_MASK: u32 = 0x0000_0007;
+const TYPE_ATTRIBUTE_NOT_PUBLIC: u32 = 0x0000_0000;
+const TYPE_ATTRIBUTE_PUBLIC: u32 = 0x0000_0001;
+/// `FileAttributes.ContainsNoMetadata`.
+const FILE_CONTAINS_NO_METADATA: u32 = 0x0001;
+/// `MethodImplAttributes.CodeTypeMask`; 0 is IL.
+const METHOD_IMPL_CODE_TYPE_MASK: u16 = 0x0003;
+/// `AssemblyFlags.PublicKey`.
+const ASSEMBLY_FLAGS_PUBLIC_KEY: u32 = 0x0001;
+
+const INTERNALS_VISIBLE_TO_ATTRIBUTE: &str =
+    "System.Runtime.CompilerServices.InternalsVisibleToAttribute";
+
+/// Tables whose rows a merged assembly cannot hold: the `*Ptr`
+/// indirection tables of unoptimized metadata and the Edit-and-Continue
+/// tables.
+const UNSUPPORTED_TABLES: &[TableId] = &[
+    TableId::FieldPtr,
+    TableId::MethodPtr,
+    TableId::ParamPtr,
+    TableId::EventPtr,
+    TableId::PropertyPtr,
+    TableId::EncLog,
+    TableId::EncMap,
+];
+
+/// Tables copied row by row with their references renumbered, once every
+/// definition and reference has its new token.
+const COPIED_TABLES: &[TableId] = &[
+    TableId::InterfaceImpl,
+    TableId::Constant,
+    TableId::FieldMarshal,
+    TableId::DeclSecurity,
+    TableId::ClassLayout,
+    TableId::FieldLayout,
+    TableId::MethodSemantics,
+    TableId::MethodImpl,
+    TableId::ImplMap,
+    TableId::NestedClass,
+    TableId::GenericParam,
+    TableId::GenericParamConstraint,
+];
+
+/// The result of [`AssemblyMerger::merge`].
+#[derive(Debug, Clone)]
+pub struct MergedAssembly {
+    /// The merged image, or `None` if a blocking conflict prevented it.
+    pub image: Option<Vec<u8>>,
+    pub report: MergeReport,
+}
+
+/// Merges several IL-only assemblies into one, as ILMerge does.
+///
+/// The first assembly is the primary one: the merged assembly takes its
+/// identity, module name, entry point, platform and Win32 resources, and
+/// its assembly- and module-level attributes. The types, members, code,
+/// field data and managed resources of every assembly are combined, and
+/// references between the merged assemblies become references to the
+/// merged definitions, in metadata, in IL and in the `System.Type`
+/// arguments of custom attributes.
+///
+/// Types whose names clash are renamed when at least one of them is not
+/// public; public clashes are blocking conflicts unless
+/// [`set_rename_public_duplicates`](Self::set_rename_public_duplicates)
+/// allows renaming those too. With
+/// [`set_internalize`](Self::set_internalize), the public types of the
+/// other assemblies become internal first, so only the primary assembly's
+/// API stays visible. Renaming does not reach type names in strings the
+/// code passes to reflection, nor those in resources.
+///
+/// Everything the merger changed or could not reconcile is listed in the
+/// [`MergeReport`]. Debug information is not merged. The merged assembly
+/// is signed when a key is set with
+/// [`set_strong_name_key`](Self::set_strong_name_key); otherwise a
+/// strong-named primary assembly leaves an empty signature area, as for a
+/// delay-signed assembly.
+///
+/// ```no_run
+/// use mscoree::AssemblyMerger;
+///
+/// let mut merger = AssemblyMerger::open("App.exe")?;
+/// merger.add_file("App.Core.dll")?;
+/// merger.add_file("Newtonsoft.Json.dll")?;
+/// merger.set_internalize(true);
+/// let merged = merger.merge()?;
+/// print!("{}", merged.report);
+/// if let Some(image) = merged.image {
+///     std::fs::write("App.merged.exe", image)?;
+/// }
+/// # Ok::<(), mscoree::Error>(())
+/// ```
+pub struct AssemblyMerger {
+    inputs: Vec<PeImage>,
+    names: Vec<String>,
+    internalize: bool,
+    internalize_exclusions: BTreeSet<String>,
+    rename_public_duplicates: bool,
+    merge_assembly_attributes: bool,
+    key: Option<StrongNameKeyPair>,
+}
+
+impl AssemblyMerger {
+    /// Creates a merger whose primary assembly is `primary`.
+    pub fn new(primary: PeImage) -> Result<Self> {
+        let name = check_input(&primary)?;
+        Ok(Self {
+            inputs: vec![primary],
+            names: vec![name],
+            internalize: false,
+            internalize_exclusions: BTreeSet::new(),
+            rename_public_duplicates: false,
+            merge_assembly_attributes: false,
+            key: None,
+        })
+    }
+
+    /// Opens the primary assembly at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(PeImage::open(path)?)
+    }
+
+    /// Adds an assembly to merge into the primary one.
+    pub fn add(&mut self, image: PeImage) -> Result<()> {
+        let name = check_input(&image)?;
+        if self.names.iter().any(|n| n.eq_ignore_ascii_case(&name)) {
+            return Err(Error::BadAssemblyName(format!(
+                "assembly {name} is already being merged"
+            )));
+        }
+        self.inputs.push(image);
+        self.names.push(name);
+        Ok(())
+    }
+
+    /// Adds the assembly at `path` to merge into the primary one.
+    pub fn add_file(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        self.add(PeImage::open(path)?)
+    }
+
+    /// Sets whether the public types of the assemblies other than the
+    /// primary one become internal.
+    pub fn set_internalize(&mut self, internalize: bool) {
+        self.internalize = internalize;
+    }
+
+    /// Keeps the type with the full name `full_name` (`Ns.Name`) public
+    /// when internalizing.
+    pub fn exclude_from_internalizing(&mut self, full_name: impl Into<String>) {
+        self.internalize_exclusions.insert(full_name.into());
+    }
+
+    /// Sets whether a public type whose name clashes with a public type of
+    /// an earlier assembly is renamed rather than reported as a blocking
+    /// conflict.
+    pub fn set_rename_public_duplicates(&mut self, rename: bool) {
+        self.rename_public_duplicates = rename;
+    }
+
+    /// Sets whether the assembly- and module-level attributes of the other
+    /// assemblies are merged too. An attribute whose type the merged
+    /// assembly already has with another value is then reported and
+    /// dropped, except for `InternalsVisibleToAttribute`, of which an
+    /// assembly may have several.
+    pub fn set_merge_assembly_attributes(&mut self, merge: bool) {
+        self.merge_assembly_attributes = merge;
+    }
+
+    /// Sets the key to strong-name sign the merged assembly with. A primary
+    /// assembly with a different public key takes the key's.
+    pub fn set_strong_name_key(&mut self, key: Option<StrongNameKeyPair>) {
+        self.key = key;
+    }
+
+    /// Merges the assemblies. The image is left out if the report has a
+    /// blocking conflict.
+    pub fn merge(&self) -> Result<MergedAssembly> {
+        Merge::new(self)?.run()
+    }
+}
+
+/// Checks that an image can be merged and returns its assembly name.
+fn check_input(image: &PeImage) -> Result<String> {
+    let cor = image.cor_header().ok_or(Error::NotManaged)?;
+    if cor.flags & COMIMAGE_FLAGS_ILONLY == 0 || cor.flags & COMIMAGE_FLAGS_NATIVE_ENTRYPOINT != 0 {
+        return Err(Error::BadImageFormat(
+            "only IL-only images can be merged".into(),
+        ));
+    }
+    let metadata = image.metadata()?;
+    let identity = metadata
+        .assembly_identity()?
+        .ok_or_else(|| Error::BadMetadata("image has no assembly manifest".into()))?;
+    if let Some(table) = UNSUPPORTED_TABLES
+        .iter()
+        .find(|&&table| metadata.row_count(table) != 0)
+    {
+        return Err(Error::BadMetadata(format!(
+            "cannot merge metadata with a {table} table"
+        )));
+    }
+    for rid in 1..=metadata.row_count(TableId::File) {
+        if metadata.row::<FileRow>(rid)?.flags & FILE_CONTAINS_NO_METADATA == 0 {
+            return Err(Error::BadImageFormat(format!(
+                "{} is a multi-module assembly, which cannot be merged",
+                identity.name
+            )));
+        }
+    }
+    Ok(identity.name)
+}
+
+/// An input assembly as the merge sees it.
+struct Input<'a> {
+    image: &'a PeImage,
+    metadata: MetadataReader,
+    identity: AssemblyIdentity,
+    /// Top-level types by namespace and name.
+    top_level: HashMap<(String, String), u32>,
+}
+
+/// The name and flags a type gets in the merged assembly.
+#[derive(Clone)]
+struct TypePlan {
+    flags: u32,
+    namespace: String,
+    name: String,
+}
+
+/// The outcome of looking up the member a member reference names on a
+/// merged type.
+enum MemberLookup {
+    Defined(u32),
+    /// Not found on the merged types; the member may be inherited from a
+    /// type outside the merged assembly.
+    Inherited,
+    Missing,
+}
+
+/// The state of one [`AssemblyMerger::merge`] call.
+struct Merge<'a> {
+    merger: &'a AssemblyMerger,
+    inputs: Vec<Input<'a>>,
+    builder: MetadataBuilder,
+    pe: PeBuilder,
+    report: MergeReport,
+    /// New tokens of every input's rows, `tokens[input][table][rid - 1]`;
+    /// 0 for rows that are not (yet) part of the merged assembly.
+    tokens: Vec<Vec<Vec<u32>>>,
+    /// For every input's `AssemblyRef` rows, the input they name if it is
+    /// merged too.
+    merged_refs: Vec<Vec<Option<usize>>>,
+    plans: Vec<Vec<TypePlan>>,
+    /// New full names of renamed top-level types, by input and old name.
+    renamed: Vec<HashMap<String, String>>,
+    /// The namespaces and names of the merged top-level types.
+    type_names: HashSet<(String, String)>,
+    /// Where the rows of the tables laid out in owner order come from, in
+    /// their new order, by table.
+    rows: Vec<Vec<(usize, u32)>>,
+    /// The first row of each owner's run of rows, by the table of the
+    /// rows: the `FieldList` of every type for `Field`, its `MethodList`
+    /// for `MethodDef`, and so on.
+    list_starts: Vec<Vec<u32>>,
+    /// How many fields and methods `<Module>` has.
+    global_members: (usize, usize),
+    assembly_refs: HashMap<(String, String, Option<[u8; 8]>), u32>,
+    /// `AssemblyRef` rows naming merged assemblies, for references that
+    /// cannot be resolved.
+    merged_assembly_refs: Vec<Option<u32>>,
+    module_refs: HashMap<String, u32>,
+    files: HashMap<String, u32>,
+    type_refs: HashMap<(u32, String, String), u32>,
+    type_specs: HashMap<Vec<u8>, u32>,
+    member_refs: HashMap<(u32, String, Vec<u8>), u32>,
+    standalone_sigs: HashMap<Vec<u8>, u32>,
+    method_specs: HashMap<(u32, Vec<u8>), u32>,
+    bodies: HashMap<(usize, u32), u32>,
+    /// The display name of the merged assembly.
+    output_name: String,
+}
+
+impl<'a> Merge<'a> {
+    fn new(merger: &'a AssemblyMerger) -> Result<Self> {
+        let mut inputs = Vec::with_capacity(merger.inputs.len());
+        for image in &merger.inputs {
+            let metadata = image.metadata()?;
+            let identity = metadata
+                .assembly_identity()?
+                .ok_or_else(|| Error::BadMetadata("image has no assembly manifest".into()))?;
+            let mut top_level = HashMap::new();
+            for rid in 2..=metadata.row_count(TableId::TypeDef) {
+                if metadata.enclosing_type(rid)?.is_none() {
+                    let row = metadata.row::<TypeDefRow>(rid)?;
+                    let key = (
+                        metadata.string(row.namespace)?.to_string(),
+                        metadata.string(row.name)?.to_string(),
+                    );
+                    top_level.entry(key).or_insert(rid);
+                }
+            }
+            inputs.push(Input {
+                image,
+                metadata,
+                identity,
+                top_level,
+            });
+        }
+        let tokens = inputs
+            .iter()
+            .map(|input| {
+                TableId::ALL
+                    .iter()
+                    .fold(vec![Vec::new(); 64], |mut tables, &table| {
+                        tables[table as usize] = vec![0; input.metadata.row_count(table) as usize];
+                        tables
+                    })
+            })
+            .collect();
+        let merged_refs = inputs
+            .iter()
+            .map(|input| vec![None; input.metadata.row_count(TableId::AssemblyRef) as usize])
+            .collect();
+
+        let primary = &inputs[0];
+        let cor = primary.image.cor_header().ok_or(Error::NotManaged)?;
+        let mut pe = PeBuilder::from_image(primary.image)?;
+        pe.set_cor_flags(cor.flags & !COMIMAGE_FLAGS_TRACKDEBUGDATA);
+        let mut builder = MetadataBuilder::new();
+        builder.set_version(primary.metadata.version());
+        let report = MergeReport {
+            inputs: inputs
+                .iter()
+                .map(|input| input.identity.to_string())
+                .collect(),
+            ..Default::default()
+        };
+        let input_count = inputs.len();
+        Ok(Self {
+            merger,
+            inputs,
+            builder,
+            pe,
+            report,
+            tokens,
+            merged_refs,
+            plans: Vec::new(),
+            renamed: vec![HashMap::new(); input_count],
+            type_names: HashSet::new(),
+            rows: vec![Vec::new(); 64],
+            list_starts: vec![Vec::new(); 64],
+            global_members: (0, 0),
+            assembly_refs: HashMap::new(),
+            merged_assembly_refs: vec![None; input_count],
+            module_refs: HashMap::new(),
+            files: HashMap::new(),
+            type_refs: HashMap::new(),
+            type_specs: HashMap::new(),
+            member_refs: HashMap::new(),
+            standalone_sigs: HashMap::new(),
+            method_specs: HashMap::new(),
+            bodies: HashMap::new(),
+            output_name: String::new(),
+        })
+    }
+
+    fn run(mut self) -> Result<MergedAssembly> {
+        self.check_platforms();
+        self.plan_types()?;
+        self.write_manifest()?;
+        self.merge_scopes()?;
+        self.lay_out_definitions()?;
+        for input in 0..self.inputs.len() {
+            for rid in 1..=self.inputs[input].metadata.row_count(TableId::TypeRef) {
+                self.type_ref(input, rid, 0)?;
+            }
+        }
+        self.merge_references()?;
+        self.write_definitions()?;
+        for &table in COPIED_TABLES {
+            for input in 0..self.inputs.len() {
+                for rid in 1..=self.inputs[input].metadata.row_count(table) {
+                    if table == TableId::DeclSecurity && input > 0 {
+                        let parent = self.inputs[input].metadata.columns(table, rid)?[1];
+                        if TableId::from_token(parent)
+                            .is_some_and(|(table, _)| table == TableId::Assembly)
+                        {
+                            continue;
+                        }
+                    }
+                    self.copy_row(input, table, rid)?;
+                }
+            }
+        }
+        self.write_field_data()?;
+        self.write_exported_types()?;
+        self.write_resources()?;
+        self.write_custom_attributes()?;
+
+        let cor = self.inputs[0].image.cor_header().ok_or(Error::NotManaged)?;
+        if token_rid(cor.entry_point) != 0 {
+            let entry_point = self.token(0, cor.entry_point).ok_or_else(|| {
+                Error::BadMetadata(format!(
+                    "entry point {:#010x} is not a method of the assembly",
+                    cor.entry_point
+                ))
+            })?;
+            self.pe.set_entry_point(entry_point);
+        }
+        self.report.assembly = self.output_name.clone();
+        if self.report.is_blocked() {
+            return Ok(MergedAssembly {
+                image: None,
+                report: self.report,
+            });
+        }
+
+        self.builder.sort_tables()?;
+        self.builder.derive_mvid()?;
+        self.pe.set_metadata(self.builder.to_bytes()?);
+        let mut image = self.pe.to_bytes()?;
+        if let Some(key) = &self.merger.key {
+            image = PeImage::from_bytes(image)?.strong_name_sign(key)?;
+        }
+        Ok(MergedAssembly {
+            image: Some(image),
+            report: self.report,
+        })
+    }
+
+    /// Reports assemblies whose code the primary assembly's platform cannot
+    /// run: only platform-neutral assemblies and those built for the same
+    /// platform can be merged.
+    fn check_platforms(&mut self) {
+        let (primary_kind, primary_machine) = self.inputs[0].image.pe_kind();
+        for input in &self.inputs[1..] {
+            let (kind, machine) = input.image.pe_kind();
+            let neutral =
+                machine == Machine::I386 && !kind.is_32bit_required() && !kind.is_pe32_plus();
+            let same = machine == primary_machine
+                && kind.is_32bit_required() == primary_kind.is_32bit_required();
+            if !neutral && !same {
+                self.report.conflict(
+                    MergeConflictKind::PlatformMismatch,
+                    true,
+                    &input.identity.name,
+                    input.identity.to_string(),
+                    format!(
+                        "targets {}, which the primary assembly's platform cannot run",
+                        platform_name(machine, kind.is_32bit_required())
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Decides the name and visibility of every type, renaming types whose
+    /// names clash.
+    fn plan_types(&mut self) -> Result<()> {
+        for input in &self.inputs {
+            let metadata = &input.metadata;
+            let mut plans = Vec::with_capacity(metadata.row_count(TableId::TypeDef) as usize);
+            for rid in 1..=metadata.row_count(TableId::TypeDef) {
+                let row = metadata.row::<TypeDefRow>(rid)?;
+                plans.push(TypePlan {
+                    flags: row.flags,
+                    namespace: metadata.string(row.namespace)?.to_string(),
+                    name: metadata.string(row.name)?.to_string(),
+                });
+            }
+            self.plans.push(plans);
+        }
+
+        let mut claimed: HashMap<(String, String), (usize, u32)> = HashMap::new();
+        for input in 0..self.inputs.len() {
+            let mut top_level: Vec<u32> = self.inputs[input].top_level.values().copied().collect();
+            top_level.sort_unstable();
+            for rid in top_level {
+                let plan = &mut self.plans[input][rid as usize - 1];
+                let full_name = full_name(&plan.namespace, &plan.name);
+                if input > 0
+                    && self.merger.internalize
+                    && plan.flags & TYPE_ATTRIBUTE_VISIBILITY_MASK == TYPE_ATTRIBUTE_PUBLIC
+                    && !self.merger.internalize_exclusions.contains(&full_name)
+                {
+                    plan.flags =
+                        plan.flags & !TYPE_ATTRIBUTE_VISIBILITY_MASK | TYPE_ATTRIBUTE_NOT_PUBLIC;
+                    self.report.internalized_types.push(full_name.clone());
+                }
+                let key = (plan.namespace.clone(), plan.name.clone());
+                let Some(&(earlier_input, earlier_rid)) = claimed.get(&key) else {
+                    claimed.insert(key, (input, rid));
+                    continue;
+                };
+                let is_public = |plan: &TypePlan| {
+                    plan.flags & TYPE_ATTRIBUTE_VISIBILITY_MASK == TYPE_ATTRIBUTE_PUBLIC
+                };
+                if !is_public(&self.plans[input][rid as usize - 1])
+                    || self.merger.rename_public_duplicates
+                        && is_public(&self.plans[earlier_input][earlier_rid as usize - 1])
+                {
+                    self.rename_type(input, rid, &mut claimed);
+                } else if !is_public(&self.plans[earlier_input][earlier_rid as usize - 1]) {
+                    claimed.insert(key, (input, rid));
+                    self.rename_type(earlier_input, earlier_rid, &mut claimed);
+                } else {
+                    let assembly = &self.inputs[input].identity.name;
+                    let earlier = &self.inputs[earlier_input].identity.name;
+                    self.report.conflict(
+                        MergeConflictKind::DuplicateType,
+                        true,
+                        assembly,
+                        full_name,
+                        format!("{earlier} defines a public type with the same name"),
+                    );
+                }
+            }
+        }
+        self.type_names = claimed.into_keys().collect();
+        Ok(())
+    }
+
+    /// Gives a top-level type a name no other type has, made from its
+    /// assembly's name and its own.
+    fn rename_type(
+        &mut self,
+        input: usize,
+        rid: u32,
+        claimed: &mut HashMap<(String, String), (usize, u32)>,
+    ) {
+        let assembly = self.inputs[input].identity.name.clone();
+        let plan = &mut self.plans[input][rid as usize - 1];
+        let old_name = full_name(&plan.namespace, &plan.name);
+        let mut suffix = 1;
+        let name = loop {
+            let prefix = match suffix {
+                1 => assembly.clone(),
+                n => format!("{assembly}_{n}"),
+            };
+            let name = format!("<{prefix}>{}", plan.name);
+            if !claimed.contains_key(&(plan.namespace.clone(), name.clone())) {
+                break name;
+            }
+            suffix += 1;
+        };
+        claimed.insert((plan.namespace.clone(), name.clone()), (input, rid));
+        plan.name = name;
+        let new_name = full_name(&plan.namespace, &plan.name);
+        self.renamed[input].insert(old_name.clone(), new_name.clone());
+        self.report.renamed_types.push(RenamedType {
+            assembly,
+            old_name,
+            new_name,
+        });
+    }
+
+    /// Writes the `Module` and `Assembly` rows, which come from the primary
+    /// assembly.
+    fn write_manifest(&mut self) -> Result<()> {
+        let primary = &self.inputs[0].metadata;
+        let module = primary.row::<ModuleRow>(1)?;
+        let row = ModuleRow {
+            generation: module.generation,
+            name: self.builder.string(primary.string(module.name)?),
+            ..Default::default()
+        };
+        self.builder.add(&row);
+
+        let assembly = primary.row::<AssemblyRow>(1)?;
+        let mut public_key = primary.blob(assembly.public_key)?.to_vec();
+        let mut flags = assembly.flags;
+        if let Some(key) = &self.merger.key {
+            let key_blob = key.public_key_blob();
+            let keeps_key = public_key == key_blob
+                || is_ecma_public_key(&public_key)
+                || primary.assembly_signature_key()?.is_some();
+            if !keeps_key {
+                public_key = key_blob;
+                flags |= ASSEMBLY_FLAGS_PUBLIC_KEY;
+            }
+            self.pe
+                .set_strong_name_signature_size(key.signature_len() as u32);
+        } else {
+            self.pe.set_strong_name_signature_size(
+                self.inputs[0]
+                    .image
+                    .strong_name_signature_range()
+                    .map_or(0, |range| range.len() as u32),
+            );
+        }
+        let name = primary.string(assembly.name)?;
+        let culture = primary.string(assembly.culture)?;
+        let row = AssemblyRow {
+            public_key: self.builder.blob(&public_key),
+            flags,
+            name: self.builder.string(name),
+            culture: self.builder.string(culture),
+            ..assembly
+        };
+        self.builder.add(&row);
+        let identity_flags = if public_key.is_empty() {
+            flags
+        } else {
+            flags | ASSEMBLY_FLAGS_PUBLIC_KEY
+        };
+        self.output_name = AssemblyIdentity::from_props(
+            name,
+            [
+                assembly.major_version,
+                assembly.minor_version,
+                assembly.build_number,
+                assembly.revision_number,
+            ],
+            culture,
+            &public_key,
+            identity_flags,
+        )?
+        .to_string();
+
+        for tokens in &mut self.tokens {
+            tokens[TableId::Module as usize][0] = TableId::Module.token(1);
+            tokens[TableId::Assembly as usize][0] = TableId::Assembly.token(1);
+        }
+        Ok(())
+    }
+
+    /// Merges the assembly, module and file references, dropping those to
+    /// merged assemblies and unifying the versions of the others.
+    fn merge_scopes(&mut self) -> Result<()> {
+        for input in 0..self.inputs.len() {
+            for rid in 1..=self.inputs[input].metadata.row_count(TableId::AssemblyRef) {
+                let identity = self.inputs[input].metadata.assembly_ref_identity(rid)?;
+                let culture = identity.culture.clone().unwrap_or_default();
+                if let Some(target) = self.inputs.iter().position(|input| {
+                    input.identity.name.eq_ignore_ascii_case(&identity.name)
+                        && input
+                            .identity
+                            .culture
+                            .as_deref()
+                            .unwrap_or_default()
+                            .eq_ignore_ascii_case(&culture)
+                }) {
+                    self.merged_refs[input][rid as usize - 1] = Some(target);
+                    continue;
+                }
+                let key = (
+                    identity.name.to_ascii_lowercase(),
+                    culture.to_ascii_lowercase(),
+                    identity.public_key_token().map(|token| token.0),
+                );
+                let new_rid = match self.assembly_refs.get(&key) {
+                    Some(&new_rid) => {
+                        let version = identity.version.unwrap_or_default();
+                        let row = self.builder.row::<AssemblyRefRow>(new_rid)?;
+                        let current = [
+                            row.major_version,
+                            row.minor_version,
+                            row.build_number,
+                            row.revision_number,
+                        ];
+                        if version.parts() != current {
+                            let unified = version.parts().max(current);
+                            self.report.conflict(
+                                MergeConflictKind::ReferenceVersionMismatch,
+                                false,
+                                &self.inputs[input].identity.name,
+                                identity.name.clone(),
+                                format!(
+                                    "references version {version} where another assembly \
+                                     references {}; unified to {}",
+                                    current.map(|part| part.to_string()).join("."),
+                                    unified.map(|part| part.to_string()).join(".")
+                                ),
+                            );
+                            for (column, part) in unified.into_iter().enumerate() {
+                                self.builder.set_column(
+                                    TableId::AssemblyRef,
+                                    new_rid,
+                                    column,
+                                    part as u32,
+                                )?;
+                            }
+                        }
+                        new_rid
+                    }
+                    None => {
+                        let new_rid = self
+                            .copy_row(input, TableId::AssemblyRef, rid)?
+                            .expect("assembly references refer to no rows");
+                        self.assembly_refs.insert(key, new_rid);
+                        new_rid
+                    }
+                };
+                self.tokens[input][TableId::AssemblyRef as usize][rid as usize - 1] =
+                    TableId::AssemblyRef.token(new_rid);
+            }
+
+            for rid in 1..=self.inputs[input].metadata.row_count(TableId::ModuleRef) {
+                let metadata = &self.inputs[input].metadata;
+                let name = metadata
+                    .string(metadata.row::<ModuleRefRow>(rid)?.name)?
+                    .to_string();
+                let new_rid = match self.module_refs.get(&name) {
+                    Some(&new_rid) => new_rid,
+                    None => {
+                        let row = ModuleRefRow {
+                            name: self.builder.string(&name),
+                        };
+                        let new_rid = self.builder.add(&row);
+                        self.module_refs.insert(name, new_rid);
+                        new_rid
+                    }
+                };
+                self.tokens[input][TableId::ModuleRef as usize][rid as usize - 1] =
+                    TableId::ModuleRef.token(new_rid);
+            }
+            for rid in 1..=self.inputs[input].metadata.row_count(TableId::File) {
+                let metadata = &self.inputs[input].metadata;
+                let name = metadata
+                    .string(metadata.row::<FileRow>(rid)?.name)?
+                    .to_string();
+                let new_rid = match self.files.get(&name) {
+                    Some(&new_rid) => new_rid,
+                    None => {
+                        let new_rid = self
+                            .copy_row(input, TableId::File, rid)?
+                            .expect("files refer to no rows");
+                        self.files.insert(name, new_rid);
+                        new_rid
+                    }
+                };
+                self.tokens[input][TableId::File as usize][rid as usize - 1] =
+                    TableId::File.token(new_rid);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Merge<'_> {
+    /// Returns the new token of an input's row: the token itself if it is
+    /// nil, `None` if the row is not part of the merged assembly.
+    fn token(&self, input: usize, token: u32) -> Option<u32> {
+        map_token(&self.tokens[input], token)
+    }
+
+    /// Returns the new token of a row an input's row refers to, which must
+    /// be part of the merged assembly.
+    fn scope(&self, input: usize, token: u32) -> Result<u32> {
+        self.token(input, token).ok_or_else(|| {
+            Error::BadMetadata(format!(
+                "{token:#010x} of {} is not part of the merged assembly",
+                self.inputs[input].identity.name
+            ))
+        })
+    }
+
+    /// Lays out the types, then their fields and methods, the methods'
+    /// parameters, and the property and event maps with their rows:
+    /// `<Module>` first, with the global members of every input, then the
+    /// types of each input in order.
+    fn lay_out_definitions(&mut self) -> Result<()> {
+        let mut types = vec![(0, 1)];
+        for input in 0..self.inputs.len() {
+            self.tokens[input][TableId::TypeDef as usize][0] = TableId::TypeDef.token(1);
+            for rid in 2..=self.inputs[input].metadata.row_count(TableId::TypeDef) {
+                types.push((input, rid));
+                self.tokens[input][TableId::TypeDef as usize][rid as usize - 1] =
+                    TableId::TypeDef.token(types.len() as u32);
+            }
+        }
+
+        let (mut fields, mut methods) = (Vec::new(), Vec::new());
+        let (mut field_starts, mut method_starts) = (Vec::new(), Vec::new());
+        for (index, &(owner, rid)) in types.iter().enumerate() {
+            field_starts.push(fields.len() as u32 + 1);
+            method_starts.push(methods.len() as u32 + 1);
+            let sources = if index == 0 {
+                0..self.inputs.len()
+            } else {
+                owner..owner + 1
+            };
+            for input in sources {
+                let metadata = &self.inputs[input].metadata;
+                for field in metadata.type_def_fields(rid)? {
+                    fields.push((input, field));
+                    self.tokens[input][TableId::Field as usize][field as usize - 1] =
+                        TableId::Field.token(fields.len() as u32);
+                }
+                for method in metadata.type_def_methods(rid)? {
+                    methods.push((input, method));
+                    self.tokens[input][TableId::MethodDef as usize][method as usize - 1] =
+                        TableId::MethodDef.token(methods.len() as u32);
+                }
+            }
+            if index == 0 {
+                self.global_members = (fields.len(), methods.len());
+            }
+        }
+
+        let (mut params, mut param_starts) = (Vec::new(), Vec::new());
+        for &(input, method) in &methods {
+            param_starts.push(params.len() as u32 + 1);
+            for param in self.inputs[input].metadata.method_params(method)? {
+                params.push((input, param));
+                self.tokens[input][TableId::Param as usize][param as usize - 1] =
+                    TableId::Param.token(params.len() as u32);
+            }
+        }
+
+        self.rows[TableId::TypeDef as usize] = types;
+        self.rows[TableId::Field as usize] = fields;
+        self.rows[TableId::MethodDef as usize] = methods;
+        self.rows[TableId::Param as usize] = params;
+        self.list_starts[TableId::Field as usize] = field_starts;
+        self.list_starts[TableId::MethodDef as usize] = method_starts;
+        self.list_starts[TableId::Param as usize] = param_starts;
+        self.lay_out_map(TableId::PropertyMap, TableId::Property)?;
+        self.lay_out_map(TableId::EventMap, TableId::Event)
+    }
+
+    /// Lays out a `PropertyMap` or `EventMap` table and its rows, input by
+    /// input.
+    fn lay_out_map(&mut self, map: TableId, child: TableId) -> Result<()> {
+        let (mut maps, mut children, mut starts) = (Vec::new(), Vec::new(), Vec::new());
+        for input in 0..self.inputs.len() {
+            let metadata = &self.inputs[input].metadata;
+            for rid in 1..=metadata.row_count(map) {
+                maps.push((input, rid));
+                self.tokens[input][map as usize][rid as usize - 1] = map.token(maps.len() as u32);
+                starts.push(children.len() as u32 + 1);
+                let parent = metadata.columns(map, rid)?[0];
+                let rows = if child == TableId::Property {
+                    metadata.type_def_properties(parent)?
+                } else {
+                    metadata.type_def_events(parent)?
+                };
+                for row in rows {
+                    children.push((input, row));
+                    self.tokens[input][child as usize][row as usize - 1] =
+                        child.token(children.len() as u32);
+                }
+            }
+        }
+        self.rows[map as usize] = maps;
+        self.rows[child as usize] = children;
+        self.list_starts[child as usize] = starts;
+        Ok(())
+    }
+
+    /// Resolves an input's `TypeRef` row: to the definition if it names a
+    /// type of a merged assembly, otherwise to a merged `TypeRef` row.
+    fn type_ref(&mut self, input: usize, rid: u32, depth: u32) -> Result<u32> {
+        let resolved = self.tokens[input][TableId::TypeRef as usize][rid as usize - 1];
+        if resolved != 0 {
+            return Ok(resolved);
+        }
+        let metadata = &self.inputs[input].metadata;
+        if depth > metadata.row_count(TableId::TypeRef) {
+            return Err(Error::BadMetadata(format!(
+                "type reference {:#010x} is nested in itself",
+                TableId::TypeRef.token(rid)
+            )));
+        }
+        let row = metadata.row::<TypeRefRow>(rid)?;
+        let namespace = metadata.string(row.namespace)?.to_string();
+        let name = metadata.string(row.name)?.to_string();
+        let token = match TableId::from_token(row.resolution_scope) {
+            Some((TableId::AssemblyRef, scope)) if scope != 0 => {
+                match self.merged_refs[input][scope as usize - 1] {
+                    Some(target) => self.merged_type(input, target, &namespace, &name, 0)?,
+                    None => {
+                        let scope = self.scope(input, row.resolution_scope)?;
+                        self.add_type_ref(scope, &namespace, &name)
+                    }
+                }
+            }
+            Some((TableId::TypeRef, outer)) if outer != 0 => {
+                let outer = self.type_ref(input, outer, depth + 1)?;
+                match TableId::from_token(outer) {
+                    Some((TableId::TypeDef, outer)) => self.nested_type(input, outer, &name)?,
+                    _ => self.add_type_ref(outer, &namespace, &name),
+                }
+            }
+            Some((TableId::Module, scope)) if scope != 0 => {
+                let key = (namespace.clone(), name.clone());
+                match self.inputs[input].top_level.get(&key) {
+                    Some(&def) => self.tokens[input][TableId::TypeDef as usize][def as usize - 1],
+                    None => {
+                        self.report.conflict(
+                            MergeConflictKind::UnresolvedType,
+                            false,
+                            &self.inputs[input].identity.name,
+                            full_name(&namespace, &name),
+                            "the module refers to a type it does not define; the reference is kept",
+                        );
+                        self.add_type_ref(TableId::Module.token(1), &namespace, &name)
+                    }
+                }
+            }
+            _ => {
+                let scope = self.scope(input, row.resolution_scope)?;
+                self.add_type_ref(scope, &namespace, &name)
+            }
+        };
+        self.tokens[input][TableId::TypeRef as usize][rid as usize - 1] = token;
+        Ok(token)
+    }
+
+    /// Resolves a reference from `input` to a top-level type of the merged
+    /// assembly `target`, following `target`'s type forwarders.
+    fn merged_type(
+        &mut self,
+        input: usize,
+        target: usize,
+        namespace: &str,
+        name: &str,
+        depth: usize,
+    ) -> Result<u32> {
+        let key = (namespace.to_string(), name.to_string());
+        if let Some(&rid) = self.inputs[target].top_level.get(&key) {
+            return Ok(self.tokens[target][TableId::TypeDef as usize][rid as usize - 1]);
+        }
+        let metadata = &self.inputs[target].metadata;
+        for rid in 1..=metadata.row_count(TableId::ExportedType) {
+            let row = metadata.row::<ExportedTypeRow>(rid)?;
+            if metadata.string(row.namespace)? != namespace || metadata.string(row.name)? != name {
+                continue;
+            }
+            let Some((TableId::AssemblyRef, scope)) = TableId::from_token(row.implementation)
+            else {
+                continue;
+            };
+            if scope == 0 {
+                continue;
+            }
+            match self.merged_refs[target][scope as usize - 1] {
+                Some(next) if depth < self.inputs.len() => {
+                    return self.merged_type(input, next, namespace, name, depth + 1);
+                }
+                Some(_) => break,
+                None => {
+                    let scope = self.scope(target, row.implementation)?;
+                    return Ok(self.add_type_ref(scope, namespace, name));
+                }
+            }
+        }
+        self.report.conflict(
+            MergeConflictKind::UnresolvedType,
+            false,
+            &self.inputs[input].identity.name,
+            full_name(namespace, name),
+            format!(
+                "{} neither defines nor forwards the type; the reference is kept",
+                self.inputs[target].identity.name
+            ),
+        );
+        let scope = self.merged_assembly_ref(target);
+        Ok(self.add_type_ref(scope, namespace, name))
+    }
+
+    /// Resolves a reference to a type nested in the merged type `outer`.
+    fn nested_type(&mut self, input: usize, outer: u32, name: &str) -> Result<u32> {
+        let (owner, rid) = self.rows[TableId::TypeDef as usize][outer as usize - 1];
+        let metadata = &self.inputs[owner].metadata;
+        for nested in metadata.nested_types(rid)? {
+            if metadata.string(metadata.row::<TypeDefRow>(nested)?.name)? == name {
+                return Ok(self.tokens[owner][TableId::TypeDef as usize][nested as usize - 1]);
+            }
+        }
+        let plan = &self.plans[owner][rid as usize - 1];
+        self.report.conflict(
+            MergeConflictKind::UnresolvedType,
+            true,
+            &self.inputs[input].identity.name,
+            format!("{}+{name}", full_name(&plan.namespace, &plan.name)),
+            "the enclosing type has no nested type of this name",
+        );
+        Ok(TableId::TypeDef.token(outer))
+    }
+
+    /// Returns an `AssemblyRef` row naming the merged assembly `target`,
+    /// for references the merge cannot resolve.
+    fn merged_assembly_ref(&mut self, target: usize) -> u32 {
+        if let Some(rid) = self.merged_assembly_refs[target] {
+            return TableId::AssemblyRef.token(rid);
+        }
+        let identity = &self.inputs[target].identity;
+        let version = identity.version.unwrap_or_default().parts();
+        let token = identity.public_key_token().map(|token| token.0.to_vec());
+        let row = AssemblyRefRow {
+            major_version: version[0],
+            minor_version: version[1],
+            build_number: version[2],
+            revision_number: version[3],
+            public_key_or_token: self.builder.blob(&token.unwrap_or_default()),
+            name: self.builder.string(&identity.name),
+            culture: self
+                .builder
+                .string(identity.culture.as_deref().unwrap_or_default()),
+            ..Default::default()
+        };
+        let rid = self.builder.add(&row);
+        self.merged_assembly_refs[target] = Some(rid);
+        TableId::AssemblyRef.token(rid)
+    }
+
+    fn add_type_ref(&mut self, scope: u32, namespace: &str, name: &str) -> u32 {
+        let key = (scope, namespace.to_string(), name.to_string());
+        if let Some(&rid) = self.type_refs.get(&key) {
+            return TableId::TypeRef.token(rid);
+        }
+        let row = TypeRefRow {
+            resolution_scope: scope,
+            name: self.builder.string(name),
+            namespace: self.builder.string(namespace),
+        };
+        let rid = self.builder.add(&row);
+        self.type_refs.insert(key, rid);
+        TableId::TypeRef.token(rid)
+    }
+
+    /// Merges the type specifications, member references and signatures,
+    /// resolving references to members of merged types to their
+    /// definitions.
+    fn merge_references(&mut self) -> Result<()> {
+        for input in 0..self.inputs.len() {
+            let metadata = &self.inputs[input].metadata;
+            for rid in 1..=metadata.row_count(TableId::TypeSpec) {
+                let blob = metadata.blob(metadata.row::<TypeSpecRow>(rid)?.signature)?;
+                let signature = remap_tokens(&self.tokens[input], blob, true)?;
+                let new_rid = match self.type_specs.get(&signature) {
+                    Some(&new_rid) => new_rid,
+                    None => {
+                        let row = TypeSpecRow {
+                            signature: self.builder.blob(&signature),
+                        };
+                        let new_rid = self.builder.add(&row);
+                        self.type_specs.insert(signature, new_rid);
+                        new_rid
+                    }
+                };
+                self.tokens[input][TableId::TypeSpec as usize][rid as usize - 1] =
+                    TableId::TypeSpec.token(new_rid);
+            }
+        }
+
+        for input in 0..self.inputs.len() {
+            for rid in 1..=self.inputs[input].metadata.row_count(TableId::MemberRef) {
+                let metadata = &self.inputs[input].metadata;
+                let row = metadata.row::<MemberRefRow>(rid)?;
+                let class = self.scope(input, row.class)?;
+                let name = metadata.string(row.name)?.to_string();
+                let signature =
+                    remap_tokens(&self.tokens[input], metadata.blob(row.signature)?, false)?;
+                let token = match TableId::from_token(class) {
+                    Some((TableId::TypeDef, type_rid)) => {
+                        match self.find_member(type_rid, &name, &signature)? {
+                            MemberLookup::Defined(token) => token,
+                            MemberLookup::Inherited => self.add_member_ref(class, name, signature),
+                            MemberLookup::Missing => {
+                                let (owner, source) =
+                                    self.rows[TableId::TypeDef as usize][type_rid as usize - 1];
+                                let plan = &self.plans[owner][source as usize - 1];
+                                self.report.conflict(
+                                    MergeConflictKind::UnresolvedMember,
+                                    false,
+                                    &self.inputs[input].identity.name,
+                                    format!("{}::{name}", full_name(&plan.namespace, &plan.name)),
+                                    "no member of the merged type or its base types has this name \
+                                     and signature; the member reference is kept",
+                                );
+                                self.add_member_ref(class, name, signature)
+                            }
+                        }
+                    }
+                    _ => self.add_member_ref(class, name, signature),
+                };
+                self.tokens[input][TableId::MemberRef as usize][rid as usize - 1] = token;
+            }
+        }
+
+        for input in 0..self.inputs.len() {
+            let metadata = &self.inputs[input].metadata;
+            for rid in 1..=metadata.row_count(TableId::StandAloneSig) {
+                let blob = metadata.blob(metadata.row::<StandAloneSigRow>(rid)?.signature)?;
+                let signature = remap_tokens(&self.tokens[input], blob, false)?;
+                let new_rid = match self.standalone_sigs.get(&signature) {
+                    Some(&new_rid) => new_rid,
+                    None => {
+                        let row = StandAloneSigRow {
+                            signature: self.builder.blob(&signature),
+                        };
+                        let new_rid = self.builder.add(&row);
+                        self.standalone_sigs.insert(signature, new_rid);
+                        new_rid
+                    }
+                };
+                self.tokens[input][TableId::StandAloneSig as usize][rid as usize - 1] =
+                    TableId::StandAloneSig.token(new_rid);
+            }
+            for rid in 1..=metadata.row_count(TableId::MethodSpec) {
+                let row = metadata.row::<MethodSpecRow>(rid)?;
+                let method = self.scope(input, row.method)?;
+                let blob = metadata.blob(row.instantiation)?;
+                let key = (method, remap_tokens(&self.tokens[input], blob, false)?);
+                let new_rid = match self.method_specs.get(&key) {
+                    Some(&new_rid) => new_rid,
+                    None => {
+                        let row = MethodSpecRow {
+                            method,
+                            instantiation: self.builder.blob(&key.1),
+                        };
+                        let new_rid = self.builder.add(&row);
+                        self.method_specs.insert(key, new_rid);
+                        new_rid
+                    }
+                };
+                self.tokens[input][TableId::MethodSpec as usize][rid as usize - 1] =
+                    TableId::MethodSpec.token(new_rid);
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the field or method with the given name and remapped
+    /// signature on the merged type `type_rid` or on its base types.
+    fn find_member(&self, type_rid: u32, name: &str, signature: &[u8]) -> Result<MemberLookup> {
+        let is_field = signature.first().is_some_and(|&calling_convention| {
+            calling_convention & IMAGE_CEE_CS_CALLCONV_MASK == IMAGE_CEE_CS_CALLCONV_FIELD
+        });
+        let mut type_rid = type_rid;
+        for _ in 0..self.rows[TableId::TypeDef as usize].len() {
+            let (owner, rid) = self.rows[TableId::TypeDef as usize][type_rid as usize - 1];
+            let metadata = &self.inputs[owner].metadata;
+            let tokens = &self.tokens[owner];
+            if is_field {
+                for field in metadata.type_def_fields(rid)? {
+                    let row = metadata.row::<FieldRow>(field)?;
+                    if metadata.string(row.name)? == name
+                        && remap_tokens(tokens, metadata.blob(row.signature)?, false)? == signature
+                    {
+                        return Ok(MemberLookup::Defined(
+                            tokens[TableId::Field as usize][field as usize - 1],
+                        ));
+                    }
+                }
+            } else {
+                for method in metadata.type_def_methods(rid)? {
+                    let row = metadata.row::<MethodDefRow>(method)?;
+                    if metadata.string(row.name)? == name
+                        && remap_tokens(tokens, metadata.blob(row.signature)?, false)? == signature
+                    {
+                        return Ok(MemberLookup::Defined(
+                            tokens[TableId::MethodDef as usize][method as usize - 1],
+                        ));
+                    }
+                }
+            }
+            let extends = metadata.row::<TypeDefRow>(rid)?.extends;
+            match TableId::from_token(self.scope(owner, extends)?) {
+                Some((TableId::TypeDef, base)) if base != 0 => type_rid = base,
+                _ if token_rid(extends) == 0 => return Ok(MemberLookup::Missing),
+                _ => return Ok(MemberLookup::Inherited),
+            }
+        }
+        Err(Error::BadMetadata(format!(
+            "type {:#010x} of the merged assembly derives from itself",
+            TableId::TypeDef.token(type_rid)
+        )))
+    }
+
+    fn add_member_ref(&mut self, class: u32, name: String, signature: Vec<u8>) -> u32 {
+        let key = (class, name, signature);
+        if let Some(&rid) = self.member_refs.get(&key) {
+            return TableId::MemberRef.token(rid);
+        }
+        let row = MemberRefRow {
+            class,
+            name: self.builder.string(&key.1),
+            signature: self.builder.blob(&key.2),
+        };
+        let rid = self.builder.add(&row);
+        self.member_refs.insert(key, rid);
+        TableId::MemberRef.token(rid)
+    }
+
+    /// Writes the tables laid out by
+    /// [`lay_out_definitions`](Self::lay_out_definitions), with the method
+    /// bodies.
+    fn write_definitions(&mut self) -> Result<()> {
+        let types = self.rows[TableId::TypeDef as usize].clone();
+        for (index, &(input, rid)) in types.iter().enumerate() {
+            let row = self.inputs[input].metadata.row::<TypeDefRow>(rid)?;
+            let extends = self.scope(input, row.extends)?;
+            let plan = &self.plans[input][rid as usize - 1];
+            let row = TypeDefRow {
+                flags: plan.flags,
+                name: self.builder.string(&plan.name),
+                namespace: self.builder.string(&plan.namespace),
+                extends,
+                field_list: self.list_starts[TableId::Field as usize][index],
+                method_list: self.list_starts[TableId::MethodDef as usize][index],
+            };
+            self.builder.add(&row);
+        }
+
+        let mut globals = HashSet::new();
+        let fields = self.rows[TableId::Field as usize].clone();
+        for (index, &(input, rid)) in fields.iter().enumerate() {
+            let metadata = &self.inputs[input].metadata;
+            let row = metadata.row::<FieldRow>(rid)?;
+            let name = metadata.string(row.name)?;
+            let signature =
+                remap_tokens(&self.tokens[input], metadata.blob(row.signature)?, false)?;
+            if index < self.global_members.0
+                && !globals.insert((name.to_string(), signature.clone()))
+            {
+                self.report.conflict(
+                    MergeConflictKind::DuplicateGlobalMember,
+                    true,
+                    &self.inputs[input].identity.name,
+                    name,
+                    "another assembly defines a global field with the same name and type",
+                );
+            }
+            let row = FieldRow {
+                flags: row.flags,
+                name: self.builder.string(name),
+                signature: self.builder.blob(&signature),
+            };
+            self.builder.add(&row);
+        }
+
+        let methods = self.rows[TableId::MethodDef as usize].clone();
+        for (index, &(input, rid)) in methods.iter().enumerate() {
+            let rva = self.method_body(input, rid)?;
+            let metadata = &self.inputs[input].metadata;
+            let row = metadata.row::<MethodDefRow>(rid)?;
+            let name = metadata.string(row.name)?;
+            let signature =
+                remap_tokens(&self.tokens[input], metadata.blob(row.signature)?, false)?;
+            if index < self.global_members.1
+                && !globals.insert((name.to_string(), signature.clone()))
+            {
+                self.report.conflict(
+                    MergeConflictKind::DuplicateGlobalMember,
+                    true,
+                    &self.inputs[input].identity.name,
+                    name,
+                    "another assembly defines a global method with the same name and signature",
+                );
+            }
+            let row = MethodDefRow {
+                rva,
+                impl_flags: row.impl_flags,
+                flags: row.flags,
+                name: self.builder.string(name),
+                signature: self.builder.blob(&signature),
+                param_list: self.list_starts[TableId::Param as usize][index],
+            };
+            self.builder.add(&row);
+        }
+
+        for &(input, rid) in &self.rows[TableId::Param as usize] {
+            let metadata = &self.inputs[input].metadata;
+            let row = metadata.row::<ParamRow>(rid)?;
+            let row = ParamRow {
+                name: self.builder.string(metadata.string(row.name)?),
+                ..row
+            };
+            self.builder.add(&row);
+        }
+
+        for (index, &(input, rid)) in self.rows[TableId::PropertyMap as usize].iter().enumerate() {
+            let row = self.inputs[input].metadata.row::<PropertyMapRow>(rid)?;
+            let parent = self.scope(input, TableId::TypeDef.token(row.parent))?;
+            let row = PropertyMapRow {
+                parent: token_rid(parent),
+                property_list: self.list_starts[TableId::Property as usize][index],
+            };
+            self.builder.add(&row);
+        }
+        for &(input, rid) in &self.rows[TableId::Property as usize] {
+            let metadata = &self.inputs[input].metadata;
+            let row = metadata.row::<PropertyRow>(rid)?;
+            let signature =
+                remap_tokens(&self.tokens[input], metadata.blob(row.signature)?, false)?;
+            let row = PropertyRow {
+                flags: row.flags,
+                name: self.builder.string(metadata.string(row.name)?),
+                signature: self.builder.blob(&signature),
+            };
+            self.builder.add(&row);
+        }
+
+        for (index, &(input, rid)) in self.rows[TableId::EventMap as usize].iter().enumerate() {
+            let row = self.inputs[input].metadata.row::<EventMapRow>(rid)?;
+            let parent = self.scope(input, TableId::TypeDef.token(row.parent))?;
+            let row = EventMapRow {
+                parent: token_rid(parent),
+                event_list: self.list_starts[TableId::Event as usize][index],
+            };
+            self.builder.add(&row);
+        }
+        for &(input, rid) in &self.rows[TableId::Event as usize] {
+            let metadata = &self.inputs[input].metadata;
+            let row = metadata.row::<EventRow>(rid)?;
+            let row = EventRow {
+                flags: row.flags,
+                name: self.builder.string(metadata.string(row.name)?),
+                event_type: self.scope(input, row.event_type)?,
+            };
+            self.builder.add(&row);
+        }
+        Ok(())
+    }
+
+    /// Copies a method's body with its tokens renumbered and returns its
+    /// new RVA, or 0 for a method without a body. Methods sharing a body
+    /// keep sharing it.
+    fn method_body(&mut self, input: usize, rid: u32) -> Result<u32> {
+        let source = &self.inputs[input];
+        let row = source.metadata.row::<MethodDefRow>(rid)?;
+        if row.rva == 0 {
+            return Ok(0);
+        }
+        if row.impl_flags & METHOD_IMPL_CODE_TYPE_MASK != 0 {
+            return Err(Error::BadImageFormat(format!(
+                "method {:#010x} of {} has a native body",
+                TableId::MethodDef.token(rid),
+                source.identity.name
+            )));
+        }
+        if let Some(&rva) = self.bodies.get(&(input, row.rva)) {
+            return Ok(rva);
+        }
+        let Some(mut body) = source.image.method_body(&source.metadata, rid)? else {
+            return Ok(0);
+        };
+        for instruction in &mut body.instructions {
+            if let Operand::Token(token) = &mut instruction.operand {
+                *token = self.il_token(input, *token)?;
+            }
+        }
+        for handler in &mut body.exception_handlers {
+            if let ExceptionHandlerKind::Catch(token) = &mut handler.kind {
+                *token = self.il_token(input, *token)?;
+            }
+        }
+        if body.local_var_sig != 0 {
+            body.local_var_sig = self.il_token(input, body.local_var_sig)?;
+        }
+        let rva = self.pe.add_method_body(&body.to_bytes()?);
+        self.bodies.insert((input, row.rva), rva);
+        Ok(rva)
+    }
+
+    /// Returns the new token of a token in an input's IL, copying user
+    /// strings into the merged `#US` heap.
+    fn il_token(&mut self, input: usize, token: u32) -> Result<u32> {
+        if token & 0xFF00_0000 == TOKEN_TYPE_STRING {
+            let units = self.inputs[input]
+                .metadata
+                .user_string_units(token_rid(token))?;
+            return Ok(TOKEN_TYPE_STRING | self.builder.user_string_units(units));
+        }
+        self.token(input, token).ok_or_else(|| {
+            Error::BadMethodBody(format!(
+                "IL of {} refers to {token:#010x}, which is not part of the merged assembly",
+                self.inputs[input].identity.name
+            ))
+        })
+    }
+
+    /// Copies an input's row with its heap entries carried over and its
+    /// references renumbered. Returns the new row id, or `None`, adding
+    /// nothing, if the row refers to a row that is not part of the merged
+    /// assembly.
+    fn copy_row(&mut self, input: usize, table: TableId, rid: u32) -> Result<Option<u32>> {
+        let metadata = &self.inputs[input].metadata;
+        let tokens = &self.tokens[input];
+        let mut values = metadata.columns(table, rid)?;
+        for (column, (spec, value)) in table.columns().iter().zip(&mut values).enumerate() {
+            *value = match spec.ty {
+                ColumnType::U16 | ColumnType::U32 => *value,
+                ColumnType::Strings => self.builder.string(metadata.string(*value)?),
+                ColumnType::Guid => match metadata.guid(*value)? {
+                    Some(guid) => self.builder.guid(guid),
+                    None => 0,
+                },
+                ColumnType::Blob => {
+                    let blob = metadata.blob(*value)?;
+                    match SIGNATURE_COLUMNS
+                        .iter()
+                        .find(|&&(t, c, _)| t == table && c == column)
+                    {
+                        Some(&(_, _, is_type)) if !blob.is_empty() => {
+                            let signature = remap_tokens(tokens, blob, is_type)?;
+                            self.builder.blob(&signature)
+                        }
+                        _ => self.builder.blob(blob),
+                    }
+                }
+                ColumnType::Table(target) => match map_token(tokens, target.token(*value)) {
+                    Some(token) => token_rid(token),
+                    None => return Ok(None),
+                },
+                ColumnType::Coded(_) => match map_token(tokens, *value) {
+                    Some(token) => token,
+                    None => return Ok(None),
+                },
+            };
+        }
+        let new_rid = self.builder.add_row(table, values)?;
+        self.tokens[input][table as usize][rid as usize - 1] = table.token(new_rid);
+        Ok(Some(new_rid))
+    }
+
+    /// Writes the `FieldRva` rows with the fields' initial data.
+    fn write_field_data(&mut self) -> Result<()> {
+        for input in 0..self.inputs.len() {
+            let source = &self.inputs[input];
+            for rid in 1..=source.metadata.row_count(TableId::FieldRva) {
+                let row = source.metadata.row::<FieldRvaRow>(rid)?;
+                let Some(field) = self.token(input, TableId::Field.token(row.field)) else {
+                    continue;
+                };
+                let data = source
+                    .image
+                    .field_data(&source.metadata, row.field)?
+                    .ok_or_else(|| {
+                        Error::BadImageFormat(format!(
+                            "field {:#010x} of {} has no data",
+                            TableId::Field.token(row.field),
+                            source.identity.name
+                        ))
+                    })?;
+                let row = FieldRvaRow {
+                    rva: self.pe.add_field_data(data),
+                    field: token_rid(field),
+                };
+                self.builder.add(&row);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the type forwarders and exported types, dropping those of
+    /// types the merged assembly now defines.
+    fn write_exported_types(&mut self) -> Result<()> {
+        let mut forwarded: HashMap<(String, String), (u32, u32)> = HashMap::new();
+        for input in 0..self.inputs.len() {
+            for rid in 1..=self.inputs[input].metadata.row_count(TableId::ExportedType) {
+                let metadata = &self.inputs[input].metadata;
+                let row = metadata.row::<ExportedTypeRow>(rid)?;
+                let implementation = match TableId::from_token(row.implementation) {
+                    Some((TableId::AssemblyRef, scope)) if scope != 0 => {
+                        if self.merged_refs[input][scope as usize - 1].is_some() {
+                            continue;
+                        }
+                        self.scope(input, row.implementation)?
+                    }
+                    Some((TableId::ExportedType, _)) => {
+                        match self.token(input, row.implementation) {
+                            Some(parent) => parent,
+                            None => continue,
+                        }
+                    }
+                    _ => continue,
+                };
+                let key = (
+                    metadata.string(row.namespace)?.to_string(),
+                    metadata.string(row.name)?.to_string(),
+                );
+                let is_top_level = TableId::from_token(implementation)
+                    .is_none_or(|(table, _)| table != TableId::ExportedType);
+                if is_top_level {
+                    let defined = self.type_names.contains(&key);
+                    match forwarded.get(&key) {
+                        Some(&(new_rid, target)) if !defined && target == implementation => {
+                            self.tokens[input][TableId::ExportedType as usize][rid as usize - 1] =
+                                TableId::ExportedType.token(new_rid);
+                            continue;
+                        }
+                        None if !defined => {}
+                        _ => {
+                            let message = if defined {
+                                "the merged assembly defines a type with the same name; \
+                                 the forwarder is dropped"
+                            } else {
+                                "another assembly forwards the type elsewhere; \
+                                 the forwarder is dropped"
+                            };
+                            self.report.conflict(
+                                MergeConflictKind::DuplicateExportedType,
+                                false,
+                                &self.inputs[input].identity.name,
+                                full_name(&key.0, &key.1),
+                                message,
+                            );
+                            continue;
+                        }
+                    }
+                }
+                let new_row = ExportedTypeRow {
+                    name: self.builder.string(&key.1),
+                    namespace: self.builder.string(&key.0),
+                    implementation,
+                    ..row
+                };
+                let new_rid = self.builder.add(&new_row);
+                if is_top_level {
+                    forwarded.insert(key, (new_rid, implementation));
+                }
+                self.tokens[input][TableId::ExportedType as usize][rid as usize - 1] =
+                    TableId::ExportedType.token(new_rid);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the managed resources, copying embedded ones. A resource
+    /// whose name an earlier assembly already used is dropped, silently if
+    /// both have the same content.
+    fn write_resources(&mut self) -> Result<()> {
+        let mut written: HashMap<String, (u32, usize, u32)> = HashMap::new();
+        for input in 0..self.inputs.len() {
+            let source = &self.inputs[input];
+            for rid in 1..=source.metadata.row_count(TableId::ManifestResource) {
+                let row = source.metadata.row::<ManifestResourceRow>(rid)?;
+                if let Some((TableId::AssemblyRef, scope)) = TableId::from_token(row.implementation)
+                    && scope != 0
+                    && self.merged_refs[input][scope as usize - 1].is_some()
+                {
+                    continue;
+                }
+                let implementation = self.scope(input, row.implementation)?;
+                let data = embedded_resource(source, &row)?;
+                let name = source.metadata.string(row.name)?.to_string();
+                if let Some(&(new_rid, earlier, earlier_rid)) = written.get(&name) {
+                    let other = &self.inputs[earlier];
+                    let other_row = other.metadata.row::<ManifestResourceRow>(earlier_rid)?;
+                    let is_same = match data {
+                        Some(data) => embedded_resource(other, &other_row)? == Some(data),
+                        None => {
+                            self.scope(earlier, other_row.implementation)? == implementation
+                                && other_row.offset == row.offset
+                        }
+                    };
+                    if is_same {
+                        self.tokens[input][TableId::ManifestResource as usize][rid as usize - 1] =
+                            TableId::ManifestResource.token(new_rid);
+                    } else {
+                        self.report.conflict(
+                            MergeConflictKind::DuplicateResource,
+                            false,
+                            &source.identity.name,
+                            name,
+                            format!(
+                                "{} has a different resource with the same name; \
+                                 this one is dropped",
+                                other.identity.name
+                            ),
+                        );
+                    }
+                    continue;
+                }
+                let new_row = ManifestResourceRow {
+                    offset: match data {
+                        Some(data) => self.pe.add_resource(data),
+                        None => row.offset,
+                    },
+                    flags: row.flags,
+                    name: self.builder.string(&name),
+                    implementation,
+                };
+                let new_rid = self.builder.add(&new_row);
+                written.insert(name, (new_rid, input, rid));
+                self.tokens[input][TableId::ManifestResource as usize][rid as usize - 1] =
+                    TableId::ManifestResource.token(new_rid);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the custom attributes. Assembly- and module-level attributes
+    /// come from the primary assembly and, if asked to, from the others
+    /// when they do not conflict; `InternalsVisibleToAttribute`s naming a
+    /// merged assembly are dropped.
+    fn write_custom_attributes(&mut self) -> Result<()> {
+        let mut manifest_attributes: Vec<(u32, String, u32, Vec<u8>)> = Vec::new();
+        for input in 0..self.inputs.len() {
+            for rid in 1..=self.inputs[input]
+                .metadata
+                .row_count(TableId::CustomAttribute)
+            {
+                let metadata = &self.inputs[input].metadata;
+                let row = metadata.row::<CustomAttributeRow>(rid)?;
+                let (Some(parent), Some(constructor)) =
+                    (self.token(input, row.parent), self.token(input, row.ty))
+                else {
+                    continue;
+                };
+                let value = self.attribute_value(input, rid)?;
+                if matches!(
+                    TableId::from_token(row.parent),
+                    Some((TableId::Assembly | TableId::Module, _))
+                ) {
+                    let type_name = metadata.custom_attribute_type_name(rid)?;
+                    let is_friend = type_name == INTERNALS_VISIBLE_TO_ATTRIBUTE;
+                    if is_friend && self.names_merged_assembly(input, rid)? {
+                        continue;
+                    }
+                    if input > 0 {
+                        if !self.merger.merge_assembly_attributes {
+                            continue;
+                        }
+                        let same_type: Vec<_> = manifest_attributes
+                            .iter()
+                            .filter(|attribute| attribute.0 == parent && attribute.1 == type_name)
+                            .collect();
+                        if same_type
+                            .iter()
+                            .any(|attribute| attribute.2 == constructor && attribute.3 == value)
+                        {
+                            continue;
+                        }
+                        if !is_friend && !same_type.is_empty() {
+                            self.report.conflict(
+                                MergeConflictKind::AttributeConflict,
+                                false,
+                                &self.inputs[input].identity.name,
+                                type_name,
+                                "the merged assembly already has this attribute with another \
+                                 value; this one is dropped",
+                            );
+                            continue;
+                        }
+                    }
+                    manifest_attributes.push((parent, type_name, constructor, value.clone()));
+                }
+                let row = CustomAttributeRow {
+                    parent,
+                    ty: constructor,
+                    value: self.builder.blob(&value),
+                };
+                self.builder.add(&row);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if an `InternalsVisibleToAttribute` names one of the
+    /// merged assemblies, whose internals are now the merged assembly's own.
+    fn names_merged_assembly(&self, input: usize, rid: u32) -> Result<bool> {
+        let value = self.inputs[input].metadata.custom_attribute_value(rid)?;
+        let Some(Some(friend)) = value.fixed_args.first().map(|arg| arg.as_str()) else {
+            return Ok(false);
+        };
+        let friend = friend.split(',').next().unwrap_or_default().trim();
+        Ok(self
+            .inputs
+            .iter()
+            .any(|input| input.identity.name.eq_ignore_ascii_case(friend)))
+    }
+
+    /// Returns a custom attribute's value blob with the names of its
+    /// `System.Type` arguments and enum types rewritten to name merged
+    /// types in the merged assembly.
+    fn attribute_value(&self, input: usize, rid: u32) -> Result<Vec<u8>> {
+        let metadata = &self.inputs[input].metadata;
+        let blob = metadata.blob(metadata.row::<CustomAttributeRow>(rid)?.value)?;
+        // Values that cannot be decoded, such as those with enum arguments
+        // whose underlying type is unknown, are kept as they are.
+        let Ok(names) = metadata.custom_attribute_type_names(rid) else {
+            return Ok(blob.to_vec());
+        };
+        let mut value = Vec::with_capacity(blob.len());
+        let mut copied = 0;
+        for range in names {
+            let mut cursor = Cursor::new(&blob[range.clone()]);
+            let len = cursor.compressed_u32()? as usize;
+            let Ok(name) = std::str::from_utf8(cursor.bytes(len)?) else {
+                continue;
+            };
+            let mut map =
+                |assembly: Option<&str>, base: &str| self.map_type_name(input, assembly, base);
+            if let Some(name) = rewrite_type_name(name, &mut map) {
+                value.extend_from_slice(&blob[copied..range.start]);
+                write_compressed_u32(&mut value, name.len() as u32);
+                value.extend_from_slice(name.as_bytes());
+                copied = range.end;
+            }
+        }
+        value.extend_from_slice(&blob[copied..]);
+        Ok(value)
+    }
+
+    /// Maps a serialized type name of a type defined in `input`, if it has
+    /// no assembly part, or in the assembly it names to its name in the
+    /// merged assembly. Returns `None` for types of other assemblies.
+    fn map_type_name(
+        &self,
+        input: usize,
+        assembly: Option<&str>,
+        base: &str,
+    ) -> Option<(String, Option<String>)> {
+        let target = match assembly {
+            Some(assembly) => {
+                let simple = assembly.split(',').next().unwrap_or_default().trim();
+                self.inputs
+                    .iter()
+                    .position(|input| input.identity.name.eq_ignore_ascii_case(simple))?
+            }
+            None => input,
+        };
+        let top_level = base.split('+').next().unwrap_or(base);
+        let renamed = self.renamed[target]
+            .get(top_level)
+            .map(|new_name| format!("{new_name}{}", &base[top_level.len()..]));
+        match assembly {
+            Some(_) => Some((
+                renamed.unwrap_or_else(|| base.to_string()),
+                Some(self.output_name.clone()),
+            )),
+            None => renamed.map(|name| (name, None)),
+        }
+    }
+}
+
+/// Returns the new token of a row from an input's new tokens: the token
+/// itself if it is nil, `None` if the row is not part of the merged
+/// assembly.
+fn map_token(tokens: &[Vec<u32>], token: u32) -> Option<u32> {
+    let (table, rid) = TableId::from_token(token)?;
+    if rid == 0 {
+        return Some(token);
+    }
+    tokens[table as usize]
+        .get(rid as usize - 1)
+        .copied()
+        .filter(|&token| token != 0)
+}
+
+/// Renumbers the type tokens of a signature blob with an input's new
+/// tokens.
+fn remap_tokens(tokens: &[Vec<u32>], blob: &[u8], is_type: bool) -> Result<Vec<u8>> {
+    remap_signature(blob, is_type, &mut |token| {
+        map_token(tokens, token)
+            .map(|token| (token, None))
+            .ok_or_else(|| {
+                Error::BadSignature(format!(
+                    "signature refers to {token:#010x}, which is not part of the merged assembly"
+                ))
+            })
+    })
+}
+
+/// Returns the data of an embedded resource, or `None` for a resource in
+/// another file or assembly.
+fn embedded_resource<'a>(input: &Input<'a>, row: &ManifestResourceRow) -> Result<Option<&'a [u8]>> {
+    if token_rid(row.implementation) != 0 {
+        return Ok(None);
+    }
+    input.image.managed_resource(row.offset).map(Some)
+}
+
+/// Returns `Ns.Name`, or `Name` for a type without a namespace.
+fn full_name(namespace: &str, name: &str) -> String {
+    if namespace.is_empty() {
+        name.to_string()
+    } else {
+        format!("{namespace}.{name}")
+    }
+}
+
+fn platform_name(machine: Machine, is_32bit_required: bool) -> String {
+    match machine {
+        Machine::I386 if is_32bit_required => "x86".to_string(),
+        Machine::I386 => "AnyCPU".to_string(),
+        Machine::AMD64 => "x64".to_string(),
+        Machine::ARM64 => "ARM64".to_string(),
+        Machine::ARMNT | Machine::ARM => "ARM".to_string(),
+        Machine(value) => format!("machine {value:#06x}"),
+    }
+}
@@ -0,0 +1,197 @@
+//! What an [`AssemblyMerger`](super::AssemblyMerger) changed, and the
+//! conflicts it met.
+
+use std::fmt;
+
+use serde_json::{Value, json};
+
+/// A disagreement between the merged assemblies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MergeConflictKind {
+    /// Two assemblies define a public type with the same name.
+    DuplicateType,
+    /// Two assemblies define a global field or method with the same name
+    /// and signature.
+    DuplicateGlobalMember,
+    /// An assembly needs a platform the primary assembly does not target.
+    PlatformMismatch,
+    /// Two assemblies embed different resources with the same name; the
+    /// later one is dropped.
+    DuplicateResource,
+    /// A type forwarder names a type the merged assembly already defines
+    /// or forwards; the later one is dropped.
+    DuplicateExportedType,
+    /// Two assemblies carry different values of an assembly- or
+    /// module-level attribute; the later one is dropped.
+    AttributeConflict,
+    /// A reference into a merged assembly names a type it neither defines
+    /// nor forwards.
+    UnresolvedType,
+    /// A reference to a member of a merged type matches none of its
+    /// members; it is kept as a member reference.
+    UnresolvedMember,
+    /// The assemblies reference different versions of the same assembly;
+    /// the references are unified to the highest version.
+    ReferenceVersionMismatch,
+}
+
+impl MergeConflictKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            MergeConflictKind::DuplicateType => "DuplicateType",
+            MergeConflictKind::DuplicateGlobalMember => "DuplicateGlobalMember",
+            MergeConflictKind::PlatformMismatch => "PlatformMismatch",
+            MergeConflictKind::DuplicateResource => "DuplicateResource",
+            MergeConflictKind::DuplicateExportedType => "DuplicateExportedType",
+            MergeConflictKind::AttributeConflict => "AttributeConflict",
+            MergeConflictKind::UnresolvedType => "UnresolvedType",
+            MergeConflictKind::UnresolvedMember => "UnresolvedMember",
+            MergeConflictKind::ReferenceVersionMismatch => "ReferenceVersionMismatch",
+        }
+    }
+}
+
+/// A conflict met while merging.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MergeConflict {
+    pub kind: MergeConflictKind,
+    /// Whether the conflict prevents writing the merged assembly.
+    pub blocking: bool,
+    /// The simple name of the input assembly the conflict was found in.
+    pub assembly: String,
+    /// The type, member, resource, attribute or assembly concerned.
+    pub item: String,
+    pub message: String,
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let marker = if self.blocking { "error" } else { "warning" };
+        write!(
+            f,
+            "[{marker}] {}: {}: {}",
+            self.assembly, self.item, self.message
+        )
+    }
+}
+
+/// A type renamed to avoid a clash with a type of the same name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RenamedType {
+    /// The simple name of the assembly that defines the type.
+    pub assembly: String,
+    pub old_name: String,
+    pub new_name: String,
+}
+
+impl fmt::Display for RenamedType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.assembly, self.old_name, self.new_name
+        )
+    }
+}
+
+/// What merging did to the input assemblies.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    /// The display name of the merged assembly.
+    pub assembly: String,
+    /// The display names of the input assemblies, primary first.
+    pub inputs: Vec<String>,
+    pub renamed_types: Vec<RenamedType>,
+    /// The full names of the public types made internal.
+    pub internalized_types: Vec<String>,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl MergeReport {
+    /// Returns `true` if a conflict prevents writing the merged assembly.
+    pub fn is_blocked(&self) -> bool {
+        self.conflicts.iter().any(|conflict| conflict.blocking)
+    }
+
+    /// Returns the conflicts that prevent writing the merged assembly.
+    pub fn blocking_conflicts(&self) -> impl Iterator<Item = &MergeConflict> {
+        self.conflicts.iter().filter(|conflict| conflict.blocking)
+    }
+
+    /// Returns the report as JSON: `{"assembly", "inputs", "blocked",
+    /// "renamedTypes": [...], "internalizedTypes": [...], "conflicts": [...]}`.
+    pub fn to_json(&self) -> Value {
+        let renamed: Vec<Value> = self
+            .renamed_types
+            .iter()
+            .map(|renamed| {
+                json!({
+                    "assembly": renamed.assembly,
+                    "oldName": renamed.old_name,
+                    "newName": renamed.new_name,
+                })
+            })
+            .collect();
+        let conflicts: Vec<Value> = self
+            .conflicts
+            .iter()
+            .map(|conflict| {
+                json!({
+                    "kind": conflict.kind.name(),
+                    "blocking": conflict.blocking,
+                    "assembly": conflict.assembly,
+                    "item": conflict.item,
+                    "message": conflict.message,
+                })
+            })
+            .collect();
+        json!({
+            "assembly": self.assembly,
+            "inputs": self.inputs,
+            "blocked": self.is_blocked(),
+            "renamedTypes": renamed,
+            "internalizedTypes": self.internalized_types,
+            "conflicts": conflicts,
+        })
+    }
+
+    pub(super) fn conflict(
+        &mut self,
+        kind: MergeConflictKind,
+        blocking: bool,
+        assembly: &str,
+        item: impl Into<String>,
+        message: impl Into<String>,
+    ) {
+        self.conflicts.push(MergeConflict {
+            kind,
+            blocking,
+            assembly: assembly.to_string(),
+            item: item.into(),
+            message: message.into(),
+        });
+    }
+}
+
+/// Writes the text report: a summary line, the inputs, then one line per
+/// renamed type and per conflict.
+impl fmt::Display for MergeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} <- {}", self.assembly, self.inputs.join("; "))?;
+        let blocking = self.blocking_conflicts().count();
+        writeln!(
+            f,
+            "{} renamed, {} internalized, {} conflict(s), {blocking} blocking",
+            self.renamed_types.len(),
+            self.internalized_types.len(),
+            self.conflicts.len()
+        )?;
+        for renamed in &self.renamed_types {
+            writeln!(f, "renamed {renamed}")?;
+        }
+        for conflict in &self.conflicts {
+            writeln!(f, "{conflict}")?;
+        }
+        Ok(())
+    }
+}
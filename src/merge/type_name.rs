@@ -0,0 +1,136 @@
+//! Rewriting serialized type names, the form custom attributes store
+//! `System.Type` arguments in:
+//! ``Ns.Outer+Inner`1[[Arg, ArgAssembly]][], Assembly, Version=1.0.0.0``.
+
+/// Maps the assembly part of a type name, if it has one, and the type name
+/// without generic arguments or modifiers (``Ns.Outer+Inner`1``) to a new
+/// type name and assembly part, or returns `None` to keep both.
+pub(super) type TypeNameMap<'a> =
+    dyn FnMut(Option<&str>, &str) -> Option<(String, Option<String>)> + 'a;
+
+/// Rewrites an assembly-qualified type name and the names of its generic
+/// arguments through `map`. Returns `None` if nothing changed.
+pub(super) fn rewrite_type_name(name: &str, map: &mut TypeNameMap) -> Option<String> {
+    let (type_part, assembly) = match find_top_level(name, |c| c == ',') {
+        Some(comma) => (&name[..comma], Some(&name[comma + 1..])),
+        None => (name, None),
+    };
+    let base_end =
+        find_top_level(type_part, |c| matches!(c, '[' | '*' | '&')).unwrap_or(type_part.len());
+    let (base, mut suffix) = type_part.split_at(base_end);
+
+    let mut changed = false;
+    let mut arguments = String::new();
+    if is_generic_argument_list(suffix)
+        && let Some(close) = matching_bracket(suffix)
+    {
+        arguments.push('[');
+        let list = &suffix[1..close];
+        let mut start = 0;
+        let ends = find_all_top_level(list, ',')
+            .into_iter()
+            .chain([list.len()]);
+        for (index, end) in ends.enumerate() {
+            if index > 0 {
+                arguments.push(',');
+            }
+            let argument = &list[start..end];
+            let trimmed = argument.trim();
+            let rewritten =
+                if trimmed.len() >= 2 && trimmed.starts_with('[') && trimmed.ends_with(']') {
+                    rewrite_type_name(&trimmed[1..trimmed.len() - 1], map)
+                        .map(|name| format!("[{name}]"))
+                } else {
+                    rewrite_type_name(trimmed, map)
+                };
+            match rewritten {
+                Some(rewritten) => {
+                    changed = true;
+                    arguments.push_str(&rewritten);
+                }
+                None => arguments.push_str(argument),
+            }
+            start = end + 1;
+        }
+        arguments.push(']');
+        suffix = &suffix[close + 1..];
+    }
+
+    let mapped = map(assembly.map(str::trim), base);
+    if !changed && mapped.is_none() {
+        return None;
+    }
+    let (new_base, new_assembly) = match mapped {
+        Some((base, assembly)) => (base, assembly.map(|assembly| format!(", {assembly}"))),
+        None => (
+            base.to_string(),
+            assembly.map(|assembly| format!(",{assembly}")),
+        ),
+    };
+    Some(format!(
+        "{new_base}{arguments}{suffix}{}",
+        new_assembly.unwrap_or_default()
+    ))
+}
+
+/// Returns `true` if `suffix` starts with a generic argument list rather
+/// than an array rank such as `[]`, `[,]` or `[*]`.
+fn is_generic_argument_list(suffix: &str) -> bool {
+    let mut chars = suffix.chars();
+    chars.next() == Some('[') && !matches!(chars.next(), None | Some(']' | ',' | '*'))
+}
+
+/// Returns the index of the `]` closing the `[` that `s` starts with.
+fn matching_bracket(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+    for (index, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Returns the index of the first character of `s` outside brackets that
+/// satisfies `is_match`, skipping escaped characters.
+fn find_top_level(s: &str, is_match: impl Fn(char) -> bool) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut escaped = false;
+    for (index, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        if depth == 0 && is_match(c) {
+            return Some(index);
+        }
+        match c {
+            '\\' => escaped = true,
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Returns the indices of every `separator` of `s` outside brackets.
+fn find_all_top_level(s: &str, separator: char) -> Vec<usize> {
+    let mut found = Vec::new();
+    let mut start = 0;
+    while let Some(index) = find_top_level(&s[start..], |c| c == separator) {
+        found.push(start + index);
+        start += index + separator.len_utf8();
+    }
+    found
+}
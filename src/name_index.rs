@@ -0,0 +1,547 @@
+//! A persistent, memory-mapped index of type and member names across many
+//! assemblies.
+//!
+//! `IMetaDataImport::FindTypeDefByName` searches a single scope. The index
+//! maps fully qualified names — `Namespace.Type`, `Outer+Nested` and
+//! `Type::Member` — to the assembly file and token that define them, so a
+//! query over thousands of assemblies touches only the pages it needs.
+//!
+//! File format (little-endian):
+//!
+//! | Offset | Contents |
+//! |--------|----------|
+//! | 0      | magic `MSCRNIDX`, format version, file and entry counts, table offsets |
+//! | files  | per file: path (string offset, length) and module MVID |
+//! | entries| per name, sorted by UTF-8 bytes: name (offset, length), file index, token |
+//! | strings| UTF-8 string data |
+
+use std::fs::File;
+use std::ops::Deref;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::error::{Error, Result};
+use crate::reader::{
+    EventRow, FieldRow, MetadataReader, MethodDefRow, ModuleRow, PeImage, PropertyRow, TableId,
+};
+
+const MAGIC: &[u8; 8] = b"MSCRNIDX";
+const FORMAT_VERSION: u32 = 1;
+const HEADER_SIZE: usize = 32;
+const FILE_RECORD_SIZE: usize = 24;
+const ENTRY_RECORD_SIZE: usize = 16;
+
+/// A file recorded in a [`NameIndex`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexedFile<'a> {
+    pub path: &'a Path,
+    /// The MVID of the file's module, used to detect changes.
+    pub mvid: [u8; 16],
+}
+
+/// A name found by a [`NameIndex`] query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NameMatch<'a> {
+    /// The fully qualified name, e.g. `System.String::Concat`.
+    pub name: &'a str,
+    /// The assembly file defining the name.
+    pub path: &'a Path,
+    /// A `TypeDef`, `MethodDef`, `Field`, `Property` or `Event` token.
+    pub token: u32,
+}
+
+enum Backing {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl Deref for Backing {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Backing::Mapped(map) => map,
+            Backing::Owned(data) => data,
+        }
+    }
+}
+
+/// A read-only name index, memory-mapped from disk.
+///
+/// Names are compared case-sensitively, like the runtime compares type
+/// names.
+pub struct NameIndex {
+    data: Backing,
+    file_count: usize,
+    entry_count: usize,
+    files_offset: usize,
+    entries_offset: usize,
+}
+
+impl std::fmt::Debug for NameIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NameIndex")
+            .field("files", &self.file_count)
+            .field("entries", &self.entry_count)
+            .finish_non_exhaustive()
+    }
+}
+
+impl NameIndex {
+    /// Maps the index file at `path`.
+    ///
+    /// The file must not be modified while it is mapped;
+    /// [`NameIndexBuilder::write`] replaces the file instead of writing to
+    /// it. Drop the index before writing a new one to the same path.
+    pub fn open(path: impl AsRef<Path>) -> Result<NameIndex> {
+        let file = File::open(path)?;
+        // SAFETY: this crate never writes an index in place; `write` creates
+        // a new file and renames it over the old one, which leaves existing
+        // mappings of the old file intact. Writers outside this crate must
+        // do the same.
+        let map = unsafe { Mmap::map(&file)? };
+        NameIndex::from_backing(Backing::Mapped(map))
+    }
+
+    /// Reads an index from bytes produced by [`NameIndexBuilder::to_bytes`].
+    pub fn from_bytes(data: Vec<u8>) -> Result<NameIndex> {
+        NameIndex::from_backing(Backing::Owned(data))
+    }
+
+    fn from_backing(data: Backing) -> Result<NameIndex> {
+        if data.len() < HEADER_SIZE || &data[..8] != MAGIC {
+            return Err(Error::BadIndex("missing MSCRNIDX header".into()));
+        }
+        let version = read_u32(&data, 8);
+        if version != FORMAT_VERSION {
+            return Err(Error::BadIndex(format!(
+                "unsupported format version {version}"
+            )));
+        }
+        let index = NameIndex {
+            file_count: read_u32(&data, 12) as usize,
+            entry_count: read_u32(&data, 16) as usize,
+            files_offset: read_u32(&data, 20) as usize,
+            entries_offset: read_u32(&data, 24) as usize,
+            data,
+        };
+        let files_end = index.file_count * FILE_RECORD_SIZE + index.files_offset;
+        let entries_end = index.entry_count * ENTRY_RECORD_SIZE + index.entries_offset;
+        if files_end > index.data.len() || entries_end > index.data.len() {
+            return Err(Error::BadIndex(
+                "tables extend past the end of the file".into(),
+            ));
+        }
+        for i in 0..index.file_count {
+            index.file(i)?;
+        }
+        Ok(index)
+    }
+
+    /// Returns the number of indexed names.
+    pub fn len(&self) -> usize {
+        self.entry_count
+    }
+
+    /// Returns `true` if the index contains no names.
+    pub fn is_empty(&self) -> bool {
+        self.entry_count == 0
+    }
+
+    /// Returns the indexed files.
+    pub fn files(&self) -> impl Iterator<Item = IndexedFile<'_>> {
+        (0..self.file_count).filter_map(|i| self.file(i).ok())
+    }
+
+    /// Returns the MVID recorded for `path`.
+    pub fn mvid(&self, path: &Path) -> Option<[u8; 16]> {
+        self.files()
+            .find(|file| file.path == path)
+            .map(|file| file.mvid)
+    }
+
+    /// Returns the definitions of exactly `name`.
+    pub fn find(&self, name: &str) -> Result<Vec<NameMatch<'_>>> {
+        let start = self.lower_bound(name.as_bytes())?;
+        let mut found = Vec::new();
+        for i in start..self.entry_count {
+            let entry = self.entry(i)?;
+            if entry.name != name {
+                break;
+            }
+            found.push(entry);
+        }
+        Ok(found)
+    }
+
+    /// Returns the names starting with `prefix`, in sorted order.
+    pub fn find_prefix(&self, prefix: &str) -> Result<Vec<NameMatch<'_>>> {
+        let start = self.lower_bound(prefix.as_bytes())?;
+        let mut found = Vec::new();
+        for i in start..self.entry_count {
+            let entry = self.entry(i)?;
+            if !entry.name.starts_with(prefix) {
+                break;
+            }
+            found.push(entry);
+        }
+        Ok(found)
+    }
+
+    /// Returns the names matching a wildcard pattern, where `*` matches any
+    /// run of characters and `?` a single character. The literal prefix of
+    /// the pattern narrows the search.
+    ///
+    /// ```
+    /// use mscoree::{NameIndex, NameIndexBuilder};
+    ///
+    /// let mut builder = NameIndexBuilder::new();
+    /// builder.add_names(
+    ///     "Lib.dll",
+    ///     [0; 16],
+    ///     vec![
+    ///         ("Lib.Widget".into(), 0x0200_0002),
+    ///         ("Lib.Widget::Draw".into(), 0x0600_0001),
+    ///         ("Lib.Window::Draw".into(), 0x0600_0002),
+    ///     ],
+    /// )?;
+    /// let index = NameIndex::from_bytes(builder.to_bytes())?;
+    /// let names: Vec<_> = index.find_wildcard("Lib.Wi*::Dr?w")?.iter().map(|m| m.name).collect();
+    /// assert_eq!(names, ["Lib.Widget::Draw", "Lib.Window::Draw"]);
+    /// assert_eq!(index.find_prefix("Lib.Widget")?.len(), 2);
+    /// # Ok::<(), mscoree::Error>(())
+    /// ```
+    pub fn find_wildcard(&self, pattern: &str) -> Result<Vec<NameMatch<'_>>> {
+        let literal = pattern
+            .find(['*', '?'])
+            .map_or(pattern, |end| &pattern[..end]);
+        let mut found = self.find_prefix(literal)?;
+        found.retain(|entry| wildcard_match(pattern.as_bytes(), entry.name.as_bytes()));
+        Ok(found)
+    }
+
+    /// Finds the first entry whose name is not less than `key`.
+    fn lower_bound(&self, key: &[u8]) -> Result<usize> {
+        let (mut low, mut high) = (0, self.entry_count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.entry(mid)?.name.as_bytes() < key {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(low)
+    }
+
+    fn file(&self, i: usize) -> Result<IndexedFile<'_>> {
+        let record = self.files_offset + i * FILE_RECORD_SIZE;
+        let path = self.string(
+            read_u32(&self.data, record),
+            read_u32(&self.data, record + 4),
+        )?;
+        let mut mvid = [0; 16];
+        mvid.copy_from_slice(&self.data[record + 8..record + 24]);
+        Ok(IndexedFile {
+            path: Path::new(path),
+            mvid,
+        })
+    }
+
+    fn entry(&self, i: usize) -> Result<NameMatch<'_>> {
+        let record = self.entries_offset + i * ENTRY_RECORD_SIZE;
+        let name = self.string(
+            read_u32(&self.data, record),
+            read_u32(&self.data, record + 4),
+        )?;
+        let file = read_u32(&self.data, record + 8) as usize;
+        if file >= self.file_count {
+            return Err(Error::BadIndex(format!("entry {i} refers to file {file}")));
+        }
+        Ok(NameMatch {
+            name,
+            path: self.file(file)?.path,
+            token: read_u32(&self.data, record + 12),
+        })
+    }
+
+    fn string(&self, offset: u32, len: u32) -> Result<&str> {
+        let (offset, len) = (offset as usize, len as usize);
+        let bytes = offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or_else(|| Error::BadIndex(format!("string at {offset:#x} is out of range")))?;
+        std::str::from_utf8(bytes)
+            .map_err(|_| Error::BadIndex(format!("string at {offset:#x} is not valid UTF-8")))
+    }
+}
+
+/// The names of one file, held by a [`NameIndexBuilder`].
+#[derive(Debug)]
+struct FileNames {
+    /// The path, which is stored as UTF-8 in the index.
+    path: String,
+    mvid: [u8; 16],
+    names: Vec<(String, u32)>,
+}
+
+/// Builds or incrementally updates a [`NameIndex`].
+///
+/// ```no_run
+/// use mscoree::{NameIndex, NameIndexBuilder};
+///
+/// let mut builder = match NameIndex::open("names.idx") {
+///     Ok(index) => NameIndexBuilder::from_index(&index)?,
+///     Err(_) => NameIndexBuilder::new(),
+/// };
+/// for entry in std::fs::read_dir("lib")? {
+///     // Files whose MVID is unchanged are not re-read.
+///     builder.add_file(entry?.path())?;
+/// }
+/// builder.retain_existing();
+/// builder.write("names.idx")?;
+/// # Ok::<(), mscoree::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct NameIndexBuilder {
+    files: Vec<FileNames>,
+}
+
+impl NameIndexBuilder {
+    /// Creates an empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a builder holding the contents of an existing index.
+    pub fn from_index(index: &NameIndex) -> Result<Self> {
+        let mut files: Vec<FileNames> = (0..index.file_count)
+            .map(|i| {
+                let file = index.file(i)?;
+                Ok(FileNames {
+                    path: utf8_path(file.path)?.to_owned(),
+                    mvid: file.mvid,
+                    names: Vec::new(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        for i in 0..index.entry_count {
+            let record = index.entries_offset + i * ENTRY_RECORD_SIZE;
+            let file = read_u32(&index.data, record + 8) as usize;
+            let entry = index.entry(i)?;
+            files[file]
+                .names
+                .push((entry.name.to_string(), entry.token));
+        }
+        Ok(NameIndexBuilder { files })
+    }
+
+    /// Indexes the assembly at `path`. Returns `false` without reading the
+    /// names again if the file is already indexed with the same MVID.
+    pub fn add_file(&mut self, path: impl AsRef<Path>) -> Result<bool> {
+        let path = path.as_ref();
+        let metadata = PeImage::open(path)?.metadata()?;
+        let mvid = module_mvid(&metadata)?;
+        if self
+            .files
+            .iter()
+            .any(|file| Path::new(&file.path) == path && file.mvid == mvid)
+        {
+            return Ok(false);
+        }
+        self.add_names(path, mvid, names(&metadata)?)?;
+        Ok(true)
+    }
+
+    /// Indexes `names` for `path`, replacing any names recorded for it.
+    ///
+    /// Paths are stored as UTF-8; a path that is not valid Unicode is
+    /// rejected with [`Error::BadIndex`].
+    pub fn add_names(
+        &mut self,
+        path: impl AsRef<Path>,
+        mvid: [u8; 16],
+        names: Vec<(String, u32)>,
+    ) -> Result<()> {
+        let path = utf8_path(path.as_ref())?.to_owned();
+        self.files.retain(|file| file.path != path);
+        self.files.push(FileNames { path, mvid, names });
+        Ok(())
+    }
+
+    /// Removes a file from the index. Returns `true` if it was indexed.
+    pub fn remove_file(&mut self, path: &Path) -> bool {
+        let before = self.files.len();
+        self.files.retain(|file| Path::new(&file.path) != path);
+        self.files.len() != before
+    }
+
+    /// Removes files that no longer exist. Returns the number removed.
+    pub fn retain_existing(&mut self) -> usize {
+        let before = self.files.len();
+        self.files.retain(|file| Path::new(&file.path).is_file());
+        before - self.files.len()
+    }
+
+    /// Serializes the index.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut entries: Vec<(&str, u32, u32)> = self
+            .files
+            .iter()
+            .enumerate()
+            .flat_map(|(i, file)| {
+                file.names
+                    .iter()
+                    .map(move |(name, token)| (name.as_str(), i as u32, *token))
+            })
+            .collect();
+        entries.sort_unstable();
+
+        let files_offset = HEADER_SIZE;
+        let entries_offset = files_offset + self.files.len() * FILE_RECORD_SIZE;
+        let strings_offset = entries_offset + entries.len() * ENTRY_RECORD_SIZE;
+        let mut out = Vec::with_capacity(strings_offset);
+        out.extend_from_slice(MAGIC);
+        for value in [
+            FORMAT_VERSION,
+            self.files.len() as u32,
+            entries.len() as u32,
+            files_offset as u32,
+            entries_offset as u32,
+            strings_offset as u32,
+        ] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut strings = Vec::new();
+        let mut add_string = |s: &str| {
+            let offset = strings_offset + strings.len();
+            strings.extend_from_slice(s.as_bytes());
+            (offset as u32, s.len() as u32)
+        };
+        for file in &self.files {
+            let (offset, len) = add_string(&file.path);
+            out.extend_from_slice(&offset.to_le_bytes());
+            out.extend_from_slice(&len.to_le_bytes());
+            out.extend_from_slice(&file.mvid);
+        }
+        let mut previous: Option<(&str, u32, u32)> = None;
+        for &(name, file, token) in &entries {
+            // Names repeat for overloads; share their string data.
+            let (offset, len) = match previous {
+                Some((prev, offset, len)) if prev == name => (offset, len),
+                _ => add_string(name),
+            };
+            previous = Some((name, offset, len));
+            for value in [offset, len, file, token] {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        out.extend_from_slice(&strings);
+        out
+    }
+
+    /// Writes the index to `path`, replacing the file atomically so that
+    /// readers mapping the old index are not affected.
+    ///
+    /// Drop any [`NameIndex`] this process has opened from `path` first:
+    /// Windows refuses to replace a mapped file, and elsewhere the open
+    /// index keeps reading the old contents.
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        std::fs::write(&temp, self.to_bytes())?;
+        std::fs::rename(&temp, path)?;
+        Ok(())
+    }
+}
+
+/// Returns `path` as UTF-8, the encoding paths are stored in.
+fn utf8_path(path: &Path) -> Result<&str> {
+    path.to_str()
+        .ok_or_else(|| Error::BadIndex(format!("path {} is not valid Unicode", path.display())))
+}
+
+/// Returns the MVID of the module.
+fn module_mvid(metadata: &MetadataReader) -> Result<[u8; 16]> {
+    let row = metadata.row::<ModuleRow>(1)?;
+    Ok(metadata.guid(row.mvid)?.unwrap_or_default())
+}
+
+/// Collects the type and member names defined by a module.
+fn names(metadata: &MetadataReader) -> Result<Vec<(String, u32)>> {
+    let mut names = Vec::new();
+    for type_rid in 1..=metadata.row_count(TableId::TypeDef) {
+        let token = TableId::TypeDef.token(type_rid);
+        let type_name = metadata.type_full_name(token)?;
+        let mut member =
+            |name: &str, token: u32| names.push((format!("{type_name}::{name}"), token));
+        for rid in metadata.type_def_methods(type_rid)? {
+            member(
+                metadata.string(metadata.row::<MethodDefRow>(rid)?.name)?,
+                TableId::MethodDef.token(rid),
+            );
+        }
+        for rid in metadata.type_def_fields(type_rid)? {
+            member(
+                metadata.string(metadata.row::<FieldRow>(rid)?.name)?,
+                TableId::Field.token(rid),
+            );
+        }
+        for rid in metadata.type_def_properties(type_rid)? {
+            member(
+                metadata.string(metadata.row::<PropertyRow>(rid)?.name)?,
+                TableId::Property.token(rid),
+            );
+        }
+        for rid in metadata.type_def_events(type_rid)? {
+            member(
+                metadata.string(metadata.row::<EventRow>(rid)?.name)?,
+                TableId::Event.token(rid),
+            );
+        }
+        names.push((type_name, token));
+    }
+    Ok(names)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Matches `name` against a pattern of literal bytes, `*` and `?`.
+fn wildcard_match(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            // `?` matches one character, not one byte.
+            Some(b'?') => {
+                p += 1;
+                n += 1;
+                while n < name.len() && name[n] & 0xc0 == 0x80 {
+                    n += 1;
+                }
+            }
+            Some(&c) if c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
@@ -0,0 +1,40 @@
+//! Pure-Rust readers for PE images and CLI metadata.
+//!
+//! Unlike the COM interfaces, these readers do not need the CLR and work on
+//! every platform.
+
+#[cfg(windows)]
+mod com;
+mod constant;
+mod cursor;
+mod custom_attribute;
+mod debug;
+mod enc;
+mod flags;
+mod image_info;
+mod marshal;
+mod metadata;
+mod navigation;
+mod pdb;
+mod pe;
+mod security;
+mod signature;
+mod tables;
+mod winmd;
+
+pub use constant::*;
+pub use custom_attribute::*;
+pub use debug::*;
+pub use enc::*;
+pub use image_info::*;
+pub use marshal::*;
+pub use metadata::*;
+pub use pdb::*;
+pub use pe::*;
+pub use security::*;
+pub use signature::*;
+pub use tables::*;
+pub use winmd::*;
+
+pub(crate) use cursor::Cursor;
+pub(crate) use flags::*;
@@ -0,0 +1,432 @@
+//! `IMetaDataTables2` served from a [`MetadataReader`], so that raw-table
+//! tools written against the runtime's interface also work on metadata the
+//! pure-Rust reader loaded or the writer produced.
+
+use std::ffi::{CString, c_void};
+
+use windows::Win32::Foundation::{E_INVALIDARG, S_FALSE, S_OK};
+use windows::core::{GUID, HRESULT, implement};
+
+use super::metadata::MetadataReader;
+use super::tables::{CodedIndex, ColumnType, TOKEN_TYPE_STRING, TableId};
+use crate::interfaces::{IMetaDataTables_Impl, IMetaDataTables2, IMetaDataTables2_Impl};
+
+/// `CLDB_E_INDEX_NOTFOUND`: a row id or heap index is out of range.
+const CLDB_E_INDEX_NOTFOUND: HRESULT = HRESULT(0x8013_1124_u32 as i32);
+
+/// Number of type system tables the runtime reports (`TBL_COUNT`).
+const TABLE_COUNT: u32 = 0x2D;
+
+/// `GetColumnInfo` column types (`iCodedToken` through `iBLOB`).
+const COLUMN_CODED_TOKEN: u32 = 64;
+const COLUMN_USHORT: u32 = 97;
+const COLUMN_ULONG: u32 = 99;
+const COLUMN_STRING: u32 = 101;
+const COLUMN_GUID: u32 = 102;
+const COLUMN_BLOB: u32 = 103;
+
+impl MetadataReader {
+    /// Serves the metadata through `IMetaDataTables2`.
+    ///
+    /// Column values and type codes follow the runtime's implementation:
+    /// `GetColumn` decodes coded indices into tokens and returns row ids for
+    /// table columns, and `GetColumnInfo` reports table columns by table
+    /// number and coded indices as `64 + kind`. Pointers returned by the
+    /// interface point into the reader's copy of the metadata and stay
+    /// valid while the object is alive.
+    pub fn into_metadata_tables(self) -> IMetaDataTables2 {
+        ReaderTables::new(self).into()
+    }
+}
+
+#[implement(IMetaDataTables2)]
+struct ReaderTables {
+    reader: MetadataReader,
+    table_names: Vec<CString>,
+    column_names: Vec<Vec<CString>>,
+    coded_names: Vec<CString>,
+    coded_tokens: Vec<Vec<u32>>,
+    stream_names: Vec<CString>,
+}
+
+impl ReaderTables {
+    fn new(reader: MetadataReader) -> Self {
+        let c_string = |name: &str| CString::new(name).unwrap_or_default();
+        let tables = (0..TABLE_COUNT as u8).map(TableId::from_u8);
+        Self {
+            table_names: tables
+                .clone()
+                .map(|table| c_string(table.map_or("", TableId::name)))
+                .collect(),
+            column_names: tables
+                .map(|table| {
+                    table
+                        .map_or(&[][..], TableId::columns)
+                        .iter()
+                        .map(|column| c_string(column.name))
+                        .collect()
+                })
+                .collect(),
+            coded_names: CodedIndex::ALL
+                .iter()
+                .map(|coded| c_string(&format!("{coded:?}")))
+                .collect(),
+            coded_tokens: CodedIndex::ALL
+                .iter()
+                .map(|coded| {
+                    coded
+                        .tables()
+                        .iter()
+                        .map(|table| table.map_or(TOKEN_TYPE_STRING, |t| t.token(0)))
+                        .collect()
+                })
+                .collect(),
+            stream_names: reader
+                .streams()
+                .iter()
+                .map(|stream| c_string(&stream.name))
+                .collect(),
+            reader,
+        }
+    }
+
+    fn table(&self, index: u32) -> Option<TableId> {
+        if index < TABLE_COUNT {
+            TableId::from_u8(index as u8)
+        } else {
+            None
+        }
+    }
+}
+
+/// Stores `value` through an optional out pointer.
+unsafe fn put<T>(out: *mut T, value: T) {
+    if !out.is_null() {
+        unsafe { out.write(value) };
+    }
+}
+
+/// Returns the data of the length-prefixed `#Blob` or `#US` entry at
+/// `index` and the index of the entry after it.
+fn heap_entry(heap: &[u8], index: u32) -> Option<(&[u8], u32)> {
+    let pos = index as usize;
+    let b0 = *heap.get(pos)? as usize;
+    let (start, len) = if b0 & 0x80 == 0 {
+        (pos + 1, b0)
+    } else if b0 & 0xC0 == 0x80 {
+        (pos + 2, ((b0 & 0x3F) << 8) | *heap.get(pos + 1)? as usize)
+    } else if b0 & 0xE0 == 0xC0 {
+        let rest = heap.get(pos + 1..pos + 4)?;
+        let len = ((b0 & 0x1F) << 24)
+            | ((rest[0] as usize) << 16)
+            | ((rest[1] as usize) << 8)
+            | rest[2] as usize;
+        (pos + 4, len)
+    } else {
+        return None;
+    };
+    let data = heap.get(start..start.checked_add(len)?)?;
+    Some((data, (start + len) as u32))
+}
+
+/// Stores the index after an entry, or 0 and `S_FALSE` at the end of the
+/// heap.
+unsafe fn put_next(out: *mut u32, next: u32, heap_len: usize) -> HRESULT {
+    if (next as usize) < heap_len {
+        unsafe { put(out, next) };
+        S_OK
+    } else {
+        unsafe { put(out, 0) };
+        S_FALSE
+    }
+}
+
+impl IMetaDataTables_Impl for ReaderTables_Impl {
+    unsafe fn GetStringHeapSize(&self, pcbStrings: *mut u32) -> HRESULT {
+        unsafe { put(pcbStrings, self.reader.strings_heap().len() as u32) };
+        S_OK
+    }
+
+    unsafe fn GetBlobHeapSize(&self, pcbBlobs: *mut u32) -> HRESULT {
+        unsafe { put(pcbBlobs, self.reader.blob_heap().len() as u32) };
+        S_OK
+    }
+
+    unsafe fn GetGuidHeapSize(&self, pcbGuids: *mut u32) -> HRESULT {
+        unsafe { put(pcbGuids, self.reader.guid_heap().len() as u32) };
+        S_OK
+    }
+
+    unsafe fn GetUserStringHeapSize(&self, pcbBlobs: *mut u32) -> HRESULT {
+        unsafe { put(pcbBlobs, self.reader.user_string_heap().len() as u32) };
+        S_OK
+    }
+
+    unsafe fn GetNumTables(&self, pcTables: *mut u32) -> HRESULT {
+        unsafe { put(pcTables, TABLE_COUNT) };
+        S_OK
+    }
+
+    unsafe fn GetTableIndex(&self, token: u32, pixTbl: *mut u32) -> HRESULT {
+        let Some(table) = self.table(token >> 24) else {
+            return E_INVALIDARG;
+        };
+        unsafe { put(pixTbl, table as u32) };
+        S_OK
+    }
+
+    unsafe fn GetTableInfo(
+        &self,
+        ixTbl: u32,
+        pcbRow: *mut u32,
+        pcRows: *mut u32,
+        pcCols: *mut u32,
+        piKey: *mut u32,
+        ppName: *mut *const u8,
+    ) -> HRESULT {
+        let Some(table) = self.table(ixTbl) else {
+            return E_INVALIDARG;
+        };
+        unsafe {
+            put(pcbRow, self.reader.row_size(table) as u32);
+            put(pcRows, self.reader.row_count(table));
+            put(pcCols, table.columns().len() as u32);
+            put(piKey, key_column(table).map_or(u32::MAX, |key| key as u32));
+            put(ppName, self.table_names[ixTbl as usize].as_ptr().cast());
+        }
+        S_OK
+    }
+
+    unsafe fn GetColumnInfo(
+        &self,
+        ixTbl: u32,
+        ixCol: u32,
+        poCol: *mut u32,
+        pcbCol: *mut u32,
+        pType: *mut u32,
+        ppName: *mut *const u8,
+    ) -> HRESULT {
+        let Some(table) = self.table(ixTbl) else {
+            return E_INVALIDARG;
+        };
+        let Some(column) = table.columns().get(ixCol as usize) else {
+            return E_INVALIDARG;
+        };
+        let column_type = match column.ty {
+            ColumnType::U16 => COLUMN_USHORT,
+            ColumnType::U32 => COLUMN_ULONG,
+            ColumnType::Strings => COLUMN_STRING,
+            ColumnType::Guid => COLUMN_GUID,
+            ColumnType::Blob => COLUMN_BLOB,
+            ColumnType::Table(target) => target as u32,
+            ColumnType::Coded(coded) => {
+                let kind = CodedIndex::ALL
+                    .iter()
+                    .position(|&c| c == coded)
+                    .unwrap_or(0);
+                COLUMN_CODED_TOKEN + kind as u32
+            }
+        };
+        let col = ixCol as usize;
+        unsafe {
+            put(poCol, self.reader.column_offset(table, col) as u32);
+            put(pcbCol, self.reader.column_size(table, col) as u32);
+            put(pType, column_type);
+            put(
+                ppName,
+                self.column_names[ixTbl as usize][col].as_ptr().cast(),
+            );
+        }
+        S_OK
+    }
+
+    unsafe fn GetCodedTokenInfo(
+        &self,
+        ixCdTkn: u32,
+        pcTokens: *mut u32,
+        ppTokens: *mut *const u32,
+        ppName: *mut *const u8,
+    ) -> HRESULT {
+        let Some(tokens) = self.coded_tokens.get(ixCdTkn as usize) else {
+            return E_INVALIDARG;
+        };
+        unsafe {
+            put(pcTokens, tokens.len() as u32);
+            put(ppTokens, tokens.as_ptr());
+            put(ppName, self.coded_names[ixCdTkn as usize].as_ptr().cast());
+        }
+        S_OK
+    }
+
+    unsafe fn GetRow(&self, ixTbl: u32, rid: u32, ppRow: *mut *const c_void) -> HRESULT {
+        let Some(table) = self.table(ixTbl) else {
+            return E_INVALIDARG;
+        };
+        match self.reader.row_bytes(table, rid) {
+            Ok(row) => {
+                unsafe { put(ppRow, row.as_ptr().cast()) };
+                S_OK
+            }
+            Err(_) => CLDB_E_INDEX_NOTFOUND,
+        }
+    }
+
+    unsafe fn GetColumn(&self, ixTbl: u32, ixCol: u32, rid: u32, pVal: *mut u32) -> HRESULT {
+        let Some(table) = self.table(ixTbl) else {
+            return E_INVALIDARG;
+        };
+        if ixCol as usize >= table.columns().len() {
+            return E_INVALIDARG;
+        }
+        match self.reader.column(table, rid, ixCol as usize) {
+            Ok(value) => {
+                unsafe { put(pVal, value) };
+                S_OK
+            }
+            Err(_) => CLDB_E_INDEX_NOTFOUND,
+        }
+    }
+
+    unsafe fn GetString(&self, ixString: u32, ppString: *mut *const u8) -> HRESULT {
+        let heap = self.reader.strings_heap();
+        // Only hand out strings that are terminated inside the heap.
+        match heap.get(ixString as usize..) {
+            Some(rest) if rest.contains(&0) => {
+                unsafe { put(ppString, rest.as_ptr()) };
+                S_OK
+            }
+            _ => CLDB_E_INDEX_NOTFOUND,
+        }
+    }
+
+    unsafe fn GetBlob(
+        &self,
+        ixBlob: u32,
+        pcbData: *mut u32,
+        ppData: *mut *const c_void,
+    ) -> HRESULT {
+        let Some((data, _)) = heap_entry(self.reader.blob_heap(), ixBlob) else {
+            return CLDB_E_INDEX_NOTFOUND;
+        };
+        unsafe {
+            put(pcbData, data.len() as u32);
+            put(ppData, data.as_ptr().cast());
+        }
+        S_OK
+    }
+
+    unsafe fn GetGuid(&self, ixGuid: u32, ppGUID: *mut *const GUID) -> HRESULT {
+        let start = (ixGuid as usize).wrapping_sub(1).wrapping_mul(16);
+        match self.reader.guid_heap().get(start..start.wrapping_add(16)) {
+            Some(guid) if ixGuid != 0 => {
+                unsafe { put(ppGUID, guid.as_ptr().cast()) };
+                S_OK
+            }
+            _ => CLDB_E_INDEX_NOTFOUND,
+        }
+    }
+
+    unsafe fn GetUserString(
+        &self,
+        ixUserString: u32,
+        pcbData: *mut u32,
+        ppData: *mut *const c_void,
+    ) -> HRESULT {
+        let Some((data, _)) = heap_entry(self.reader.user_string_heap(), ixUserString) else {
+            return CLDB_E_INDEX_NOTFOUND;
+        };
+        unsafe {
+            put(pcbData, data.len() as u32);
+            put(ppData, data.as_ptr().cast());
+        }
+        S_OK
+    }
+
+    unsafe fn GetNextString(&self, ixString: u32, pNext: *mut u32) -> HRESULT {
+        let heap = self.reader.strings_heap();
+        let Some(len) = heap
+            .get(ixString as usize..)
+            .and_then(|rest| rest.iter().position(|&b| b == 0))
+        else {
+            return CLDB_E_INDEX_NOTFOUND;
+        };
+        unsafe { put_next(pNext, ixString + len as u32 + 1, heap.len()) }
+    }
+
+    unsafe fn GetNextBlob(&self, ixBlob: u32, pNext: *mut u32) -> HRESULT {
+        let heap = self.reader.blob_heap();
+        match heap_entry(heap, ixBlob) {
+            Some((_, next)) => unsafe { put_next(pNext, next, heap.len()) },
+            None => CLDB_E_INDEX_NOTFOUND,
+        }
+    }
+
+    unsafe fn GetNextGuid(&self, ixGuid: u32, pNext: *mut u32) -> HRESULT {
+        let count = self.reader.guid_heap().len() / 16;
+        if ixGuid == 0 || ixGuid as usize > count {
+            return CLDB_E_INDEX_NOTFOUND;
+        }
+        // GUID indices are 1-based, so the last valid one equals the count.
+        unsafe { put_next(pNext, ixGuid + 1, count + 1) }
+    }
+
+    unsafe fn GetNextUserString(&self, ixUserString: u32, pNext: *mut u32) -> HRESULT {
+        let heap = self.reader.user_string_heap();
+        match heap_entry(heap, ixUserString) {
+            Some((_, next)) => unsafe { put_next(pNext, next, heap.len()) },
+            None => CLDB_E_INDEX_NOTFOUND,
+        }
+    }
+}
+
+impl IMetaDataTables2_Impl for ReaderTables_Impl {
+    unsafe fn GetMetaDataStorage(&self, ppvMd: *mut *const c_void, pcbMd: *mut u32) -> HRESULT {
+        let data = self.reader.data();
+        unsafe {
+            put(ppvMd, data.as_ptr().cast());
+            put(pcbMd, data.len() as u32);
+        }
+        S_OK
+    }
+
+    unsafe fn GetMetaDataStreamInfo(
+        &self,
+        ix: u32,
+        ppchName: *mut *const u8,
+        ppv: *mut *const c_void,
+        pcb: *mut u32,
+    ) -> HRESULT {
+        let Some(stream) = self.reader.streams().get(ix as usize) else {
+            return S_FALSE;
+        };
+        let data = &self.reader.data()[stream.offset as usize..][..stream.size as usize];
+        unsafe {
+            put(ppchName, self.stream_names[ix as usize].as_ptr().cast());
+            put(ppv, data.as_ptr().cast());
+            put(pcb, stream.size);
+        }
+        S_OK
+    }
+}
+
+/// Returns the column a sorted table is keyed on, as reported by
+/// `GetTableInfo`.
+fn key_column(table: TableId) -> Option<usize> {
+    Some(match table {
+        TableId::InterfaceImpl => 0,
+        TableId::Constant => 1,
+        TableId::CustomAttribute => 0,
+        TableId::FieldMarshal => 0,
+        TableId::DeclSecurity => 1,
+        TableId::ClassLayout => 2,
+        TableId::FieldLayout => 1,
+        TableId::MethodSemantics => 2,
+        TableId::MethodImpl => 0,
+        TableId::ImplMap => 1,
+        TableId::FieldRva => 1,
+        TableId::NestedClass => 0,
+        TableId::GenericParam => 2,
+        TableId::GenericParamConstraint => 0,
+        _ => return None,
+    })
+}
@@ -0,0 +1,275 @@
+//! Decoding of literal values: the `Constant` table (II.22.9), enum
+//! members and `FieldRVA` initial data (II.22.18).
+
+use std::fmt;
+
+use super::cursor::Cursor;
+use super::metadata::MetadataReader;
+use super::pe::PeImage;
+use super::signature::*;
+use super::tables::{ClassLayoutRow, ConstantRow, FieldRow, FieldRvaRow, TableId};
+use crate::error::{Error, Result};
+
+const FIELD_ATTRIBUTE_STATIC: u16 = 0x0010;
+const FIELD_ATTRIBUTE_LITERAL: u16 = 0x0040;
+
+/// A literal value from the `Constant` table, as returned by
+/// `GetFieldProps`, `GetParamProps` and `GetPropertyProps` as a type code
+/// and a pointer to the value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstantValue {
+    Boolean(bool),
+    Char(u16),
+    I1(i8),
+    U1(u8),
+    I2(i16),
+    U2(u16),
+    I4(i32),
+    U4(u32),
+    I8(i64),
+    U8(u64),
+    R4(f32),
+    R8(f64),
+    /// A string as UTF-16 code units, which may contain unpaired
+    /// surrogates.
+    String(Vec<u16>),
+    /// The null reference (`ELEMENT_TYPE_CLASS` with a zero value).
+    Null,
+}
+
+impl ConstantValue {
+    /// Decodes a constant of type `element_type` (an `ELEMENT_TYPE_*` code)
+    /// from its blob.
+    ///
+    /// ```
+    /// use mscoree::ConstantValue;
+    ///
+    /// assert_eq!(ConstantValue::decode(0x08, &[0xff; 4])?, ConstantValue::I4(-1));
+    /// let hello = ConstantValue::decode(0x0e, &[b'h', 0, b'i', 0])?;
+    /// assert_eq!(hello.to_string_lossy().as_deref(), Some("hi"));
+    /// assert_eq!(ConstantValue::decode(0x12, &[0; 4])?, ConstantValue::Null);
+    /// # Ok::<(), mscoree::Error>(())
+    /// ```
+    pub fn decode(element_type: u8, blob: &[u8]) -> Result<ConstantValue> {
+        let mut cur = Cursor::new(blob);
+        let value = match element_type {
+            ELEMENT_TYPE_BOOLEAN => ConstantValue::Boolean(cur.u8()? != 0),
+            ELEMENT_TYPE_CHAR => ConstantValue::Char(cur.u16()?),
+            ELEMENT_TYPE_I1 => ConstantValue::I1(cur.u8()? as i8),
+            ELEMENT_TYPE_U1 => ConstantValue::U1(cur.u8()?),
+            ELEMENT_TYPE_I2 => ConstantValue::I2(cur.u16()? as i16),
+            ELEMENT_TYPE_U2 => ConstantValue::U2(cur.u16()?),
+            ELEMENT_TYPE_I4 => ConstantValue::I4(cur.u32()? as i32),
+            ELEMENT_TYPE_U4 => ConstantValue::U4(cur.u32()?),
+            ELEMENT_TYPE_I8 => ConstantValue::I8(cur.u64()? as i64),
+            ELEMENT_TYPE_U8 => ConstantValue::U8(cur.u64()?),
+            ELEMENT_TYPE_R4 => ConstantValue::R4(f32::from_bits(cur.u32()?)),
+            ELEMENT_TYPE_R8 => ConstantValue::R8(f64::from_bits(cur.u64()?)),
+            ELEMENT_TYPE_STRING => {
+                if !blob.len().is_multiple_of(2) {
+                    return Err(Error::BadMetadata(format!(
+                        "string constant has odd length {}",
+                        blob.len()
+                    )));
+                }
+                return Ok(ConstantValue::String(
+                    blob.chunks_exact(2)
+                        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                        .collect(),
+                ));
+            }
+            ELEMENT_TYPE_CLASS => {
+                if blob.iter().any(|&b| b != 0) {
+                    return Err(Error::BadMetadata(
+                        "class constant is not a null reference".into(),
+                    ));
+                }
+                return Ok(ConstantValue::Null);
+            }
+            other => {
+                return Err(Error::BadMetadata(format!(
+                    "invalid constant type {other:#04x}"
+                )));
+            }
+        };
+        Ok(value)
+    }
+
+    /// Returns the `ELEMENT_TYPE_*` code of the value.
+    pub fn element_type(&self) -> u8 {
+        match self {
+            ConstantValue::Boolean(_) => ELEMENT_TYPE_BOOLEAN,
+            ConstantValue::Char(_) => ELEMENT_TYPE_CHAR,
+            ConstantValue::I1(_) => ELEMENT_TYPE_I1,
+            ConstantValue::U1(_) => ELEMENT_TYPE_U1,
+            ConstantValue::I2(_) => ELEMENT_TYPE_I2,
+            ConstantValue::U2(_) => ELEMENT_TYPE_U2,
+            ConstantValue::I4(_) => ELEMENT_TYPE_I4,
+            ConstantValue::U4(_) => ELEMENT_TYPE_U4,
+            ConstantValue::I8(_) => ELEMENT_TYPE_I8,
+            ConstantValue::U8(_) => ELEMENT_TYPE_U8,
+            ConstantValue::R4(_) => ELEMENT_TYPE_R4,
+            ConstantValue::R8(_) => ELEMENT_TYPE_R8,
+            ConstantValue::String(_) => ELEMENT_TYPE_STRING,
+            ConstantValue::Null => ELEMENT_TYPE_CLASS,
+        }
+    }
+
+    /// Returns the value of an integral, `bool` or `char` constant, such
+    /// as an enum member.
+    pub fn as_integer(&self) -> Option<i128> {
+        Some(match *self {
+            ConstantValue::Boolean(v) => v as i128,
+            ConstantValue::Char(v) => v as i128,
+            ConstantValue::I1(v) => v as i128,
+            ConstantValue::U1(v) => v as i128,
+            ConstantValue::I2(v) => v as i128,
+            ConstantValue::U2(v) => v as i128,
+            ConstantValue::I4(v) => v as i128,
+            ConstantValue::U4(v) => v as i128,
+            ConstantValue::I8(v) => v as i128,
+            ConstantValue::U8(v) => v as i128,
+            _ => return None,
+        })
+    }
+
+    /// Returns a string constant, replacing unpaired surrogates.
+    pub fn to_string_lossy(&self) -> Option<String> {
+        match self {
+            ConstantValue::String(units) => Some(String::from_utf16_lossy(units)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConstantValue {
+    /// Formats the value as a C# literal.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstantValue::Boolean(v) => write!(f, "{v}"),
+            ConstantValue::Char(v) => match char::from_u32(*v as u32) {
+                Some(c) => write!(f, "'{}'", c.escape_debug()),
+                None => write!(f, "'\\u{v:04x}'"),
+            },
+            ConstantValue::I1(v) => write!(f, "{v}"),
+            ConstantValue::U1(v) => write!(f, "{v}"),
+            ConstantValue::I2(v) => write!(f, "{v}"),
+            ConstantValue::U2(v) => write!(f, "{v}"),
+            ConstantValue::I4(v) => write!(f, "{v}"),
+            ConstantValue::U4(v) => write!(f, "{v}U"),
+            ConstantValue::I8(v) => write!(f, "{v}L"),
+            ConstantValue::U8(v) => write!(f, "{v}UL"),
+            ConstantValue::R4(v) => write!(f, "{v:?}F"),
+            ConstantValue::R8(v) => write!(f, "{v:?}"),
+            ConstantValue::String(units) => {
+                f.write_str("\"")?;
+                for c in char::decode_utf16(units.iter().copied()) {
+                    match c {
+                        Ok(c) => write!(f, "{}", c.escape_debug())?,
+                        Err(e) => write!(f, "\\u{:04x}", e.unpaired_surrogate())?,
+                    }
+                }
+                f.write_str("\"")
+            }
+            ConstantValue::Null => f.write_str("null"),
+        }
+    }
+}
+
+/// A named value of an enum type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumMember {
+    /// The `Field` rid of the member.
+    pub field: u32,
+    pub name: String,
+    pub value: ConstantValue,
+}
+
+impl MetadataReader {
+    /// Returns the constant of a `Field`, `Param` or `Property` token.
+    pub fn constant(&self, parent: u32) -> Result<Option<ConstantValue>> {
+        match self.find_row(TableId::Constant, 1, parent)? {
+            Some(rid) => self.constant_value(rid).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Decodes row `rid` of the `Constant` table.
+    pub fn constant_value(&self, rid: u32) -> Result<ConstantValue> {
+        let row = self.row::<ConstantRow>(rid)?;
+        ConstantValue::decode(row.ty as u8, self.blob(row.value)?)
+    }
+
+    /// Returns the members of an enum type in declaration order: its
+    /// literal static fields and their values.
+    pub fn enum_members(&self, type_rid: u32) -> Result<Vec<EnumMember>> {
+        let mut members = Vec::new();
+        for field in self.type_def_fields(type_rid)? {
+            let row = self.row::<FieldRow>(field)?;
+            let literal = FIELD_ATTRIBUTE_STATIC | FIELD_ATTRIBUTE_LITERAL;
+            if row.flags & literal != literal {
+                continue;
+            }
+            if let Some(value) = self.constant(TableId::Field.token(field))? {
+                members.push(EnumMember {
+                    field,
+                    name: self.string(row.name)?.to_string(),
+                    value,
+                });
+            }
+        }
+        Ok(members)
+    }
+
+    /// Returns the RVA of a field's initial data from the `FieldRVA` table.
+    pub fn field_rva(&self, field_rid: u32) -> Result<Option<u32>> {
+        match self.find_row(TableId::FieldRva, 1, field_rid)? {
+            Some(rid) => Ok(Some(self.row::<FieldRvaRow>(rid)?.rva)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the size of a field's initial data: the size of a primitive
+    /// field, or the `ClassLayout` size of a value type such as the
+    /// `__StaticArrayInitTypeSize=N` types compilers emit for array
+    /// initializers. Returns `None` if metadata does not give the size.
+    pub fn field_rva_size(&self, field_rid: u32) -> Result<Option<u32>> {
+        let row = self.row::<FieldRow>(field_rid)?;
+        let ty = parse_field_sig(self.blob(row.signature)?)?;
+        let size = match ty.strip_modifiers() {
+            TypeSig::Boolean | TypeSig::I1 | TypeSig::U1 => 1,
+            TypeSig::Char | TypeSig::I2 | TypeSig::U2 => 2,
+            TypeSig::I4 | TypeSig::U4 | TypeSig::R4 => 4,
+            TypeSig::I8 | TypeSig::U8 | TypeSig::R8 => 8,
+            TypeSig::ValueType(token) => {
+                let Some((TableId::TypeDef, type_rid)) = TableId::from_token(*token) else {
+                    return Ok(None);
+                };
+                match self.find_row(TableId::ClassLayout, 2, type_rid)? {
+                    Some(layout) => self.row::<ClassLayoutRow>(layout)?.class_size,
+                    None => return Ok(None),
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(size))
+    }
+}
+
+impl PeImage {
+    /// Returns the initial data of a field with a `FieldRVA` row, sized by
+    /// [`MetadataReader::field_rva_size`].
+    pub fn field_data(&self, metadata: &MetadataReader, field_rid: u32) -> Result<Option<&[u8]>> {
+        let (Some(rva), Some(size)) = (
+            metadata.field_rva(field_rid)?,
+            metadata.field_rva_size(field_rid)?,
+        ) else {
+            return Ok(None);
+        };
+        self.rva_slice(rva, size as usize).map(Some).ok_or_else(|| {
+            Error::BadImageFormat(format!(
+                "field data at RVA {rva:#x} ({size} bytes) is outside the image"
+            ))
+        })
+    }
+}
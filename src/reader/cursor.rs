@@ -0,0 +1,139 @@
+//! Little-endian byte cursor shared by the image, heap and signature readers.
+
+use crate::error::{Error, Result};
+
+/// Forward-only little-endian reader over a byte slice.
+#[derive(Clone)]
+pub(crate) struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub(crate) fn at(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    pub(crate) fn rest(&self) -> &'a [u8] {
+        self.data.get(self.pos..).unwrap_or(&[])
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| truncated(self.pos, len))?;
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    pub(crate) fn skip(&mut self, len: usize) -> Result<()> {
+        self.bytes(len).map(|_| ())
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn peek_u8(&self) -> Result<u8> {
+        self.data
+            .get(self.pos)
+            .copied()
+            .ok_or_else(|| truncated(self.pos, 1))
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    /// Reads a 2- or 4-byte index depending on `wide`.
+    pub(crate) fn index(&mut self, wide: bool) -> Result<u32> {
+        if wide {
+            self.u32()
+        } else {
+            self.u16().map(u32::from)
+        }
+    }
+
+    /// Reads an ECMA-335 compressed unsigned integer (II.23.2).
+    pub(crate) fn compressed_u32(&mut self) -> Result<u32> {
+        let b0 = self.u8()? as u32;
+        if b0 & 0x80 == 0 {
+            Ok(b0)
+        } else if b0 & 0xC0 == 0x80 {
+            let b1 = self.u8()? as u32;
+            Ok(((b0 & 0x3F) << 8) | b1)
+        } else if b0 & 0xE0 == 0xC0 {
+            let rest = self.bytes(3)?;
+            Ok(((b0 & 0x1F) << 24)
+                | ((rest[0] as u32) << 16)
+                | ((rest[1] as u32) << 8)
+                | rest[2] as u32)
+        } else {
+            Err(Error::BadSignature(format!(
+                "invalid compressed integer lead byte {b0:#04x}"
+            )))
+        }
+    }
+
+    /// Reads an ECMA-335 compressed signed integer (II.23.2).
+    pub(crate) fn compressed_i32(&mut self) -> Result<i32> {
+        let start = self.pos;
+        let raw = self.compressed_u32()?;
+        let sign_mask = match self.pos - start {
+            1 => 0xFFFF_FFC0u32,
+            2 => 0xFFFF_E000,
+            _ => 0xF000_0000,
+        };
+        let value = raw >> 1;
+        if raw & 1 == 0 {
+            Ok(value as i32)
+        } else {
+            Ok((value | sign_mask) as i32)
+        }
+    }
+
+    /// Reads a null-terminated byte string (not including the terminator).
+    pub(crate) fn c_str(&mut self) -> Result<&'a [u8]> {
+        let rest = self.rest();
+        let len = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| truncated(self.pos, rest.len() + 1))?;
+        let s = &rest[..len];
+        self.pos += len + 1;
+        Ok(s)
+    }
+}
+
+fn truncated(pos: usize, len: usize) -> Error {
+    Error::BadMetadata(format!(
+        "unexpected end of data reading {len} bytes at {pos:#x}"
+    ))
+}
@@ -0,0 +1,387 @@
+//! Custom attribute blob decoding (ECMA-335 II.23.3).
+
+use std::ops::Range;
+
+use super::cursor::Cursor;
+use super::metadata::MetadataReader;
+use super::signature::*;
+use super::tables::{CustomAttributeRow, FieldRow, MemberRefRow, MethodDefRow, TableId};
+use crate::error::{Error, Result};
+
+const SERIALIZATION_TYPE_TYPE: u8 = 0x50;
+const SERIALIZATION_TYPE_TAGGED_OBJECT: u8 = 0x51;
+const SERIALIZATION_TYPE_FIELD: u8 = 0x53;
+const SERIALIZATION_TYPE_PROPERTY: u8 = 0x54;
+const SERIALIZATION_TYPE_ENUM: u8 = 0x55;
+
+const FIELD_ATTRIBUTE_STATIC: u16 = 0x0010;
+
+/// A decoded custom attribute argument value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CustomAttributeArgument {
+    Boolean(bool),
+    Char(u16),
+    I1(i8),
+    U1(u8),
+    I2(i16),
+    U2(u16),
+    I4(i32),
+    U4(u32),
+    I8(i64),
+    U8(u64),
+    R4(f32),
+    R8(f64),
+    /// A string, or `None` for a null string.
+    String(Option<String>),
+    /// A `System.Type` given by its serialized (assembly-qualified) name.
+    Type(Option<String>),
+    /// An enum value with the full name of the enum type.
+    Enum {
+        type_name: String,
+        value: Box<CustomAttributeArgument>,
+    },
+    /// A single-dimensional array, or `None` for a null array.
+    Array(Option<Vec<CustomAttributeArgument>>),
+}
+
+impl CustomAttributeArgument {
+    /// Returns the string value of a `String` argument.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            CustomAttributeArgument::String(Some(s)) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// A named field or property argument of a custom attribute.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomAttributeNamedArgument {
+    /// `true` for a field, `false` for a property.
+    pub is_field: bool,
+    pub name: String,
+    pub value: CustomAttributeArgument,
+}
+
+/// The decoded value of a custom attribute blob.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CustomAttributeValue {
+    pub fixed_args: Vec<CustomAttributeArgument>,
+    pub named_args: Vec<CustomAttributeNamedArgument>,
+}
+
+impl CustomAttributeValue {
+    /// Returns the named argument called `name`.
+    pub fn named(&self, name: &str) -> Option<&CustomAttributeArgument> {
+        self.named_args
+            .iter()
+            .find(|arg| arg.name == name)
+            .map(|arg| &arg.value)
+    }
+}
+
+/// Argument types as they appear in a custom attribute blob.
+enum ArgType {
+    Primitive(u8),
+    Type,
+    Boxed,
+    Enum { type_name: String, underlying: u8 },
+    SzArray(Box<ArgType>),
+}
+
+impl MetadataReader {
+    /// Returns the constructor token (`MethodDef` or `MemberRef`) of a custom attribute.
+    pub fn custom_attribute_constructor(&self, ca_rid: u32) -> Result<u32> {
+        Ok(self.row::<CustomAttributeRow>(ca_rid)?.ty)
+    }
+
+    /// Returns the full name of the attribute type of a custom attribute.
+    pub fn custom_attribute_type_name(&self, ca_rid: u32) -> Result<String> {
+        self.method_parent_type_name(self.custom_attribute_constructor(ca_rid)?)
+    }
+
+    /// Decodes the value blob of a custom attribute.
+    ///
+    /// Enum arguments are resolved against the types defined in this module.
+    /// Enums defined in other assemblies cannot be resolved from a single
+    /// scope; they are assumed to have an `int32` underlying type, which is
+    /// what the vast majority of enums use.
+    pub fn custom_attribute_value(&self, ca_rid: u32) -> Result<CustomAttributeValue> {
+        self.decode_custom_attribute(ca_rid, &mut Vec::new())
+    }
+
+    /// Returns where the value blob of a custom attribute holds serialized
+    /// type names: `System.Type` arguments and the enum types of named and
+    /// boxed arguments. Each range covers a non-null `SerString`, length
+    /// prefix included.
+    pub(crate) fn custom_attribute_type_names(&self, ca_rid: u32) -> Result<Vec<Range<usize>>> {
+        let mut names = Vec::new();
+        self.decode_custom_attribute(ca_rid, &mut names)?;
+        Ok(names)
+    }
+
+    /// Decodes the value blob of a custom attribute, recording the ranges
+    /// of its serialized type names in `names`.
+    fn decode_custom_attribute(
+        &self,
+        ca_rid: u32,
+        names: &mut Vec<Range<usize>>,
+    ) -> Result<CustomAttributeValue> {
+        let row = self.row::<CustomAttributeRow>(ca_rid)?;
+        let signature = match TableId::from_token(row.ty) {
+            Some((TableId::MethodDef, rid)) => self.row::<MethodDefRow>(rid)?.signature,
+            Some((TableId::MemberRef, rid)) => self.row::<MemberRefRow>(rid)?.signature,
+            _ => {
+                return Err(Error::BadMetadata(format!(
+                    "custom attribute {ca_rid} has an invalid constructor"
+                )));
+            }
+        };
+        let ctor = MethodSig::parse(self.blob(signature)?)?;
+        let blob = self.blob(row.value)?;
+        if blob.is_empty() {
+            return Ok(CustomAttributeValue::default());
+        }
+
+        let mut cur = Cursor::new(blob);
+        if cur.u16()? != 0x0001 {
+            return Err(Error::BadSignature(
+                "missing custom attribute prolog".into(),
+            ));
+        }
+        let mut value = CustomAttributeValue::default();
+        for param in &ctor.params {
+            let ty = self.arg_type_from_sig(param)?;
+            value.fixed_args.push(self.read_arg(&mut cur, &ty, names)?);
+        }
+        if cur.is_empty() {
+            return Ok(value);
+        }
+        let count = cur.u16()?;
+        for _ in 0..count {
+            value
+                .named_args
+                .push(self.read_named_arg_with_names(&mut cur, names)?);
+        }
+        Ok(value)
+    }
+
+    /// Reads a named field or property argument (`NamedArg`), which custom
+    /// attributes and binary permission sets share.
+    pub(crate) fn read_named_arg(&self, cur: &mut Cursor) -> Result<CustomAttributeNamedArgument> {
+        self.read_named_arg_with_names(cur, &mut Vec::new())
+    }
+
+    fn read_named_arg_with_names(
+        &self,
+        cur: &mut Cursor,
+        names: &mut Vec<Range<usize>>,
+    ) -> Result<CustomAttributeNamedArgument> {
+        let is_field = match cur.u8()? {
+            SERIALIZATION_TYPE_FIELD => true,
+            SERIALIZATION_TYPE_PROPERTY => false,
+            other => {
+                return Err(Error::BadSignature(format!(
+                    "invalid named argument kind {other:#04x}"
+                )));
+            }
+        };
+        let ty = self.read_arg_type(cur, names)?;
+        let name = read_ser_string(cur)?.unwrap_or_default();
+        let value = self.read_arg(cur, &ty, names)?;
+        Ok(CustomAttributeNamedArgument {
+            is_field,
+            name,
+            value,
+        })
+    }
+
+    /// Returns the `CustomAttribute` rids on `token` whose attribute type is
+    /// `type_name` (e.g. `System.Runtime.Versioning.TargetFrameworkAttribute`).
+    pub fn find_custom_attributes(&self, token: u32, type_name: &str) -> Result<Vec<u32>> {
+        let mut found = Vec::new();
+        for rid in self.custom_attributes(token)? {
+            // Attributes on generic instantiations have no simple type name.
+            if self.custom_attribute_type_name(rid).ok().as_deref() == Some(type_name) {
+                found.push(rid);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Finds a `TypeDef` by its full reflection name (`Ns.Outer+Inner`).
+    pub fn find_type_def(&self, full_name: &str) -> Result<Option<u32>> {
+        for rid in 1..=self.row_count(TableId::TypeDef) {
+            if self.type_full_name(TableId::TypeDef.token(rid))? == full_name {
+                return Ok(Some(rid));
+            }
+        }
+        Ok(None)
+    }
+
+    fn arg_type_from_sig(&self, sig: &TypeSig) -> Result<ArgType> {
+        let sig = sig.strip_modifiers();
+        if let Some(element_type) = sig.primitive_element_type() {
+            return match element_type {
+                ELEMENT_TYPE_OBJECT => Ok(ArgType::Boxed),
+                ELEMENT_TYPE_BOOLEAN..=ELEMENT_TYPE_STRING => Ok(ArgType::Primitive(element_type)),
+                _ => Err(Error::BadSignature(format!(
+                    "type {sig:?} is not valid in a custom attribute"
+                ))),
+            };
+        }
+        match sig {
+            TypeSig::SzArray(element) => {
+                Ok(ArgType::SzArray(Box::new(self.arg_type_from_sig(element)?)))
+            }
+            TypeSig::Class(token) | TypeSig::ValueType(token) => {
+                let type_name = self.type_full_name(*token)?;
+                if type_name == "System.Type" {
+                    Ok(ArgType::Type)
+                } else {
+                    let underlying = self.enum_underlying_type(*token)?;
+                    Ok(ArgType::Enum {
+                        type_name,
+                        underlying,
+                    })
+                }
+            }
+            other => Err(Error::BadSignature(format!(
+                "type {other:?} is not valid in a custom attribute"
+            ))),
+        }
+    }
+
+    /// Reads a `FieldOrPropType` as used by named and boxed arguments.
+    fn read_arg_type(&self, cur: &mut Cursor, names: &mut Vec<Range<usize>>) -> Result<ArgType> {
+        Ok(match cur.u8()? {
+            SERIALIZATION_TYPE_TYPE => ArgType::Type,
+            SERIALIZATION_TYPE_TAGGED_OBJECT => ArgType::Boxed,
+            ELEMENT_TYPE_SZARRAY => ArgType::SzArray(Box::new(self.read_arg_type(cur, names)?)),
+            SERIALIZATION_TYPE_ENUM => {
+                let qualified = read_type_name(cur, names)?.unwrap_or_default();
+                let type_name = qualified
+                    .split(',')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string();
+                let underlying = match self.find_type_def(&type_name)? {
+                    Some(rid) => self.enum_underlying_type(TableId::TypeDef.token(rid))?,
+                    None => ELEMENT_TYPE_I4,
+                };
+                ArgType::Enum {
+                    type_name,
+                    underlying,
+                }
+            }
+            element_type @ ELEMENT_TYPE_BOOLEAN..=ELEMENT_TYPE_STRING => {
+                ArgType::Primitive(element_type)
+            }
+            other => {
+                return Err(Error::BadSignature(format!(
+                    "invalid custom attribute element type {other:#04x}"
+                )));
+            }
+        })
+    }
+
+    fn read_arg(
+        &self,
+        cur: &mut Cursor,
+        ty: &ArgType,
+        names: &mut Vec<Range<usize>>,
+    ) -> Result<CustomAttributeArgument> {
+        match ty {
+            ArgType::Primitive(element_type) => read_primitive(cur, *element_type),
+            ArgType::Type => Ok(CustomAttributeArgument::Type(read_type_name(cur, names)?)),
+            ArgType::Enum {
+                type_name,
+                underlying,
+            } => Ok(CustomAttributeArgument::Enum {
+                type_name: type_name.clone(),
+                value: Box::new(read_primitive(cur, *underlying)?),
+            }),
+            ArgType::SzArray(element) => {
+                let count = cur.u32()?;
+                if count == 0xFFFF_FFFF {
+                    return Ok(CustomAttributeArgument::Array(None));
+                }
+                let items = (0..count)
+                    .map(|_| self.read_arg(cur, element, names))
+                    .collect::<Result<_>>()?;
+                Ok(CustomAttributeArgument::Array(Some(items)))
+            }
+            ArgType::Boxed => {
+                let ty = self.read_arg_type(cur, names)?;
+                self.read_arg(cur, &ty, names)
+            }
+        }
+    }
+
+    /// Returns the `ELEMENT_TYPE_*` of an enum's underlying type, assuming
+    /// `int32` for enums that are not defined in this module.
+    pub(crate) fn enum_underlying_type(&self, token: u32) -> Result<u8> {
+        let Some((TableId::TypeDef, rid)) = TableId::from_token(token) else {
+            return Ok(ELEMENT_TYPE_I4);
+        };
+        for field in self.type_def_fields(rid)? {
+            let row = self.row::<FieldRow>(field)?;
+            // The only instance field of an enum holds its value (`value__`).
+            if row.flags & FIELD_ATTRIBUTE_STATIC == 0 {
+                let ty = parse_field_sig(self.blob(row.signature)?)?;
+                if let Some(element_type) = ty.strip_modifiers().primitive_element_type() {
+                    return Ok(element_type);
+                }
+            }
+        }
+        Ok(ELEMENT_TYPE_I4)
+    }
+}
+
+pub(crate) fn read_ser_string(cur: &mut Cursor) -> Result<Option<String>> {
+    if cur.peek_u8()? == 0xFF {
+        cur.u8()?;
+        return Ok(None);
+    }
+    let len = cur.compressed_u32()? as usize;
+    let bytes = cur.bytes(len)?;
+    String::from_utf8(bytes.to_vec())
+        .map(Some)
+        .map_err(|_| Error::BadSignature("custom attribute string is not valid UTF-8".into()))
+}
+
+/// Reads a `SerString` holding a serialized type name and records where it
+/// is.
+fn read_type_name(cur: &mut Cursor, names: &mut Vec<Range<usize>>) -> Result<Option<String>> {
+    let start = cur.position();
+    let name = read_ser_string(cur)?;
+    if name.is_some() {
+        names.push(start..cur.position());
+    }
+    Ok(name)
+}
+
+fn read_primitive(cur: &mut Cursor, element_type: u8) -> Result<CustomAttributeArgument> {
+    use CustomAttributeArgument as Arg;
+    Ok(match element_type {
+        ELEMENT_TYPE_BOOLEAN => Arg::Boolean(cur.u8()? != 0),
+        ELEMENT_TYPE_CHAR => Arg::Char(cur.u16()?),
+        ELEMENT_TYPE_I1 => Arg::I1(cur.u8()? as i8),
+        ELEMENT_TYPE_U1 => Arg::U1(cur.u8()?),
+        ELEMENT_TYPE_I2 => Arg::I2(cur.u16()? as i16),
+        ELEMENT_TYPE_U2 => Arg::U2(cur.u16()?),
+        ELEMENT_TYPE_I4 => Arg::I4(cur.u32()? as i32),
+        ELEMENT_TYPE_U4 => Arg::U4(cur.u32()?),
+        ELEMENT_TYPE_I8 => Arg::I8(cur.u64()? as i64),
+        ELEMENT_TYPE_U8 => Arg::U8(cur.u64()?),
+        ELEMENT_TYPE_R4 => Arg::R4(f32::from_bits(cur.u32()?)),
+        ELEMENT_TYPE_R8 => Arg::R8(f64::from_bits(cur.u64()?)),
+        ELEMENT_TYPE_STRING => Arg::String(read_ser_string(cur)?),
+        other => {
+            return Err(Error::BadSignature(format!(
+                "invalid custom attribute element type {other:#04x}"
+            )));
+        }
+    })
+}
@@ -0,0 +1,125 @@
+//! The PE debug directory, which links an image to its symbols.
+
+use super::cursor::Cursor;
+use super::pe::{IMAGE_DIRECTORY_ENTRY_DEBUG, PeImage};
+use crate::error::{Error, Result};
+
+/// Debug directory entry type: a CodeView record naming the PDB.
+pub const IMAGE_DEBUG_TYPE_CODEVIEW: u32 = 2;
+/// Debug directory entry type: the image was built deterministically.
+pub const IMAGE_DEBUG_TYPE_REPRO: u32 = 16;
+/// Debug directory entry type: a Deflate-compressed Portable PDB.
+pub const IMAGE_DEBUG_TYPE_EMBEDDED_PORTABLE_PDB: u32 = 17;
+/// Debug directory entry type: a checksum of the PDB the image refers to.
+pub const IMAGE_DEBUG_TYPE_PDBCHECKSUM: u32 = 19;
+
+/// Size of an `IMAGE_DEBUG_DIRECTORY` entry.
+const DEBUG_DIRECTORY_ENTRY_SIZE: usize = 28;
+/// `RSDS`, the signature of a CodeView PDB 7.0 record.
+const CODEVIEW_SIGNATURE: u32 = 0x5344_5352;
+/// Minor version of CodeView entries that point at a Portable PDB.
+pub const PORTABLE_PDB_CODEVIEW_MINOR_VERSION: u16 = 0x504D;
+
+/// An `IMAGE_DEBUG_DIRECTORY` entry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DebugDirectoryEntry {
+    pub characteristics: u32,
+    pub time_date_stamp: u32,
+    pub major_version: u16,
+    pub minor_version: u16,
+    /// The entry type (`IMAGE_DEBUG_TYPE_*`).
+    pub kind: u32,
+    pub size_of_data: u32,
+    pub address_of_raw_data: u32,
+    pub pointer_to_raw_data: u32,
+}
+
+/// The CodeView record of a debug directory: the identity and path of the
+/// PDB an image was built with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeViewInfo {
+    pub guid: [u8; 16],
+    pub age: u32,
+    pub path: String,
+    /// Whether the PDB is a Portable PDB, whose id is `guid` followed by
+    /// the entry's time stamp.
+    pub is_portable: bool,
+    /// The entry's time stamp.
+    pub time_date_stamp: u32,
+}
+
+impl CodeViewInfo {
+    /// Returns the id a Portable PDB must have to match the image.
+    pub fn portable_pdb_id(&self) -> [u8; 20] {
+        let mut id = [0; 20];
+        id[..16].copy_from_slice(&self.guid);
+        id[16..].copy_from_slice(&self.time_date_stamp.to_le_bytes());
+        id
+    }
+}
+
+impl PeImage {
+    /// Returns the entries of the debug directory.
+    pub fn debug_directory(&self) -> Result<Vec<DebugDirectoryEntry>> {
+        let dir = self.data_directory(IMAGE_DIRECTORY_ENTRY_DEBUG);
+        if dir.is_empty() {
+            return Ok(Vec::new());
+        }
+        let data = self
+            .directory_data(dir)
+            .ok_or_else(|| Error::BadImageFormat("debug directory is outside the image".into()))?;
+        let mut cur = Cursor::new(data);
+        let mut entries = Vec::with_capacity(data.len() / DEBUG_DIRECTORY_ENTRY_SIZE);
+        while cur.remaining() >= DEBUG_DIRECTORY_ENTRY_SIZE {
+            entries.push(DebugDirectoryEntry {
+                characteristics: cur.u32()?,
+                time_date_stamp: cur.u32()?,
+                major_version: cur.u16()?,
+                minor_version: cur.u16()?,
+                kind: cur.u32()?,
+                size_of_data: cur.u32()?,
+                address_of_raw_data: cur.u32()?,
+                pointer_to_raw_data: cur.u32()?,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Returns the data of a debug directory entry.
+    pub fn debug_data(&self, entry: &DebugDirectoryEntry) -> Option<&[u8]> {
+        if entry.size_of_data == 0 {
+            return Some(&[]);
+        }
+        let start = entry.pointer_to_raw_data as usize;
+        self.data()
+            .get(start..start.checked_add(entry.size_of_data as usize)?)
+    }
+
+    /// Returns the first CodeView record of the debug directory, if any.
+    pub fn codeview(&self) -> Result<Option<CodeViewInfo>> {
+        let Some(entry) = self
+            .debug_directory()?
+            .into_iter()
+            .find(|e| e.kind == IMAGE_DEBUG_TYPE_CODEVIEW)
+        else {
+            return Ok(None);
+        };
+        let data = self
+            .debug_data(&entry)
+            .ok_or_else(|| Error::BadImageFormat("CodeView record is outside the image".into()))?;
+        let mut cur = Cursor::new(data);
+        if cur.u32()? != CODEVIEW_SIGNATURE {
+            return Ok(None);
+        }
+        let guid = cur.bytes(16)?.try_into().expect("16 bytes");
+        let age = cur.u32()?;
+        let path = String::from_utf8_lossy(cur.c_str()?).into_owned();
+        Ok(Some(CodeViewInfo {
+            guid,
+            age,
+            path,
+            is_portable: entry.minor_version == PORTABLE_PDB_CODEVIEW_MINOR_VERSION,
+            time_date_stamp: entry.time_date_stamp,
+        }))
+    }
+}
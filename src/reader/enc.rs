@@ -0,0 +1,343 @@
+//! Edit-and-Continue deltas: the `EncLog` and `EncMap` tables and merging
+//! deltas into their baseline.
+
+use std::collections::{HashMap, HashSet};
+
+use super::metadata::{HEAP_ENC_DELTA, MetadataReader};
+use super::tables::{EncLogRow, EncMapRow, TableId};
+use crate::error::{Error, Result};
+use crate::writer::{MetadataBuilder, TablesFormat};
+
+/// The operation recorded by an `EncLog` row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EncFuncCode {
+    /// The row named by the token was added or updated.
+    Default,
+    /// A method was added to the `TypeDef` named by the token; the next
+    /// entry names the new `MethodDef`.
+    AddMethod,
+    /// A field was added to the `TypeDef` named by the token.
+    AddField,
+    /// A parameter was added to the `MethodDef` named by the token.
+    AddParameter,
+    /// A property was added to the `PropertyMap` named by the token.
+    AddProperty,
+    /// An event was added to the `EventMap` named by the token.
+    AddEvent,
+}
+
+impl EncFuncCode {
+    /// Returns the operation with the given `FuncCode` value.
+    pub fn from_u32(value: u32) -> Option<EncFuncCode> {
+        Some(match value {
+            0 => EncFuncCode::Default,
+            1 => EncFuncCode::AddMethod,
+            2 => EncFuncCode::AddField,
+            3 => EncFuncCode::AddParameter,
+            4 => EncFuncCode::AddProperty,
+            5 => EncFuncCode::AddEvent,
+            _ => return None,
+        })
+    }
+
+    /// Returns the `FuncCode` value of the operation.
+    pub fn to_u32(self) -> u32 {
+        self as u32
+    }
+
+    /// Returns the list a member is added to: the parent table, its list
+    /// column, the member table and the indirection table that maps list
+    /// positions to member rows.
+    pub(crate) fn member_list(self) -> Option<(TableId, usize, TableId, TableId)> {
+        Some(match self {
+            EncFuncCode::Default => return None,
+            EncFuncCode::AddMethod => (TableId::TypeDef, 5, TableId::MethodDef, TableId::MethodPtr),
+            EncFuncCode::AddField => (TableId::TypeDef, 4, TableId::Field, TableId::FieldPtr),
+            EncFuncCode::AddParameter => (TableId::MethodDef, 5, TableId::Param, TableId::ParamPtr),
+            EncFuncCode::AddProperty => (
+                TableId::PropertyMap,
+                1,
+                TableId::Property,
+                TableId::PropertyPtr,
+            ),
+            EncFuncCode::AddEvent => (TableId::EventMap, 1, TableId::Event, TableId::EventPtr),
+        })
+    }
+}
+
+/// An entry of the `EncLog` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EncLogEntry {
+    pub token: u32,
+    pub func_code: EncFuncCode,
+}
+
+impl MetadataReader {
+    /// Returns `true` if the metadata is an Edit-and-Continue delta rather
+    /// than a complete module.
+    pub fn is_enc_delta(&self) -> bool {
+        self.heap_sizes() & HEAP_ENC_DELTA != 0 || self.row_count(TableId::EncLog) != 0
+    }
+
+    /// Returns the entries of the `EncLog` table in order.
+    pub fn enc_log(&self) -> Result<Vec<EncLogEntry>> {
+        self.rows::<EncLogRow>()
+            .map(|row| {
+                let (rid, row) = row?;
+                let func_code = EncFuncCode::from_u32(row.func_code).ok_or_else(|| {
+                    Error::BadMetadata(format!(
+                        "EncLog row {rid} has invalid function code {}",
+                        row.func_code
+                    ))
+                })?;
+                Ok(EncLogEntry {
+                    token: row.token,
+                    func_code,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the tokens of the `EncMap` table: for each table, the
+    /// baseline tokens of the delta's rows in row order.
+    pub fn enc_map(&self) -> Result<Vec<u32>> {
+        self.rows::<EncMapRow>()
+            .map(|row| Ok(row?.1.token))
+            .collect()
+    }
+
+    /// Applies an Edit-and-Continue delta and returns the resulting
+    /// metadata, in which every token and heap index of the baseline and of
+    /// the delta keeps its meaning.
+    ///
+    /// The delta's heaps are appended to the baseline's, and each delta row
+    /// replaces or appends the row its `EncMap` entry names. Members added
+    /// to a type other than the last are linked into its list through
+    /// `*Ptr` tables, so the result may be uncompressed (`#-`) metadata.
+    pub fn apply_delta(&self, delta: &MetadataReader) -> Result<MetadataReader> {
+        let mut merger = DeltaMerger::new(self)?;
+        merger.apply(delta)?;
+        merger.finish()
+    }
+
+    /// Applies a sequence of deltas in generation order.
+    pub fn apply_deltas<'a>(
+        &self,
+        deltas: impl IntoIterator<Item = &'a MetadataReader>,
+    ) -> Result<MetadataReader> {
+        let mut merger = DeltaMerger::new(self)?;
+        for delta in deltas {
+            merger.apply(delta)?;
+        }
+        merger.finish()
+    }
+}
+
+struct DeltaMerger {
+    builder: MetadataBuilder,
+}
+
+impl DeltaMerger {
+    fn new(baseline: &MetadataReader) -> Result<Self> {
+        let mut builder = MetadataBuilder::from_reader(baseline)?;
+        builder.clear_table(TableId::EncLog);
+        builder.clear_table(TableId::EncMap);
+        Ok(Self { builder })
+    }
+
+    fn apply(&mut self, delta: &MetadataReader) -> Result<()> {
+        self.builder.append_raw_heaps(
+            delta.strings_heap(),
+            delta.blob_heap(),
+            delta.guid_heap(),
+            delta.user_string_heap(),
+        );
+
+        // Delta rows appear in the same order as their tables' EncMap
+        // entries.
+        let mut targets: HashMap<u32, (TableId, u32)> = HashMap::new();
+        let mut next_rid = [1u32; 64];
+        for token in delta.enc_map()? {
+            let (table, _) = TableId::from_token(token).ok_or_else(|| {
+                Error::BadMetadata(format!("EncMap token {token:#010x} has no table"))
+            })?;
+            let rid = next_rid[table as usize];
+            next_rid[table as usize] += 1;
+            targets.insert(token, (table, rid));
+        }
+
+        if delta.row_count(TableId::Module) != 0 && !targets.contains_key(&TableId::Module.token(1))
+        {
+            targets.insert(TableId::Module.token(1), (TableId::Module, 1));
+        }
+        let mut applied = HashSet::new();
+        let mut pending_parent: Option<(EncFuncCode, u32)> = None;
+        for entry in delta.enc_log()? {
+            if entry.func_code != EncFuncCode::Default {
+                pending_parent = Some((entry.func_code, entry.token));
+                continue;
+            }
+            let Some(&(table, delta_rid)) = targets.get(&entry.token) else {
+                // Entries for rows the delta does not carry (such as a
+                // method whose body alone changed) need no table update.
+                continue;
+            };
+            let values = delta.columns(table, delta_rid)?;
+            let parent = pending_parent
+                .take()
+                .filter(|&(code, _)| code.member_list().is_some_and(|(_, _, t, _)| t == table));
+            self.store(table, entry.token, values, parent)?;
+            applied.insert(entry.token);
+        }
+
+        let mut rest: Vec<_> = targets
+            .into_iter()
+            .filter(|(token, _)| !applied.contains(token))
+            .collect();
+        rest.sort_unstable();
+        for (token, (table, delta_rid)) in rest {
+            let values = delta.columns(table, delta_rid)?;
+            self.store(table, token, values, None)?;
+        }
+        Ok(())
+    }
+
+    /// Updates or appends the row named by `token`, linking a new member
+    /// into its parent's list.
+    fn store(
+        &mut self,
+        table: TableId,
+        token: u32,
+        values: Vec<u32>,
+        parent: Option<(EncFuncCode, u32)>,
+    ) -> Result<()> {
+        let rid = token & 0x00FF_FFFF;
+        let count = self.builder.row_count(table);
+        if rid <= count {
+            return self.builder.set_row(table, rid, values);
+        }
+        if rid != count + 1 {
+            return Err(Error::BadMetadata(format!(
+                "delta adds {table} row {rid} but the table has {count} rows"
+            )));
+        }
+        match parent {
+            Some((code, parent_token)) => {
+                let (parent_table, column, member_table, ptr_table) = code.member_list().unwrap();
+                let (table_of_parent, parent_rid) =
+                    TableId::from_token(parent_token).ok_or_else(|| {
+                        Error::BadMetadata(format!(
+                            "EncLog token {parent_token:#010x} has no table"
+                        ))
+                    })?;
+                if table_of_parent != parent_table {
+                    return Err(Error::BadMetadata(format!(
+                        "{code:?} names {table_of_parent} token {parent_token:#010x}"
+                    )));
+                }
+                self.builder.add_row(member_table, values)?;
+                self.link(parent_table, column, parent_rid, ptr_table, rid)
+            }
+            None => {
+                self.builder.add_row(table, values)?;
+                if let Some(ptr_table) = ptr_table_of(table)
+                    && self.builder.row_count(ptr_table) != 0
+                {
+                    self.builder.add_row(ptr_table, vec![rid])?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Inserts member `member_rid` at the end of the list of row
+    /// `parent_rid`, introducing the indirection table if the member does
+    /// not belong at the end of the member table.
+    fn link(
+        &mut self,
+        parent_table: TableId,
+        column: usize,
+        parent_rid: u32,
+        ptr_table: TableId,
+        member_rid: u32,
+    ) -> Result<()> {
+        let parents = self.builder.row_count(parent_table);
+        if parent_rid == 0 || parent_rid > parents {
+            return Err(Error::BadMetadata(format!(
+                "delta adds a member to {parent_table} row {parent_rid} but the table has {parents} rows"
+            )));
+        }
+        let has_ptr = self.builder.row_count(ptr_table) != 0;
+        // Positions in the list are member rids, or Ptr rows once the
+        // indirection table exists. The new member is already counted.
+        let positions = if has_ptr {
+            self.builder.row_count(ptr_table) + 1
+        } else {
+            member_rid
+        };
+        let end = if parent_rid < parents {
+            self.builder.row_values(parent_table, parent_rid + 1)?[column]
+        } else {
+            positions
+        };
+        let start = self.builder.row_values(parent_table, parent_rid)?[column];
+        if start > end || end > positions {
+            return Err(Error::BadMetadata(format!(
+                "{parent_table} row {parent_rid} has an invalid member list"
+            )));
+        }
+
+        if !has_ptr && end == positions {
+            // Appending to the last non-empty list: the member table order
+            // already places it there.
+            return self.fix_empty_lists(parent_table, column, parent_rid, end);
+        }
+        if !has_ptr {
+            for rid in 1..member_rid {
+                self.builder.add_row(ptr_table, vec![rid])?;
+            }
+        }
+        self.builder.insert_row(ptr_table, end, vec![member_rid])?;
+        for rid in parent_rid + 1..=parents {
+            let start = self.builder.row_values(parent_table, rid)?[column];
+            if start >= end {
+                self.builder
+                    .set_column(parent_table, rid, column, start + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Keeps the lists of rows after `parent_rid` empty when a member is
+    /// appended at position `end`.
+    fn fix_empty_lists(
+        &mut self,
+        parent_table: TableId,
+        column: usize,
+        parent_rid: u32,
+        end: u32,
+    ) -> Result<()> {
+        for rid in parent_rid + 1..=self.builder.row_count(parent_table) {
+            self.builder
+                .set_column(parent_table, rid, column, end + 1)?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<MetadataReader> {
+        self.builder.set_format(TablesFormat::Uncompressed);
+        self.builder.set_sorted_tables(0);
+        MetadataReader::from_bytes(self.builder.to_bytes()?)
+    }
+}
+
+fn ptr_table_of(table: TableId) -> Option<TableId> {
+    Some(match table {
+        TableId::Field => TableId::FieldPtr,
+        TableId::MethodDef => TableId::MethodPtr,
+        TableId::Param => TableId::ParamPtr,
+        TableId::Property => TableId::PropertyPtr,
+        TableId::Event => TableId::EventPtr,
+        _ => return None,
+    })
+}
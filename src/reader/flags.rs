@@ -0,0 +1,16 @@
+//! Flags of metadata table columns (II.23.1) used by more than one module.
+
+/// `TypeAttributes.Interface`.
+pub(crate) const TYPE_ATTRIBUTE_INTERFACE: u32 = 0x0000_0020;
+/// `TypeAttributes.Import`: the type was imported from a type library.
+pub(crate) const TYPE_ATTRIBUTE_IMPORT: u32 = 0x0000_1000;
+
+/// `MethodAttributes.Static`.
+pub(crate) const METHOD_ATTRIBUTE_STATIC: u16 = 0x0010;
+/// `MethodImplAttributes.PreserveSig`.
+pub(crate) const METHOD_IMPL_PRESERVE_SIG: u16 = 0x0080;
+
+/// `ParamAttributes.In`.
+pub(crate) const PARAM_ATTRIBUTE_IN: u16 = 0x0001;
+/// `ParamAttributes.Out`.
+pub(crate) const PARAM_ATTRIBUTE_OUT: u16 = 0x0002;
@@ -0,0 +1,164 @@
+//! Runtime-related classification of managed images.
+//!
+//! Pure-Rust equivalents of `IMetaDataImport2::GetPEKind`,
+//! `IMetaDataImport2::GetVersionString` and `ICLRMetaHost::GetVersionFromFile`.
+
+use std::fmt;
+use std::path::Path;
+
+use super::metadata::MetadataReader;
+use super::pe::{CorPEKind, Machine, PeImage};
+use super::tables::TableId;
+use crate::error::{Error, Result};
+
+const TARGET_FRAMEWORK_ATTRIBUTE: &str = "System.Runtime.Versioning.TargetFrameworkAttribute";
+
+/// A parsed target framework moniker such as `.NETFramework,Version=v4.7.2`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FrameworkName {
+    /// The framework identifier, e.g. `.NETCoreApp`.
+    pub identifier: String,
+    /// The version without the leading `v`, e.g. `8.0`.
+    pub version: String,
+    /// The profile, e.g. `Client`, if present.
+    pub profile: Option<String>,
+}
+
+impl FrameworkName {
+    /// Parses a framework name in `Identifier,Version=vX.Y[,Profile=P]` form.
+    pub fn parse(s: &str) -> Option<FrameworkName> {
+        let mut parts = s.split(',').map(str::trim);
+        let identifier = parts.next().filter(|id| !id.is_empty())?.to_string();
+        let mut version = None;
+        let mut profile = None;
+        for part in parts {
+            let (key, value) = part.split_once('=')?;
+            match key.trim().to_ascii_lowercase().as_str() {
+                "version" => {
+                    let value = value.trim();
+                    version = Some(value.strip_prefix(['v', 'V']).unwrap_or(value).to_string());
+                }
+                "profile" => profile = Some(value.trim().to_string()).filter(|p| !p.is_empty()),
+                _ => {}
+            }
+        }
+        Some(FrameworkName {
+            identifier,
+            version: version?,
+            profile,
+        })
+    }
+
+    /// Returns the version components, e.g. `[4, 7, 2]`.
+    pub fn version_parts(&self) -> Vec<u32> {
+        self.version
+            .split('.')
+            .map_while(|part| part.parse().ok())
+            .collect()
+    }
+
+    /// Returns the NuGet short folder name, e.g. `net472`, `net8.0` or
+    /// `netstandard2.0`, for the common framework families.
+    pub fn short_name(&self) -> Option<String> {
+        let parts = self.version_parts();
+        let major = *parts.first()?;
+        let minor = parts.get(1).copied().unwrap_or(0);
+        let name = match self.identifier.to_ascii_lowercase().as_str() {
+            ".netframework" => {
+                let mut name = format!("net{major}{minor}");
+                if let Some(&build) = parts.get(2).filter(|&&b| b != 0) {
+                    name.push_str(&build.to_string());
+                }
+                if self.profile.as_deref() == Some("Client") {
+                    name.push_str("-client");
+                }
+                name
+            }
+            ".netcoreapp" if major >= 5 => format!("net{major}.{minor}"),
+            ".netcoreapp" => format!("netcoreapp{major}.{minor}"),
+            ".netstandard" => format!("netstandard{major}.{minor}"),
+            "uap" => format!("uap{major}.{minor}"),
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+impl fmt::Display for FrameworkName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},Version=v{}", self.identifier, self.version)?;
+        if let Some(profile) = &self.profile {
+            write!(f, ",Profile={profile}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Runtime-relevant facts about a managed image, read without the CLR.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClrImageInfo {
+    /// PE kind flags, as from `IMetaDataImport2::GetPEKind`.
+    pub pe_kind: CorPEKind,
+    /// Machine from the COFF header, as from `IMetaDataImport2::GetPEKind`.
+    pub machine: Machine,
+    /// The CLI header flags (`COMIMAGE_FLAGS_*`).
+    pub cor_flags: u32,
+    /// The CLI header runtime version (`MajorRuntimeVersion.MinorRuntimeVersion`).
+    pub cor_header_version: (u16, u16),
+    /// The metadata version string, e.g. `v4.0.30319`.
+    pub metadata_version: String,
+    /// The raw `TargetFrameworkAttribute` value, if the assembly has one.
+    pub target_framework: Option<String>,
+}
+
+impl ClrImageInfo {
+    /// Reads the image at `path` and classifies it.
+    pub fn open(path: impl AsRef<Path>) -> Result<ClrImageInfo> {
+        Self::from_image(&PeImage::open(path)?)
+    }
+
+    /// Classifies an already loaded image.
+    pub fn from_image(image: &PeImage) -> Result<ClrImageInfo> {
+        let cor = image.cor_header().ok_or(Error::NotManaged)?;
+        let metadata = image.metadata()?;
+        let (pe_kind, machine) = image.pe_kind();
+        Ok(ClrImageInfo {
+            pe_kind,
+            machine,
+            cor_flags: cor.flags,
+            cor_header_version: (cor.major_runtime_version, cor.minor_runtime_version),
+            metadata_version: metadata.version().to_string(),
+            target_framework: metadata.target_framework()?,
+        })
+    }
+
+    /// Returns the parsed target framework, if present and well-formed.
+    pub fn framework_name(&self) -> Option<FrameworkName> {
+        FrameworkName::parse(self.target_framework.as_deref()?)
+    }
+}
+
+impl MetadataReader {
+    /// Returns the framework name from the assembly's
+    /// `System.Runtime.Versioning.TargetFrameworkAttribute`, if any.
+    pub fn target_framework(&self) -> Result<Option<String>> {
+        if self.row_count(TableId::Assembly) == 0 {
+            return Ok(None);
+        }
+        let assembly = TableId::Assembly.token(1);
+        for rid in self.find_custom_attributes(assembly, TARGET_FRAMEWORK_ATTRIBUTE)? {
+            let value = self.custom_attribute_value(rid)?;
+            if let Some(name) = value.fixed_args.first().and_then(|arg| arg.as_str()) {
+                return Ok(Some(name.to_string()));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Returns the runtime version an image was built against, the way
+/// `ICLRMetaHost::GetVersionFromFile` does (e.g. `v4.0.30319`).
+pub fn runtime_version_from_file(path: impl AsRef<Path>) -> Result<String> {
+    let image = PeImage::open(path)?;
+    Ok(image.metadata()?.version().to_string())
+}
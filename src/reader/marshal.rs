@@ -0,0 +1,719 @@
+//! Decoding of interop metadata: marshalling descriptors from the
+//! `FieldMarshal` table (II.23.4) and P/Invoke maps from the `ImplMap`
+//! table (II.22.22).
+
+use std::fmt;
+
+use super::cursor::Cursor;
+use super::flags::METHOD_IMPL_PRESERVE_SIG;
+use super::metadata::MetadataReader;
+use super::tables::{FieldMarshalRow, ImplMapRow, MethodDefRow, ModuleRefRow, TableId};
+use crate::error::{Error, Result};
+
+const NATIVE_TYPE_BOOLEAN: u8 = 0x02;
+const NATIVE_TYPE_I1: u8 = 0x03;
+const NATIVE_TYPE_U1: u8 = 0x04;
+const NATIVE_TYPE_I2: u8 = 0x05;
+const NATIVE_TYPE_U2: u8 = 0x06;
+const NATIVE_TYPE_I4: u8 = 0x07;
+const NATIVE_TYPE_U4: u8 = 0x08;
+const NATIVE_TYPE_I8: u8 = 0x09;
+const NATIVE_TYPE_U8: u8 = 0x0A;
+const NATIVE_TYPE_R4: u8 = 0x0B;
+const NATIVE_TYPE_R8: u8 = 0x0C;
+const NATIVE_TYPE_CURRENCY: u8 = 0x0F;
+const NATIVE_TYPE_BSTR: u8 = 0x13;
+const NATIVE_TYPE_LPSTR: u8 = 0x14;
+const NATIVE_TYPE_LPWSTR: u8 = 0x15;
+const NATIVE_TYPE_LPTSTR: u8 = 0x16;
+const NATIVE_TYPE_FIXEDSYSSTRING: u8 = 0x17;
+const NATIVE_TYPE_IUNKNOWN: u8 = 0x19;
+const NATIVE_TYPE_IDISPATCH: u8 = 0x1A;
+const NATIVE_TYPE_STRUCT: u8 = 0x1B;
+const NATIVE_TYPE_INTF: u8 = 0x1C;
+const NATIVE_TYPE_SAFEARRAY: u8 = 0x1D;
+const NATIVE_TYPE_FIXEDARRAY: u8 = 0x1E;
+const NATIVE_TYPE_INT: u8 = 0x1F;
+const NATIVE_TYPE_UINT: u8 = 0x20;
+const NATIVE_TYPE_BYVALSTR: u8 = 0x22;
+const NATIVE_TYPE_ANSIBSTR: u8 = 0x23;
+const NATIVE_TYPE_TBSTR: u8 = 0x24;
+const NATIVE_TYPE_VARIANTBOOL: u8 = 0x25;
+const NATIVE_TYPE_FUNC: u8 = 0x26;
+const NATIVE_TYPE_ASANY: u8 = 0x28;
+const NATIVE_TYPE_ARRAY: u8 = 0x2A;
+const NATIVE_TYPE_LPSTRUCT: u8 = 0x2B;
+const NATIVE_TYPE_CUSTOMMARSHALER: u8 = 0x2C;
+const NATIVE_TYPE_ERROR: u8 = 0x2D;
+const NATIVE_TYPE_IINSPECTABLE: u8 = 0x2E;
+const NATIVE_TYPE_HSTRING: u8 = 0x2F;
+const NATIVE_TYPE_LPUTF8STR: u8 = 0x30;
+/// Marks an unspecified array element type.
+const NATIVE_TYPE_MAX: u8 = 0x50;
+
+/// `NATIVE_TYPE_ARRAY` flag: the parameter number gives `SizeParamIndex`.
+const NTA_SIZE_PARAM_INDEX_SPECIFIED: u32 = 0x0001;
+
+/// A decoded marshalling descriptor: how a field or parameter is passed to
+/// native code, as written by `[MarshalAs]`.
+///
+/// Variants are named after `System.Runtime.InteropServices.UnmanagedType`
+/// and `Display` formats the descriptor as a C# `MarshalAs` argument list.
+///
+/// ```
+/// use mscoree::NativeType;
+///
+/// // LPArray of LPStr whose length is parameter 1.
+/// let ty = NativeType::parse(&[0x2a, 0x14, 0x01])?;
+/// assert_eq!(
+///     ty.to_string(),
+///     "UnmanagedType.LPArray, ArraySubType = UnmanagedType.LPStr, SizeParamIndex = 1"
+/// );
+/// # Ok::<(), mscoree::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NativeType {
+    Bool,
+    I1,
+    U1,
+    I2,
+    U2,
+    I4,
+    U4,
+    I8,
+    U8,
+    R4,
+    R8,
+    Currency,
+    BStr,
+    LPStr,
+    LPWStr,
+    LPTStr,
+    /// A fixed-length inline character buffer.
+    ByValTStr {
+        size_const: u32,
+    },
+    IUnknown {
+        iid_parameter_index: Option<u32>,
+    },
+    IDispatch {
+        iid_parameter_index: Option<u32>,
+    },
+    Struct,
+    Interface {
+        iid_parameter_index: Option<u32>,
+    },
+    SafeArray {
+        /// The `VARENUM` element type.
+        sub_type: Option<u16>,
+        /// The element type name for `VT_RECORD` and similar element types.
+        user_defined_sub_type: Option<String>,
+    },
+    /// A fixed-length inline array.
+    ByValArray {
+        size_const: u32,
+        array_sub_type: Option<Box<NativeType>>,
+    },
+    SysInt,
+    SysUInt,
+    VBByRefStr,
+    AnsiBStr,
+    TBStr,
+    VariantBool,
+    FunctionPtr,
+    AsAny,
+    /// A pointer to the first element of a C-style array.
+    LPArray {
+        array_sub_type: Option<Box<NativeType>>,
+        /// The 0-based parameter holding the element count.
+        size_param_index: Option<u32>,
+        /// The element count, or the count added to the size parameter.
+        size_const: Option<u32>,
+    },
+    LPStruct,
+    CustomMarshaler {
+        /// The unused GUID string slot of the descriptor.
+        guid: String,
+        /// The unused native type name slot of the descriptor.
+        native_type_name: String,
+        /// The assembly-qualified name of the `ICustomMarshaler` type.
+        marshal_type: String,
+        marshal_cookie: String,
+    },
+    Error,
+    IInspectable,
+    HString,
+    LPUTF8Str,
+    /// A code with no `UnmanagedType` member, such as the obsolete
+    /// `NATIVE_TYPE_VARIANT` (0x0E) or `NATIVE_TYPE_PTR` (0x10).
+    Other(u8),
+}
+
+impl NativeType {
+    /// Decodes a marshalling descriptor blob, as returned by
+    /// `GetFieldMarshal`.
+    pub fn parse(blob: &[u8]) -> Result<NativeType> {
+        let mut cur = Cursor::new(blob);
+        let code = cur.u8()?;
+        let optional = |cur: &mut Cursor| -> Result<Option<u32>> {
+            if cur.is_empty() {
+                Ok(None)
+            } else {
+                cur.compressed_u32().map(Some)
+            }
+        };
+        let ty = match code {
+            NATIVE_TYPE_FIXEDSYSSTRING => NativeType::ByValTStr {
+                size_const: cur.compressed_u32()?,
+            },
+            NATIVE_TYPE_IUNKNOWN => NativeType::IUnknown {
+                iid_parameter_index: optional(&mut cur)?,
+            },
+            NATIVE_TYPE_IDISPATCH => NativeType::IDispatch {
+                iid_parameter_index: optional(&mut cur)?,
+            },
+            NATIVE_TYPE_INTF => NativeType::Interface {
+                iid_parameter_index: optional(&mut cur)?,
+            },
+            NATIVE_TYPE_SAFEARRAY => {
+                let sub_type = optional(&mut cur)?.map(|vt| vt as u16);
+                let user_defined_sub_type = if cur.is_empty() {
+                    None
+                } else {
+                    Some(read_string(&mut cur)?).filter(|name| !name.is_empty())
+                };
+                NativeType::SafeArray {
+                    sub_type,
+                    user_defined_sub_type,
+                }
+            }
+            NATIVE_TYPE_FIXEDARRAY => {
+                let size_const = cur.compressed_u32()?;
+                let array_sub_type = match optional(&mut cur)? {
+                    Some(sub) => element_type(sub)?,
+                    None => None,
+                };
+                NativeType::ByValArray {
+                    size_const,
+                    array_sub_type,
+                }
+            }
+            NATIVE_TYPE_ARRAY => {
+                let array_sub_type = match optional(&mut cur)? {
+                    Some(sub) => element_type(sub)?,
+                    None => None,
+                };
+                let param = optional(&mut cur)?;
+                let size_const = optional(&mut cur)?;
+                // Descriptors written before the flags existed give the
+                // parameter number without them.
+                let flags = optional(&mut cur)?;
+                let size_param_index =
+                    param.filter(|_| flags.is_none_or(|f| f & NTA_SIZE_PARAM_INDEX_SPECIFIED != 0));
+                NativeType::LPArray {
+                    array_sub_type,
+                    size_param_index,
+                    size_const,
+                }
+            }
+            NATIVE_TYPE_CUSTOMMARSHALER => NativeType::CustomMarshaler {
+                guid: read_string(&mut cur)?,
+                native_type_name: read_string(&mut cur)?,
+                marshal_type: read_string(&mut cur)?,
+                marshal_cookie: read_string(&mut cur)?,
+            },
+            code => NativeType::simple(code),
+        };
+        Ok(ty)
+    }
+
+    /// Returns the descriptor for a code without parameters.
+    fn simple(code: u8) -> NativeType {
+        match code {
+            NATIVE_TYPE_BOOLEAN => NativeType::Bool,
+            NATIVE_TYPE_I1 => NativeType::I1,
+            NATIVE_TYPE_U1 => NativeType::U1,
+            NATIVE_TYPE_I2 => NativeType::I2,
+            NATIVE_TYPE_U2 => NativeType::U2,
+            NATIVE_TYPE_I4 => NativeType::I4,
+            NATIVE_TYPE_U4 => NativeType::U4,
+            NATIVE_TYPE_I8 => NativeType::I8,
+            NATIVE_TYPE_U8 => NativeType::U8,
+            NATIVE_TYPE_R4 => NativeType::R4,
+            NATIVE_TYPE_R8 => NativeType::R8,
+            NATIVE_TYPE_CURRENCY => NativeType::Currency,
+            NATIVE_TYPE_BSTR => NativeType::BStr,
+            NATIVE_TYPE_LPSTR => NativeType::LPStr,
+            NATIVE_TYPE_LPWSTR => NativeType::LPWStr,
+            NATIVE_TYPE_LPTSTR => NativeType::LPTStr,
+            NATIVE_TYPE_FIXEDSYSSTRING => NativeType::ByValTStr { size_const: 0 },
+            NATIVE_TYPE_IUNKNOWN => NativeType::IUnknown {
+                iid_parameter_index: None,
+            },
+            NATIVE_TYPE_IDISPATCH => NativeType::IDispatch {
+                iid_parameter_index: None,
+            },
+            NATIVE_TYPE_STRUCT => NativeType::Struct,
+            NATIVE_TYPE_INTF => NativeType::Interface {
+                iid_parameter_index: None,
+            },
+            NATIVE_TYPE_SAFEARRAY => NativeType::SafeArray {
+                sub_type: None,
+                user_defined_sub_type: None,
+            },
+            NATIVE_TYPE_FIXEDARRAY => NativeType::ByValArray {
+                size_const: 0,
+                array_sub_type: None,
+            },
+            NATIVE_TYPE_INT => NativeType::SysInt,
+            NATIVE_TYPE_UINT => NativeType::SysUInt,
+            NATIVE_TYPE_BYVALSTR => NativeType::VBByRefStr,
+            NATIVE_TYPE_ANSIBSTR => NativeType::AnsiBStr,
+            NATIVE_TYPE_TBSTR => NativeType::TBStr,
+            NATIVE_TYPE_VARIANTBOOL => NativeType::VariantBool,
+            NATIVE_TYPE_FUNC => NativeType::FunctionPtr,
+            NATIVE_TYPE_ASANY => NativeType::AsAny,
+            NATIVE_TYPE_ARRAY => NativeType::LPArray {
+                array_sub_type: None,
+                size_param_index: None,
+                size_const: None,
+            },
+            NATIVE_TYPE_LPSTRUCT => NativeType::LPStruct,
+            NATIVE_TYPE_CUSTOMMARSHALER => NativeType::CustomMarshaler {
+                guid: String::new(),
+                native_type_name: String::new(),
+                marshal_type: String::new(),
+                marshal_cookie: String::new(),
+            },
+            NATIVE_TYPE_ERROR => NativeType::Error,
+            NATIVE_TYPE_IINSPECTABLE => NativeType::IInspectable,
+            NATIVE_TYPE_HSTRING => NativeType::HString,
+            NATIVE_TYPE_LPUTF8STR => NativeType::LPUTF8Str,
+            other => NativeType::Other(other),
+        }
+    }
+
+    /// Returns the `NATIVE_TYPE_*` code (the `UnmanagedType` value).
+    pub fn code(&self) -> u8 {
+        match self {
+            NativeType::Bool => NATIVE_TYPE_BOOLEAN,
+            NativeType::I1 => NATIVE_TYPE_I1,
+            NativeType::U1 => NATIVE_TYPE_U1,
+            NativeType::I2 => NATIVE_TYPE_I2,
+            NativeType::U2 => NATIVE_TYPE_U2,
+            NativeType::I4 => NATIVE_TYPE_I4,
+            NativeType::U4 => NATIVE_TYPE_U4,
+            NativeType::I8 => NATIVE_TYPE_I8,
+            NativeType::U8 => NATIVE_TYPE_U8,
+            NativeType::R4 => NATIVE_TYPE_R4,
+            NativeType::R8 => NATIVE_TYPE_R8,
+            NativeType::Currency => NATIVE_TYPE_CURRENCY,
+            NativeType::BStr => NATIVE_TYPE_BSTR,
+            NativeType::LPStr => NATIVE_TYPE_LPSTR,
+            NativeType::LPWStr => NATIVE_TYPE_LPWSTR,
+            NativeType::LPTStr => NATIVE_TYPE_LPTSTR,
+            NativeType::ByValTStr { .. } => NATIVE_TYPE_FIXEDSYSSTRING,
+            NativeType::IUnknown { .. } => NATIVE_TYPE_IUNKNOWN,
+            NativeType::IDispatch { .. } => NATIVE_TYPE_IDISPATCH,
+            NativeType::Struct => NATIVE_TYPE_STRUCT,
+            NativeType::Interface { .. } => NATIVE_TYPE_INTF,
+            NativeType::SafeArray { .. } => NATIVE_TYPE_SAFEARRAY,
+            NativeType::ByValArray { .. } => NATIVE_TYPE_FIXEDARRAY,
+            NativeType::SysInt => NATIVE_TYPE_INT,
+            NativeType::SysUInt => NATIVE_TYPE_UINT,
+            NativeType::VBByRefStr => NATIVE_TYPE_BYVALSTR,
+            NativeType::AnsiBStr => NATIVE_TYPE_ANSIBSTR,
+            NativeType::TBStr => NATIVE_TYPE_TBSTR,
+            NativeType::VariantBool => NATIVE_TYPE_VARIANTBOOL,
+            NativeType::FunctionPtr => NATIVE_TYPE_FUNC,
+            NativeType::AsAny => NATIVE_TYPE_ASANY,
+            NativeType::LPArray { .. } => NATIVE_TYPE_ARRAY,
+            NativeType::LPStruct => NATIVE_TYPE_LPSTRUCT,
+            NativeType::CustomMarshaler { .. } => NATIVE_TYPE_CUSTOMMARSHALER,
+            NativeType::Error => NATIVE_TYPE_ERROR,
+            NativeType::IInspectable => NATIVE_TYPE_IINSPECTABLE,
+            NativeType::HString => NATIVE_TYPE_HSTRING,
+            NativeType::LPUTF8Str => NATIVE_TYPE_LPUTF8STR,
+            NativeType::Other(code) => *code,
+        }
+    }
+
+    /// Returns the `UnmanagedType` member name, or `None` for
+    /// [`NativeType::Other`].
+    pub fn name(&self) -> Option<&'static str> {
+        Some(match self {
+            NativeType::Bool => "Bool",
+            NativeType::I1 => "I1",
+            NativeType::U1 => "U1",
+            NativeType::I2 => "I2",
+            NativeType::U2 => "U2",
+            NativeType::I4 => "I4",
+            NativeType::U4 => "U4",
+            NativeType::I8 => "I8",
+            NativeType::U8 => "U8",
+            NativeType::R4 => "R4",
+            NativeType::R8 => "R8",
+            NativeType::Currency => "Currency",
+            NativeType::BStr => "BStr",
+            NativeType::LPStr => "LPStr",
+            NativeType::LPWStr => "LPWStr",
+            NativeType::LPTStr => "LPTStr",
+            NativeType::ByValTStr { .. } => "ByValTStr",
+            NativeType::IUnknown { .. } => "IUnknown",
+            NativeType::IDispatch { .. } => "IDispatch",
+            NativeType::Struct => "Struct",
+            NativeType::Interface { .. } => "Interface",
+            NativeType::SafeArray { .. } => "SafeArray",
+            NativeType::ByValArray { .. } => "ByValArray",
+            NativeType::SysInt => "SysInt",
+            NativeType::SysUInt => "SysUInt",
+            NativeType::VBByRefStr => "VBByRefStr",
+            NativeType::AnsiBStr => "AnsiBStr",
+            NativeType::TBStr => "TBStr",
+            NativeType::VariantBool => "VariantBool",
+            NativeType::FunctionPtr => "FunctionPtr",
+            NativeType::AsAny => "AsAny",
+            NativeType::LPArray { .. } => "LPArray",
+            NativeType::LPStruct => "LPStruct",
+            NativeType::CustomMarshaler { .. } => "CustomMarshaler",
+            NativeType::Error => "Error",
+            NativeType::IInspectable => "IInspectable",
+            NativeType::HString => "HString",
+            NativeType::LPUTF8Str => "LPUTF8Str",
+            NativeType::Other(_) => return None,
+        })
+    }
+
+    /// Returns `true` for string marshalling whose character width depends
+    /// on the charset in effect (`LPTStr`, `TBStr` and `ByValTStr`).
+    pub fn is_charset_dependent(&self) -> bool {
+        matches!(
+            self,
+            NativeType::LPTStr | NativeType::TBStr | NativeType::ByValTStr { .. }
+        )
+    }
+}
+
+/// Decodes an array element type, which is a single code.
+fn element_type(code: u32) -> Result<Option<Box<NativeType>>> {
+    let code = u8::try_from(code)
+        .map_err(|_| Error::BadSignature(format!("invalid array element type {code:#x}")))?;
+    Ok((code != NATIVE_TYPE_MAX).then(|| Box::new(NativeType::simple(code))))
+}
+
+/// Reads a length-prefixed UTF-8 string.
+fn read_string(cur: &mut Cursor) -> Result<String> {
+    let len = cur.compressed_u32()? as usize;
+    let bytes = cur.bytes(len)?;
+    String::from_utf8(bytes.to_vec())
+        .map_err(|_| Error::BadSignature("marshalling string is not valid UTF-8".into()))
+}
+
+struct UnmanagedType<'a>(&'a NativeType);
+
+impl fmt::Display for UnmanagedType<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.name() {
+            Some(name) => write!(f, "UnmanagedType.{name}"),
+            None => write!(f, "(UnmanagedType){}", self.0.code()),
+        }
+    }
+}
+
+impl fmt::Display for NativeType {
+    /// Formats the descriptor as the arguments of a C# `MarshalAs`
+    /// attribute.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", UnmanagedType(self))?;
+        match self {
+            NativeType::ByValTStr { size_const } => write!(f, ", SizeConst = {size_const}"),
+            NativeType::IUnknown {
+                iid_parameter_index: Some(index),
+            }
+            | NativeType::IDispatch {
+                iid_parameter_index: Some(index),
+            }
+            | NativeType::Interface {
+                iid_parameter_index: Some(index),
+            } => write!(f, ", IidParameterIndex = {index}"),
+            NativeType::SafeArray {
+                sub_type,
+                user_defined_sub_type,
+            } => {
+                if let Some(vt) = sub_type {
+                    write!(f, ", SafeArraySubType = ")?;
+                    match variant_type_name(*vt) {
+                        Some(name) => write!(f, "VarEnum.{name}")?,
+                        None => write!(f, "(VarEnum){vt}")?,
+                    }
+                }
+                if let Some(name) = user_defined_sub_type {
+                    write!(f, ", SafeArrayUserDefinedSubType = typeof({name})")?;
+                }
+                Ok(())
+            }
+            NativeType::ByValArray {
+                size_const,
+                array_sub_type,
+            } => {
+                write!(f, ", SizeConst = {size_const}")?;
+                if let Some(sub) = array_sub_type {
+                    write!(f, ", ArraySubType = {}", UnmanagedType(sub))?;
+                }
+                Ok(())
+            }
+            NativeType::LPArray {
+                array_sub_type,
+                size_param_index,
+                size_const,
+            } => {
+                if let Some(sub) = array_sub_type {
+                    write!(f, ", ArraySubType = {}", UnmanagedType(sub))?;
+                }
+                if let Some(index) = size_param_index {
+                    write!(f, ", SizeParamIndex = {index}")?;
+                }
+                if let Some(size) = size_const {
+                    write!(f, ", SizeConst = {size}")?;
+                }
+                Ok(())
+            }
+            NativeType::CustomMarshaler {
+                marshal_type,
+                marshal_cookie,
+                ..
+            } => {
+                write!(f, ", MarshalType = {marshal_type:?}")?;
+                if !marshal_cookie.is_empty() {
+                    write!(f, ", MarshalCookie = {marshal_cookie:?}")?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Returns the `VarEnum` member name of a `VARENUM` value, including the
+/// `VT_ARRAY`, `VT_BYREF` and `VT_VECTOR` modifier bits as a suffix.
+pub fn variant_type_name(vt: u16) -> Option<String> {
+    let base = match vt & 0x0FFF {
+        0 => "VT_EMPTY",
+        1 => "VT_NULL",
+        2 => "VT_I2",
+        3 => "VT_I4",
+        4 => "VT_R4",
+        5 => "VT_R8",
+        6 => "VT_CY",
+        7 => "VT_DATE",
+        8 => "VT_BSTR",
+        9 => "VT_DISPATCH",
+        10 => "VT_ERROR",
+        11 => "VT_BOOL",
+        12 => "VT_VARIANT",
+        13 => "VT_UNKNOWN",
+        14 => "VT_DECIMAL",
+        16 => "VT_I1",
+        17 => "VT_UI1",
+        18 => "VT_UI2",
+        19 => "VT_UI4",
+        20 => "VT_I8",
+        21 => "VT_UI8",
+        22 => "VT_INT",
+        23 => "VT_UINT",
+        24 => "VT_VOID",
+        25 => "VT_HRESULT",
+        26 => "VT_PTR",
+        27 => "VT_SAFEARRAY",
+        28 => "VT_CARRAY",
+        29 => "VT_USERDEFINED",
+        30 => "VT_LPSTR",
+        31 => "VT_LPWSTR",
+        36 => "VT_RECORD",
+        37 => "VT_INT_PTR",
+        38 => "VT_UINT_PTR",
+        64 => "VT_FILETIME",
+        65 => "VT_BLOB",
+        66 => "VT_STREAM",
+        67 => "VT_STORAGE",
+        68 => "VT_STREAMED_OBJECT",
+        69 => "VT_STORED_OBJECT",
+        70 => "VT_BLOB_OBJECT",
+        71 => "VT_CF",
+        72 => "VT_CLSID",
+        _ => return None,
+    };
+    let mut name = base.to_string();
+    for (bit, suffix) in [
+        (0x1000, " | VarEnum.VT_VECTOR"),
+        (0x2000, " | VarEnum.VT_ARRAY"),
+        (0x4000, " | VarEnum.VT_BYREF"),
+    ] {
+        if vt & bit != 0 {
+            name.push_str(suffix);
+        }
+    }
+    Some(name)
+}
+
+/// `PInvokeAttributes` character set (`CharSetMask`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CharSet {
+    NotSpecified,
+    Ansi,
+    Unicode,
+    Auto,
+}
+
+/// `PInvokeAttributes` calling convention (`CallConvMask`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PInvokeCallingConvention {
+    /// No calling convention bits; the runtime uses the platform default.
+    NotSpecified,
+    /// The platform default (`CallingConvention.Winapi`).
+    Winapi,
+    Cdecl,
+    StdCall,
+    ThisCall,
+    FastCall,
+    /// A reserved value.
+    Other(u16),
+}
+
+/// A decoded `ImplMap` row: the native export a P/Invoke method binds to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PInvokeMap {
+    /// The `MethodDef` (or, rarely, `Field`) token the row belongs to.
+    pub member: u32,
+    /// The raw `PInvokeAttributes` flags.
+    pub flags: u16,
+    /// The export name (`DllImport.EntryPoint`).
+    pub entry_point: String,
+    /// The native library name from `ModuleRef`.
+    pub module: String,
+    /// Whether the method's `HRESULT` is returned as is
+    /// (`MethodImplAttributes.PreserveSig`, set by `DllImport` by default).
+    pub preserve_sig: bool,
+}
+
+impl PInvokeMap {
+    /// `PInvokeAttributes.NoMangle` (`DllImport.ExactSpelling`).
+    pub const NO_MANGLE: u16 = 0x0001;
+    /// `PInvokeAttributes.CharSetMask`.
+    pub const CHAR_SET_MASK: u16 = 0x0006;
+    /// `PInvokeAttributes.BestFitMask`.
+    pub const BEST_FIT_MASK: u16 = 0x0030;
+    /// `PInvokeAttributes.SupportsLastError` (`DllImport.SetLastError`).
+    pub const SUPPORTS_LAST_ERROR: u16 = 0x0040;
+    /// `PInvokeAttributes.CallConvMask`.
+    pub const CALL_CONV_MASK: u16 = 0x0700;
+    /// `PInvokeAttributes.ThrowOnUnmappableCharMask`.
+    pub const THROW_ON_UNMAPPABLE_CHAR_MASK: u16 = 0x3000;
+
+    /// Returns the character set used to marshal strings.
+    pub fn char_set(&self) -> CharSet {
+        match self.flags & Self::CHAR_SET_MASK {
+            0x0002 => CharSet::Ansi,
+            0x0004 => CharSet::Unicode,
+            0x0006 => CharSet::Auto,
+            _ => CharSet::NotSpecified,
+        }
+    }
+
+    /// Returns the unmanaged calling convention.
+    pub fn calling_convention(&self) -> PInvokeCallingConvention {
+        match self.flags & Self::CALL_CONV_MASK {
+            0 => PInvokeCallingConvention::NotSpecified,
+            0x0100 => PInvokeCallingConvention::Winapi,
+            0x0200 => PInvokeCallingConvention::Cdecl,
+            0x0300 => PInvokeCallingConvention::StdCall,
+            0x0400 => PInvokeCallingConvention::ThisCall,
+            0x0500 => PInvokeCallingConvention::FastCall,
+            other => PInvokeCallingConvention::Other(other),
+        }
+    }
+
+    /// Returns `true` if the entry point name is used without `A`/`W`
+    /// probing (`DllImport.ExactSpelling`).
+    pub fn exact_spelling(&self) -> bool {
+        self.flags & Self::NO_MANGLE != 0
+    }
+
+    /// Returns `true` if the runtime saves the last Win32 error after the
+    /// call (`DllImport.SetLastError`).
+    pub fn set_last_error(&self) -> bool {
+        self.flags & Self::SUPPORTS_LAST_ERROR != 0
+    }
+
+    /// Returns `DllImport.BestFitMapping`, or `None` if the assembly-level
+    /// setting applies.
+    pub fn best_fit_mapping(&self) -> Option<bool> {
+        match self.flags & Self::BEST_FIT_MASK {
+            0x0010 => Some(true),
+            0x0020 => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Returns `DllImport.ThrowOnUnmappableChar`, or `None` if the
+    /// assembly-level setting applies.
+    pub fn throw_on_unmappable_char(&self) -> Option<bool> {
+        match self.flags & Self::THROW_ON_UNMAPPABLE_CHAR_MASK {
+            0x1000 => Some(true),
+            0x2000 => Some(false),
+            _ => None,
+        }
+    }
+}
+
+impl MetadataReader {
+    /// Returns the marshalling descriptor of a `Field` or `Param` token.
+    pub fn field_marshal(&self, token: u32) -> Result<Option<NativeType>> {
+        match self.find_row(TableId::FieldMarshal, 0, token)? {
+            Some(rid) => {
+                let row = self.row::<FieldMarshalRow>(rid)?;
+                NativeType::parse(self.blob(row.native_type)?).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the P/Invoke map of a `MethodDef` token.
+    pub fn pinvoke_map(&self, method: u32) -> Result<Option<PInvokeMap>> {
+        match self.find_row(TableId::ImplMap, 1, method)? {
+            Some(rid) => self.impl_map(rid).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns every P/Invoke map in the module, in `ImplMap` order.
+    pub fn pinvoke_maps(&self) -> Result<Vec<PInvokeMap>> {
+        (1..=self.row_count(TableId::ImplMap))
+            .map(|rid| self.impl_map(rid))
+            .collect()
+    }
+
+    /// Decodes row `rid` of the `ImplMap` table.
+    pub fn impl_map(&self, rid: u32) -> Result<PInvokeMap> {
+        let row = self.row::<ImplMapRow>(rid)?;
+        let module = if row.import_scope == 0 {
+            String::new()
+        } else {
+            let module_ref = self.row::<ModuleRefRow>(row.import_scope)?;
+            self.string(module_ref.name)?.to_string()
+        };
+        let preserve_sig = match TableId::from_token(row.member_forwarded) {
+            Some((TableId::MethodDef, method)) => {
+                self.row::<MethodDefRow>(method)?.impl_flags & METHOD_IMPL_PRESERVE_SIG != 0
+            }
+            _ => false,
+        };
+        Ok(PInvokeMap {
+            member: row.member_forwarded,
+            flags: row.mapping_flags,
+            entry_point: self.string(row.import_name)?.to_string(),
+            module,
+            preserve_sig,
+        })
+    }
+}
@@ -0,0 +1,523 @@
+//! CLI metadata reader: metadata root, streams, heaps and tables.
+
+use std::ops::Range;
+
+use super::cursor::Cursor;
+use super::tables::{CodedIndex, ColumnType, TableId, TableRow};
+use crate::error::{Error, Result};
+
+/// Signature of the metadata root (`BSJB`).
+pub const METADATA_SIGNATURE: u32 = 0x424A_5342;
+
+/// `HeapSizes` flag: `#Strings` indices are 4 bytes wide.
+pub const HEAP_STRING_4: u8 = 0x01;
+/// `HeapSizes` flag: `#GUID` indices are 4 bytes wide.
+pub const HEAP_GUID_4: u8 = 0x02;
+/// `HeapSizes` flag: `#Blob` indices are 4 bytes wide.
+pub const HEAP_BLOB_4: u8 = 0x04;
+/// `HeapSizes` flag: the tables stream holds an Edit-and-Continue delta.
+pub const HEAP_ENC_DELTA: u8 = 0x20;
+/// `HeapSizes` flag: an extra 4-byte value follows the row counts.
+pub const HEAP_EXTRA_DATA: u8 = 0x40;
+/// `HeapSizes` flag: the delta contains deleted-token marks.
+pub const HEAP_DELETED_MARKS: u8 = 0x80;
+
+/// A stream header from the metadata root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamHeader {
+    pub name: String,
+    /// Offset of the stream from the start of the metadata root.
+    pub offset: u32,
+    pub size: u32,
+}
+
+/// Contents of the `#Pdb` stream of a Portable PDB.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PdbStream {
+    /// The PDB id (GUID followed by a 4-byte stamp).
+    pub id: [u8; 20],
+    /// Entry point method token, or 0.
+    pub entry_point: u32,
+    /// Bit mask of type system tables referenced by the PDB.
+    pub referenced_type_system_tables: u64,
+    /// Row counts of the referenced type system tables, indexed by table number.
+    pub type_system_table_rows: [u32; 64],
+}
+
+#[derive(Debug, Clone, Default)]
+struct TableLayout {
+    rows: u32,
+    offset: usize,
+    row_size: usize,
+    /// Offset within the row and width in bytes of each column.
+    columns: Vec<(usize, usize)>,
+}
+
+/// A parsed CLI metadata blob (the data starting at the `BSJB` root).
+///
+/// This is a pure-Rust counterpart of `IMetaDataImport`/`IMetaDataTables`
+/// that works on any platform. The reader owns a copy of the metadata.
+#[derive(Clone)]
+pub struct MetadataReader {
+    data: Vec<u8>,
+    major_version: u16,
+    minor_version: u16,
+    version: String,
+    flags: u16,
+    streams: Vec<StreamHeader>,
+    strings: Range<usize>,
+    blobs: Range<usize>,
+    guids: Range<usize>,
+    user_strings: Range<usize>,
+    is_uncompressed: bool,
+    is_minimal_delta: bool,
+    tables_major_version: u8,
+    tables_minor_version: u8,
+    heap_sizes: u8,
+    valid: u64,
+    sorted: u64,
+    tables: Vec<TableLayout>,
+    pdb: Option<PdbStream>,
+}
+
+impl MetadataReader {
+    /// Parses a metadata blob.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let mut cur = Cursor::new(&data);
+        if cur.u32()? != METADATA_SIGNATURE {
+            return Err(Error::BadMetadata("missing BSJB signature".into()));
+        }
+        let major_version = cur.u16()?;
+        let minor_version = cur.u16()?;
+        cur.skip(4)?; // reserved
+        let version_len = cur.u32()? as usize;
+        let raw_version = cur.bytes(version_len)?;
+        let end = raw_version
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(raw_version.len());
+        let version = String::from_utf8_lossy(&raw_version[..end]).into_owned();
+        let flags = cur.u16()?;
+        let stream_count = cur.u16()?;
+
+        let mut streams = Vec::with_capacity(stream_count as usize);
+        for _ in 0..stream_count {
+            let offset = cur.u32()?;
+            let size = cur.u32()?;
+            let name_start = cur.position();
+            let name = String::from_utf8_lossy(cur.c_str()?).into_owned();
+            let consumed = cur.position() - name_start;
+            cur.skip((4 - consumed % 4) % 4)?;
+            streams.push(StreamHeader { name, offset, size });
+        }
+
+        let mut reader = Self {
+            data,
+            major_version,
+            minor_version,
+            version,
+            flags,
+            streams,
+            strings: 0..0,
+            blobs: 0..0,
+            guids: 0..0,
+            user_strings: 0..0,
+            is_uncompressed: false,
+            is_minimal_delta: false,
+            tables_major_version: 0,
+            tables_minor_version: 0,
+            heap_sizes: 0,
+            valid: 0,
+            sorted: 0,
+            tables: vec![TableLayout::default(); 64],
+            pdb: None,
+        };
+
+        let mut tables_range = None;
+        for stream in &reader.streams {
+            let start = stream.offset as usize;
+            let range = start..start + stream.size as usize;
+            if range.end > reader.data.len() {
+                return Err(Error::BadMetadata(format!(
+                    "stream {} extends past the end of the metadata",
+                    stream.name
+                )));
+            }
+            match stream.name.as_str() {
+                "#Strings" => reader.strings = range,
+                "#Blob" => reader.blobs = range,
+                "#GUID" => reader.guids = range,
+                "#US" => reader.user_strings = range,
+                "#~" => tables_range = Some(range),
+                "#-" => {
+                    reader.is_uncompressed = true;
+                    tables_range = Some(range);
+                }
+                "#JTD" => reader.is_minimal_delta = true,
+                "#Pdb" => reader.pdb = Some(parse_pdb_stream(&reader.data[range])?),
+                _ => {}
+            }
+        }
+
+        if let Some(range) = tables_range {
+            reader.parse_tables(range)?;
+        }
+        Ok(reader)
+    }
+
+    fn parse_tables(&mut self, range: Range<usize>) -> Result<()> {
+        let mut cur = Cursor::new(&self.data[range.clone()]);
+        cur.skip(4)?; // reserved
+        self.tables_major_version = cur.u8()?;
+        self.tables_minor_version = cur.u8()?;
+        self.heap_sizes = cur.u8()?;
+        cur.skip(1)?; // reserved
+        self.valid = cur.u64()?;
+        self.sorted = cur.u64()?;
+
+        for table in 0..64 {
+            if self.valid & (1u64 << table) != 0 {
+                if TableId::from_u8(table as u8).is_none() {
+                    return Err(Error::BadMetadata(format!("unknown table {table:#04x}")));
+                }
+                self.tables[table].rows = cur.u32()?;
+            }
+        }
+        if self.heap_sizes & HEAP_EXTRA_DATA != 0 {
+            cur.skip(4)?;
+        }
+
+        let mut offset = range.start + cur.position();
+        for &table in TableId::ALL {
+            let mut columns = Vec::new();
+            let mut row_size = 0;
+            for column in table.columns() {
+                let width = self.column_width(column.ty);
+                columns.push((row_size, width));
+                row_size += width;
+            }
+            let layout = &mut self.tables[table as usize];
+            layout.row_size = row_size;
+            layout.columns = columns;
+            layout.offset = offset;
+            offset += row_size * layout.rows as usize;
+        }
+        if offset > range.end {
+            return Err(Error::BadMetadata(
+                "table data extends past the end of the tables stream".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Row count used to size indices into `table`, including rows that only
+    /// exist in the type system image a Portable PDB refers to.
+    fn index_row_count(&self, table: TableId) -> u32 {
+        let local = self.tables[table as usize].rows;
+        match &self.pdb {
+            Some(pdb) if pdb.referenced_type_system_tables & (1u64 << table as u8) != 0 => {
+                local.max(pdb.type_system_table_rows[table as usize])
+            }
+            _ => local,
+        }
+    }
+
+    fn column_width(&self, ty: ColumnType) -> usize {
+        if self.is_minimal_delta && !matches!(ty, ColumnType::U16 | ColumnType::U32) {
+            return 4;
+        }
+        let wide = |flag: u8| if self.heap_sizes & flag != 0 { 4 } else { 2 };
+        match ty {
+            ColumnType::U16 => 2,
+            ColumnType::U32 => 4,
+            ColumnType::Strings => wide(HEAP_STRING_4),
+            ColumnType::Guid => wide(HEAP_GUID_4),
+            ColumnType::Blob => wide(HEAP_BLOB_4),
+            ColumnType::Table(table) => {
+                if self.index_row_count(table) < 0x1_0000 {
+                    2
+                } else {
+                    4
+                }
+            }
+            ColumnType::Coded(coded) => {
+                let limit = 1u32 << (16 - coded.tag_bits());
+                let max_rows = coded
+                    .tables()
+                    .iter()
+                    .flatten()
+                    .map(|&t| self.index_row_count(t))
+                    .max()
+                    .unwrap_or(0);
+                if max_rows < limit { 2 } else { 4 }
+            }
+        }
+    }
+
+    /// Returns the raw metadata blob.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the metadata root major version.
+    pub fn major_version(&self) -> u16 {
+        self.major_version
+    }
+
+    /// Returns the metadata root minor version.
+    pub fn minor_version(&self) -> u16 {
+        self.minor_version
+    }
+
+    /// Returns the runtime version string from the metadata root
+    /// (the value of `IMetaDataImport2::GetVersionString`, e.g. `v4.0.30319`).
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Returns the metadata root flags.
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    /// Returns the stream headers.
+    pub fn streams(&self) -> &[StreamHeader] {
+        &self.streams
+    }
+
+    /// Returns the contents of the named stream.
+    pub fn stream(&self, name: &str) -> Option<&[u8]> {
+        let header = self.streams.iter().find(|s| s.name == name)?;
+        let start = header.offset as usize;
+        self.data.get(start..start + header.size as usize)
+    }
+
+    /// Returns `true` if the tables are stored in the uncompressed `#-` format.
+    pub fn is_uncompressed(&self) -> bool {
+        self.is_uncompressed
+    }
+
+    /// Returns `true` if the metadata is a minimal Edit-and-Continue delta (`#JTD`).
+    pub fn is_minimal_delta(&self) -> bool {
+        self.is_minimal_delta
+    }
+
+    /// Returns the tables stream major and minor version.
+    pub fn tables_version(&self) -> (u8, u8) {
+        (self.tables_major_version, self.tables_minor_version)
+    }
+
+    /// Returns the `HeapSizes` byte of the tables stream.
+    pub fn heap_sizes(&self) -> u8 {
+        self.heap_sizes
+    }
+
+    /// Returns the bit mask of present tables.
+    pub fn valid_tables(&self) -> u64 {
+        self.valid
+    }
+
+    /// Returns the bit mask of sorted tables.
+    pub fn sorted_tables(&self) -> u64 {
+        self.sorted
+    }
+
+    /// Returns the `#Pdb` stream if this is a Portable PDB.
+    pub fn pdb_stream(&self) -> Option<&PdbStream> {
+        self.pdb.as_ref()
+    }
+
+    /// Returns the `#Strings` heap.
+    pub fn strings_heap(&self) -> &[u8] {
+        &self.data[self.strings.clone()]
+    }
+
+    /// Returns the `#Blob` heap.
+    pub fn blob_heap(&self) -> &[u8] {
+        &self.data[self.blobs.clone()]
+    }
+
+    /// Returns the `#GUID` heap.
+    pub fn guid_heap(&self) -> &[u8] {
+        &self.data[self.guids.clone()]
+    }
+
+    /// Returns the `#US` heap.
+    pub fn user_string_heap(&self) -> &[u8] {
+        &self.data[self.user_strings.clone()]
+    }
+
+    /// Returns the string at `index` in the `#Strings` heap.
+    pub fn string(&self, index: u32) -> Result<&str> {
+        let heap = self.strings_heap();
+        let bytes = heap
+            .get(index as usize..)
+            .ok_or_else(|| Error::BadMetadata(format!("string index {index:#x} out of range")))?;
+        let bytes = Cursor::new(bytes).c_str()?;
+        std::str::from_utf8(bytes)
+            .map_err(|_| Error::BadMetadata(format!("string at {index:#x} is not valid UTF-8")))
+    }
+
+    /// Returns the blob at `index` in the `#Blob` heap.
+    pub fn blob(&self, index: u32) -> Result<&[u8]> {
+        let heap = self.blob_heap();
+        let mut cur = Cursor::at(heap, index as usize);
+        if index as usize >= heap.len() {
+            return if index == 0 {
+                Ok(&[])
+            } else {
+                Err(Error::BadMetadata(format!(
+                    "blob index {index:#x} out of range"
+                )))
+            };
+        }
+        let len = cur.compressed_u32()? as usize;
+        cur.bytes(len)
+    }
+
+    /// Returns the GUID at 1-based `index` in the `#GUID` heap, or `None` for index 0.
+    pub fn guid(&self, index: u32) -> Result<Option<[u8; 16]>> {
+        if index == 0 {
+            return Ok(None);
+        }
+        let start = (index as usize - 1) * 16;
+        let bytes = self
+            .guid_heap()
+            .get(start..start + 16)
+            .ok_or_else(|| Error::BadMetadata(format!("GUID index {index} out of range")))?;
+        Ok(Some(bytes.try_into().unwrap()))
+    }
+
+    /// Returns the user string at `index` in the `#US` heap
+    /// (the low 24 bits of an `mdString` token).
+    pub fn user_string(&self, index: u32) -> Result<String> {
+        Ok(String::from_utf16_lossy(&self.user_string_units(index)?))
+    }
+
+    /// Returns the UTF-16 code units of the user string at `index`, which
+    /// may hold unpaired surrogates.
+    pub(crate) fn user_string_units(&self, index: u32) -> Result<Vec<u16>> {
+        let heap = self.user_string_heap();
+        if index as usize >= heap.len() {
+            return Err(Error::BadMetadata(format!(
+                "user string index {index:#x} out of range"
+            )));
+        }
+        let mut cur = Cursor::at(heap, index as usize);
+        let len = cur.compressed_u32()? as usize;
+        let bytes = cur.bytes(len)?;
+        // The final byte is a flag that marks strings needing special handling.
+        Ok(bytes[..len & !1]
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect())
+    }
+
+    /// Returns the number of rows in `table`.
+    pub fn row_count(&self, table: TableId) -> u32 {
+        self.tables[table as usize].rows
+    }
+
+    /// Returns the size in bytes of one row of `table`.
+    pub fn row_size(&self, table: TableId) -> usize {
+        self.tables[table as usize].row_size
+    }
+
+    /// Returns the size in bytes of column `column` of `table`.
+    pub fn column_size(&self, table: TableId, column: usize) -> usize {
+        self.tables[table as usize]
+            .columns
+            .get(column)
+            .map_or(0, |&(_, width)| width)
+    }
+
+    /// Returns the offset within a row of column `column` of `table`.
+    pub fn column_offset(&self, table: TableId, column: usize) -> usize {
+        self.tables[table as usize]
+            .columns
+            .get(column)
+            .map_or(0, |&(offset, _)| offset)
+    }
+
+    /// Returns `true` if `table` is marked as sorted.
+    pub fn is_sorted(&self, table: TableId) -> bool {
+        self.sorted & (1u64 << table as u8) != 0
+    }
+
+    /// Returns the raw bytes of row `rid` (1-based) of `table`.
+    pub fn row_bytes(&self, table: TableId, rid: u32) -> Result<&[u8]> {
+        let layout = &self.tables[table as usize];
+        if rid == 0 || rid > layout.rows {
+            return Err(Error::BadMetadata(format!(
+                "row {rid} out of range for table {table} ({} rows)",
+                layout.rows
+            )));
+        }
+        let start = layout.offset + (rid as usize - 1) * layout.row_size;
+        Ok(&self.data[start..start + layout.row_size])
+    }
+
+    /// Returns the undecoded value of a column.
+    pub fn raw_column(&self, table: TableId, rid: u32, column: usize) -> Result<u32> {
+        let row = self.row_bytes(table, rid)?;
+        let &(offset, width) =
+            self.tables[table as usize]
+                .columns
+                .get(column)
+                .ok_or_else(|| {
+                    Error::BadMetadata(format!("column {column} out of range for {table}"))
+                })?;
+        Cursor::at(row, offset).index(width == 4)
+    }
+
+    /// Returns the value of a column, decoding coded indices into tokens.
+    pub fn column(&self, table: TableId, rid: u32, column: usize) -> Result<u32> {
+        let raw = self.raw_column(table, rid, column)?;
+        match table.columns()[column].ty {
+            ColumnType::Coded(coded) => decode_coded(coded, raw),
+            _ => Ok(raw),
+        }
+    }
+
+    /// Returns all decoded column values of a row.
+    pub fn columns(&self, table: TableId, rid: u32) -> Result<Vec<u32>> {
+        (0..table.columns().len())
+            .map(|column| self.column(table, rid, column))
+            .collect()
+    }
+
+    /// Reads row `rid` (1-based) as a typed row.
+    pub fn row<R: TableRow>(&self, rid: u32) -> Result<R> {
+        Ok(R::from_columns(&self.columns(R::TABLE, rid)?))
+    }
+
+    /// Iterates over all rows of a table as `(rid, row)` pairs.
+    pub fn rows<R: TableRow>(&self) -> impl Iterator<Item = Result<(u32, R)>> + '_ {
+        (1..=self.row_count(R::TABLE)).map(move |rid| Ok((rid, self.row::<R>(rid)?)))
+    }
+}
+
+fn decode_coded(coded: CodedIndex, raw: u32) -> Result<u32> {
+    coded
+        .decode(raw)
+        .ok_or_else(|| Error::BadMetadata(format!("invalid {coded:?} coded index {raw:#x}")))
+}
+
+fn parse_pdb_stream(data: &[u8]) -> Result<PdbStream> {
+    let mut cur = Cursor::new(data);
+    let id = cur.bytes(20)?.try_into().unwrap();
+    let entry_point = cur.u32()?;
+    let referenced_type_system_tables = cur.u64()?;
+    let mut type_system_table_rows = [0u32; 64];
+    for (table, rows) in type_system_table_rows.iter_mut().enumerate() {
+        if referenced_type_system_tables & (1u64 << table) != 0 {
+            *rows = cur.u32()?;
+        }
+    }
+    Ok(PdbStream {
+        id,
+        entry_point,
+        referenced_type_system_tables,
+        type_system_table_rows,
+    })
+}
@@ -0,0 +1,337 @@
+//! Higher-level navigation over the metadata tables: member lists, owner
+//! lookups, keyed searches and type names.
+
+use std::ops::Range;
+
+use super::metadata::MetadataReader;
+use super::tables::{
+    CodedIndex, ColumnType, NestedClassRow, TableId, TypeDefRow, TypeRefRow, token_rid,
+};
+use crate::error::{Error, Result};
+
+impl MetadataReader {
+    /// Follows an indirection (`*Ptr`) table if the metadata has one.
+    fn resolve_ptr(&self, ptr_table: TableId, index: u32) -> Result<u32> {
+        if self.row_count(ptr_table) == 0 {
+            Ok(index)
+        } else {
+            self.column(ptr_table, index, 0)
+        }
+    }
+
+    /// Returns the range of child indices owned by row `rid` of a table with a
+    /// list column (e.g. `TypeDef.FieldList`).
+    fn list_range(
+        &self,
+        table: TableId,
+        column: usize,
+        rid: u32,
+        child: TableId,
+    ) -> Result<Range<u32>> {
+        let start = self.column(table, rid, column)?;
+        let child_rows = match child {
+            TableId::Field => self.row_count(TableId::FieldPtr).max(self.row_count(child)),
+            TableId::MethodDef => self
+                .row_count(TableId::MethodPtr)
+                .max(self.row_count(child)),
+            TableId::Param => self.row_count(TableId::ParamPtr).max(self.row_count(child)),
+            TableId::Event => self.row_count(TableId::EventPtr).max(self.row_count(child)),
+            TableId::Property => self
+                .row_count(TableId::PropertyPtr)
+                .max(self.row_count(child)),
+            _ => self.row_count(child),
+        };
+        let end = if rid < self.row_count(table) {
+            self.column(table, rid + 1, column)?
+        } else {
+            child_rows + 1
+        };
+        let limit = child_rows + 1;
+        let start = start.min(limit);
+        Ok(start..end.clamp(start, limit))
+    }
+
+    fn list(
+        &self,
+        table: TableId,
+        column: usize,
+        rid: u32,
+        child: TableId,
+        ptr: TableId,
+    ) -> Result<Vec<u32>> {
+        self.list_range(table, column, rid, child)?
+            .map(|index| self.resolve_ptr(ptr, index))
+            .collect()
+    }
+
+    /// Returns the `Field` rids of a type.
+    pub fn type_def_fields(&self, type_rid: u32) -> Result<Vec<u32>> {
+        self.list(
+            TableId::TypeDef,
+            4,
+            type_rid,
+            TableId::Field,
+            TableId::FieldPtr,
+        )
+    }
+
+    /// Returns the `MethodDef` rids of a type.
+    pub fn type_def_methods(&self, type_rid: u32) -> Result<Vec<u32>> {
+        self.list(
+            TableId::TypeDef,
+            5,
+            type_rid,
+            TableId::MethodDef,
+            TableId::MethodPtr,
+        )
+    }
+
+    /// Returns the `Param` rids of a method.
+    pub fn method_params(&self, method_rid: u32) -> Result<Vec<u32>> {
+        self.list(
+            TableId::MethodDef,
+            5,
+            method_rid,
+            TableId::Param,
+            TableId::ParamPtr,
+        )
+    }
+
+    /// Returns the `Event` rids of a type.
+    pub fn type_def_events(&self, type_rid: u32) -> Result<Vec<u32>> {
+        match self.find_row(TableId::EventMap, 0, type_rid)? {
+            Some(map) => self.list(TableId::EventMap, 1, map, TableId::Event, TableId::EventPtr),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Returns the `Property` rids of a type.
+    pub fn type_def_properties(&self, type_rid: u32) -> Result<Vec<u32>> {
+        match self.find_row(TableId::PropertyMap, 0, type_rid)? {
+            Some(map) => self.list(
+                TableId::PropertyMap,
+                1,
+                map,
+                TableId::Property,
+                TableId::PropertyPtr,
+            ),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Returns the rid of the type that owns a list entry, by searching the
+    /// owner's list column.
+    fn list_owner(
+        &self,
+        table: TableId,
+        column: usize,
+        child: TableId,
+        ptr: TableId,
+        child_rid: u32,
+    ) -> Result<Option<u32>> {
+        // With an indirection table the lists are not in child order, so map
+        // the child back to its position first.
+        let position = if self.row_count(ptr) == 0 {
+            child_rid
+        } else {
+            match (1..=self.row_count(ptr))
+                .find(|&i| self.column(ptr, i, 0).ok() == Some(child_rid))
+            {
+                Some(position) => position,
+                None => return Ok(None),
+            }
+        };
+        let rows = self.row_count(table);
+        let (mut lo, mut hi) = (1u32, rows + 1);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.column(table, mid, column)? <= position {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        let owner = lo - 1;
+        if owner == 0 {
+            return Ok(None);
+        }
+        let range = self.list_range(table, column, owner, child)?;
+        Ok(range.contains(&position).then_some(owner))
+    }
+
+    /// Returns the `TypeDef` rid that declares a field.
+    pub fn field_declaring_type(&self, field_rid: u32) -> Result<Option<u32>> {
+        self.list_owner(
+            TableId::TypeDef,
+            4,
+            TableId::Field,
+            TableId::FieldPtr,
+            field_rid,
+        )
+    }
+
+    /// Returns the `TypeDef` rid that declares a method.
+    pub fn method_declaring_type(&self, method_rid: u32) -> Result<Option<u32>> {
+        self.list_owner(
+            TableId::TypeDef,
+            5,
+            TableId::MethodDef,
+            TableId::MethodPtr,
+            method_rid,
+        )
+    }
+
+    /// Returns the `MethodDef` rid that declares a parameter.
+    pub fn param_declaring_method(&self, param_rid: u32) -> Result<Option<u32>> {
+        self.list_owner(
+            TableId::MethodDef,
+            5,
+            TableId::Param,
+            TableId::ParamPtr,
+            param_rid,
+        )
+    }
+
+    /// Returns the rids of all rows of `table` whose `column` equals `value`.
+    ///
+    /// Coded index columns are compared as tokens. Sorted tables are searched
+    /// with a binary search, others are scanned.
+    pub fn find_rows(&self, table: TableId, column: usize, value: u32) -> Result<Vec<u32>> {
+        let rows = self.row_count(table);
+        if !self.is_sorted(table) {
+            let mut found = Vec::new();
+            for rid in 1..=rows {
+                if self.column(table, rid, column)? == value {
+                    found.push(rid);
+                }
+            }
+            return Ok(found);
+        }
+
+        // Sorted tables are ordered by the raw column value, which for coded
+        // indices differs from token order.
+        let key = match table.columns()[column].ty {
+            ColumnType::Coded(coded) => match coded.encode(value) {
+                Some(key) => key,
+                None => return Ok(Vec::new()),
+            },
+            _ => value,
+        };
+        let lower = self.partition_point(table, column, |v| v < key)?;
+        let upper = self.partition_point(table, column, |v| v <= key)?;
+        Ok((lower..upper).collect())
+    }
+
+    /// Returns the rid of the first row of `table` whose `column` equals `value`.
+    pub fn find_row(&self, table: TableId, column: usize, value: u32) -> Result<Option<u32>> {
+        Ok(self.find_rows(table, column, value)?.first().copied())
+    }
+
+    fn partition_point(
+        &self,
+        table: TableId,
+        column: usize,
+        pred: impl Fn(u32) -> bool,
+    ) -> Result<u32> {
+        let (mut lo, mut hi) = (1u32, self.row_count(table) + 1);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if pred(self.raw_column(table, mid, column)?) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        Ok(lo)
+    }
+
+    /// Returns the `CustomAttribute` rids attached to `token`.
+    pub fn custom_attributes(&self, token: u32) -> Result<Vec<u32>> {
+        if CodedIndex::HasCustomAttribute.encode(token).is_none() {
+            return Ok(Vec::new());
+        }
+        self.find_rows(TableId::CustomAttribute, 0, token)
+    }
+
+    /// Returns the `TypeDef` rid enclosing a nested type, if any.
+    pub fn enclosing_type(&self, type_rid: u32) -> Result<Option<u32>> {
+        match self.find_row(TableId::NestedClass, 0, type_rid)? {
+            Some(rid) => Ok(Some(self.row::<NestedClassRow>(rid)?.enclosing_class)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the `TypeDef` rids nested directly inside a type.
+    pub fn nested_types(&self, type_rid: u32) -> Result<Vec<u32>> {
+        self.find_rows(TableId::NestedClass, 1, type_rid)?
+            .into_iter()
+            .map(|rid| Ok(self.row::<NestedClassRow>(rid)?.nested_class))
+            .collect()
+    }
+
+    /// Returns the namespace and name of a `TypeDef` or `TypeRef`.
+    pub fn type_namespace_and_name(&self, token: u32) -> Result<(&str, &str)> {
+        match TableId::from_token(token) {
+            Some((TableId::TypeDef, rid)) => {
+                let row = self.row::<TypeDefRow>(rid)?;
+                Ok((self.string(row.namespace)?, self.string(row.name)?))
+            }
+            Some((TableId::TypeRef, rid)) => {
+                let row = self.row::<TypeRefRow>(rid)?;
+                Ok((self.string(row.namespace)?, self.string(row.name)?))
+            }
+            _ => Err(Error::BadMetadata(format!(
+                "token {token:#010x} is not a TypeDef or TypeRef"
+            ))),
+        }
+    }
+
+    /// Returns the full name of a `TypeDef` or `TypeRef` in reflection
+    /// notation, with nested types separated by `+` (e.g. `Ns.Outer+Inner`).
+    pub fn type_full_name(&self, token: u32) -> Result<String> {
+        let (namespace, name) = self.type_namespace_and_name(token)?;
+        let enclosing = match TableId::from_token(token) {
+            Some((TableId::TypeDef, rid)) => self
+                .enclosing_type(rid)?
+                .map(|outer| TableId::TypeDef.token(outer)),
+            Some((TableId::TypeRef, rid)) => {
+                let scope = self.row::<TypeRefRow>(rid)?.resolution_scope;
+                (scope >> 24 == TableId::TypeRef as u32 && token_rid(scope) != 0).then_some(scope)
+            }
+            _ => None,
+        };
+        Ok(match enclosing {
+            Some(outer) if outer != token => format!("{}+{name}", self.type_full_name(outer)?),
+            _ if namespace.is_empty() => name.to_string(),
+            _ => format!("{namespace}.{name}"),
+        })
+    }
+
+    /// Returns the full name of the type that declares a method token
+    /// (`MethodDef` or `MemberRef`), such as a custom attribute constructor.
+    pub fn method_parent_type_name(&self, token: u32) -> Result<String> {
+        match TableId::from_token(token) {
+            Some((TableId::MethodDef, rid)) => {
+                let owner = self.method_declaring_type(rid)?.ok_or_else(|| {
+                    Error::BadMetadata(format!("method {token:#010x} has no declaring type"))
+                })?;
+                self.type_full_name(TableId::TypeDef.token(owner))
+            }
+            Some((TableId::MemberRef, rid)) => {
+                let parent = self.column(TableId::MemberRef, rid, 0)?;
+                match TableId::from_token(parent) {
+                    Some((TableId::TypeDef | TableId::TypeRef, _)) => self.type_full_name(parent),
+                    Some((TableId::TypeSpec, _)) => Err(Error::BadMetadata(format!(
+                        "member {token:#010x} belongs to a generic instantiation"
+                    ))),
+                    _ => Err(Error::BadMetadata(format!(
+                        "member {token:#010x} has no parent type"
+                    ))),
+                }
+            }
+            _ => Err(Error::BadMetadata(format!(
+                "token {token:#010x} is not a method"
+            ))),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Sequence points of Portable PDBs.
+
+use super::cursor::Cursor;
+use super::metadata::MetadataReader;
+use super::tables::{MethodDebugInformationRow, TableId};
+use crate::error::{Error, Result};
+
+/// A sequence point: maps the IL starting at `offset` to a span of source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SequencePoint {
+    /// The `Document` rid of the source file.
+    pub document: u32,
+    pub offset: u32,
+    pub start_line: u32,
+    pub start_column: u16,
+    pub end_line: u32,
+    pub end_column: u16,
+}
+
+impl SequencePoint {
+    /// The line number of hidden sequence points.
+    pub const HIDDEN_LINE: u32 = 0xFE_EFEE;
+
+    /// Creates a hidden sequence point, which marks IL that does not
+    /// correspond to any source.
+    pub fn hidden(document: u32, offset: u32) -> Self {
+        Self {
+            document,
+            offset,
+            start_line: Self::HIDDEN_LINE,
+            start_column: 0,
+            end_line: Self::HIDDEN_LINE,
+            end_column: 0,
+        }
+    }
+
+    /// Returns `true` if this is a hidden sequence point.
+    pub fn is_hidden(&self) -> bool {
+        self.start_line == Self::HIDDEN_LINE
+    }
+}
+
+/// The decoded `SequencePoints` blob of a `MethodDebugInformation` row.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MethodSequencePoints {
+    /// The `StandAloneSig` rid of the method's local signature, or 0.
+    pub local_signature: u32,
+    /// The sequence points in IL offset order.
+    pub points: Vec<SequencePoint>,
+}
+
+impl MetadataReader {
+    /// Decodes the sequence points of a method from a Portable PDB.
+    ///
+    /// `MethodDebugInformation` rows correspond one to one with the
+    /// `MethodDef` rows of the image, so `method_rid` is the rid of either.
+    /// Returns an empty list for methods without debug information.
+    pub fn sequence_points(&self, method_rid: u32) -> Result<MethodSequencePoints> {
+        if method_rid == 0 || method_rid > self.row_count(TableId::MethodDebugInformation) {
+            return Ok(MethodSequencePoints::default());
+        }
+        let row = self.row::<MethodDebugInformationRow>(method_rid)?;
+        if row.sequence_points == 0 {
+            return Ok(MethodSequencePoints::default());
+        }
+        let bad = |msg: &str| {
+            Error::BadMetadata(format!(
+                "sequence points of method {:#010x}: {msg}",
+                TableId::MethodDef.token(method_rid)
+            ))
+        };
+        let mut cur = Cursor::new(self.blob(row.sequence_points)?);
+        let local_signature = cur.compressed_u32()?;
+        let mut document = match row.document {
+            0 => cur.compressed_u32()?,
+            document => document,
+        };
+        let mut points = Vec::new();
+        let mut offset = 0u32;
+        let mut previous_start: Option<(u32, u16)> = None;
+        while !cur.is_empty() {
+            let delta_offset = cur.compressed_u32()?;
+            if delta_offset == 0 && !points.is_empty() {
+                document = cur.compressed_u32()?;
+                continue;
+            }
+            offset = offset
+                .checked_add(delta_offset)
+                .ok_or_else(|| bad("IL offset overflows"))?;
+            let delta_lines = cur.compressed_u32()?;
+            let delta_columns = if delta_lines == 0 {
+                cur.compressed_u32()? as i64
+            } else {
+                cur.compressed_i32()? as i64
+            };
+            if delta_lines == 0 && delta_columns == 0 {
+                points.push(SequencePoint::hidden(document, offset));
+                continue;
+            }
+            let (start_line, start_column) = match previous_start {
+                None => (cur.compressed_u32()? as i64, cur.compressed_u32()? as i64),
+                Some((line, column)) => (
+                    line as i64 + cur.compressed_i32()? as i64,
+                    column as i64 + cur.compressed_i32()? as i64,
+                ),
+            };
+            let end_line = start_line + delta_lines as i64;
+            let end_column = start_column + delta_columns;
+            let start_line = u32::try_from(start_line).map_err(|_| bad("bad start line"))?;
+            let start_column = u16::try_from(start_column).map_err(|_| bad("bad start column"))?;
+            let point = SequencePoint {
+                document,
+                offset,
+                start_line,
+                start_column,
+                end_line: u32::try_from(end_line).map_err(|_| bad("bad end line"))?,
+                end_column: u16::try_from(end_column).map_err(|_| bad("bad end column"))?,
+            };
+            previous_start = Some((start_line, start_column));
+            points.push(point);
+        }
+        Ok(MethodSequencePoints {
+            local_signature,
+            points,
+        })
+    }
+}
@@ -0,0 +1,637 @@
+//! PE/COFF image reader.
+//!
+//! Parses just enough of the PE format to locate the CLI header and metadata
+//! of a managed image, without loading it through the OS loader.
+
+use std::fmt;
+use std::path::Path;
+
+use super::cursor::Cursor;
+use super::metadata::MetadataReader;
+use crate::error::{Error, Result};
+
+/// Index of the Win32 resource table data directory.
+pub const IMAGE_DIRECTORY_ENTRY_RESOURCE: usize = 2;
+/// Index of the certificate table (security directory) data directory.
+pub const IMAGE_DIRECTORY_ENTRY_SECURITY: usize = 4;
+/// Index of the debug directory data directory.
+pub const IMAGE_DIRECTORY_ENTRY_DEBUG: usize = 6;
+/// Index of the CLI header (COM descriptor) data directory.
+pub const IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR: usize = 14;
+
+/// CLI header flag: the image contains only IL code.
+pub const COMIMAGE_FLAGS_ILONLY: u32 = 0x0000_0001;
+/// CLI header flag: the image can only be loaded into a 32-bit process.
+pub const COMIMAGE_FLAGS_32BITREQUIRED: u32 = 0x0000_0002;
+/// CLI header flag: the image is an IL library.
+pub const COMIMAGE_FLAGS_IL_LIBRARY: u32 = 0x0000_0004;
+/// CLI header flag: the image is strong-name signed.
+pub const COMIMAGE_FLAGS_STRONGNAMESIGNED: u32 = 0x0000_0008;
+/// CLI header flag: the entry point is a native RVA rather than a token.
+pub const COMIMAGE_FLAGS_NATIVE_ENTRYPOINT: u32 = 0x0000_0010;
+/// CLI header flag: the image is tracked for debugging.
+pub const COMIMAGE_FLAGS_TRACKDEBUGDATA: u32 = 0x0001_0000;
+/// CLI header flag: prefer a 32-bit process (only meaningful with `32BITREQUIRED`).
+pub const COMIMAGE_FLAGS_32BITPREFERRED: u32 = 0x0002_0000;
+
+/// Target machine of a PE image (`IMAGE_FILE_HEADER::Machine`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Machine(pub u16);
+
+impl Machine {
+    pub const UNKNOWN: Machine = Machine(0x0000);
+    pub const I386: Machine = Machine(0x014c);
+    pub const ARM: Machine = Machine(0x01c0);
+    pub const ARMNT: Machine = Machine(0x01c4);
+    pub const IA64: Machine = Machine(0x0200);
+    pub const AMD64: Machine = Machine(0x8664);
+    pub const ARM64: Machine = Machine(0xaa64);
+    pub const LOONGARCH64: Machine = Machine(0x6264);
+    pub const RISCV64: Machine = Machine(0x5064);
+
+    /// Machine value overrides used by ReadyToRun images compiled for non-Windows
+    /// targets. The real machine is XOR-ed with one of these.
+    const OS_OVERRIDES: [(u16, TargetOs); 5] = [
+        (0x7B79, TargetOs::Linux),
+        (0x4644, TargetOs::MacOs),
+        (0xADC4, TargetOs::FreeBsd),
+        (0x1993, TargetOs::NetBsd),
+        (0x1992, TargetOs::Sun),
+    ];
+
+    /// Splits a ReadyToRun machine value into the real machine and target OS.
+    ///
+    /// Windows images (and IL-only images) are returned unchanged with
+    /// [`TargetOs::Windows`].
+    pub fn native(self) -> (Machine, TargetOs) {
+        if self.name().is_some() {
+            return (self, TargetOs::Windows);
+        }
+        for (mask, os) in Self::OS_OVERRIDES {
+            let candidate = Machine(self.0 ^ mask);
+            if candidate != Machine::UNKNOWN && candidate.name().is_some() {
+                return (candidate, os);
+            }
+        }
+        (self, TargetOs::Windows)
+    }
+
+    /// Returns the conventional name of a known machine type.
+    pub fn name(self) -> Option<&'static str> {
+        Some(match self {
+            Machine::UNKNOWN => "Unknown",
+            Machine::I386 => "I386",
+            Machine::ARM => "ARM",
+            Machine::ARMNT => "ARMNT",
+            Machine::IA64 => "IA64",
+            Machine::AMD64 => "AMD64",
+            Machine::ARM64 => "ARM64",
+            Machine::LOONGARCH64 => "LOONGARCH64",
+            Machine::RISCV64 => "RISCV64",
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for Machine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "{:#06x}", self.0),
+        }
+    }
+}
+
+/// Operating system a ReadyToRun image was compiled for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TargetOs {
+    Windows,
+    Linux,
+    MacOs,
+    FreeBsd,
+    NetBsd,
+    Sun,
+}
+
+/// A PE data directory entry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DataDirectory {
+    pub virtual_address: u32,
+    pub size: u32,
+}
+
+impl DataDirectory {
+    /// Returns `true` if the directory is absent.
+    pub fn is_empty(&self) -> bool {
+        self.virtual_address == 0 || self.size == 0
+    }
+}
+
+/// A PE section header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionHeader {
+    pub name: String,
+    pub virtual_size: u32,
+    pub virtual_address: u32,
+    pub size_of_raw_data: u32,
+    pub pointer_to_raw_data: u32,
+    pub characteristics: u32,
+}
+
+/// The CLI header (`IMAGE_COR20_HEADER`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CorHeader {
+    pub cb: u32,
+    pub major_runtime_version: u16,
+    pub minor_runtime_version: u16,
+    pub metadata: DataDirectory,
+    pub flags: u32,
+    /// Entry point method token, or RVA with `COMIMAGE_FLAGS_NATIVE_ENTRYPOINT`.
+    pub entry_point: u32,
+    pub resources: DataDirectory,
+    pub strong_name_signature: DataDirectory,
+    pub code_manager_table: DataDirectory,
+    pub vtable_fixups: DataDirectory,
+    pub export_address_table_jumps: DataDirectory,
+    pub managed_native_header: DataDirectory,
+}
+
+/// PE kind flags as returned by `IMetaDataImport2::GetPEKind` (`CorPEKind`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CorPEKind(pub u32);
+
+impl CorPEKind {
+    /// Not a PE file.
+    pub const peNot: CorPEKind = CorPEKind(0x00);
+    /// The image contains only IL.
+    pub const peILonly: CorPEKind = CorPEKind(0x01);
+    /// The image requires a 32-bit process.
+    pub const pe32BitRequired: CorPEKind = CorPEKind(0x02);
+    /// The image is PE32+ (64-bit).
+    pub const pe32Plus: CorPEKind = CorPEKind(0x04);
+    /// The image is a native PE without a CLI header.
+    pub const pe32Unmanaged: CorPEKind = CorPEKind(0x08);
+    /// The image prefers a 32-bit process.
+    pub const pe32BitPreferred: CorPEKind = CorPEKind(0x10);
+
+    /// Returns `true` if all flags in `other` are set.
+    pub fn contains(self, other: CorPEKind) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_il_only(self) -> bool {
+        self.contains(Self::peILonly)
+    }
+
+    pub fn is_32bit_required(self) -> bool {
+        self.contains(Self::pe32BitRequired)
+    }
+
+    pub fn is_32bit_preferred(self) -> bool {
+        self.contains(Self::pe32BitPreferred)
+    }
+
+    pub fn is_pe32_plus(self) -> bool {
+        self.contains(Self::pe32Plus)
+    }
+
+    pub fn is_unmanaged(self) -> bool {
+        self.contains(Self::pe32Unmanaged)
+    }
+}
+
+impl std::ops::BitOr for CorPEKind {
+    type Output = CorPEKind;
+
+    fn bitor(self, rhs: CorPEKind) -> CorPEKind {
+        CorPEKind(self.0 | rhs.0)
+    }
+}
+
+/// A PE/COFF image loaded from disk or memory.
+///
+/// The image is kept in its file layout; RVAs are translated through the
+/// section table.
+pub struct PeImage {
+    data: Vec<u8>,
+    machine: Machine,
+    characteristics: u16,
+    time_date_stamp: u32,
+    is_pe32_plus: bool,
+    subsystem: u16,
+    dll_characteristics: u16,
+    file_alignment: u32,
+    section_alignment: u32,
+    image_base: u64,
+    pe_header_offset: usize,
+    optional_header_offset: usize,
+    data_directories_offset: usize,
+    section_headers_offset: usize,
+    size_of_headers: u32,
+    data_directories: Vec<DataDirectory>,
+    sections: Vec<SectionHeader>,
+    cor_header: Option<CorHeader>,
+}
+
+impl PeImage {
+    /// Reads and parses the image at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    /// Parses an image from its file bytes.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let mut dos = Cursor::new(&data);
+        if dos.u16().map_err(bad_image)? != 0x5A4D {
+            return Err(Error::BadImageFormat("missing MZ signature".into()));
+        }
+        let pe_header_offset = Cursor::at(&data, 0x3C).u32().map_err(bad_image)? as usize;
+
+        let mut cur = Cursor::at(&data, pe_header_offset);
+        if cur.u32().map_err(bad_image)? != 0x0000_4550 {
+            return Err(Error::BadImageFormat("missing PE signature".into()));
+        }
+        let machine = Machine(cur.u16().map_err(bad_image)?);
+        let number_of_sections = cur.u16().map_err(bad_image)?;
+        let time_date_stamp = cur.u32().map_err(bad_image)?;
+        cur.skip(8).map_err(bad_image)?; // symbol table pointer and count
+        let size_of_optional_header = cur.u16().map_err(bad_image)? as usize;
+        let characteristics = cur.u16().map_err(bad_image)?;
+
+        let optional_header_offset = cur.position();
+        let is_pe32_plus = match cur.u16().map_err(bad_image)? {
+            0x10b => false,
+            0x20b => true,
+            magic => {
+                return Err(Error::BadImageFormat(format!(
+                    "unknown optional header magic {magic:#x}"
+                )));
+            }
+        };
+
+        let image_base = if is_pe32_plus {
+            Cursor::at(&data, optional_header_offset + 24)
+                .u64()
+                .map_err(bad_image)?
+        } else {
+            Cursor::at(&data, optional_header_offset + 28)
+                .u32()
+                .map_err(bad_image)? as u64
+        };
+        let mut opt = Cursor::at(&data, optional_header_offset + 32);
+        let section_alignment = opt.u32().map_err(bad_image)?;
+        let file_alignment = opt.u32().map_err(bad_image)?;
+        let size_of_headers = Cursor::at(&data, optional_header_offset + 60)
+            .u32()
+            .map_err(bad_image)?;
+        let mut opt = Cursor::at(&data, optional_header_offset + 68);
+        let subsystem = opt.u16().map_err(bad_image)?;
+        let dll_characteristics = opt.u16().map_err(bad_image)?;
+
+        let (count_offset, data_directories_offset) =
+            if is_pe32_plus { (108, 112) } else { (92, 96) };
+        let data_directories_offset = optional_header_offset + data_directories_offset;
+        let count = Cursor::at(&data, optional_header_offset + count_offset)
+            .u32()
+            .map_err(bad_image)?
+            .min(16) as usize;
+        if size_of_optional_header < data_directories_offset - optional_header_offset + count * 8 {
+            return Err(Error::BadImageFormat(
+                "optional header is too small for its data directories".into(),
+            ));
+        }
+        let mut dirs = Cursor::at(&data, data_directories_offset);
+        let mut data_directories = Vec::with_capacity(count);
+        for _ in 0..count {
+            data_directories.push(DataDirectory {
+                virtual_address: dirs.u32().map_err(bad_image)?,
+                size: dirs.u32().map_err(bad_image)?,
+            });
+        }
+
+        let section_headers_offset = optional_header_offset + size_of_optional_header;
+        let mut sec = Cursor::at(&data, section_headers_offset);
+        let mut sections = Vec::with_capacity(number_of_sections as usize);
+        for _ in 0..number_of_sections {
+            let raw_name = sec.bytes(8).map_err(bad_image)?;
+            let name_len = raw_name.iter().position(|&b| b == 0).unwrap_or(8);
+            let name = String::from_utf8_lossy(&raw_name[..name_len]).into_owned();
+            let virtual_size = sec.u32().map_err(bad_image)?;
+            let virtual_address = sec.u32().map_err(bad_image)?;
+            let size_of_raw_data = sec.u32().map_err(bad_image)?;
+            let pointer_to_raw_data = sec.u32().map_err(bad_image)?;
+            sec.skip(12).map_err(bad_image)?; // relocations and line numbers
+            let characteristics = sec.u32().map_err(bad_image)?;
+            sections.push(SectionHeader {
+                name,
+                virtual_size,
+                virtual_address,
+                size_of_raw_data,
+                pointer_to_raw_data,
+                characteristics,
+            });
+        }
+
+        let mut image = Self {
+            data,
+            machine,
+            characteristics,
+            time_date_stamp,
+            is_pe32_plus,
+            subsystem,
+            dll_characteristics,
+            file_alignment,
+            section_alignment,
+            image_base,
+            pe_header_offset,
+            optional_header_offset,
+            data_directories_offset,
+            section_headers_offset,
+            size_of_headers,
+            data_directories,
+            sections,
+            cor_header: None,
+        };
+        image.cor_header = image.read_cor_header()?;
+        Ok(image)
+    }
+
+    fn read_cor_header(&self) -> Result<Option<CorHeader>> {
+        let dir = self.data_directory(IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR);
+        if dir.is_empty() {
+            return Ok(None);
+        }
+        let bytes = self
+            .rva_slice(dir.virtual_address, 72)
+            .ok_or_else(|| Error::BadImageFormat("CLI header is outside the image".into()))?;
+        let mut cur = Cursor::new(bytes);
+        let directory = |cur: &mut Cursor| -> Result<DataDirectory> {
+            Ok(DataDirectory {
+                virtual_address: cur.u32()?,
+                size: cur.u32()?,
+            })
+        };
+        Ok(Some(CorHeader {
+            cb: cur.u32()?,
+            major_runtime_version: cur.u16()?,
+            minor_runtime_version: cur.u16()?,
+            metadata: directory(&mut cur)?,
+            flags: cur.u32()?,
+            entry_point: cur.u32()?,
+            resources: directory(&mut cur)?,
+            strong_name_signature: directory(&mut cur)?,
+            code_manager_table: directory(&mut cur)?,
+            vtable_fixups: directory(&mut cur)?,
+            export_address_table_jumps: directory(&mut cur)?,
+            managed_native_header: directory(&mut cur)?,
+        }))
+    }
+
+    /// Returns the raw file bytes of the image.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Consumes the image and returns its file bytes.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Returns the machine type from the COFF header.
+    pub fn machine(&self) -> Machine {
+        self.machine
+    }
+
+    /// Returns the COFF header characteristics (`IMAGE_FILE_*`).
+    pub fn characteristics(&self) -> u16 {
+        self.characteristics
+    }
+
+    /// Returns `true` if the image is a DLL (`IMAGE_FILE_DLL`).
+    pub fn is_dll(&self) -> bool {
+        self.characteristics & 0x2000 != 0
+    }
+
+    /// Returns the COFF header timestamp.
+    pub fn time_date_stamp(&self) -> u32 {
+        self.time_date_stamp
+    }
+
+    /// Returns `true` if the image has a PE32+ optional header.
+    pub fn is_pe32_plus(&self) -> bool {
+        self.is_pe32_plus
+    }
+
+    /// Returns the subsystem from the optional header.
+    pub fn subsystem(&self) -> u16 {
+        self.subsystem
+    }
+
+    /// Returns the DLL characteristics from the optional header.
+    pub fn dll_characteristics(&self) -> u16 {
+        self.dll_characteristics
+    }
+
+    /// Returns the file alignment from the optional header.
+    pub fn file_alignment(&self) -> u32 {
+        self.file_alignment
+    }
+
+    /// Returns the section alignment from the optional header.
+    pub fn section_alignment(&self) -> u32 {
+        self.section_alignment
+    }
+
+    /// Returns the preferred image base.
+    pub fn image_base(&self) -> u64 {
+        self.image_base
+    }
+
+    /// Returns the combined size of the headers, rounded to the file alignment.
+    pub fn size_of_headers(&self) -> u32 {
+        self.size_of_headers
+    }
+
+    /// File offset of the `PE\0\0` signature.
+    pub fn pe_header_offset(&self) -> usize {
+        self.pe_header_offset
+    }
+
+    /// File offset of the optional header.
+    pub fn optional_header_offset(&self) -> usize {
+        self.optional_header_offset
+    }
+
+    /// File offset of the optional header's `CheckSum` field.
+    pub fn checksum_offset(&self) -> usize {
+        self.optional_header_offset + 64
+    }
+
+    /// File offset of data directory `index` within the optional header.
+    pub fn data_directory_offset(&self, index: usize) -> usize {
+        self.data_directories_offset + index * 8
+    }
+
+    /// File offset of the section table.
+    pub fn section_headers_offset(&self) -> usize {
+        self.section_headers_offset
+    }
+
+    /// Returns data directory `index`, or an empty directory if absent.
+    pub fn data_directory(&self, index: usize) -> DataDirectory {
+        self.data_directories
+            .get(index)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns all data directories present in the optional header.
+    pub fn data_directories(&self) -> &[DataDirectory] {
+        &self.data_directories
+    }
+
+    /// Returns the section headers.
+    pub fn sections(&self) -> &[SectionHeader] {
+        &self.sections
+    }
+
+    /// Returns the CLI header, if the image is managed.
+    pub fn cor_header(&self) -> Option<&CorHeader> {
+        self.cor_header.as_ref()
+    }
+
+    /// Returns `true` if the image has a CLI header.
+    pub fn is_managed(&self) -> bool {
+        self.cor_header.is_some()
+    }
+
+    /// Translates an RVA to a file offset.
+    pub fn rva_to_offset(&self, rva: u32) -> Option<usize> {
+        if rva < self.size_of_headers {
+            return Some(rva as usize);
+        }
+        self.sections.iter().find_map(|s| {
+            let size = s.virtual_size.max(s.size_of_raw_data);
+            if rva >= s.virtual_address && rva - s.virtual_address < size {
+                let delta = rva - s.virtual_address;
+                let offset = s.pointer_to_raw_data.checked_add(delta)?;
+                (delta < s.size_of_raw_data).then_some(offset as usize)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Returns `len` bytes of file data starting at `rva`.
+    pub fn rva_slice(&self, rva: u32, len: usize) -> Option<&[u8]> {
+        let start = self.rva_to_offset(rva)?;
+        self.data.get(start..start.checked_add(len)?)
+    }
+
+    /// Returns the bytes described by a data directory.
+    pub fn directory_data(&self, dir: DataDirectory) -> Option<&[u8]> {
+        if dir.is_empty() {
+            return None;
+        }
+        self.rva_slice(dir.virtual_address, dir.size as usize)
+    }
+
+    /// Returns the raw metadata blob referenced by the CLI header.
+    pub fn metadata_bytes(&self) -> Result<&[u8]> {
+        let cor = self.cor_header.as_ref().ok_or(Error::NotManaged)?;
+        self.directory_data(cor.metadata)
+            .ok_or_else(|| Error::BadImageFormat("metadata directory is outside the image".into()))
+    }
+
+    /// Parses the image's CLI metadata.
+    pub fn metadata(&self) -> Result<MetadataReader> {
+        MetadataReader::from_bytes(self.metadata_bytes()?.to_vec())
+    }
+
+    /// Returns the data of the managed resource at `offset` in the CLI
+    /// header's resources directory: the `Offset` of an embedded
+    /// `ManifestResource` row.
+    pub fn managed_resource(&self, offset: u32) -> Result<&[u8]> {
+        let cor = self.cor_header.as_ref().ok_or(Error::NotManaged)?;
+        let outside = || {
+            Error::BadImageFormat(format!(
+                "managed resource at offset {offset:#x} is outside the resources directory"
+            ))
+        };
+        let resources = self.directory_data(cor.resources).ok_or_else(outside)?;
+        let start = offset as usize;
+        let len = resources
+            .get(start..start + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().expect("4 bytes")) as usize)
+            .ok_or_else(outside)?;
+        resources
+            .get(start + 4..start + 4 + len)
+            .ok_or_else(outside)
+    }
+
+    /// Classifies the image the way `IMetaDataImport2::GetPEKind` does.
+    ///
+    /// Returns the PE kind flags and the machine from the COFF header.
+    pub fn pe_kind(&self) -> (CorPEKind, Machine) {
+        let mut kind = CorPEKind::peNot;
+        if self.is_pe32_plus {
+            kind = kind | CorPEKind::pe32Plus;
+        }
+        match &self.cor_header {
+            Some(cor) => {
+                if cor.flags & COMIMAGE_FLAGS_ILONLY != 0 {
+                    kind = kind | CorPEKind::peILonly;
+                }
+                let bitness =
+                    cor.flags & (COMIMAGE_FLAGS_32BITREQUIRED | COMIMAGE_FLAGS_32BITPREFERRED);
+                if bitness == COMIMAGE_FLAGS_32BITREQUIRED {
+                    kind = kind | CorPEKind::pe32BitRequired;
+                } else if bitness == COMIMAGE_FLAGS_32BITREQUIRED | COMIMAGE_FLAGS_32BITPREFERRED {
+                    kind = kind | CorPEKind::pe32BitPreferred;
+                }
+                // Mixed-mode C++ images carry no flags at all; the runtime
+                // treats them as requiring a 32-bit process.
+                if kind == CorPEKind::peNot {
+                    kind = CorPEKind::pe32BitRequired;
+                }
+            }
+            None => kind = kind | CorPEKind::pe32Unmanaged,
+        }
+        (kind, self.machine)
+    }
+
+    /// Returns the `CheckSum` stored in the optional header.
+    pub fn checksum(&self) -> u32 {
+        Cursor::at(&self.data, self.checksum_offset())
+            .u32()
+            .unwrap_or(0)
+    }
+
+    /// Computes the image checksum the way `CheckSumMappedFile` does.
+    pub fn compute_checksum(&self) -> u32 {
+        let skip = self.checksum_offset();
+        let mut sum = 0u64;
+        for (i, chunk) in self.data.chunks(2).enumerate() {
+            let offset = i * 2;
+            if offset >= skip && offset < skip + 4 {
+                continue;
+            }
+            let word = match *chunk {
+                [lo, hi] => u16::from_le_bytes([lo, hi]),
+                [lo] => lo as u16,
+                _ => unreachable!(),
+            };
+            sum += word as u64;
+            sum = (sum & 0xFFFF) + (sum >> 16);
+        }
+        sum = (sum & 0xFFFF) + (sum >> 16);
+        (sum as u32).wrapping_add(self.data.len() as u32)
+    }
+}
+
+fn bad_image(e: Error) -> Error {
+    match e {
+        Error::BadMetadata(msg) => Error::BadImageFormat(msg),
+        other => other,
+    }
+}
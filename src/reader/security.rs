@@ -0,0 +1,262 @@
+//! Declarative security decoding: permission sets from the `DeclSecurity`
+//! table (II.22.11) in the binary (II.23.1.3) and legacy XML formats.
+
+use roxmltree::Document;
+
+use super::cursor::Cursor;
+use super::custom_attribute::{
+    CustomAttributeArgument, CustomAttributeNamedArgument, read_ser_string,
+};
+use super::metadata::MetadataReader;
+use super::tables::{DeclSecurityRow, TableId};
+use crate::error::{Error, Result};
+
+/// Marks a permission set in the binary format.
+const BINARY_FORMAT_PREFIX: u8 = b'.';
+
+/// `System.Security.Permissions.SecurityAction`, plus the values only the
+/// runtime and the metadata APIs use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SecurityAction {
+    Request,
+    Demand,
+    Assert,
+    Deny,
+    PermitOnly,
+    LinkDemand,
+    InheritanceDemand,
+    RequestMinimum,
+    RequestOptional,
+    RequestRefuse,
+    PrejitGrant,
+    PrejitDenied,
+    NonCasDemand,
+    NonCasLinkDemand,
+    NonCasInheritance,
+    /// A value outside the defined range.
+    Other(u16),
+}
+
+impl SecurityAction {
+    /// Returns the action with the given `Action` column value.
+    pub fn from_u16(value: u16) -> SecurityAction {
+        match value {
+            1 => SecurityAction::Request,
+            2 => SecurityAction::Demand,
+            3 => SecurityAction::Assert,
+            4 => SecurityAction::Deny,
+            5 => SecurityAction::PermitOnly,
+            6 => SecurityAction::LinkDemand,
+            7 => SecurityAction::InheritanceDemand,
+            8 => SecurityAction::RequestMinimum,
+            9 => SecurityAction::RequestOptional,
+            10 => SecurityAction::RequestRefuse,
+            11 => SecurityAction::PrejitGrant,
+            12 => SecurityAction::PrejitDenied,
+            13 => SecurityAction::NonCasDemand,
+            14 => SecurityAction::NonCasLinkDemand,
+            15 => SecurityAction::NonCasInheritance,
+            other => SecurityAction::Other(other),
+        }
+    }
+
+    /// Returns the `Action` column value.
+    pub fn to_u16(self) -> u16 {
+        match self {
+            SecurityAction::Request => 1,
+            SecurityAction::Demand => 2,
+            SecurityAction::Assert => 3,
+            SecurityAction::Deny => 4,
+            SecurityAction::PermitOnly => 5,
+            SecurityAction::LinkDemand => 6,
+            SecurityAction::InheritanceDemand => 7,
+            SecurityAction::RequestMinimum => 8,
+            SecurityAction::RequestOptional => 9,
+            SecurityAction::RequestRefuse => 10,
+            SecurityAction::PrejitGrant => 11,
+            SecurityAction::PrejitDenied => 12,
+            SecurityAction::NonCasDemand => 13,
+            SecurityAction::NonCasLinkDemand => 14,
+            SecurityAction::NonCasInheritance => 15,
+            SecurityAction::Other(value) => value,
+        }
+    }
+}
+
+/// The encoding of a permission set blob.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PermissionSetFormat {
+    /// The compact format of .NET Framework 2.0 and later: serialized
+    /// security attributes.
+    Binary,
+    /// The `<PermissionSet>` XML written by .NET Framework 1.x compilers.
+    Xml,
+}
+
+/// One permission of a permission set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecurityAttribute {
+    /// The type named by the blob: the assembly-qualified name of a
+    /// security attribute in the binary format, or the `class` of an
+    /// `<IPermission>` element in XML.
+    pub type_name: String,
+    /// The named arguments. XML attributes other than `class` and `version`
+    /// are reported as string properties.
+    pub properties: Vec<CustomAttributeNamedArgument>,
+}
+
+impl SecurityAttribute {
+    /// Returns the property called `name`.
+    pub fn property(&self, name: &str) -> Option<&CustomAttributeArgument> {
+        self.properties
+            .iter()
+            .find(|arg| arg.name == name)
+            .map(|arg| &arg.value)
+    }
+}
+
+/// A decoded declarative security permission set.
+///
+/// ```
+/// use mscoree::{MetadataBuilder, MetadataReader, PermissionSetFormat, SecurityAction};
+///
+/// let reader = MetadataReader::from_bytes(MetadataBuilder::new().to_bytes()?)?;
+/// let xml = r#"<PermissionSet class="System.Security.PermissionSet" version="1">
+///   <IPermission class="System.Security.Permissions.SecurityPermission, mscorlib"
+///                version="1" Flags="UnmanagedCode"/>
+/// </PermissionSet>"#;
+/// let set = reader.decode_permission_set(2, xml.as_bytes())?;
+/// assert_eq!(set.action, SecurityAction::Demand);
+/// assert_eq!(set.format, PermissionSetFormat::Xml);
+/// let flags = set.attributes[0].property("Flags").and_then(|v| v.as_str());
+/// assert_eq!(flags, Some("UnmanagedCode"));
+/// # Ok::<(), mscoree::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PermissionSet {
+    pub action: SecurityAction,
+    pub format: PermissionSetFormat,
+    /// Whether the XML set grants every permission (`Unrestricted="true"`).
+    /// Binary sets express this with an `Unrestricted` property instead.
+    pub unrestricted: bool,
+    pub attributes: Vec<SecurityAttribute>,
+}
+
+impl MetadataReader {
+    /// Returns the permission sets attached to a `TypeDef`, `MethodDef` or
+    /// `Assembly` token.
+    pub fn permission_sets(&self, token: u32) -> Result<Vec<PermissionSet>> {
+        self.find_rows(TableId::DeclSecurity, 1, token)?
+            .into_iter()
+            .map(|rid| self.decl_security(rid))
+            .collect()
+    }
+
+    /// Decodes row `rid` of the `DeclSecurity` table.
+    pub fn decl_security(&self, rid: u32) -> Result<PermissionSet> {
+        let row = self.row::<DeclSecurityRow>(rid)?;
+        self.decode_permission_set(row.action, self.blob(row.permission_set)?)
+    }
+
+    /// Decodes a permission set blob in either format, such as one returned
+    /// by `GetPermissionSetProps`.
+    ///
+    /// Enum properties of the binary format are resolved like custom
+    /// attribute arguments: against this module, or as `int32`.
+    pub fn decode_permission_set(&self, action: u16, blob: &[u8]) -> Result<PermissionSet> {
+        let action = SecurityAction::from_u16(action);
+        if blob.first() == Some(&BINARY_FORMAT_PREFIX) {
+            return Ok(PermissionSet {
+                action,
+                format: PermissionSetFormat::Binary,
+                unrestricted: false,
+                attributes: self.binary_permission_set(blob)?,
+            });
+        }
+        let (unrestricted, attributes) = xml_permission_set(&decode_text(blob)?)?;
+        Ok(PermissionSet {
+            action,
+            format: PermissionSetFormat::Xml,
+            unrestricted,
+            attributes,
+        })
+    }
+
+    fn binary_permission_set(&self, blob: &[u8]) -> Result<Vec<SecurityAttribute>> {
+        let mut cur = Cursor::new(&blob[1..]);
+        let count = cur.compressed_u32()?;
+        let mut attributes = Vec::new();
+        for _ in 0..count {
+            let type_name = read_ser_string(&mut cur)?.unwrap_or_default();
+            let len = cur.compressed_u32()? as usize;
+            let mut props = Cursor::new(cur.bytes(len)?);
+            let prop_count = props.compressed_u32()?;
+            let properties = (0..prop_count)
+                .map(|_| self.read_named_arg(&mut props))
+                .collect::<Result<_>>()?;
+            attributes.push(SecurityAttribute {
+                type_name,
+                properties,
+            });
+        }
+        Ok(attributes)
+    }
+}
+
+/// Decodes the text of an XML permission set, which 1.x compilers wrote as
+/// UTF-16LE, usually without a byte order mark.
+fn decode_text(blob: &[u8]) -> Result<String> {
+    let utf16 = blob.starts_with(&[0xFF, 0xFE]) || (blob.len() >= 2 && blob[1] == 0);
+    if utf16 {
+        if !blob.len().is_multiple_of(2) {
+            return Err(Error::BadMetadata(
+                "UTF-16 permission set has an odd length".into(),
+            ));
+        }
+        let units: Vec<u16> = blob
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16(&units)
+            .map_err(|_| Error::BadMetadata("permission set is not valid UTF-16".into()))
+    } else {
+        String::from_utf8(blob.to_vec())
+            .map_err(|_| Error::BadMetadata("permission set is not valid UTF-8".into()))
+    }
+}
+
+fn xml_permission_set(text: &str) -> Result<(bool, Vec<SecurityAttribute>)> {
+    let text = text.trim_start_matches('\u{feff}').trim_end_matches('\0');
+    let document = Document::parse(text)
+        .map_err(|e| Error::BadMetadata(format!("invalid permission set XML: {e}")))?;
+    let root = document.root_element();
+    if root.tag_name().name() != "PermissionSet" {
+        return Err(Error::BadMetadata(format!(
+            "permission set XML has root element <{}>",
+            root.tag_name().name()
+        )));
+    }
+    let unrestricted = root
+        .attribute("Unrestricted")
+        .is_some_and(|value| value.eq_ignore_ascii_case("true"));
+    let attributes = root
+        .children()
+        .filter(|node| node.is_element())
+        .map(|permission| SecurityAttribute {
+            type_name: permission
+                .attribute("class")
+                .unwrap_or_default()
+                .to_string(),
+            properties: permission
+                .attributes()
+                .filter(|attr| !matches!(attr.name(), "class" | "version"))
+                .map(|attr| CustomAttributeNamedArgument {
+                    is_field: false,
+                    name: attr.name().to_string(),
+                    value: CustomAttributeArgument::String(Some(attr.value().to_string())),
+                })
+                .collect(),
+        })
+        .collect();
+    Ok((unrestricted, attributes))
+}
@@ -0,0 +1,449 @@
+//! Signature blob decoding (ECMA-335 II.23.2).
+
+use super::cursor::Cursor;
+use super::tables::TableId;
+use crate::error::{Error, Result};
+
+pub(crate) const ELEMENT_TYPE_END: u8 = 0x00;
+pub(crate) const ELEMENT_TYPE_VOID: u8 = 0x01;
+pub(crate) const ELEMENT_TYPE_BOOLEAN: u8 = 0x02;
+pub(crate) const ELEMENT_TYPE_CHAR: u8 = 0x03;
+pub(crate) const ELEMENT_TYPE_I1: u8 = 0x04;
+pub(crate) const ELEMENT_TYPE_U1: u8 = 0x05;
+pub(crate) const ELEMENT_TYPE_I2: u8 = 0x06;
+pub(crate) const ELEMENT_TYPE_U2: u8 = 0x07;
+pub(crate) const ELEMENT_TYPE_I4: u8 = 0x08;
+pub(crate) const ELEMENT_TYPE_U4: u8 = 0x09;
+pub(crate) const ELEMENT_TYPE_I8: u8 = 0x0a;
+pub(crate) const ELEMENT_TYPE_U8: u8 = 0x0b;
+pub(crate) const ELEMENT_TYPE_R4: u8 = 0x0c;
+pub(crate) const ELEMENT_TYPE_R8: u8 = 0x0d;
+pub(crate) const ELEMENT_TYPE_STRING: u8 = 0x0e;
+pub(crate) const ELEMENT_TYPE_PTR: u8 = 0x0f;
+pub(crate) const ELEMENT_TYPE_BYREF: u8 = 0x10;
+pub(crate) const ELEMENT_TYPE_VALUETYPE: u8 = 0x11;
+pub(crate) const ELEMENT_TYPE_CLASS: u8 = 0x12;
+pub(crate) const ELEMENT_TYPE_VAR: u8 = 0x13;
+pub(crate) const ELEMENT_TYPE_ARRAY: u8 = 0x14;
+pub(crate) const ELEMENT_TYPE_GENERICINST: u8 = 0x15;
+pub(crate) const ELEMENT_TYPE_TYPEDBYREF: u8 = 0x16;
+pub(crate) const ELEMENT_TYPE_I: u8 = 0x18;
+pub(crate) const ELEMENT_TYPE_U: u8 = 0x19;
+pub(crate) const ELEMENT_TYPE_FNPTR: u8 = 0x1b;
+pub(crate) const ELEMENT_TYPE_OBJECT: u8 = 0x1c;
+pub(crate) const ELEMENT_TYPE_SZARRAY: u8 = 0x1d;
+pub(crate) const ELEMENT_TYPE_MVAR: u8 = 0x1e;
+pub(crate) const ELEMENT_TYPE_CMOD_REQD: u8 = 0x1f;
+pub(crate) const ELEMENT_TYPE_CMOD_OPT: u8 = 0x20;
+pub(crate) const ELEMENT_TYPE_INTERNAL: u8 = 0x21;
+pub(crate) const ELEMENT_TYPE_SENTINEL: u8 = 0x41;
+pub(crate) const ELEMENT_TYPE_PINNED: u8 = 0x45;
+
+/// Calling convention: default managed calling convention.
+pub const IMAGE_CEE_CS_CALLCONV_DEFAULT: u8 = 0x00;
+/// Calling convention: unmanaged cdecl.
+pub const IMAGE_CEE_CS_CALLCONV_C: u8 = 0x01;
+/// Calling convention: unmanaged stdcall.
+pub const IMAGE_CEE_CS_CALLCONV_STDCALL: u8 = 0x02;
+/// Calling convention: unmanaged thiscall.
+pub const IMAGE_CEE_CS_CALLCONV_THISCALL: u8 = 0x03;
+/// Calling convention: unmanaged fastcall.
+pub const IMAGE_CEE_CS_CALLCONV_FASTCALL: u8 = 0x04;
+/// Calling convention: managed varargs.
+pub const IMAGE_CEE_CS_CALLCONV_VARARG: u8 = 0x05;
+/// Signature kind: field signature.
+pub const IMAGE_CEE_CS_CALLCONV_FIELD: u8 = 0x06;
+/// Signature kind: local variable signature.
+pub const IMAGE_CEE_CS_CALLCONV_LOCAL_SIG: u8 = 0x07;
+/// Signature kind: property signature.
+pub const IMAGE_CEE_CS_CALLCONV_PROPERTY: u8 = 0x08;
+/// Calling convention: unmanaged, with the convention in modifiers.
+pub const IMAGE_CEE_CS_CALLCONV_UNMANAGED: u8 = 0x09;
+/// Signature kind: generic method instantiation.
+pub const IMAGE_CEE_CS_CALLCONV_GENERICINST: u8 = 0x0a;
+/// Mask for the calling convention kind.
+pub const IMAGE_CEE_CS_CALLCONV_MASK: u8 = 0x0f;
+/// Flag: the method has generic parameters.
+pub const IMAGE_CEE_CS_CALLCONV_GENERIC: u8 = 0x10;
+/// Flag: the method has a `this` pointer.
+pub const IMAGE_CEE_CS_CALLCONV_HASTHIS: u8 = 0x20;
+/// Flag: the `this` pointer is explicitly listed in the parameters.
+pub const IMAGE_CEE_CS_CALLCONV_EXPLICITTHIS: u8 = 0x40;
+
+/// Shape of a general (multi-dimensional) array.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ArrayShape {
+    pub rank: u32,
+    pub sizes: Vec<u32>,
+    pub lower_bounds: Vec<i32>,
+}
+
+/// A type as encoded in a signature blob.
+///
+/// Type references are kept as `TypeDef`, `TypeRef` or `TypeSpec` tokens of
+/// the scope the signature was read from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeSig {
+    Void,
+    Boolean,
+    Char,
+    I1,
+    U1,
+    I2,
+    U2,
+    I4,
+    U4,
+    I8,
+    U8,
+    R4,
+    R8,
+    I,
+    U,
+    String,
+    Object,
+    TypedByRef,
+    /// A reference type.
+    Class(u32),
+    /// A value type.
+    ValueType(u32),
+    /// An unmanaged pointer.
+    Ptr(Box<TypeSig>),
+    /// A managed reference.
+    ByRef(Box<TypeSig>),
+    /// A single-dimensional, zero-based array.
+    SzArray(Box<TypeSig>),
+    /// A general array.
+    Array(Box<TypeSig>, ArrayShape),
+    /// An instantiation of a generic type.
+    GenericInst {
+        value_type: bool,
+        definition: u32,
+        args: Vec<TypeSig>,
+    },
+    /// A generic parameter of the enclosing type (`!n`).
+    Var(u32),
+    /// A generic parameter of the enclosing method (`!!n`).
+    MVar(u32),
+    /// A function pointer.
+    FnPtr(Box<MethodSig>),
+    /// A type with a required (`modreq`) or optional (`modopt`) custom modifier.
+    Modified {
+        required: bool,
+        modifier: u32,
+        ty: Box<TypeSig>,
+    },
+    /// A pinned local.
+    Pinned(Box<TypeSig>),
+    /// A runtime-internal type handle (`ELEMENT_TYPE_INTERNAL`).
+    Internal(u64),
+}
+
+impl TypeSig {
+    /// Parses a standalone type signature (e.g. a `TypeSpec` blob).
+    pub fn parse(blob: &[u8]) -> Result<TypeSig> {
+        SignatureReader::new(blob).type_sig()
+    }
+
+    /// Returns the type with all custom modifiers and pinning removed.
+    pub fn strip_modifiers(&self) -> &TypeSig {
+        match self {
+            TypeSig::Modified { ty, .. } | TypeSig::Pinned(ty) => ty.strip_modifiers(),
+            other => other,
+        }
+    }
+
+    /// Returns the `ELEMENT_TYPE_*` code of a primitive type.
+    pub fn primitive_element_type(&self) -> Option<u8> {
+        Some(match self {
+            TypeSig::Void => ELEMENT_TYPE_VOID,
+            TypeSig::Boolean => ELEMENT_TYPE_BOOLEAN,
+            TypeSig::Char => ELEMENT_TYPE_CHAR,
+            TypeSig::I1 => ELEMENT_TYPE_I1,
+            TypeSig::U1 => ELEMENT_TYPE_U1,
+            TypeSig::I2 => ELEMENT_TYPE_I2,
+            TypeSig::U2 => ELEMENT_TYPE_U2,
+            TypeSig::I4 => ELEMENT_TYPE_I4,
+            TypeSig::U4 => ELEMENT_TYPE_U4,
+            TypeSig::I8 => ELEMENT_TYPE_I8,
+            TypeSig::U8 => ELEMENT_TYPE_U8,
+            TypeSig::R4 => ELEMENT_TYPE_R4,
+            TypeSig::R8 => ELEMENT_TYPE_R8,
+            TypeSig::I => ELEMENT_TYPE_I,
+            TypeSig::U => ELEMENT_TYPE_U,
+            TypeSig::String => ELEMENT_TYPE_STRING,
+            TypeSig::Object => ELEMENT_TYPE_OBJECT,
+            TypeSig::TypedByRef => ELEMENT_TYPE_TYPEDBYREF,
+            _ => return None,
+        })
+    }
+
+    /// Returns the primitive type for an `ELEMENT_TYPE_*` code.
+    pub fn from_primitive_element_type(element_type: u8) -> Option<TypeSig> {
+        Some(match element_type {
+            ELEMENT_TYPE_VOID => TypeSig::Void,
+            ELEMENT_TYPE_BOOLEAN => TypeSig::Boolean,
+            ELEMENT_TYPE_CHAR => TypeSig::Char,
+            ELEMENT_TYPE_I1 => TypeSig::I1,
+            ELEMENT_TYPE_U1 => TypeSig::U1,
+            ELEMENT_TYPE_I2 => TypeSig::I2,
+            ELEMENT_TYPE_U2 => TypeSig::U2,
+            ELEMENT_TYPE_I4 => TypeSig::I4,
+            ELEMENT_TYPE_U4 => TypeSig::U4,
+            ELEMENT_TYPE_I8 => TypeSig::I8,
+            ELEMENT_TYPE_U8 => TypeSig::U8,
+            ELEMENT_TYPE_R4 => TypeSig::R4,
+            ELEMENT_TYPE_R8 => TypeSig::R8,
+            ELEMENT_TYPE_I => TypeSig::I,
+            ELEMENT_TYPE_U => TypeSig::U,
+            ELEMENT_TYPE_STRING => TypeSig::String,
+            ELEMENT_TYPE_OBJECT => TypeSig::Object,
+            ELEMENT_TYPE_TYPEDBYREF => TypeSig::TypedByRef,
+            _ => return None,
+        })
+    }
+}
+
+/// A method signature (`MethodDefSig`, `MethodRefSig` or `StandAloneMethodSig`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodSig {
+    /// The calling convention byte, including `HASTHIS`/`GENERIC` flags.
+    pub calling_convention: u8,
+    pub generic_param_count: u32,
+    pub return_type: TypeSig,
+    pub params: Vec<TypeSig>,
+    /// Index in `params` of the first vararg parameter, if a sentinel was present.
+    pub sentinel: Option<usize>,
+}
+
+impl MethodSig {
+    /// Parses a method signature blob.
+    pub fn parse(blob: &[u8]) -> Result<MethodSig> {
+        let mut reader = SignatureReader::new(blob);
+        let calling_convention = reader.cursor.u8()?;
+        reader.method_sig_body(calling_convention)
+    }
+
+    /// Returns `true` if the method has a `this` pointer.
+    pub fn has_this(&self) -> bool {
+        self.calling_convention & IMAGE_CEE_CS_CALLCONV_HASTHIS != 0
+    }
+
+    /// Returns `true` if the method is generic.
+    pub fn is_generic(&self) -> bool {
+        self.calling_convention & IMAGE_CEE_CS_CALLCONV_GENERIC != 0
+    }
+
+    /// Returns the calling convention kind (`IMAGE_CEE_CS_CALLCONV_MASK` bits).
+    pub fn kind(&self) -> u8 {
+        self.calling_convention & IMAGE_CEE_CS_CALLCONV_MASK
+    }
+}
+
+/// A property signature.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PropertySig {
+    pub has_this: bool,
+    pub ty: TypeSig,
+    pub params: Vec<TypeSig>,
+}
+
+impl PropertySig {
+    /// Parses a property signature blob.
+    pub fn parse(blob: &[u8]) -> Result<PropertySig> {
+        let mut reader = SignatureReader::new(blob);
+        let header = reader.cursor.u8()?;
+        if header & IMAGE_CEE_CS_CALLCONV_MASK != IMAGE_CEE_CS_CALLCONV_PROPERTY {
+            return Err(Error::BadSignature(format!(
+                "expected property signature, found {header:#04x}"
+            )));
+        }
+        let count = reader.cursor.compressed_u32()?;
+        let ty = reader.type_sig()?;
+        let params = (0..count)
+            .map(|_| reader.type_sig())
+            .collect::<Result<_>>()?;
+        Ok(PropertySig {
+            has_this: header & IMAGE_CEE_CS_CALLCONV_HASTHIS != 0,
+            ty,
+            params,
+        })
+    }
+}
+
+/// Parses a field signature blob and returns the field type.
+pub fn parse_field_sig(blob: &[u8]) -> Result<TypeSig> {
+    let mut reader = SignatureReader::new(blob);
+    let header = reader.cursor.u8()?;
+    if header & IMAGE_CEE_CS_CALLCONV_MASK != IMAGE_CEE_CS_CALLCONV_FIELD {
+        return Err(Error::BadSignature(format!(
+            "expected field signature, found {header:#04x}"
+        )));
+    }
+    reader.type_sig()
+}
+
+/// Parses a local variable signature blob and returns the local types.
+pub fn parse_local_var_sig(blob: &[u8]) -> Result<Vec<TypeSig>> {
+    let mut reader = SignatureReader::new(blob);
+    let header = reader.cursor.u8()?;
+    if header != IMAGE_CEE_CS_CALLCONV_LOCAL_SIG {
+        return Err(Error::BadSignature(format!(
+            "expected local variable signature, found {header:#04x}"
+        )));
+    }
+    let count = reader.cursor.compressed_u32()?;
+    (0..count).map(|_| reader.type_sig()).collect()
+}
+
+/// Parses a `MethodSpec` instantiation blob and returns the type arguments.
+pub fn parse_method_spec(blob: &[u8]) -> Result<Vec<TypeSig>> {
+    let mut reader = SignatureReader::new(blob);
+    let header = reader.cursor.u8()?;
+    if header != IMAGE_CEE_CS_CALLCONV_GENERICINST {
+        return Err(Error::BadSignature(format!(
+            "expected method instantiation, found {header:#04x}"
+        )));
+    }
+    let count = reader.cursor.compressed_u32()?;
+    (0..count).map(|_| reader.type_sig()).collect()
+}
+
+/// Decodes a `TypeDefOrRefOrSpecEncoded` value into a token.
+pub(crate) fn decode_type_def_or_ref(encoded: u32) -> Result<u32> {
+    let table = match encoded & 0x3 {
+        0 => TableId::TypeDef,
+        1 => TableId::TypeRef,
+        2 => TableId::TypeSpec,
+        _ => {
+            return Err(Error::BadSignature(format!(
+                "invalid TypeDefOrRefOrSpecEncoded value {encoded:#x}"
+            )));
+        }
+    };
+    Ok(table.token(encoded >> 2))
+}
+
+struct SignatureReader<'a> {
+    cursor: Cursor<'a>,
+}
+
+impl<'a> SignatureReader<'a> {
+    fn new(blob: &'a [u8]) -> Self {
+        Self {
+            cursor: Cursor::new(blob),
+        }
+    }
+
+    fn type_def_or_ref(&mut self) -> Result<u32> {
+        decode_type_def_or_ref(self.cursor.compressed_u32()?)
+    }
+
+    fn method_sig_body(&mut self, calling_convention: u8) -> Result<MethodSig> {
+        let generic_param_count = if calling_convention & IMAGE_CEE_CS_CALLCONV_GENERIC != 0 {
+            self.cursor.compressed_u32()?
+        } else {
+            0
+        };
+        let count = self.cursor.compressed_u32()?;
+        let return_type = self.type_sig()?;
+        let mut params = Vec::with_capacity(count as usize);
+        let mut sentinel = None;
+        for _ in 0..count {
+            if self.cursor.peek_u8()? == ELEMENT_TYPE_SENTINEL {
+                self.cursor.u8()?;
+                sentinel = Some(params.len());
+            }
+            params.push(self.type_sig()?);
+        }
+        Ok(MethodSig {
+            calling_convention,
+            generic_param_count,
+            return_type,
+            params,
+            sentinel,
+        })
+    }
+
+    fn type_sig(&mut self) -> Result<TypeSig> {
+        let element_type = self.cursor.u8()?;
+        if let Some(primitive) = TypeSig::from_primitive_element_type(element_type) {
+            return Ok(primitive);
+        }
+        Ok(match element_type {
+            ELEMENT_TYPE_CLASS => TypeSig::Class(self.type_def_or_ref()?),
+            ELEMENT_TYPE_VALUETYPE => TypeSig::ValueType(self.type_def_or_ref()?),
+            ELEMENT_TYPE_PTR => TypeSig::Ptr(Box::new(self.type_sig()?)),
+            ELEMENT_TYPE_BYREF => TypeSig::ByRef(Box::new(self.type_sig()?)),
+            ELEMENT_TYPE_SZARRAY => TypeSig::SzArray(Box::new(self.type_sig()?)),
+            ELEMENT_TYPE_PINNED => TypeSig::Pinned(Box::new(self.type_sig()?)),
+            ELEMENT_TYPE_ARRAY => {
+                let element = self.type_sig()?;
+                let rank = self.cursor.compressed_u32()?;
+                let num_sizes = self.cursor.compressed_u32()?;
+                let sizes = (0..num_sizes)
+                    .map(|_| self.cursor.compressed_u32())
+                    .collect::<Result<_>>()?;
+                let num_lo_bounds = self.cursor.compressed_u32()?;
+                let lower_bounds = (0..num_lo_bounds)
+                    .map(|_| self.cursor.compressed_i32())
+                    .collect::<Result<_>>()?;
+                TypeSig::Array(
+                    Box::new(element),
+                    ArrayShape {
+                        rank,
+                        sizes,
+                        lower_bounds,
+                    },
+                )
+            }
+            ELEMENT_TYPE_GENERICINST => {
+                let value_type = match self.cursor.u8()? {
+                    ELEMENT_TYPE_CLASS => false,
+                    ELEMENT_TYPE_VALUETYPE => true,
+                    other => {
+                        return Err(Error::BadSignature(format!(
+                            "invalid generic instantiation kind {other:#04x}"
+                        )));
+                    }
+                };
+                let definition = self.type_def_or_ref()?;
+                let count = self.cursor.compressed_u32()?;
+                let args = (0..count).map(|_| self.type_sig()).collect::<Result<_>>()?;
+                TypeSig::GenericInst {
+                    value_type,
+                    definition,
+                    args,
+                }
+            }
+            ELEMENT_TYPE_VAR => TypeSig::Var(self.cursor.compressed_u32()?),
+            ELEMENT_TYPE_MVAR => TypeSig::MVar(self.cursor.compressed_u32()?),
+            ELEMENT_TYPE_FNPTR => {
+                let calling_convention = self.cursor.u8()?;
+                TypeSig::FnPtr(Box::new(self.method_sig_body(calling_convention)?))
+            }
+            ELEMENT_TYPE_CMOD_REQD | ELEMENT_TYPE_CMOD_OPT => {
+                let modifier = self.type_def_or_ref()?;
+                TypeSig::Modified {
+                    required: element_type == ELEMENT_TYPE_CMOD_REQD,
+                    modifier,
+                    ty: Box::new(self.type_sig()?),
+                }
+            }
+            ELEMENT_TYPE_INTERNAL => {
+                // The runtime only emits these into in-memory signatures, with
+                // a pointer-sized handle; assume the 64-bit layout.
+                TypeSig::Internal(self.cursor.u64()?)
+            }
+            ELEMENT_TYPE_END | ELEMENT_TYPE_SENTINEL => {
+                return Err(Error::BadSignature(format!(
+                    "unexpected element type {element_type:#04x}"
+                )));
+            }
+            other => {
+                return Err(Error::BadSignature(format!(
+                    "unknown element type {other:#04x}"
+                )));
+            }
+        })
+    }
+}
@@ -0,0 +1,674 @@
+//! Metadata table schema (ECMA-335 II.22 and the Portable PDB tables).
+//!
+//! Every table is described once by the `tables!` invocation below, which
+//! generates the [`TableId`] enumeration, the per-table column layout and a
+//! typed row struct implementing [`TableRow`].
+
+use std::fmt;
+
+/// Width-independent type of a metadata table column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColumnType {
+    /// A 2-byte constant.
+    U16,
+    /// A 4-byte constant.
+    U32,
+    /// An index into the `#Strings` heap.
+    Strings,
+    /// An index into the `#GUID` heap.
+    Guid,
+    /// An index into the `#Blob` heap.
+    Blob,
+    /// A row index into a single table.
+    Table(TableId),
+    /// A coded index into one of several tables.
+    Coded(CodedIndex),
+}
+
+/// A named column of a metadata table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Column {
+    pub name: &'static str,
+    pub ty: ColumnType,
+}
+
+/// Coded index kinds (ECMA-335 II.24.2.6).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CodedIndex {
+    TypeDefOrRef,
+    HasConstant,
+    HasCustomAttribute,
+    HasFieldMarshal,
+    HasDeclSecurity,
+    MemberRefParent,
+    HasSemantics,
+    MethodDefOrRef,
+    MemberForwarded,
+    Implementation,
+    CustomAttributeType,
+    ResolutionScope,
+    TypeOrMethodDef,
+    HasCustomDebugInformation,
+}
+
+impl CodedIndex {
+    /// Every coded index kind, in declaration order.
+    pub const ALL: [CodedIndex; 14] = [
+        CodedIndex::TypeDefOrRef,
+        CodedIndex::HasConstant,
+        CodedIndex::HasCustomAttribute,
+        CodedIndex::HasFieldMarshal,
+        CodedIndex::HasDeclSecurity,
+        CodedIndex::MemberRefParent,
+        CodedIndex::HasSemantics,
+        CodedIndex::MethodDefOrRef,
+        CodedIndex::MemberForwarded,
+        CodedIndex::Implementation,
+        CodedIndex::CustomAttributeType,
+        CodedIndex::ResolutionScope,
+        CodedIndex::TypeOrMethodDef,
+        CodedIndex::HasCustomDebugInformation,
+    ];
+
+    /// Returns the tables addressed by each tag value; `None` marks unused tags.
+    pub fn tables(self) -> &'static [Option<TableId>] {
+        use TableId::*;
+        match self {
+            CodedIndex::TypeDefOrRef => &[Some(TypeDef), Some(TypeRef), Some(TypeSpec)],
+            CodedIndex::HasConstant => &[Some(Field), Some(Param), Some(Property)],
+            CodedIndex::HasCustomAttribute => &[
+                Some(MethodDef),
+                Some(Field),
+                Some(TypeRef),
+                Some(TypeDef),
+                Some(Param),
+                Some(InterfaceImpl),
+                Some(MemberRef),
+                Some(Module),
+                Some(DeclSecurity),
+                Some(Property),
+                Some(Event),
+                Some(StandAloneSig),
+                Some(ModuleRef),
+                Some(TypeSpec),
+                Some(Assembly),
+                Some(AssemblyRef),
+                Some(File),
+                Some(ExportedType),
+                Some(ManifestResource),
+                Some(GenericParam),
+                Some(GenericParamConstraint),
+                Some(MethodSpec),
+            ],
+            CodedIndex::HasFieldMarshal => &[Some(Field), Some(Param)],
+            CodedIndex::HasDeclSecurity => &[Some(TypeDef), Some(MethodDef), Some(Assembly)],
+            CodedIndex::MemberRefParent => &[
+                Some(TypeDef),
+                Some(TypeRef),
+                Some(ModuleRef),
+                Some(MethodDef),
+                Some(TypeSpec),
+            ],
+            CodedIndex::HasSemantics => &[Some(Event), Some(Property)],
+            CodedIndex::MethodDefOrRef => &[Some(MethodDef), Some(MemberRef)],
+            CodedIndex::MemberForwarded => &[Some(Field), Some(MethodDef)],
+            CodedIndex::Implementation => &[Some(File), Some(AssemblyRef), Some(ExportedType)],
+            CodedIndex::CustomAttributeType => {
+                &[None, None, Some(MethodDef), Some(MemberRef), None]
+            }
+            CodedIndex::ResolutionScope => &[
+                Some(Module),
+                Some(ModuleRef),
+                Some(AssemblyRef),
+                Some(TypeRef),
+            ],
+            CodedIndex::TypeOrMethodDef => &[Some(TypeDef), Some(MethodDef)],
+            CodedIndex::HasCustomDebugInformation => &[
+                Some(MethodDef),
+                Some(Field),
+                Some(TypeRef),
+                Some(TypeDef),
+                Some(Param),
+                Some(InterfaceImpl),
+                Some(MemberRef),
+                Some(Module),
+                Some(DeclSecurity),
+                Some(Property),
+                Some(Event),
+                Some(StandAloneSig),
+                Some(ModuleRef),
+                Some(TypeSpec),
+                Some(Assembly),
+                Some(AssemblyRef),
+                Some(File),
+                Some(ExportedType),
+                Some(ManifestResource),
+                Some(GenericParam),
+                Some(GenericParamConstraint),
+                Some(MethodSpec),
+                Some(Document),
+                Some(LocalScope),
+                Some(LocalVariable),
+                Some(LocalConstant),
+                Some(ImportScope),
+            ],
+        }
+    }
+
+    /// Number of low bits used for the tag.
+    pub fn tag_bits(self) -> u32 {
+        let n = self.tables().len() as u32;
+        32 - (n - 1).leading_zeros()
+    }
+
+    /// Decodes a raw coded index into a metadata token.
+    pub fn decode(self, raw: u32) -> Option<u32> {
+        let bits = self.tag_bits();
+        let tag = (raw & ((1 << bits) - 1)) as usize;
+        let table = (*self.tables().get(tag)?)?;
+        Some(table.token(raw >> bits))
+    }
+
+    /// Encodes a metadata token as a coded index of this kind.
+    pub fn encode(self, token: u32) -> Option<u32> {
+        let (table, rid) = TableId::from_token(token)?;
+        let tag = self.tables().iter().position(|&t| t == Some(table))? as u32;
+        Some((rid << self.tag_bits()) | tag)
+    }
+}
+
+/// Token type for user strings (`mdtString`).
+pub const TOKEN_TYPE_STRING: u32 = 0x7000_0000;
+
+/// Returns the row id (low 24 bits) of a metadata token.
+pub fn token_rid(token: u32) -> u32 {
+    token & 0x00FF_FFFF
+}
+
+/// Returns `true` if the token's row id is zero.
+pub fn is_nil_token(token: u32) -> bool {
+    token_rid(token) == 0
+}
+
+/// Conversion between typed row fields and raw column values.
+pub trait ColumnValue: Copy {
+    fn from_column(value: u32) -> Self;
+    fn to_column(self) -> u32;
+}
+
+impl ColumnValue for u16 {
+    fn from_column(value: u32) -> Self {
+        value as u16
+    }
+
+    fn to_column(self) -> u32 {
+        self as u32
+    }
+}
+
+impl ColumnValue for u32 {
+    fn from_column(value: u32) -> Self {
+        value
+    }
+
+    fn to_column(self) -> u32 {
+        self
+    }
+}
+
+/// A typed row of a metadata table.
+///
+/// Column values follow the `IMetaDataTables::GetColumn` conventions: heap
+/// columns hold heap offsets, table columns hold row ids and coded index
+/// columns hold full metadata tokens.
+pub trait TableRow: Sized {
+    /// The table this row belongs to.
+    const TABLE: TableId;
+
+    /// Builds a row from decoded column values.
+    fn from_columns(columns: &[u32]) -> Self;
+
+    /// Returns the decoded column values of this row.
+    fn to_columns(&self) -> Vec<u32>;
+}
+
+macro_rules! column_type {
+    (U16) => {
+        ColumnType::U16
+    };
+    (U32) => {
+        ColumnType::U32
+    };
+    (Strings) => {
+        ColumnType::Strings
+    };
+    (Guid) => {
+        ColumnType::Guid
+    };
+    (Blob) => {
+        ColumnType::Blob
+    };
+    (Table($t:ident)) => {
+        ColumnType::Table(TableId::$t)
+    };
+    (Coded($c:ident)) => {
+        ColumnType::Coded(CodedIndex::$c)
+    };
+}
+
+macro_rules! column_rust_type {
+    (U16) => {
+        u16
+    };
+    ($kind:ident $(($arg:ident))?) => {
+        u32
+    };
+}
+
+macro_rules! tables {
+    ($(
+        $(#[$doc:meta])*
+        $table:ident = $id:literal, $row:ident {
+            $($field:ident $col_name:literal : $kind:ident $(($arg:ident))?),* $(,)?
+        }
+    )*) => {
+        /// Metadata table identifiers.
+        #[repr(u8)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum TableId {
+            $($(#[$doc])* $table = $id,)*
+        }
+
+        impl TableId {
+            /// Every known table, in table number order.
+            pub const ALL: &'static [TableId] = &[$(TableId::$table,)*];
+
+            /// Returns the table with the given number.
+            pub fn from_u8(value: u8) -> Option<TableId> {
+                match value {
+                    $($id => Some(TableId::$table),)*
+                    _ => None,
+                }
+            }
+
+            /// Returns the ECMA-335 name of the table.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(TableId::$table => stringify!($table),)*
+                }
+            }
+
+            /// Returns the column layout of the table.
+            pub fn columns(self) -> &'static [Column] {
+                match self {
+                    $(TableId::$table => &[
+                        $(Column { name: $col_name, ty: column_type!($kind $(($arg))?) },)*
+                    ],)*
+                }
+            }
+        }
+
+        $(
+            $(#[$doc])*
+            #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+            pub struct $row {
+                $(pub $field: column_rust_type!($kind $(($arg))?),)*
+            }
+
+            impl TableRow for $row {
+                const TABLE: TableId = TableId::$table;
+
+                fn from_columns(columns: &[u32]) -> Self {
+                    let mut values = columns.iter().copied();
+                    Self {
+                        $($field: ColumnValue::from_column(values.next().unwrap_or(0)),)*
+                    }
+                }
+
+                fn to_columns(&self) -> Vec<u32> {
+                    vec![$(self.$field.to_column(),)*]
+                }
+            }
+        )*
+    };
+}
+
+tables! {
+    /// The current module (II.22.30).
+    Module = 0x00, ModuleRow {
+        generation "Generation": U16,
+        name "Name": Strings,
+        mvid "Mvid": Guid,
+        enc_id "EncId": Guid,
+        enc_base_id "EncBaseId": Guid,
+    }
+    /// A reference to a type defined elsewhere (II.22.38).
+    TypeRef = 0x01, TypeRefRow {
+        resolution_scope "ResolutionScope": Coded(ResolutionScope),
+        name "TypeName": Strings,
+        namespace "TypeNamespace": Strings,
+    }
+    /// A type defined in this module (II.22.37).
+    TypeDef = 0x02, TypeDefRow {
+        flags "Flags": U32,
+        name "TypeName": Strings,
+        namespace "TypeNamespace": Strings,
+        extends "Extends": Coded(TypeDefOrRef),
+        field_list "FieldList": Table(Field),
+        method_list "MethodList": Table(MethodDef),
+    }
+    /// Field indirection table of unoptimized metadata.
+    FieldPtr = 0x03, FieldPtrRow {
+        field "Field": Table(Field),
+    }
+    /// A field definition (II.22.15).
+    Field = 0x04, FieldRow {
+        flags "Flags": U16,
+        name "Name": Strings,
+        signature "Signature": Blob,
+    }
+    /// Method indirection table of unoptimized metadata.
+    MethodPtr = 0x05, MethodPtrRow {
+        method "Method": Table(MethodDef),
+    }
+    /// A method definition (II.22.26).
+    MethodDef = 0x06, MethodDefRow {
+        rva "RVA": U32,
+        impl_flags "ImplFlags": U16,
+        flags "Flags": U16,
+        name "Name": Strings,
+        signature "Signature": Blob,
+        param_list "ParamList": Table(Param),
+    }
+    /// Parameter indirection table of unoptimized metadata.
+    ParamPtr = 0x07, ParamPtrRow {
+        param "Param": Table(Param),
+    }
+    /// A method parameter (II.22.33).
+    Param = 0x08, ParamRow {
+        flags "Flags": U16,
+        sequence "Sequence": U16,
+        name "Name": Strings,
+    }
+    /// An interface implemented by a type (II.22.23).
+    InterfaceImpl = 0x09, InterfaceImplRow {
+        class "Class": Table(TypeDef),
+        interface "Interface": Coded(TypeDefOrRef),
+    }
+    /// A reference to a field or method of another type (II.22.25).
+    MemberRef = 0x0A, MemberRefRow {
+        class "Class": Coded(MemberRefParent),
+        name "Name": Strings,
+        signature "Signature": Blob,
+    }
+    /// A compile-time constant (II.22.9).
+    Constant = 0x0B, ConstantRow {
+        ty "Type": U16,
+        parent "Parent": Coded(HasConstant),
+        value "Value": Blob,
+    }
+    /// A custom attribute (II.22.10).
+    CustomAttribute = 0x0C, CustomAttributeRow {
+        parent "Parent": Coded(HasCustomAttribute),
+        ty "Type": Coded(CustomAttributeType),
+        value "Value": Blob,
+    }
+    /// Marshalling information of a field or parameter (II.22.17).
+    FieldMarshal = 0x0D, FieldMarshalRow {
+        parent "Parent": Coded(HasFieldMarshal),
+        native_type "NativeType": Blob,
+    }
+    /// A declarative security permission set (II.22.11).
+    DeclSecurity = 0x0E, DeclSecurityRow {
+        action "Action": U16,
+        parent "Parent": Coded(HasDeclSecurity),
+        permission_set "PermissionSet": Blob,
+    }
+    /// Explicit packing and size of a type (II.22.8).
+    ClassLayout = 0x0F, ClassLayoutRow {
+        packing_size "PackingSize": U16,
+        class_size "ClassSize": U32,
+        parent "Parent": Table(TypeDef),
+    }
+    /// Explicit offset of a field (II.22.16).
+    FieldLayout = 0x10, FieldLayoutRow {
+        offset "Offset": U32,
+        field "Field": Table(Field),
+    }
+    /// A stand-alone signature (II.22.36).
+    StandAloneSig = 0x11, StandAloneSigRow {
+        signature "Signature": Blob,
+    }
+    /// Maps a type to its events (II.22.12).
+    EventMap = 0x12, EventMapRow {
+        parent "Parent": Table(TypeDef),
+        event_list "EventList": Table(Event),
+    }
+    /// Event indirection table of unoptimized metadata.
+    EventPtr = 0x13, EventPtrRow {
+        event "Event": Table(Event),
+    }
+    /// An event definition (II.22.13).
+    Event = 0x14, EventRow {
+        flags "EventFlags": U16,
+        name "Name": Strings,
+        event_type "EventType": Coded(TypeDefOrRef),
+    }
+    /// Maps a type to its properties (II.22.35).
+    PropertyMap = 0x15, PropertyMapRow {
+        parent "Parent": Table(TypeDef),
+        property_list "PropertyList": Table(Property),
+    }
+    /// Property indirection table of unoptimized metadata.
+    PropertyPtr = 0x16, PropertyPtrRow {
+        property "Property": Table(Property),
+    }
+    /// A property definition (II.22.34).
+    Property = 0x17, PropertyRow {
+        flags "PropFlags": U16,
+        name "Name": Strings,
+        signature "Type": Blob,
+    }
+    /// Links an event or property to its accessor methods (II.22.28).
+    MethodSemantics = 0x18, MethodSemanticsRow {
+        semantics "Semantics": U16,
+        method "Method": Table(MethodDef),
+        association "Association": Coded(HasSemantics),
+    }
+    /// An explicit method override (II.22.27).
+    MethodImpl = 0x19, MethodImplRow {
+        class "Class": Table(TypeDef),
+        method_body "MethodBody": Coded(MethodDefOrRef),
+        method_declaration "MethodDeclaration": Coded(MethodDefOrRef),
+    }
+    /// A reference to another module (II.22.31).
+    ModuleRef = 0x1A, ModuleRefRow {
+        name "Name": Strings,
+    }
+    /// A type specification signature (II.22.39).
+    TypeSpec = 0x1B, TypeSpecRow {
+        signature "Signature": Blob,
+    }
+    /// P/Invoke information of a method or field (II.22.22).
+    ImplMap = 0x1C, ImplMapRow {
+        mapping_flags "MappingFlags": U16,
+        member_forwarded "MemberForwarded": Coded(MemberForwarded),
+        import_name "ImportName": Strings,
+        import_scope "ImportScope": Table(ModuleRef),
+    }
+    /// Initial data of a field (II.22.18).
+    FieldRva = 0x1D, FieldRvaRow {
+        rva "RVA": U32,
+        field "Field": Table(Field),
+    }
+    /// Edit-and-Continue log.
+    EncLog = 0x1E, EncLogRow {
+        token "Token": U32,
+        func_code "FuncCode": U32,
+    }
+    /// Edit-and-Continue token map.
+    EncMap = 0x1F, EncMapRow {
+        token "Token": U32,
+    }
+    /// The assembly manifest (II.22.2).
+    Assembly = 0x20, AssemblyRow {
+        hash_alg_id "HashAlgId": U32,
+        major_version "MajorVersion": U16,
+        minor_version "MinorVersion": U16,
+        build_number "BuildNumber": U16,
+        revision_number "RevisionNumber": U16,
+        flags "Flags": U32,
+        public_key "PublicKey": Blob,
+        name "Name": Strings,
+        culture "Culture": Strings,
+    }
+    /// Unused (II.22.4).
+    AssemblyProcessor = 0x21, AssemblyProcessorRow {
+        processor "Processor": U32,
+    }
+    /// Unused (II.22.3).
+    AssemblyOs = 0x22, AssemblyOsRow {
+        os_platform_id "OSPlatformID": U32,
+        os_major_version "OSMajorVersion": U32,
+        os_minor_version "OSMinorVersion": U32,
+    }
+    /// A reference to another assembly (II.22.5).
+    AssemblyRef = 0x23, AssemblyRefRow {
+        major_version "MajorVersion": U16,
+        minor_version "MinorVersion": U16,
+        build_number "BuildNumber": U16,
+        revision_number "RevisionNumber": U16,
+        flags "Flags": U32,
+        public_key_or_token "PublicKeyOrToken": Blob,
+        name "Name": Strings,
+        culture "Culture": Strings,
+        hash_value "HashValue": Blob,
+    }
+    /// Unused (II.22.7).
+    AssemblyRefProcessor = 0x24, AssemblyRefProcessorRow {
+        processor "Processor": U32,
+        assembly_ref "AssemblyRef": Table(AssemblyRef),
+    }
+    /// Unused (II.22.6).
+    AssemblyRefOs = 0x25, AssemblyRefOsRow {
+        os_platform_id "OSPlatformId": U32,
+        os_major_version "OSMajorVersion": U32,
+        os_minor_version "OSMinorVersion": U32,
+        assembly_ref "AssemblyRef": Table(AssemblyRef),
+    }
+    /// A file of a multi-module assembly (II.22.19).
+    File = 0x26, FileRow {
+        flags "Flags": U32,
+        name "Name": Strings,
+        hash_value "HashValue": Blob,
+    }
+    /// A type exported or forwarded by the assembly (II.22.14).
+    ExportedType = 0x27, ExportedTypeRow {
+        flags "Flags": U32,
+        type_def_id "TypeDefId": U32,
+        name "TypeName": Strings,
+        namespace "TypeNamespace": Strings,
+        implementation "Implementation": Coded(Implementation),
+    }
+    /// A manifest resource (II.22.24).
+    ManifestResource = 0x28, ManifestResourceRow {
+        offset "Offset": U32,
+        flags "Flags": U32,
+        name "Name": Strings,
+        implementation "Implementation": Coded(Implementation),
+    }
+    /// Nesting of a type inside another (II.22.32).
+    NestedClass = 0x29, NestedClassRow {
+        nested_class "NestedClass": Table(TypeDef),
+        enclosing_class "EnclosingClass": Table(TypeDef),
+    }
+    /// A generic parameter of a type or method (II.22.20).
+    GenericParam = 0x2A, GenericParamRow {
+        number "Number": U16,
+        flags "Flags": U16,
+        owner "Owner": Coded(TypeOrMethodDef),
+        name "Name": Strings,
+    }
+    /// A generic method instantiation (II.22.29).
+    MethodSpec = 0x2B, MethodSpecRow {
+        method "Method": Coded(MethodDefOrRef),
+        instantiation "Instantiation": Blob,
+    }
+    /// A constraint on a generic parameter (II.22.21).
+    GenericParamConstraint = 0x2C, GenericParamConstraintRow {
+        owner "Owner": Table(GenericParam),
+        constraint "Constraint": Coded(TypeDefOrRef),
+    }
+    /// A source document (Portable PDB).
+    Document = 0x30, DocumentRow {
+        name "Name": Blob,
+        hash_algorithm "HashAlgorithm": Guid,
+        hash "Hash": Blob,
+        language "Language": Guid,
+    }
+    /// Sequence points of a method (Portable PDB).
+    MethodDebugInformation = 0x31, MethodDebugInformationRow {
+        document "Document": Table(Document),
+        sequence_points "SequencePoints": Blob,
+    }
+    /// A lexical scope of a method (Portable PDB).
+    LocalScope = 0x32, LocalScopeRow {
+        method "Method": Table(MethodDef),
+        import_scope "ImportScope": Table(ImportScope),
+        variable_list "VariableList": Table(LocalVariable),
+        constant_list "ConstantList": Table(LocalConstant),
+        start_offset "StartOffset": U32,
+        length "Length": U32,
+    }
+    /// A local variable (Portable PDB).
+    LocalVariable = 0x33, LocalVariableRow {
+        attributes "Attributes": U16,
+        index "Index": U16,
+        name "Name": Strings,
+    }
+    /// A local constant (Portable PDB).
+    LocalConstant = 0x34, LocalConstantRow {
+        name "Name": Strings,
+        signature "Signature": Blob,
+    }
+    /// A namespace import scope (Portable PDB).
+    ImportScope = 0x35, ImportScopeRow {
+        parent "Parent": Table(ImportScope),
+        imports "Imports": Blob,
+    }
+    /// Links a state machine's MoveNext to its kickoff method (Portable PDB).
+    StateMachineMethod = 0x36, StateMachineMethodRow {
+        move_next_method "MoveNextMethod": Table(MethodDef),
+        kickoff_method "KickoffMethod": Table(MethodDef),
+    }
+    /// Custom debug information (Portable PDB).
+    CustomDebugInformation = 0x37, CustomDebugInformationRow {
+        parent "Parent": Coded(HasCustomDebugInformation),
+        kind "Kind": Guid,
+        value "Value": Blob,
+    }
+}
+
+impl TableId {
+    /// Returns the metadata token for row `rid` of this table.
+    pub fn token(self, rid: u32) -> u32 {
+        ((self as u32) << 24) | (rid & 0x00FF_FFFF)
+    }
+
+    /// Splits a metadata token into its table and row id.
+    pub fn from_token(token: u32) -> Option<(TableId, u32)> {
+        let table = TableId::from_u8((token >> 24) as u8)?;
+        Some((table, token_rid(token)))
+    }
+
+    /// Returns `true` for the Portable PDB tables.
+    pub fn is_debug_table(self) -> bool {
+        (self as u8) >= 0x30
+    }
+}
+
+impl fmt::Display for TableId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -0,0 +1,497 @@
+//! Windows Metadata (`.winmd`) files and the CLR's projection of Windows
+//! Runtime types onto .NET types.
+
+use std::collections::HashMap;
+
+use super::custom_attribute::CustomAttributeArgument;
+use super::flags::TYPE_ATTRIBUTE_IMPORT;
+use super::metadata::MetadataReader;
+use super::tables::{
+    AssemblyRefRow, AssemblyRow, CodedIndex, ColumnType, TableId, TableRow, TypeDefRow, TypeRefRow,
+    token_rid,
+};
+use crate::error::{Error, Result};
+use crate::writer::{MetadataBuilder, SIGNATURE_COLUMNS, remap_signature};
+
+/// Prefix of the metadata version string of Windows Metadata files.
+const WINMD_VERSION_PREFIX: &str = "WindowsRuntime ";
+
+/// Prefix the CLR gives to WinRT types whose projection hides them.
+const WINRT_PREFIX: &str = "<WinRT>";
+/// Prefix compilers give to the implementation of a managed WinRT type.
+const CLR_PREFIX: &str = "<CLR>";
+
+const TYPE_ATTRIBUTE_VISIBILITY_MASK: u32 = 0x0000_0007;
+const TYPE_ATTRIBUTE_PUBLIC: u32 = 0x0000_0001;
+const TYPE_ATTRIBUTE_NESTED_PUBLIC: u32 = 0x0000_0002;
+const TYPE_ATTRIBUTE_NESTED_PRIVATE: u32 = 0x0000_0003;
+const TYPE_ATTRIBUTE_WINDOWS_RUNTIME: u32 = 0x0000_4000;
+/// `AssemblyFlags.ContentType` value for Windows Runtime assemblies.
+const ASSEMBLY_CONTENT_TYPE_WINDOWS_RUNTIME: u32 = 0x0000_0200;
+
+const CONTRACT_VERSION_ATTRIBUTE: &str = "Windows.Foundation.Metadata.ContractVersionAttribute";
+
+/// The public key token of the framework facades (`b03f5f7f11d50a3a`).
+const FRAMEWORK_TOKEN: [u8; 8] = [0xb0, 0x3f, 0x5f, 0x7f, 0x11, 0xd5, 0x0a, 0x3a];
+/// The public key token of the ECMA-signed framework assemblies
+/// (`b77a5c561934e089`).
+const ECMA_TOKEN: [u8; 8] = [0xb7, 0x7a, 0x5c, 0x56, 0x19, 0x34, 0xe0, 0x89];
+
+/// The kind of a Windows Metadata file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WinMdKind {
+    /// A winmd produced by the Windows SDK tools (`WindowsRuntime 1.4`).
+    Windows,
+    /// A winmd produced by a .NET compiler, which also carries managed
+    /// implementations (`WindowsRuntime 1.4;CLR v4.0.30319`).
+    Managed,
+}
+
+/// The .NET type a Windows Runtime type is projected to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProjectedType {
+    pub winrt_namespace: &'static str,
+    pub winrt_name: &'static str,
+    pub namespace: &'static str,
+    pub name: &'static str,
+    /// The assembly that defines the .NET type.
+    pub assembly: &'static str,
+    /// Whether the Windows Runtime type is a value type.
+    pub winrt_is_value_type: bool,
+    /// Whether the .NET type is a value type; signatures change from
+    /// `class` to `valuetype` (or back) where this differs.
+    pub is_value_type: bool,
+}
+
+const SYSTEM_RUNTIME: &str = "System.Runtime";
+const SYSTEM_OBJECT_MODEL: &str = "System.ObjectModel";
+const SYSTEM_RUNTIME_WINDOWS_RUNTIME: &str = "System.Runtime.WindowsRuntime";
+const SYSTEM_RUNTIME_WINDOWS_RUNTIME_XAML: &str = "System.Runtime.WindowsRuntime.UI.Xaml";
+const SYSTEM_RUNTIME_INTEROP_WINDOWS_RUNTIME: &str =
+    "System.Runtime.InteropServices.WindowsRuntime";
+const SYSTEM_NUMERICS_VECTORS: &str = "System.Numerics.Vectors";
+
+/// The assemblies projected types live in, with their versions and public
+/// key tokens.
+const PROJECTION_ASSEMBLIES: &[(&str, [u8; 8])] = &[
+    (SYSTEM_RUNTIME, FRAMEWORK_TOKEN),
+    (SYSTEM_OBJECT_MODEL, FRAMEWORK_TOKEN),
+    (SYSTEM_RUNTIME_WINDOWS_RUNTIME, ECMA_TOKEN),
+    (SYSTEM_RUNTIME_WINDOWS_RUNTIME_XAML, ECMA_TOKEN),
+    (SYSTEM_RUNTIME_INTEROP_WINDOWS_RUNTIME, FRAMEWORK_TOKEN),
+    (SYSTEM_NUMERICS_VECTORS, FRAMEWORK_TOKEN),
+];
+
+macro_rules! projections {
+    ($($wns:literal $wname:literal $wvalue:literal => $ns:literal $name:literal $value:literal in $asm:ident,)*) => {
+        &[$(ProjectedType {
+            winrt_namespace: $wns,
+            winrt_name: $wname,
+            namespace: $ns,
+            name: $name,
+            assembly: $asm,
+            winrt_is_value_type: $wvalue,
+            is_value_type: $value,
+        },)*]
+    };
+}
+
+/// The types the CLR redirects (`winrtprojectedtypes.h`).
+const PROJECTED_TYPES: &[ProjectedType] = projections! {
+    "Windows.Foundation.Metadata" "AttributeUsageAttribute" false => "System" "AttributeUsageAttribute" false in SYSTEM_RUNTIME,
+    "Windows.Foundation.Metadata" "AttributeTargets" true => "System" "AttributeTargets" true in SYSTEM_RUNTIME,
+    "Windows.UI" "Color" true => "Windows.UI" "Color" true in SYSTEM_RUNTIME_WINDOWS_RUNTIME,
+    "Windows.Foundation" "DateTime" true => "System" "DateTimeOffset" true in SYSTEM_RUNTIME,
+    "Windows.Foundation" "EventHandler`1" false => "System" "EventHandler`1" false in SYSTEM_RUNTIME,
+    "Windows.Foundation" "EventRegistrationToken" true => "System.Runtime.InteropServices.WindowsRuntime" "EventRegistrationToken" true in SYSTEM_RUNTIME_INTEROP_WINDOWS_RUNTIME,
+    "Windows.Foundation" "HResult" true => "System" "Exception" false in SYSTEM_RUNTIME,
+    "Windows.Foundation" "IReference`1" false => "System" "Nullable`1" true in SYSTEM_RUNTIME,
+    "Windows.Foundation" "Point" true => "Windows.Foundation" "Point" true in SYSTEM_RUNTIME_WINDOWS_RUNTIME,
+    "Windows.Foundation" "Rect" true => "Windows.Foundation" "Rect" true in SYSTEM_RUNTIME_WINDOWS_RUNTIME,
+    "Windows.Foundation" "Size" true => "Windows.Foundation" "Size" true in SYSTEM_RUNTIME_WINDOWS_RUNTIME,
+    "Windows.Foundation" "TimeSpan" true => "System" "TimeSpan" true in SYSTEM_RUNTIME,
+    "Windows.Foundation" "Uri" false => "System" "Uri" false in SYSTEM_RUNTIME,
+    "Windows.Foundation" "IClosable" false => "System" "IDisposable" false in SYSTEM_RUNTIME,
+    "Windows.Foundation.Collections" "IIterable`1" false => "System.Collections.Generic" "IEnumerable`1" false in SYSTEM_RUNTIME,
+    "Windows.Foundation.Collections" "IVector`1" false => "System.Collections.Generic" "IList`1" false in SYSTEM_RUNTIME,
+    "Windows.Foundation.Collections" "IVectorView`1" false => "System.Collections.Generic" "IReadOnlyList`1" false in SYSTEM_RUNTIME,
+    "Windows.Foundation.Collections" "IMap`2" false => "System.Collections.Generic" "IDictionary`2" false in SYSTEM_RUNTIME,
+    "Windows.Foundation.Collections" "IMapView`2" false => "System.Collections.Generic" "IReadOnlyDictionary`2" false in SYSTEM_RUNTIME,
+    "Windows.Foundation.Collections" "IKeyValuePair`2" false => "System.Collections.Generic" "KeyValuePair`2" true in SYSTEM_RUNTIME,
+    "Windows.UI.Xaml.Input" "ICommand" false => "System.Windows.Input" "ICommand" false in SYSTEM_OBJECT_MODEL,
+    "Windows.UI.Xaml.Interop" "IBindableIterable" false => "System.Collections" "IEnumerable" false in SYSTEM_RUNTIME,
+    "Windows.UI.Xaml.Interop" "IBindableVector" false => "System.Collections" "IList" false in SYSTEM_RUNTIME,
+    "Windows.UI.Xaml.Interop" "INotifyCollectionChanged" false => "System.Collections.Specialized" "INotifyCollectionChanged" false in SYSTEM_OBJECT_MODEL,
+    "Windows.UI.Xaml.Interop" "NotifyCollectionChangedEventHandler" false => "System.Collections.Specialized" "NotifyCollectionChangedEventHandler" false in SYSTEM_OBJECT_MODEL,
+    "Windows.UI.Xaml.Interop" "NotifyCollectionChangedEventArgs" false => "System.Collections.Specialized" "NotifyCollectionChangedEventArgs" false in SYSTEM_OBJECT_MODEL,
+    "Windows.UI.Xaml.Interop" "NotifyCollectionChangedAction" true => "System.Collections.Specialized" "NotifyCollectionChangedAction" true in SYSTEM_OBJECT_MODEL,
+    "Windows.UI.Xaml.Data" "INotifyPropertyChanged" false => "System.ComponentModel" "INotifyPropertyChanged" false in SYSTEM_OBJECT_MODEL,
+    "Windows.UI.Xaml.Data" "PropertyChangedEventHandler" false => "System.ComponentModel" "PropertyChangedEventHandler" false in SYSTEM_OBJECT_MODEL,
+    "Windows.UI.Xaml.Data" "PropertyChangedEventArgs" false => "System.ComponentModel" "PropertyChangedEventArgs" false in SYSTEM_OBJECT_MODEL,
+    "Windows.UI.Xaml.Interop" "TypeName" true => "System" "Type" false in SYSTEM_RUNTIME,
+    "Windows.UI.Xaml" "CornerRadius" true => "Windows.UI.Xaml" "CornerRadius" true in SYSTEM_RUNTIME_WINDOWS_RUNTIME_XAML,
+    "Windows.UI.Xaml" "Duration" true => "Windows.UI.Xaml" "Duration" true in SYSTEM_RUNTIME_WINDOWS_RUNTIME_XAML,
+    "Windows.UI.Xaml" "DurationType" true => "Windows.UI.Xaml" "DurationType" true in SYSTEM_RUNTIME_WINDOWS_RUNTIME_XAML,
+    "Windows.UI.Xaml" "GridLength" true => "Windows.UI.Xaml" "GridLength" true in SYSTEM_RUNTIME_WINDOWS_RUNTIME_XAML,
+    "Windows.UI.Xaml" "GridUnitType" true => "Windows.UI.Xaml" "GridUnitType" true in SYSTEM_RUNTIME_WINDOWS_RUNTIME_XAML,
+    "Windows.UI.Xaml" "Thickness" true => "Windows.UI.Xaml" "Thickness" true in SYSTEM_RUNTIME_WINDOWS_RUNTIME_XAML,
+    "Windows.UI.Xaml.Controls.Primitives" "GeneratorPosition" true => "Windows.UI.Xaml.Controls.Primitives" "GeneratorPosition" true in SYSTEM_RUNTIME_WINDOWS_RUNTIME_XAML,
+    "Windows.UI.Xaml.Media" "Matrix" true => "Windows.UI.Xaml.Media" "Matrix" true in SYSTEM_RUNTIME_WINDOWS_RUNTIME_XAML,
+    "Windows.UI.Xaml.Media.Animation" "KeyTime" true => "Windows.UI.Xaml.Media.Animation" "KeyTime" true in SYSTEM_RUNTIME_WINDOWS_RUNTIME_XAML,
+    "Windows.UI.Xaml.Media.Animation" "RepeatBehavior" true => "Windows.UI.Xaml.Media.Animation" "RepeatBehavior" true in SYSTEM_RUNTIME_WINDOWS_RUNTIME_XAML,
+    "Windows.UI.Xaml.Media.Animation" "RepeatBehaviorType" true => "Windows.UI.Xaml.Media.Animation" "RepeatBehaviorType" true in SYSTEM_RUNTIME_WINDOWS_RUNTIME_XAML,
+    "Windows.UI.Xaml.Media.Media3D" "Matrix3D" true => "Windows.UI.Xaml.Media.Media3D" "Matrix3D" true in SYSTEM_RUNTIME_WINDOWS_RUNTIME_XAML,
+    "Windows.Foundation.Numerics" "Matrix3x2" true => "System.Numerics" "Matrix3x2" true in SYSTEM_NUMERICS_VECTORS,
+    "Windows.Foundation.Numerics" "Matrix4x4" true => "System.Numerics" "Matrix4x4" true in SYSTEM_NUMERICS_VECTORS,
+    "Windows.Foundation.Numerics" "Plane" true => "System.Numerics" "Plane" true in SYSTEM_NUMERICS_VECTORS,
+    "Windows.Foundation.Numerics" "Quaternion" true => "System.Numerics" "Quaternion" true in SYSTEM_NUMERICS_VECTORS,
+    "Windows.Foundation.Numerics" "Vector2" true => "System.Numerics" "Vector2" true in SYSTEM_NUMERICS_VECTORS,
+    "Windows.Foundation.Numerics" "Vector3" true => "System.Numerics" "Vector3" true in SYSTEM_NUMERICS_VECTORS,
+    "Windows.Foundation.Numerics" "Vector4" true => "System.Numerics" "Vector4" true in SYSTEM_NUMERICS_VECTORS,
+};
+
+/// Returns the .NET projection of a Windows Runtime type.
+///
+/// ```
+/// let uri = mscoree::winrt_projected_type("Windows.Foundation", "Uri").unwrap();
+/// assert_eq!((uri.namespace, uri.name, uri.assembly), ("System", "Uri", "System.Runtime"));
+/// ```
+pub fn winrt_projected_type(namespace: &str, name: &str) -> Option<&'static ProjectedType> {
+    PROJECTED_TYPES
+        .iter()
+        .find(|ty| ty.winrt_namespace == namespace && ty.winrt_name == name)
+}
+
+/// The API contract a Windows Runtime type belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ContractVersion {
+    /// The full name of the contract type
+    /// (e.g. `Windows.Foundation.UniversalApiContract`).
+    pub contract: String,
+    /// The contract version, with the major version in the high 16 bits.
+    pub version: u32,
+}
+
+impl ContractVersion {
+    pub fn major(&self) -> u16 {
+        (self.version >> 16) as u16
+    }
+
+    pub fn minor(&self) -> u16 {
+        self.version as u16
+    }
+}
+
+impl MetadataReader {
+    /// Returns the kind of Windows Metadata file, or `None` for ordinary
+    /// CLI metadata.
+    pub fn winmd_kind(&self) -> Option<WinMdKind> {
+        let rest = self.version().strip_prefix(WINMD_VERSION_PREFIX)?;
+        Some(if rest.contains("CLR") {
+            WinMdKind::Managed
+        } else {
+            WinMdKind::Windows
+        })
+    }
+
+    /// Returns the `ContractVersionAttribute` of a `TypeDef`: the contract a
+    /// type was introduced in or, on a contract type itself, its version.
+    pub fn contract_version(&self, token: u32) -> Result<Option<ContractVersion>> {
+        let Some(&rid) = self
+            .find_custom_attributes(token, CONTRACT_VERSION_ATTRIBUTE)?
+            .first()
+        else {
+            return Ok(None);
+        };
+        let value = self.custom_attribute_value(rid)?;
+        let (contract, version) = match value.fixed_args.as_slice() {
+            [CustomAttributeArgument::U4(version)] => (self.type_full_name(token)?, *version),
+            [
+                CustomAttributeArgument::Type(Some(name))
+                | CustomAttributeArgument::String(Some(name)),
+                CustomAttributeArgument::U4(version),
+            ] => (
+                name.split(',')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string(),
+                *version,
+            ),
+            _ => {
+                return Err(Error::BadMetadata(format!(
+                    "{} has an invalid ContractVersionAttribute",
+                    self.type_full_name(token)?
+                )));
+            }
+        };
+        Ok(Some(ContractVersion { contract, version }))
+    }
+
+    /// Returns the metadata as the CLR presents a winmd when it opens it
+    /// with projection enabled.
+    ///
+    /// - References to projected Windows Runtime types (in `TypeRef` rows,
+    ///   coded columns and signatures) name the .NET type instead, with
+    ///   `class` and `valuetype` adjusted in signatures. Definitions of such
+    ///   types are renamed `<WinRT>Name` and made private.
+    /// - `mscorlib` references become `System.Runtime`, references to other
+    ///   winmds and the assembly itself are marked as Windows Runtime
+    ///   content.
+    /// - Runtime classes and interfaces of a Windows winmd are `[ComImport]`.
+    /// - In a managed winmd, `<CLR>Name` implementations become the public
+    ///   `Name` and the Windows Runtime facing types are hidden as
+    ///   `<WinRT>Name`.
+    ///
+    /// The result keeps the winmd version string, so
+    /// [`winmd_kind`](Self::winmd_kind) still recognizes it.
+    pub fn winmd_projection(&self) -> Result<MetadataReader> {
+        let kind = self.winmd_kind().ok_or_else(|| {
+            Error::BadMetadata(format!(
+                "metadata version {:?} is not Windows Metadata",
+                self.version()
+            ))
+        })?;
+        let mut projector = Projector {
+            reader: self,
+            builder: MetadataBuilder::from_reader(self)?,
+            redirects: HashMap::new(),
+        };
+        projector.assembly_refs(kind)?;
+        projector.type_refs()?;
+        projector.type_defs(kind)?;
+        projector.coded_columns()?;
+        projector.signatures()?;
+        MetadataReader::from_bytes(projector.builder.to_bytes()?)
+    }
+}
+
+struct Projector<'a> {
+    reader: &'a MetadataReader,
+    builder: MetadataBuilder,
+    /// References to redirect: the new `TypeDefOrRef` token and, where the
+    /// projection changes it, whether the type is now a value type.
+    redirects: HashMap<u32, (u32, Option<bool>)>,
+}
+
+impl Projector<'_> {
+    fn assembly_refs(&mut self, kind: WinMdKind) -> Result<()> {
+        if self.builder.row_count(TableId::Assembly) != 0 {
+            let mut row = self.builder.row::<AssemblyRow>(1)?;
+            row.flags |= ASSEMBLY_CONTENT_TYPE_WINDOWS_RUNTIME;
+            self.builder
+                .set_row(TableId::Assembly, 1, row.to_columns())?;
+        }
+        for rid in 1..=self.builder.row_count(TableId::AssemblyRef) {
+            let mut row = self.builder.row::<AssemblyRefRow>(rid)?;
+            if self.reader.string(row.name)? == "mscorlib" {
+                row = self.projection_assembly_ref(SYSTEM_RUNTIME, FRAMEWORK_TOKEN);
+            } else if kind == WinMdKind::Windows {
+                row.flags |= ASSEMBLY_CONTENT_TYPE_WINDOWS_RUNTIME;
+            }
+            self.builder
+                .set_row(TableId::AssemblyRef, rid, row.to_columns())?;
+        }
+        Ok(())
+    }
+
+    fn projection_assembly_ref(&mut self, name: &str, token: [u8; 8]) -> AssemblyRefRow {
+        AssemblyRefRow {
+            major_version: 4,
+            minor_version: 0,
+            build_number: 0,
+            revision_number: 0,
+            public_key_or_token: self.builder.blob(&token),
+            name: self.builder.string(name),
+            ..AssemblyRefRow::default()
+        }
+    }
+
+    /// Returns the `AssemblyRef` token of a projection assembly, adding the
+    /// reference if the winmd has none.
+    fn assembly_ref_token(&mut self, name: &str) -> Result<u32> {
+        for rid in 1..=self.builder.row_count(TableId::AssemblyRef) {
+            let row = self.builder.row::<AssemblyRefRow>(rid)?;
+            if self.builder_string(row.name)? == name {
+                return Ok(TableId::AssemblyRef.token(rid));
+            }
+        }
+        let token = PROJECTION_ASSEMBLIES
+            .iter()
+            .find(|(assembly, _)| *assembly == name)
+            .map_or(FRAMEWORK_TOKEN, |(_, token)| *token);
+        let row = self.projection_assembly_ref(name, token);
+        Ok(TableId::AssemblyRef.token(self.builder.add(&row)))
+    }
+
+    /// Reads a string the builder may have added after the reader's heap.
+    fn builder_string(&self, index: u32) -> Result<String> {
+        let heap = self.builder.strings_heap();
+        let start = index as usize;
+        let end = heap
+            .get(start..)
+            .and_then(|rest| rest.iter().position(|&b| b == 0))
+            .map(|len| start + len)
+            .ok_or_else(|| Error::BadIndex(format!("string index {index:#x} is out of range")))?;
+        String::from_utf8(heap[start..end].to_vec())
+            .map_err(|_| Error::BadMetadata(format!("string {index:#x} is not valid UTF-8")))
+    }
+
+    fn type_refs(&mut self) -> Result<()> {
+        for rid in 1..=self.reader.row_count(TableId::TypeRef) {
+            let row = self.reader.row::<TypeRefRow>(rid)?;
+            // Nested type references keep their enclosing type as scope.
+            if TableId::from_token(row.resolution_scope)
+                .is_some_and(|(table, _)| table == TableId::TypeRef)
+            {
+                continue;
+            }
+            let namespace = self.reader.string(row.namespace)?;
+            let name = self.reader.string(row.name)?;
+            let Some(projected) = winrt_projected_type(namespace, name) else {
+                continue;
+            };
+            let projected_row = TypeRefRow {
+                resolution_scope: self.assembly_ref_token(projected.assembly)?,
+                name: self.builder.string(projected.name),
+                namespace: self.builder.string(projected.namespace),
+            };
+            self.builder
+                .set_row(TableId::TypeRef, rid, projected_row.to_columns())?;
+            let token = TableId::TypeRef.token(rid);
+            self.redirects
+                .insert(token, (token, kind_change(projected)));
+        }
+        Ok(())
+    }
+
+    fn type_defs(&mut self, kind: WinMdKind) -> Result<()> {
+        let count = self.reader.row_count(TableId::TypeDef);
+        let mut by_name = HashMap::new();
+        for rid in 1..=count {
+            let row = self.reader.row::<TypeDefRow>(rid)?;
+            by_name.insert((row.namespace, self.reader.string(row.name)?), rid);
+        }
+        for rid in 1..=count {
+            let mut row = self.reader.row::<TypeDefRow>(rid)?;
+            let token = TableId::TypeDef.token(rid);
+            let namespace = self.reader.string(row.namespace)?;
+            let name = self.reader.string(row.name)?;
+            if let Some(projected) = winrt_projected_type(namespace, name) {
+                let type_ref = TypeRefRow {
+                    resolution_scope: self.assembly_ref_token(projected.assembly)?,
+                    name: self.builder.string(projected.name),
+                    namespace: self.builder.string(projected.namespace),
+                };
+                let type_ref = TableId::TypeRef.token(self.builder.add(&type_ref));
+                self.redirects
+                    .insert(token, (type_ref, kind_change(projected)));
+                hide(&mut self.builder, &mut row, name);
+            } else if kind == WinMdKind::Managed
+                && let Some(public_name) = name.strip_prefix(CLR_PREFIX)
+            {
+                row.name = self.builder.string(public_name);
+                row.flags = (row.flags & !TYPE_ATTRIBUTE_VISIBILITY_MASK)
+                    | if row.flags & TYPE_ATTRIBUTE_VISIBILITY_MASK >= TYPE_ATTRIBUTE_NESTED_PUBLIC
+                    {
+                        TYPE_ATTRIBUTE_NESTED_PUBLIC
+                    } else {
+                        TYPE_ATTRIBUTE_PUBLIC
+                    };
+            } else if kind == WinMdKind::Managed
+                && by_name.contains_key(&(row.namespace, format!("{CLR_PREFIX}{name}").as_str()))
+            {
+                hide(&mut self.builder, &mut row, name);
+            } else if kind == WinMdKind::Windows
+                && row.flags & TYPE_ATTRIBUTE_WINDOWS_RUNTIME != 0
+                && self.is_com_import_candidate(&row)?
+            {
+                row.flags |= TYPE_ATTRIBUTE_IMPORT;
+            } else {
+                continue;
+            }
+            self.builder
+                .set_row(TableId::TypeDef, rid, row.to_columns())?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` for runtime classes and interfaces, whose
+    /// implementation is a COM object.
+    fn is_com_import_candidate(&self, row: &TypeDefRow) -> Result<bool> {
+        if token_rid(row.extends) == 0 {
+            // Interfaces have no base type.
+            return Ok(true);
+        }
+        let base = self.reader.type_full_name(row.extends)?;
+        Ok(!matches!(
+            base.as_str(),
+            "System.ValueType" | "System.Enum" | "System.MulticastDelegate" | "System.Attribute"
+        ))
+    }
+
+    /// Redirects `TypeDefOrRef` and `MemberRefParent` columns that name a
+    /// projected type defined in this winmd.
+    fn coded_columns(&mut self) -> Result<()> {
+        if !self
+            .redirects
+            .keys()
+            .any(|&token| TableId::from_token(token).is_some_and(|(t, _)| t == TableId::TypeDef))
+        {
+            return Ok(());
+        }
+        for &table in TableId::ALL {
+            for (column, info) in table.columns().iter().enumerate() {
+                if !matches!(
+                    info.ty,
+                    ColumnType::Coded(CodedIndex::TypeDefOrRef | CodedIndex::MemberRefParent)
+                ) {
+                    continue;
+                }
+                for rid in 1..=self.builder.row_count(table) {
+                    let value = self.builder.row_values(table, rid)?[column];
+                    if let Some(&(target, _)) = self.redirects.get(&value)
+                        && target != value
+                    {
+                        self.builder.set_column(table, rid, column, target)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn signatures(&mut self) -> Result<()> {
+        if self.redirects.is_empty() {
+            return Ok(());
+        }
+        for &(table, column, is_type) in SIGNATURE_COLUMNS {
+            for rid in 1..=self.reader.row_count(table) {
+                let index = self.reader.column(table, rid, column)?;
+                let blob = self.reader.blob(index)?;
+                let rewritten = remap_signature(blob, is_type, &mut |token| {
+                    Ok(self.redirects.get(&token).copied().unwrap_or((token, None)))
+                })?;
+                if rewritten != blob {
+                    let index = self.builder.blob(&rewritten);
+                    self.builder.set_column(table, rid, column, index)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn kind_change(projected: &ProjectedType) -> Option<bool> {
+    (projected.winrt_is_value_type != projected.is_value_type).then_some(projected.is_value_type)
+}
+
+/// Renames a type `<WinRT>Name` and makes it private.
+fn hide(builder: &mut MetadataBuilder, row: &mut TypeDefRow, name: &str) {
+    row.name = builder.string(&format!("{WINRT_PREFIX}{name}"));
+    let visibility = row.flags & TYPE_ATTRIBUTE_VISIBILITY_MASK;
+    row.flags &= !TYPE_ATTRIBUTE_VISIBILITY_MASK;
+    if visibility >= TYPE_ATTRIBUTE_NESTED_PUBLIC {
+        row.flags |= TYPE_ATTRIBUTE_NESTED_PRIVATE;
+    }
+}
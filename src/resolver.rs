@@ -0,0 +1,408 @@
+//! Locating referenced assemblies on disk.
+//!
+//! [`AssemblyResolver`] follows the probing rules of the runtime loaders:
+//! the application base and private bin paths of .NET Framework
+//! (`AppDomainSetup.PrivateBinPath`, `ICorRuntimeHost`'s
+//! `GetPrivateBinPaths`), the `*.deps.json` assets and shared framework
+//! directories of .NET Core, and any extra search paths. Every file that
+//! was looked at is recorded so failed binds can be diagnosed like with
+//! the fusion log.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::assembly_identity::{AssemblyCompareFlags, AssemblyIdentity};
+use crate::deps_json::{DepsAsset, DepsAssetType, DepsFile, DepsTargetLibrary};
+use crate::error::Result;
+use crate::reader::{MetadataReader, PeImage};
+
+/// How the version of a candidate must relate to the requested version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum VersionPolicy {
+    /// Any version is accepted.
+    Any,
+    /// The candidate must have the requested version or a higher one, as
+    /// on .NET Core.
+    #[default]
+    AtLeast,
+    /// The candidate must have exactly the requested version, as for
+    /// strong-named assemblies on .NET Framework without binding redirects.
+    Exact,
+}
+
+/// Where a probed location came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProbeSource {
+    /// An asset listed in the `*.deps.json` file.
+    DepsJson,
+    /// A shared framework directory.
+    Framework,
+    /// The application base directory.
+    AppBase,
+    /// A private bin path below the application base.
+    PrivateBinPath,
+    /// A custom search path.
+    SearchPath,
+}
+
+/// The result of looking at one location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeOutcome {
+    /// No file exists at the location.
+    NotFound,
+    /// The file matches the requested identity and was chosen.
+    Accepted,
+    /// The file exists but does not match, with the reason.
+    Rejected(String),
+}
+
+/// One entry of the probing trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeAttempt {
+    pub path: PathBuf,
+    pub source: ProbeSource,
+    pub outcome: ProbeOutcome,
+}
+
+impl fmt::Display for ProbeAttempt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}: ", self.source, self.path.display())?;
+        match &self.outcome {
+            ProbeOutcome::NotFound => f.write_str("not found"),
+            ProbeOutcome::Accepted => f.write_str("accepted"),
+            ProbeOutcome::Rejected(reason) => write!(f, "rejected, {reason}"),
+        }
+    }
+}
+
+/// The outcome of [`AssemblyResolver::resolve`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Resolution {
+    /// The chosen file, if any location matched.
+    pub path: Option<PathBuf>,
+    /// The identity of the chosen file.
+    pub identity: Option<AssemblyIdentity>,
+    /// Every location that was looked at, in order.
+    pub trace: Vec<ProbeAttempt>,
+}
+
+impl Resolution {
+    /// Returns `true` if a matching file was found.
+    pub fn is_resolved(&self) -> bool {
+        self.path.is_some()
+    }
+}
+
+/// Finds the file that satisfies an assembly reference.
+///
+/// Locations are searched in this order:
+///
+/// 1. the assets of a matching library in the `*.deps.json` file, in the
+///    application directory and then below the probe paths;
+/// 2. the shared framework directories;
+/// 3. the application base and its private bin paths, trying
+///    `Name.dll` and `Name/Name.dll` in each of them before `Name.exe` and
+///    `Name/Name.exe`, or the same below a `culture` subdirectory for
+///    satellite assemblies;
+/// 4. the custom search paths, probed like the application base.
+///
+/// A candidate is accepted if its name, culture and public key token match
+/// the reference and its version satisfies the [`VersionPolicy`].
+#[derive(Debug, Clone, Default)]
+pub struct AssemblyResolver {
+    app_base: Option<PathBuf>,
+    private_bin_paths: Vec<PathBuf>,
+    deps: Option<(DepsFile, PathBuf)>,
+    rid: Option<String>,
+    probe_paths: Vec<PathBuf>,
+    framework_dirs: Vec<PathBuf>,
+    search_paths: Vec<PathBuf>,
+    version_policy: VersionPolicy,
+}
+
+impl AssemblyResolver {
+    /// Creates a resolver without any locations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the application base directory.
+    pub fn with_app_base(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.app_base = Some(dir.into());
+        self
+    }
+
+    /// Adds private bin paths in `GetPrivateBinPaths` form: a
+    /// semicolon-separated list of directories relative to the application
+    /// base.
+    pub fn with_private_bin_paths(mut self, paths: &str) -> Self {
+        self.private_bin_paths.extend(
+            paths
+                .split(';')
+                .map(str::trim)
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+        );
+        self
+    }
+
+    /// Uses the assets of a `*.deps.json` file of the application in
+    /// `app_dir`.
+    pub fn with_deps_file(mut self, deps: DepsFile, app_dir: impl Into<PathBuf>) -> Self {
+        self.deps = Some((deps, app_dir.into()));
+        self
+    }
+
+    /// Sets the runtime identifier, e.g. `linux-x64`, used to pick
+    /// platform-specific `*.deps.json` assets.
+    pub fn with_runtime_identifier(mut self, rid: impl Into<String>) -> Self {
+        self.rid = Some(rid.into());
+        self
+    }
+
+    /// Adds a package probing directory, such as the NuGet package cache,
+    /// for `*.deps.json` packages that were not published with the app.
+    pub fn with_probe_path(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.probe_paths.push(dir.into());
+        self
+    }
+
+    /// Adds a shared framework directory, e.g.
+    /// `/usr/share/dotnet/shared/Microsoft.NETCore.App/8.0.0`.
+    pub fn with_framework_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.framework_dirs.push(dir.into());
+        self
+    }
+
+    /// Adds a custom search path, probed after all other locations.
+    pub fn with_search_path(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.search_paths.push(dir.into());
+        self
+    }
+
+    /// Sets how candidate versions are checked.
+    pub fn with_version_policy(mut self, policy: VersionPolicy) -> Self {
+        self.version_policy = policy;
+        self
+    }
+
+    /// Resolves `AssemblyRef` row `rid` of `metadata`.
+    pub fn resolve_ref(&self, metadata: &MetadataReader, rid: u32) -> Result<Resolution> {
+        Ok(self.resolve(&metadata.assembly_ref_identity(rid)?))
+    }
+
+    /// Finds the file for `reference`.
+    pub fn resolve(&self, reference: &AssemblyIdentity) -> Resolution {
+        let mut probe = Probe {
+            resolver: self,
+            reference,
+            seen: HashSet::new(),
+            resolution: Resolution::default(),
+        };
+        let culture = reference.culture.as_deref().unwrap_or("");
+
+        if let Some((deps, app_dir)) = &self.deps {
+            let rids = self
+                .rid
+                .as_deref()
+                .map(|rid| deps.rid_fallbacks(rid))
+                .unwrap_or_default();
+            for (library, asset) in deps_assets(deps, &rids, &reference.name, culture) {
+                let local = match &asset.locale {
+                    Some(locale) => app_dir.join(locale).join(asset.file_name()),
+                    None => app_dir.join(asset.file_name()),
+                };
+                let mut candidates = vec![local, app_dir.join(&asset.path)];
+                if deps
+                    .library(&library.name, &library.version)
+                    .is_none_or(|info| info.is_package())
+                {
+                    let package = deps
+                        .library(&library.name, &library.version)
+                        .and_then(|info| info.path.clone())
+                        .unwrap_or_else(|| {
+                            format!("{}/{}", library.name, library.version).to_lowercase()
+                        });
+                    for dir in &self.probe_paths {
+                        candidates.push(dir.join(&package).join(&asset.path));
+                    }
+                }
+                for candidate in candidates {
+                    if probe.try_path(candidate, ProbeSource::DepsJson) {
+                        return probe.resolution;
+                    }
+                }
+            }
+        }
+
+        let framework: Vec<_> = self
+            .framework_dirs
+            .iter()
+            .map(|dir| (dir.clone(), ProbeSource::Framework))
+            .collect();
+        if probe.try_dirs(&framework) {
+            return probe.resolution;
+        }
+
+        if let Some(app_base) = &self.app_base {
+            let mut dirs = vec![(app_base.clone(), ProbeSource::AppBase)];
+            dirs.extend(
+                self.private_bin_paths
+                    .iter()
+                    .map(|dir| (app_base.join(dir), ProbeSource::PrivateBinPath)),
+            );
+            if probe.try_dirs(&dirs) {
+                return probe.resolution;
+            }
+        }
+
+        let search: Vec<_> = self
+            .search_paths
+            .iter()
+            .map(|dir| (dir.clone(), ProbeSource::SearchPath))
+            .collect();
+        if probe.try_dirs(&search) {
+            return probe.resolution;
+        }
+        probe.resolution
+    }
+}
+
+/// State of one [`AssemblyResolver::resolve`] call.
+struct Probe<'a> {
+    resolver: &'a AssemblyResolver,
+    reference: &'a AssemblyIdentity,
+    seen: HashSet<PathBuf>,
+    resolution: Resolution,
+}
+
+impl Probe<'_> {
+    /// Probes the fusion-style candidates below `dirs`. Like Fusion, every
+    /// `.dll` candidate is tried before any `.exe`.
+    fn try_dirs(&mut self, dirs: &[(PathBuf, ProbeSource)]) -> bool {
+        let reference = self.reference;
+        let name = &reference.name;
+        let culture = reference.culture.as_deref().filter(|c| !c.is_empty());
+        for extension in ["dll", "exe"] {
+            let file = format!("{name}.{extension}");
+            for (dir, source) in dirs {
+                let dir = match culture {
+                    Some(culture) => dir.join(culture),
+                    None => dir.clone(),
+                };
+                if self.try_path(dir.join(&file), *source)
+                    || self.try_path(dir.join(name).join(&file), *source)
+                {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Probes one file and records the attempt. Returns `true` if the file
+    /// was accepted.
+    fn try_path(&mut self, path: PathBuf, source: ProbeSource) -> bool {
+        if !self.seen.insert(path.clone()) {
+            return false;
+        }
+        let outcome = if !path.is_file() {
+            ProbeOutcome::NotFound
+        } else {
+            match self.check(&path) {
+                Ok(identity) => {
+                    self.resolution.path = Some(path.clone());
+                    self.resolution.identity = Some(identity);
+                    ProbeOutcome::Accepted
+                }
+                Err(reason) => ProbeOutcome::Rejected(reason),
+            }
+        };
+        let accepted = outcome == ProbeOutcome::Accepted;
+        self.resolution.trace.push(ProbeAttempt {
+            path,
+            source,
+            outcome,
+        });
+        accepted
+    }
+
+    /// Reads the identity of a candidate and checks it against the
+    /// reference.
+    fn check(&self, path: &Path) -> std::result::Result<AssemblyIdentity, String> {
+        let identity = PeImage::open(path)
+            .and_then(|image| image.metadata()?.assembly_identity())
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "file has no assembly manifest".to_string())?;
+        let reference = self.reference;
+
+        let partial = AssemblyIdentity {
+            name: reference.name.clone(),
+            culture: Some(reference.culture.clone().unwrap_or_default()),
+            public_key: reference.public_key.clone(),
+            ..Default::default()
+        };
+        if !partial.is_equal(&identity, AssemblyCompareFlags::DEFAULT) {
+            return Err(format!("identity {identity} does not match {reference}"));
+        }
+
+        if let (Some(requested), Some(found)) = (reference.version, identity.version) {
+            let satisfied = match self.resolver.version_policy {
+                VersionPolicy::Any => true,
+                VersionPolicy::AtLeast => found.parts() >= requested.parts(),
+                VersionPolicy::Exact => found.parts() == requested.parts(),
+            };
+            if !satisfied {
+                return Err(format!("version {found} does not satisfy {requested}"));
+            }
+        }
+        Ok(identity)
+    }
+}
+
+/// Returns the managed assets named `name` in the runtime target for the
+/// RID chain `rids`, or the resource assets for `culture` when it is not
+/// neutral.
+fn deps_assets<'d>(
+    deps: &'d DepsFile,
+    rids: &[String],
+    name: &str,
+    culture: &str,
+) -> Vec<(&'d DepsTargetLibrary, &'d DepsAsset)> {
+    let Some(target) = deps.runtime_target() else {
+        return Vec::new();
+    };
+    let matches = |asset: &DepsAsset| {
+        let file = asset.file_name();
+        let stem = file.rsplit_once('.').map_or(file, |(stem, _)| stem);
+        stem.eq_ignore_ascii_case(name)
+    };
+    let mut assets = Vec::new();
+    for library in &target.libraries {
+        if culture.is_empty() {
+            assets.extend(
+                library
+                    .assets_for_rid(DepsAssetType::Runtime, rids)
+                    .into_iter()
+                    .filter(|asset| matches(asset))
+                    .map(|asset| (library, asset)),
+            );
+        } else {
+            assets.extend(
+                library
+                    .resources
+                    .iter()
+                    .filter(|asset| {
+                        matches(asset)
+                            && asset
+                                .locale
+                                .as_deref()
+                                .is_some_and(|locale| locale.eq_ignore_ascii_case(culture))
+                    })
+                    .map(|asset| (library, asset)),
+            );
+        }
+    }
+    assets
+}
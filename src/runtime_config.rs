@@ -0,0 +1,539 @@
+//! `*.runtimeconfig.json` files and shared framework selection.
+//!
+//! The .NET Core counterpart of `ICLRMetaHost::EnumerateInstalledRuntimes`:
+//! [`installed_frameworks`] lists the shared frameworks below a `dotnet`
+//! root and [`RuntimeConfig::select_framework`] picks the version the host
+//! (`hostfxr`) would load for a framework reference under its roll-forward
+//! policy.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use crate::deps_json::{object, string};
+use crate::error::{Error, Result};
+
+/// Roll-forward policy of a framework reference (`rollForward`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RollForward {
+    /// Only the exact requested version is accepted.
+    Disable,
+    /// Roll forward to the latest patch of the requested major.minor.
+    LatestPatch,
+    /// Roll forward to the lowest higher minor version if the requested
+    /// minor version is missing.
+    #[default]
+    Minor,
+    /// Roll forward to the highest minor version of the requested major.
+    LatestMinor,
+    /// Roll forward to the lowest higher major version if the requested
+    /// major version is missing.
+    Major,
+    /// Roll forward to the highest installed version.
+    LatestMajor,
+}
+
+impl RollForward {
+    /// All policies, from the most to the least restrictive.
+    pub const ALL: [RollForward; 6] = [
+        RollForward::Disable,
+        RollForward::LatestPatch,
+        RollForward::Minor,
+        RollForward::LatestMinor,
+        RollForward::Major,
+        RollForward::LatestMajor,
+    ];
+
+    /// Returns the name used in `runtimeconfig.json` and `DOTNET_ROLL_FORWARD`.
+    pub fn name(self) -> &'static str {
+        match self {
+            RollForward::Disable => "Disable",
+            RollForward::LatestPatch => "LatestPatch",
+            RollForward::Minor => "Minor",
+            RollForward::LatestMinor => "LatestMinor",
+            RollForward::Major => "Major",
+            RollForward::LatestMajor => "LatestMajor",
+        }
+    }
+
+    /// Maps the legacy `rollForwardOnNoCandidateFx` setting.
+    pub fn from_roll_forward_on_no_candidate_fx(value: u64) -> Option<RollForward> {
+        match value {
+            0 => Some(RollForward::LatestPatch),
+            1 => Some(RollForward::Minor),
+            2 => Some(RollForward::Major),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for RollForward {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<RollForward> {
+        RollForward::ALL
+            .into_iter()
+            .find(|policy| policy.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| Error::BadConfig(format!("unknown roll forward policy {s:?}")))
+    }
+}
+
+/// A shared framework version: `major.minor.patch[-prerelease][+build]`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct FrameworkVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    /// The pre-release label without the `-`, empty for releases.
+    pub prerelease: String,
+    /// The build metadata without the `+`, ignored when comparing.
+    pub build: String,
+}
+
+impl FrameworkVersion {
+    /// Creates a release version.
+    pub fn new(major: u32, minor: u32, patch: u32) -> Self {
+        FrameworkVersion {
+            major,
+            minor,
+            patch,
+            ..Default::default()
+        }
+    }
+
+    /// Returns `true` for pre-release versions such as `9.0.0-rc.1.24431.7`.
+    pub fn is_prerelease(&self) -> bool {
+        !self.prerelease.is_empty()
+    }
+}
+
+impl fmt::Display for FrameworkVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if self.is_prerelease() {
+            write!(f, "-{}", self.prerelease)?;
+        }
+        if !self.build.is_empty() {
+            write!(f, "+{}", self.build)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for FrameworkVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<FrameworkVersion> {
+        let invalid = || Error::BadConfig(format!("invalid framework version {s:?}"));
+        let (rest, build) = s.split_once('+').unwrap_or((s, ""));
+        let (core, prerelease) = rest.split_once('-').unwrap_or((rest, ""));
+        let parts = core
+            .split('.')
+            .map(|part| part.parse::<u32>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>>>()?;
+        let [major, minor, patch] = parts[..] else {
+            return Err(invalid());
+        };
+        if s.ends_with('-') || s.ends_with('+') {
+            return Err(invalid());
+        }
+        Ok(FrameworkVersion {
+            major,
+            minor,
+            patch,
+            prerelease: prerelease.to_string(),
+            build: build.to_string(),
+        })
+    }
+}
+
+impl Ord for FrameworkVersion {
+    /// Orders by SemVer precedence; build metadata only breaks ties so the
+    /// order stays consistent with `Eq`.
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.is_prerelease(), other.is_prerelease()) {
+                (false, false) => Ordering::Equal,
+                (false, true) => Ordering::Greater,
+                (true, false) => Ordering::Less,
+                (true, true) => compare_prerelease(&self.prerelease, &other.prerelease),
+            })
+            .then_with(|| self.build.cmp(&other.build))
+    }
+}
+
+impl PartialOrd for FrameworkVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Compares pre-release labels identifier by identifier: numeric ones
+/// numerically and below alphanumeric ones.
+fn compare_prerelease(a: &str, b: &str) -> Ordering {
+    let mut left = a.split('.');
+    let mut right = b.split('.');
+    loop {
+        match (left.next(), right.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => {
+                let order = match (x.parse::<u64>(), y.parse::<u64>()) {
+                    (Ok(x), Ok(y)) => x.cmp(&y),
+                    (Ok(_), Err(_)) => Ordering::Less,
+                    (Err(_), Ok(_)) => Ordering::Greater,
+                    (Err(_), Err(_)) => x.cmp(y),
+                };
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+        }
+    }
+}
+
+/// Selects the version `hostfxr` would use for a framework reference to
+/// `requested` among the `installed` versions.
+///
+/// Release references roll forward to pre-release versions only when no
+/// release version matches. With `apply_patches` unset the lowest matching
+/// patch is chosen instead of the latest.
+///
+/// ```
+/// use mscoree::{FrameworkVersion, RollForward, select_framework_version};
+///
+/// let installed: Vec<FrameworkVersion> = ["6.0.2", "6.0.25", "7.0.14", "8.0.0", "8.0.11"]
+///     .iter()
+///     .map(|v| v.parse().unwrap())
+///     .collect();
+/// let requested = FrameworkVersion::new(6, 0, 0);
+/// let select = |policy| {
+///     select_framework_version(&requested, policy, true, &installed).map(|v| v.to_string())
+/// };
+/// assert_eq!(select(RollForward::Disable), None);
+/// assert_eq!(select(RollForward::Minor).as_deref(), Some("6.0.25"));
+/// assert_eq!(select(RollForward::Major).as_deref(), Some("6.0.25"));
+/// assert_eq!(select(RollForward::LatestMajor).as_deref(), Some("8.0.11"));
+///
+/// let requested = FrameworkVersion::new(6, 1, 0);
+/// let select = |policy| {
+///     select_framework_version(&requested, policy, true, &installed).map(|v| v.to_string())
+/// };
+/// assert_eq!(select(RollForward::Minor), None);
+/// assert_eq!(select(RollForward::Major).as_deref(), Some("7.0.14"));
+///
+/// let installed: Vec<FrameworkVersion> = ["9.0.0-rc.2"].iter().map(|v| v.parse().unwrap()).collect();
+/// let requested = FrameworkVersion::new(8, 0, 0);
+/// let select = |policy| {
+///     select_framework_version(&requested, policy, true, &installed).map(|v| v.to_string())
+/// };
+/// assert_eq!(select(RollForward::Minor), None);
+/// assert_eq!(select(RollForward::Major).as_deref(), Some("9.0.0-rc.2"));
+/// ```
+pub fn select_framework_version<'a>(
+    requested: &FrameworkVersion,
+    roll_forward: RollForward,
+    apply_patches: bool,
+    installed: impl IntoIterator<Item = &'a FrameworkVersion>,
+) -> Option<&'a FrameworkVersion> {
+    let installed: Vec<&FrameworkVersion> =
+        installed.into_iter().filter(|v| *v >= requested).collect();
+    if requested.is_prerelease() {
+        return select_among(requested, roll_forward, apply_patches, installed);
+    }
+    let releases = installed
+        .iter()
+        .copied()
+        .filter(|v| !v.is_prerelease())
+        .collect();
+    select_among(requested, roll_forward, apply_patches, releases)
+        .or_else(|| select_among(requested, roll_forward, apply_patches, installed))
+}
+
+/// Applies the roll-forward policy to the installed versions not below
+/// `requested`.
+fn select_among<'a>(
+    requested: &FrameworkVersion,
+    roll_forward: RollForward,
+    apply_patches: bool,
+    candidates: Vec<&'a FrameworkVersion>,
+) -> Option<&'a FrameworkVersion> {
+    // The patch to use once a major.minor has been chosen.
+    let patch_of = |major: u32, minor: u32| {
+        let same = candidates
+            .iter()
+            .copied()
+            .filter(|v| v.major == major && v.minor == minor);
+        if apply_patches {
+            same.max()
+        } else {
+            same.min()
+        }
+    };
+    let same_minor = || patch_of(requested.major, requested.minor);
+    let higher_minor = || {
+        candidates
+            .iter()
+            .filter(|v| v.major == requested.major && v.minor > requested.minor)
+            .min()
+            .and_then(|v| patch_of(v.major, v.minor))
+    };
+    let higher_major = || {
+        candidates
+            .iter()
+            .filter(|v| v.major > requested.major)
+            .min()
+            .and_then(|v| patch_of(v.major, v.minor))
+    };
+
+    match roll_forward {
+        RollForward::Disable => candidates.iter().copied().find(|v| *v == requested),
+        RollForward::LatestPatch => same_minor(),
+        RollForward::Minor => same_minor().or_else(higher_minor),
+        RollForward::LatestMinor => candidates
+            .iter()
+            .filter(|v| v.major == requested.major)
+            .max()
+            .and_then(|v| patch_of(v.major, v.minor)),
+        RollForward::Major => same_minor().or_else(higher_minor).or_else(higher_major),
+        RollForward::LatestMajor => candidates
+            .iter()
+            .max()
+            .and_then(|v| patch_of(v.major, v.minor)),
+    }
+}
+
+/// A shared framework installed below a `dotnet` root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledFramework {
+    /// The framework name, e.g. `Microsoft.NETCore.App`.
+    pub name: String,
+    pub version: FrameworkVersion,
+    /// The framework directory, e.g. `shared/Microsoft.NETCore.App/8.0.11`.
+    pub path: PathBuf,
+}
+
+/// Lists the shared frameworks in `dotnet_root/shared`, sorted by name and
+/// version. Directories whose names are not versions are skipped.
+pub fn installed_frameworks(dotnet_root: impl AsRef<Path>) -> Result<Vec<InstalledFramework>> {
+    let shared = dotnet_root.as_ref().join("shared");
+    let mut frameworks = Vec::new();
+    if !shared.is_dir() {
+        return Ok(frameworks);
+    }
+    for framework in std::fs::read_dir(&shared)? {
+        let framework = framework?;
+        if !framework.file_type()?.is_dir() {
+            continue;
+        }
+        let name = framework.file_name().to_string_lossy().into_owned();
+        for version in std::fs::read_dir(framework.path())? {
+            let version = version?;
+            if !version.file_type()?.is_dir() {
+                continue;
+            }
+            if let Ok(parsed) = version.file_name().to_string_lossy().parse() {
+                frameworks.push(InstalledFramework {
+                    name: name.clone(),
+                    version: parsed,
+                    path: version.path(),
+                });
+            }
+        }
+    }
+    frameworks.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.version.cmp(&b.version)));
+    Ok(frameworks)
+}
+
+/// A framework reference from `framework`, `frameworks` or
+/// `includedFrameworks`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameworkReference {
+    /// The framework name, e.g. `Microsoft.AspNetCore.App`.
+    pub name: String,
+    pub version: FrameworkVersion,
+    /// A policy overriding the application-wide one.
+    pub roll_forward: Option<RollForward>,
+    /// An `applyPatches` setting overriding the application-wide one.
+    pub apply_patches: Option<bool>,
+}
+
+/// A parsed `*.runtimeconfig.json` file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuntimeConfig {
+    /// The target framework moniker, e.g. `net8.0`.
+    pub tfm: Option<String>,
+    /// The shared frameworks of a framework-dependent app.
+    pub frameworks: Vec<FrameworkReference>,
+    /// The frameworks bundled with a self-contained app.
+    pub included_frameworks: Vec<FrameworkReference>,
+    /// The application-wide roll-forward policy.
+    pub roll_forward: Option<RollForward>,
+    /// The application-wide `applyPatches` setting.
+    pub apply_patches: Option<bool>,
+    /// Runtime properties, with non-string values in their JSON form.
+    pub config_properties: BTreeMap<String, String>,
+    /// Extra package probing directories.
+    pub additional_probing_paths: Vec<String>,
+}
+
+impl RuntimeConfig {
+    /// Reads and parses the runtime config at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<RuntimeConfig> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses runtime config JSON.
+    ///
+    /// ```
+    /// use mscoree::{RollForward, RuntimeConfig};
+    ///
+    /// let config = RuntimeConfig::parse(r#"{
+    ///     "runtimeOptions": {
+    ///         "tfm": "net8.0",
+    ///         "rollForward": "LatestMinor",
+    ///         "frameworks": [
+    ///             { "name": "Microsoft.NETCore.App", "version": "8.0.0" },
+    ///             { "name": "Microsoft.AspNetCore.App", "version": "8.0.0", "rollForward": "Disable" }
+    ///         ],
+    ///         "configProperties": { "System.GC.Server": true }
+    ///     }
+    /// }"#).unwrap();
+    /// assert_eq!(config.roll_forward_for(&config.frameworks[0]), RollForward::LatestMinor);
+    /// assert_eq!(config.roll_forward_for(&config.frameworks[1]), RollForward::Disable);
+    /// assert_eq!(config.config_properties["System.GC.Server"], "true");
+    /// ```
+    pub fn parse(json: &str) -> Result<RuntimeConfig> {
+        let root: Value = serde_json::from_str(json)
+            .map_err(|e| Error::BadConfig(format!("invalid runtimeconfig.json: {e}")))?;
+        let mut config = RuntimeConfig::default();
+        let Some(options) = object(&root, "runtimeconfig.json")?.get("runtimeOptions") else {
+            return Ok(config);
+        };
+        let options = object(options, "runtimeOptions")?;
+
+        config.tfm = string(options, "tfm");
+        let (roll_forward, apply_patches) = parse_roll_forward(options)?;
+        config.roll_forward = roll_forward;
+        config.apply_patches = apply_patches;
+
+        if let Some(framework) = options.get("framework") {
+            config.frameworks.push(parse_framework(framework)?);
+        }
+        for (key, list) in [
+            ("frameworks", &mut config.frameworks),
+            ("includedFrameworks", &mut config.included_frameworks),
+        ] {
+            if let Some(frameworks) = options.get(key) {
+                let frameworks = frameworks
+                    .as_array()
+                    .ok_or_else(|| Error::BadConfig(format!("{key} is not a JSON array")))?;
+                for framework in frameworks {
+                    list.push(parse_framework(framework)?);
+                }
+            }
+        }
+
+        if let Some(properties) = options.get("configProperties") {
+            for (name, value) in object(properties, "configProperties")? {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                config.config_properties.insert(name.clone(), value);
+            }
+        }
+        if let Some(Value::Array(paths)) = options.get("additionalProbingPaths") {
+            config.additional_probing_paths = paths
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect();
+        }
+        Ok(config)
+    }
+
+    /// Returns `true` for self-contained apps, which carry their frameworks.
+    pub fn is_self_contained(&self) -> bool {
+        self.frameworks.is_empty() && !self.included_frameworks.is_empty()
+    }
+
+    /// Returns the effective roll-forward policy of `framework`.
+    pub fn roll_forward_for(&self, framework: &FrameworkReference) -> RollForward {
+        framework
+            .roll_forward
+            .or(self.roll_forward)
+            .unwrap_or_default()
+    }
+
+    /// Returns the effective `applyPatches` setting of `framework`.
+    pub fn apply_patches_for(&self, framework: &FrameworkReference) -> bool {
+        framework
+            .apply_patches
+            .or(self.apply_patches)
+            .unwrap_or(true)
+    }
+
+    /// Selects the installed framework the host would use for `framework`.
+    pub fn select_framework<'a>(
+        &self,
+        framework: &FrameworkReference,
+        installed: &'a [InstalledFramework],
+    ) -> Option<&'a InstalledFramework> {
+        let same_name = || {
+            installed
+                .iter()
+                .filter(|candidate| candidate.name.eq_ignore_ascii_case(&framework.name))
+        };
+        let version = select_framework_version(
+            &framework.version,
+            self.roll_forward_for(framework),
+            self.apply_patches_for(framework),
+            same_name().map(|candidate| &candidate.version),
+        )?;
+        same_name().find(|candidate| candidate.version == *version)
+    }
+}
+
+fn parse_framework(value: &Value) -> Result<FrameworkReference> {
+    let framework = object(value, "framework")?;
+    let name = string(framework, "name")
+        .ok_or_else(|| Error::BadConfig("framework reference has no name".into()))?;
+    let version = string(framework, "version")
+        .ok_or_else(|| Error::BadConfig(format!("framework {name} has no version")))?
+        .parse()?;
+    let (roll_forward, apply_patches) = parse_roll_forward(framework)?;
+    Ok(FrameworkReference {
+        name,
+        version,
+        roll_forward,
+        apply_patches,
+    })
+}
+
+/// Reads `rollForward`, the legacy `rollForwardOnNoCandidateFx` and
+/// `applyPatches`.
+fn parse_roll_forward(
+    section: &serde_json::Map<String, Value>,
+) -> Result<(Option<RollForward>, Option<bool>)> {
+    let apply_patches = section.get("applyPatches").and_then(Value::as_bool);
+    let roll_forward = match string(section, "rollForward") {
+        Some(policy) => Some(policy.parse()?),
+        None => section
+            .get("rollForwardOnNoCandidateFx")
+            .and_then(Value::as_u64)
+            .map(|value| {
+                RollForward::from_roll_forward_on_no_candidate_fx(value).ok_or_else(|| {
+                    Error::BadConfig(format!("invalid rollForwardOnNoCandidateFx {value}"))
+                })
+            })
+            .transpose()?,
+    };
+    Ok((roll_forward, apply_patches))
+}
@@ -0,0 +1,19 @@
+//! Pure-Rust strong-name support.
+//!
+//! Cross-platform counterparts of the `ICLRStrongName` token, verification
+//! and signing functions, and `.snk` key file parsing.
+
+mod hash;
+mod key;
+mod sign;
+mod snk;
+mod token;
+mod verify;
+
+pub use key::*;
+pub use sign::*;
+pub use snk::*;
+pub use token::*;
+pub use verify::*;
+
+pub(crate) use token::decode_hex;
@@ -0,0 +1,82 @@
+//! Strong-name image hashing (ECMA-335 II.6.2.1.3).
+
+use std::ops::Range;
+
+use super::key::StrongNameHashAlgorithm;
+use crate::error::{Error, Result};
+use crate::reader::{IMAGE_DIRECTORY_ENTRY_SECURITY, PeImage};
+
+/// Size of an `IMAGE_SECTION_HEADER`.
+const SECTION_HEADER_SIZE: usize = 40;
+
+impl PeImage {
+    /// Returns the file range of the strong-name signature blob, if the CLI
+    /// header reserves one.
+    pub fn strong_name_signature_range(&self) -> Option<Range<usize>> {
+        let dir = self.cor_header()?.strong_name_signature;
+        if dir.is_empty() {
+            return None;
+        }
+        let start = self.rva_to_offset(dir.virtual_address)?;
+        let end = start.checked_add(dir.size as usize)?;
+        (end <= self.data().len()).then_some(start..end)
+    }
+
+    /// Returns the strong-name signature blob, if the CLI header reserves one.
+    pub fn strong_name_signature(&self) -> Option<&[u8]> {
+        self.strong_name_signature_range()
+            .map(|range| &self.data()[range])
+    }
+
+    /// Returns the bytes covered by the strong-name signature.
+    ///
+    /// These are the DOS and NT headers with the checksum and the certificate
+    /// table directory zeroed, the section table and the raw data of every
+    /// section except the signature blob itself. Header padding and data
+    /// outside sections (such as Authenticode certificates) are not covered.
+    pub fn strong_name_signed_content(&self) -> Result<Vec<u8>> {
+        let data = self.data();
+        let truncated = || Error::BadImageFormat("image is truncated".into());
+
+        let mut headers = data
+            .get(..self.section_headers_offset())
+            .ok_or_else(truncated)?
+            .to_vec();
+        let checksum = self.checksum_offset();
+        headers
+            .get_mut(checksum..checksum + 4)
+            .ok_or_else(truncated)?
+            .fill(0);
+        if self.data_directories().len() > IMAGE_DIRECTORY_ENTRY_SECURITY {
+            let security = self.data_directory_offset(IMAGE_DIRECTORY_ENTRY_SECURITY);
+            headers
+                .get_mut(security..security + 8)
+                .ok_or_else(truncated)?
+                .fill(0);
+        }
+
+        let table_start = self.section_headers_offset();
+        let table_end = table_start + self.sections().len() * SECTION_HEADER_SIZE;
+        let mut content = headers;
+        content.extend_from_slice(data.get(table_start..table_end).ok_or_else(truncated)?);
+
+        let signature = self.strong_name_signature_range().unwrap_or(0..0);
+        for section in self.sections() {
+            let start = section.pointer_to_raw_data as usize;
+            let end = start + section.size_of_raw_data as usize;
+            let raw = data.get(start..end).ok_or_else(truncated)?;
+            // Exclude the part of the signature blob that falls in this section.
+            let skip_start = signature.start.clamp(start, end) - start;
+            let skip_end = signature.end.clamp(start, end) - start;
+            content.extend_from_slice(&raw[..skip_start]);
+            content.extend_from_slice(&raw[skip_end..]);
+        }
+        Ok(content)
+    }
+
+    /// Computes the strong-name hash of the image.
+    pub fn strong_name_hash(&self, algorithm: StrongNameHashAlgorithm) -> Result<Vec<u8>> {
+        let content = self.strong_name_signed_content()?;
+        Ok(algorithm.digest([content.as_slice()]))
+    }
+}
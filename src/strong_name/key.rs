@@ -0,0 +1,223 @@
+//! Strong-name public keys in `PublicKeyBlob` format.
+
+use rsa::{BigUint, Pkcs1v15Sign, RsaPublicKey};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::error::{Error, Result};
+
+/// The ECMA "neutral" public key used by framework assemblies such as
+/// `mscorlib`. It is a placeholder rather than a real RSA key; the runtime
+/// substitutes the platform key when verifying signatures made with it.
+pub const ECMA_PUBLIC_KEY: [u8; 16] = [0, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0];
+
+/// `CALG_RSA_SIGN`.
+pub const CALG_RSA_SIGN: u32 = 0x2400;
+/// `CALG_RSA_KEYX`.
+pub const CALG_RSA_KEYX: u32 = 0xA400;
+/// `CALG_SHA1`.
+pub const CALG_SHA1: u32 = 0x8004;
+/// `CALG_SHA_256`.
+pub const CALG_SHA_256: u32 = 0x800C;
+/// `CALG_SHA_384`.
+pub const CALG_SHA_384: u32 = 0x800D;
+/// `CALG_SHA_512`.
+pub const CALG_SHA_512: u32 = 0x800E;
+
+const ALG_CLASS_MASK: u32 = 0xE000;
+const ALG_CLASS_SIGNATURE: u32 = 0x2000;
+const ALG_CLASS_HASH: u32 = 0x8000;
+
+/// `PUBLICKEYBLOB` blob type of a CryptoAPI key blob.
+pub(crate) const PUBLICKEYBLOB: u8 = 0x06;
+/// Size of the `PublicKeyBlob` header (SigAlgID, HashAlgID, cbPublicKey).
+pub(crate) const PUBLIC_KEY_BLOB_HEADER: usize = 12;
+/// Size of a CryptoAPI `BLOBHEADER`.
+pub(crate) const BLOBHEADER_SIZE: usize = 8;
+/// Size of a CryptoAPI `RSAPUBKEY`.
+pub(crate) const RSAPUBKEY_SIZE: usize = 12;
+/// `RSAPUBKEY.magic` of a public key (`RSA1`).
+pub(crate) const RSA1_MAGIC: u32 = 0x3141_5352;
+/// Largest RSA modulus accepted for strong-name keys, in bits.
+pub(crate) const MAX_KEY_BITS: usize = 16384;
+
+/// Hash algorithm of a strong-name signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StrongNameHashAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl StrongNameHashAlgorithm {
+    /// Maps a CryptoAPI `ALG_ID` to a hash algorithm. 0 selects the
+    /// default, SHA-1.
+    pub fn from_alg_id(alg_id: u32) -> Option<StrongNameHashAlgorithm> {
+        match alg_id {
+            0 | CALG_SHA1 => Some(StrongNameHashAlgorithm::Sha1),
+            CALG_SHA_256 => Some(StrongNameHashAlgorithm::Sha256),
+            CALG_SHA_384 => Some(StrongNameHashAlgorithm::Sha384),
+            CALG_SHA_512 => Some(StrongNameHashAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    /// Returns the CryptoAPI `ALG_ID` of the algorithm.
+    pub fn alg_id(self) -> u32 {
+        match self {
+            StrongNameHashAlgorithm::Sha1 => CALG_SHA1,
+            StrongNameHashAlgorithm::Sha256 => CALG_SHA_256,
+            StrongNameHashAlgorithm::Sha384 => CALG_SHA_384,
+            StrongNameHashAlgorithm::Sha512 => CALG_SHA_512,
+        }
+    }
+
+    /// Hashes the concatenation of `parts`.
+    pub fn digest<'a>(self, parts: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+        fn run<'p, D: Digest>(parts: impl IntoIterator<Item = &'p [u8]>) -> Vec<u8> {
+            let mut hasher = D::new();
+            for part in parts {
+                hasher.update(part);
+            }
+            hasher.finalize().to_vec()
+        }
+        match self {
+            StrongNameHashAlgorithm::Sha1 => run::<Sha1>(parts),
+            StrongNameHashAlgorithm::Sha256 => run::<Sha256>(parts),
+            StrongNameHashAlgorithm::Sha384 => run::<Sha384>(parts),
+            StrongNameHashAlgorithm::Sha512 => run::<Sha512>(parts),
+        }
+    }
+
+    /// Returns the PKCS#1 v1.5 padding scheme for the algorithm.
+    pub(crate) fn pkcs1v15(self) -> Pkcs1v15Sign {
+        match self {
+            StrongNameHashAlgorithm::Sha1 => Pkcs1v15Sign::new::<Sha1>(),
+            StrongNameHashAlgorithm::Sha256 => Pkcs1v15Sign::new::<Sha256>(),
+            StrongNameHashAlgorithm::Sha384 => Pkcs1v15Sign::new::<Sha384>(),
+            StrongNameHashAlgorithm::Sha512 => Pkcs1v15Sign::new::<Sha512>(),
+        }
+    }
+}
+
+/// A strong-name public key in `PublicKeyBlob` format, as stored in the
+/// `Assembly` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicKeyBlob<'a> {
+    /// Signature algorithm (`CALG_RSA_SIGN`, or 0 for the default).
+    pub sig_alg_id: u32,
+    /// Hash algorithm (`CALG_SHA1` and friends, or 0 for the default).
+    pub hash_alg_id: u32,
+    /// The CryptoAPI `PUBLICKEYBLOB` (`BLOBHEADER` + `RSAPUBKEY` + modulus).
+    pub public_key: &'a [u8],
+}
+
+impl<'a> PublicKeyBlob<'a> {
+    /// Parses and validates a `PublicKeyBlob`, the way
+    /// `StrongNameTokenFromPublicKey` does before hashing it.
+    ///
+    /// The ECMA neutral key is accepted even though it holds no RSA key.
+    pub fn parse(data: &'a [u8]) -> Result<PublicKeyBlob<'a>> {
+        if data.len() < PUBLIC_KEY_BLOB_HEADER {
+            return Err(Error::BadStrongName("public key blob is truncated".into()));
+        }
+        let read = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        let blob = PublicKeyBlob {
+            sig_alg_id: read(0),
+            hash_alg_id: read(4),
+            public_key: &data[PUBLIC_KEY_BLOB_HEADER..],
+        };
+        if read(8) as usize != blob.public_key.len() {
+            return Err(Error::BadStrongName(
+                "public key size does not match the blob length".into(),
+            ));
+        }
+        if is_ecma_public_key(data) {
+            return Ok(blob);
+        }
+        if blob.sig_alg_id != 0 && blob.sig_alg_id & ALG_CLASS_MASK != ALG_CLASS_SIGNATURE {
+            return Err(Error::BadStrongName(format!(
+                "unsupported signature algorithm {:#x}",
+                blob.sig_alg_id
+            )));
+        }
+        if blob.hash_alg_id != 0 && blob.hash_alg_id & ALG_CLASS_MASK != ALG_CLASS_HASH {
+            return Err(Error::BadStrongName(format!(
+                "unsupported hash algorithm {:#x}",
+                blob.hash_alg_id
+            )));
+        }
+        if blob.public_key.len() < BLOBHEADER_SIZE || blob.public_key[0] != PUBLICKEYBLOB {
+            return Err(Error::BadStrongName(
+                "public key is not a CryptoAPI PUBLICKEYBLOB".into(),
+            ));
+        }
+        Ok(blob)
+    }
+
+    /// Returns `true` if this is the ECMA neutral key.
+    pub fn is_ecma_key(&self) -> bool {
+        self.sig_alg_id == 0 && self.hash_alg_id == 0 && self.public_key == [0; 4]
+    }
+
+    /// Returns the hash algorithm signatures made with this key use.
+    pub fn hash_algorithm(&self) -> Result<StrongNameHashAlgorithm> {
+        StrongNameHashAlgorithm::from_alg_id(self.hash_alg_id).ok_or_else(|| {
+            Error::BadStrongName(format!(
+                "unsupported hash algorithm {:#x}",
+                self.hash_alg_id
+            ))
+        })
+    }
+
+    /// Returns the size in bytes of the RSA modulus, which is also the size
+    /// of signatures made with this key.
+    pub fn modulus_len(&self) -> Result<usize> {
+        Ok(self.rsa_components()?.0.len())
+    }
+
+    /// Splits the CryptoAPI key into its little-endian modulus and public
+    /// exponent.
+    fn rsa_components(&self) -> Result<(&'a [u8], u32)> {
+        let key = self.public_key;
+        let header = BLOBHEADER_SIZE + RSAPUBKEY_SIZE;
+        if key.len() < header {
+            return Err(Error::BadStrongName("RSA public key is truncated".into()));
+        }
+        let read = |at: usize| u32::from_le_bytes(key[at..at + 4].try_into().unwrap());
+        if read(BLOBHEADER_SIZE) != RSA1_MAGIC {
+            return Err(Error::BadStrongName("public key is not an RSA key".into()));
+        }
+        let bit_len = read(BLOBHEADER_SIZE + 4) as usize;
+        let modulus = key
+            .get(header..header + bit_len.div_ceil(8))
+            .ok_or_else(|| Error::BadStrongName("RSA modulus is truncated".into()))?;
+        Ok((modulus, read(BLOBHEADER_SIZE + 8)))
+    }
+
+    /// Converts the key for use with the `rsa` crate.
+    pub(crate) fn rsa_public_key(&self) -> Result<RsaPublicKey> {
+        let (modulus, exponent) = self.rsa_components()?;
+        RsaPublicKey::new_with_max_size(
+            BigUint::from_bytes_le(modulus),
+            BigUint::from(exponent),
+            MAX_KEY_BITS,
+        )
+        .map_err(|e| Error::BadStrongName(format!("invalid RSA public key: {e}")))
+    }
+
+    /// Verifies a strong-name style signature (little-endian, as stored in
+    /// images) of `hash` made with this key.
+    pub(crate) fn verify_hash(&self, hash: &[u8], signature: &[u8]) -> Result<bool> {
+        let scheme = self.hash_algorithm()?.pkcs1v15();
+        let key = self.rsa_public_key()?;
+        let signature: Vec<u8> = signature.iter().rev().copied().collect();
+        Ok(key.verify(scheme, hash, &signature).is_ok())
+    }
+}
+
+/// Returns `true` if `public_key` is the ECMA neutral key.
+pub fn is_ecma_public_key(public_key: &[u8]) -> bool {
+    public_key == ECMA_PUBLIC_KEY
+}
@@ -0,0 +1,88 @@
+//! Strong-name signing (`StrongNameSignatureGenerationEx`).
+
+use std::path::Path;
+
+use super::key::PublicKeyBlob;
+use super::snk::StrongNameKeyPair;
+use crate::error::{Error, Result};
+use crate::reader::{
+    COMIMAGE_FLAGS_STRONGNAMESIGNED, IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR, PeImage,
+};
+
+/// Offset of `Flags` within the CLI header.
+const COR_HEADER_FLAGS_OFFSET: usize = 16;
+
+impl PeImage {
+    /// Strong-name signs the image with `key` and returns the signed file.
+    ///
+    /// The image must already reserve a signature directory of the right
+    /// size, as compilers do when signing or delay-signing. This fills the
+    /// signature, sets `COMIMAGE_FLAGS_STRONGNAMESIGNED` in the CLI header
+    /// and updates the PE checksum. Delay-signed assemblies are re-signed the
+    /// same way.
+    ///
+    /// The key must match the assembly's public key or, for enhanced strong
+    /// naming, the key in its `AssemblySignatureKeyAttribute`.
+    pub fn strong_name_sign(&self, key: &StrongNameKeyPair) -> Result<Vec<u8>> {
+        let metadata = self.metadata()?;
+        let identity = metadata
+            .assembly_public_key()?
+            .ok_or_else(|| Error::BadStrongName("assembly does not have a public key".into()))?;
+        let expected = match metadata.assembly_signature_key()? {
+            Some(signature_key) => signature_key.public_key,
+            None => identity.to_vec(),
+        };
+        let public_key = key.public_key_blob();
+        if !PublicKeyBlob::parse(identity)?.is_ecma_key() && expected != public_key {
+            return Err(Error::BadStrongName(
+                "key does not match the assembly's public key".into(),
+            ));
+        }
+
+        let range = self.strong_name_signature_range().ok_or_else(|| {
+            Error::BadStrongName("image has no strong-name signature directory".into())
+        })?;
+        if range.len() != key.signature_len() {
+            return Err(Error::BadStrongName(format!(
+                "signature directory is {} bytes but the key produces {}-byte signatures",
+                range.len(),
+                key.signature_len()
+            )));
+        }
+
+        // The CLI header flags are covered by the signature, so set them first.
+        let cor = self.data_directory(IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR);
+        let flags_offset = self
+            .rva_to_offset(cor.virtual_address)
+            .ok_or_else(|| Error::BadImageFormat("CLI header is outside the image".into()))?
+            + COR_HEADER_FLAGS_OFFSET;
+        let flags = self.cor_header().map_or(0, |cor| cor.flags) | COMIMAGE_FLAGS_STRONGNAMESIGNED;
+        let mut data = self.data().to_vec();
+        data[flags_offset..flags_offset + 4].copy_from_slice(&flags.to_le_bytes());
+
+        let image = PeImage::from_bytes(data)?;
+        let hash = image.strong_name_hash(key.hash_algorithm())?;
+        let signature = key.sign_hash(&hash)?;
+        let checksum_offset = image.checksum_offset();
+        let mut data = image.into_data();
+        data[range].copy_from_slice(&signature);
+
+        let image = PeImage::from_bytes(data)?;
+        let checksum = image.compute_checksum();
+        let mut data = image.into_data();
+        data[checksum_offset..checksum_offset + 4].copy_from_slice(&checksum.to_le_bytes());
+        Ok(data)
+    }
+}
+
+/// Strong-name signs the assembly at `path` in place, like
+/// `StrongNameSignatureGenerationEx`.
+pub fn strong_name_signature_generation(
+    path: impl AsRef<Path>,
+    key: &StrongNameKeyPair,
+) -> Result<()> {
+    let path = path.as_ref();
+    let signed = PeImage::open(path)?.strong_name_sign(key)?;
+    std::fs::write(path, signed)?;
+    Ok(())
+}
@@ -0,0 +1,197 @@
+//! Strong-name key files (`.snk`), as produced by `StrongNameKeyGen`
+//! (`sn -k`) and `StrongNameGetPublicKey` (`sn -p`).
+
+use std::path::Path;
+
+use rsa::{BigUint, RsaPrivateKey};
+
+use super::key::{
+    BLOBHEADER_SIZE, CALG_RSA_SIGN, PUBLIC_KEY_BLOB_HEADER, PUBLICKEYBLOB, PublicKeyBlob,
+    RSA1_MAGIC, RSAPUBKEY_SIZE, StrongNameHashAlgorithm,
+};
+use super::token::PublicKeyToken;
+use crate::error::{Error, Result};
+
+/// `PRIVATEKEYBLOB` blob type of a CryptoAPI key blob.
+const PRIVATEKEYBLOB: u8 = 0x07;
+/// `CUR_BLOB_VERSION`.
+const CUR_BLOB_VERSION: u8 = 0x02;
+/// `RSAPUBKEY.magic` of a private key (`RSA2`).
+const RSA2_MAGIC: u32 = 0x3241_5352;
+
+/// The contents of a `.snk` file.
+#[derive(Debug, Clone)]
+pub enum StrongNameKey {
+    /// A key pair (`sn -k`), which can sign assemblies.
+    KeyPair(Box<StrongNameKeyPair>),
+    /// A public key in `PublicKeyBlob` format (`sn -p`), which can only be
+    /// used to delay-sign.
+    PublicKey(Vec<u8>),
+}
+
+impl StrongNameKey {
+    /// Reads and parses the key file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<StrongNameKey> {
+        Self::parse(&std::fs::read(path)?)
+    }
+
+    /// Parses a key pair (`PRIVATEKEYBLOB`), a `PublicKeyBlob` or a bare
+    /// CryptoAPI `PUBLICKEYBLOB`.
+    pub fn parse(data: &[u8]) -> Result<StrongNameKey> {
+        match data.first() {
+            Some(&PRIVATEKEYBLOB) => Ok(StrongNameKey::KeyPair(Box::new(
+                StrongNameKeyPair::from_snk(data)?,
+            ))),
+            Some(&PUBLICKEYBLOB) => {
+                let blob =
+                    public_key_blob(CALG_RSA_SIGN, StrongNameHashAlgorithm::Sha1.alg_id(), data);
+                PublicKeyBlob::parse(&blob)?.rsa_public_key()?;
+                Ok(StrongNameKey::PublicKey(blob))
+            }
+            _ => {
+                PublicKeyBlob::parse(data)?;
+                Ok(StrongNameKey::PublicKey(data.to_vec()))
+            }
+        }
+    }
+
+    /// Returns the public key in `PublicKeyBlob` format.
+    pub fn public_key_blob(&self) -> Vec<u8> {
+        match self {
+            StrongNameKey::KeyPair(pair) => pair.public_key_blob(),
+            StrongNameKey::PublicKey(blob) => blob.clone(),
+        }
+    }
+
+    /// Returns the key pair, if the file holds the private key.
+    pub fn key_pair(&self) -> Option<&StrongNameKeyPair> {
+        match self {
+            StrongNameKey::KeyPair(pair) => Some(pair),
+            StrongNameKey::PublicKey(_) => None,
+        }
+    }
+}
+
+/// An RSA strong-name key pair.
+#[derive(Debug, Clone)]
+pub struct StrongNameKeyPair {
+    blob: Vec<u8>,
+    key: RsaPrivateKey,
+    hash_algorithm: StrongNameHashAlgorithm,
+}
+
+impl StrongNameKeyPair {
+    /// Parses a CryptoAPI `PRIVATEKEYBLOB`, the format of `sn -k` key files.
+    ///
+    /// The key signs with SHA-1 unless changed with
+    /// [`StrongNameKeyPair::with_hash_algorithm`].
+    pub fn from_snk(data: &[u8]) -> Result<StrongNameKeyPair> {
+        let header = BLOBHEADER_SIZE + RSAPUBKEY_SIZE;
+        if data.len() < header || data[0] != PRIVATEKEYBLOB {
+            return Err(Error::BadStrongName(
+                "key is not a CryptoAPI PRIVATEKEYBLOB".into(),
+            ));
+        }
+        let read = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        if read(BLOBHEADER_SIZE) != RSA2_MAGIC {
+            return Err(Error::BadStrongName("key pair is not an RSA key".into()));
+        }
+        let bit_len = read(BLOBHEADER_SIZE + 4) as usize;
+        let exponent = read(BLOBHEADER_SIZE + 8);
+
+        // modulus, prime1, prime2, exponent1, exponent2, coefficient,
+        // privateExponent; the primes and CRT values are half-size.
+        let full = bit_len.div_ceil(8);
+        let half = bit_len.div_ceil(16);
+        let sizes = [full, half, half, half, half, half, full];
+        let mut parts = Vec::with_capacity(sizes.len());
+        let mut offset = header;
+        for size in sizes {
+            let part = data
+                .get(offset..offset + size)
+                .ok_or_else(|| Error::BadStrongName("key pair is truncated".into()))?;
+            parts.push(BigUint::from_bytes_le(part));
+            offset += size;
+        }
+        let [n, p, q, _, _, _, d] = <[BigUint; 7]>::try_from(parts).unwrap();
+        let key = RsaPrivateKey::from_components(n, BigUint::from(exponent), d, vec![p, q])
+            .map_err(|e| Error::BadStrongName(format!("invalid RSA key pair: {e}")))?;
+        Ok(StrongNameKeyPair {
+            blob: data[..offset].to_vec(),
+            key,
+            hash_algorithm: StrongNameHashAlgorithm::Sha1,
+        })
+    }
+
+    /// Reads and parses the key pair file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<StrongNameKeyPair> {
+        Self::from_snk(&std::fs::read(path)?)
+    }
+
+    /// Selects the hash algorithm recorded in the public key and used for
+    /// signatures, e.g. SHA-256 for enhanced strong naming.
+    pub fn with_hash_algorithm(mut self, algorithm: StrongNameHashAlgorithm) -> Self {
+        self.hash_algorithm = algorithm;
+        self
+    }
+
+    /// Returns the hash algorithm used for signatures.
+    pub fn hash_algorithm(&self) -> StrongNameHashAlgorithm {
+        self.hash_algorithm
+    }
+
+    /// Returns the key pair in `.snk` (`PRIVATEKEYBLOB`) format.
+    pub fn to_snk(&self) -> &[u8] {
+        &self.blob
+    }
+
+    /// Returns the public key in `PublicKeyBlob` format, like
+    /// `StrongNameGetPublicKey`.
+    pub fn public_key_blob(&self) -> Vec<u8> {
+        let header = BLOBHEADER_SIZE + RSAPUBKEY_SIZE;
+        let bit_len = u32::from_le_bytes(self.blob[12..16].try_into().unwrap()) as usize;
+        let mut key = Vec::with_capacity(header + bit_len / 8);
+        key.extend_from_slice(&[PUBLICKEYBLOB, CUR_BLOB_VERSION, 0, 0]);
+        key.extend_from_slice(&CALG_RSA_SIGN.to_le_bytes());
+        key.extend_from_slice(&RSA1_MAGIC.to_le_bytes());
+        key.extend_from_slice(&self.blob[12..header + bit_len.div_ceil(8)]);
+        public_key_blob(CALG_RSA_SIGN, self.hash_algorithm.alg_id(), &key)
+    }
+
+    /// Returns the public key token of the key pair.
+    pub fn public_key_token(&self) -> PublicKeyToken {
+        PublicKeyToken::from_public_key_unchecked(&self.public_key_blob())
+    }
+
+    /// Returns the size in bytes of signatures made with this key.
+    pub fn signature_len(&self) -> usize {
+        rsa::traits::PublicKeyParts::size(&self.key)
+    }
+
+    /// Signs `hash` and returns the signature in the little-endian form
+    /// stored in images and `AssemblySignatureKeyAttribute` values.
+    pub fn sign_hash(&self, hash: &[u8]) -> Result<Vec<u8>> {
+        let mut signature = self
+            .key
+            .sign(self.hash_algorithm.pkcs1v15(), hash)
+            .map_err(|e| Error::BadStrongName(format!("signing failed: {e}")))?;
+        signature.reverse();
+        Ok(signature)
+    }
+
+    /// Hashes `data` with the key's hash algorithm and signs it, e.g. to
+    /// counter-sign a signature public key for enhanced strong naming.
+    pub fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>> {
+        self.sign_hash(&self.hash_algorithm.digest([data]))
+    }
+}
+
+/// Prefixes a CryptoAPI `PUBLICKEYBLOB` with the `PublicKeyBlob` header.
+fn public_key_blob(sig_alg_id: u32, hash_alg_id: u32, key: &[u8]) -> Vec<u8> {
+    let mut blob = Vec::with_capacity(PUBLIC_KEY_BLOB_HEADER + key.len());
+    blob.extend_from_slice(&sig_alg_id.to_le_bytes());
+    blob.extend_from_slice(&hash_alg_id.to_le_bytes());
+    blob.extend_from_slice(&(key.len() as u32).to_le_bytes());
+    blob.extend_from_slice(key);
+    blob
+}
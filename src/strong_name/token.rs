@@ -0,0 +1,145 @@
+//! Strong-name public key tokens (`StrongNameTokenFromPublicKey`,
+//! `StrongNameTokenFromAssembly`).
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use sha1::{Digest, Sha1};
+
+use super::key::PublicKeyBlob;
+use crate::error::{Error, Result};
+use crate::reader::{AssemblyRefRow, AssemblyRow, MetadataReader, PeImage, TableId};
+
+/// `AssemblyFlags.PublicKey`: the `PublicKey`/`PublicKeyOrToken` column holds
+/// a full public key rather than a token.
+pub const AF_PUBLIC_KEY: u32 = 0x0001;
+
+/// An 8-byte strong-name public key token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PublicKeyToken(pub [u8; 8]);
+
+impl PublicKeyToken {
+    /// Computes the token of a `PublicKeyBlob`: the last 8 bytes of its
+    /// SHA-1 hash in reverse order.
+    ///
+    /// ```
+    /// use mscoree::{ECMA_PUBLIC_KEY, PublicKeyToken};
+    ///
+    /// // mscorlib and the other ECMA-keyed framework assemblies.
+    /// let token = PublicKeyToken::from_public_key(&ECMA_PUBLIC_KEY).unwrap();
+    /// assert_eq!(token.to_string(), "b77a5c561934e089");
+    ///
+    /// // The Microsoft key used by e.g. System.Drawing.
+    /// let microsoft = "002400000480000094000000060200000024000052534131000400000100010007d1fa57\
+    ///     c4aed9f0a32e84aa0faefd0de9e8fd6aec8f87fb03766c834c99921eb23be79ad9d5dcc1dd9ad23613\
+    ///     2102900b723cf980957fc4e177108fc607774f29e8320e92ea05ece4e821c0a5efe8f1645c4c0c93c1\
+    ///     ab99285d622caa652c1dfad63d745d6f2de5f17e5eaf0fc4963d261c8a12436518206dc093344d5ad293";
+    /// let key: Vec<u8> = (0..microsoft.len())
+    ///     .step_by(2)
+    ///     .map(|i| u8::from_str_radix(&microsoft[i..i + 2], 16).unwrap())
+    ///     .collect();
+    /// let token = PublicKeyToken::from_public_key(&key).unwrap();
+    /// assert_eq!(token, "b03f5f7f11d50a3a".parse().unwrap());
+    ///
+    /// // Blobs whose size field disagrees with their length are rejected.
+    /// assert!(PublicKeyToken::from_public_key(&key[..100]).is_err());
+    /// ```
+    pub fn from_public_key(public_key: &[u8]) -> Result<PublicKeyToken> {
+        PublicKeyBlob::parse(public_key)?;
+        Ok(Self::from_public_key_unchecked(public_key))
+    }
+
+    /// Computes the token of a public key without validating its format.
+    pub fn from_public_key_unchecked(public_key: &[u8]) -> PublicKeyToken {
+        let hash = Sha1::digest(public_key);
+        let mut token = [0u8; 8];
+        for (dst, src) in token.iter_mut().zip(hash.iter().rev()) {
+            *dst = *src;
+        }
+        PublicKeyToken(token)
+    }
+
+    /// Returns the token bytes.
+    pub fn as_bytes(&self) -> &[u8; 8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for PublicKeyToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PublicKeyToken {
+    type Err = Error;
+
+    /// Parses a 16-digit hexadecimal token such as `b77a5c561934e089`.
+    fn from_str(s: &str) -> Result<PublicKeyToken> {
+        decode_hex(s)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(PublicKeyToken)
+            .ok_or_else(|| Error::BadStrongName(format!("invalid public key token {s:?}")))
+    }
+}
+
+impl MetadataReader {
+    /// Returns the public key blob of the assembly manifest, if it has one.
+    pub fn assembly_public_key(&self) -> Result<Option<&[u8]>> {
+        if self.row_count(TableId::Assembly) == 0 {
+            return Ok(None);
+        }
+        let row = self.row::<AssemblyRow>(1)?;
+        let key = self.blob(row.public_key)?;
+        Ok((!key.is_empty()).then_some(key))
+    }
+
+    /// Returns the public key token of the assembly manifest, the way
+    /// `StrongNameTokenFromAssembly` does. Unsigned assemblies have none.
+    pub fn assembly_public_key_token(&self) -> Result<Option<PublicKeyToken>> {
+        self.assembly_public_key()?
+            .map(PublicKeyToken::from_public_key)
+            .transpose()
+    }
+
+    /// Returns the public key token of an `AssemblyRef`, hashing the full key
+    /// when the reference stores one (`AF_PUBLIC_KEY`).
+    pub fn assembly_ref_public_key_token(&self, rid: u32) -> Result<Option<PublicKeyToken>> {
+        let row = self.row::<AssemblyRefRow>(rid)?;
+        let data = self.blob(row.public_key_or_token)?;
+        if data.is_empty() {
+            return Ok(None);
+        }
+        if row.flags & AF_PUBLIC_KEY != 0 {
+            return PublicKeyToken::from_public_key(data).map(Some);
+        }
+        let token = data.try_into().map_err(|_| {
+            Error::BadStrongName(format!(
+                "assembly reference {rid} has a {}-byte public key token",
+                data.len()
+            ))
+        })?;
+        Ok(Some(PublicKeyToken(token)))
+    }
+}
+
+/// Returns the public key token of the assembly at `path`, like
+/// `ICLRStrongName::StrongNameTokenFromAssembly`.
+pub fn strong_name_token_from_assembly(path: impl AsRef<Path>) -> Result<Option<PublicKeyToken>> {
+    PeImage::open(path)?.metadata()?.assembly_public_key_token()
+}
+
+/// Decodes a string of hexadecimal digit pairs.
+pub(crate) fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
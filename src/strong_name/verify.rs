@@ -0,0 +1,172 @@
+//! Strong-name signature verification (`StrongNameSignatureVerificationEx`,
+//! `StrongNameSignatureVerificationFromImage`).
+
+use std::path::Path;
+
+use super::key::PublicKeyBlob;
+use super::token::decode_hex;
+use crate::error::{Error, Result};
+use crate::reader::{COMIMAGE_FLAGS_STRONGNAMESIGNED, MetadataReader, PeImage, TableId};
+
+const ASSEMBLY_SIGNATURE_KEY_ATTRIBUTE: &str = "System.Reflection.AssemblySignatureKeyAttribute";
+
+/// Outcome of verifying an assembly's strong-name signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrongNameStatus {
+    /// The signature is valid.
+    Verified,
+    /// The assembly carries a public key but was only delay-signed: the CLI
+    /// header does not have `COMIMAGE_FLAGS_STRONGNAMESIGNED` set.
+    DelaySigned,
+    /// The assembly has no public key or no room for a signature.
+    NotStrongNamed,
+    /// The image hash does not match the signature.
+    SignatureMismatch,
+    /// The `AssemblySignatureKeyAttribute` counter-signature does not match
+    /// the identity key.
+    CounterSignatureMismatch,
+}
+
+impl StrongNameStatus {
+    /// Returns `true` for [`StrongNameStatus::Verified`].
+    pub fn is_verified(self) -> bool {
+        self == StrongNameStatus::Verified
+    }
+}
+
+/// The signature key and counter-signature of an assembly using enhanced
+/// strong naming, from its `AssemblySignatureKeyAttribute`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblySignatureKey {
+    /// The `PublicKeyBlob` of the key that signed the image.
+    pub public_key: Vec<u8>,
+    /// The identity key's signature over `public_key`, as stored in the
+    /// attribute (little-endian).
+    pub counter_signature: Vec<u8>,
+}
+
+impl MetadataReader {
+    /// Returns the assembly's `AssemblySignatureKeyAttribute`, if it uses
+    /// enhanced strong naming.
+    pub fn assembly_signature_key(&self) -> Result<Option<AssemblySignatureKey>> {
+        if self.row_count(TableId::Assembly) == 0 {
+            return Ok(None);
+        }
+        let assembly = TableId::Assembly.token(1);
+        for rid in self.find_custom_attributes(assembly, ASSEMBLY_SIGNATURE_KEY_ATTRIBUTE)? {
+            let value = self.custom_attribute_value(rid)?;
+            let mut args = value.fixed_args.iter().map(|arg| arg.as_str());
+            if let (Some(Some(public_key)), Some(Some(counter_signature))) =
+                (args.next(), args.next())
+            {
+                return Ok(Some(AssemblySignatureKey {
+                    public_key: decode_hex(public_key).ok_or_else(|| invalid_hex(public_key))?,
+                    counter_signature: decode_hex(counter_signature)
+                        .ok_or_else(|| invalid_hex(counter_signature))?,
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl PeImage {
+    /// Verifies the strong-name signature of the image, like
+    /// `StrongNameSignatureVerificationFromImage`.
+    ///
+    /// Assemblies using enhanced strong naming are verified against the
+    /// signature key from their `AssemblySignatureKeyAttribute`, whose
+    /// counter-signature is checked against the identity key. Images signed
+    /// with the ECMA neutral key cannot be verified this way because the
+    /// substituted platform key is not public; use
+    /// [`PeImage::verify_strong_name_with_key`] instead.
+    pub fn verify_strong_name(&self) -> Result<StrongNameStatus> {
+        let metadata = self.metadata()?;
+        let Some(identity) = metadata.assembly_public_key()? else {
+            return Ok(StrongNameStatus::NotStrongNamed);
+        };
+        let identity = PublicKeyBlob::parse(identity)?;
+        if let Some(status) = self.unsigned_status() {
+            return Ok(status);
+        }
+
+        match metadata.assembly_signature_key()? {
+            Some(signature_key) => {
+                let key = PublicKeyBlob::parse(&signature_key.public_key)?;
+                if !self.verify_image_signature(&key)? {
+                    return Ok(StrongNameStatus::SignatureMismatch);
+                }
+                if identity.is_ecma_key() {
+                    return Err(ecma_key_error());
+                }
+                let hash = identity
+                    .hash_algorithm()?
+                    .digest([signature_key.public_key.as_slice()]);
+                if !identity.verify_hash(&hash, &signature_key.counter_signature)? {
+                    return Ok(StrongNameStatus::CounterSignatureMismatch);
+                }
+                Ok(StrongNameStatus::Verified)
+            }
+            None if identity.is_ecma_key() => Err(ecma_key_error()),
+            None => Ok(self.image_status(&identity)?),
+        }
+    }
+
+    /// Verifies the image signature against an explicit `PublicKeyBlob`,
+    /// ignoring the key recorded in the metadata.
+    ///
+    /// This is how assemblies signed with the ECMA neutral key are checked:
+    /// pass the platform key the runtime substitutes for it.
+    pub fn verify_strong_name_with_key(&self, public_key: &[u8]) -> Result<StrongNameStatus> {
+        let key = PublicKeyBlob::parse(public_key)?;
+        if let Some(status) = self.unsigned_status() {
+            return Ok(status);
+        }
+        self.image_status(&key)
+    }
+
+    /// Returns the status of images that do not carry a full signature.
+    fn unsigned_status(&self) -> Option<StrongNameStatus> {
+        let cor = self.cor_header()?;
+        if self.strong_name_signature_range().is_none() {
+            Some(StrongNameStatus::NotStrongNamed)
+        } else if cor.flags & COMIMAGE_FLAGS_STRONGNAMESIGNED == 0 {
+            Some(StrongNameStatus::DelaySigned)
+        } else {
+            None
+        }
+    }
+
+    fn image_status(&self, key: &PublicKeyBlob<'_>) -> Result<StrongNameStatus> {
+        Ok(if self.verify_image_signature(key)? {
+            StrongNameStatus::Verified
+        } else {
+            StrongNameStatus::SignatureMismatch
+        })
+    }
+
+    fn verify_image_signature(&self, key: &PublicKeyBlob<'_>) -> Result<bool> {
+        let signature = self.strong_name_signature().unwrap_or_default();
+        if signature.len() != key.modulus_len()? {
+            return Ok(false);
+        }
+        let hash = self.strong_name_hash(key.hash_algorithm()?)?;
+        key.verify_hash(&hash, signature)
+    }
+}
+
+/// Verifies the strong-name signature of the assembly at `path`, like
+/// `StrongNameSignatureVerificationEx`.
+pub fn strong_name_signature_verification(path: impl AsRef<Path>) -> Result<StrongNameStatus> {
+    PeImage::open(path)?.verify_strong_name()
+}
+
+fn ecma_key_error() -> Error {
+    Error::BadStrongName(
+        "assembly is signed with the ECMA key; verify it against the platform key".into(),
+    )
+}
+
+fn invalid_hex(s: &str) -> Error {
+    Error::BadStrongName(format!("invalid hexadecimal key data {s:?}"))
+}
@@ -0,0 +1,14 @@
+//! A cross-assembly type system over the pure-Rust metadata reader.
+//!
+//! [`Workspace`] loads a set of assemblies and resolves references between
+//! them the way the runtime's class loader does, without the CLR, and
+//! [`TypeInst`] models exact and shared generic instantiations. Instance field
+//! layouts are computed from metadata as the runtime would.
+
+mod generics;
+mod layout;
+mod workspace;
+
+pub use generics::*;
+pub use layout::*;
+pub use workspace::*;
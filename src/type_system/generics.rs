@@ -0,0 +1,631 @@
+//! Generic instantiation: substituting `!n`/`!!n` with type arguments and
+//! computing the shared (`__Canon`) forms the runtime compiles code for.
+
+use std::fmt;
+
+use super::workspace::{MemberHandle, ModuleId, TypeHandle, Workspace};
+use crate::error::{Error, Result};
+use crate::reader::{
+    ArrayShape, FieldRow, IMAGE_CEE_CS_CALLCONV_GENERIC, IMAGE_CEE_CS_CALLCONV_HASTHIS,
+    MemberRefRow, MethodDefRow, MethodSig, MethodSpecRow, TableId, TypeDefRow, TypeSig,
+    parse_field_sig, parse_method_spec, token_rid,
+};
+
+/// Column of `GenericParam.Owner`.
+const GENERIC_PARAM_OWNER: usize = 2;
+
+/// A type independent of the module whose signature it was read from:
+/// type tokens are resolved to definitions in a [`Workspace`].
+///
+/// Custom modifiers and `pinned` do not affect type identity and are
+/// dropped.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeInst {
+    Void,
+    Boolean,
+    Char,
+    I1,
+    U1,
+    I2,
+    U2,
+    I4,
+    U4,
+    I8,
+    U8,
+    R4,
+    R8,
+    I,
+    U,
+    String,
+    Object,
+    TypedByRef,
+    /// A class or value type, possibly instantiated. `handle` is `None`
+    /// when the definition is not loaded.
+    Named {
+        value_type: bool,
+        handle: Option<TypeHandle>,
+        name: String,
+        args: Vec<TypeInst>,
+    },
+    Ptr(Box<TypeInst>),
+    ByRef(Box<TypeInst>),
+    SzArray(Box<TypeInst>),
+    Array(Box<TypeInst>, ArrayShape),
+    /// A generic parameter of the enclosing type (`!n`).
+    Var(u32),
+    /// A generic parameter of the enclosing method (`!!n`).
+    MVar(u32),
+    FnPtr(Box<MethodInstSig>),
+    /// `System.__Canon`, standing for any reference type in shared code.
+    Canon,
+}
+
+/// A method signature made of [`TypeInst`]s.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodInstSig {
+    /// The calling convention byte, including `HASTHIS`/`GENERIC` flags.
+    pub calling_convention: u8,
+    pub generic_param_count: u32,
+    pub return_type: TypeInst,
+    pub params: Vec<TypeInst>,
+    /// Index in `params` of the first vararg parameter.
+    pub sentinel: Option<usize>,
+}
+
+/// The type and method arguments substituted for `!n` and `!!n`.
+///
+/// An empty list leaves the corresponding parameters open, so the
+/// signatures of generic definitions can be read without instantiating
+/// them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct GenericContext {
+    pub type_args: Vec<TypeInst>,
+    pub method_args: Vec<TypeInst>,
+}
+
+impl GenericContext {
+    /// Creates a context with type arguments only.
+    pub fn for_type(type_args: Vec<TypeInst>) -> Self {
+        GenericContext {
+            type_args,
+            method_args: Vec::new(),
+        }
+    }
+}
+
+/// An exact instantiation of a method, as passed to
+/// `ICorProfilerInfo2::GetFunctionFromTokenAndTypeArgs`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodInstance {
+    pub method: MemberHandle,
+    /// The arguments of the declaring type.
+    pub type_args: Vec<TypeInst>,
+    pub method_args: Vec<TypeInst>,
+}
+
+impl MethodInstance {
+    /// Returns the context for the method's signature and body.
+    pub fn context(&self) -> GenericContext {
+        GenericContext {
+            type_args: self.type_args.clone(),
+            method_args: self.method_args.clone(),
+        }
+    }
+
+    /// Returns the instantiation whose code is shared with this one.
+    pub fn canonical(&self) -> MethodInstance {
+        MethodInstance {
+            method: self.method,
+            type_args: self.type_args.iter().map(TypeInst::canonical_arg).collect(),
+            method_args: self
+                .method_args
+                .iter()
+                .map(TypeInst::canonical_arg)
+                .collect(),
+        }
+    }
+
+    /// Returns `true` if the instantiation uses shared code.
+    pub fn is_shared(&self) -> bool {
+        self.type_args
+            .iter()
+            .chain(&self.method_args)
+            .any(TypeInst::contains_canon)
+    }
+}
+
+impl TypeInst {
+    /// Replaces `!n` and `!!n` with the arguments in `context`.
+    pub fn substitute(&self, context: &GenericContext) -> Result<TypeInst> {
+        let arg = |args: &[TypeInst], n: u32, open: TypeInst, prefix: &str| {
+            if args.is_empty() {
+                return Ok(open);
+            }
+            args.get(n as usize).cloned().ok_or_else(|| {
+                Error::BadSignature(format!(
+                    "generic parameter {prefix}{n} out of range ({} arguments)",
+                    args.len()
+                ))
+            })
+        };
+        Ok(match self {
+            TypeInst::Var(n) => arg(&context.type_args, *n, self.clone(), "!")?,
+            TypeInst::MVar(n) => arg(&context.method_args, *n, self.clone(), "!!")?,
+            TypeInst::Named {
+                value_type,
+                handle,
+                name,
+                args,
+            } => TypeInst::Named {
+                value_type: *value_type,
+                handle: *handle,
+                name: name.clone(),
+                args: args
+                    .iter()
+                    .map(|a| a.substitute(context))
+                    .collect::<Result<_>>()?,
+            },
+            TypeInst::Ptr(ty) => TypeInst::Ptr(Box::new(ty.substitute(context)?)),
+            TypeInst::ByRef(ty) => TypeInst::ByRef(Box::new(ty.substitute(context)?)),
+            TypeInst::SzArray(ty) => TypeInst::SzArray(Box::new(ty.substitute(context)?)),
+            TypeInst::Array(ty, shape) => {
+                TypeInst::Array(Box::new(ty.substitute(context)?), shape.clone())
+            }
+            TypeInst::FnPtr(sig) => TypeInst::FnPtr(Box::new(sig.substitute(context)?)),
+            other => other.clone(),
+        })
+    }
+
+    /// Returns `true` if the type mentions `!n` or `!!n`.
+    pub fn is_open(&self) -> bool {
+        self.any(&|ty| matches!(ty, TypeInst::Var(_) | TypeInst::MVar(_)))
+    }
+
+    /// Returns `true` if the type mentions `System.__Canon`.
+    pub fn contains_canon(&self) -> bool {
+        self.any(&|ty| matches!(ty, TypeInst::Canon))
+    }
+
+    /// Returns `true` for reference types: classes, interfaces, `string`,
+    /// `object` and arrays.
+    pub fn is_reference_type(&self) -> bool {
+        match self {
+            TypeInst::String
+            | TypeInst::Object
+            | TypeInst::SzArray(_)
+            | TypeInst::Array(..)
+            | TypeInst::Canon => true,
+            TypeInst::Named { value_type, .. } => !value_type,
+            _ => false,
+        }
+    }
+
+    /// Returns the canonical form the runtime shares code for: reference
+    /// type arguments become `__Canon`, value type arguments keep their
+    /// identity with their own arguments canonicalized. For example
+    /// `Dictionary<string, KeyValuePair<object, int>>` becomes
+    /// `Dictionary<__Canon, KeyValuePair<__Canon, int>>`.
+    ///
+    /// ```
+    /// use mscoree::TypeInst;
+    ///
+    /// let named = |value_type, name: &str, args| TypeInst::Named {
+    ///     value_type,
+    ///     handle: None,
+    ///     name: name.into(),
+    ///     args,
+    /// };
+    /// let open = named(false, "System.Collections.Generic.List`1", vec![TypeInst::Var(0)]);
+    /// let list = open.substitute(&mscoree::GenericContext::for_type(vec![TypeInst::String]))?;
+    /// assert_eq!(list.to_string(), "System.Collections.Generic.List`1<System.String>");
+    /// assert_eq!(list.canonical().to_string(), "System.Collections.Generic.List`1<System.__Canon>");
+    ///
+    /// let pair = named(true, "KeyValuePair`2", vec![TypeInst::Object, TypeInst::I4]);
+    /// let list = named(false, "List`1", vec![pair]);
+    /// assert_eq!(list.canonical().to_string(), "List`1<KeyValuePair`2<System.__Canon,System.Int32>>");
+    /// # Ok::<(), mscoree::Error>(())
+    /// ```
+    pub fn canonical(&self) -> TypeInst {
+        match self {
+            TypeInst::Named {
+                value_type,
+                handle,
+                name,
+                args,
+            } => TypeInst::Named {
+                value_type: *value_type,
+                handle: *handle,
+                name: name.clone(),
+                args: args.iter().map(TypeInst::canonical_arg).collect(),
+            },
+            TypeInst::Ptr(ty) => TypeInst::Ptr(Box::new(ty.canonical())),
+            TypeInst::ByRef(ty) => TypeInst::ByRef(Box::new(ty.canonical())),
+            TypeInst::SzArray(ty) => TypeInst::SzArray(Box::new(ty.canonical())),
+            TypeInst::Array(ty, shape) => TypeInst::Array(Box::new(ty.canonical()), shape.clone()),
+            other => other.clone(),
+        }
+    }
+
+    /// Canonicalizes a type used as a generic argument.
+    fn canonical_arg(&self) -> TypeInst {
+        if self.is_reference_type() {
+            TypeInst::Canon
+        } else {
+            self.canonical()
+        }
+    }
+
+    fn any(&self, pred: &dyn Fn(&TypeInst) -> bool) -> bool {
+        pred(self)
+            || match self {
+                TypeInst::Named { args, .. } => args.iter().any(|a| a.any(pred)),
+                TypeInst::Ptr(ty)
+                | TypeInst::ByRef(ty)
+                | TypeInst::SzArray(ty)
+                | TypeInst::Array(ty, _) => ty.any(pred),
+                TypeInst::FnPtr(sig) => {
+                    sig.return_type.any(pred) || sig.params.iter().any(|p| p.any(pred))
+                }
+                _ => false,
+            }
+    }
+}
+
+impl MethodInstSig {
+    /// Replaces `!n` and `!!n` in the return and parameter types.
+    pub fn substitute(&self, context: &GenericContext) -> Result<MethodInstSig> {
+        Ok(MethodInstSig {
+            calling_convention: self.calling_convention,
+            generic_param_count: self.generic_param_count,
+            return_type: self.return_type.substitute(context)?,
+            params: self
+                .params
+                .iter()
+                .map(|p| p.substitute(context))
+                .collect::<Result<_>>()?,
+            sentinel: self.sentinel,
+        })
+    }
+
+    /// Returns `true` if the method has a `this` pointer.
+    pub fn has_this(&self) -> bool {
+        self.calling_convention & IMAGE_CEE_CS_CALLCONV_HASTHIS != 0
+    }
+}
+
+impl fmt::Display for TypeInst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let primitive = match self {
+            TypeInst::Void => "System.Void",
+            TypeInst::Boolean => "System.Boolean",
+            TypeInst::Char => "System.Char",
+            TypeInst::I1 => "System.SByte",
+            TypeInst::U1 => "System.Byte",
+            TypeInst::I2 => "System.Int16",
+            TypeInst::U2 => "System.UInt16",
+            TypeInst::I4 => "System.Int32",
+            TypeInst::U4 => "System.UInt32",
+            TypeInst::I8 => "System.Int64",
+            TypeInst::U8 => "System.UInt64",
+            TypeInst::R4 => "System.Single",
+            TypeInst::R8 => "System.Double",
+            TypeInst::I => "System.IntPtr",
+            TypeInst::U => "System.UIntPtr",
+            TypeInst::String => "System.String",
+            TypeInst::Object => "System.Object",
+            TypeInst::TypedByRef => "System.TypedReference",
+            TypeInst::Canon => "System.__Canon",
+            _ => "",
+        };
+        if !primitive.is_empty() {
+            return f.write_str(primitive);
+        }
+        match self {
+            TypeInst::Named { name, args, .. } => {
+                f.write_str(name)?;
+                if !args.is_empty() {
+                    f.write_str("<")?;
+                    for (i, arg) in args.iter().enumerate() {
+                        if i > 0 {
+                            f.write_str(",")?;
+                        }
+                        write!(f, "{arg}")?;
+                    }
+                    f.write_str(">")?;
+                }
+                Ok(())
+            }
+            TypeInst::Ptr(ty) => write!(f, "{ty}*"),
+            TypeInst::ByRef(ty) => write!(f, "{ty}&"),
+            TypeInst::SzArray(ty) => write!(f, "{ty}[]"),
+            TypeInst::Array(ty, shape) if shape.rank <= 1 => write!(f, "{ty}[*]"),
+            TypeInst::Array(ty, shape) => {
+                write!(f, "{ty}[{}]", ",".repeat(shape.rank as usize - 1))
+            }
+            TypeInst::Var(n) => write!(f, "!{n}"),
+            TypeInst::MVar(n) => write!(f, "!!{n}"),
+            TypeInst::FnPtr(sig) => {
+                write!(f, "method {} *(", sig.return_type)?;
+                for (i, param) in sig.params.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{param}")?;
+                }
+                f.write_str(")")
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Workspace {
+    /// Converts a signature type of `module` into a [`TypeInst`].
+    pub fn type_inst(&self, module: ModuleId, sig: &TypeSig) -> Result<TypeInst> {
+        Ok(match sig {
+            TypeSig::Void => TypeInst::Void,
+            TypeSig::Boolean => TypeInst::Boolean,
+            TypeSig::Char => TypeInst::Char,
+            TypeSig::I1 => TypeInst::I1,
+            TypeSig::U1 => TypeInst::U1,
+            TypeSig::I2 => TypeInst::I2,
+            TypeSig::U2 => TypeInst::U2,
+            TypeSig::I4 => TypeInst::I4,
+            TypeSig::U4 => TypeInst::U4,
+            TypeSig::I8 => TypeInst::I8,
+            TypeSig::U8 => TypeInst::U8,
+            TypeSig::R4 => TypeInst::R4,
+            TypeSig::R8 => TypeInst::R8,
+            TypeSig::I => TypeInst::I,
+            TypeSig::U => TypeInst::U,
+            TypeSig::String => TypeInst::String,
+            TypeSig::Object => TypeInst::Object,
+            TypeSig::TypedByRef => TypeInst::TypedByRef,
+            TypeSig::Class(token) => self.named(module, *token, false, Vec::new())?,
+            TypeSig::ValueType(token) => self.named(module, *token, true, Vec::new())?,
+            TypeSig::GenericInst {
+                value_type,
+                definition,
+                args,
+            } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.type_inst(module, arg))
+                    .collect::<Result<_>>()?;
+                self.named(module, *definition, *value_type, args)?
+            }
+            TypeSig::Ptr(ty) => TypeInst::Ptr(Box::new(self.type_inst(module, ty)?)),
+            TypeSig::ByRef(ty) => TypeInst::ByRef(Box::new(self.type_inst(module, ty)?)),
+            TypeSig::SzArray(ty) => TypeInst::SzArray(Box::new(self.type_inst(module, ty)?)),
+            TypeSig::Array(ty, shape) => {
+                TypeInst::Array(Box::new(self.type_inst(module, ty)?), shape.clone())
+            }
+            TypeSig::Var(n) => TypeInst::Var(*n),
+            TypeSig::MVar(n) => TypeInst::MVar(*n),
+            TypeSig::FnPtr(sig) => TypeInst::FnPtr(Box::new(self.method_inst_sig(module, sig)?)),
+            TypeSig::Modified { ty, .. } | TypeSig::Pinned(ty) => self.type_inst(module, ty)?,
+            TypeSig::Internal(_) => {
+                return Err(Error::BadSignature(
+                    "ELEMENT_TYPE_INTERNAL cannot be resolved offline".into(),
+                ));
+            }
+        })
+    }
+
+    /// Converts a method signature of `module` into a [`MethodInstSig`].
+    pub fn method_inst_sig(&self, module: ModuleId, sig: &MethodSig) -> Result<MethodInstSig> {
+        Ok(MethodInstSig {
+            calling_convention: sig.calling_convention,
+            generic_param_count: sig.generic_param_count,
+            return_type: self.type_inst(module, &sig.return_type)?,
+            params: sig
+                .params
+                .iter()
+                .map(|p| self.type_inst(module, p))
+                .collect::<Result<_>>()?,
+            sentinel: sig.sentinel,
+        })
+    }
+
+    /// Converts a `TypeDef`, `TypeRef` or `TypeSpec` token of `module`.
+    pub fn type_inst_from_token(&self, module: ModuleId, token: u32) -> Result<TypeInst> {
+        match TableId::from_token(token) {
+            Some((TableId::TypeSpec, rid)) => {
+                let metadata = &self.module(module).metadata;
+                let blob = metadata.blob(metadata.column(TableId::TypeSpec, rid, 0)?)?;
+                self.type_inst(module, &TypeSig::parse(blob)?)
+            }
+            _ => {
+                let value_type = match self.resolve_type(module, token)? {
+                    Some(ty) => self.is_value_type(ty)?,
+                    None => false,
+                };
+                self.named(module, token, value_type, Vec::new())
+            }
+        }
+    }
+
+    /// Returns the uninstantiated [`TypeInst`] of a type definition.
+    pub fn type_inst_of(&self, ty: TypeHandle) -> Result<TypeInst> {
+        Ok(TypeInst::Named {
+            value_type: self.is_value_type(ty)?,
+            handle: Some(ty),
+            name: self.type_name(ty)?,
+            args: Vec::new(),
+        })
+    }
+
+    /// Returns `true` if a type derives from `System.ValueType`, including
+    /// enums but not `System.Enum` itself.
+    pub fn is_value_type(&self, ty: TypeHandle) -> Result<bool> {
+        let metadata = &self.module(ty.module).metadata;
+        let extends = metadata.row::<TypeDefRow>(token_rid(ty.token))?.extends;
+        if token_rid(extends) == 0 {
+            return Ok(false);
+        }
+        let base = match TableId::from_token(extends) {
+            Some((TableId::TypeSpec, _)) => return Ok(false),
+            _ => metadata.type_full_name(extends)?,
+        };
+        Ok(base == "System.Enum"
+            || (base == "System.ValueType" && self.type_name(ty)? != "System.Enum"))
+    }
+
+    /// Returns the number of generic parameters of a `TypeDef` or
+    /// `MethodDef`.
+    pub fn generic_param_count(&self, module: ModuleId, token: u32) -> Result<usize> {
+        Ok(self
+            .module(module)
+            .metadata
+            .find_rows(TableId::GenericParam, GENERIC_PARAM_OWNER, token)?
+            .len())
+    }
+
+    /// Instantiates a generic type definition, like
+    /// `ICorProfilerInfo2::GetClassFromTokenAndTypeArgs`.
+    pub fn instantiate_type(&self, ty: TypeHandle, args: Vec<TypeInst>) -> Result<TypeInst> {
+        self.check_arity(ty.module, ty.token, args.len())?;
+        let TypeInst::Named {
+            value_type,
+            handle,
+            name,
+            ..
+        } = self.type_inst_of(ty)?
+        else {
+            unreachable!()
+        };
+        Ok(TypeInst::Named {
+            value_type,
+            handle,
+            name,
+            args,
+        })
+    }
+
+    /// Instantiates a method and its declaring type, like
+    /// `ICorProfilerInfo2::GetFunctionFromTokenAndTypeArgs`.
+    pub fn instantiate_method(
+        &self,
+        method: MemberHandle,
+        type_args: Vec<TypeInst>,
+        method_args: Vec<TypeInst>,
+    ) -> Result<MethodInstance> {
+        if let Some(owner) = self.declaring_type(method)? {
+            self.check_arity(owner.module, owner.token, type_args.len())?;
+        }
+        self.check_arity(method.module, method.token, method_args.len())?;
+        Ok(MethodInstance {
+            method,
+            type_args,
+            method_args,
+        })
+    }
+
+    /// Returns the method instance a `MethodDef`, `MemberRef` or
+    /// `MethodSpec` token of `module` refers to, substituting the caller's
+    /// `context` into its arguments. Returns `None` if the method is not
+    /// loaded.
+    pub fn method_instance(
+        &self,
+        module: ModuleId,
+        token: u32,
+        context: &GenericContext,
+    ) -> Result<Option<MethodInstance>> {
+        let metadata = &self.module(module).metadata;
+        let (reference, method_args) = match TableId::from_token(token) {
+            Some((TableId::MethodSpec, rid)) => {
+                let row = metadata.row::<MethodSpecRow>(rid)?;
+                let args = parse_method_spec(metadata.blob(row.instantiation)?)?
+                    .iter()
+                    .map(|arg| self.type_inst(module, arg)?.substitute(context))
+                    .collect::<Result<_>>()?;
+                (row.method, args)
+            }
+            _ => (token, Vec::new()),
+        };
+        let Some(method) = self.resolve_member(module, reference)? else {
+            return Ok(None);
+        };
+        if !method.is_method() {
+            return Err(Error::BadMetadata(format!(
+                "token {token:#010x} does not refer to a method"
+            )));
+        }
+        let mut type_args = Vec::new();
+        if let Some((TableId::MemberRef, rid)) = TableId::from_token(reference) {
+            let parent = metadata.row::<MemberRefRow>(rid)?.class;
+            if parent >> 24 == TableId::TypeSpec as u32
+                && let TypeInst::Named { args, .. } = self
+                    .type_inst_from_token(module, parent)?
+                    .substitute(context)?
+            {
+                type_args = args;
+            }
+        }
+        Ok(Some(MethodInstance {
+            method,
+            type_args,
+            method_args,
+        }))
+    }
+
+    /// Returns the type of a field with `context` substituted.
+    pub fn field_type(&self, field: MemberHandle, context: &GenericContext) -> Result<TypeInst> {
+        let metadata = &self.module(field.module).metadata;
+        let row = metadata.row::<FieldRow>(token_rid(field.token))?;
+        let sig = parse_field_sig(metadata.blob(row.signature)?)?;
+        self.type_inst(field.module, &sig)?.substitute(context)
+    }
+
+    /// Returns the signature of a method with `context` substituted.
+    pub fn method_signature(
+        &self,
+        method: MemberHandle,
+        context: &GenericContext,
+    ) -> Result<MethodInstSig> {
+        let metadata = &self.module(method.module).metadata;
+        let row = metadata.row::<MethodDefRow>(token_rid(method.token))?;
+        let sig = MethodSig::parse(metadata.blob(row.signature)?)?;
+        let mut sig = self
+            .method_inst_sig(method.module, &sig)?
+            .substitute(context)?;
+        if !context.method_args.is_empty() {
+            sig.calling_convention &= !IMAGE_CEE_CS_CALLCONV_GENERIC;
+            sig.generic_param_count = 0;
+        }
+        Ok(sig)
+    }
+
+    fn named(
+        &self,
+        module: ModuleId,
+        token: u32,
+        value_type: bool,
+        args: Vec<TypeInst>,
+    ) -> Result<TypeInst> {
+        if token >> 24 == TableId::TypeSpec as u32 {
+            return self.type_inst_from_token(module, token);
+        }
+        let handle = self.resolve_type(module, token)?;
+        let name = match handle {
+            Some(ty) => self.type_name(ty)?,
+            None => self.module(module).metadata.type_full_name(token)?,
+        };
+        Ok(TypeInst::Named {
+            value_type,
+            handle,
+            name,
+            args,
+        })
+    }
+
+    fn check_arity(&self, module: ModuleId, token: u32, count: usize) -> Result<()> {
+        let expected = self.generic_param_count(module, token)?;
+        if expected != count {
+            return Err(Error::BadSignature(format!(
+                "{token:#010x} has {expected} generic parameters, {count} arguments given"
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,454 @@
+//! Offline instance field layout, following the rules of CoreCLR's
+//! `MethodTableBuilder`, for decoding objects in dumps without the DAC.
+
+use super::generics::{GenericContext, TypeInst};
+use super::workspace::{MemberHandle, TypeHandle, Workspace};
+use crate::error::{Error, Result};
+use crate::reader::{
+    ClassLayoutRow, ELEMENT_TYPE_BOOLEAN, ELEMENT_TYPE_CHAR, ELEMENT_TYPE_I, ELEMENT_TYPE_I1,
+    ELEMENT_TYPE_I2, ELEMENT_TYPE_I8, ELEMENT_TYPE_R8, ELEMENT_TYPE_U, ELEMENT_TYPE_U1,
+    ELEMENT_TYPE_U2, ELEMENT_TYPE_U8, FieldLayoutRow, FieldRow, TableId, TypeDefRow,
+    parse_field_sig, token_rid,
+};
+
+/// `TypeAttributes.LayoutMask`.
+const TYPE_ATTRIBUTE_LAYOUT_MASK: u32 = 0x0000_0018;
+/// `TypeAttributes.SequentialLayout`.
+const TYPE_ATTRIBUTE_SEQUENTIAL_LAYOUT: u32 = 0x0000_0008;
+/// `TypeAttributes.ExplicitLayout`.
+const TYPE_ATTRIBUTE_EXPLICIT_LAYOUT: u32 = 0x0000_0010;
+/// `FieldAttributes.Static`.
+const FIELD_ATTRIBUTE_STATIC: u16 = 0x0010;
+/// Column of `ClassLayout.Parent` and `FieldLayout.Field`.
+const CLASS_LAYOUT_PARENT: usize = 2;
+const FIELD_LAYOUT_FIELD: usize = 1;
+/// Packing used when `ClassLayout.PackingSize` is 0.
+const DEFAULT_PACKING: u32 = 8;
+/// Bound on nested value types and base classes.
+const MAX_DEPTH: usize = 64;
+
+/// The pointer size of the target process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PointerSize {
+    Bits32,
+    Bits64,
+}
+
+impl PointerSize {
+    /// Returns the size of a pointer in bytes.
+    pub fn bytes(self) -> u32 {
+        match self {
+            PointerSize::Bits32 => 4,
+            PointerSize::Bits64 => 8,
+        }
+    }
+}
+
+/// How the fields of a type are placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LayoutKind {
+    /// The runtime orders fields: primitives and object references from
+    /// the largest, with references first among pointer-sized fields,
+    /// then value types.
+    Auto,
+    /// Fields are placed in declaration order.
+    Sequential,
+    /// Fields are placed at the offsets in the `FieldLayout` table.
+    Explicit,
+}
+
+/// The placement of an instance field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldOffset {
+    pub field: MemberHandle,
+    pub name: String,
+    pub ty: TypeInst,
+    /// Offset from the start of the field data, as in
+    /// `DacpFieldDescData::dwOffset`: after the method table pointer for
+    /// objects, from the start of the value for value types.
+    pub offset: u32,
+    pub size: u32,
+    /// `true` for fields declared by a base class.
+    pub inherited: bool,
+}
+
+/// The computed instance layout of a type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeLayout {
+    pub ty: TypeInst,
+    pub kind: LayoutKind,
+    pub is_value_type: bool,
+    /// Instance fields, including inherited ones, ordered by offset.
+    pub fields: Vec<FieldOffset>,
+    /// Bytes of instance field data (`GetNumInstanceFieldBytes`). At least
+    /// 1 for value types.
+    pub instance_size: u32,
+    /// Alignment of the type when used as a field.
+    pub alignment: u32,
+    /// Size of an object or boxed value including the object header and
+    /// method table pointer, as in `DacpMethodTableData::BaseSize`.
+    pub base_size: u32,
+    /// Offsets of object references in the field data, including those in
+    /// embedded value types.
+    pub gc_pointers: Vec<u32>,
+}
+
+impl TypeLayout {
+    /// Returns `true` if instances contain object references, as in
+    /// `DacpMethodTableData::bContainsPointers`.
+    pub fn contains_pointers(&self) -> bool {
+        !self.gc_pointers.is_empty()
+    }
+
+    /// Returns the placement of a field by name.
+    pub fn field(&self, name: &str) -> Option<&FieldOffset> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+/// Size, alignment and references of a field's type.
+struct FieldShape {
+    size: u32,
+    alignment: u32,
+    gc_pointers: Vec<u32>,
+    /// Primitives and references are placed before value types by auto
+    /// layout.
+    is_struct: bool,
+}
+
+/// An instance field declared by the type being laid out.
+struct PendingField {
+    field: MemberHandle,
+    name: String,
+    ty: TypeInst,
+    shape: FieldShape,
+    explicit_offset: Option<u32>,
+}
+
+impl Workspace {
+    /// Computes the instance layout of a type definition or of an
+    /// instantiation of one. The type must be closed: generic parameters
+    /// are not allowed.
+    pub fn type_layout(&self, ty: &TypeInst, pointer_size: PointerSize) -> Result<TypeLayout> {
+        self.layout(ty, pointer_size, 0)
+    }
+
+    fn layout(&self, ty: &TypeInst, pointer_size: PointerSize, depth: usize) -> Result<TypeLayout> {
+        if depth > MAX_DEPTH {
+            return Err(Error::BadMetadata(format!(
+                "type {ty} is nested too deeply to lay out"
+            )));
+        }
+        let TypeInst::Named {
+            value_type,
+            handle: Some(handle),
+            args,
+            ..
+        } = ty
+        else {
+            return Err(Error::BadMetadata(format!(
+                "type {ty} is not a loaded class or value type"
+            )));
+        };
+        let handle = *handle;
+        let context = GenericContext::for_type(args.clone());
+        let metadata = &self.module(handle.module).metadata;
+        let rid = token_rid(handle.token);
+        let row = metadata.row::<TypeDefRow>(rid)?;
+        let is_value_type = *value_type || self.is_value_type(handle)?;
+        let pointer = pointer_size.bytes();
+
+        let (packing, class_size) =
+            match metadata.find_row(TableId::ClassLayout, CLASS_LAYOUT_PARENT, rid)? {
+                Some(layout_rid) => {
+                    let layout = metadata.row::<ClassLayoutRow>(layout_rid)?;
+                    let packing = match layout.packing_size {
+                        0 => DEFAULT_PACKING,
+                        packing => packing as u32,
+                    };
+                    (packing, layout.class_size)
+                }
+                None => (DEFAULT_PACKING, 0),
+            };
+
+        // Fields of the base class come first.
+        let (mut fields, mut gc_pointers, start) = if is_value_type {
+            (Vec::new(), Vec::new(), 0)
+        } else {
+            match self.base_type_inst(handle, &context)? {
+                Some(base) => {
+                    let base = self.layout(&base, pointer_size, depth + 1)?;
+                    let mut fields = base.fields;
+                    for field in &mut fields {
+                        field.inherited = true;
+                    }
+                    (fields, base.gc_pointers, base.instance_size)
+                }
+                None => (Vec::new(), Vec::new(), 0),
+            }
+        };
+
+        let mut pending = Vec::new();
+        for field_rid in metadata.type_def_fields(rid)? {
+            let field_row = metadata.row::<FieldRow>(field_rid)?;
+            if field_row.flags & FIELD_ATTRIBUTE_STATIC != 0 {
+                continue;
+            }
+            let sig = parse_field_sig(metadata.blob(field_row.signature)?)?;
+            let field_ty = self.type_inst(handle.module, &sig)?.substitute(&context)?;
+            let explicit_offset = metadata
+                .find_row(TableId::FieldLayout, FIELD_LAYOUT_FIELD, field_rid)?
+                .map(|layout| metadata.row::<FieldLayoutRow>(layout))
+                .transpose()?
+                .map(|layout| layout.offset);
+            pending.push(PendingField {
+                field: MemberHandle {
+                    module: handle.module,
+                    token: TableId::Field.token(field_rid),
+                },
+                name: metadata.string(field_row.name)?.to_string(),
+                shape: self.field_shape(&field_ty, pointer_size, depth)?,
+                ty: field_ty,
+                explicit_offset,
+            });
+        }
+
+        // Sequential layout is only kept for types without object
+        // references, like the runtime does for non-blittable types.
+        let kind = match row.flags & TYPE_ATTRIBUTE_LAYOUT_MASK {
+            TYPE_ATTRIBUTE_EXPLICIT_LAYOUT => LayoutKind::Explicit,
+            TYPE_ATTRIBUTE_SEQUENTIAL_LAYOUT
+                if gc_pointers.is_empty()
+                    && pending.iter().all(|f| f.shape.gc_pointers.is_empty()) =>
+            {
+                LayoutKind::Sequential
+            }
+            _ => LayoutKind::Auto,
+        };
+
+        let mut alignment = 1;
+        let mut end = start;
+        let mut place = |field: PendingField, offset: u32, alignment_used: u32| {
+            alignment = alignment.max(alignment_used);
+            end = end.max(offset + field.shape.size);
+            gc_pointers.extend(field.shape.gc_pointers.iter().map(|p| offset + p));
+            fields.push(FieldOffset {
+                field: field.field,
+                name: field.name,
+                ty: field.ty,
+                offset,
+                size: field.shape.size,
+                inherited: false,
+            });
+        };
+        match kind {
+            LayoutKind::Explicit => {
+                for field in pending {
+                    let offset = start
+                        + field.explicit_offset.ok_or_else(|| {
+                            Error::BadMetadata(format!(
+                                "field {} of explicit layout type {ty} has no offset",
+                                field.name
+                            ))
+                        })?;
+                    let field_alignment = field.shape.alignment.min(packing);
+                    place(field, offset, field_alignment);
+                }
+            }
+            LayoutKind::Sequential => {
+                let mut position = start;
+                for field in pending {
+                    let field_alignment = field.shape.alignment.min(packing);
+                    let offset = align(position, field_alignment);
+                    position = offset + field.shape.size;
+                    place(field, offset, field_alignment);
+                }
+            }
+            LayoutKind::Auto => {
+                // Primitives and references go into buckets by size (1, 2,
+                // 4 and 8 bytes); references lead the pointer-sized bucket.
+                let is_reference = |f: &PendingField| f.shape.gc_pointers.len() == 1;
+                let (structs, primitives): (Vec<_>, Vec<_>) =
+                    pending.into_iter().partition(|f| f.shape.is_struct);
+                let mut buckets: [Vec<PendingField>; 4] = Default::default();
+                for field in primitives {
+                    let log2 = field.shape.size.trailing_zeros().min(3) as usize;
+                    buckets[log2].push(field);
+                }
+                buckets[pointer.trailing_zeros() as usize].sort_by_key(|f| !is_reference(f));
+
+                // When the base class leaves the fields unaligned, the gap
+                // is back-filled with the largest fields that fit, as long
+                // as larger fields follow. References are never moved
+                // into the gap.
+                let mut position = start;
+                if position % pointer != 0 {
+                    let mut log2 = 0;
+                    while log2 < 3 {
+                        if position % (2 << log2) == 0 {
+                            log2 += 1;
+                            continue;
+                        }
+                        if buckets[log2 + 1..].iter().all(Vec::is_empty) {
+                            break;
+                        }
+                        let Some((fill, index)) = (0..=log2).rev().find_map(|fill| {
+                            let index = buckets[fill].iter().position(|f| !is_reference(f))?;
+                            Some((fill, index))
+                        }) else {
+                            break;
+                        };
+                        let field = buckets[fill].remove(index);
+                        let offset = align(position, 1 << fill);
+                        position = offset + field.shape.size;
+                        let field_alignment = field.shape.alignment;
+                        place(field, offset, field_alignment);
+                        log2 = fill + 1;
+                    }
+                }
+
+                // Then the largest fields first, and value types last.
+                for field in buckets.into_iter().rev().flatten().chain(structs) {
+                    let field_alignment = field.shape.alignment;
+                    let offset = align(position, field_alignment);
+                    position = offset + field.shape.size;
+                    place(field, offset, field_alignment);
+                }
+            }
+        }
+
+        if kind != LayoutKind::Auto {
+            alignment = alignment.min(packing);
+        }
+        let mut instance_size = end.max(start + class_size);
+        if is_value_type {
+            instance_size = align(instance_size.max(1), alignment);
+        }
+        let base_size = align(2 * pointer + instance_size, pointer).max(3 * pointer);
+
+        fields.sort_by_key(|field| field.offset);
+        gc_pointers.sort_unstable();
+        gc_pointers.dedup();
+        Ok(TypeLayout {
+            ty: ty.clone(),
+            kind,
+            is_value_type,
+            fields,
+            instance_size,
+            alignment,
+            base_size,
+            gc_pointers,
+        })
+    }
+
+    /// Returns the base class of a type with the type's arguments
+    /// substituted, or `None` for `System.Object`.
+    fn base_type_inst(&self, ty: TypeHandle, context: &GenericContext) -> Result<Option<TypeInst>> {
+        let extends = self
+            .module(ty.module)
+            .metadata
+            .row::<TypeDefRow>(token_rid(ty.token))?
+            .extends;
+        if token_rid(extends) == 0 {
+            return Ok(None);
+        }
+        Ok(Some(
+            self.type_inst_from_token(ty.module, extends)?
+                .substitute(context)?,
+        ))
+    }
+
+    fn field_shape(
+        &self,
+        ty: &TypeInst,
+        pointer_size: PointerSize,
+        depth: usize,
+    ) -> Result<FieldShape> {
+        let pointer = pointer_size.bytes();
+        let primitive = |size: u32| FieldShape {
+            size,
+            alignment: if size == 8 { pointer } else { size },
+            gc_pointers: Vec::new(),
+            is_struct: false,
+        };
+        let reference = || FieldShape {
+            size: pointer,
+            alignment: pointer,
+            gc_pointers: vec![0],
+            is_struct: false,
+        };
+        Ok(match ty {
+            TypeInst::Boolean | TypeInst::I1 | TypeInst::U1 => primitive(1),
+            TypeInst::Char | TypeInst::I2 | TypeInst::U2 => primitive(2),
+            TypeInst::I4 | TypeInst::U4 | TypeInst::R4 => primitive(4),
+            TypeInst::I8 | TypeInst::U8 | TypeInst::R8 => primitive(8),
+            TypeInst::I | TypeInst::U | TypeInst::Ptr(_) | TypeInst::FnPtr(_) => primitive(pointer),
+            TypeInst::String
+            | TypeInst::Object
+            | TypeInst::SzArray(_)
+            | TypeInst::Array(..)
+            | TypeInst::Canon
+            | TypeInst::ByRef(_) => reference(),
+            TypeInst::TypedByRef => FieldShape {
+                size: 2 * pointer,
+                alignment: pointer,
+                gc_pointers: vec![0],
+                is_struct: true,
+            },
+            TypeInst::Named {
+                value_type: false, ..
+            } => reference(),
+            TypeInst::Named { handle, .. } => {
+                let enum_size = match handle {
+                    Some(handle) => self.enum_size(*handle, pointer)?,
+                    None => None,
+                };
+                match enum_size {
+                    // Enums are laid out as their underlying primitive.
+                    Some(size) => primitive(size),
+                    None => {
+                        let layout = self.layout(ty, pointer_size, depth + 1)?;
+                        FieldShape {
+                            size: layout.instance_size,
+                            alignment: layout.alignment,
+                            gc_pointers: layout.gc_pointers,
+                            is_struct: true,
+                        }
+                    }
+                }
+            }
+            TypeInst::Void | TypeInst::Var(_) | TypeInst::MVar(_) => {
+                return Err(Error::BadMetadata(format!(
+                    "cannot lay out a field of type {ty}"
+                )));
+            }
+        })
+    }
+
+    /// Returns the size of an enum's underlying type, or `None` if `ty` is
+    /// not an enum.
+    fn enum_size(&self, ty: TypeHandle, pointer: u32) -> Result<Option<u32>> {
+        let metadata = &self.module(ty.module).metadata;
+        let extends = metadata.row::<TypeDefRow>(token_rid(ty.token))?.extends;
+        let is_enum = match TableId::from_token(extends) {
+            Some((TableId::TypeDef | TableId::TypeRef, rid)) if rid != 0 => {
+                metadata.type_full_name(extends)? == "System.Enum"
+            }
+            _ => false,
+        };
+        if !is_enum {
+            return Ok(None);
+        }
+        Ok(Some(match metadata.enum_underlying_type(ty.token)? {
+            ELEMENT_TYPE_BOOLEAN | ELEMENT_TYPE_I1 | ELEMENT_TYPE_U1 => 1,
+            ELEMENT_TYPE_CHAR | ELEMENT_TYPE_I2 | ELEMENT_TYPE_U2 => 2,
+            ELEMENT_TYPE_I8 | ELEMENT_TYPE_U8 | ELEMENT_TYPE_R8 => 8,
+            ELEMENT_TYPE_I | ELEMENT_TYPE_U => pointer,
+            _ => 4,
+        }))
+    }
+}
+
+fn align(value: u32, alignment: u32) -> u32 {
+    value.next_multiple_of(alignment.max(1))
+}
@@ -0,0 +1,880 @@
+//! Loading assemblies and resolving `TypeRef`, `MemberRef` and type
+//! forwarders across them (`IMetaDataImport::ResolveTypeRef`).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::assembly_identity::{AssemblyCompareFlags, AssemblyIdentity};
+use crate::error::{Error, Result};
+use crate::reader::{
+    ExportedTypeRow, FieldRow, MemberRefRow, MetadataReader, MethodDefRow, MethodSig,
+    MethodSpecRow, ModuleRefRow, ModuleRow, PeImage, TableId, TypeDefRow, TypeRefRow, TypeSig,
+    parse_field_sig, token_rid,
+};
+use crate::resolver::AssemblyResolver;
+
+/// `TypeAttributes.VisibilityMask`: nested types have a non-zero
+/// visibility above `Public`.
+const TYPE_ATTRIBUTE_VISIBILITY_MASK: u32 = 0x0000_0007;
+/// `IMAGE_CEE_CS_CALLCONV_FIELD`.
+const CALLCONV_FIELD: u8 = 0x06;
+/// Bound on forwarder and nesting chains, to stop on cyclic metadata.
+const MAX_DEPTH: usize = 32;
+
+/// Identifies a module loaded into a [`Workspace`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ModuleId(pub usize);
+
+/// A type definition: a `TypeDef` token in a loaded module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TypeHandle {
+    pub module: ModuleId,
+    pub token: u32,
+}
+
+/// A member definition: a `MethodDef` or `Field` token in a loaded module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MemberHandle {
+    pub module: ModuleId,
+    pub token: u32,
+}
+
+impl MemberHandle {
+    /// Returns `true` if the member is a method.
+    pub fn is_method(&self) -> bool {
+        self.token >> 24 == TableId::MethodDef as u32
+    }
+
+    /// Returns `true` if the member is a field.
+    pub fn is_field(&self) -> bool {
+        self.token >> 24 == TableId::Field as u32
+    }
+}
+
+/// A module loaded into a [`Workspace`].
+pub struct LoadedModule {
+    /// The file the module was loaded from, if any.
+    pub path: Option<PathBuf>,
+    pub metadata: MetadataReader,
+    /// The assembly identity, for manifest modules.
+    pub identity: Option<AssemblyIdentity>,
+    /// The name in the `Module` table, e.g. `System.Runtime.dll`.
+    pub module_name: String,
+    /// Top-level types by namespace and name.
+    types: HashMap<(String, String), u32>,
+}
+
+impl fmt::Debug for LoadedModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoadedModule")
+            .field("path", &self.path)
+            .field("identity", &self.identity)
+            .field("module_name", &self.module_name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LoadedModule {
+    /// Returns the assembly display name, or the module name for modules
+    /// without a manifest.
+    pub fn display_name(&self) -> String {
+        match &self.identity {
+            Some(identity) => identity.to_string(),
+            None => self.module_name.clone(),
+        }
+    }
+}
+
+/// Why a reference could not be resolved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnresolvedReason {
+    /// The referenced assembly is not loaded into the workspace.
+    AssemblyNotLoaded(AssemblyIdentity),
+    /// The referenced module is not loaded into the workspace.
+    ModuleNotLoaded(String),
+    /// The target scope does not define or forward the type.
+    TypeNotFound { type_name: String, scope: String },
+    /// The type has no member with the referenced name and signature.
+    MemberNotFound { type_name: String },
+    /// Type forwarders or nesting form a cycle.
+    Cycle,
+}
+
+impl fmt::Display for UnresolvedReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnresolvedReason::AssemblyNotLoaded(identity) => {
+                write!(f, "assembly {identity} is not loaded")
+            }
+            UnresolvedReason::ModuleNotLoaded(name) => write!(f, "module {name} is not loaded"),
+            UnresolvedReason::TypeNotFound { type_name, scope } => {
+                write!(f, "type {type_name} is not defined in {scope}")
+            }
+            UnresolvedReason::MemberNotFound { type_name } => {
+                write!(f, "no matching member in {type_name}")
+            }
+            UnresolvedReason::Cycle => f.write_str("type forwarders form a cycle"),
+        }
+    }
+}
+
+/// A `TypeRef` or `MemberRef` that could not be resolved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedReference {
+    /// The module containing the reference.
+    pub module: ModuleId,
+    /// The display name of the requesting assembly.
+    pub requesting_assembly: String,
+    pub token: u32,
+    /// The referenced name, e.g. `System.Span`1` or `System.Console::WriteLine`.
+    pub name: String,
+    pub reason: UnresolvedReason,
+}
+
+impl fmt::Display for UnresolvedReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} ({:#010x}): {}",
+            self.requesting_assembly, self.name, self.token, self.reason
+        )
+    }
+}
+
+type Resolve<T> = std::result::Result<T, UnresolvedReason>;
+
+/// A set of loaded assemblies with cross-assembly reference resolution.
+///
+/// ```no_run
+/// use mscoree::{AssemblyResolver, Workspace};
+///
+/// let mut workspace = Workspace::new();
+/// workspace.add_file("app/App.dll")?;
+/// let resolver = AssemblyResolver::new()
+///     .with_app_base("app")
+///     .with_framework_dir("/usr/share/dotnet/shared/Microsoft.NETCore.App/8.0.11");
+/// for missing in workspace.load_references(&resolver)? {
+///     println!("missing assembly {missing}");
+/// }
+/// for unresolved in workspace.unresolved_references()? {
+///     println!("{unresolved}");
+/// }
+/// # Ok::<(), mscoree::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct Workspace {
+    modules: Vec<LoadedModule>,
+}
+
+impl Workspace {
+    /// Creates an empty workspace.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a module from its metadata.
+    pub fn add_metadata(
+        &mut self,
+        metadata: MetadataReader,
+        path: Option<PathBuf>,
+    ) -> Result<ModuleId> {
+        let module_name = if metadata.row_count(TableId::Module) > 0 {
+            metadata
+                .string(metadata.row::<ModuleRow>(1)?.name)?
+                .to_string()
+        } else {
+            String::new()
+        };
+        let identity = metadata.assembly_identity()?;
+        let mut types = HashMap::new();
+        for rid in 1..=metadata.row_count(TableId::TypeDef) {
+            let row = metadata.row::<TypeDefRow>(rid)?;
+            if row.flags & TYPE_ATTRIBUTE_VISIBILITY_MASK > 1 {
+                continue;
+            }
+            let key = (
+                metadata.string(row.namespace)?.to_string(),
+                metadata.string(row.name)?.to_string(),
+            );
+            types.entry(key).or_insert(rid);
+        }
+        self.modules.push(LoadedModule {
+            path,
+            metadata,
+            identity,
+            module_name,
+            types,
+        });
+        Ok(ModuleId(self.modules.len() - 1))
+    }
+
+    /// Adds the module in a PE image.
+    pub fn add_image(&mut self, image: &PeImage, path: Option<PathBuf>) -> Result<ModuleId> {
+        self.add_metadata(image.metadata()?, path)
+    }
+
+    /// Loads the module at `path`. A file that is already loaded is not
+    /// loaded again.
+    pub fn add_file(&mut self, path: impl AsRef<Path>) -> Result<ModuleId> {
+        let path = path.as_ref();
+        if let Some(id) = self
+            .modules
+            .iter()
+            .position(|m| m.path.as_deref() == Some(path))
+        {
+            return Ok(ModuleId(id));
+        }
+        self.add_image(&PeImage::open(path)?, Some(path.to_path_buf()))
+    }
+
+    /// Loads the assemblies referenced by the workspace, transitively, using
+    /// `resolver` to find them. Returns the references that could not be
+    /// found.
+    pub fn load_references(
+        &mut self,
+        resolver: &AssemblyResolver,
+    ) -> Result<Vec<AssemblyIdentity>> {
+        let mut missing: Vec<AssemblyIdentity> = Vec::new();
+        let mut next = 0;
+        while next < self.modules.len() {
+            let metadata = &self.modules[next].metadata;
+            let references = (1..=metadata.row_count(TableId::AssemblyRef))
+                .map(|rid| metadata.assembly_ref_identity(rid))
+                .collect::<Result<Vec<_>>>()?;
+            next += 1;
+            for reference in references {
+                if self.find_assembly(&reference).is_some()
+                    || missing
+                        .iter()
+                        .any(|m| m.to_string() == reference.to_string())
+                {
+                    continue;
+                }
+                match resolver.resolve(&reference).path {
+                    Some(path) => {
+                        self.add_file(path)?;
+                    }
+                    None => missing.push(reference),
+                }
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Returns a loaded module.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` does not belong to this workspace.
+    pub fn module(&self, id: ModuleId) -> &LoadedModule {
+        &self.modules[id.0]
+    }
+
+    /// Returns the loaded modules.
+    pub fn modules(&self) -> impl Iterator<Item = (ModuleId, &LoadedModule)> {
+        self.modules
+            .iter()
+            .enumerate()
+            .map(|(id, module)| (ModuleId(id), module))
+    }
+
+    /// Finds the manifest module of the assembly `reference` binds to:
+    /// name, culture and public key token must match. Among several
+    /// versions, the exact one or else the highest is chosen.
+    pub fn find_assembly(&self, reference: &AssemblyIdentity) -> Option<ModuleId> {
+        let partial = AssemblyIdentity {
+            name: reference.name.clone(),
+            culture: Some(reference.culture.clone().unwrap_or_default()),
+            public_key: reference.public_key.clone(),
+            ..Default::default()
+        };
+        let candidates = self.modules().filter(|(_, module)| {
+            module
+                .identity
+                .as_ref()
+                .is_some_and(|identity| partial.is_equal(identity, AssemblyCompareFlags::DEFAULT))
+        });
+        let version = |module: &LoadedModule| {
+            module
+                .identity
+                .as_ref()
+                .and_then(|identity| identity.version)
+                .map(|version| version.parts())
+        };
+        let requested = reference.version.map(|version| version.parts());
+        let mut best: Option<(ModuleId, &LoadedModule)> = None;
+        for (id, module) in candidates {
+            if requested.is_some() && version(module) == requested {
+                return Some(id);
+            }
+            if best.is_none_or(|(_, current)| version(module) > version(current)) {
+                best = Some((id, module));
+            }
+        }
+        best.map(|(id, _)| id)
+    }
+
+    /// Finds a top-level type defined in `module`.
+    pub fn find_type(&self, module: ModuleId, namespace: &str, name: &str) -> Option<TypeHandle> {
+        let rid = self
+            .module(module)
+            .types
+            .get(&(namespace.to_string(), name.to_string()))?;
+        Some(TypeHandle {
+            module,
+            token: TableId::TypeDef.token(*rid),
+        })
+    }
+
+    /// Finds a top-level type by namespace and name in the assembly whose
+    /// manifest is `module`, following its type forwarders.
+    pub fn find_exported_type(
+        &self,
+        module: ModuleId,
+        namespace: &str,
+        name: &str,
+    ) -> Result<Option<TypeHandle>> {
+        Ok(self.lookup_in_assembly(module, namespace, name, 0)?.ok())
+    }
+
+    /// Resolves a `TypeDef`, `TypeRef` or generic `TypeSpec` token of
+    /// `module` to the type definition. Returns `None` if the definition is
+    /// not loaded, or for `TypeSpec`s that are not generic instantiations.
+    pub fn resolve_type(&self, module: ModuleId, token: u32) -> Result<Option<TypeHandle>> {
+        Ok(self.resolve_type_inner(module, token, 0)?.ok())
+    }
+
+    /// Resolves a `MethodDef`, `Field`, `MemberRef` or `MethodSpec` token
+    /// of `module` to the member definition.
+    pub fn resolve_member(&self, module: ModuleId, token: u32) -> Result<Option<MemberHandle>> {
+        Ok(self.resolve_member_inner(module, token)?.ok())
+    }
+
+    /// Returns the resolved base type of a type, or `None` for
+    /// `System.Object`, interfaces and unresolved base types.
+    pub fn base_type(&self, ty: TypeHandle) -> Result<Option<TypeHandle>> {
+        let extends = self
+            .module(ty.module)
+            .metadata
+            .row::<TypeDefRow>(token_rid(ty.token))?
+            .extends;
+        if token_rid(extends) == 0 {
+            return Ok(None);
+        }
+        self.resolve_type(ty.module, extends)
+    }
+
+    /// Returns the full name of a type, e.g. `System.Collections.Generic.List`1`.
+    pub fn type_name(&self, ty: TypeHandle) -> Result<String> {
+        self.module(ty.module).metadata.type_full_name(ty.token)
+    }
+
+    /// Returns the `TypeDef` that declares a member.
+    pub fn declaring_type(&self, member: MemberHandle) -> Result<Option<TypeHandle>> {
+        let metadata = &self.module(member.module).metadata;
+        let rid = token_rid(member.token);
+        let owner = if member.is_method() {
+            metadata.method_declaring_type(rid)?
+        } else {
+            metadata.field_declaring_type(rid)?
+        };
+        Ok(owner.map(|owner| TypeHandle {
+            module: member.module,
+            token: TableId::TypeDef.token(owner),
+        }))
+    }
+
+    /// Resolves every `TypeRef` and `MemberRef` in the workspace and
+    /// returns the ones that fail. Members of unresolved types are not
+    /// reported again, and members of arrays are provided by the runtime.
+    pub fn unresolved_references(&self) -> Result<Vec<UnresolvedReference>> {
+        let mut unresolved = Vec::new();
+        for (id, module) in self.modules() {
+            let metadata = &module.metadata;
+            for rid in 1..=metadata.row_count(TableId::TypeRef) {
+                let token = TableId::TypeRef.token(rid);
+                if let Err(reason) = self.type_ref(id, rid, 0)? {
+                    unresolved.push(UnresolvedReference {
+                        module: id,
+                        requesting_assembly: module.display_name(),
+                        token,
+                        name: metadata.type_full_name(token)?,
+                        reason,
+                    });
+                }
+            }
+            for rid in 1..=metadata.row_count(TableId::MemberRef) {
+                let token = TableId::MemberRef.token(rid);
+                if let Err(reason @ UnresolvedReason::MemberNotFound { .. }) =
+                    self.member_ref(id, rid)?
+                {
+                    unresolved.push(UnresolvedReference {
+                        module: id,
+                        requesting_assembly: module.display_name(),
+                        token,
+                        name: self.member_ref_name(id, rid)?,
+                        reason,
+                    });
+                }
+            }
+        }
+        Ok(unresolved)
+    }
+
+    fn resolve_type_inner(
+        &self,
+        module: ModuleId,
+        token: u32,
+        depth: usize,
+    ) -> Result<Resolve<TypeHandle>> {
+        match TableId::from_token(token) {
+            Some((TableId::TypeDef, _)) => Ok(Ok(TypeHandle { module, token })),
+            Some((TableId::TypeRef, rid)) => self.type_ref(module, rid, depth),
+            Some((TableId::TypeSpec, rid)) => {
+                let metadata = &self.module(module).metadata;
+                let blob = metadata.blob(metadata.column(TableId::TypeSpec, rid, 0)?)?;
+                match TypeSig::parse(blob)? {
+                    TypeSig::GenericInst { definition, .. } => {
+                        self.resolve_type_inner(module, definition, depth + 1)
+                    }
+                    other => Ok(Err(UnresolvedReason::TypeNotFound {
+                        type_name: format!("{other:?}"),
+                        scope: "a type specification".into(),
+                    })),
+                }
+            }
+            _ => Err(Error::BadMetadata(format!(
+                "token {token:#010x} is not a type"
+            ))),
+        }
+    }
+
+    /// Resolves `TypeRef` row `rid` of `module`.
+    fn type_ref(&self, module: ModuleId, rid: u32, depth: usize) -> Result<Resolve<TypeHandle>> {
+        if depth > MAX_DEPTH {
+            return Ok(Err(UnresolvedReason::Cycle));
+        }
+        let metadata = &self.module(module).metadata;
+        let row = metadata.row::<TypeRefRow>(rid)?;
+        let namespace = metadata.string(row.namespace)?;
+        let name = metadata.string(row.name)?;
+        let scope = row.resolution_scope;
+
+        match TableId::from_token(scope) {
+            // A nil scope refers to an exported type of this assembly.
+            _ if token_rid(scope) == 0 => {
+                self.lookup_in_assembly(module, namespace, name, depth + 1)
+            }
+            Some((TableId::Module, _)) => {
+                Ok(self.find_type(module, namespace, name).ok_or_else(|| {
+                    UnresolvedReason::TypeNotFound {
+                        type_name: full_name(namespace, name),
+                        scope: self.module(module).display_name(),
+                    }
+                }))
+            }
+            Some((TableId::ModuleRef, scope_rid)) => {
+                let module_name = metadata.string(metadata.row::<ModuleRefRow>(scope_rid)?.name)?;
+                let Some(target) = self.find_module(module, module_name) else {
+                    return Ok(Err(UnresolvedReason::ModuleNotLoaded(
+                        module_name.to_string(),
+                    )));
+                };
+                Ok(self.find_type(target, namespace, name).ok_or_else(|| {
+                    UnresolvedReason::TypeNotFound {
+                        type_name: full_name(namespace, name),
+                        scope: module_name.to_string(),
+                    }
+                }))
+            }
+            Some((TableId::AssemblyRef, scope_rid)) => {
+                let reference = metadata.assembly_ref_identity(scope_rid)?;
+                match self.find_assembly(&reference) {
+                    Some(target) => self.lookup_in_assembly(target, namespace, name, depth + 1),
+                    None => Ok(Err(UnresolvedReason::AssemblyNotLoaded(reference))),
+                }
+            }
+            Some((TableId::TypeRef, outer_rid)) => {
+                let outer = match self.type_ref(module, outer_rid, depth + 1)? {
+                    Ok(outer) => outer,
+                    Err(reason) => return Ok(Err(reason)),
+                };
+                self.find_nested(outer, name)
+            }
+            _ => Err(Error::BadMetadata(format!(
+                "TypeRef {rid} has invalid resolution scope {scope:#010x}"
+            ))),
+        }
+    }
+
+    /// Finds a type defined in, or exported by, the assembly whose manifest
+    /// is `module`.
+    fn lookup_in_assembly(
+        &self,
+        module: ModuleId,
+        namespace: &str,
+        name: &str,
+        depth: usize,
+    ) -> Result<Resolve<TypeHandle>> {
+        if depth > MAX_DEPTH {
+            return Ok(Err(UnresolvedReason::Cycle));
+        }
+        if let Some(found) = self.find_type(module, namespace, name) {
+            return Ok(Ok(found));
+        }
+        let metadata = &self.module(module).metadata;
+        for rid in 1..=metadata.row_count(TableId::ExportedType) {
+            let row = metadata.row::<ExportedTypeRow>(rid)?;
+            if metadata.string(row.name)? != name || metadata.string(row.namespace)? != namespace {
+                continue;
+            }
+            return match TableId::from_token(row.implementation) {
+                Some((TableId::AssemblyRef, scope_rid)) => {
+                    let reference = metadata.assembly_ref_identity(scope_rid)?;
+                    match self.find_assembly(&reference) {
+                        Some(target) => self.lookup_in_assembly(target, namespace, name, depth + 1),
+                        None => Ok(Err(UnresolvedReason::AssemblyNotLoaded(reference))),
+                    }
+                }
+                Some((TableId::File, file_rid)) => {
+                    let file = metadata.string(metadata.column(TableId::File, file_rid, 1)?)?;
+                    match self.find_module(module, file) {
+                        Some(target) => {
+                            Ok(self.find_type(target, namespace, name).ok_or_else(|| {
+                                UnresolvedReason::TypeNotFound {
+                                    type_name: full_name(namespace, name),
+                                    scope: file.to_string(),
+                                }
+                            }))
+                        }
+                        None => Ok(Err(UnresolvedReason::ModuleNotLoaded(file.to_string()))),
+                    }
+                }
+                // Nested exported types are found through their enclosing type.
+                _ => continue,
+            };
+        }
+        Ok(Err(UnresolvedReason::TypeNotFound {
+            type_name: full_name(namespace, name),
+            scope: self.module(module).display_name(),
+        }))
+    }
+
+    /// Finds the type nested in `outer` named `name`.
+    fn find_nested(&self, outer: TypeHandle, name: &str) -> Result<Resolve<TypeHandle>> {
+        let metadata = &self.module(outer.module).metadata;
+        for nested in metadata.nested_types(token_rid(outer.token))? {
+            if metadata.string(metadata.row::<TypeDefRow>(nested)?.name)? == name {
+                return Ok(Ok(TypeHandle {
+                    module: outer.module,
+                    token: TableId::TypeDef.token(nested),
+                }));
+            }
+        }
+        Ok(Err(UnresolvedReason::TypeNotFound {
+            type_name: format!("{}+{name}", self.type_name(outer)?),
+            scope: self.module(outer.module).display_name(),
+        }))
+    }
+
+    /// Finds a module by file name, preferring one next to `from`.
+    fn find_module(&self, from: ModuleId, name: &str) -> Option<ModuleId> {
+        let directory = self.module(from).path.as_deref().and_then(Path::parent);
+        let matches = |module: &LoadedModule| module.module_name.eq_ignore_ascii_case(name);
+        self.modules()
+            .find(|(_, module)| {
+                matches(module) && module.path.as_deref().and_then(Path::parent) == directory
+            })
+            .or_else(|| self.modules().find(|(_, module)| matches(module)))
+            .map(|(id, _)| id)
+    }
+
+    fn resolve_member_inner(&self, module: ModuleId, token: u32) -> Result<Resolve<MemberHandle>> {
+        match TableId::from_token(token) {
+            Some((TableId::MethodDef | TableId::Field, _)) => {
+                Ok(Ok(MemberHandle { module, token }))
+            }
+            Some((TableId::MemberRef, rid)) => self.member_ref(module, rid),
+            Some((TableId::MethodSpec, rid)) => {
+                let method = self
+                    .module(module)
+                    .metadata
+                    .row::<MethodSpecRow>(rid)?
+                    .method;
+                self.resolve_member_inner(module, method)
+            }
+            _ => Err(Error::BadMetadata(format!(
+                "token {token:#010x} is not a member"
+            ))),
+        }
+    }
+
+    /// Resolves `MemberRef` row `rid` of `module`, searching the parent type
+    /// and then its base types.
+    fn member_ref(&self, module: ModuleId, rid: u32) -> Result<Resolve<MemberHandle>> {
+        let metadata = &self.module(module).metadata;
+        let row = metadata.row::<MemberRefRow>(rid)?;
+        let name = metadata.string(row.name)?;
+        let signature = metadata.blob(row.signature)?;
+
+        let parent = match TableId::from_token(row.class) {
+            // A vararg call site refers to the method definition directly.
+            Some((TableId::MethodDef, _)) => {
+                return Ok(Ok(MemberHandle {
+                    module,
+                    token: row.class,
+                }));
+            }
+            Some((TableId::ModuleRef, scope_rid)) => {
+                let module_name = metadata.string(metadata.row::<ModuleRefRow>(scope_rid)?.name)?;
+                match self.find_module(module, module_name) {
+                    // Global members belong to the `<Module>` type.
+                    Some(target) => TypeHandle {
+                        module: target,
+                        token: TableId::TypeDef.token(1),
+                    },
+                    None => {
+                        return Ok(Err(UnresolvedReason::ModuleNotLoaded(
+                            module_name.to_string(),
+                        )));
+                    }
+                }
+            }
+            _ => match self.resolve_type_inner(module, row.class, 0)? {
+                Ok(parent) => parent,
+                Err(reason) => return Ok(Err(reason)),
+            },
+        };
+
+        let is_field = signature.first().map(|b| b & 0x0f) == Some(CALLCONV_FIELD);
+        let mut current = Some(parent);
+        let mut depth = 0;
+        while let Some(ty) = current {
+            if let Some(found) = self.find_member(ty, name, is_field, module, signature)? {
+                return Ok(Ok(found));
+            }
+            depth += 1;
+            current = if depth > MAX_DEPTH {
+                None
+            } else {
+                self.base_type(ty)?
+            };
+        }
+        Ok(Err(UnresolvedReason::MemberNotFound {
+            type_name: self.type_name(parent)?,
+        }))
+    }
+
+    /// Finds a member of `ty` named `name` whose signature matches
+    /// `signature` from `sig_module`.
+    fn find_member(
+        &self,
+        ty: TypeHandle,
+        name: &str,
+        is_field: bool,
+        sig_module: ModuleId,
+        signature: &[u8],
+    ) -> Result<Option<MemberHandle>> {
+        let metadata = &self.module(ty.module).metadata;
+        let type_rid = token_rid(ty.token);
+        if is_field {
+            let wanted = parse_field_sig(signature)?;
+            for field in metadata.type_def_fields(type_rid)? {
+                let row = metadata.row::<FieldRow>(field)?;
+                if metadata.string(row.name)? != name {
+                    continue;
+                }
+                let candidate = parse_field_sig(metadata.blob(row.signature)?)?;
+                if self.type_sig_eq(sig_module, &wanted, ty.module, &candidate)? {
+                    return Ok(Some(MemberHandle {
+                        module: ty.module,
+                        token: TableId::Field.token(field),
+                    }));
+                }
+            }
+        } else {
+            let wanted = MethodSig::parse(signature)?;
+            for method in metadata.type_def_methods(type_rid)? {
+                let row = metadata.row::<MethodDefRow>(method)?;
+                if metadata.string(row.name)? != name {
+                    continue;
+                }
+                let candidate = MethodSig::parse(metadata.blob(row.signature)?)?;
+                if self.method_sig_eq(sig_module, &wanted, ty.module, &candidate)? {
+                    return Ok(Some(MemberHandle {
+                        module: ty.module,
+                        token: TableId::MethodDef.token(method),
+                    }));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Compares a reference signature with a definition signature. Vararg
+    /// call sites only compare the fixed parameters.
+    fn method_sig_eq(
+        &self,
+        a_module: ModuleId,
+        a: &MethodSig,
+        b_module: ModuleId,
+        b: &MethodSig,
+    ) -> Result<bool> {
+        let fixed = a.sentinel.unwrap_or(a.params.len());
+        if a.calling_convention != b.calling_convention
+            || a.generic_param_count != b.generic_param_count
+            || fixed != b.params.len()
+            || !self.type_sig_eq(a_module, &a.return_type, b_module, &b.return_type)?
+        {
+            return Ok(false);
+        }
+        for (x, y) in a.params[..fixed].iter().zip(&b.params) {
+            if !self.type_sig_eq(a_module, x, b_module, y)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Compares type signatures from two modules, resolving type tokens.
+    fn type_sig_eq(
+        &self,
+        a_module: ModuleId,
+        a: &TypeSig,
+        b_module: ModuleId,
+        b: &TypeSig,
+    ) -> Result<bool> {
+        Ok(match (a, b) {
+            (TypeSig::Class(x), TypeSig::Class(y))
+            | (TypeSig::ValueType(x), TypeSig::ValueType(y)) => {
+                self.type_token_eq(a_module, *x, b_module, *y)?
+            }
+            (TypeSig::Ptr(x), TypeSig::Ptr(y))
+            | (TypeSig::ByRef(x), TypeSig::ByRef(y))
+            | (TypeSig::SzArray(x), TypeSig::SzArray(y))
+            | (TypeSig::Pinned(x), TypeSig::Pinned(y)) => {
+                self.type_sig_eq(a_module, x, b_module, y)?
+            }
+            (TypeSig::Array(x, x_shape), TypeSig::Array(y, y_shape)) => {
+                x_shape == y_shape && self.type_sig_eq(a_module, x, b_module, y)?
+            }
+            (
+                TypeSig::GenericInst {
+                    value_type: x_value,
+                    definition: x_def,
+                    args: x_args,
+                },
+                TypeSig::GenericInst {
+                    value_type: y_value,
+                    definition: y_def,
+                    args: y_args,
+                },
+            ) => {
+                if x_value != y_value
+                    || x_args.len() != y_args.len()
+                    || !self.type_token_eq(a_module, *x_def, b_module, *y_def)?
+                {
+                    return Ok(false);
+                }
+                for (x, y) in x_args.iter().zip(y_args) {
+                    if !self.type_sig_eq(a_module, x, b_module, y)? {
+                        return Ok(false);
+                    }
+                }
+                true
+            }
+            (TypeSig::FnPtr(x), TypeSig::FnPtr(y)) => {
+                x.sentinel == y.sentinel && self.method_sig_eq(a_module, x, b_module, y)?
+            }
+            (
+                TypeSig::Modified {
+                    required: x_required,
+                    modifier: x_modifier,
+                    ty: x,
+                },
+                TypeSig::Modified {
+                    required: y_required,
+                    modifier: y_modifier,
+                    ty: y,
+                },
+            ) => {
+                x_required == y_required
+                    && self.type_token_eq(a_module, *x_modifier, b_module, *y_modifier)?
+                    && self.type_sig_eq(a_module, x, b_module, y)?
+            }
+            (x, y) => x == y,
+        })
+    }
+
+    /// Compares type tokens from two modules: by definition when both
+    /// resolve, otherwise by full name.
+    fn type_token_eq(
+        &self,
+        a_module: ModuleId,
+        a: u32,
+        b_module: ModuleId,
+        b: u32,
+    ) -> Result<bool> {
+        if a_module == b_module && a == b {
+            return Ok(true);
+        }
+        let is_spec = |token: u32| token >> 24 == TableId::TypeSpec as u32;
+        if is_spec(a) || is_spec(b) {
+            if !(is_spec(a) && is_spec(b)) {
+                return Ok(false);
+            }
+            let spec = |module: ModuleId, token: u32| -> Result<TypeSig> {
+                let metadata = &self.module(module).metadata;
+                TypeSig::parse(metadata.blob(metadata.column(
+                    TableId::TypeSpec,
+                    token_rid(token),
+                    0,
+                )?)?)
+            };
+            return self.type_sig_eq(a_module, &spec(a_module, a)?, b_module, &spec(b_module, b)?);
+        }
+        match (
+            self.resolve_type(a_module, a)?,
+            self.resolve_type(b_module, b)?,
+        ) {
+            (Some(x), Some(y)) => Ok(x == y),
+            _ => Ok(self.module(a_module).metadata.type_full_name(a)?
+                == self.module(b_module).metadata.type_full_name(b)?),
+        }
+    }
+
+    /// Returns `Type::Member` for a `MemberRef`.
+    fn member_ref_name(&self, module: ModuleId, rid: u32) -> Result<String> {
+        let metadata = &self.module(module).metadata;
+        let row = metadata.row::<MemberRefRow>(rid)?;
+        let parent = match TableId::from_token(row.class) {
+            Some((TableId::TypeDef | TableId::TypeRef, _)) => metadata.type_full_name(row.class)?,
+            Some((TableId::TypeSpec, spec)) => {
+                match TypeSig::parse(metadata.blob(metadata.column(
+                    TableId::TypeSpec,
+                    spec,
+                    0,
+                )?)?)? {
+                    TypeSig::GenericInst { definition, .. } => {
+                        metadata.type_full_name(definition)?
+                    }
+                    other => format!("{other:?}"),
+                }
+            }
+            Some((TableId::ModuleRef, scope)) => metadata
+                .string(metadata.row::<ModuleRefRow>(scope)?.name)?
+                .to_string(),
+            _ => String::from("<Module>"),
+        };
+        Ok(format!("{parent}::{}", metadata.string(row.name)?))
+    }
+}
+
+fn full_name(namespace: &str, name: &str) -> String {
+    if namespace.is_empty() {
+        name.to_string()
+    } else {
+        format!("{namespace}.{name}")
+    }
+}
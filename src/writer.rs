@@ -0,0 +1,18 @@
+//! Pure-Rust writers for CLI metadata and IL-only PE images.
+//!
+//! The writers produce blobs that [`MetadataReader`](crate::MetadataReader)
+//! and the runtime can read, and work on every platform.
+
+mod compact;
+mod enc;
+mod metadata;
+mod pdb;
+mod pe;
+mod reference;
+mod signature;
+
+pub use enc::*;
+pub use metadata::*;
+pub use pe::*;
+
+pub(crate) use signature::{SIGNATURE_COLUMNS, remap_signature};
@@ -0,0 +1,356 @@
+//! Removing rows from a [`MetadataBuilder`] and sorting its tables, with
+//! every reference to the affected rows renumbered.
+
+use super::metadata::MetadataBuilder;
+use super::signature::{SIGNATURE_COLUMNS, remap_signature};
+use crate::error::{Error, Result};
+use crate::reader::{ColumnType, TableId, token_rid};
+
+/// Tables whose rows only describe rows of other tables. A row of these
+/// tables goes away with any row it refers to.
+const DEPENDENT_TABLES: &[TableId] = &[
+    TableId::InterfaceImpl,
+    TableId::MemberRef,
+    TableId::Constant,
+    TableId::CustomAttribute,
+    TableId::FieldMarshal,
+    TableId::DeclSecurity,
+    TableId::ClassLayout,
+    TableId::FieldLayout,
+    TableId::EventMap,
+    TableId::PropertyMap,
+    TableId::MethodSemantics,
+    TableId::MethodImpl,
+    TableId::ImplMap,
+    TableId::FieldRva,
+    TableId::NestedClass,
+    TableId::GenericParam,
+    TableId::MethodSpec,
+    TableId::GenericParamConstraint,
+];
+
+/// Columns that hold the first row of a run of child rows (II.22): the run
+/// ends where the next owner row's run starts.
+const LIST_COLUMNS: &[(TableId, usize, TableId)] = &[
+    (TableId::TypeDef, 4, TableId::Field),
+    (TableId::TypeDef, 5, TableId::MethodDef),
+    (TableId::MethodDef, 5, TableId::Param),
+    (TableId::PropertyMap, 1, TableId::Property),
+    (TableId::EventMap, 1, TableId::Event),
+];
+
+/// The sorted tables with their primary and secondary key columns, in an
+/// order where every table's keys are final before it is sorted.
+const SORT_KEYS: &[(TableId, usize, Option<usize>)] = &[
+    (TableId::InterfaceImpl, 0, Some(1)),
+    (TableId::GenericParam, 2, Some(0)),
+    (TableId::GenericParamConstraint, 0, None),
+    (TableId::DeclSecurity, 1, None),
+    (TableId::Constant, 1, None),
+    (TableId::FieldMarshal, 0, None),
+    (TableId::ClassLayout, 2, None),
+    (TableId::FieldLayout, 1, None),
+    (TableId::MethodSemantics, 2, None),
+    (TableId::MethodImpl, 0, None),
+    (TableId::ImplMap, 1, None),
+    (TableId::FieldRva, 1, None),
+    (TableId::NestedClass, 0, None),
+    (TableId::CustomAttribute, 0, None),
+];
+
+const INDIRECTION_TABLES: &[TableId] = &[
+    TableId::FieldPtr,
+    TableId::MethodPtr,
+    TableId::ParamPtr,
+    TableId::EventPtr,
+    TableId::PropertyPtr,
+];
+
+/// New rids for the rows of some tables: `maps[table][old rid - 1]`, `None`
+/// for a removed row. Tables without a map keep their rids.
+struct Renumbering {
+    maps: Vec<Option<Vec<Option<u32>>>>,
+}
+
+impl Renumbering {
+    fn new() -> Self {
+        Self {
+            maps: vec![None; 64],
+        }
+    }
+
+    fn rid(&self, table: TableId, rid: u32) -> Option<u32> {
+        match &self.maps[table as usize] {
+            Some(map) if rid != 0 => map.get(rid as usize - 1).copied().flatten(),
+            _ => Some(rid),
+        }
+    }
+
+    fn token(&self, token: u32) -> Option<u32> {
+        let (table, rid) = TableId::from_token(token)?;
+        self.rid(table, rid).map(|rid| table.token(rid))
+    }
+
+    /// Returns the new start of a run of `child` rows that began at `start`:
+    /// the first surviving row at or after it.
+    fn list_start(&self, child: TableId, start: u32) -> u32 {
+        match &self.maps[child as usize] {
+            Some(map) => {
+                let before = (start as usize).saturating_sub(1).min(map.len());
+                map[..before].iter().filter(|rid| rid.is_some()).count() as u32 + 1
+            }
+            None => start,
+        }
+    }
+}
+
+impl MetadataBuilder {
+    /// Removes the rows named by `tokens` and every row that belongs to
+    /// them: the fields, methods and nested types of a removed type, the
+    /// parameters of a removed method, the properties and events of a
+    /// removed map row, and the rows of tables such as `CustomAttribute`,
+    /// `Constant`, `MethodSemantics` or `GenericParam` that describe a
+    /// removed row.
+    ///
+    /// Later rows move up, and references to them are renumbered in every
+    /// table and in the type tokens of signature blobs. Fails if a row that
+    /// stays, or a signature, still refers to a removed row.
+    pub fn remove_rows(&mut self, tokens: impl IntoIterator<Item = u32>) -> Result<()> {
+        let removed = self.removal_closure(tokens)?;
+        self.remove_marked(&removed)
+    }
+
+    /// Returns, per table and rid, whether [`remove_rows`](Self::remove_rows)
+    /// would remove the row when given `tokens`.
+    pub(super) fn removal_closure(
+        &self,
+        tokens: impl IntoIterator<Item = u32>,
+    ) -> Result<Vec<Vec<bool>>> {
+        self.check_no_indirection()?;
+        let mut removed: Vec<Vec<bool>> = TableId::ALL
+            .iter()
+            .map(|&table| vec![false; self.row_count(table) as usize + 1])
+            .collect();
+        removed.resize(64, Vec::new());
+        for token in tokens {
+            let (table, rid) = TableId::from_token(token)
+                .filter(|&(table, rid)| rid != 0 && rid <= self.row_count(table))
+                .ok_or_else(|| Error::BadIndex(format!("no row for token {token:#010x}")))?;
+            removed[table as usize][rid as usize] = true;
+        }
+        self.cascade_removal(&mut removed)?;
+        Ok(removed)
+    }
+
+    /// Removes the rows flagged in `removed`, as returned by
+    /// [`removal_closure`](Self::removal_closure), and renumbers the rest.
+    pub(super) fn remove_marked(&mut self, removed: &[Vec<bool>]) -> Result<()> {
+        let mut renumbering = Renumbering::new();
+        for &table in TableId::ALL {
+            let flags = &removed[table as usize];
+            if !flags.iter().any(|&r| r) {
+                continue;
+            }
+            let mut next = 0;
+            let map = flags[1..]
+                .iter()
+                .map(|&gone| {
+                    (!gone).then(|| {
+                        next += 1;
+                        next
+                    })
+                })
+                .collect();
+            renumbering.maps[table as usize] = Some(map);
+        }
+        if renumbering.maps.iter().all(Option::is_none) {
+            return Ok(());
+        }
+        for &table in TableId::ALL {
+            if renumbering.maps[table as usize].is_some() {
+                let flags = &removed[table as usize];
+                let mut rid = 0;
+                self.tables[table as usize].retain(|_| {
+                    rid += 1;
+                    !flags[rid]
+                });
+            }
+        }
+        self.renumber(&renumbering)
+    }
+
+    /// Sorts the tables marked as sorted (see
+    /// [`set_sorted_tables`](Self::set_sorted_tables)) by their key columns,
+    /// as ECMA-335 requires for optimized metadata, and renumbers the
+    /// references to rows that moved. Rows with equal keys keep their order.
+    pub fn sort_tables(&mut self) -> Result<()> {
+        for &(table, primary, secondary) in SORT_KEYS {
+            if self.sorted & (1 << table as u8) == 0 {
+                continue;
+            }
+            let columns = table.columns();
+            let key = |row: &Vec<u32>, column: usize| match columns[column].ty {
+                ColumnType::Coded(_) if token_rid(row[column]) == 0 => 0,
+                ColumnType::Coded(coded) => coded.encode(row[column]).unwrap_or(u32::MAX),
+                _ => row[column],
+            };
+            let rows = &self.tables[table as usize];
+            let mut order: Vec<usize> = (0..rows.len()).collect();
+            order.sort_by_key(|&i| {
+                (
+                    key(&rows[i], primary),
+                    secondary.map_or(0, |column| key(&rows[i], column)),
+                )
+            });
+            if order.iter().enumerate().all(|(new, &old)| new == old) {
+                continue;
+            }
+            let mut map = vec![None; rows.len()];
+            for (new, &old) in order.iter().enumerate() {
+                map[old] = Some(new as u32 + 1);
+            }
+            let sorted = order.iter().map(|&old| rows[old].clone()).collect();
+            self.tables[table as usize] = sorted;
+            let mut renumbering = Renumbering::new();
+            renumbering.maps[table as usize] = Some(map);
+            self.renumber(&renumbering)?;
+        }
+        Ok(())
+    }
+
+    fn check_no_indirection(&self) -> Result<()> {
+        match INDIRECTION_TABLES
+            .iter()
+            .find(|&&table| self.row_count(table) != 0)
+        {
+            Some(table) => Err(Error::BadMetadata(format!(
+                "cannot renumber rows of metadata with a {table} table"
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Extends `removed` with the rows that belong to removed rows.
+    fn cascade_removal(&self, removed: &mut [Vec<bool>]) -> Result<()> {
+        let mark = |removed: &mut [Vec<bool>], table: TableId, rid: u32| -> bool {
+            let slot = &mut removed[table as usize][rid as usize];
+            !std::mem::replace(slot, true)
+        };
+        loop {
+            let mut changed = false;
+            for &(owner, column, child) in LIST_COLUMNS {
+                let count = self.row_count(owner);
+                for rid in 1..=count {
+                    if !removed[owner as usize][rid as usize] {
+                        continue;
+                    }
+                    let start = self.row_values(owner, rid)?[column].max(1);
+                    let end = if rid < count {
+                        self.row_values(owner, rid + 1)?[column]
+                    } else {
+                        self.row_count(child) + 1
+                    };
+                    for child_rid in start..end.min(self.row_count(child) + 1) {
+                        changed |= mark(removed, child, child_rid);
+                    }
+                }
+            }
+            // Types nested in a removed type.
+            for rid in 1..=self.row_count(TableId::NestedClass) {
+                let row = self.row_values(TableId::NestedClass, rid)?;
+                if removed[TableId::TypeDef as usize]
+                    .get(row[1] as usize)
+                    .copied()
+                    .unwrap_or(false)
+                {
+                    changed |= mark(removed, TableId::TypeDef, row[0]);
+                }
+            }
+            for &table in DEPENDENT_TABLES {
+                let columns = table.columns();
+                for rid in 1..=self.row_count(table) {
+                    if removed[table as usize][rid as usize] {
+                        continue;
+                    }
+                    let row = self.row_values(table, rid)?;
+                    let refers_to_removed = columns.iter().zip(row).any(|(column, &value)| {
+                        let target = match column.ty {
+                            ColumnType::Table(target) => Some((target, value)),
+                            ColumnType::Coded(_) => TableId::from_token(value),
+                            _ => None,
+                        };
+                        target.is_some_and(|(target, rid)| {
+                            removed[target as usize]
+                                .get(rid as usize)
+                                .copied()
+                                .unwrap_or(false)
+                        })
+                    });
+                    if refers_to_removed {
+                        changed |= mark(removed, table, rid);
+                    }
+                }
+            }
+            if !changed {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Rewrites every row index, token and list start through `renumbering`,
+    /// and the type tokens of signatures if type rows moved.
+    fn renumber(&mut self, renumbering: &Renumbering) -> Result<()> {
+        for &table in TableId::ALL {
+            let columns = table.columns();
+            for (index, row) in self.tables[table as usize].iter_mut().enumerate() {
+                for (column, value) in row.iter_mut().enumerate() {
+                    if let Some(&(_, _, child)) = LIST_COLUMNS
+                        .iter()
+                        .find(|&&(owner, list, _)| owner == table && list == column)
+                    {
+                        *value = renumbering.list_start(child, *value);
+                        continue;
+                    }
+                    let renumbered = match columns[column].ty {
+                        ColumnType::Table(target) => renumbering.rid(target, *value),
+                        ColumnType::Coded(_) if token_rid(*value) != 0 => renumbering.token(*value),
+                        _ => continue,
+                    };
+                    *value = renumbered.ok_or_else(|| {
+                        Error::BadMetadata(format!(
+                            "{table} row {} column {} refers to a removed row",
+                            index + 1,
+                            columns[column].name
+                        ))
+                    })?;
+                }
+            }
+        }
+
+        let types_moved = [TableId::TypeDef, TableId::TypeRef, TableId::TypeSpec]
+            .iter()
+            .any(|&table| renumbering.maps[table as usize].is_some());
+        if !types_moved {
+            return Ok(());
+        }
+        for &(table, column, is_type) in SIGNATURE_COLUMNS {
+            for rid in 1..=self.row_count(table) {
+                let index = self.row_values(table, rid)?[column];
+                let blob = self.blob_at(index)?;
+                let rewritten = remap_signature(blob, is_type, &mut |token| {
+                    let target = renumbering.token(token).ok_or_else(|| {
+                        Error::BadSignature(format!(
+                            "{table} row {rid} signature refers to removed type {token:#010x}"
+                        ))
+                    })?;
+                    Ok((target, None))
+                })?;
+                if rewritten != blob {
+                    let index = self.blob(&rewritten);
+                    self.set_column(table, rid, column, index)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+//! Generation of Edit-and-Continue metadata deltas.
+
+use std::collections::{BTreeMap, HashMap};
+
+use super::metadata::{
+    MetadataBuilder, TablesFormat, check_columns, encode_user_string, write_compressed_u32,
+};
+use crate::error::{Error, Result};
+use crate::reader::{EncFuncCode, EncLogRow, EncMapRow, MetadataReader, ModuleRow, TableId};
+
+/// Builds an Edit-and-Continue delta against a baseline: the original
+/// module, or the result of applying earlier deltas to it.
+///
+/// Rows and heap entries use the numbering of the combined metadata: heap
+/// indices returned by the builder continue after the baseline's heaps and
+/// new rows are numbered after the baseline's rows, exactly as the runtime
+/// and [`MetadataReader::apply_delta`] interpret them.
+#[derive(Debug, Clone)]
+pub struct EncDeltaBuilder {
+    module: ModuleRow,
+    string_base: u32,
+    blob_base: u32,
+    guid_base: u32,
+    user_string_base: u32,
+    strings: Vec<u8>,
+    string_map: HashMap<String, u32>,
+    blobs: Vec<u8>,
+    blob_map: HashMap<Vec<u8>, u32>,
+    guids: Vec<u8>,
+    user_strings: Vec<u8>,
+    row_counts: [u32; 64],
+    rows: BTreeMap<u32, Vec<u32>>,
+    log: Vec<(u32, EncFuncCode)>,
+}
+
+impl EncDeltaBuilder {
+    /// Starts a delta whose `Module` row carries `enc_id` as the new
+    /// generation id and the baseline's `EncId` (or `Mvid` for the first
+    /// generation) as `EncBaseId`.
+    pub fn new(baseline: &MetadataReader, enc_id: [u8; 16]) -> Result<Self> {
+        if baseline.row_count(TableId::Module) == 0 {
+            return Err(Error::BadMetadata("baseline has no Module row".into()));
+        }
+        let mut row_counts = [0; 64];
+        for &table in TableId::ALL {
+            row_counts[table as usize] = baseline.row_count(table);
+        }
+        let mut builder = Self {
+            module: baseline.row::<ModuleRow>(1)?,
+            string_base: heap_len(baseline.strings_heap())?,
+            blob_base: heap_len(baseline.blob_heap())?,
+            guid_base: heap_len(baseline.guid_heap())? / 16,
+            user_string_base: heap_len(baseline.user_string_heap())?,
+            strings: Vec::new(),
+            string_map: HashMap::new(),
+            blobs: Vec::new(),
+            blob_map: HashMap::new(),
+            guids: Vec::new(),
+            user_strings: Vec::new(),
+            row_counts,
+            rows: BTreeMap::new(),
+            log: Vec::new(),
+        };
+        let base_id = match builder.module.enc_id {
+            0 => builder.module.mvid,
+            id => id,
+        };
+        builder.module.enc_base_id = base_id;
+        builder.module.enc_id = builder.guid(enc_id);
+        builder.module.generation = builder.module.generation.wrapping_add(1);
+        Ok(builder)
+    }
+
+    /// Returns the `Module` row the delta will carry.
+    pub fn module(&self) -> &ModuleRow {
+        &self.module
+    }
+
+    /// Adds a string to the delta's `#Strings` heap and returns its index
+    /// in the combined heap.
+    pub fn string(&mut self, value: &str) -> u32 {
+        if value.is_empty() {
+            return 0;
+        }
+        if let Some(&index) = self.string_map.get(value) {
+            return index;
+        }
+        let index = self.string_base + self.strings.len() as u32;
+        self.strings.extend_from_slice(value.as_bytes());
+        self.strings.push(0);
+        self.string_map.insert(value.to_string(), index);
+        index
+    }
+
+    /// Adds a blob to the delta's `#Blob` heap and returns its index in the
+    /// combined heap.
+    pub fn blob(&mut self, value: &[u8]) -> u32 {
+        if let Some(&index) = self.blob_map.get(value) {
+            return index;
+        }
+        let index = self.blob_base + self.blobs.len() as u32;
+        write_compressed_u32(&mut self.blobs, value.len() as u32);
+        self.blobs.extend_from_slice(value);
+        self.blob_map.insert(value.to_vec(), index);
+        index
+    }
+
+    /// Adds a GUID to the delta's `#GUID` heap and returns its 1-based index
+    /// in the combined heap.
+    pub fn guid(&mut self, value: [u8; 16]) -> u32 {
+        self.guids.extend_from_slice(&value);
+        self.guid_base + (self.guids.len() / 16) as u32
+    }
+
+    /// Adds a string to the delta's `#US` heap and returns its index in the
+    /// combined heap (the low 24 bits of its `mdString` token).
+    pub fn user_string(&mut self, value: &str) -> u32 {
+        let units: Vec<u16> = value.encode_utf16().collect();
+        let index = self.user_string_base + self.user_strings.len() as u32;
+        encode_user_string(&mut self.user_strings, &units);
+        index
+    }
+
+    /// Returns the number of rows `table` will have once the delta is
+    /// applied.
+    pub fn row_count(&self, table: TableId) -> u32 {
+        self.row_counts[table as usize]
+    }
+
+    /// Replaces an existing row of the baseline.
+    pub fn update_row(&mut self, token: u32, values: Vec<u32>) -> Result<()> {
+        let (table, _) = self.existing(token)?;
+        check_columns(table, &values)?;
+        if table == TableId::Module {
+            return Err(Error::BadMetadata(
+                "the delta's Module row is maintained by the builder".into(),
+            ));
+        }
+        self.rows.insert(token, values);
+        self.log.push((token, EncFuncCode::Default));
+        Ok(())
+    }
+
+    /// Adds a row to a table without a parent list (such as `TypeRef`,
+    /// `MemberRef` or `CustomAttribute`) and returns its token.
+    ///
+    /// Members (`MethodDef`, `Field`, `Param`, `Property` and `Event` rows)
+    /// must be added with the method naming their parent.
+    pub fn add_row(&mut self, table: TableId, values: Vec<u32>) -> Result<u32> {
+        if matches!(
+            table,
+            TableId::MethodDef
+                | TableId::Field
+                | TableId::Param
+                | TableId::Property
+                | TableId::Event
+                | TableId::Module
+                | TableId::EncLog
+                | TableId::EncMap
+        ) || table.is_debug_table()
+        {
+            return Err(Error::BadMetadata(format!(
+                "{table} rows cannot be added with add_row"
+            )));
+        }
+        self.push(table, values, None)
+    }
+
+    /// Adds a method to a type and returns the new `MethodDef` token.
+    pub fn add_method(&mut self, type_token: u32, values: Vec<u32>) -> Result<u32> {
+        self.add_member(EncFuncCode::AddMethod, type_token, values)
+    }
+
+    /// Adds a field to a type and returns the new `Field` token.
+    pub fn add_field(&mut self, type_token: u32, values: Vec<u32>) -> Result<u32> {
+        self.add_member(EncFuncCode::AddField, type_token, values)
+    }
+
+    /// Adds a parameter to a method and returns the new `Param` token.
+    pub fn add_param(&mut self, method_token: u32, values: Vec<u32>) -> Result<u32> {
+        self.add_member(EncFuncCode::AddParameter, method_token, values)
+    }
+
+    /// Adds a property to a `PropertyMap` row and returns the new
+    /// `Property` token.
+    pub fn add_property(&mut self, property_map_token: u32, values: Vec<u32>) -> Result<u32> {
+        self.add_member(EncFuncCode::AddProperty, property_map_token, values)
+    }
+
+    /// Adds an event to an `EventMap` row and returns the new `Event`
+    /// token.
+    pub fn add_event(&mut self, event_map_token: u32, values: Vec<u32>) -> Result<u32> {
+        self.add_member(EncFuncCode::AddEvent, event_map_token, values)
+    }
+
+    /// Records that a method's body changed without touching its row, so
+    /// that the runtime reloads it; the body itself travels in the IL
+    /// delta.
+    pub fn update_method_body(&mut self, method_token: u32) -> Result<()> {
+        let (table, _) = self.existing(method_token)?;
+        if table != TableId::MethodDef {
+            return Err(Error::BadMetadata(format!(
+                "token {method_token:#010x} is not a MethodDef"
+            )));
+        }
+        self.log.push((method_token, EncFuncCode::Default));
+        Ok(())
+    }
+
+    fn add_member(&mut self, code: EncFuncCode, parent: u32, values: Vec<u32>) -> Result<u32> {
+        let (parent_table, _, member_table, _) = code.member_list().unwrap();
+        let (table, _) = self.existing(parent)?;
+        if table != parent_table {
+            return Err(Error::BadMetadata(format!(
+                "{code:?} needs a {parent_table} token, got {parent:#010x}"
+            )));
+        }
+        self.push(member_table, values, Some((parent, code)))
+    }
+
+    fn push(
+        &mut self,
+        table: TableId,
+        values: Vec<u32>,
+        parent: Option<(u32, EncFuncCode)>,
+    ) -> Result<u32> {
+        check_columns(table, &values)?;
+        let count = &mut self.row_counts[table as usize];
+        *count += 1;
+        let token = table.token(*count);
+        if let Some(entry) = parent {
+            self.log.push(entry);
+        }
+        self.log.push((token, EncFuncCode::Default));
+        self.rows.insert(token, values);
+        Ok(token)
+    }
+
+    fn existing(&self, token: u32) -> Result<(TableId, u32)> {
+        match TableId::from_token(token) {
+            Some((table, rid)) if rid != 0 && rid <= self.row_counts[table as usize] => {
+                Ok((table, rid))
+            }
+            _ => Err(Error::BadMetadata(format!(
+                "token {token:#010x} does not name an existing row"
+            ))),
+        }
+    }
+
+    /// Writes the delta metadata blob.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut builder = MetadataBuilder::with_raw_heaps(
+            pad(&self.strings),
+            pad(&self.blobs),
+            self.guids.clone(),
+            pad(&self.user_strings),
+        );
+        builder.set_format(TablesFormat::MinimalDelta);
+        builder.add(&self.module);
+        builder.add(&EncMapRow {
+            token: TableId::Module.token(1),
+        });
+        // BTreeMap order sorts the rows by table and then by rid, which is
+        // the order EncMap requires.
+        for (&token, values) in &self.rows {
+            let (table, _) = TableId::from_token(token).unwrap();
+            builder.add_row(table, values.clone())?;
+            builder.add(&EncMapRow { token });
+        }
+        for &(token, code) in &self.log {
+            builder.add(&EncLogRow {
+                token,
+                func_code: code.to_u32(),
+            });
+        }
+        builder.to_bytes()
+    }
+}
+
+fn heap_len(heap: &[u8]) -> Result<u32> {
+    u32::try_from(heap.len()).map_err(|_| Error::BadMetadata("heap is too large".into()))
+}
+
+/// Pads a delta heap to the 4-byte stream alignment, so that the next
+/// generation's indices start where the runtime expects them.
+fn pad(heap: &[u8]) -> Vec<u8> {
+    let mut heap = heap.to_vec();
+    heap.resize(heap.len().next_multiple_of(4), 0);
+    heap
+}
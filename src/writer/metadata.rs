@@ -0,0 +1,721 @@
+//! Serialization of CLI metadata: heaps, tables and the metadata root.
+
+use std::collections::HashMap;
+
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use crate::reader::{
+    CodedIndex, ColumnType, HEAP_BLOB_4, HEAP_ENC_DELTA, HEAP_GUID_4, HEAP_STRING_4,
+    METADATA_SIGNATURE, MetadataReader, PdbStream, TableId, TableRow,
+};
+
+/// Default metadata version string written to the root.
+pub const DEFAULT_METADATA_VERSION: &str = "v4.0.30319";
+
+/// Layout of the tables stream written by [`MetadataBuilder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TablesFormat {
+    /// An optimized `#~` stream. Falls back to `#-` if the builder has
+    /// `*Ptr` indirection tables, which only uncompressed metadata may use.
+    #[default]
+    Compressed,
+    /// An unoptimized `#-` stream, as written by the runtime's emitter for
+    /// images that were edited in place.
+    Uncompressed,
+    /// An Edit-and-Continue delta: a `#-` stream with a `#JTD` marker, in
+    /// which every heap and table index is 4 bytes wide.
+    MinimalDelta,
+}
+
+/// Builds a metadata blob (the data starting at the `BSJB` root) from rows
+/// and heap entries.
+///
+/// Rows are stored as decoded column values, in the same form
+/// [`MetadataReader::columns`] returns them: coded indices are metadata
+/// tokens and are encoded when the blob is written. Heap entries are
+/// deduplicated.
+///
+/// ```
+/// use mscoree::{MetadataBuilder, MetadataReader, ModuleRow, TypeDefRow};
+///
+/// let mut builder = MetadataBuilder::new();
+/// let module = ModuleRow {
+///     name: builder.string("Demo.dll"),
+///     mvid: builder.guid([7; 16]),
+///     ..Default::default()
+/// };
+/// builder.add(&module);
+/// let point = TypeDefRow {
+///     name: builder.string("Point"),
+///     namespace: builder.string("Demo"),
+///     field_list: 1,
+///     method_list: 1,
+///     ..Default::default()
+/// };
+/// builder.add(&point);
+///
+/// let metadata = MetadataReader::from_bytes(builder.to_bytes()?)?;
+/// assert_eq!(metadata.type_full_name(0x0200_0001)?, "Demo.Point");
+/// # Ok::<(), mscoree::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct MetadataBuilder {
+    version: String,
+    format: TablesFormat,
+    pub(super) sorted: u64,
+    strings: Vec<u8>,
+    string_map: HashMap<Vec<u8>, u32>,
+    blobs: Vec<u8>,
+    blob_map: HashMap<Vec<u8>, u32>,
+    guids: Vec<u8>,
+    user_strings: Vec<u8>,
+    user_string_map: HashMap<Vec<u16>, u32>,
+    pub(super) tables: Vec<Vec<Vec<u32>>>,
+    pdb: Option<PdbStream>,
+}
+
+impl Default for MetadataBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetadataBuilder {
+    /// Creates an empty builder whose heaps hold only their mandatory
+    /// empty entries.
+    pub fn new() -> Self {
+        let mut builder = Self::with_raw_heaps(vec![0], vec![0], Vec::new(), vec![0]);
+        builder.sorted = default_sorted_tables();
+        builder
+    }
+
+    /// Creates a builder whose heaps start with the given bytes, verbatim.
+    /// Entries are not indexed for deduplication.
+    pub(crate) fn with_raw_heaps(
+        strings: Vec<u8>,
+        blobs: Vec<u8>,
+        guids: Vec<u8>,
+        user_strings: Vec<u8>,
+    ) -> Self {
+        Self {
+            version: DEFAULT_METADATA_VERSION.to_string(),
+            format: TablesFormat::Compressed,
+            sorted: 0,
+            strings,
+            string_map: HashMap::new(),
+            blobs,
+            blob_map: HashMap::new(),
+            guids,
+            user_strings,
+            user_string_map: HashMap::new(),
+            tables: vec![Vec::new(); 64],
+            pdb: None,
+        }
+    }
+
+    /// Creates a builder holding a copy of existing metadata.
+    ///
+    /// The heaps are copied byte for byte, so every heap index and row id of
+    /// `reader` stays valid in the builder and in the blob it writes.
+    pub fn from_reader(reader: &MetadataReader) -> Result<Self> {
+        let mut builder = Self::with_raw_heaps(
+            reader.strings_heap().to_vec(),
+            reader.blob_heap().to_vec(),
+            reader.guid_heap().to_vec(),
+            reader.user_string_heap().to_vec(),
+        );
+        if builder.strings.is_empty() {
+            builder.strings.push(0);
+        }
+        if builder.blobs.is_empty() {
+            builder.blobs.push(0);
+        }
+        if builder.user_strings.is_empty() {
+            builder.user_strings.push(0);
+        }
+        builder.index_heaps();
+        builder.version = reader.version().to_string();
+        builder.sorted = reader.sorted_tables();
+        builder.pdb = reader.pdb_stream().cloned();
+        if reader.is_uncompressed() {
+            builder.format = TablesFormat::Uncompressed;
+        }
+        for &table in TableId::ALL {
+            for rid in 1..=reader.row_count(table) {
+                let row = reader.columns(table, rid)?;
+                builder.tables[table as usize].push(row);
+            }
+        }
+        Ok(builder)
+    }
+
+    /// Records the entries of copied heaps so that adding an existing
+    /// string or blob returns its index.
+    fn index_heaps(&mut self) {
+        let mut pos = 0;
+        while pos < self.strings.len() {
+            let end = self.strings[pos..]
+                .iter()
+                .position(|&b| b == 0)
+                .map_or(self.strings.len(), |n| pos + n);
+            self.string_map
+                .entry(self.strings[pos..end].to_vec())
+                .or_insert(pos as u32);
+            pos = end + 1;
+        }
+        let mut pos = 0;
+        while let Some((start, len)) = read_compressed(&self.blobs, pos) {
+            let Some(data) = self.blobs.get(start..start + len) else {
+                break;
+            };
+            self.blob_map.entry(data.to_vec()).or_insert(pos as u32);
+            pos = start + len;
+        }
+        let mut pos = 0;
+        while let Some((start, len)) = read_compressed(&self.user_strings, pos) {
+            let Some(data) = self.user_strings.get(start..start + len) else {
+                break;
+            };
+            let units = data[..len & !1]
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            self.user_string_map.entry(units).or_insert(pos as u32);
+            pos = start + len;
+        }
+    }
+
+    /// Sets the runtime version string written to the metadata root.
+    pub fn set_version(&mut self, version: &str) {
+        self.version = version.to_string();
+    }
+
+    /// Sets the layout of the tables stream.
+    pub fn set_format(&mut self, format: TablesFormat) {
+        self.format = format;
+    }
+
+    /// Returns the layout of the tables stream.
+    pub fn format(&self) -> TablesFormat {
+        self.format
+    }
+
+    /// Sets the bit mask of tables marked as sorted.
+    ///
+    /// The builder does not sort rows; callers that mark a table sorted must
+    /// add its rows in key order.
+    pub fn set_sorted_tables(&mut self, sorted: u64) {
+        self.sorted = sorted;
+    }
+
+    /// Sets the `#Pdb` stream, which makes the blob a Portable PDB. Its
+    /// type system row counts also size indices into the tables of the
+    /// image the PDB describes.
+    pub fn set_pdb_stream(&mut self, pdb: Option<PdbStream>) {
+        self.pdb = pdb;
+    }
+
+    /// Returns the `#Pdb` stream, if the builder holds a Portable PDB.
+    pub fn pdb_stream(&self) -> Option<&PdbStream> {
+        self.pdb.as_ref()
+    }
+
+    /// Adds a string to the `#Strings` heap and returns its index.
+    pub fn string(&mut self, value: &str) -> u32 {
+        if value.is_empty() {
+            return 0;
+        }
+        if let Some(&index) = self.string_map.get(value.as_bytes()) {
+            return index;
+        }
+        let index = self.strings.len() as u32;
+        self.strings.extend_from_slice(value.as_bytes());
+        self.strings.push(0);
+        self.string_map.insert(value.as_bytes().to_vec(), index);
+        index
+    }
+
+    /// Adds a blob to the `#Blob` heap and returns its index.
+    pub fn blob(&mut self, value: &[u8]) -> u32 {
+        if value.is_empty() {
+            return 0;
+        }
+        if let Some(&index) = self.blob_map.get(value) {
+            return index;
+        }
+        let index = self.blobs.len() as u32;
+        write_compressed_u32(&mut self.blobs, value.len() as u32);
+        self.blobs.extend_from_slice(value);
+        self.blob_map.insert(value.to_vec(), index);
+        index
+    }
+
+    /// Returns the blob at `index` of the `#Blob` heap.
+    pub(crate) fn blob_at(&self, index: u32) -> Result<&[u8]> {
+        read_compressed(&self.blobs, index as usize)
+            .and_then(|(start, len)| self.blobs.get(start..start + len))
+            .ok_or_else(|| Error::BadMetadata(format!("blob index {index:#x} out of range")))
+    }
+
+    /// Adds a GUID to the `#GUID` heap and returns its 1-based index.
+    pub fn guid(&mut self, value: [u8; 16]) -> u32 {
+        if let Some(i) = self.guids.chunks_exact(16).position(|g| g == value) {
+            return i as u32 + 1;
+        }
+        self.guids.extend_from_slice(&value);
+        (self.guids.len() / 16) as u32
+    }
+
+    /// Adds a string to the `#US` heap and returns its index (the low 24
+    /// bits of its `mdString` token).
+    pub fn user_string(&mut self, value: &str) -> u32 {
+        self.user_string_units(value.encode_utf16().collect())
+    }
+
+    /// Adds a string given as UTF-16 code units, which may hold unpaired
+    /// surrogates, to the `#US` heap and returns its index.
+    pub(crate) fn user_string_units(&mut self, units: Vec<u16>) -> u32 {
+        if let Some(&index) = self.user_string_map.get(&units) {
+            return index;
+        }
+        let index = self.user_strings.len() as u32;
+        encode_user_string(&mut self.user_strings, &units);
+        self.user_string_map.insert(units, index);
+        index
+    }
+
+    /// Returns the `#Strings` heap written so far.
+    pub fn strings_heap(&self) -> &[u8] {
+        &self.strings
+    }
+
+    /// Returns the `#Blob` heap written so far.
+    pub fn blob_heap(&self) -> &[u8] {
+        &self.blobs
+    }
+
+    /// Returns the `#GUID` heap written so far.
+    pub fn guid_heap(&self) -> &[u8] {
+        &self.guids
+    }
+
+    /// Returns the `#US` heap written so far.
+    pub fn user_string_heap(&self) -> &[u8] {
+        &self.user_strings
+    }
+
+    /// Appends bytes to the heaps verbatim, as when applying an
+    /// Edit-and-Continue delta whose heaps continue the baseline's.
+    pub(crate) fn append_raw_heaps(
+        &mut self,
+        strings: &[u8],
+        blobs: &[u8],
+        guids: &[u8],
+        user_strings: &[u8],
+    ) {
+        self.strings.extend_from_slice(strings);
+        self.blobs.extend_from_slice(blobs);
+        self.guids.extend_from_slice(guids);
+        self.user_strings.extend_from_slice(user_strings);
+    }
+
+    /// Returns the number of rows in `table`.
+    pub fn row_count(&self, table: TableId) -> u32 {
+        self.tables[table as usize].len() as u32
+    }
+
+    /// Returns the column values of row `rid` (1-based) of `table`.
+    pub fn row_values(&self, table: TableId, rid: u32) -> Result<&[u32]> {
+        self.tables[table as usize]
+            .get((rid as usize).wrapping_sub(1))
+            .map(Vec::as_slice)
+            .ok_or_else(|| row_out_of_range(table, rid, self.row_count(table)))
+    }
+
+    /// Reads row `rid` (1-based) as a typed row.
+    pub fn row<R: TableRow>(&self, rid: u32) -> Result<R> {
+        Ok(R::from_columns(self.row_values(R::TABLE, rid)?))
+    }
+
+    /// Appends a row given as column values and returns its rid.
+    pub fn add_row(&mut self, table: TableId, values: Vec<u32>) -> Result<u32> {
+        check_columns(table, &values)?;
+        let rows = &mut self.tables[table as usize];
+        rows.push(values);
+        Ok(rows.len() as u32)
+    }
+
+    /// Appends a typed row and returns its rid.
+    pub fn add<R: TableRow>(&mut self, row: &R) -> u32 {
+        let rows = &mut self.tables[R::TABLE as usize];
+        rows.push(row.to_columns());
+        rows.len() as u32
+    }
+
+    /// Replaces row `rid` (1-based) of `table`.
+    pub fn set_row(&mut self, table: TableId, rid: u32, values: Vec<u32>) -> Result<()> {
+        check_columns(table, &values)?;
+        let count = self.row_count(table);
+        let row = self.tables[table as usize]
+            .get_mut((rid as usize).wrapping_sub(1))
+            .ok_or_else(|| row_out_of_range(table, rid, count))?;
+        *row = values;
+        Ok(())
+    }
+
+    /// Sets a single column of row `rid` (1-based) of `table`.
+    pub fn set_column(
+        &mut self,
+        table: TableId,
+        rid: u32,
+        column: usize,
+        value: u32,
+    ) -> Result<()> {
+        let count = self.row_count(table);
+        let slot = self.tables[table as usize]
+            .get_mut((rid as usize).wrapping_sub(1))
+            .ok_or_else(|| row_out_of_range(table, rid, count))?
+            .get_mut(column)
+            .ok_or_else(|| {
+                Error::BadMetadata(format!("column {column} out of range for {table}"))
+            })?;
+        *slot = value;
+        Ok(())
+    }
+
+    /// Inserts a row before row `rid` (1-based), shifting later rows down.
+    /// References to the shifted rows are not updated.
+    pub fn insert_row(&mut self, table: TableId, rid: u32, values: Vec<u32>) -> Result<()> {
+        check_columns(table, &values)?;
+        let count = self.row_count(table);
+        if rid == 0 || rid > count + 1 {
+            return Err(row_out_of_range(table, rid, count));
+        }
+        self.tables[table as usize].insert(rid as usize - 1, values);
+        Ok(())
+    }
+
+    /// Removes every row of `table`.
+    pub fn clear_table(&mut self, table: TableId) {
+        self.tables[table as usize].clear();
+    }
+
+    /// Sets the module's MVID to a GUID derived from the metadata with the
+    /// MVID zeroed, so that the same metadata always gets the same MVID.
+    pub(crate) fn derive_mvid(&mut self) -> Result<()> {
+        let mvid = match self.row_values(TableId::Module, 1)?[2] {
+            0 => {
+                let index = self.guid([0xFF; 16]);
+                self.set_column(TableId::Module, 1, 2, index)?;
+                index
+            }
+            index => index,
+        };
+        let mvid_range = (mvid as usize - 1) * 16..mvid as usize * 16;
+        self.guids[mvid_range.clone()].fill(0);
+        let hash = Sha256::digest(self.to_bytes()?);
+        let mut guid: [u8; 16] = hash[..16].try_into().expect("16 bytes");
+        guid[7] = (guid[7] & 0x0F) | 0x40;
+        guid[8] = (guid[8] & 0x3F) | 0x80;
+        self.guids[mvid_range].copy_from_slice(&guid);
+        Ok(())
+    }
+
+    /// Writes the metadata blob.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let has_ptr_tables = [
+            TableId::FieldPtr,
+            TableId::MethodPtr,
+            TableId::ParamPtr,
+            TableId::EventPtr,
+            TableId::PropertyPtr,
+        ]
+        .iter()
+        .any(|&t| self.row_count(t) != 0);
+        let (tables_name, minimal) = match self.format {
+            TablesFormat::Compressed if !has_ptr_tables => ("#~", false),
+            TablesFormat::Compressed | TablesFormat::Uncompressed => ("#-", false),
+            TablesFormat::MinimalDelta => ("#-", true),
+        };
+
+        let tables = self.tables_stream(minimal)?;
+        let pdb = self.pdb.as_ref().map(pdb_stream_bytes);
+        let mut streams: Vec<(&str, &[u8])> = Vec::new();
+        if let Some(pdb) = &pdb {
+            streams.push(("#Pdb", pdb));
+        }
+        streams.extend([
+            (tables_name, &tables[..]),
+            ("#Strings", &self.strings),
+            ("#US", &self.user_strings),
+            ("#GUID", &self.guids),
+            ("#Blob", &self.blobs),
+        ]);
+        if minimal {
+            streams.push(("#JTD", &[]));
+        }
+
+        let mut version = self.version.as_bytes().to_vec();
+        version.push(0);
+        version.resize(version.len().next_multiple_of(4), 0);
+
+        let header_len = 16
+            + version.len()
+            + 4
+            + streams
+                .iter()
+                .map(|(name, _)| 8 + (name.len() + 1).next_multiple_of(4))
+                .sum::<usize>();
+        let mut out = Vec::new();
+        out.extend_from_slice(&METADATA_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&(version.len() as u32).to_le_bytes());
+        out.extend_from_slice(&version);
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out.extend_from_slice(&(streams.len() as u16).to_le_bytes());
+        let mut offset = header_len;
+        for (name, data) in &streams {
+            let size = data.len().next_multiple_of(4);
+            out.extend_from_slice(&(offset as u32).to_le_bytes());
+            out.extend_from_slice(&(size as u32).to_le_bytes());
+            out.extend_from_slice(name.as_bytes());
+            out.resize((out.len() + 1).next_multiple_of(4), 0);
+            offset += size;
+        }
+        for (_, data) in &streams {
+            out.extend_from_slice(data);
+            out.resize(out.len().next_multiple_of(4), 0);
+        }
+        Ok(out)
+    }
+
+    fn tables_stream(&self, minimal: bool) -> Result<Vec<u8>> {
+        let mut heap_sizes = 0;
+        if minimal {
+            heap_sizes = HEAP_STRING_4 | HEAP_GUID_4 | HEAP_BLOB_4 | HEAP_ENC_DELTA;
+        } else {
+            if self.strings.len() > 0xFFFF {
+                heap_sizes |= HEAP_STRING_4;
+            }
+            if self.guids.len() / 16 > 0xFFFF {
+                heap_sizes |= HEAP_GUID_4;
+            }
+            if self.blobs.len() > 0xFFFF {
+                heap_sizes |= HEAP_BLOB_4;
+            }
+        }
+        let valid = TableId::ALL
+            .iter()
+            .filter(|&&t| self.row_count(t) != 0)
+            .fold(0u64, |mask, &t| mask | (1u64 << t as u8));
+
+        let mut out = Vec::new();
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.push(2);
+        out.push(0);
+        out.push(heap_sizes);
+        out.push(1);
+        out.extend_from_slice(&valid.to_le_bytes());
+        out.extend_from_slice(&(self.sorted & valid).to_le_bytes());
+        for &table in TableId::ALL {
+            if valid & (1u64 << table as u8) != 0 {
+                out.extend_from_slice(&self.row_count(table).to_le_bytes());
+            }
+        }
+
+        for &table in TableId::ALL {
+            let columns = table.columns();
+            let widths: Vec<usize> = columns
+                .iter()
+                .map(|c| self.column_width(c.ty, heap_sizes, minimal))
+                .collect();
+            for (index, row) in self.tables[table as usize].iter().enumerate() {
+                for ((column, &value), &width) in columns.iter().zip(row).zip(&widths) {
+                    let raw = match column.ty {
+                        ColumnType::Coded(coded) => encode_coded(coded, value, table, index)?,
+                        _ => value,
+                    };
+                    if width == 2 {
+                        let narrow = u16::try_from(raw).map_err(|_| {
+                            Error::BadMetadata(format!(
+                                "{table} row {} column {} value {raw:#x} does not fit in 2 bytes",
+                                index + 1,
+                                column.name
+                            ))
+                        })?;
+                        out.extend_from_slice(&narrow.to_le_bytes());
+                    } else {
+                        out.extend_from_slice(&raw.to_le_bytes());
+                    }
+                }
+            }
+        }
+        out.resize(out.len().next_multiple_of(4), 0);
+        Ok(out)
+    }
+
+    /// Row count used to size indices into `table`, including rows that only
+    /// exist in the type system image a Portable PDB refers to.
+    fn index_row_count(&self, table: TableId) -> u32 {
+        let local = self.row_count(table);
+        match &self.pdb {
+            Some(pdb) if pdb.referenced_type_system_tables & (1u64 << table as u8) != 0 => {
+                local.max(pdb.type_system_table_rows[table as usize])
+            }
+            _ => local,
+        }
+    }
+
+    fn column_width(&self, ty: ColumnType, heap_sizes: u8, minimal: bool) -> usize {
+        let wide = |flag: u8| if heap_sizes & flag != 0 { 4 } else { 2 };
+        match ty {
+            ColumnType::U16 => 2,
+            ColumnType::U32 => 4,
+            _ if minimal => 4,
+            ColumnType::Strings => wide(HEAP_STRING_4),
+            ColumnType::Guid => wide(HEAP_GUID_4),
+            ColumnType::Blob => wide(HEAP_BLOB_4),
+            ColumnType::Table(table) => {
+                if self.index_row_count(table) < 0x1_0000 {
+                    2
+                } else {
+                    4
+                }
+            }
+            ColumnType::Coded(coded) => {
+                let limit = 1u32 << (16 - coded.tag_bits());
+                let max_rows = coded
+                    .tables()
+                    .iter()
+                    .flatten()
+                    .map(|&t| self.index_row_count(t))
+                    .max()
+                    .unwrap_or(0);
+                if max_rows < limit { 2 } else { 4 }
+            }
+        }
+    }
+}
+
+fn pdb_stream_bytes(pdb: &PdbStream) -> Vec<u8> {
+    let mut out = pdb.id.to_vec();
+    out.extend_from_slice(&pdb.entry_point.to_le_bytes());
+    out.extend_from_slice(&pdb.referenced_type_system_tables.to_le_bytes());
+    for (table, rows) in pdb.type_system_table_rows.iter().enumerate() {
+        if pdb.referenced_type_system_tables & (1u64 << table) != 0 {
+            out.extend_from_slice(&rows.to_le_bytes());
+        }
+    }
+    out
+}
+
+/// Tables that ECMA-335 requires to be sorted in optimized metadata.
+fn default_sorted_tables() -> u64 {
+    [
+        TableId::InterfaceImpl,
+        TableId::Constant,
+        TableId::CustomAttribute,
+        TableId::FieldMarshal,
+        TableId::DeclSecurity,
+        TableId::ClassLayout,
+        TableId::FieldLayout,
+        TableId::MethodSemantics,
+        TableId::MethodImpl,
+        TableId::ImplMap,
+        TableId::FieldRva,
+        TableId::NestedClass,
+        TableId::GenericParam,
+        TableId::GenericParamConstraint,
+    ]
+    .iter()
+    .fold(0, |mask, &t| mask | (1u64 << t as u8))
+}
+
+pub(crate) fn check_columns(table: TableId, values: &[u32]) -> Result<()> {
+    if values.len() != table.columns().len() {
+        return Err(Error::BadMetadata(format!(
+            "{table} rows have {} columns, got {}",
+            table.columns().len(),
+            values.len()
+        )));
+    }
+    Ok(())
+}
+
+fn row_out_of_range(table: TableId, rid: u32, rows: u32) -> Error {
+    Error::BadMetadata(format!(
+        "row {rid} out of range for table {table} ({rows} rows)"
+    ))
+}
+
+fn encode_coded(coded: CodedIndex, token: u32, table: TableId, index: usize) -> Result<u32> {
+    if token & 0x00FF_FFFF == 0 {
+        return Ok(0);
+    }
+    coded.encode(token).ok_or_else(|| {
+        Error::BadMetadata(format!(
+            "{table} row {}: token {token:#010x} is not a valid {coded:?}",
+            index + 1
+        ))
+    })
+}
+
+/// Reads a compressed length at `pos`, returning the data start and length.
+fn read_compressed(heap: &[u8], pos: usize) -> Option<(usize, usize)> {
+    let b0 = *heap.get(pos)? as usize;
+    if b0 & 0x80 == 0 {
+        Some((pos + 1, b0))
+    } else if b0 & 0xC0 == 0x80 {
+        Some((pos + 2, ((b0 & 0x3F) << 8) | *heap.get(pos + 1)? as usize))
+    } else if b0 & 0xE0 == 0xC0 {
+        let rest = heap.get(pos + 1..pos + 4)?;
+        let len = ((b0 & 0x1F) << 24)
+            | ((rest[0] as usize) << 16)
+            | ((rest[1] as usize) << 8)
+            | rest[2] as usize;
+        Some((pos + 4, len))
+    } else {
+        None
+    }
+}
+
+/// Appends an ECMA-335 compressed unsigned integer (II.23.2).
+pub(crate) fn write_compressed_u32(out: &mut Vec<u8>, value: u32) {
+    if value < 0x80 {
+        out.push(value as u8);
+    } else if value < 0x4000 {
+        out.extend_from_slice(&((value as u16) | 0x8000).to_be_bytes());
+    } else {
+        out.extend_from_slice(&(value | 0xC000_0000).to_be_bytes());
+    }
+}
+
+/// Appends an ECMA-335 compressed signed integer (II.23.2).
+pub(crate) fn write_compressed_i32(out: &mut Vec<u8>, value: i32) {
+    let sign = (value < 0) as u32;
+    let rotated = (value as u32) << 1;
+    if (-0x40..0x40).contains(&value) {
+        out.push(((rotated & 0x7E) | sign) as u8);
+    } else if (-0x2000..0x2000).contains(&value) {
+        out.extend_from_slice(&((((rotated & 0x3FFE) | sign) as u16) | 0x8000).to_be_bytes());
+    } else {
+        out.extend_from_slice(&((rotated & 0x1FFF_FFFE) | sign | 0xC000_0000).to_be_bytes());
+    }
+}
+
+/// Appends a `#US` heap entry: the UTF-16 data followed by a flag byte that
+/// is set when the string holds characters needing special handling
+/// (II.24.2.4).
+pub(crate) fn encode_user_string(out: &mut Vec<u8>, units: &[u16]) {
+    let special = units
+        .iter()
+        .any(|&u| u > 0x7F || matches!(u, 0x01..=0x08 | 0x0E..=0x1F | 0x27 | 0x2D | 0x7F));
+    write_compressed_u32(out, units.len() as u32 * 2 + 1);
+    for unit in units {
+        out.extend_from_slice(&unit.to_le_bytes());
+    }
+    out.push(special as u8);
+}
@@ -0,0 +1,82 @@
+//! Encoding of Portable PDB sequence points.
+
+use super::metadata::{write_compressed_i32, write_compressed_u32};
+use crate::error::{Error, Result};
+use crate::reader::MethodSequencePoints;
+
+impl MethodSequencePoints {
+    /// Encodes the sequence points as a `SequencePoints` blob and returns
+    /// it with the value of the `MethodDebugInformation.Document` column:
+    /// the document of every point, or 0 if the points span several
+    /// documents.
+    ///
+    /// Points must be in strictly increasing IL offset order.
+    pub fn to_blob(&self) -> Result<(u32, Vec<u8>)> {
+        let mut out = Vec::new();
+        let Some(first) = self.points.first() else {
+            return Ok((0, out));
+        };
+        let single_document = self.points.iter().all(|p| p.document == first.document);
+        write_compressed_u32(&mut out, self.local_signature);
+        if !single_document {
+            write_compressed_u32(&mut out, first.document);
+        }
+
+        let mut document = first.document;
+        let mut previous: Option<u32> = None;
+        let mut previous_start: Option<(u32, u16)> = None;
+        for point in &self.points {
+            let bad = |msg: &str| {
+                Error::BadMetadata(format!(
+                    "sequence point at IL offset {:#x}: {msg}",
+                    point.offset
+                ))
+            };
+            if point.document != document {
+                write_compressed_u32(&mut out, 0);
+                write_compressed_u32(&mut out, point.document);
+                document = point.document;
+            }
+            let delta_offset = match previous {
+                None => point.offset,
+                Some(previous) if point.offset > previous => point.offset - previous,
+                Some(_) => return Err(bad("IL offsets are not increasing")),
+            };
+            write_compressed_u32(&mut out, delta_offset);
+            previous = Some(point.offset);
+            if point.is_hidden() {
+                write_compressed_u32(&mut out, 0);
+                write_compressed_u32(&mut out, 0);
+                continue;
+            }
+
+            let delta_lines = point
+                .end_line
+                .checked_sub(point.start_line)
+                .ok_or_else(|| bad("the span ends before it starts"))?;
+            let delta_columns = point.end_column as i32 - point.start_column as i32;
+            if delta_lines == 0 && delta_columns <= 0 {
+                return Err(bad("the span is empty"));
+            }
+            write_compressed_u32(&mut out, delta_lines);
+            if delta_lines == 0 {
+                write_compressed_u32(&mut out, delta_columns as u32);
+            } else {
+                write_compressed_i32(&mut out, delta_columns);
+            }
+            match previous_start {
+                None => {
+                    write_compressed_u32(&mut out, point.start_line);
+                    write_compressed_u32(&mut out, point.start_column as u32);
+                }
+                Some((line, column)) => {
+                    write_compressed_i32(&mut out, point.start_line as i32 - line as i32);
+                    write_compressed_i32(&mut out, point.start_column as i32 - column as i32);
+                }
+            }
+            previous_start = Some((point.start_line, point.start_column));
+        }
+        let document = if single_document { first.document } else { 0 };
+        Ok((document, out))
+    }
+}
@@ -0,0 +1,538 @@
+//! PE/COFF image writer for IL-only assemblies.
+//!
+//! Produces the layout compilers emit for managed code: a `.text` section
+//! holding the CLI header, method bodies, metadata, managed resources, the
+//! strong-name signature area and the debug directory, plus `.rsrc` for Win32 resources and, for
+//! 32-bit images, the `mscoree.dll` import and its `.reloc` fixup.
+
+use crate::error::{Error, Result};
+use crate::reader::{
+    COMIMAGE_FLAGS_ILONLY, COMIMAGE_FLAGS_NATIVE_ENTRYPOINT, COMIMAGE_FLAGS_STRONGNAMESIGNED,
+    DebugDirectoryEntry, IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR, IMAGE_DIRECTORY_ENTRY_DEBUG,
+    IMAGE_DIRECTORY_ENTRY_RESOURCE, Machine, PeImage,
+};
+
+const FILE_ALIGNMENT: u32 = 0x200;
+const SECTION_ALIGNMENT: u32 = 0x2000;
+const TEXT_RVA: u32 = SECTION_ALIGNMENT;
+const COR_HEADER_SIZE: u32 = 72;
+const DEBUG_DIRECTORY_ENTRY_SIZE: u32 = 28;
+
+const IMAGE_DIRECTORY_ENTRY_IMPORT: usize = 1;
+const IMAGE_DIRECTORY_ENTRY_BASERELOC: usize = 5;
+const IMAGE_DIRECTORY_ENTRY_IAT: usize = 12;
+
+const IMAGE_FILE_EXECUTABLE_IMAGE: u16 = 0x0002;
+const IMAGE_FILE_LARGE_ADDRESS_AWARE: u16 = 0x0020;
+const IMAGE_FILE_DLL: u16 = 0x2000;
+
+const IMAGE_DLLCHARACTERISTICS_HIGH_ENTROPY_VA: u16 = 0x0020;
+/// `DYNAMIC_BASE | NX_COMPAT | NO_SEH | TERMINAL_SERVER_AWARE`.
+const DEFAULT_DLL_CHARACTERISTICS: u16 = 0x8540;
+
+const IMAGE_SCN_TEXT: u32 = 0x6000_0020;
+const IMAGE_SCN_RSRC: u32 = 0x4000_0040;
+const IMAGE_SCN_RELOC: u32 = 0x4200_0040;
+
+const IMAGE_SUBSYSTEM_WINDOWS_CUI: u16 = 3;
+
+/// The DOS header and stub program every PE image starts with; `e_lfanew`
+/// points just past it.
+const DOS_HEADER: [u8; 128] = [
+    0x4D, 0x5A, 0x90, 0x00, 0x03, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00,
+    0xB8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00,
+    0x0E, 0x1F, 0xBA, 0x0E, 0x00, 0xB4, 0x09, 0xCD, 0x21, 0xB8, 0x01, 0x4C, 0xCD, 0x21, 0x54, 0x68,
+    0x69, 0x73, 0x20, 0x70, 0x72, 0x6F, 0x67, 0x72, 0x61, 0x6D, 0x20, 0x63, 0x61, 0x6E, 0x6E, 0x6F,
+    0x74, 0x20, 0x62, 0x65, 0x20, 0x72, 0x75, 0x6E, 0x20, 0x69, 0x6E, 0x20, 0x44, 0x4F, 0x53, 0x20,
+    0x6D, 0x6F, 0x64, 0x65, 0x2E, 0x0D, 0x0D, 0x0A, 0x24, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// Builds an IL-only PE image around a metadata blob.
+///
+/// Method bodies and field data are added first and get their final RVAs
+/// straight away, so the metadata that refers to them can be built
+/// afterwards:
+///
+/// ```
+/// use mscoree::{Machine, MetadataBuilder, PeBuilder, PeImage};
+///
+/// let mut pe = PeBuilder::new(Machine::I386);
+/// let rva = pe.add_method_body(&[0x0A, 0x14, 0x7A]); // throw null
+/// assert_eq!(rva, 0x2050);
+/// pe.set_metadata(MetadataBuilder::new().to_bytes()?);
+/// let image = PeImage::from_bytes(pe.to_bytes()?)?;
+/// assert!(image.is_dll());
+/// assert_eq!(image.rva_slice(rva, 3), Some(&[0x0A, 0x14, 0x7A][..]));
+/// # Ok::<(), mscoree::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct PeBuilder {
+    machine: Machine,
+    is_dll: bool,
+    subsystem: u16,
+    dll_characteristics: u16,
+    image_base: Option<u64>,
+    time_date_stamp: u32,
+    runtime_version: (u16, u16),
+    cor_flags: u32,
+    entry_point: u32,
+    il: Vec<u8>,
+    metadata: Vec<u8>,
+    resources: Vec<u8>,
+    strong_name_signature_size: u32,
+    win32_resources: Option<(Vec<u8>, u32)>,
+    debug_entries: Vec<(DebugDirectoryEntry, Vec<u8>)>,
+}
+
+impl PeBuilder {
+    /// Creates an IL-only DLL for `machine`: `I386` for a 32-bit or AnyCPU
+    /// image, or a 64-bit machine for a PE32+ image.
+    pub fn new(machine: Machine) -> Self {
+        let is_64_bit = is_64_bit(machine);
+        Self {
+            machine,
+            is_dll: true,
+            subsystem: IMAGE_SUBSYSTEM_WINDOWS_CUI,
+            dll_characteristics: if is_64_bit {
+                DEFAULT_DLL_CHARACTERISTICS | IMAGE_DLLCHARACTERISTICS_HIGH_ENTROPY_VA
+            } else {
+                DEFAULT_DLL_CHARACTERISTICS
+            },
+            image_base: None,
+            time_date_stamp: 0,
+            runtime_version: (2, 5),
+            cor_flags: COMIMAGE_FLAGS_ILONLY,
+            entry_point: 0,
+            il: Vec::new(),
+            metadata: Vec::new(),
+            resources: Vec::new(),
+            strong_name_signature_size: 0,
+            win32_resources: None,
+            debug_entries: Vec::new(),
+        }
+    }
+
+    /// Creates a builder with the headers, CLI flags and Win32 resources of
+    /// `image`. Code, metadata, managed resources, the entry point and the
+    /// debug directory are not copied, and the strong-name flag is cleared.
+    pub fn from_image(image: &PeImage) -> Result<Self> {
+        let cor = image.cor_header().ok_or(Error::NotManaged)?;
+        let mut builder = Self::new(image.machine());
+        builder.is_dll = image.is_dll();
+        builder.subsystem = image.subsystem();
+        builder.dll_characteristics = image.dll_characteristics();
+        builder.image_base = Some(image.image_base());
+        builder.time_date_stamp = image.time_date_stamp();
+        builder.runtime_version = (cor.major_runtime_version, cor.minor_runtime_version);
+        builder.cor_flags = cor.flags & !COMIMAGE_FLAGS_STRONGNAMESIGNED;
+        let dir = image.data_directory(IMAGE_DIRECTORY_ENTRY_RESOURCE);
+        if !dir.is_empty() {
+            let data = image.directory_data(dir).ok_or_else(|| {
+                Error::BadImageFormat("Win32 resources are outside the image".into())
+            })?;
+            builder.win32_resources = Some((data.to_vec(), dir.virtual_address));
+        }
+        Ok(builder)
+    }
+
+    /// Sets whether the image is a DLL rather than an executable.
+    pub fn set_dll(&mut self, is_dll: bool) {
+        self.is_dll = is_dll;
+    }
+
+    /// Sets the Windows subsystem (`IMAGE_SUBSYSTEM_*`).
+    pub fn set_subsystem(&mut self, subsystem: u16) {
+        self.subsystem = subsystem;
+    }
+
+    /// Sets the preferred load address. Defaults to `0x400000`, or
+    /// `0x180000000` for a 64-bit DLL.
+    pub fn set_image_base(&mut self, image_base: u64) {
+        self.image_base = Some(image_base);
+    }
+
+    /// Sets the COFF `TimeDateStamp`; deterministic builds use a content
+    /// hash rather than the time.
+    pub fn set_time_date_stamp(&mut self, time_date_stamp: u32) {
+        self.time_date_stamp = time_date_stamp;
+    }
+
+    /// Sets the CLI header flags (`COMIMAGE_FLAGS_*`).
+    pub fn set_cor_flags(&mut self, flags: u32) {
+        self.cor_flags = flags;
+    }
+
+    /// Sets the entry point method token, or 0 for none.
+    pub fn set_entry_point(&mut self, token: u32) {
+        self.entry_point = token;
+    }
+
+    /// Adds a method body (header, code and extra sections) and returns its
+    /// RVA. Fat bodies are 4-byte aligned.
+    pub fn add_method_body(&mut self, body: &[u8]) -> u32 {
+        let is_fat = body.first().is_some_and(|&b| b & 0x03 == 0x03);
+        if is_fat {
+            self.il.resize(self.il.len().next_multiple_of(4), 0);
+        }
+        let rva = self.il_rva() + self.il.len() as u32;
+        self.il.extend_from_slice(body);
+        rva
+    }
+
+    /// Adds the initial data of a field with an RVA and returns the RVA.
+    pub fn add_field_data(&mut self, data: &[u8]) -> u32 {
+        self.il.resize(self.il.len().next_multiple_of(8), 0);
+        let rva = self.il_rva() + self.il.len() as u32;
+        self.il.extend_from_slice(data);
+        rva
+    }
+
+    /// Sets the metadata blob.
+    pub fn set_metadata(&mut self, metadata: Vec<u8>) {
+        self.metadata = metadata;
+    }
+
+    /// Adds an embedded managed resource and returns its offset within the
+    /// resources directory, for `ManifestResource.Offset`.
+    pub fn add_resource(&mut self, data: &[u8]) -> u32 {
+        self.resources
+            .resize(self.resources.len().next_multiple_of(8), 0);
+        let offset = self.resources.len() as u32;
+        self.resources
+            .extend_from_slice(&(data.len() as u32).to_le_bytes());
+        self.resources.extend_from_slice(data);
+        offset
+    }
+
+    /// Reserves a zeroed strong-name signature area of `size` bytes, for
+    /// [`PeImage::strong_name_sign`] to fill in later. 0 removes it.
+    pub fn set_strong_name_signature_size(&mut self, size: u32) {
+        self.strong_name_signature_size = size;
+    }
+
+    /// Adds an entry to the debug directory, with `data` as its raw data.
+    /// The size and location fields of `entry` are filled in on write.
+    pub fn add_debug_entry(&mut self, entry: DebugDirectoryEntry, data: &[u8]) {
+        self.debug_entries.push((entry, data.to_vec()));
+    }
+
+    /// Writes the image file, including its checksum.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        if self.metadata.is_empty() {
+            return Err(Error::BadMetadata("PE image has no metadata".into()));
+        }
+        let is_64_bit = is_64_bit(self.machine);
+        let needs_stub = self.machine == Machine::I386;
+        let image_base = self.image_base.unwrap_or(if is_64_bit && self.is_dll {
+            0x1_8000_0000
+        } else {
+            0x40_0000
+        });
+        if !is_64_bit && image_base > u32::MAX as u64 {
+            return Err(Error::BadImageFormat(format!(
+                "image base {image_base:#x} does not fit a PE32 image"
+            )));
+        }
+
+        // .text
+        let mut text = Vec::new();
+        if needs_stub {
+            text.resize(8, 0); // IAT, filled in below
+        }
+        let cor_header_rva = TEXT_RVA + text.len() as u32;
+        text.resize(text.len() + COR_HEADER_SIZE as usize, 0);
+        text.extend_from_slice(&self.il);
+        align(&mut text, 4);
+        let metadata_rva = TEXT_RVA + text.len() as u32;
+        text.extend_from_slice(&self.metadata);
+        align(&mut text, 8);
+        let resources_rva = TEXT_RVA + text.len() as u32;
+        text.extend_from_slice(&self.resources);
+        align(&mut text, 8);
+        let signature_rva = TEXT_RVA + text.len() as u32;
+        text.resize(text.len() + self.strong_name_signature_size as usize, 0);
+
+        let mut directories = [(0u32, 0u32); 16];
+        // Offsets in .text of the `PointerToRawData` fields, which depend on
+        // where .text ends up in the file.
+        let mut debug_pointers = Vec::new();
+        if !self.debug_entries.is_empty() {
+            align(&mut text, 4);
+            let directory_start = text.len();
+            let directory_size = DEBUG_DIRECTORY_ENTRY_SIZE * self.debug_entries.len() as u32;
+            directories[IMAGE_DIRECTORY_ENTRY_DEBUG] =
+                (TEXT_RVA + directory_start as u32, directory_size);
+            text.resize(directory_start + directory_size as usize, 0);
+            for (index, (entry, data)) in self.debug_entries.iter().enumerate() {
+                let data_rva = if data.is_empty() {
+                    0
+                } else {
+                    align(&mut text, 4);
+                    TEXT_RVA + text.len() as u32
+                };
+                text.extend_from_slice(data);
+                let mut raw = Vec::with_capacity(DEBUG_DIRECTORY_ENTRY_SIZE as usize);
+                put_u32(&mut raw, entry.characteristics);
+                put_u32(&mut raw, entry.time_date_stamp);
+                raw.extend_from_slice(&entry.major_version.to_le_bytes());
+                raw.extend_from_slice(&entry.minor_version.to_le_bytes());
+                put_u32(&mut raw, entry.kind);
+                put_u32(&mut raw, data.len() as u32);
+                put_u32(&mut raw, data_rva);
+                put_u32(&mut raw, data_rva); // PointerToRawData, rebased below
+                let start = directory_start + index * DEBUG_DIRECTORY_ENTRY_SIZE as usize;
+                text[start..start + raw.len()].copy_from_slice(&raw);
+                if data_rva != 0 {
+                    debug_pointers.push(start + 24);
+                }
+            }
+        }
+        let mut entry_point_rva = 0;
+        let mut fixup_rva = None;
+        if needs_stub {
+            align(&mut text, 4);
+            let import_rva = TEXT_RVA + text.len() as u32;
+            let ilt_rva = import_rva + 40;
+            let hint_name_rva = ilt_rva + 8;
+            let entry_name: &[u8] = if self.is_dll {
+                b"_CorDllMain\0"
+            } else {
+                b"_CorExeMain\0"
+            };
+            let dll_name_rva = hint_name_rva + 2 + entry_name.len() as u32;
+            // Import descriptor, then a null descriptor.
+            put_u32(&mut text, ilt_rva);
+            put_u32(&mut text, 0);
+            put_u32(&mut text, 0);
+            put_u32(&mut text, dll_name_rva);
+            put_u32(&mut text, TEXT_RVA);
+            text.resize(text.len() + 20, 0);
+            // Import lookup table.
+            put_u32(&mut text, hint_name_rva);
+            put_u32(&mut text, 0);
+            text.extend_from_slice(&[0, 0]);
+            text.extend_from_slice(entry_name);
+            text.extend_from_slice(b"mscoree.dll\0");
+            directories[IMAGE_DIRECTORY_ENTRY_IMPORT] = (import_rva, 40);
+            directories[IMAGE_DIRECTORY_ENTRY_IAT] = (TEXT_RVA, 8);
+            text[0..4].copy_from_slice(&hint_name_rva.to_le_bytes());
+
+            // `jmp dword ptr [IAT]`, with the address operand 4-byte aligned.
+            align(&mut text, 4);
+            text.extend_from_slice(&[0, 0]);
+            entry_point_rva = TEXT_RVA + text.len() as u32;
+            text.extend_from_slice(&[0xFF, 0x25]);
+            fixup_rva = Some(TEXT_RVA + text.len() as u32);
+            put_u32(&mut text, image_base as u32 + TEXT_RVA);
+        }
+
+        let mut cor = Vec::with_capacity(COR_HEADER_SIZE as usize);
+        put_u32(&mut cor, COR_HEADER_SIZE);
+        cor.extend_from_slice(&self.runtime_version.0.to_le_bytes());
+        cor.extend_from_slice(&self.runtime_version.1.to_le_bytes());
+        put_u32(&mut cor, metadata_rva);
+        put_u32(&mut cor, self.metadata.len() as u32);
+        put_u32(
+            &mut cor,
+            self.cor_flags & !(COMIMAGE_FLAGS_STRONGNAMESIGNED | COMIMAGE_FLAGS_NATIVE_ENTRYPOINT),
+        );
+        put_u32(&mut cor, self.entry_point);
+        let directory = |cor: &mut Vec<u8>, rva: u32, size: usize| {
+            put_u32(cor, if size == 0 { 0 } else { rva });
+            put_u32(cor, size as u32);
+        };
+        directory(&mut cor, resources_rva, self.resources.len());
+        directory(
+            &mut cor,
+            signature_rva,
+            self.strong_name_signature_size as usize,
+        );
+        cor.resize(COR_HEADER_SIZE as usize, 0);
+        let cor_start = (cor_header_rva - TEXT_RVA) as usize;
+        text[cor_start..cor_start + COR_HEADER_SIZE as usize].copy_from_slice(&cor);
+        directories[IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR] = (cor_header_rva, COR_HEADER_SIZE);
+
+        // Sections after .text.
+        let mut sections = vec![(".text", TEXT_RVA, text, IMAGE_SCN_TEXT)];
+        let next_rva = |sections: &[(&str, u32, Vec<u8>, u32)]| {
+            let (_, rva, data, _) = sections.last().expect("sections start with .text");
+            rva + (data.len() as u32).next_multiple_of(SECTION_ALIGNMENT)
+        };
+        if let Some((data, old_rva)) = &self.win32_resources {
+            let rva = next_rva(&sections);
+            let mut data = data.clone();
+            rebase_resources(&mut data, *old_rva, rva)?;
+            directories[IMAGE_DIRECTORY_ENTRY_RESOURCE] = (rva, data.len() as u32);
+            sections.push((".rsrc", rva, data, IMAGE_SCN_RSRC));
+        }
+        if let Some(fixup_rva) = fixup_rva {
+            let rva = next_rva(&sections);
+            let page = fixup_rva & !0xFFF;
+            let mut reloc = Vec::new();
+            put_u32(&mut reloc, page);
+            put_u32(&mut reloc, 12);
+            // IMAGE_REL_BASED_HIGHLOW, then IMAGE_REL_BASED_ABSOLUTE padding.
+            reloc.extend_from_slice(&((3 << 12) | (fixup_rva - page) as u16).to_le_bytes());
+            reloc.extend_from_slice(&[0, 0]);
+            directories[IMAGE_DIRECTORY_ENTRY_BASERELOC] = (rva, reloc.len() as u32);
+            sections.push((".reloc", rva, reloc, IMAGE_SCN_RELOC));
+        }
+
+        let optional_header_size: u16 = if is_64_bit { 240 } else { 224 };
+        let headers_len =
+            DOS_HEADER.len() + 4 + 20 + optional_header_size as usize + 40 * sections.len();
+        let size_of_headers = (headers_len as u32).next_multiple_of(FILE_ALIGNMENT);
+        let size_of_image = next_rva(&sections);
+        let text = &mut sections[0].2;
+        for at in debug_pointers {
+            let rva = u32::from_le_bytes(text[at..at + 4].try_into().expect("4 bytes"));
+            put_u32_at(text, at, rva - TEXT_RVA + size_of_headers);
+        }
+
+        let mut out = DOS_HEADER.to_vec();
+        out.extend_from_slice(b"PE\0\0");
+        out.extend_from_slice(&self.machine.0.to_le_bytes());
+        out.extend_from_slice(&(sections.len() as u16).to_le_bytes());
+        put_u32(&mut out, self.time_date_stamp);
+        put_u32(&mut out, 0);
+        put_u32(&mut out, 0);
+        out.extend_from_slice(&optional_header_size.to_le_bytes());
+        let mut characteristics = IMAGE_FILE_EXECUTABLE_IMAGE | IMAGE_FILE_LARGE_ADDRESS_AWARE;
+        if self.is_dll {
+            characteristics |= IMAGE_FILE_DLL;
+        }
+        out.extend_from_slice(&characteristics.to_le_bytes());
+
+        let raw_size = |data: &Vec<u8>| (data.len() as u32).next_multiple_of(FILE_ALIGNMENT);
+        let size_of_code = raw_size(&sections[0].2);
+        let size_of_data: u32 = sections[1..].iter().map(|s| raw_size(&s.2)).sum();
+        out.extend_from_slice(&(if is_64_bit { 0x20Bu16 } else { 0x10B }).to_le_bytes());
+        out.extend_from_slice(&[48, 0]); // linker version
+        put_u32(&mut out, size_of_code);
+        put_u32(&mut out, size_of_data);
+        put_u32(&mut out, 0);
+        put_u32(&mut out, entry_point_rva);
+        put_u32(&mut out, TEXT_RVA);
+        if is_64_bit {
+            out.extend_from_slice(&image_base.to_le_bytes());
+        } else {
+            put_u32(&mut out, sections.get(1).map_or(0, |s| s.1));
+            put_u32(&mut out, image_base as u32);
+        }
+        put_u32(&mut out, SECTION_ALIGNMENT);
+        put_u32(&mut out, FILE_ALIGNMENT);
+        for version in [4u16, 0, 0, 0, 4, 0] {
+            out.extend_from_slice(&version.to_le_bytes());
+        }
+        put_u32(&mut out, 0);
+        put_u32(&mut out, size_of_image);
+        put_u32(&mut out, size_of_headers);
+        put_u32(&mut out, 0); // checksum, set below
+        out.extend_from_slice(&self.subsystem.to_le_bytes());
+        out.extend_from_slice(&self.dll_characteristics.to_le_bytes());
+        for size in [0x10_0000u64, 0x1000, 0x10_0000, 0x1000] {
+            if is_64_bit {
+                out.extend_from_slice(&size.to_le_bytes());
+            } else {
+                put_u32(&mut out, size as u32);
+            }
+        }
+        put_u32(&mut out, 0);
+        put_u32(&mut out, directories.len() as u32);
+        for (rva, size) in directories {
+            put_u32(&mut out, rva);
+            put_u32(&mut out, size);
+        }
+
+        let mut pointer_to_raw_data = size_of_headers;
+        for (name, rva, data, flags) in &sections {
+            let mut raw_name = [0u8; 8];
+            raw_name[..name.len()].copy_from_slice(name.as_bytes());
+            out.extend_from_slice(&raw_name);
+            put_u32(&mut out, data.len() as u32);
+            put_u32(&mut out, *rva);
+            put_u32(&mut out, raw_size(data));
+            put_u32(&mut out, pointer_to_raw_data);
+            out.resize(out.len() + 12, 0);
+            put_u32(&mut out, *flags);
+            pointer_to_raw_data += raw_size(data);
+        }
+        out.resize(size_of_headers as usize, 0);
+        for (_, _, data, _) in &sections {
+            out.extend_from_slice(data);
+            align(&mut out, FILE_ALIGNMENT as usize);
+        }
+
+        let image = PeImage::from_bytes(out)?;
+        let checksum = image.compute_checksum();
+        let checksum_offset = image.checksum_offset();
+        let mut out = image.into_data();
+        out[checksum_offset..checksum_offset + 4].copy_from_slice(&checksum.to_le_bytes());
+        Ok(out)
+    }
+
+    fn il_rva(&self) -> u32 {
+        let iat = if self.machine == Machine::I386 { 8 } else { 0 };
+        TEXT_RVA + iat + COR_HEADER_SIZE
+    }
+}
+
+fn is_64_bit(machine: Machine) -> bool {
+    !matches!(machine, Machine::I386 | Machine::ARM | Machine::ARMNT)
+}
+
+fn align(data: &mut Vec<u8>, alignment: usize) {
+    data.resize(data.len().next_multiple_of(alignment), 0);
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32_at(out: &mut [u8], at: usize, value: u32) {
+    out[at..at + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Moves the data entries of a Win32 resource tree that was at `old_rva` to
+/// `new_rva`. Directory offsets are relative to the tree and stay as they
+/// are.
+fn rebase_resources(data: &mut [u8], old_rva: u32, new_rva: u32) -> Result<()> {
+    let bad = || Error::BadImageFormat("malformed Win32 resource directory".into());
+    let read_u16 = |data: &[u8], at: usize| -> Result<u16> {
+        let bytes = data.get(at..at + 2).ok_or_else(bad)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    };
+    let read_u32 = |data: &[u8], at: usize| -> Result<u32> {
+        let bytes = data.get(at..at + 4).ok_or_else(bad)?;
+        Ok(u32::from_le_bytes(bytes.try_into().expect("4 bytes")))
+    };
+    let mut pending = vec![0usize];
+    let mut seen = std::collections::HashSet::new();
+    while let Some(directory) = pending.pop() {
+        if !seen.insert(directory) {
+            continue;
+        }
+        let count =
+            read_u16(data, directory + 12)? as usize + read_u16(data, directory + 14)? as usize;
+        for i in 0..count {
+            let offset = read_u32(data, directory + 16 + 8 * i + 4)?;
+            if offset & 0x8000_0000 != 0 {
+                pending.push((offset & 0x7FFF_FFFF) as usize);
+                continue;
+            }
+            let entry = offset as usize;
+            if !seen.insert(entry) {
+                continue;
+            }
+            let rva = read_u32(data, entry)?;
+            let relative = rva
+                .checked_sub(old_rva)
+                .filter(|&r| (r as usize) < data.len());
+            let rebased = relative.ok_or_else(bad)? + new_rva;
+            data[entry..entry + 4].copy_from_slice(&rebased.to_le_bytes());
+        }
+    }
+    Ok(())
+}
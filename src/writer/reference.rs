@@ -0,0 +1,416 @@
+//! Reference assembly generation: an assembly's public surface without its
+//! implementation.
+
+use std::collections::BTreeSet;
+
+use super::metadata::MetadataBuilder;
+use super::pe::PeBuilder;
+use super::signature::{SIGNATURE_COLUMNS, remap_signature};
+use crate::error::{Error, Result};
+use crate::reader::{
+    AssemblyRefRow, COMIMAGE_FLAGS_ILONLY, COMIMAGE_FLAGS_TRACKDEBUGDATA, ColumnType,
+    CustomAttributeRow, FieldRow, MemberRefRow, MetadataReader, MethodDefRow, PeImage, TableId,
+    TypeDefRow, TypeRefRow, token_rid,
+};
+
+const TYPE_ATTRIBUTE_VISIBILITY_MASK: u32 = 0x0000_0007;
+const TYPE_ATTRIBUTE_NOT_PUBLIC: u32 = 0x0000_0000;
+const TYPE_ATTRIBUTE_PUBLIC: u32 = 0x0000_0001;
+const TYPE_ATTRIBUTE_NESTED_PUBLIC: u32 = 0x0000_0002;
+const TYPE_ATTRIBUTE_NESTED_PRIVATE: u32 = 0x0000_0003;
+const TYPE_ATTRIBUTE_NESTED_FAMILY: u32 = 0x0000_0004;
+const TYPE_ATTRIBUTE_NESTED_FAM_OR_ASSEM: u32 = 0x0000_0007;
+
+const MEMBER_ACCESS_MASK: u16 = 0x0007;
+const MEMBER_ACCESS_FAM_AND_ASSEM: u16 = 0x0002;
+const MEMBER_ACCESS_ASSEMBLY: u16 = 0x0003;
+const MEMBER_ACCESS_FAMILY: u16 = 0x0004;
+const MEMBER_ACCESS_FAM_OR_ASSEM: u16 = 0x0005;
+const MEMBER_ACCESS_PUBLIC: u16 = 0x0006;
+const MEMBER_STATIC: u16 = 0x0010;
+const METHOD_ATTRIBUTE_VIRTUAL: u16 = 0x0040;
+
+/// `MethodSemantics.Association`.
+const METHOD_SEMANTICS_ASSOCIATION: usize = 2;
+
+/// The body every kept method gets: `ldnull; throw` in a tiny header.
+const THROW_NULL_BODY: [u8; 3] = [0x0A, 0x14, 0x7A];
+
+const REFERENCE_ASSEMBLY_ATTRIBUTE: (&str, &str) = (
+    "System.Runtime.CompilerServices",
+    "ReferenceAssemblyAttribute",
+);
+const INTERNALS_VISIBLE_TO_ATTRIBUTE: &str =
+    "System.Runtime.CompilerServices.InternalsVisibleToAttribute";
+
+/// Assemblies that can define `ReferenceAssemblyAttribute`, for images
+/// without a `System.Object` reference to follow.
+const CORE_LIBRARIES: &[&str] = &[
+    "System.Runtime",
+    "netstandard",
+    "mscorlib",
+    "System.Private.CoreLib",
+];
+
+/// Signature of a parameterless instance constructor.
+const DEFAULT_CTOR_SIG: [u8; 3] = [0x20, 0x00, 0x01];
+/// A custom attribute value with no arguments.
+const EMPTY_ATTRIBUTE_VALUE: [u8; 4] = [0x01, 0x00, 0x00, 0x00];
+
+impl PeImage {
+    /// Generates a reference assembly from this implementation assembly, as
+    /// compilers do with `/refout`.
+    ///
+    /// The result keeps the types and members visible outside the assembly
+    /// with their attributes, plus internal ones when the assembly has an
+    /// `InternalsVisibleToAttribute`. It also keeps the instance fields of
+    /// value types, which decide their layout, and private virtual methods,
+    /// which implement interface members. Every method body becomes
+    /// `throw null`, and managed resources, the entry point and debug
+    /// information are dropped. The assembly gets a
+    /// `ReferenceAssemblyAttribute` and an MVID derived from its metadata,
+    /// so the same input always gives the same bytes.
+    ///
+    /// A strong-name signature area of the original size is reserved but
+    /// left empty; sign the result with [`PeImage::strong_name_sign`].
+    pub fn reference_assembly(&self) -> Result<Vec<u8>> {
+        let cor = self.cor_header().ok_or(Error::NotManaged)?;
+        if cor.flags & COMIMAGE_FLAGS_ILONLY == 0 {
+            return Err(Error::BadImageFormat(
+                "reference assemblies can only be generated from IL-only images".into(),
+            ));
+        }
+        let reader = self.metadata()?;
+        let assembly = TableId::Assembly.token(1);
+        if reader.row_count(TableId::Assembly) == 0 {
+            return Err(Error::BadMetadata("image has no assembly manifest".into()));
+        }
+
+        let mut pe = PeBuilder::from_image(self)?;
+        pe.set_cor_flags(cor.flags & !COMIMAGE_FLAGS_TRACKDEBUGDATA);
+        pe.set_entry_point(0);
+        pe.set_strong_name_signature_size(
+            self.strong_name_signature_range()
+                .map_or(0, |range| range.len() as u32),
+        );
+
+        let mut builder = MetadataBuilder::from_reader(&reader)?;
+        let removed = reference_removals(&reader, &builder)?;
+
+        // Field data is looked up by the original field rid, so stash its
+        // index in the FieldRVA row before rows are renumbered.
+        let mut field_data = Vec::new();
+        for rid in 1..=builder.row_count(TableId::FieldRva) {
+            let field = builder.row_values(TableId::FieldRva, rid)?[1];
+            if removed[TableId::FieldRva as usize][rid as usize] {
+                continue;
+            }
+            let data = self.field_data(&reader, field)?.ok_or_else(|| {
+                Error::BadMetadata(format!(
+                    "size of the data of field {:#010x} is unknown",
+                    TableId::Field.token(field)
+                ))
+            })?;
+            field_data.push(data);
+            builder.set_column(TableId::FieldRva, rid, 0, field_data.len() as u32)?;
+        }
+        builder.remove_marked(&removed)?;
+
+        let throw_null = pe.add_method_body(&THROW_NULL_BODY);
+        for rid in 1..=builder.row_count(TableId::MethodDef) {
+            if builder.row_values(TableId::MethodDef, rid)?[0] != 0 {
+                builder.set_column(TableId::MethodDef, rid, 0, throw_null)?;
+            }
+        }
+        for rid in 1..=builder.row_count(TableId::FieldRva) {
+            let index = builder.row_values(TableId::FieldRva, rid)?[0];
+            let rva = pe.add_field_data(field_data[index as usize - 1]);
+            builder.set_column(TableId::FieldRva, rid, 0, rva)?;
+        }
+
+        let (namespace, name) = REFERENCE_ASSEMBLY_ATTRIBUTE;
+        let full_name = format!("{namespace}.{name}");
+        if reader
+            .find_custom_attributes(assembly, &full_name)?
+            .is_empty()
+        {
+            let class = reference_assembly_attribute_type(&reader, &mut builder)?;
+            let row = MemberRefRow {
+                class,
+                name: builder.string(".ctor"),
+                signature: builder.blob(&DEFAULT_CTOR_SIG),
+            };
+            let ctor = builder.add(&row);
+            let value = builder.blob(&EMPTY_ATTRIBUTE_VALUE);
+            builder.add(&CustomAttributeRow {
+                parent: assembly,
+                ty: TableId::MemberRef.token(ctor),
+                value,
+            });
+        }
+        builder.sort_tables()?;
+        builder.derive_mvid()?;
+
+        pe.set_metadata(builder.to_bytes()?);
+        pe.to_bytes()
+    }
+}
+
+/// Works out which rows of `builder`, a copy of `reader`, a reference
+/// assembly leaves out, including the rows they take with them.
+fn reference_removals(
+    reader: &MetadataReader,
+    builder: &MetadataBuilder,
+) -> Result<Vec<Vec<bool>>> {
+    let assembly = TableId::Assembly.token(1);
+    let include_internal = !reader
+        .find_custom_attributes(assembly, INTERNALS_VISIBLE_TO_ATTRIBUTE)?
+        .is_empty();
+    let type_count = reader.row_count(TableId::TypeDef);
+
+    // Types: the visible ones and `<Module>`, then whatever the fields that
+    // make up a kept value type need.
+    let mut kept = vec![false; type_count as usize + 1];
+    let mut value_types = vec![false; type_count as usize + 1];
+    for rid in 1..=type_count {
+        kept[rid as usize] = rid == 1 || is_reference_type_visible(reader, rid, include_internal)?;
+        let extends = reader.row::<TypeDefRow>(rid)?.extends;
+        value_types[rid as usize] = token_rid(extends) != 0
+            && matches!(
+                reader.type_full_name(extends).as_deref(),
+                Ok("System.ValueType" | "System.Enum")
+            );
+    }
+    let mut pending: Vec<u32> = (1..=type_count).filter(|&rid| kept[rid as usize]).collect();
+    while let Some(rid) = pending.pop() {
+        let mut needed = Vec::new();
+        if let Some(outer) = reader.enclosing_type(rid)? {
+            needed.push(outer);
+        }
+        if value_types[rid as usize] {
+            for field in reader.type_def_fields(rid)? {
+                let row = reader.row::<FieldRow>(field)?;
+                if row.flags & MEMBER_STATIC == 0 {
+                    for token in signature_type_tokens(reader.blob(row.signature)?, false)? {
+                        if let Some((TableId::TypeDef, type_rid)) = TableId::from_token(token) {
+                            needed.push(type_rid);
+                        }
+                    }
+                }
+            }
+        }
+        for type_rid in needed {
+            if !std::mem::replace(&mut kept[type_rid as usize], true) {
+                pending.push(type_rid);
+            }
+        }
+    }
+
+    let mut tokens = BTreeSet::new();
+    for rid in 1..=type_count {
+        if !kept[rid as usize] {
+            tokens.insert(TableId::TypeDef.token(rid));
+            continue;
+        }
+        for field in reader.type_def_fields(rid)? {
+            let flags = reader.row::<FieldRow>(field)?.flags;
+            let is_layout = value_types[rid as usize] && flags & MEMBER_STATIC == 0;
+            if !is_layout && !is_reference_member_visible(flags, include_internal) {
+                tokens.insert(TableId::Field.token(field));
+            }
+        }
+        for method in reader.type_def_methods(rid)? {
+            let flags = reader.row::<MethodDefRow>(method)?.flags;
+            let is_interface_impl = flags & METHOD_ATTRIBUTE_VIRTUAL != 0;
+            if !is_interface_impl && !is_reference_member_visible(flags, include_internal) {
+                tokens.insert(TableId::MethodDef.token(method));
+            }
+        }
+    }
+    for table in [
+        TableId::ManifestResource,
+        TableId::StandAloneSig,
+        TableId::MethodSpec,
+    ] {
+        tokens.extend((1..=reader.row_count(table)).map(|rid| table.token(rid)));
+    }
+
+    // Removing rows orphans others: references only method bodies used,
+    // signatures naming removed types, and properties and events without
+    // accessors. Drop those until nothing changes.
+    loop {
+        let removed = builder.removal_closure(tokens.iter().copied())?;
+        let is_removed = |token: u32| {
+            TableId::from_token(token).is_some_and(|(table, rid)| {
+                removed[table as usize]
+                    .get(rid as usize)
+                    .copied()
+                    .unwrap_or(false)
+            })
+        };
+        let mut referenced = BTreeSet::new();
+        let mut accessed = BTreeSet::new();
+        for &table in TableId::ALL {
+            let columns = table.columns();
+            for rid in 1..=builder.row_count(table) {
+                if removed[table as usize][rid as usize] {
+                    continue;
+                }
+                let row = builder.row_values(table, rid)?;
+                for (column, &value) in columns.iter().zip(row) {
+                    match column.ty {
+                        ColumnType::Table(target) => referenced.insert(target.token(value)),
+                        ColumnType::Coded(_) => referenced.insert(value),
+                        _ => false,
+                    };
+                }
+                if table == TableId::MethodSemantics {
+                    accessed.insert(row[METHOD_SEMANTICS_ASSOCIATION]);
+                }
+            }
+        }
+
+        let mut orphans = Vec::new();
+        for table in [TableId::MemberRef, TableId::TypeSpec] {
+            for rid in 1..=builder.row_count(table) {
+                let token = table.token(rid);
+                if !is_removed(token) && !referenced.contains(&token) {
+                    orphans.push(token);
+                }
+            }
+        }
+        for table in [TableId::Property, TableId::Event] {
+            for rid in 1..=builder.row_count(table) {
+                let token = table.token(rid);
+                if !is_removed(token) && !accessed.contains(&token) {
+                    orphans.push(token);
+                }
+            }
+        }
+        for &(table, column, is_type) in SIGNATURE_COLUMNS {
+            for rid in 1..=builder.row_count(table) {
+                let token = table.token(rid);
+                if is_removed(token) {
+                    continue;
+                }
+                let blob = builder.blob_at(builder.row_values(table, rid)?[column])?;
+                if signature_type_tokens(blob, is_type)?
+                    .into_iter()
+                    .any(is_removed)
+                {
+                    orphans.push(token);
+                }
+            }
+        }
+        if orphans.is_empty() {
+            return Ok(removed);
+        }
+        tokens.extend(orphans);
+    }
+}
+
+/// Returns `true` if a type is visible to the assemblies that compile
+/// against the reference assembly.
+fn is_reference_type_visible(
+    reader: &MetadataReader,
+    rid: u32,
+    include_internal: bool,
+) -> Result<bool> {
+    let mut current = rid;
+    for _ in 0..reader.row_count(TableId::TypeDef) {
+        let visibility = reader.row::<TypeDefRow>(current)?.flags & TYPE_ATTRIBUTE_VISIBILITY_MASK;
+        let visible = match visibility {
+            TYPE_ATTRIBUTE_PUBLIC
+            | TYPE_ATTRIBUTE_NESTED_PUBLIC
+            | TYPE_ATTRIBUTE_NESTED_FAMILY
+            | TYPE_ATTRIBUTE_NESTED_FAM_OR_ASSEM => true,
+            TYPE_ATTRIBUTE_NESTED_PRIVATE => false,
+            _ => include_internal,
+        };
+        if !visible {
+            return Ok(false);
+        }
+        if matches!(
+            visibility,
+            TYPE_ATTRIBUTE_NOT_PUBLIC | TYPE_ATTRIBUTE_PUBLIC
+        ) {
+            return Ok(true);
+        }
+        match reader.enclosing_type(current)? {
+            Some(outer) => current = outer,
+            None => return Ok(false),
+        }
+    }
+    Err(Error::BadMetadata(format!(
+        "type {:#010x} is nested in itself",
+        TableId::TypeDef.token(rid)
+    )))
+}
+
+fn is_reference_member_visible(flags: u16, include_internal: bool) -> bool {
+    match flags & MEMBER_ACCESS_MASK {
+        MEMBER_ACCESS_PUBLIC | MEMBER_ACCESS_FAMILY | MEMBER_ACCESS_FAM_OR_ASSEM => true,
+        MEMBER_ACCESS_ASSEMBLY | MEMBER_ACCESS_FAM_AND_ASSEM => include_internal,
+        _ => false,
+    }
+}
+
+/// Returns the `TypeDefOrRef` tokens a signature blob mentions.
+fn signature_type_tokens(blob: &[u8], is_type: bool) -> Result<Vec<u32>> {
+    let mut tokens = Vec::new();
+    remap_signature(blob, is_type, &mut |token| {
+        tokens.push(token);
+        Ok((token, None))
+    })?;
+    Ok(tokens)
+}
+
+/// Returns the `ReferenceAssemblyAttribute` type to attach to the assembly:
+/// the assembly's own definition in the core library, an existing reference,
+/// or a new reference into the assembly `System.Object` comes from or, failing
+/// that, into a referenced core library.
+fn reference_assembly_attribute_type(
+    reader: &MetadataReader,
+    builder: &mut MetadataBuilder,
+) -> Result<u32> {
+    let (namespace, name) = REFERENCE_ASSEMBLY_ATTRIBUTE;
+    let is_named = |row: &[u32], ns: &str, n: &str| {
+        reader.string(row[1]).ok() == Some(n) && reader.string(row[2]).ok() == Some(ns)
+    };
+    for rid in 1..=builder.row_count(TableId::TypeDef) {
+        if is_named(builder.row_values(TableId::TypeDef, rid)?, namespace, name) {
+            return Ok(TableId::TypeDef.token(rid));
+        }
+    }
+    // Type references are never removed, so their rids match the reader's.
+    let mut object_scope = None;
+    for rid in 1..=builder.row_count(TableId::TypeRef) {
+        let row = builder.row_values(TableId::TypeRef, rid)?;
+        if is_named(row, namespace, name) {
+            return Ok(TableId::TypeRef.token(rid));
+        }
+        if is_named(row, "System", "Object") && object_scope.is_none() {
+            object_scope = Some(row[0]);
+        }
+    }
+    if object_scope.is_none() {
+        for rid in 1..=builder.row_count(TableId::AssemblyRef) {
+            let name = reader.string(builder.row::<AssemblyRefRow>(rid)?.name)?;
+            if CORE_LIBRARIES.contains(&name) {
+                object_scope = Some(TableId::AssemblyRef.token(rid));
+                break;
+            }
+        }
+    }
+    let resolution_scope = object_scope.ok_or_else(|| {
+        Error::BadMetadata(
+            "cannot find the core library to reference ReferenceAssemblyAttribute from".into(),
+        )
+    })?;
+    let row = TypeRefRow {
+        resolution_scope,
+        name: builder.string(name),
+        namespace: builder.string(namespace),
+    };
+    Ok(TableId::TypeRef.token(builder.add(&row)))
+}
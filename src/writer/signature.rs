@@ -0,0 +1,175 @@
+//! Rewriting the type tokens of signature blobs (II.23.2).
+
+use super::metadata::write_compressed_u32;
+use crate::error::{Error, Result};
+use crate::reader::{
+    CodedIndex, Cursor, ELEMENT_TYPE_ARRAY, ELEMENT_TYPE_BYREF, ELEMENT_TYPE_CLASS,
+    ELEMENT_TYPE_CMOD_OPT, ELEMENT_TYPE_CMOD_REQD, ELEMENT_TYPE_FNPTR, ELEMENT_TYPE_GENERICINST,
+    ELEMENT_TYPE_I, ELEMENT_TYPE_MVAR, ELEMENT_TYPE_OBJECT, ELEMENT_TYPE_PINNED, ELEMENT_TYPE_PTR,
+    ELEMENT_TYPE_SENTINEL, ELEMENT_TYPE_STRING, ELEMENT_TYPE_SZARRAY, ELEMENT_TYPE_TYPEDBYREF,
+    ELEMENT_TYPE_U, ELEMENT_TYPE_VALUETYPE, ELEMENT_TYPE_VAR, ELEMENT_TYPE_VOID,
+    IMAGE_CEE_CS_CALLCONV_FIELD, IMAGE_CEE_CS_CALLCONV_GENERIC, IMAGE_CEE_CS_CALLCONV_GENERICINST,
+    IMAGE_CEE_CS_CALLCONV_LOCAL_SIG, IMAGE_CEE_CS_CALLCONV_MASK, IMAGE_CEE_CS_CALLCONV_PROPERTY,
+    TableId,
+};
+
+/// The signature blob columns: table, column and whether the blob is a bare
+/// type (`TypeSpec`) rather than a field, method, property, local or
+/// method instantiation signature.
+pub(crate) const SIGNATURE_COLUMNS: &[(TableId, usize, bool)] = &[
+    (TableId::Field, 2, false),
+    (TableId::MethodDef, 4, false),
+    (TableId::MemberRef, 2, false),
+    (TableId::StandAloneSig, 0, false),
+    (TableId::Property, 2, false),
+    (TableId::TypeSpec, 0, true),
+    (TableId::MethodSpec, 1, false),
+];
+
+/// Maps a `TypeDefOrRef` token to its replacement and, if the replacement
+/// changes between `class` and `valuetype`, whether it is a value type.
+pub(crate) type TypeTokenMap<'a> = dyn FnMut(u32) -> Result<(u32, Option<bool>)> + 'a;
+
+/// Copies a signature blob with its type tokens passed through `map`.
+/// `is_type` is set for `TypeSpec` blobs, which hold a bare type.
+pub(crate) fn remap_signature(
+    blob: &[u8],
+    is_type: bool,
+    map: &mut TypeTokenMap,
+) -> Result<Vec<u8>> {
+    SignatureRewriter {
+        blob,
+        cursor: Cursor::new(blob),
+        out: Vec::with_capacity(blob.len()),
+        map,
+    }
+    .rewrite(is_type)
+}
+
+struct SignatureRewriter<'a, 'm> {
+    blob: &'a [u8],
+    cursor: Cursor<'a>,
+    out: Vec<u8>,
+    map: &'a mut TypeTokenMap<'m>,
+}
+
+impl SignatureRewriter<'_, '_> {
+    fn rewrite(mut self, is_type: bool) -> Result<Vec<u8>> {
+        if is_type {
+            self.ty()?;
+            return Ok(self.out);
+        }
+        let first = self.byte()?;
+        match first & IMAGE_CEE_CS_CALLCONV_MASK {
+            IMAGE_CEE_CS_CALLCONV_FIELD => self.ty()?,
+            IMAGE_CEE_CS_CALLCONV_LOCAL_SIG | IMAGE_CEE_CS_CALLCONV_GENERICINST => {
+                for _ in 0..self.compressed()? {
+                    self.ty()?;
+                }
+            }
+            IMAGE_CEE_CS_CALLCONV_PROPERTY => self.method_body(false)?,
+            _ => self.method_body(first & IMAGE_CEE_CS_CALLCONV_GENERIC != 0)?,
+        }
+        self.out.extend_from_slice(self.cursor.rest());
+        Ok(self.out)
+    }
+
+    fn method_body(&mut self, generic: bool) -> Result<()> {
+        if generic {
+            self.compressed()?;
+        }
+        let count = self.compressed()?;
+        self.ty()?;
+        for _ in 0..count {
+            if self.cursor.peek_u8()? == ELEMENT_TYPE_SENTINEL {
+                self.byte()?;
+            }
+            self.ty()?;
+        }
+        Ok(())
+    }
+
+    fn ty(&mut self) -> Result<()> {
+        match self.byte()? {
+            ELEMENT_TYPE_PTR | ELEMENT_TYPE_BYREF | ELEMENT_TYPE_SZARRAY | ELEMENT_TYPE_PINNED => {
+                self.ty()
+            }
+            ELEMENT_TYPE_CMOD_REQD | ELEMENT_TYPE_CMOD_OPT => {
+                self.type_token(None)?;
+                self.ty()
+            }
+            element_type @ (ELEMENT_TYPE_CLASS | ELEMENT_TYPE_VALUETYPE) => {
+                self.type_token(Some(element_type))
+            }
+            ELEMENT_TYPE_VAR | ELEMENT_TYPE_MVAR => self.compressed().map(drop),
+            ELEMENT_TYPE_ARRAY => {
+                self.ty()?;
+                self.compressed()?;
+                for _ in 0..self.compressed()? {
+                    self.compressed()?;
+                }
+                for _ in 0..self.compressed()? {
+                    self.compressed()?;
+                }
+                Ok(())
+            }
+            ELEMENT_TYPE_GENERICINST => {
+                let element_type = self.byte()?;
+                self.type_token(Some(element_type))?;
+                for _ in 0..self.compressed()? {
+                    self.ty()?;
+                }
+                Ok(())
+            }
+            ELEMENT_TYPE_FNPTR => {
+                let calling_convention = self.byte()?;
+                self.method_body(calling_convention & IMAGE_CEE_CS_CALLCONV_GENERIC != 0)
+            }
+            ELEMENT_TYPE_VOID..=ELEMENT_TYPE_STRING
+            | ELEMENT_TYPE_TYPEDBYREF
+            | ELEMENT_TYPE_I
+            | ELEMENT_TYPE_U
+            | ELEMENT_TYPE_OBJECT => Ok(()),
+            other => Err(Error::BadSignature(format!(
+                "unexpected element type {other:#04x}"
+            ))),
+        }
+    }
+
+    /// Copies a `TypeDefOrRef` token through the map. `element_type` is the
+    /// `class` or `valuetype` byte just written before it, if any.
+    fn type_token(&mut self, element_type: Option<u8>) -> Result<()> {
+        let raw = self.cursor.compressed_u32()?;
+        let token = CodedIndex::TypeDefOrRef
+            .decode(raw)
+            .ok_or_else(|| Error::BadSignature(format!("invalid TypeDefOrRef {raw:#x}")))?;
+        let (target, value_type) = (self.map)(token)?;
+        if let (Some(_), Some(value_type)) = (element_type, value_type) {
+            *self.out.last_mut().unwrap() = if value_type {
+                ELEMENT_TYPE_VALUETYPE
+            } else {
+                ELEMENT_TYPE_CLASS
+            };
+        }
+        let raw = CodedIndex::TypeDefOrRef
+            .encode(target)
+            .ok_or_else(|| Error::BadSignature(format!("invalid type token {target:#010x}")))?;
+        write_compressed_u32(&mut self.out, raw);
+        Ok(())
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        let byte = self.cursor.u8()?;
+        self.out.push(byte);
+        Ok(byte)
+    }
+
+    /// Copies a compressed integer verbatim and returns its unsigned value.
+    fn compressed(&mut self) -> Result<u32> {
+        let start = self.cursor.position();
+        let value = self.cursor.compressed_u32()?;
+        self.out
+            .extend_from_slice(&self.blob[start..self.cursor.position()]);
+        Ok(value)
+    }
+}
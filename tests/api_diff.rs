@@ -0,0 +1,268 @@
+mod common;
+
+use common::{Fixture, string_attribute};
+use mscoree::{ApiChangeKind, ApiSurface, ExportedTypeRow, NestedClassRow, TableId, token_rid};
+
+const PUBLIC: u32 = 0x0010_0001;
+const SEALED: u32 = 0x0000_0100;
+const NESTED_PUBLIC: u32 = 0x0010_0002;
+const NESTED_FAMILY: u32 = 0x0010_0004;
+/// `TypeAttributes.Forwarder` of an `ExportedType`.
+const FORWARDER: u32 = 0x0020_0000;
+
+const PUBLIC_METHOD: u16 = 0x0086;
+const PROTECTED_METHOD: u16 = 0x0084;
+const PRIVATE_METHOD: u16 = 0x0081;
+/// `instance void ()`.
+const VOID: [u8; 3] = [0x20, 0x00, 0x01];
+
+/// The API surface of `Lib`, with the types `build` adds; they derive from
+/// the `System.Object` token it is given.
+fn surface(build: impl FnOnce(&mut Fixture, u32)) -> ApiSurface {
+    let mut lib = Fixture::new("Lib.dll");
+    let runtime = lib.assembly_ref("System.Runtime", 8);
+    let object = lib.type_ref(runtime, "System", "Object");
+    build(&mut lib, object);
+    lib.to_reader().api_surface().unwrap()
+}
+
+/// The kind, breaking flag, type and member of each change.
+fn changes(old: &ApiSurface, new: &ApiSurface) -> Vec<(ApiChangeKind, bool, String, String)> {
+    old.diff(new)
+        .changes
+        .into_iter()
+        .map(|change| {
+            let member = change.member.unwrap_or_default();
+            (change.kind, change.breaking, change.type_name, member)
+        })
+        .collect()
+}
+
+fn change(
+    kind: ApiChangeKind,
+    breaking: bool,
+    ty: &str,
+    member: &str,
+) -> (ApiChangeKind, bool, String, String) {
+    (kind, breaking, ty.to_owned(), member.to_owned())
+}
+
+/// Forwards `Lib.Moved` to `Lib.Core` with an `ExportedType` row.
+fn export_moved(lib: &mut Fixture) -> u32 {
+    let core = lib.assembly_ref("Lib.Core", 1);
+    let (namespace, name) = (lib.b.string("Lib"), lib.b.string("Moved"));
+    TableId::ExportedType.token(lib.b.add(&ExportedTypeRow {
+        flags: FORWARDER,
+        type_def_id: 0,
+        name,
+        namespace,
+        implementation: core,
+    }))
+}
+
+#[test]
+fn removed_type_is_breaking() {
+    let old = surface(|lib, object| {
+        lib.type_def(PUBLIC, "Lib", "Kept", object);
+        lib.type_def(PUBLIC, "Lib", "Removed", object);
+    });
+    let new = surface(|lib, object| {
+        lib.type_def(PUBLIC, "Lib", "Kept", object);
+    });
+    assert_eq!(
+        changes(&old, &new),
+        [change(ApiChangeKind::TypeRemoved, true, "Lib.Removed", "")]
+    );
+    assert!(old.diff(&new).is_breaking());
+}
+
+#[test]
+fn added_type_is_not_breaking() {
+    let old = surface(|_, _| {});
+    let new = surface(|lib, object| {
+        lib.type_def(PUBLIC, "Lib", "Added", object);
+        // Internal types are not part of the surface.
+        lib.type_def(0x0010_0000, "Lib", "Internal", object);
+    });
+    assert_eq!(
+        changes(&old, &new),
+        [change(ApiChangeKind::TypeAdded, false, "Lib.Added", "")]
+    );
+    assert!(!old.diff(&new).is_breaking());
+}
+
+#[test]
+fn type_forwarded_by_exported_type_is_not_removed() {
+    let old = surface(|lib, object| {
+        lib.type_def(PUBLIC, "Lib", "Moved", object);
+    });
+    let new = surface(|lib, _| {
+        export_moved(lib);
+    });
+    let forwarded = new.find_forwarded_type("Lib.Moved").unwrap();
+    assert_eq!(forwarded.assembly, "Lib.Core");
+    assert_eq!(
+        changes(&old, &new),
+        [change(ApiChangeKind::TypeForwarded, false, "Lib.Moved", "")]
+    );
+}
+
+#[test]
+fn type_forwarded_by_attribute_is_not_removed() {
+    let old = surface(|lib, object| {
+        lib.type_def(PUBLIC, "Lib", "Moved", object);
+    });
+    let new = surface(|lib, _| {
+        let runtime = TableId::AssemblyRef.token(1);
+        let ty = lib.type_ref(runtime, "System", "Type");
+        let attribute = lib.type_ref(
+            runtime,
+            "System.Runtime.CompilerServices",
+            "TypeForwardedToAttribute",
+        );
+        // .ctor(class System.Type)
+        let signature = [0x20, 0x01, 0x01, 0x12, (token_rid(ty) << 2 | 1) as u8];
+        let ctor = lib.member_ref(attribute, ".ctor", &signature);
+        let value = string_attribute("Lib.Moved, Lib.Core, Version=1.0.0.0, Culture=neutral");
+        lib.attribute(TableId::Assembly.token(1), ctor, &value);
+    });
+    let forwarded = new.find_forwarded_type("Lib.Moved").unwrap();
+    assert_eq!(forwarded.assembly, "Lib.Core");
+    assert!(!old.diff(&new).is_breaking());
+}
+
+#[test]
+fn forwarded_nested_types_are_named_after_their_enclosing_type() {
+    let surface = surface(|lib, _| {
+        let moved = export_moved(lib);
+        let (namespace, name) = (lib.b.string(""), lib.b.string("Inner"));
+        lib.b.add(&ExportedTypeRow {
+            flags: FORWARDER,
+            type_def_id: 0,
+            name,
+            namespace,
+            implementation: moved,
+        });
+    });
+    let names: Vec<_> = surface
+        .forwarded_types
+        .iter()
+        .map(|ty| ty.name.as_str())
+        .collect();
+    assert_eq!(names, ["Lib.Moved", "Lib.Moved+Inner"]);
+}
+
+#[test]
+fn removed_forwarder_is_breaking() {
+    let old = surface(|lib, _| {
+        export_moved(lib);
+    });
+    let new = surface(|_, _| {});
+    assert_eq!(
+        changes(&old, &new),
+        [change(ApiChangeKind::TypeRemoved, true, "Lib.Moved", "")]
+    );
+}
+
+#[test]
+fn reduced_visibility_is_breaking() {
+    let old = surface(|lib, object| {
+        let outer = lib.type_def(PUBLIC, "Lib", "Widget", object);
+        lib.method(0, PUBLIC_METHOD, "Run", &VOID);
+        lib.method(0, PROTECTED_METHOD, "Hook", &VOID);
+        let part = lib.type_def(NESTED_PUBLIC, "", "Part", object);
+        lib.b.add(&NestedClassRow {
+            nested_class: token_rid(part),
+            enclosing_class: token_rid(outer),
+        });
+    });
+    let new = surface(|lib, object| {
+        let outer = lib.type_def(PUBLIC, "Lib", "Widget", object);
+        lib.method(0, PROTECTED_METHOD, "Run", &VOID);
+        lib.method(0, PUBLIC_METHOD, "Hook", &VOID);
+        let part = lib.type_def(NESTED_FAMILY, "", "Part", object);
+        lib.b.add(&NestedClassRow {
+            nested_class: token_rid(part),
+            enclosing_class: token_rid(outer),
+        });
+    });
+    assert_eq!(
+        changes(&old, &new),
+        [
+            change(
+                ApiChangeKind::VisibilityChanged,
+                false,
+                "Lib.Widget",
+                "method Hook()"
+            ),
+            change(
+                ApiChangeKind::VisibilityChanged,
+                true,
+                "Lib.Widget",
+                "method Run()"
+            ),
+            change(
+                ApiChangeKind::VisibilityChanged,
+                true,
+                "Lib.Widget+Part",
+                ""
+            ),
+        ]
+    );
+}
+
+#[test]
+fn removed_members_are_breaking_and_added_ones_are_not() {
+    let old = surface(|lib, object| {
+        lib.type_def(PUBLIC, "Lib", "Widget", object);
+        lib.method(0, PUBLIC_METHOD, "Run", &VOID);
+        lib.method(0, PUBLIC_METHOD, "Gone", &VOID);
+    });
+    let new = surface(|lib, object| {
+        lib.type_def(PUBLIC, "Lib", "Widget", object);
+        lib.method(0, PUBLIC_METHOD, "Run", &VOID);
+        lib.field(0x0006, "Count", &[0x06, 0x08]);
+    });
+    assert_eq!(
+        changes(&old, &new),
+        [
+            change(
+                ApiChangeKind::MemberRemoved,
+                true,
+                "Lib.Widget",
+                "method Gone()"
+            ),
+            change(
+                ApiChangeKind::MemberAdded,
+                false,
+                "Lib.Widget",
+                "field Count"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn protected_members_of_sealed_types_are_not_surface() {
+    let old = surface(|lib, object| {
+        let outer = lib.type_def(PUBLIC | SEALED, "Lib", "Closed", object);
+        lib.method(0, PUBLIC_METHOD, "Open", &VOID);
+        lib.method(0, PROTECTED_METHOD, "Hook", &VOID);
+        let nested = lib.type_def(NESTED_FAMILY, "", "Part", object);
+        lib.b.add(&NestedClassRow {
+            nested_class: token_rid(nested),
+            enclosing_class: token_rid(outer),
+        });
+    });
+    let closed = old.find_type("Lib.Closed").unwrap();
+    let members: Vec<_> = closed.members.iter().map(ToString::to_string).collect();
+    assert_eq!(members, ["method Open()"]);
+    assert!(old.find_type("Lib.Closed+Part").is_none());
+
+    let new = surface(|lib, object| {
+        lib.type_def(PUBLIC | SEALED, "Lib", "Closed", object);
+        lib.method(0, PUBLIC_METHOD, "Open", &VOID);
+        lib.method(0, PRIVATE_METHOD, "Hook", &VOID);
+    });
+    assert_eq!(changes(&old, &new), []);
+}
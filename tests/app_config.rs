@@ -0,0 +1,85 @@
+use mscoree::{AppConfig, RuntimeSource, requested_runtime};
+
+fn config(startup: &str) -> AppConfig {
+    AppConfig::parse(&format!("<configuration>{startup}</configuration>")).unwrap()
+}
+
+/// The version and source of the runtime `requested_runtime` picks.
+fn pick(
+    config: Option<&AppConfig>,
+    image_version: Option<&str>,
+    installed: &[&str],
+    apply_upgrade_policy: bool,
+) -> Option<(String, RuntimeSource)> {
+    requested_runtime(config, image_version, installed, apply_upgrade_policy)
+        .map(|runtime| (runtime.version, runtime.source))
+}
+
+fn runtime(version: &str, source: RuntimeSource) -> Option<(String, RuntimeSource)> {
+    Some((version.to_owned(), source))
+}
+
+#[test]
+fn first_installed_supported_runtime_wins() {
+    let config = config(
+        r#"<startup>
+             <supportedRuntime version="v1.1.4322" />
+             <supportedRuntime version="v4.0" sku=".NETFramework,Version=v4.8" />
+             <supportedRuntime version="v2.0.50727" />
+           </startup>"#,
+    );
+    let installed = ["v2.0.50727", "v4.0.30319"];
+    let selected = requested_runtime(Some(&config), None, &installed, false).unwrap();
+    assert_eq!(selected.version, "v4.0.30319");
+    assert_eq!(selected.source, RuntimeSource::ConfigFile);
+    assert_eq!(selected.sku.as_deref(), Some(".NETFramework,Version=v4.8"));
+}
+
+#[test]
+fn required_runtime_applies_when_no_supported_runtime_is_installed() {
+    let config = config(
+        r#"<startup>
+             <supportedRuntime version="v1.1.4322" />
+             <requiredRuntime version="v2.0.50727" />
+           </startup>"#,
+    );
+    assert_eq!(
+        pick(Some(&config), None, &["v2.0.50727", "v4.0.30319"], false),
+        runtime("v2.0.50727", RuntimeSource::ConfigFile)
+    );
+}
+
+#[test]
+fn image_version_applies_when_the_configuration_matches_nothing() {
+    let config = config(r#"<startup><supportedRuntime version="v1.1.4322" /></startup>"#);
+    assert_eq!(
+        pick(Some(&config), Some("v2.0.50727"), &["v2.0.50727"], false),
+        runtime("v2.0.50727", RuntimeSource::Image)
+    );
+}
+
+#[test]
+fn upgrade_policy_rolls_forward_when_no_supported_runtime_is_installed() {
+    let config = config(r#"<startup><supportedRuntime version="v1.1.4322" /></startup>"#);
+    let installed = ["v4.0.30319", "v2.0.50727"];
+    assert_eq!(
+        pick(Some(&config), None, &installed, true),
+        runtime("v4.0.30319", RuntimeSource::UpgradePolicy)
+    );
+    assert_eq!(pick(Some(&config), None, &installed, false), None);
+}
+
+#[test]
+fn legacy_activation_policy_is_reported_with_the_runtime() {
+    let legacy = config(
+        r#"<startup useLegacyV2RuntimeActivationPolicy="true">
+             <supportedRuntime version="v4.0" />
+           </startup>"#,
+    );
+    let runtime = requested_runtime(Some(&legacy), None, &["v4.0.30319"], false).unwrap();
+    assert!(runtime.use_legacy_v2_runtime_activation_policy);
+
+    let default = config(r#"<startup><supportedRuntime version="v4.0" /></startup>"#);
+    let runtime = requested_runtime(Some(&default), None, &["v4.0.30319"], false).unwrap();
+    assert!(!runtime.use_legacy_v2_runtime_activation_policy);
+}
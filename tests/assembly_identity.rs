@@ -0,0 +1,94 @@
+mod common;
+
+use common::test_key;
+use mscoree::{AssemblyCompareFlags, AssemblyIdentity, Error};
+
+fn parse(name: &str) -> Result<AssemblyIdentity, Error> {
+    name.parse()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[test]
+fn malformed_display_names_are_rejected() {
+    for name in [
+        "",
+        "Version=1.0.0.0",
+        "Lib, Version",
+        "Lib, Version=1.0.0.0, version=2.0.0.0",
+        "Lib, Version=1",
+        "Lib, Version=1.0.0.0.0",
+        "Lib, Version=1.x",
+        "Lib, Version=65536.0",
+        "Lib, PublicKeyToken=b77a5c56",
+        "Lib, PublicKeyToken=b77a5c561934e08g",
+        "Lib, PublicKey=002400000",
+        "Lib, PublicKey=00zz",
+        "Lib, PublicKey=00, PublicKeyToken=b77a5c561934e089",
+        "Lib, Retargetable=Maybe",
+        "Lib, ContentType=Native",
+        "Lib, ProcessorArchitecture=Z80",
+        "Lib, Culture=\"de",
+        "Lib\\",
+        "Lib, Culture=\"de\"x",
+    ] {
+        assert!(
+            matches!(parse(name), Err(Error::BadAssemblyName(_))),
+            "{name:?} was accepted"
+        );
+    }
+}
+
+#[test]
+fn unknown_properties_are_ignored() {
+    let identity = parse("Lib, Version=1.0, Custom=yes").unwrap();
+    assert_eq!(identity.to_string(), "Lib, Version=1.0");
+}
+
+#[test]
+fn quoted_and_escaped_values_are_unwrapped() {
+    let identity = parse(r#"Li\,b, Culture="de-DE""#).unwrap();
+    assert_eq!(identity.name, "Li,b");
+    assert_eq!(identity.culture.as_deref(), Some("de-DE"));
+}
+
+#[test]
+fn public_key_matches_its_token() {
+    let key = test_key();
+    let token = key.public_key_token().to_string();
+    let full = parse(&format!(
+        "Lib, Version=1.0.0.0, Culture=neutral, PublicKey={}",
+        hex(&key.public_key_blob())
+    ))
+    .unwrap();
+    assert_eq!(full.public_key_token(), Some(key.public_key_token()));
+    let reference = parse(&format!("Lib, PublicKeyToken={token}")).unwrap();
+    assert!(reference.is_equal(&full, AssemblyCompareFlags::DEFAULT));
+}
+
+#[test]
+fn public_key_token_mismatch_is_not_equal() {
+    let key = test_key();
+    let full = parse(&format!("Lib, PublicKey={}", hex(&key.public_key_blob()))).unwrap();
+    for reference in [
+        "Lib, PublicKeyToken=b77a5c561934e089",
+        "Lib, PublicKeyToken=null",
+    ] {
+        let reference = parse(reference).unwrap();
+        assert!(!reference.is_equal(&full, AssemblyCompareFlags::DEFAULT));
+    }
+    // A name without a token binds to any key.
+    assert!(
+        parse("Lib")
+            .unwrap()
+            .is_equal(&full, AssemblyCompareFlags::DEFAULT)
+    );
+}
+
+#[test]
+fn props_with_a_short_token_are_rejected() {
+    let result = AssemblyIdentity::from_props("Lib", [1, 0, 0, 0], "", &[1, 2, 3], 0);
+    assert!(matches!(result, Err(Error::BadAssemblyName(_))));
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use common::{Fixture, string_attribute};
+use mscoree::{TableId, Workspace, token_rid};
+
+/// `public interface` with `[ComImport]`.
+const COM_IMPORT_INTERFACE: u32 = 0x10A1;
+const PUBLIC_SEALED: u32 = 0x0101;
+/// `public hidebysig newslot abstract virtual`.
+const INTERFACE_METHOD: u16 = 0x05C6;
+/// `public specialname rtspecialname`, the flags of `value__`.
+const ENUM_VALUE: u16 = 0x0606;
+
+/// A `valuetype` signature element for `token`.
+fn value_type(token: u32) -> [u8; 2] {
+    let tag = match TableId::from_token(token) {
+        Some((TableId::TypeRef, _)) => 1,
+        _ => 0,
+    };
+    [0x11, (token_rid(token) << 2 | tag) as u8]
+}
+
+/// `Contoso.Colors`, which defines the `ushort` enum `Contoso.Shade`.
+fn colors() -> Fixture {
+    let mut colors = Fixture::new("Contoso.Colors.dll");
+    let runtime = colors.assembly_ref("System.Runtime", 8);
+    let base = colors.type_ref(runtime, "System", "Enum");
+    colors.type_def(PUBLIC_SEALED, "Contoso", "Shade", base);
+    colors.field(ENUM_VALUE, "value__", &[0x06, 0x07]);
+    colors
+}
+
+/// `Contoso` with the COM interface
+///
+/// ```csharp
+/// [ComImport, Guid("6d5140c1-7436-11ce-8034-00aa006009fa")]
+/// [InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+/// public interface IWidget {
+///     int Count();
+///     void Paint(Color color, Shade shade);
+/// }
+/// ```
+///
+/// where `Color` is a `byte` enum of `Contoso` and `Shade` comes from
+/// `Contoso.Colors`.
+fn contoso() -> Fixture {
+    let mut lib = Fixture::new("Contoso.dll");
+    let runtime = lib.assembly_ref("System.Runtime", 8);
+    let colors = lib.assembly_ref("Contoso.Colors", 1);
+    let base = lib.type_ref(runtime, "System", "Enum");
+    let shade = lib.type_ref(colors, "Contoso", "Shade");
+    let guid = lib.type_ref(runtime, "System.Runtime.InteropServices", "GuidAttribute");
+    let guid = lib.member_ref(guid, ".ctor", &[0x20, 0x01, 0x01, 0x0E]);
+    let interface_type = lib.type_ref(
+        runtime,
+        "System.Runtime.InteropServices",
+        "InterfaceTypeAttribute",
+    );
+    let interface_type = lib.member_ref(interface_type, ".ctor", &[0x20, 0x01, 0x01, 0x06]);
+
+    let color = lib.type_def(PUBLIC_SEALED, "Contoso", "Color", base);
+    lib.field(ENUM_VALUE, "value__", &[0x06, 0x05]);
+    let widget = lib.type_def(COM_IMPORT_INTERFACE, "Contoso", "IWidget", 0);
+    lib.attribute(
+        widget,
+        guid,
+        &string_attribute("6d5140c1-7436-11ce-8034-00aa006009fa"),
+    );
+    // InterfaceType(ComInterfaceType.InterfaceIsIUnknown)
+    lib.attribute(
+        widget,
+        interface_type,
+        &[0x01, 0x00, 0x01, 0x00, 0x00, 0x00],
+    );
+    lib.method(0, INTERFACE_METHOD, "Count", &[0x20, 0x00, 0x08]);
+    let mut paint = vec![0x20, 0x02, 0x01];
+    paint.extend_from_slice(&value_type(color));
+    paint.extend_from_slice(&value_type(shade));
+    lib.method(0, INTERFACE_METHOD, "Paint", &paint);
+    lib
+}
+
+#[test]
+fn interface_is_generated_as_a_trait() {
+    let bindings = contoso().to_reader().com_bindings().unwrap();
+    let [widget] = &bindings.interfaces[..] else {
+        panic!("expected one interface");
+    };
+    assert_eq!(widget.full_name, "Contoso.IWidget");
+    assert_eq!(widget.iid, "6D5140C1-7436-11CE-8034-00AA006009FA");
+    assert_eq!(
+        bindings.to_string(),
+        "\
+//! COM interfaces of the `Contoso` assembly.
+//!
+//! Generated from the assembly metadata.
+
+use windows::core::{HRESULT, IUnknown, IUnknown_Vtbl, interface};
+
+// Structures to define alongside these interfaces: Shade.
+
+/// IWidget - `Contoso.IWidget`.
+#[interface(\"6D5140C1-7436-11CE-8034-00AA006009FA\")]
+pub unsafe trait IWidget: IUnknown {
+    pub unsafe fn Count(&self, pRetVal: *mut i32) -> HRESULT;
+    pub unsafe fn Paint(&self, param1: u8, param2: Shade) -> HRESULT;
+}
+"
+    );
+}
+
+#[test]
+fn enums_from_referenced_assemblies_map_to_their_underlying_type() {
+    let mut workspace = Workspace::new();
+    let module = workspace.add_metadata(contoso().to_reader(), None).unwrap();
+    workspace.add_metadata(colors().to_reader(), None).unwrap();
+    let bindings = workspace.com_bindings(module).unwrap();
+    assert!(bindings.external_types.is_empty());
+    let paint = bindings.interfaces[0].methods[1].to_string();
+    assert_eq!(
+        paint,
+        "    pub unsafe fn Paint(&self, param1: u8, param2: u16) -> HRESULT;\n"
+    );
+}
+
+#[test]
+fn unresolved_enums_are_left_to_define() {
+    // Without `Contoso.Colors` loaded, `Shade` could be a struct.
+    let mut workspace = Workspace::new();
+    let module = workspace.add_metadata(contoso().to_reader(), None).unwrap();
+    let bindings = workspace.com_bindings(module).unwrap();
+    assert_eq!(
+        bindings.external_types.iter().collect::<Vec<_>>(),
+        ["Shade"]
+    );
+}
@@ -0,0 +1,219 @@
+//! Fixtures shared by the integration tests.
+
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use mscoree::{
+    AssemblyRefRow, AssemblyRow, CustomAttributeRow, FieldRow, MemberRefRow, MetadataBuilder,
+    MetadataReader, MethodDefRow, ModuleRow, StrongNameKeyPair, TableId, TypeDefRow, TypeRefRow,
+};
+use rsa::RsaPrivateKey;
+use rsa::rand_core::{CryptoRng, RngCore};
+use rsa::traits::{PrivateKeyParts, PublicKeyParts};
+
+/// The metadata of a test assembly: the module `{name}.dll` or
+/// `{name}.exe`, the manifest of assembly `{name}` and `<Module>`.
+///
+/// Fields and methods belong to the type added last before them.
+pub struct Fixture {
+    pub b: MetadataBuilder,
+}
+
+impl Fixture {
+    pub fn new(module: &str) -> Self {
+        Fixture::versioned(module, 1)
+    }
+
+    /// Like [`Fixture::new`], for version `major_version`.0.0.0 of the
+    /// assembly.
+    pub fn versioned(module: &str, major_version: u16) -> Self {
+        let mut b = MetadataBuilder::new();
+        let row = ModuleRow {
+            name: b.string(module),
+            mvid: b.guid([1; 16]),
+            ..Default::default()
+        };
+        b.add(&row);
+        let name = module.rsplit_once('.').map_or(module, |(name, _)| name);
+        let row = AssemblyRow {
+            hash_alg_id: 0x8004,
+            major_version,
+            name: b.string(name),
+            ..Default::default()
+        };
+        b.add(&row);
+        let mut fixture = Fixture { b };
+        fixture.type_def(0, "", "<Module>", 0);
+        fixture
+    }
+
+    /// Adds a reference to assembly `name` at `major_version` and returns
+    /// its token.
+    pub fn assembly_ref(&mut self, name: &str, major_version: u16) -> u32 {
+        let name = self.b.string(name);
+        TableId::AssemblyRef.token(self.b.add(&AssemblyRefRow {
+            name,
+            major_version,
+            ..Default::default()
+        }))
+    }
+
+    pub fn type_ref(&mut self, scope: u32, namespace: &str, name: &str) -> u32 {
+        let (namespace, name) = (self.b.string(namespace), self.b.string(name));
+        TableId::TypeRef.token(self.b.add(&TypeRefRow {
+            resolution_scope: scope,
+            namespace,
+            name,
+        }))
+    }
+
+    pub fn member_ref(&mut self, class: u32, name: &str, signature: &[u8]) -> u32 {
+        let (name, signature) = (self.b.string(name), self.b.blob(signature));
+        TableId::MemberRef.token(self.b.add(&MemberRefRow {
+            class,
+            name,
+            signature,
+        }))
+    }
+
+    /// Adds a type owning the fields and methods added after it.
+    pub fn type_def(&mut self, flags: u32, namespace: &str, name: &str, extends: u32) -> u32 {
+        let (namespace, name) = (self.b.string(namespace), self.b.string(name));
+        TableId::TypeDef.token(self.b.add(&TypeDefRow {
+            flags,
+            namespace,
+            name,
+            extends,
+            field_list: self.b.row_count(TableId::Field) + 1,
+            method_list: self.b.row_count(TableId::MethodDef) + 1,
+        }))
+    }
+
+    pub fn field(&mut self, flags: u16, name: &str, signature: &[u8]) -> u32 {
+        let (name, signature) = (self.b.string(name), self.b.blob(signature));
+        TableId::Field.token(self.b.add(&FieldRow {
+            flags,
+            name,
+            signature,
+        }))
+    }
+
+    pub fn method(&mut self, rva: u32, flags: u16, name: &str, signature: &[u8]) -> u32 {
+        let (name, signature) = (self.b.string(name), self.b.blob(signature));
+        TableId::MethodDef.token(self.b.add(&MethodDefRow {
+            rva,
+            flags,
+            name,
+            signature,
+            param_list: self.b.row_count(TableId::Param) + 1,
+            ..Default::default()
+        }))
+    }
+
+    /// Applies the attribute constructed by `ctor` with the value blob
+    /// `value` to `parent`.
+    pub fn attribute(&mut self, parent: u32, ctor: u32, value: &[u8]) {
+        let value = self.b.blob(value);
+        self.b.add(&CustomAttributeRow {
+            parent,
+            ty: ctor,
+            value,
+        });
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.b.to_bytes().unwrap()
+    }
+
+    pub fn to_reader(&self) -> MetadataReader {
+        MetadataReader::from_bytes(self.to_bytes()).unwrap()
+    }
+}
+
+/// A custom attribute value with a single string or `Type` argument.
+pub fn string_attribute(value: &str) -> Vec<u8> {
+    let mut blob = vec![0x01, 0x00];
+    match value.len() {
+        len @ 0..0x80 => blob.push(len as u8),
+        len => blob.extend_from_slice(&(len as u16 | 0x8000).to_be_bytes()),
+    }
+    blob.extend_from_slice(value.as_bytes());
+    blob.extend_from_slice(&[0x00, 0x00]);
+    blob
+}
+
+/// Creates an empty directory for the test `name`, removing what an earlier
+/// run left behind.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mscoree-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A fixed RSA-1024 key pair, generated from a seeded generator so that
+/// tests need no key file.
+pub fn test_key() -> StrongNameKeyPair {
+    StrongNameKeyPair::from_snk(&snk(0x5EED)).unwrap()
+}
+
+/// An RSA-1024 key pair in `.snk` (`PRIVATEKEYBLOB`) format, generated from
+/// `seed`.
+pub fn snk(seed: u64) -> Vec<u8> {
+    let key = RsaPrivateKey::new(&mut SplitMix64(seed), 1024).unwrap();
+    let [p, q] = key.primes() else {
+        panic!("expected two primes");
+    };
+    let mut snk = vec![0x07, 0x02, 0x00, 0x00];
+    snk.extend_from_slice(&0x2400u32.to_le_bytes());
+    snk.extend_from_slice(b"RSA2");
+    snk.extend_from_slice(&1024u32.to_le_bytes());
+    snk.extend_from_slice(&65537u32.to_le_bytes());
+    let crt_coefficient = key.crt_coefficient().unwrap();
+    for (value, size) in [
+        (key.n(), 128),
+        (p, 64),
+        (q, 64),
+        (key.dp().unwrap(), 64),
+        (key.dq().unwrap(), 64),
+        (&crt_coefficient, 64),
+        (key.d(), 128),
+    ] {
+        let mut bytes = value.to_bytes_le();
+        bytes.resize(size, 0);
+        snk.extend_from_slice(&bytes);
+    }
+    snk
+}
+
+/// SplitMix64, a deterministic stand-in for a system RNG.
+struct SplitMix64(u64);
+
+impl RngCore for SplitMix64 {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rsa::rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for SplitMix64 {}
@@ -0,0 +1,152 @@
+mod common;
+
+use common::Fixture;
+use mscoree::{ConstantRow, ConstantValue, Error, TableId, token_rid};
+
+/// Every `ELEMENT_TYPE` a constant can have, with a blob and its value.
+fn samples() -> Vec<(u8, Vec<u8>, ConstantValue)> {
+    vec![
+        (0x02, vec![1], ConstantValue::Boolean(true)),
+        (0x03, vec![0x41, 0x00], ConstantValue::Char(0x41)),
+        (0x04, vec![0xFF], ConstantValue::I1(-1)),
+        (0x05, vec![0xFF], ConstantValue::U1(255)),
+        (0x06, vec![0xFE, 0xFF], ConstantValue::I2(-2)),
+        (0x07, vec![0xFE, 0xFF], ConstantValue::U2(0xFFFE)),
+        (0x08, (-3i32).to_le_bytes().to_vec(), ConstantValue::I4(-3)),
+        (0x09, 7u32.to_le_bytes().to_vec(), ConstantValue::U4(7)),
+        (0x0A, (-4i64).to_le_bytes().to_vec(), ConstantValue::I8(-4)),
+        (
+            0x0B,
+            u64::MAX.to_le_bytes().to_vec(),
+            ConstantValue::U8(u64::MAX),
+        ),
+        (0x0C, 1.5f32.to_le_bytes().to_vec(), ConstantValue::R4(1.5)),
+        (
+            0x0D,
+            (-0.25f64).to_le_bytes().to_vec(),
+            ConstantValue::R8(-0.25),
+        ),
+        (
+            0x0E,
+            vec![b'h', 0, b'i', 0],
+            ConstantValue::String(vec![0x68, 0x69]),
+        ),
+        (0x12, vec![0; 4], ConstantValue::Null),
+    ]
+}
+
+#[test]
+fn every_element_type_decodes() {
+    for (element_type, blob, value) in samples() {
+        assert_eq!(ConstantValue::decode(element_type, &blob).unwrap(), value);
+        assert_eq!(value.element_type(), element_type);
+    }
+}
+
+#[test]
+fn values_format_as_csharp_literals() {
+    let literals: Vec<_> = samples()
+        .into_iter()
+        .map(|(_, _, value)| value.to_string())
+        .collect();
+    assert_eq!(
+        literals,
+        [
+            "true",
+            "'A'",
+            "-1",
+            "255",
+            "-2",
+            "65534",
+            "-3",
+            "7U",
+            "-4L",
+            "18446744073709551615UL",
+            "1.5F",
+            "-0.25",
+            "\"hi\"",
+            "null",
+        ]
+    );
+    let lone = ConstantValue::String(vec![0xD800]);
+    assert_eq!(lone.to_string(), "\"\\ud800\"");
+    assert_eq!(lone.to_string_lossy().as_deref(), Some("\u{FFFD}"));
+}
+
+#[test]
+fn empty_strings_decode() {
+    assert_eq!(
+        ConstantValue::decode(0x0E, &[]).unwrap(),
+        ConstantValue::String(Vec::new())
+    );
+}
+
+#[test]
+fn truncated_blobs_are_rejected() {
+    for (element_type, blob, _) in samples() {
+        if matches!(element_type, 0x0E | 0x12) {
+            continue;
+        }
+        let truncated = &blob[..blob.len() - 1];
+        assert!(
+            matches!(
+                ConstantValue::decode(element_type, truncated),
+                Err(Error::BadMetadata(_))
+            ),
+            "{element_type:#04x} decoded from {truncated:?}"
+        );
+    }
+    assert!(matches!(
+        ConstantValue::decode(0x0E, &[b'h', 0, b'i']),
+        Err(Error::BadMetadata(_))
+    ));
+}
+
+#[test]
+fn invalid_types_and_non_null_classes_are_rejected() {
+    for element_type in [0x00, 0x01, 0x0F, 0x11, 0x1C] {
+        assert!(matches!(
+            ConstantValue::decode(element_type, &[0; 8]),
+            Err(Error::BadMetadata(_))
+        ));
+    }
+    assert!(matches!(
+        ConstantValue::decode(0x12, &[1, 0, 0, 0]),
+        Err(Error::BadMetadata(_))
+    ));
+}
+
+#[test]
+fn enum_members_are_read_from_the_constant_table() {
+    let mut fixture = Fixture::new("Enums.dll");
+    let runtime = fixture.assembly_ref("System.Runtime", 8);
+    let base = fixture.type_ref(runtime, "System", "Enum");
+    let ty = fixture.type_def(0x0101, "Enums", "Color", base);
+    fixture.field(0x0606, "value__", &[0x06, 0x05]);
+    for (name, value) in [("Red", 1u8), ("Green", 2)] {
+        // public static literal
+        let field = fixture.field(0x8056, name, &[0x06, 0x11, 0x08]);
+        let value = fixture.b.blob(&[value]);
+        fixture.b.add(&ConstantRow {
+            ty: 0x05,
+            parent: field,
+            value,
+        });
+    }
+    let metadata = fixture.to_reader();
+    let members: Vec<_> = metadata
+        .enum_members(token_rid(ty))
+        .unwrap()
+        .into_iter()
+        .map(|member| (member.name, member.value.as_integer()))
+        .collect();
+    assert_eq!(
+        members,
+        [("Red".to_owned(), Some(1)), ("Green".to_owned(), Some(2))]
+    );
+    assert_eq!(
+        metadata.constant(TableId::Field.token(2)).unwrap(),
+        Some(ConstantValue::U1(1))
+    );
+    assert_eq!(metadata.constant(TableId::Field.token(1)).unwrap(), None);
+}
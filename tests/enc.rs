@@ -0,0 +1,182 @@
+mod common;
+
+use common::Fixture;
+use mscoree::{
+    EncDeltaBuilder, EncFuncCode, FieldRow, MetadataReader, MethodDefRow, TableId, TableRow,
+};
+
+const A: u32 = 0x0200_0002;
+const B: u32 = 0x0200_0003;
+/// The method `K` added to `B` by [`generation_1`].
+const K: u32 = 0x0600_0003;
+/// The method `H` added to `A` by [`generation_1`].
+const H: u32 = 0x0600_0004;
+/// The field `z` added to `A` by [`generation_1`].
+const Z: u32 = 0x0400_0003;
+
+/// `class A { int x; void F(); }` followed by `class B { int y; void G(); }`.
+fn baseline() -> MetadataReader {
+    let mut enc = Fixture::new("Enc.dll");
+    for (ty, field, method) in [("A", "x", "F"), ("B", "y", "G")] {
+        enc.type_def(0x0010_0001, "Enc", ty, 0);
+        enc.field(0x0001, field, &[0x06, 0x08]);
+        enc.method(0, 0x0006, method, &[0x20, 0x00, 0x01]);
+    }
+    enc.to_reader()
+}
+
+/// Adds `void K()` to `B`, then `void H()` and `long z` to `A`.
+fn generation_1(baseline: &MetadataReader) -> MetadataReader {
+    let mut delta = EncDeltaBuilder::new(baseline, [2; 16]).unwrap();
+    let values = method(&mut delta, "K");
+    assert_eq!(delta.add_method(B, values).unwrap(), K);
+    let values = method(&mut delta, "H");
+    assert_eq!(delta.add_method(A, values).unwrap(), H);
+    let values = field(&mut delta, "z");
+    assert_eq!(delta.add_field(A, values).unwrap(), Z);
+    MetadataReader::from_bytes(delta.to_bytes().unwrap()).unwrap()
+}
+
+/// Adds `void I()` to `A` and `long w` to `B`.
+fn generation_2(gen1: &MetadataReader) -> MetadataReader {
+    let mut delta = EncDeltaBuilder::new(gen1, [3; 16]).unwrap();
+    let values = method(&mut delta, "I");
+    delta.add_method(A, values).unwrap();
+    let values = field(&mut delta, "w");
+    delta.add_field(B, values).unwrap();
+    MetadataReader::from_bytes(delta.to_bytes().unwrap()).unwrap()
+}
+
+fn apply(base: &MetadataReader, delta: &MetadataReader) -> MetadataReader {
+    let applied = base.apply_delta(delta).unwrap();
+    MetadataReader::from_bytes(applied.data().to_vec()).unwrap()
+}
+
+fn method(delta: &mut EncDeltaBuilder, name: &str) -> Vec<u32> {
+    MethodDefRow {
+        flags: 0x0006,
+        name: delta.string(name),
+        signature: delta.blob(&[0x20, 0x00, 0x01]),
+        param_list: 1,
+        ..Default::default()
+    }
+    .to_columns()
+}
+
+fn field(delta: &mut EncDeltaBuilder, name: &str) -> Vec<u32> {
+    FieldRow {
+        flags: 0x0001,
+        name: delta.string(name),
+        signature: delta.blob(&[0x06, 0x0a]),
+    }
+    .to_columns()
+}
+
+fn method_names(metadata: &MetadataReader, type_rid: u32) -> Vec<String> {
+    metadata
+        .type_def_methods(type_rid)
+        .unwrap()
+        .into_iter()
+        .map(|rid| {
+            let name = metadata.row::<MethodDefRow>(rid).unwrap().name;
+            metadata.string(name).unwrap().to_owned()
+        })
+        .collect()
+}
+
+fn field_names(metadata: &MetadataReader, type_rid: u32) -> Vec<String> {
+    metadata
+        .type_def_fields(type_rid)
+        .unwrap()
+        .into_iter()
+        .map(|rid| {
+            let name = metadata.row::<FieldRow>(rid).unwrap().name;
+            metadata.string(name).unwrap().to_owned()
+        })
+        .collect()
+}
+
+#[test]
+fn enc_map_is_sorted_by_table_then_rid() {
+    let delta = generation_1(&baseline());
+    assert!(delta.is_enc_delta());
+    assert_eq!(
+        delta.enc_map().unwrap(),
+        [TableId::Module.token(1), Z, K, H]
+    );
+}
+
+#[test]
+fn enc_log_records_each_addition_against_its_parent() {
+    let delta = generation_1(&baseline());
+    let log: Vec<_> = delta
+        .enc_log()
+        .unwrap()
+        .into_iter()
+        .map(|entry| (entry.token, entry.func_code))
+        .collect();
+    assert_eq!(
+        log,
+        [
+            (B, EncFuncCode::AddMethod),
+            (K, EncFuncCode::Default),
+            (A, EncFuncCode::AddMethod),
+            (H, EncFuncCode::Default),
+            (A, EncFuncCode::AddField),
+            (Z, EncFuncCode::Default),
+        ]
+    );
+}
+
+#[test]
+fn members_of_the_last_type_are_appended() {
+    let baseline = baseline();
+    let mut delta = EncDeltaBuilder::new(&baseline, [2; 16]).unwrap();
+    let values = method(&mut delta, "K");
+    delta.add_method(B, values).unwrap();
+    let delta = MetadataReader::from_bytes(delta.to_bytes().unwrap()).unwrap();
+
+    let gen1 = apply(&baseline, &delta);
+    assert_eq!(gen1.row_count(TableId::MethodPtr), 0);
+    assert_eq!(method_names(&gen1, 3), ["G", "K"]);
+}
+
+#[test]
+fn members_of_earlier_types_are_linked_through_indirection_tables() {
+    let baseline = baseline();
+    let gen1 = apply(&baseline, &generation_1(&baseline));
+    assert_ne!(gen1.row_count(TableId::MethodPtr), 0);
+    assert_ne!(gen1.row_count(TableId::FieldPtr), 0);
+    assert_eq!(method_names(&gen1, 2), ["F", "H"]);
+    assert_eq!(method_names(&gen1, 3), ["G", "K"]);
+    assert_eq!(field_names(&gen1, 2), ["x", "z"]);
+    assert_eq!(field_names(&gen1, 3), ["y"]);
+}
+
+#[test]
+fn generation_2_links_through_the_existing_indirection_tables() {
+    let baseline = baseline();
+    let gen1 = apply(&baseline, &generation_1(&baseline));
+    let delta = generation_2(&gen1);
+    assert_eq!(
+        delta.enc_map().unwrap(),
+        [
+            TableId::Module.token(1),
+            TableId::Field.token(4),
+            TableId::MethodDef.token(5),
+        ]
+    );
+
+    let gen2 = apply(&gen1, &delta);
+    assert_eq!(method_names(&gen2, 2), ["F", "H", "I"]);
+    assert_eq!(method_names(&gen2, 3), ["G", "K"]);
+    assert_eq!(field_names(&gen2, 2), ["x", "z"]);
+    assert_eq!(field_names(&gen2, 3), ["y", "w"]);
+}
+
+#[test]
+fn generation_2_delta_does_not_apply_to_the_baseline() {
+    let baseline = baseline();
+    let gen1 = apply(&baseline, &generation_1(&baseline));
+    assert!(baseline.apply_delta(&generation_2(&gen1)).is_err());
+}
@@ -0,0 +1,208 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::temp_dir;
+use mscoree::{AssemblyIdentity, GacCache, GacRuntime, OfflineGac, ProcessorArchitecture};
+
+const TOKEN: &str = "b77a5c561934e089";
+
+/// Creates `{root}/{cache}/{name}/{dir}/{name}.{extension}`.
+fn install(root: &Path, cache: &str, name: &str, dir: &str, extension: &str) -> PathBuf {
+    let dir = root.join(cache).join(name).join(dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("{name}.{extension}"));
+    fs::write(&path, b"").unwrap();
+    path
+}
+
+/// A Windows directory with the CLR 2.0 and CLR 4 GACs.
+fn windows(test: &str) -> PathBuf {
+    let root = temp_dir(test);
+    let v2 = root.join("assembly");
+    let v4 = root.join("Microsoft.NET/assembly");
+    install(
+        &v2,
+        "GAC_MSIL",
+        "System.Xml",
+        &format!("2.0.0.0__{TOKEN}"),
+        "dll",
+    );
+    install(
+        &v4,
+        "GAC_MSIL",
+        "System.Xml",
+        &format!("v4.0_4.0.0.0__{TOKEN}"),
+        "dll",
+    );
+    install(
+        &v4,
+        "GAC_32",
+        "System.Data",
+        &format!("v4.0_4.0.0.0__{TOKEN}"),
+        "dll",
+    );
+    install(
+        &v4,
+        "GAC_64",
+        "System.Data",
+        &format!("v4.0_4.0.0.0__{TOKEN}"),
+        "dll",
+    );
+    install(
+        &v4,
+        "GAC_MSIL",
+        "Tool",
+        "v4.0_1.0.0.0_de_0123456789abcdef",
+        "exe",
+    );
+    root
+}
+
+fn summary(entries: &[mscoree::GacEntry]) -> Vec<(String, GacCache, GacRuntime)> {
+    entries
+        .iter()
+        .map(|entry| (entry.identity.to_string(), entry.cache, entry.runtime))
+        .collect()
+}
+
+#[test]
+fn identities_come_from_the_version_culture_token_directories() {
+    let root = windows("gac-enumerate");
+    let entries = OfflineGac::new(&root).enumerate().unwrap();
+    let names: Vec<_> = entries
+        .iter()
+        .map(|entry| {
+            let identity = &entry.identity;
+            (
+                identity.name.as_str(),
+                identity.version.unwrap().to_string(),
+                identity.culture.clone().unwrap(),
+                identity.public_key_token().unwrap().to_string(),
+                entry.runtime,
+            )
+        })
+        .collect();
+    let token = TOKEN.to_owned();
+    assert_eq!(
+        names,
+        [
+            (
+                "System.Data",
+                "4.0.0.0".into(),
+                "".into(),
+                token.clone(),
+                GacRuntime::V4
+            ),
+            (
+                "System.Data",
+                "4.0.0.0".into(),
+                "".into(),
+                token.clone(),
+                GacRuntime::V4
+            ),
+            (
+                "System.Xml",
+                "2.0.0.0".into(),
+                "".into(),
+                token.clone(),
+                GacRuntime::V2
+            ),
+            (
+                "System.Xml",
+                "4.0.0.0".into(),
+                "".into(),
+                token,
+                GacRuntime::V4
+            ),
+            (
+                "Tool",
+                "1.0.0.0".into(),
+                "de".into(),
+                "0123456789abcdef".into(),
+                GacRuntime::V4
+            ),
+        ]
+    );
+    let tool = entries.last().unwrap();
+    assert_eq!(
+        tool.path,
+        root.join("Microsoft.NET/assembly/GAC_MSIL/Tool/v4.0_1.0.0.0_de_0123456789abcdef/Tool.exe")
+    );
+}
+
+#[test]
+fn caches_give_the_processor_architecture() {
+    let root = windows("gac-architecture");
+    let entries = OfflineGac::new(&root).find_by_name("system.data").unwrap();
+    let caches: Vec<_> = entries
+        .iter()
+        .map(|entry| (entry.cache, entry.identity.processor_architecture))
+        .collect();
+    assert_eq!(caches.len(), 2);
+    assert!(caches.contains(&(GacCache::X86, Some(ProcessorArchitecture::X86))));
+    assert!(caches.contains(&(GacCache::X64, Some(ProcessorArchitecture::Amd64))));
+    let xml = OfflineGac::new(&root).find_by_name("System.Xml").unwrap();
+    assert!(
+        xml.iter()
+            .all(|entry| entry.identity.processor_architecture == Some(ProcessorArchitecture::Msil))
+    );
+}
+
+#[test]
+fn find_compares_only_the_properties_of_the_reference() {
+    let root = windows("gac-find");
+    let gac = OfflineGac::new(&root);
+    let reference: AssemblyIdentity = "System.Xml, Version=4.0.0.0".parse().unwrap();
+    let entries = gac.find(&reference).unwrap();
+    assert_eq!(
+        summary(&entries),
+        [(
+            format!(
+                "System.Xml, Version=4.0.0.0, Culture=neutral, PublicKeyToken={TOKEN}, \
+                 ProcessorArchitecture=MSIL"
+            ),
+            GacCache::Msil,
+            GacRuntime::V4
+        )]
+    );
+    let reference: AssemblyIdentity = "System.Xml".parse().unwrap();
+    assert_eq!(gac.find(&reference).unwrap().len(), 2);
+}
+
+#[test]
+fn missing_assemblies_are_not_found() {
+    let root = windows("gac-missing");
+    let gac = OfflineGac::new(&root);
+    assert!(gac.find_by_name("System.Web").unwrap().is_empty());
+    let reference: AssemblyIdentity = "System.Xml, Version=3.5.0.0".parse().unwrap();
+    assert!(gac.find(&reference).unwrap().is_empty());
+    assert!(
+        OfflineGac::new(root.join("nowhere"))
+            .enumerate()
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn a_single_gac_directory_can_be_the_root() {
+    // Directory names are matched case-insensitively; version directories
+    // that do not parse or hold no assembly are skipped.
+    let root = temp_dir("gac-single");
+    install(&root, "gac_msil", "Lib", "v4.0_1.0.0.0__", "DLL");
+    install(&root, "gac_msil", "Lib", "not-a-version", "dll");
+    fs::create_dir_all(root.join("gac_msil/Lib/v4.0_2.0.0.0__")).unwrap();
+    let entries = OfflineGac::new(&root).enumerate().unwrap();
+    assert_eq!(
+        summary(&entries),
+        [(
+            "Lib, Version=1.0.0.0, Culture=neutral, PublicKeyToken=null, \
+             ProcessorArchitecture=MSIL"
+                .to_owned(),
+            GacCache::Msil,
+            GacRuntime::V4
+        )]
+    );
+}
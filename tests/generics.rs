@@ -0,0 +1,115 @@
+use mscoree::{Error, GenericContext, MemberHandle, MethodInstance, ModuleId, TypeInst};
+
+fn class(name: &str, args: Vec<TypeInst>) -> TypeInst {
+    TypeInst::Named {
+        value_type: false,
+        handle: None,
+        name: name.into(),
+        args,
+    }
+}
+
+fn value_type(name: &str, args: Vec<TypeInst>) -> TypeInst {
+    TypeInst::Named {
+        value_type: true,
+        handle: None,
+        name: name.into(),
+        args,
+    }
+}
+
+fn list(arg: TypeInst) -> TypeInst {
+    class("List`1", vec![arg])
+}
+
+fn pair(key: TypeInst, value: TypeInst) -> TypeInst {
+    value_type("KeyValuePair`2", vec![key, value])
+}
+
+#[test]
+fn nested_reference_type_arguments_collapse_to_canon() {
+    let nested = list(list(TypeInst::String));
+    assert_eq!(nested.canonical().to_string(), "List`1<System.__Canon>");
+    let array = list(TypeInst::SzArray(Box::new(TypeInst::I4)));
+    assert_eq!(array.canonical().to_string(), "List`1<System.__Canon>");
+}
+
+#[test]
+fn value_type_arguments_keep_their_identity_with_canonical_arguments() {
+    let ty = list(pair(TypeInst::String, list(TypeInst::I4)));
+    assert_eq!(
+        ty.canonical().to_string(),
+        "List`1<KeyValuePair`2<System.__Canon,System.__Canon>>"
+    );
+    let nullable = value_type(
+        "Nullable`1",
+        vec![pair(TypeInst::I4, pair(TypeInst::Object, TypeInst::I8))],
+    );
+    assert_eq!(
+        nullable.canonical().to_string(),
+        "Nullable`1<KeyValuePair`2<System.Int32,KeyValuePair`2<System.__Canon,System.Int64>>>"
+    );
+}
+
+#[test]
+fn element_types_are_canonicalized_inside_arrays_and_pointers() {
+    let ty = TypeInst::SzArray(Box::new(list(TypeInst::String)));
+    assert_eq!(ty.canonical().to_string(), "List`1<System.__Canon>[]");
+    let ty = TypeInst::ByRef(Box::new(pair(TypeInst::String, TypeInst::I4)));
+    assert_eq!(
+        ty.canonical().to_string(),
+        "KeyValuePair`2<System.__Canon,System.Int32>&"
+    );
+    // The outer type itself is never replaced.
+    assert_eq!(TypeInst::String.canonical(), TypeInst::String);
+}
+
+#[test]
+fn substitution_reaches_nested_arguments_before_canonicalization() {
+    let open = list(pair(TypeInst::Var(0), list(TypeInst::MVar(0))));
+    let context = GenericContext {
+        type_args: vec![TypeInst::I4],
+        method_args: vec![TypeInst::String],
+    };
+    let closed = open.substitute(&context).unwrap();
+    assert_eq!(
+        closed.to_string(),
+        "List`1<KeyValuePair`2<System.Int32,List`1<System.String>>>"
+    );
+    assert!(!closed.is_open());
+    assert_eq!(
+        closed.canonical().to_string(),
+        "List`1<KeyValuePair`2<System.Int32,System.__Canon>>"
+    );
+    assert!(closed.canonical().contains_canon());
+}
+
+#[test]
+fn shared_method_instances_canonicalize_nested_arguments() {
+    let instance = MethodInstance {
+        method: MemberHandle {
+            module: ModuleId(0),
+            token: 0x0600_0001,
+        },
+        type_args: vec![pair(TypeInst::String, TypeInst::I4)],
+        method_args: vec![list(TypeInst::I4), TypeInst::R8],
+    };
+    assert!(!instance.is_shared());
+    let canonical = instance.canonical();
+    assert!(canonical.is_shared());
+    assert_eq!(canonical.type_args, [pair(TypeInst::Canon, TypeInst::I4)]);
+    assert_eq!(canonical.method_args, [TypeInst::Canon, TypeInst::R8]);
+}
+
+#[test]
+fn out_of_range_parameters_are_rejected() {
+    let open = list(list(TypeInst::Var(1)));
+    let context = GenericContext::for_type(vec![TypeInst::I4]);
+    assert!(matches!(
+        open.substitute(&context),
+        Err(Error::BadSignature(_))
+    ));
+    // Without arguments the parameters stay open.
+    let open = list(TypeInst::MVar(0));
+    assert_eq!(open.substitute(&context).unwrap(), open);
+}
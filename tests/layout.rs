@@ -0,0 +1,90 @@
+mod common;
+
+use common::Fixture;
+use mscoree::{MetadataReader, PointerSize, TypeLayout, Workspace, token_rid};
+
+const PUBLIC: u32 = 0x0010_0001;
+const FIELD: u16 = 0x0001;
+
+/// `enum MyByteEnum : byte`, a root `class Base { int b; }`,
+/// `class Holder : Base { MyByteEnum e; long l; int i; }` and a root
+/// `class Refs { long l; object o; int i; }`.
+fn metadata() -> MetadataReader {
+    let mut layout = Fixture::new("Layout.dll");
+    let runtime = layout.assembly_ref("System.Runtime", 8);
+    let system_enum = layout.type_ref(runtime, "System", "Enum");
+
+    let my_enum = layout.type_def(PUBLIC, "Layout", "MyByteEnum", system_enum);
+    layout.field(0x0606, "value__", &[0x06, 0x05]);
+    // Without a core library loaded, `Base` stands in for a root class.
+    let base = layout.type_def(PUBLIC, "Layout", "Base", 0);
+    layout.field(FIELD, "b", &[0x06, 0x08]);
+    layout.type_def(PUBLIC, "Layout", "Holder", base);
+    layout.field(FIELD, "e", &[0x06, 0x11, (token_rid(my_enum) << 2) as u8]);
+    layout.field(FIELD, "l", &[0x06, 0x0a]);
+    layout.field(FIELD, "i", &[0x06, 0x08]);
+    layout.type_def(PUBLIC, "Layout", "Refs", 0);
+    layout.field(FIELD, "l", &[0x06, 0x0a]);
+    layout.field(FIELD, "o", &[0x06, 0x1c]);
+    layout.field(FIELD, "i", &[0x06, 0x08]);
+    layout.to_reader()
+}
+
+/// The layout of `Layout.{name}`.
+fn layout(name: &str, pointer: PointerSize) -> TypeLayout {
+    let mut workspace = Workspace::new();
+    let module = workspace.add_metadata(metadata(), None).unwrap();
+    let ty = workspace.find_type(module, "Layout", name).unwrap();
+    let ty = workspace.type_inst_of(ty).unwrap();
+    workspace.type_layout(&ty, pointer).unwrap()
+}
+
+/// The offsets of the named fields of `Layout.{name}`.
+fn offsets<const N: usize>(name: &str, pointer: PointerSize, fields: [&str; N]) -> [u32; N] {
+    let layout = layout(name, pointer);
+    fields.map(|field| layout.field(field).unwrap().offset)
+}
+
+#[test]
+fn enum_fields_are_laid_out_as_their_underlying_primitive() {
+    let layout = layout("Holder", PointerSize::Bits64);
+    assert_eq!(layout.field("e").unwrap().size, 1);
+}
+
+#[test]
+fn largest_field_that_fits_back_fills_the_base_class_gap() {
+    // `Base.b` ends at 4, short of the 8-byte alignment of the `long`: the
+    // `int` fills the gap, and the enum goes last with the other bytes.
+    assert_eq!(
+        offsets("Holder", PointerSize::Bits64, ["b", "i", "l", "e"]),
+        [0, 4, 8, 16]
+    );
+}
+
+#[test]
+fn fields_follow_the_base_class_when_it_ends_aligned() {
+    // On 32-bit `Base.b` ends aligned, so there is no gap to fill and
+    // the `long`, only 4-byte aligned, comes first.
+    assert_eq!(
+        offsets("Holder", PointerSize::Bits32, ["b", "l", "i", "e"]),
+        [0, 4, 12, 16]
+    );
+}
+
+#[test]
+fn references_lead_the_pointer_sized_fields() {
+    assert_eq!(
+        offsets("Refs", PointerSize::Bits64, ["o", "l", "i"]),
+        [0, 8, 16]
+    );
+}
+
+#[test]
+fn references_follow_longs_on_32_bit() {
+    // References are 4 bytes there, after the `long` but before the `int`
+    // declared ahead of them.
+    assert_eq!(
+        offsets("Refs", PointerSize::Bits32, ["l", "o", "i"]),
+        [0, 8, 12]
+    );
+}
@@ -0,0 +1,388 @@
+mod common;
+
+use common::{Fixture, string_attribute, test_key};
+use mscoree::{
+    AssemblyMerger, CustomAttributeArgument, Machine, ManifestResourceRow, MergeConflictKind,
+    MergedAssembly, MetadataReader, MethodDefRow, NestedClassRow, OpCode, Operand, PeBuilder,
+    PeImage, RenamedType, StrongNameStatus, TableId, TypeDefRow, token_rid,
+};
+
+const PUBLIC: u32 = 0x0010_0001;
+const NOT_PUBLIC: u32 = 0x0010_0000;
+const NESTED_PUBLIC: u32 = 0x0010_0002;
+/// `public static hidebysig`.
+const STATIC: u16 = 0x0096;
+
+/// A `.ctor(string)` or `.ctor(Type)` of an attribute in `scope`.
+fn attribute_ctor(
+    input: &mut Fixture,
+    scope: u32,
+    namespace: &str,
+    name: &str,
+    type_arg: Option<u32>,
+) -> u32 {
+    let class = input.type_ref(scope, namespace, name);
+    let signature = match type_arg {
+        Some(ty) => vec![0x20, 0x01, 0x01, 0x12, (token_rid(ty) << 2 | 1) as u8],
+        None => vec![0x20, 0x01, 0x01, 0x0E],
+    };
+    input.member_ref(class, ".ctor", &signature)
+}
+
+/// Embeds `data` in `pe` as the public manifest resource `name`.
+fn resource(input: &mut Fixture, pe: &mut PeBuilder, name: &str, data: &[u8]) {
+    let offset = pe.add_resource(data);
+    let name = input.b.string(name);
+    input.b.add(&ManifestResourceRow {
+        offset,
+        flags: 0x0001,
+        name,
+        implementation: 0,
+    });
+}
+
+fn finish(input: Fixture, mut pe: PeBuilder) -> PeImage {
+    pe.set_metadata(input.to_bytes());
+    PeImage::from_bytes(pe.to_bytes().unwrap()).unwrap()
+}
+
+/// `Lib`: public `Lib.Helper` with `static int Twice(int)` and a nested
+/// `Inner`, and `Shared.Util`, against `System.Runtime` 8.
+fn lib(public_util: bool) -> PeImage {
+    let mut pe = PeBuilder::new(Machine::I386);
+    pe.set_dll(true);
+    let twice = pe.add_method_body(&[0x12, 0x02, 0x18, 0x5A, 0x2A]);
+    let run = pe.add_method_body(&[0x06, 0x2A]);
+
+    let mut lib = Fixture::new("Lib.dll");
+    let runtime = lib.assembly_ref("System.Runtime", 8);
+    let object = lib.type_ref(runtime, "System", "Object");
+    let title = attribute_ctor(
+        &mut lib,
+        runtime,
+        "System.Reflection",
+        "AssemblyTitleAttribute",
+        None,
+    );
+    let ty = lib.type_ref(runtime, "System", "Type");
+    let designer = attribute_ctor(
+        &mut lib,
+        runtime,
+        "System.ComponentModel",
+        "DesignerAttribute",
+        Some(ty),
+    );
+    let helper = lib.type_def(PUBLIC, "Lib", "Helper", object);
+    lib.method(twice, STATIC, "Twice", &[0x00, 0x01, 0x08, 0x08]);
+    let util = if public_util { PUBLIC } else { NOT_PUBLIC };
+    lib.type_def(util, "Shared", "Util", object);
+    lib.method(run, STATIC, "Run", &[0x00, 0x00, 0x01]);
+    let inner = lib.type_def(NESTED_PUBLIC, "", "Inner", object);
+    lib.b.add(&NestedClassRow {
+        nested_class: token_rid(inner),
+        enclosing_class: token_rid(helper),
+    });
+    lib.attribute(TableId::Assembly.token(1), title, &string_attribute("Lib"));
+    lib.attribute(helper, designer, &string_attribute("Shared.Util"));
+    resource(&mut lib, &mut pe, "shared.txt", b"same");
+    resource(&mut lib, &mut pe, "lib.txt", b"lib");
+    resource(&mut lib, &mut pe, "conflict.txt", b"lib version");
+    finish(lib, pe)
+}
+
+/// `App`: `App.Program.Main` calls `Lib.Helper.Twice` and loads the token
+/// of `Lib.Helper+Inner`; it also defines its own `Shared.Util` and
+/// references `System.Runtime` 7.
+fn app(public_util: bool) -> PeImage {
+    let mut pe = PeBuilder::new(Machine::I386);
+    let mut app = Fixture::new("App.exe");
+    let runtime = app.assembly_ref("System.Runtime", 7);
+    let lib = app.assembly_ref("Lib", 1);
+    let hi = app.b.user_string("hi");
+    let object = app.type_ref(runtime, "System", "Object");
+    let helper = app.type_ref(lib, "Lib", "Helper");
+    let inner = app.type_ref(helper, "", "Inner");
+    let twice = app.member_ref(helper, "Twice", &[0x00, 0x01, 0x08, 0x08]);
+    let title = attribute_ctor(
+        &mut app,
+        runtime,
+        "System.Reflection",
+        "AssemblyTitleAttribute",
+        None,
+    );
+    let ivt = attribute_ctor(
+        &mut app,
+        runtime,
+        "System.Runtime.CompilerServices",
+        "InternalsVisibleToAttribute",
+        None,
+    );
+
+    // ldstr "hi"; pop; ldc.i4.3; call Twice; pop; ldtoken Inner; pop; ret
+    let mut main = vec![0x52, 0x72];
+    main.extend_from_slice(&(0x7000_0000 | hi).to_le_bytes());
+    main.extend_from_slice(&[0x26, 0x19, 0x28]);
+    main.extend_from_slice(&twice.to_le_bytes());
+    main.extend_from_slice(&[0x26, 0xD0]);
+    main.extend_from_slice(&inner.to_le_bytes());
+    main.extend_from_slice(&[0x26, 0x2A]);
+    let main = pe.add_method_body(&main);
+    let run = pe.add_method_body(&[0x06, 0x2A]);
+
+    app.type_def(PUBLIC, "App", "Program", object);
+    let main = app.method(main, STATIC, "Main", &[0x00, 0x00, 0x01]);
+    let util = if public_util { PUBLIC } else { NOT_PUBLIC };
+    app.type_def(util, "Shared", "Util", object);
+    app.method(run, STATIC, "Run", &[0x00, 0x00, 0x01]);
+    let assembly = TableId::Assembly.token(1);
+    app.attribute(assembly, title, &string_attribute("App"));
+    app.attribute(assembly, ivt, &string_attribute("Lib"));
+    resource(&mut app, &mut pe, "shared.txt", b"same");
+    resource(&mut app, &mut pe, "conflict.txt", b"app version");
+    pe.set_entry_point(main);
+    finish(app, pe)
+}
+
+/// Merges `Lib` into `App`, internalizing `Lib` and merging the assembly
+/// attributes.
+fn merge() -> MergedAssembly {
+    let mut merger = AssemblyMerger::new(app(false)).unwrap();
+    merger.add(lib(false)).unwrap();
+    merger.set_internalize(true);
+    merger.set_merge_assembly_attributes(true);
+    merger.merge().unwrap()
+}
+
+fn merged_image() -> PeImage {
+    PeImage::from_bytes(merge().image.unwrap()).unwrap()
+}
+
+fn type_names(metadata: &MetadataReader) -> Vec<String> {
+    (1..=metadata.row_count(TableId::TypeDef))
+        .map(|rid| {
+            metadata
+                .type_full_name(TableId::TypeDef.token(rid))
+                .unwrap()
+        })
+        .collect()
+}
+
+fn type_flags(metadata: &MetadataReader, name: &str) -> u32 {
+    let rid = type_names(metadata).iter().position(|n| n == name).unwrap();
+    metadata.row::<TypeDefRow>(rid as u32 + 1).unwrap().flags
+}
+
+#[test]
+fn non_public_duplicates_are_renamed() {
+    let merged = merge();
+    assert_eq!(
+        merged.report.renamed_types,
+        [RenamedType {
+            assembly: "Lib".into(),
+            old_name: "Shared.Util".into(),
+            new_name: "Shared.<Lib>Util".into(),
+        }]
+    );
+    let image = PeImage::from_bytes(merged.image.unwrap()).unwrap();
+    assert_eq!(
+        type_names(&image.metadata().unwrap()),
+        [
+            "<Module>",
+            "App.Program",
+            "Shared.Util",
+            "Lib.Helper",
+            "Shared.<Lib>Util",
+            "Lib.Helper+Inner",
+        ]
+    );
+}
+
+#[test]
+fn internalized_types_are_no_longer_public() {
+    let merged = merge();
+    assert_eq!(merged.report.internalized_types, ["Lib.Helper"]);
+    let image = PeImage::from_bytes(merged.image.unwrap()).unwrap();
+    let metadata = image.metadata().unwrap();
+    assert_eq!(type_flags(&metadata, "Lib.Helper"), NOT_PUBLIC);
+    // Nested types keep their visibility.
+    assert_eq!(type_flags(&metadata, "Lib.Helper+Inner"), NESTED_PUBLIC);
+}
+
+#[test]
+fn non_blocking_conflicts_are_reported() {
+    let report = merge().report;
+    assert!(!report.is_blocked());
+    let conflicts: Vec<_> = report
+        .conflicts
+        .iter()
+        .map(|c| (c.kind, c.assembly.as_str(), c.item.as_str(), c.blocking))
+        .collect();
+    assert_eq!(
+        conflicts,
+        [
+            (
+                MergeConflictKind::ReferenceVersionMismatch,
+                "Lib",
+                "System.Runtime",
+                false
+            ),
+            (
+                MergeConflictKind::DuplicateResource,
+                "Lib",
+                "conflict.txt",
+                false
+            ),
+            (
+                MergeConflictKind::AttributeConflict,
+                "Lib",
+                "System.Reflection.AssemblyTitleAttribute",
+                false
+            ),
+        ]
+    );
+}
+
+#[test]
+fn references_are_unified_to_the_highest_version() {
+    // References between the inputs become definitions.
+    let metadata = merged_image().metadata().unwrap();
+    assert_eq!(metadata.row_count(TableId::AssemblyRef), 1);
+    assert_eq!(
+        metadata.assembly_ref_identity(1).unwrap().to_string(),
+        "System.Runtime, Version=8.0.0.0, Culture=neutral, PublicKeyToken=null"
+    );
+}
+
+#[test]
+fn tokens_in_method_bodies_are_rewritten() {
+    let image = merged_image();
+    let metadata = image.metadata().unwrap();
+    let entry_point = image.cor_header().unwrap().entry_point;
+    let main = image
+        .method_body(&metadata, token_rid(entry_point))
+        .unwrap()
+        .unwrap();
+    let operands: Vec<_> = main
+        .instructions
+        .iter()
+        .filter_map(|i| match i.operand {
+            Operand::Token(token) => Some((i.opcode, token)),
+            _ => None,
+        })
+        .collect();
+    let [
+        (OpCode::LDSTR, hi),
+        (OpCode::CALL, twice),
+        (OpCode::LDTOKEN, inner),
+    ] = operands[..]
+    else {
+        panic!("unexpected body {operands:?}");
+    };
+    assert_eq!(metadata.user_string(token_rid(hi)).unwrap(), "hi");
+    assert_eq!(TableId::from_token(twice).unwrap().0, TableId::MethodDef);
+    assert_eq!(
+        metadata.method_parent_type_name(twice).unwrap(),
+        "Lib.Helper"
+    );
+    let name = metadata.row::<MethodDefRow>(token_rid(twice)).unwrap().name;
+    assert_eq!(metadata.string(name).unwrap(), "Twice");
+    assert_eq!(metadata.type_full_name(inner).unwrap(), "Lib.Helper+Inner");
+}
+
+#[test]
+fn type_names_in_attribute_values_follow_renamed_types() {
+    let metadata = merged_image().metadata().unwrap();
+    let designer: Vec<_> = (1..=metadata.row_count(TableId::CustomAttribute))
+        .filter(|&rid| {
+            metadata.custom_attribute_type_name(rid).unwrap()
+                == "System.ComponentModel.DesignerAttribute"
+        })
+        .map(|rid| metadata.custom_attribute_value(rid).unwrap().fixed_args)
+        .collect();
+    assert_eq!(
+        designer,
+        [vec![CustomAttributeArgument::Type(Some(
+            "Shared.<Lib>Util".into()
+        ))]]
+    );
+}
+
+#[test]
+fn first_input_wins_a_resource_name_conflict() {
+    // Identical resources are kept once.
+    let image = merged_image();
+    let metadata = image.metadata().unwrap();
+    let resources: Vec<_> = (1..=metadata.row_count(TableId::ManifestResource))
+        .map(|rid| {
+            let row = metadata.row::<ManifestResourceRow>(rid).unwrap();
+            (
+                metadata.string(row.name).unwrap().to_owned(),
+                image.managed_resource(row.offset).unwrap().to_vec(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        resources,
+        [
+            ("shared.txt".to_owned(), b"same".to_vec()),
+            ("conflict.txt".to_owned(), b"app version".to_vec()),
+            ("lib.txt".to_owned(), b"lib".to_vec()),
+        ]
+    );
+}
+
+#[test]
+fn public_duplicates_block_the_merge() {
+    let mut merger = AssemblyMerger::new(app(true)).unwrap();
+    merger.add(lib(true)).unwrap();
+    let merged = merger.merge().unwrap();
+    assert!(merged.image.is_none());
+    let blocking: Vec<_> = merged
+        .report
+        .blocking_conflicts()
+        .map(|c| (c.kind, c.item.as_str()))
+        .collect();
+    assert_eq!(
+        blocking,
+        [(MergeConflictKind::DuplicateType, "Shared.Util")]
+    );
+}
+
+#[test]
+fn public_duplicates_can_be_renamed() {
+    let mut merger = AssemblyMerger::new(app(true)).unwrap();
+    merger.add(lib(true)).unwrap();
+    merger.set_rename_public_duplicates(true);
+    let merged = merger.merge().unwrap();
+    assert!(!merged.report.is_blocked());
+    let image = PeImage::from_bytes(merged.image.unwrap()).unwrap();
+    let metadata = image.metadata().unwrap();
+    assert_eq!(type_flags(&metadata, "Shared.Util"), PUBLIC);
+    assert_eq!(type_flags(&metadata, "Shared.<Lib>Util"), PUBLIC);
+}
+
+#[test]
+fn merged_assembly_is_signed() {
+    let key = test_key();
+    let mut merger = AssemblyMerger::new(app(false)).unwrap();
+    merger.add(lib(false)).unwrap();
+    merger.set_strong_name_key(Some(key.clone()));
+    let merged = merger.merge().unwrap();
+
+    let token = key.public_key_token().to_string();
+    assert!(
+        merged
+            .report
+            .assembly
+            .ends_with(&format!("PublicKeyToken={token}"))
+    );
+    let image = PeImage::from_bytes(merged.image.unwrap()).unwrap();
+    assert_eq!(
+        image.metadata().unwrap().assembly_public_key().unwrap(),
+        Some(&key.public_key_blob()[..])
+    );
+    assert_eq!(
+        image.verify_strong_name().unwrap(),
+        StrongNameStatus::Verified
+    );
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::{Fixture, temp_dir};
+use mscoree::{Error, Machine, NameIndex, NameIndexBuilder, PeBuilder};
+
+/// Writes `Lib.dll`, defining `Lib.Widget` with `void Draw()`, to `dir`.
+fn lib(dir: &Path) -> PathBuf {
+    let mut lib = Fixture::new("Lib.dll");
+    lib.type_def(0x0010_0001, "Lib", "Widget", 0);
+    lib.method(0, 0x0006, "Draw", &[0x20, 0x00, 0x01]);
+    let mut pe = PeBuilder::new(Machine::I386);
+    pe.set_dll(true);
+    pe.set_metadata(lib.to_bytes());
+    let path = dir.join("Lib.dll");
+    fs::write(&path, pe.to_bytes().unwrap()).unwrap();
+    path
+}
+
+fn names(index: &NameIndex) -> Vec<(&str, u32)> {
+    index
+        .find_prefix("")
+        .unwrap()
+        .into_iter()
+        .map(|found| (found.name, found.token))
+        .collect()
+}
+
+#[test]
+fn rebuilding_skips_files_with_an_unchanged_mvid() {
+    let dir = temp_dir("name-index-rebuild");
+    let path = lib(&dir);
+    let mut builder = NameIndexBuilder::new();
+    assert!(builder.add_file(&path).unwrap());
+    assert!(!builder.add_file(&path).unwrap());
+    builder.write(dir.join("names.idx")).unwrap();
+
+    let index = NameIndex::open(dir.join("names.idx")).unwrap();
+    assert_eq!(
+        names(&index),
+        [
+            ("<Module>", 0x0200_0001),
+            ("Lib.Widget", 0x0200_0002),
+            ("Lib.Widget::Draw", 0x0600_0001),
+        ]
+    );
+    assert_eq!(index.mvid(&path), Some([1; 16]));
+
+    let mut rebuilt = NameIndexBuilder::from_index(&index).unwrap();
+    assert!(!rebuilt.add_file(&path).unwrap());
+    assert_eq!(rebuilt.to_bytes(), builder.to_bytes());
+}
+
+#[test]
+fn stale_files_are_reindexed_or_removed() {
+    let dir = temp_dir("name-index-stale");
+    let path = lib(&dir);
+    let mut builder = NameIndexBuilder::new();
+    builder
+        .add_names(&path, [9; 16], vec![("Lib.Old".into(), 0x0200_0002)])
+        .unwrap();
+    builder
+        .add_names(
+            dir.join("Gone.dll"),
+            [2; 16],
+            vec![("Gone.Type".into(), 0x0200_0002)],
+        )
+        .unwrap();
+
+    assert!(builder.add_file(&path).unwrap());
+    assert_eq!(builder.retain_existing(), 1);
+    let index = NameIndex::from_bytes(builder.to_bytes()).unwrap();
+    assert!(index.find("Lib.Old").unwrap().is_empty());
+    assert!(index.find("Gone.Type").unwrap().is_empty());
+    let found = index.find("Lib.Widget").unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].path, path);
+    assert_eq!(index.files().count(), 1);
+    assert!(!builder.remove_file(&dir.join("Gone.dll")));
+    assert!(builder.remove_file(&path));
+}
+
+#[test]
+fn writing_replaces_an_index_mapped_by_another_reader() {
+    let dir = temp_dir("name-index-replace");
+    let path = dir.join("names.idx");
+    let mut builder = NameIndexBuilder::new();
+    builder
+        .add_names("A.dll", [1; 16], vec![("A.Type".into(), 0x0200_0002)])
+        .unwrap();
+    builder.write(&path).unwrap();
+
+    let old = NameIndex::open(&path).unwrap();
+    builder
+        .add_names("B.dll", [2; 16], vec![("B.Type".into(), 0x0200_0002)])
+        .unwrap();
+    #[cfg(unix)]
+    {
+        builder.write(&path).unwrap();
+        assert_eq!(names(&old), [("A.Type", 0x0200_0002)]);
+    }
+    drop(old);
+    builder.write(&path).unwrap();
+    let new = NameIndex::open(&path).unwrap();
+    assert_eq!(
+        names(&new),
+        [("A.Type", 0x0200_0002), ("B.Type", 0x0200_0002)]
+    );
+    assert!(!Path::new(&format!("{}.tmp", path.display())).exists());
+}
+
+#[test]
+fn corrupt_indexes_are_rejected() {
+    let mut builder = NameIndexBuilder::new();
+    builder
+        .add_names("A.dll", [1; 16], vec![("A.Type".into(), 0x0200_0002)])
+        .unwrap();
+    let bytes = builder.to_bytes();
+    let bad_index =
+        |bytes: Vec<u8>| matches!(NameIndex::from_bytes(bytes), Err(Error::BadIndex(_)));
+
+    let mut magic = bytes.clone();
+    magic[0] = b'X';
+    assert!(bad_index(magic));
+    let mut version = bytes.clone();
+    version[8] = 2;
+    assert!(bad_index(version));
+    assert!(bad_index(bytes[..40].to_vec()));
+    // The length of the first file's path.
+    let mut path = bytes.clone();
+    path[36] = 0xff;
+    assert!(bad_index(path));
+    assert!(bad_index(Vec::new()));
+}
+
+#[cfg(unix)]
+#[test]
+fn non_unicode_paths_are_rejected() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut builder = NameIndexBuilder::new();
+    let path = Path::new(OsStr::from_bytes(b"lib/\xffLib.dll"));
+    assert!(matches!(
+        builder.add_names(path, [1; 16], Vec::new()),
+        Err(Error::BadIndex(_))
+    ));
+    assert_eq!(
+        NameIndex::from_bytes(builder.to_bytes())
+            .unwrap()
+            .files()
+            .count(),
+        0
+    );
+}
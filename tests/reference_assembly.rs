@@ -0,0 +1,190 @@
+mod common;
+
+use common::Fixture;
+use mscoree::{
+    CodedIndex, CustomAttributeRow, FieldRow, GenericParamRow, Machine, MetadataReader,
+    MethodDefRow, MethodSemanticsRow, PeBuilder, PeImage, PropertyMapRow, PropertyRow, TableId,
+    token_rid,
+};
+
+/// An implementation assembly with an internal `Hidden<U>` and a public
+/// `Widget<T>` whose private members come before its public ones, so that
+/// stripping them renumbers every table the attributes, accessors and
+/// generic parameters point into.
+fn implementation() -> Vec<u8> {
+    let mut pe = PeBuilder::new(Machine::I386);
+    pe.set_dll(true);
+    let body = pe.add_method_body(&[0x06, 0x2A]);
+
+    let mut lib = Fixture::new("Lib.dll");
+    let runtime = lib.assembly_ref("System.Runtime", 0);
+    let object = lib.type_ref(runtime, "System", "Object");
+    let obsolete = lib.type_ref(runtime, "System", "ObsoleteAttribute");
+    let obsolete = lib.member_ref(obsolete, ".ctor", &[0x20, 0x00, 0x01]);
+
+    let hidden = lib.type_def(0x0010_0000, "Lib", "Hidden`1", object);
+    lib.field(0x0006, "h", &[0x06, 0x08]);
+    lib.method(body, 0x0086, "Run", &[0x20, 0x00, 0x01]);
+    let widget = lib.type_def(0x0010_0001, "Lib", "Widget`1", object);
+    let secret_field = lib.field(0x0001, "secret", &[0x06, 0x08]);
+    let count_field = lib.field(0x0006, "count", &[0x06, 0x08]);
+    let secret = lib.method(body, 0x0081, "Secret", &[0x20, 0x00, 0x01]);
+    let get_count = lib.method(body, 0x0886, "get_Count", &[0x20, 0x00, 0x08]);
+    let process = lib.method(body, 0x0086, "Process", &[0x30, 0x01, 0x00, 0x01]);
+
+    lib.b.add(&PropertyMapRow {
+        parent: token_rid(widget),
+        property_list: 1,
+    });
+    let (name, signature) = (lib.b.string("Count"), lib.b.blob(&[0x28, 0x00, 0x08]));
+    let count = TableId::Property.token(lib.b.add(&PropertyRow {
+        flags: 0,
+        name,
+        signature,
+    }));
+    lib.b.add(&MethodSemanticsRow {
+        semantics: 0x0002,
+        method: token_rid(get_count),
+        association: count,
+    });
+    let mut generic_params = Vec::new();
+    for (owner, name) in [(process, "M"), (widget, "T"), (hidden, "U")] {
+        let name = lib.b.string(name);
+        generic_params.push(TableId::GenericParam.token(lib.b.add(&GenericParamRow {
+            number: 0,
+            flags: 0,
+            owner,
+            name,
+        })));
+    }
+    for parent in [
+        generic_params[2],
+        generic_params[1],
+        count,
+        process,
+        secret,
+        count_field,
+        secret_field,
+        widget,
+        hidden,
+    ] {
+        lib.attribute(parent, obsolete, &[0x01, 0x00, 0x00, 0x00]);
+    }
+    lib.b.sort_tables().unwrap();
+    pe.set_metadata(lib.to_bytes());
+    pe.to_bytes().unwrap()
+}
+
+/// The metadata of the reference assembly of [`implementation`].
+fn reference() -> MetadataReader {
+    let image = PeImage::from_bytes(implementation()).unwrap();
+    let reference = PeImage::from_bytes(image.reference_assembly().unwrap()).unwrap();
+    reference.metadata().unwrap()
+}
+
+/// Names the row a token refers to.
+fn describe(metadata: &MetadataReader, token: u32) -> String {
+    let (table, rid) = TableId::from_token(token).unwrap();
+    let name = match table {
+        TableId::Assembly => return "<assembly>".into(),
+        TableId::TypeDef => return metadata.type_full_name(token).unwrap(),
+        TableId::MethodDef => metadata.row::<MethodDefRow>(rid).unwrap().name,
+        TableId::Field => metadata.row::<FieldRow>(rid).unwrap().name,
+        TableId::Property => metadata.row::<PropertyRow>(rid).unwrap().name,
+        TableId::GenericParam => metadata.row::<GenericParamRow>(rid).unwrap().name,
+        _ => panic!("unexpected parent {token:#010x}"),
+    };
+    metadata.string(name).unwrap().to_owned()
+}
+
+#[test]
+fn internal_types_and_private_members_are_stripped() {
+    let metadata = reference();
+    assert_eq!(metadata.row_count(TableId::TypeDef), 2);
+    assert_eq!(
+        metadata.type_full_name(0x0200_0002).unwrap(),
+        "Lib.Widget`1"
+    );
+    let fields: Vec<_> = metadata
+        .type_def_fields(2)
+        .unwrap()
+        .into_iter()
+        .map(|rid| describe(&metadata, TableId::Field.token(rid)))
+        .collect();
+    assert_eq!(fields, ["count"]);
+    let methods: Vec<_> = metadata
+        .type_def_methods(2)
+        .unwrap()
+        .into_iter()
+        .map(|rid| describe(&metadata, TableId::MethodDef.token(rid)))
+        .collect();
+    assert_eq!(methods, ["get_Count", "Process"]);
+}
+
+#[test]
+fn custom_attributes_follow_their_parents_and_stay_sorted() {
+    let metadata = reference();
+    let mut attributes = Vec::new();
+    let mut parents = Vec::new();
+    for rid in 1..=metadata.row_count(TableId::CustomAttribute) {
+        let parent = metadata.row::<CustomAttributeRow>(rid).unwrap().parent;
+        parents.push(CodedIndex::HasCustomAttribute.encode(parent).unwrap());
+        attributes.push((
+            describe(&metadata, parent),
+            metadata.custom_attribute_type_name(rid).unwrap(),
+        ));
+    }
+    assert!(parents.is_sorted());
+    attributes.sort();
+    let obsolete = "System.ObsoleteAttribute".to_owned();
+    assert_eq!(
+        attributes,
+        [
+            (
+                "<assembly>".to_owned(),
+                "System.Runtime.CompilerServices.ReferenceAssemblyAttribute".to_owned()
+            ),
+            ("Count".to_owned(), obsolete.clone()),
+            ("Lib.Widget`1".to_owned(), obsolete.clone()),
+            ("Process".to_owned(), obsolete.clone()),
+            ("T".to_owned(), obsolete.clone()),
+            ("count".to_owned(), obsolete),
+        ]
+    );
+}
+
+#[test]
+fn accessors_keep_their_property() {
+    let metadata = reference();
+    assert_eq!(metadata.row_count(TableId::MethodSemantics), 1);
+    let semantics = metadata.row::<MethodSemanticsRow>(1).unwrap();
+    assert_eq!(
+        describe(&metadata, TableId::MethodDef.token(semantics.method)),
+        "get_Count"
+    );
+    assert_eq!(describe(&metadata, semantics.association), "Count");
+    let map = metadata.row::<PropertyMapRow>(1).unwrap();
+    assert_eq!(map.parent, 2);
+}
+
+#[test]
+fn generic_parameters_of_removed_owners_are_removed() {
+    // The others are renumbered and sorted by owner.
+    let metadata = reference();
+    let generic_params: Vec<_> = (1..=metadata.row_count(TableId::GenericParam))
+        .map(|rid| {
+            let row = metadata.row::<GenericParamRow>(rid).unwrap();
+            (
+                describe(&metadata, TableId::GenericParam.token(rid)),
+                describe(&metadata, row.owner),
+            )
+        })
+        .collect();
+    assert_eq!(
+        generic_params,
+        [
+            ("T".to_owned(), "Lib.Widget`1".to_owned()),
+            ("M".to_owned(), "Process".to_owned()),
+        ]
+    );
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::{Fixture, temp_dir};
+use mscoree::{AssemblyIdentity, AssemblyResolver, Machine, PeBuilder, ProbeOutcome, ProbeSource};
+
+const LIB: &str = "Lib, Version=1.0.0.0, Culture=neutral, PublicKeyToken=null";
+
+/// Writes the assembly `Lib` 1.0.0.0 to `path`.
+fn write_lib(path: &Path) {
+    let mut pe = PeBuilder::new(Machine::I386);
+    pe.set_dll(true);
+    pe.set_metadata(Fixture::new("Lib.dll").to_bytes());
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, pe.to_bytes().unwrap()).unwrap();
+}
+
+fn resolve(resolver: &AssemblyResolver, reference: &str) -> Vec<(PathBuf, ProbeSource, bool)> {
+    let reference: AssemblyIdentity = reference.parse().unwrap();
+    resolver
+        .resolve(&reference)
+        .trace
+        .into_iter()
+        .map(|attempt| {
+            let accepted = attempt.outcome == ProbeOutcome::Accepted;
+            (attempt.path, attempt.source, accepted)
+        })
+        .collect()
+}
+
+#[test]
+fn every_dll_is_probed_before_any_exe() {
+    let base = temp_dir("resolver-dll-first");
+    write_lib(&base.join("Lib.exe"));
+    write_lib(&base.join("bin/Lib/Lib.dll"));
+    let resolver = AssemblyResolver::new()
+        .with_app_base(&base)
+        .with_private_bin_paths("bin");
+    assert_eq!(
+        resolve(&resolver, LIB),
+        [
+            (base.join("Lib.dll"), ProbeSource::AppBase, false),
+            (base.join("Lib/Lib.dll"), ProbeSource::AppBase, false),
+            (base.join("bin/Lib.dll"), ProbeSource::PrivateBinPath, false),
+            (
+                base.join("bin/Lib/Lib.dll"),
+                ProbeSource::PrivateBinPath,
+                true
+            ),
+        ]
+    );
+}
+
+#[test]
+fn exe_is_accepted_when_no_dll_exists() {
+    let base = temp_dir("resolver-exe");
+    write_lib(&base.join("bin/Lib.exe"));
+    let resolver = AssemblyResolver::new()
+        .with_app_base(&base)
+        .with_private_bin_paths("bin");
+    assert_eq!(
+        resolve(&resolver, LIB),
+        [
+            (base.join("Lib.dll"), ProbeSource::AppBase, false),
+            (base.join("Lib/Lib.dll"), ProbeSource::AppBase, false),
+            (base.join("bin/Lib.dll"), ProbeSource::PrivateBinPath, false),
+            (
+                base.join("bin/Lib/Lib.dll"),
+                ProbeSource::PrivateBinPath,
+                false
+            ),
+            (base.join("Lib.exe"), ProbeSource::AppBase, false),
+            (base.join("Lib/Lib.exe"), ProbeSource::AppBase, false),
+            (base.join("bin/Lib.exe"), ProbeSource::PrivateBinPath, true),
+        ]
+    );
+}
+
+#[test]
+fn satellite_assemblies_are_probed_below_their_culture() {
+    let base = temp_dir("resolver-satellite");
+    let resolver = AssemblyResolver::new()
+        .with_app_base(&base)
+        .with_private_bin_paths("bin");
+    let paths: Vec<_> = resolve(
+        &resolver,
+        "Lib.resources, Version=1.0.0.0, Culture=de, PublicKeyToken=null",
+    )
+    .into_iter()
+    .map(|(path, ..)| path)
+    .collect();
+    assert_eq!(
+        paths,
+        [
+            "de/Lib.resources.dll",
+            "de/Lib.resources/Lib.resources.dll",
+            "bin/de/Lib.resources.dll",
+            "bin/de/Lib.resources/Lib.resources.dll",
+            "de/Lib.resources.exe",
+            "de/Lib.resources/Lib.resources.exe",
+            "bin/de/Lib.resources.exe",
+            "bin/de/Lib.resources/Lib.resources.exe",
+        ]
+        .map(|path| base.join(path))
+    );
+}
+
+#[test]
+fn search_paths_come_after_the_application_base() {
+    let base = temp_dir("resolver-search-app");
+    let search = temp_dir("resolver-search-extra");
+    write_lib(&base.join("Lib.exe"));
+    write_lib(&search.join("Lib.dll"));
+    let resolver = AssemblyResolver::new()
+        .with_app_base(&base)
+        .with_search_path(&search);
+    let resolution = resolver.resolve(&LIB.parse().unwrap());
+    assert_eq!(resolution.path, Some(base.join("Lib.exe")));
+}
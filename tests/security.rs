@@ -0,0 +1,123 @@
+mod common;
+
+use common::Fixture;
+use mscoree::{
+    CustomAttributeArgument, DeclSecurityRow, Error, MetadataReader, PermissionSetFormat,
+    SecurityAction, TableId,
+};
+
+const SECURITY_PERMISSION: &str =
+    "System.Security.Permissions.SecurityPermissionAttribute, mscorlib";
+
+/// A binary permission set: `SecurityPermission(UnmanagedCode = true)`.
+fn binary_set() -> Vec<u8> {
+    let mut properties = vec![0x01, 0x54, 0x02, 13];
+    properties.extend_from_slice(b"UnmanagedCode");
+    properties.push(0x01);
+    let mut blob = vec![b'.', 0x01, SECURITY_PERMISSION.len() as u8];
+    blob.extend_from_slice(SECURITY_PERMISSION.as_bytes());
+    blob.push(properties.len() as u8);
+    blob.extend_from_slice(&properties);
+    blob
+}
+
+/// An unrestricted XML permission set in UTF-16LE without a byte order
+/// mark, as 1.x compilers wrote it.
+fn xml_set() -> Vec<u8> {
+    r#"<PermissionSet class="System.Security.PermissionSet" version="1" Unrestricted="true">
+  <IPermission class="System.Security.Permissions.FileIOPermission, mscorlib"
+               version="1" Read="C:\Data"/>
+</PermissionSet>"#
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+fn reader() -> MetadataReader {
+    Fixture::new("Security.dll").to_reader()
+}
+
+fn bad_metadata(blob: &[u8]) -> bool {
+    matches!(
+        reader().decode_permission_set(2, blob),
+        Err(Error::BadMetadata(_))
+    )
+}
+
+#[test]
+fn binary_permission_sets_decode_their_properties() {
+    let set = reader().decode_permission_set(6, &binary_set()).unwrap();
+    assert_eq!(set.action, SecurityAction::LinkDemand);
+    assert_eq!(set.format, PermissionSetFormat::Binary);
+    assert!(!set.unrestricted);
+    assert_eq!(set.attributes.len(), 1);
+    assert_eq!(set.attributes[0].type_name, SECURITY_PERMISSION);
+    assert_eq!(
+        set.attributes[0].property("UnmanagedCode"),
+        Some(&CustomAttributeArgument::Boolean(true))
+    );
+}
+
+#[test]
+fn xml_permission_sets_decode_utf16_text() {
+    let set = reader().decode_permission_set(3, &xml_set()).unwrap();
+    assert_eq!(set.action, SecurityAction::Assert);
+    assert_eq!(set.format, PermissionSetFormat::Xml);
+    assert!(set.unrestricted);
+    assert_eq!(set.attributes.len(), 1);
+    let permission = &set.attributes[0];
+    assert_eq!(
+        permission.type_name,
+        "System.Security.Permissions.FileIOPermission, mscorlib"
+    );
+    assert_eq!(
+        permission.property("Read").and_then(|value| value.as_str()),
+        Some(r"C:\Data")
+    );
+    assert!(permission.property("version").is_none());
+}
+
+#[test]
+fn permission_sets_are_found_by_parent() {
+    let mut fixture = Fixture::new("Security.dll");
+    let widget = fixture.type_def(0x0010_0001, "Lib", "Widget", 0);
+    let assembly = TableId::Assembly.token(1);
+    // The table is sorted by the coded parent, which puts `Assembly` 1
+    // before `TypeDef` 2.
+    for (action, parent, blob) in [(3, assembly, xml_set()), (6, widget, binary_set())] {
+        let permission_set = fixture.b.blob(&blob);
+        fixture.b.add(&DeclSecurityRow {
+            action,
+            parent,
+            permission_set,
+        });
+    }
+    let reader = fixture.to_reader();
+
+    let on_type = reader.permission_sets(widget).unwrap();
+    assert_eq!(on_type.len(), 1);
+    assert_eq!(on_type[0].format, PermissionSetFormat::Binary);
+    let on_assembly = reader.permission_sets(assembly).unwrap();
+    assert_eq!(on_assembly.len(), 1);
+    assert_eq!(on_assembly[0].format, PermissionSetFormat::Xml);
+    assert!(
+        reader
+            .permission_sets(TableId::MethodDef.token(1))
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn malformed_permission_sets_are_bad_metadata() {
+    let xml = xml_set();
+    assert!(bad_metadata(&xml[..xml.len() - 1]));
+    assert!(bad_metadata(&[0x41, 0x00, 0x00, 0xd8]));
+    assert!(bad_metadata(b"<PermissionSet>\xff</PermissionSet>"));
+    assert!(bad_metadata(
+        b"<PermissionSet><IPermission></PermissionSet>"
+    ));
+    assert!(bad_metadata(b"<Permissions/>"));
+    let binary = binary_set();
+    assert!(bad_metadata(&binary[..binary.len() - 1]));
+}
@@ -0,0 +1,335 @@
+mod common;
+
+use common::{snk, temp_dir, test_key};
+use mscoree::{
+    AssemblyRefRow, AssemblyRow, COMIMAGE_FLAGS_STRONGNAMESIGNED, CustomAttributeRow, Error,
+    Machine, MemberRefRow, MetadataBuilder, MethodDefRow, ModuleRow, PeBuilder, PeImage,
+    StrongNameHashAlgorithm, StrongNameKey, StrongNameKeyPair, StrongNameStatus, TableId,
+    TypeDefRow, TypeRefRow, strong_name_signature_generation, strong_name_signature_verification,
+};
+
+/// A 1 KB PE32 image whose `SizeOfOptionalHeader` is too small for the 16
+/// data directories its `NumberOfRvaAndSizes` announces.
+fn truncated_optional_header_image() -> Vec<u8> {
+    let mut data = vec![0u8; 1024];
+    data[..2].copy_from_slice(b"MZ");
+    data[0x3C..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+    data[0x80..0x84].copy_from_slice(b"PE\0\0");
+    data[0x84..0x86].copy_from_slice(&0x014Cu16.to_le_bytes());
+    // SizeOfOptionalHeader
+    data[0x94..0x96].copy_from_slice(&0x10u16.to_le_bytes());
+    let optional_header = 0x98;
+    data[optional_header..optional_header + 2].copy_from_slice(&0x10Bu16.to_le_bytes());
+    // NumberOfRvaAndSizes
+    data[optional_header + 92..optional_header + 96].copy_from_slice(&16u32.to_le_bytes());
+    data
+}
+
+#[test]
+fn optional_header_too_small_for_data_directories_is_rejected() {
+    let result = PeImage::from_bytes(truncated_optional_header_image());
+    assert!(matches!(result, Err(Error::BadImageFormat(_))));
+}
+
+/// A minimal image with an assembly manifest.
+fn image() -> Vec<u8> {
+    let mut metadata = MetadataBuilder::new();
+    let name = metadata.string("Lib");
+    metadata.add(&AssemblyRow {
+        name,
+        ..Default::default()
+    });
+    let mut pe = PeBuilder::new(Machine::I386);
+    pe.set_metadata(metadata.to_bytes().unwrap());
+    pe.to_bytes().unwrap()
+}
+
+#[test]
+fn section_data_past_the_end_of_the_address_space_is_rejected() {
+    let mut data = image();
+    let section = PeImage::from_bytes(data.clone())
+        .unwrap()
+        .section_headers_offset();
+    // PointerToRawData of the first section.
+    data[section + 20..section + 24].copy_from_slice(&u32::MAX.to_le_bytes());
+    let result = PeImage::from_bytes(data);
+    assert!(matches!(result, Err(Error::BadImageFormat(_))));
+}
+
+#[test]
+fn well_formed_image_hashes() {
+    let image = PeImage::from_bytes(image()).unwrap();
+    let content = image.strong_name_signed_content().unwrap();
+    assert!(content.len() > image.section_headers_offset());
+}
+
+/// The body of `static void Run()`: `nop; ret`.
+const RUN: [u8; 3] = [0x0A, 0x00, 0x2A];
+
+/// A strong-named assembly whose identity is `identity`, reserving room
+/// for a signature by `identity` or, with enhanced strong naming, by the
+/// key in `signature_key` (a public key and its counter-signature).
+fn strong_named_image(
+    identity: &StrongNameKeyPair,
+    signature_key: Option<(&StrongNameKeyPair, Vec<u8>)>,
+) -> PeImage {
+    let mut pe = PeBuilder::new(Machine::I386);
+    pe.set_dll(true);
+    let rva = pe.add_method_body(&RUN);
+    let signer = signature_key.as_ref().map_or(identity, |(key, _)| key);
+    pe.set_strong_name_signature_size(signer.signature_len() as u32);
+
+    let mut b = MetadataBuilder::new();
+    let module = ModuleRow {
+        name: b.string("Lib.dll"),
+        mvid: b.guid([1; 16]),
+        ..Default::default()
+    };
+    b.add(&module);
+    let assembly = AssemblyRow {
+        hash_alg_id: 0x8004,
+        major_version: 1,
+        public_key: b.blob(&identity.public_key_blob()),
+        name: b.string("Lib"),
+        ..Default::default()
+    };
+    b.add(&assembly);
+    let name = b.string("<Module>");
+    b.add(&TypeDefRow {
+        name,
+        field_list: 1,
+        method_list: 1,
+        ..Default::default()
+    });
+    let (name, signature) = (b.string("Run"), b.blob(&[0x00, 0x00, 0x01]));
+    b.add(&MethodDefRow {
+        rva,
+        flags: 0x0016,
+        name,
+        signature,
+        param_list: 1,
+        ..Default::default()
+    });
+
+    if let Some((key, counter_signature)) = signature_key {
+        let name = b.string("System.Runtime");
+        b.add(&AssemblyRefRow {
+            name,
+            ..Default::default()
+        });
+        let (namespace, name) = (
+            b.string("System.Reflection"),
+            b.string("AssemblySignatureKeyAttribute"),
+        );
+        let class = b.add(&TypeRefRow {
+            resolution_scope: TableId::AssemblyRef.token(1),
+            namespace,
+            name,
+        });
+        // .ctor(string publicKey, string countersignature)
+        let ctor = MemberRefRow {
+            class: TableId::TypeRef.token(class),
+            name: b.string(".ctor"),
+            signature: b.blob(&[0x20, 0x02, 0x01, 0x0E, 0x0E]),
+        };
+        let ctor = b.add(&ctor);
+        let mut value = vec![0x01, 0x00];
+        for argument in [hex(&key.public_key_blob()), hex(&counter_signature)] {
+            let len = argument.len() as u16 | 0x8000;
+            value.extend_from_slice(&len.to_be_bytes());
+            value.extend_from_slice(argument.as_bytes());
+        }
+        value.extend_from_slice(&[0x00, 0x00]);
+        let attribute = CustomAttributeRow {
+            parent: TableId::Assembly.token(1),
+            ty: TableId::MemberRef.token(ctor),
+            value: b.blob(&value),
+        };
+        b.add(&attribute);
+    }
+    pe.set_metadata(b.to_bytes().unwrap());
+    PeImage::from_bytes(pe.to_bytes().unwrap()).unwrap()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// A second key pair, to sign with under enhanced strong naming.
+fn signature_key() -> StrongNameKeyPair {
+    StrongNameKeyPair::from_snk(&snk(0x51C)).unwrap()
+}
+
+#[test]
+fn signed_image_verifies() {
+    let key = test_key();
+    let signed = strong_named_image(&key, None)
+        .strong_name_sign(&key)
+        .unwrap();
+    let image = PeImage::from_bytes(signed).unwrap();
+    assert_eq!(
+        image.verify_strong_name().unwrap(),
+        StrongNameStatus::Verified
+    );
+    assert_eq!(
+        image
+            .verify_strong_name_with_key(&key.public_key_blob())
+            .unwrap(),
+        StrongNameStatus::Verified
+    );
+}
+
+#[test]
+fn one_byte_change_breaks_the_signature() {
+    let key = test_key();
+    let image = strong_named_image(&key, None);
+    let body = image
+        .metadata()
+        .unwrap()
+        .row::<MethodDefRow>(1)
+        .unwrap()
+        .rva;
+    let mut signed = image.strong_name_sign(&key).unwrap();
+    let offset = PeImage::from_bytes(signed.clone())
+        .unwrap()
+        .rva_to_offset(body)
+        .unwrap();
+    // `nop` becomes `ldarg.0`.
+    signed[offset + 1] = 0x02;
+    let image = PeImage::from_bytes(signed).unwrap();
+    assert_eq!(
+        image.verify_strong_name().unwrap(),
+        StrongNameStatus::SignatureMismatch
+    );
+}
+
+#[test]
+fn delay_signed_image_is_not_verified() {
+    let key = test_key();
+    let image = strong_named_image(&key, None);
+    assert_eq!(
+        image.verify_strong_name().unwrap(),
+        StrongNameStatus::DelaySigned
+    );
+    assert!(!image.verify_strong_name().unwrap().is_verified());
+}
+
+#[test]
+fn counter_signed_signature_key_verifies() {
+    let (identity, key) = (test_key(), signature_key());
+    let counter_signature = identity.sign_data(&key.public_key_blob()).unwrap();
+    let image = strong_named_image(&identity, Some((&key, counter_signature)));
+    let signed = image.strong_name_sign(&key).unwrap();
+    assert_eq!(
+        PeImage::from_bytes(signed)
+            .unwrap()
+            .verify_strong_name()
+            .unwrap(),
+        StrongNameStatus::Verified
+    );
+}
+
+#[test]
+fn counter_signature_by_another_key_is_rejected() {
+    let (identity, key) = (test_key(), signature_key());
+    // Signed by the signature key itself instead of the identity key.
+    let counter_signature = key.sign_data(&key.public_key_blob()).unwrap();
+    let image = strong_named_image(&identity, Some((&key, counter_signature)));
+    let signed = image.strong_name_sign(&key).unwrap();
+    assert_eq!(
+        PeImage::from_bytes(signed)
+            .unwrap()
+            .verify_strong_name()
+            .unwrap(),
+        StrongNameStatus::CounterSignatureMismatch
+    );
+}
+
+#[test]
+fn key_pair_snk_parses() {
+    let snk = snk(0x5EED);
+    let key = StrongNameKey::parse(&snk).unwrap();
+    let pair = key.key_pair().unwrap();
+    assert_eq!(pair.to_snk(), snk);
+    assert_eq!(pair.hash_algorithm(), StrongNameHashAlgorithm::Sha1);
+    assert_eq!(pair.signature_len(), 128);
+
+    // RSA signatures over SHA-1 hashes, then a 148-byte CryptoAPI blob.
+    let public_key = key.public_key_blob();
+    assert_eq!(
+        public_key[..12],
+        [0x00, 0x24, 0, 0, 0x04, 0x80, 0, 0, 148, 0, 0, 0]
+    );
+    assert_eq!(public_key[12..20], [0x06, 0x02, 0, 0, 0x00, 0x24, 0, 0]);
+    assert_eq!(&public_key[20..24], b"RSA1");
+    assert_eq!(public_key.len(), 12 + 148);
+}
+
+#[test]
+fn public_key_only_blobs_parse() {
+    let public_key = test_key().public_key_blob();
+
+    // `sn -p` output, a `PublicKeyBlob`.
+    let key = StrongNameKey::parse(&public_key).unwrap();
+    assert!(key.key_pair().is_none());
+    assert_eq!(key.public_key_blob(), public_key);
+
+    // A bare CryptoAPI `PUBLICKEYBLOB` gets the `PublicKeyBlob` header.
+    let key = StrongNameKey::parse(&public_key[12..]).unwrap();
+    assert!(matches!(key, StrongNameKey::PublicKey(_)));
+    assert_eq!(key.public_key_blob(), public_key);
+}
+
+#[test]
+fn malformed_snk_is_rejected() {
+    let snk = snk(0x5EED);
+    let truncated = StrongNameKeyPair::from_snk(&snk[..snk.len() - 1]);
+    assert!(matches!(truncated, Err(Error::BadStrongName(_))));
+
+    let mut not_rsa = snk.clone();
+    not_rsa[8..12].copy_from_slice(b"DSS2");
+    let not_rsa = StrongNameKeyPair::from_snk(&not_rsa);
+    assert!(matches!(not_rsa, Err(Error::BadStrongName(_))));
+
+    // A corrupted private exponent no longer matches the modulus.
+    let mut mismatched = snk.clone();
+    let last = mismatched.len() - 1;
+    mismatched[last] ^= 0x40;
+    let mismatched = StrongNameKeyPair::from_snk(&mismatched);
+    assert!(matches!(mismatched, Err(Error::BadStrongName(_))));
+
+    assert!(StrongNameKey::parse(b"not a key").is_err());
+}
+
+#[test]
+fn signing_with_a_key_file_round_trips_through_verification() {
+    let dir = temp_dir("sign");
+    let (key_path, image_path) = (dir.join("key.snk"), dir.join("Lib.dll"));
+    let key = test_key();
+    std::fs::write(&key_path, key.to_snk()).unwrap();
+    std::fs::write(&image_path, strong_named_image(&key, None).data()).unwrap();
+    assert_eq!(
+        strong_name_signature_verification(&image_path).unwrap(),
+        StrongNameStatus::DelaySigned
+    );
+
+    let key = StrongNameKeyPair::open(&key_path).unwrap();
+    strong_name_signature_generation(&image_path, &key).unwrap();
+    assert_eq!(
+        strong_name_signature_verification(&image_path).unwrap(),
+        StrongNameStatus::Verified
+    );
+    let image = PeImage::open(&image_path).unwrap();
+    assert_ne!(
+        image.cor_header().unwrap().flags & COMIMAGE_FLAGS_STRONGNAMESIGNED,
+        0
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn signing_with_another_key_is_rejected() {
+    let image = strong_named_image(&test_key(), None);
+    let result = image.strong_name_sign(&signature_key());
+    assert!(matches!(result, Err(Error::BadStrongName(_))));
+}
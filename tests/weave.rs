@@ -0,0 +1,238 @@
+mod common;
+
+use common::Fixture;
+use mscoree::{
+    AssemblyWeaver, DebugDirectoryEntry, Error, IMAGE_DEBUG_TYPE_PDBCHECKSUM, Label, LocalScopeRow,
+    Machine, MetadataBuilder, MetadataReader, MethodBody, MethodDebugInformationRow, MethodWeaver,
+    OpCode, Operand, PdbStream, PeBuilder, PeImage, TableId, WeaveContext,
+};
+use sha2::{Digest, Sha256};
+
+/// `static int Branchy(int x) => x != 0 ? 1 : 0;`
+///
+/// `ldarg.0; brtrue.s L; ldc.i4.0; ret; L: ldc.i4.1; ret`
+const BRANCHY: [u8; 8] = [0x1E, 0x02, 0x2D, 0x02, 0x16, 0x2A, 0x17, 0x2A];
+
+/// `static void Guarded() { try { } finally { } }`
+///
+/// `.try { nop; leave.s L } finally { endfinally } L: ret`, with a fat
+/// header declaring a `max_stack` of 0.
+const GUARDED: [u8; 36] = [
+    0x0B, 0x30, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // header
+    0x00, 0xDE, 0x01, 0xDC, 0x2A, 0x00, 0x00, 0x00, // code
+    0x01, 0x10, 0x00, 0x00, // small EH section
+    0x02, 0x00, 0x00, 0x00, 0x03, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, // finally clause
+];
+
+/// `static void Untouched() { }`
+const UNTOUCHED: [u8; 2] = [0x06, 0x2A];
+
+const PDB_ID: [u8; 20] = [9; 20];
+
+fn image() -> Vec<u8> {
+    let mut pe = PeBuilder::new(Machine::I386);
+    pe.set_dll(true);
+    let rvas = [
+        pe.add_method_body(&BRANCHY),
+        pe.add_method_body(&GUARDED),
+        pe.add_method_body(&UNTOUCHED),
+    ];
+
+    let mut woven = Fixture::new("Woven.dll");
+    for (rva, (name, signature)) in rvas.into_iter().zip([
+        ("Branchy", &[0x00, 0x01, 0x08, 0x08][..]),
+        ("Guarded", &[0x00, 0x00, 0x01]),
+        ("Untouched", &[0x00, 0x00, 0x01]),
+    ]) {
+        woven.method(rva, 0x0016, name, signature);
+    }
+    pe.set_metadata(woven.to_bytes());
+
+    // The checksum of the original PDB does not matter: the weaver
+    // recomputes it for the PDB it writes.
+    let mut checksum = b"SHA256\0".to_vec();
+    checksum.extend_from_slice(&[0; 32]);
+    let entry = DebugDirectoryEntry {
+        characteristics: 0,
+        time_date_stamp: 0,
+        major_version: 1,
+        minor_version: 0,
+        kind: IMAGE_DEBUG_TYPE_PDBCHECKSUM,
+        size_of_data: 0,
+        address_of_raw_data: 0,
+        pointer_to_raw_data: 0,
+    };
+    pe.add_debug_entry(entry, &checksum);
+    pe.to_bytes().unwrap()
+}
+
+/// A Portable PDB with a scope from the branch of `Branchy` to its end and one
+/// over all of `Guarded`.
+fn pdb() -> Vec<u8> {
+    let mut b = MetadataBuilder::new();
+    for _ in 0..3 {
+        b.add(&MethodDebugInformationRow {
+            document: 0,
+            sequence_points: 0,
+        });
+    }
+    for (method, start_offset, length) in [(1, 1, 6), (2, 0, 5)] {
+        b.add(&LocalScopeRow {
+            method,
+            import_scope: 0,
+            variable_list: 1,
+            constant_list: 1,
+            start_offset,
+            length,
+        });
+    }
+    let mut type_system_table_rows = [0; 64];
+    type_system_table_rows[TableId::MethodDef as usize] = 3;
+    b.set_pdb_stream(Some(PdbStream {
+        id: PDB_ID,
+        entry_point: 0,
+        referenced_type_system_tables: 1 << TableId::MethodDef as u8,
+        type_system_table_rows,
+    }));
+    b.to_bytes().unwrap()
+}
+
+/// Inserts `dup; pop` before the branch of `Branchy` and
+/// `ldc.i4.2; ldc.i4.3; pop; pop` before the try block of `Guarded`.
+struct Inserter;
+
+impl MethodWeaver for Inserter {
+    fn select(&mut self, _metadata: &MetadataReader, method: u32) -> bool {
+        method != 3
+    }
+
+    fn weave(
+        &mut self,
+        _context: &mut WeaveContext<'_>,
+        method: u32,
+        body: &mut MethodBody,
+    ) -> Result<(), Error> {
+        let (index, code) = match method {
+            1 => (1, &[OpCode::DUP, OpCode::POP][..]),
+            _ => (
+                0,
+                &[OpCode::LDC_I4_2, OpCode::LDC_I4_3, OpCode::POP, OpCode::POP][..],
+            ),
+        };
+        for (i, &opcode) in code.iter().enumerate() {
+            let instruction = body.instruction(opcode, Operand::None);
+            body.instructions.insert(index + i, instruction);
+        }
+        Ok(())
+    }
+}
+
+fn offsets(body: &MethodBody) -> Vec<u32> {
+    body.instructions
+        .iter()
+        .map(|i| i.original_offset.unwrap())
+        .collect()
+}
+
+/// The image and PDB written after weaving [`Inserter`] into [`image`].
+fn woven() -> (PeImage, Vec<u8>) {
+    let mut weaver = AssemblyWeaver::new(PeImage::from_bytes(image()).unwrap()).unwrap();
+    weaver.set_pdb(pdb()).unwrap();
+    assert_eq!(weaver.weave(&mut Inserter).unwrap(), 2);
+    let woven = weaver.write().unwrap();
+    (
+        PeImage::from_bytes(woven.image).unwrap(),
+        woven.pdb.unwrap(),
+    )
+}
+
+#[test]
+fn branches_keep_their_targets() {
+    let (image, _) = woven();
+    let metadata = image.metadata().unwrap();
+    // The branch still lands on `ldc.i4.1`, now two bytes further on.
+    let branchy = image.method_body(&metadata, 1).unwrap().unwrap();
+    assert_eq!(offsets(&branchy), [0, 1, 2, 3, 5, 6, 7, 8]);
+    assert_eq!(branchy.instructions[3].opcode, OpCode::BRTRUE_S);
+    assert_eq!(branchy.instructions[3].operand, Operand::Target(Label(6)));
+    assert_eq!(branchy.instructions[6].opcode, OpCode::LDC_I4_1);
+}
+
+#[test]
+fn handlers_move_with_the_protected_code() {
+    let (image, _) = woven();
+    let metadata = image.metadata().unwrap();
+    // The try block starts after the inserted code.
+    let guarded = image.method_body(&metadata, 2).unwrap().unwrap();
+    assert_eq!(offsets(&guarded), [0, 1, 2, 3, 4, 5, 7, 8]);
+    let [handler] = guarded.exception_handlers[..] else {
+        panic!("expected one handler");
+    };
+    let position = |label| guarded.position(label).unwrap();
+    assert_eq!(position(handler.try_start), 4);
+    assert_eq!(position(handler.try_end), 6);
+    assert_eq!(position(handler.handler_start), 6);
+    assert_eq!(position(handler.handler_end), 7);
+    assert_eq!(guarded.instructions[5].operand, Operand::Target(Label(7)));
+}
+
+#[test]
+fn max_stack_grows_with_the_inserted_code() {
+    let (image, _) = woven();
+    let metadata = image.metadata().unwrap();
+    // The fat header declared 0; the inserted code pushes two constants.
+    let guarded = image.method_body(&metadata, 2).unwrap().unwrap();
+    assert_eq!(guarded.max_stack, 2);
+}
+
+#[test]
+fn methods_not_woven_keep_their_bodies() {
+    let original = PeImage::from_bytes(image()).unwrap();
+    let original_metadata = original.metadata().unwrap();
+    let (image, _) = woven();
+    let metadata = image.metadata().unwrap();
+    assert_eq!(
+        image.method_body_bytes(&metadata, 3).unwrap(),
+        original.method_body_bytes(&original_metadata, 3).unwrap()
+    );
+}
+
+#[test]
+fn local_scopes_follow_the_instructions() {
+    // Branchy's scope starts at the branch, which moved from 1 to 3, and
+    // both end with the new code.
+    let (_, pdb) = woven();
+    let reader = MetadataReader::from_bytes(pdb).unwrap();
+    let scopes: Vec<_> = (1..=reader.row_count(TableId::LocalScope))
+        .map(|rid| {
+            let row = reader.row::<LocalScopeRow>(rid).unwrap();
+            (row.method, row.start_offset, row.length)
+        })
+        .collect();
+    assert_eq!(scopes, [(1, 3, 6), (2, 0, 9)]);
+}
+
+#[test]
+fn pdb_checksum_covers_the_new_pdb() {
+    // The checksum is taken with the PDB id zeroed.
+    let (image, pdb) = woven();
+    let entry = image
+        .debug_directory()
+        .unwrap()
+        .into_iter()
+        .find(|entry| entry.kind == IMAGE_DEBUG_TYPE_PDBCHECKSUM)
+        .unwrap();
+    let data = image.debug_data(&entry).unwrap();
+    let reader = MetadataReader::from_bytes(pdb.clone()).unwrap();
+    let id_offset = reader
+        .streams()
+        .iter()
+        .find(|stream| stream.name == "#Pdb")
+        .unwrap()
+        .offset as usize;
+    assert_eq!(&pdb[id_offset..id_offset + 20], PDB_ID);
+    let mut zeroed = pdb.clone();
+    zeroed[id_offset..id_offset + 20].fill(0);
+    assert_eq!(&data[..7], b"SHA256\0");
+    assert_eq!(&data[7..], Sha256::digest(&zeroed).as_slice());
+}
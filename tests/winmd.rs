@@ -0,0 +1,187 @@
+mod common;
+
+use common::Fixture;
+use mscoree::{
+    AssemblyRefRow, AssemblyRow, Error, FieldRow, InterfaceImplRow, MetadataReader, TableId,
+    TypeDefRow, TypeRefRow, WinMdKind, token_rid, winrt_projected_type,
+};
+
+const PUBLIC: u32 = 0x0010_0001;
+const INTERFACE: u32 = 0x0000_00a1;
+const WINDOWS_RUNTIME: u32 = 0x0000_4000;
+const IMPORT: u32 = 0x0000_1000;
+const VISIBILITY_MASK: u32 = 0x0000_0007;
+/// `AssemblyFlags.ContentType` of Windows Runtime assemblies.
+const CONTENT_TYPE_WINDOWS_RUNTIME: u32 = 0x0000_0200;
+const WINDOWS: &str = "WindowsRuntime 1.4";
+const MANAGED: &str = "WindowsRuntime 1.4;CLR v4.0.30319";
+
+/// Returns a `TypeDefOrRef` coded index for use in a signature.
+fn coded(token: u32) -> u8 {
+    let tag = match TableId::from_token(token).unwrap().0 {
+        TableId::TypeDef => 0,
+        TableId::TypeRef => 1,
+        _ => unreachable!(),
+    };
+    (token_rid(token) << 2 | tag) as u8
+}
+
+/// The `TypeRef` a reference was redirected to, as `Assembly!Namespace.Name`.
+fn type_ref(metadata: &MetadataReader, token: u32) -> String {
+    let row = metadata.row::<TypeRefRow>(token_rid(token)).unwrap();
+    let scope = metadata
+        .row::<AssemblyRefRow>(token_rid(row.resolution_scope))
+        .unwrap();
+    format!(
+        "{}!{}",
+        metadata.string(scope.name).unwrap(),
+        metadata.type_full_name(token).unwrap()
+    )
+}
+
+fn type_def(metadata: &MetadataReader, token: u32) -> (String, u32) {
+    let row = metadata.row::<TypeDefRow>(token_rid(token)).unwrap();
+    (metadata.type_full_name(token).unwrap(), row.flags)
+}
+
+#[test]
+fn windows_runtime_types_project_to_clr_types() {
+    let closable = winrt_projected_type("Windows.Foundation", "IClosable").unwrap();
+    assert_eq!(
+        (closable.namespace, closable.name, closable.assembly),
+        ("System", "IDisposable", "System.Runtime")
+    );
+    let pair = winrt_projected_type("Windows.Foundation.Collections", "IKeyValuePair`2").unwrap();
+    assert_eq!(pair.name, "KeyValuePair`2");
+    assert!(!pair.winrt_is_value_type && pair.is_value_type);
+    let command = winrt_projected_type("Windows.UI.Xaml.Input", "ICommand").unwrap();
+    assert_eq!(
+        (command.namespace, command.assembly),
+        ("System.Windows.Input", "System.ObjectModel")
+    );
+    assert!(winrt_projected_type("Windows.Foundation", "IAsyncAction").is_none());
+    assert!(winrt_projected_type("System", "Uri").is_none());
+}
+
+#[test]
+fn winmd_kind_comes_from_the_version_string() {
+    let mut fixture = Fixture::new("Lib.dll");
+    assert_eq!(fixture.to_reader().winmd_kind(), None);
+    assert!(matches!(
+        fixture.to_reader().winmd_projection(),
+        Err(Error::BadMetadata(_))
+    ));
+    fixture.b.set_version(WINDOWS);
+    assert_eq!(fixture.to_reader().winmd_kind(), Some(WinMdKind::Windows));
+    fixture.b.set_version(MANAGED);
+    assert_eq!(fixture.to_reader().winmd_kind(), Some(WinMdKind::Managed));
+}
+
+#[test]
+fn referenced_interfaces_are_redirected() {
+    let mut winmd = Fixture::new("Contoso.winmd");
+    winmd.b.set_version(WINDOWS);
+    let mscorlib = winmd.assembly_ref("mscorlib", 255);
+    let foundation = winmd.assembly_ref("Windows.Foundation", 255);
+    let object = winmd.type_ref(mscorlib, "System", "Object");
+    let closable = winmd.type_ref(foundation, "Windows.Foundation", "IClosable");
+    let hresult = winmd.type_ref(foundation, "Windows.Foundation", "HResult");
+    let widget = winmd.type_def(PUBLIC | WINDOWS_RUNTIME, "Contoso", "Widget", object);
+    winmd.field(0x0001, "error", &[0x06, 0x11, coded(hresult)]);
+    winmd.b.add(&InterfaceImplRow {
+        class: token_rid(widget),
+        interface: closable,
+    });
+
+    let projected = winmd.to_reader().winmd_projection().unwrap();
+    assert_eq!(projected.winmd_kind(), Some(WinMdKind::Windows));
+    let interface = projected.row::<InterfaceImplRow>(1).unwrap().interface;
+    assert_eq!(interface, closable);
+    assert_eq!(
+        type_ref(&projected, interface),
+        "System.Runtime!System.IDisposable"
+    );
+    assert_eq!(
+        type_ref(&projected, hresult),
+        "System.Runtime!System.Exception"
+    );
+    assert_eq!(type_ref(&projected, object), "System.Runtime!System.Object");
+    // `HResult` is a value type, `Exception` a class.
+    let field = projected.row::<FieldRow>(1).unwrap();
+    assert_eq!(
+        projected.blob(field.signature).unwrap(),
+        [0x06, 0x12, coded(hresult)]
+    );
+
+    let (name, flags) = type_def(&projected, widget);
+    assert_eq!(name, "Contoso.Widget");
+    assert_eq!(flags & IMPORT, IMPORT);
+    let foundation = projected
+        .row::<AssemblyRefRow>(token_rid(foundation))
+        .unwrap();
+    assert_eq!(
+        foundation.flags & CONTENT_TYPE_WINDOWS_RUNTIME,
+        CONTENT_TYPE_WINDOWS_RUNTIME
+    );
+    let assembly = projected.row::<AssemblyRow>(1).unwrap();
+    assert_eq!(
+        assembly.flags & CONTENT_TYPE_WINDOWS_RUNTIME,
+        CONTENT_TYPE_WINDOWS_RUNTIME
+    );
+}
+
+#[test]
+fn projected_definitions_are_hidden_and_references_redirected() {
+    let mut winmd = Fixture::new("Windows.Foundation.winmd");
+    winmd.b.set_version(WINDOWS);
+    let closable = winmd.type_def(
+        PUBLIC | INTERFACE | WINDOWS_RUNTIME,
+        "Windows.Foundation",
+        "IClosable",
+        0,
+    );
+    let stream = winmd.type_def(
+        PUBLIC | INTERFACE | WINDOWS_RUNTIME,
+        "Windows.Foundation",
+        "IStream",
+        0,
+    );
+    winmd.b.add(&InterfaceImplRow {
+        class: token_rid(stream),
+        interface: closable,
+    });
+
+    let projected = winmd.to_reader().winmd_projection().unwrap();
+    let (name, flags) = type_def(&projected, closable);
+    assert_eq!(name, "Windows.Foundation.<WinRT>IClosable");
+    assert_eq!(flags & VISIBILITY_MASK, 0);
+    let interface = projected.row::<InterfaceImplRow>(1).unwrap().interface;
+    assert_eq!(TableId::from_token(interface).unwrap().0, TableId::TypeRef);
+    assert_eq!(
+        type_ref(&projected, interface),
+        "System.Runtime!System.IDisposable"
+    );
+    let (name, flags) = type_def(&projected, stream);
+    assert_eq!(name, "Windows.Foundation.IStream");
+    assert_eq!(flags & IMPORT, IMPORT);
+}
+
+#[test]
+fn managed_winmds_expose_the_clr_implementation() {
+    let mut winmd = Fixture::new("Contoso.winmd");
+    winmd.b.set_version(MANAGED);
+    let mscorlib = winmd.assembly_ref("mscorlib", 4);
+    let object = winmd.type_ref(mscorlib, "System", "Object");
+    let facing = winmd.type_def(PUBLIC | WINDOWS_RUNTIME, "Contoso", "Widget", object);
+    let implementation = winmd.type_def(0, "Contoso", "<CLR>Widget", object);
+
+    let projected = winmd.to_reader().winmd_projection().unwrap();
+    assert_eq!(projected.winmd_kind(), Some(WinMdKind::Managed));
+    let (name, flags) = type_def(&projected, facing);
+    assert_eq!(name, "Contoso.<WinRT>Widget");
+    assert_eq!(flags & VISIBILITY_MASK, 0);
+    assert_eq!(flags & IMPORT, 0);
+    let (name, flags) = type_def(&projected, implementation);
+    assert_eq!(name, "Contoso.Widget");
+    assert_eq!(flags & VISIBILITY_MASK, 1);
+}
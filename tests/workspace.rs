@@ -0,0 +1,216 @@
+mod common;
+
+use std::fs;
+
+use common::{Fixture, temp_dir};
+use mscoree::{Machine, ModuleRefRow, NestedClassRow, PeBuilder, TableId, Workspace, token_rid};
+
+const PUBLIC: u32 = 0x0010_0001;
+const NESTED_PUBLIC: u32 = 0x0010_0002;
+/// `instance void ()`.
+const VOID: [u8; 3] = [0x20, 0x00, 0x01];
+
+/// `Core` at `major_version`: `Core.Widget` with `void Run()` and a nested
+/// `Part`.
+fn core(major_version: u16) -> Fixture {
+    let mut core = Fixture::versioned("Core.dll", major_version);
+    let widget = core.type_def(PUBLIC, "Core", "Widget", 0);
+    core.method(0, 0x0006, "Run", &VOID);
+    let part = core.type_def(NESTED_PUBLIC, "", "Part", 0);
+    core.b.add(&NestedClassRow {
+        nested_class: token_rid(part),
+        enclosing_class: token_rid(widget),
+    });
+    core
+}
+
+/// The references of `App`, in token order.
+struct AppRefs {
+    widget: u32,
+    part: u32,
+    gone: u32,
+    elsewhere: u32,
+    extra: u32,
+    run: u32,
+    stop: u32,
+}
+
+/// `App`, referencing `Core` 1.0.0.0, `Other` and the module
+/// `Extra.netmodule`.
+fn app() -> (Fixture, AppRefs) {
+    let mut app = Fixture::new("App.dll");
+    let core = app.assembly_ref("Core", 1);
+    let other = app.assembly_ref("Other", 1);
+    let name = app.b.string("Extra.netmodule");
+    let module = TableId::ModuleRef.token(app.b.add(&ModuleRefRow { name }));
+    let widget = app.type_ref(core, "Core", "Widget");
+    let refs = AppRefs {
+        widget,
+        part: app.type_ref(widget, "", "Part"),
+        gone: app.type_ref(core, "Core", "Gone"),
+        elsewhere: app.type_ref(other, "Other", "Thing"),
+        extra: app.type_ref(module, "Extra", "Thing"),
+        run: app.member_ref(widget, "Run", &VOID),
+        stop: app.member_ref(widget, "Stop", &VOID),
+    };
+    (app, refs)
+}
+
+/// A netmodule defining `Extra.Thing`.
+fn extra() -> Fixture {
+    let mut extra = Fixture::new("Extra.netmodule");
+    extra.type_def(PUBLIC, "Extra", "Thing", 0);
+    extra
+}
+
+#[test]
+fn added_modules_are_listed_with_their_identity() {
+    let mut workspace = Workspace::new();
+    let app = workspace.add_metadata(app().0.to_reader(), None).unwrap();
+    let core = workspace.add_metadata(core(1).to_reader(), None).unwrap();
+    let names: Vec<_> = workspace
+        .modules()
+        .map(|(id, module)| (id, module.module_name.clone(), module.display_name()))
+        .collect();
+    assert_eq!(
+        names,
+        [
+            (
+                app,
+                "App.dll".to_owned(),
+                "App, Version=1.0.0.0, Culture=neutral, PublicKeyToken=null".to_owned()
+            ),
+            (
+                core,
+                "Core.dll".to_owned(),
+                "Core, Version=1.0.0.0, Culture=neutral, PublicKeyToken=null".to_owned()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn find_type_only_sees_top_level_types() {
+    let mut workspace = Workspace::new();
+    let core = workspace.add_metadata(core(1).to_reader(), None).unwrap();
+    let widget = workspace.find_type(core, "Core", "Widget").unwrap();
+    assert_eq!(workspace.type_name(widget).unwrap(), "Core.Widget");
+    assert!(workspace.find_type(core, "", "Part").is_none());
+    assert!(workspace.find_type(core, "Core", "Missing").is_none());
+}
+
+#[test]
+fn type_refs_resolve_to_definitions_in_other_assemblies() {
+    let (app, refs) = app();
+    let mut workspace = Workspace::new();
+    let app = workspace.add_metadata(app.to_reader(), None).unwrap();
+    let core = workspace.add_metadata(core(1).to_reader(), None).unwrap();
+
+    let widget = workspace.resolve_type(app, refs.widget).unwrap().unwrap();
+    assert_eq!(widget, workspace.find_type(core, "Core", "Widget").unwrap());
+    let part = workspace.resolve_type(app, refs.part).unwrap().unwrap();
+    assert_eq!(part.module, core);
+    assert_eq!(workspace.type_name(part).unwrap(), "Core.Widget+Part");
+}
+
+#[test]
+fn member_refs_resolve_to_their_definition() {
+    let (app, refs) = app();
+    let mut workspace = Workspace::new();
+    let app = workspace.add_metadata(app.to_reader(), None).unwrap();
+    let core = workspace.add_metadata(core(1).to_reader(), None).unwrap();
+
+    let run = workspace.resolve_member(app, refs.run).unwrap().unwrap();
+    assert!(run.is_method());
+    assert_eq!(run.module, core);
+    let declaring = workspace.declaring_type(run).unwrap().unwrap();
+    assert_eq!(workspace.type_name(declaring).unwrap(), "Core.Widget");
+    assert!(workspace.resolve_member(app, refs.stop).unwrap().is_none());
+}
+
+#[test]
+fn type_refs_into_loaded_netmodules_resolve() {
+    let (app, refs) = app();
+    let mut workspace = Workspace::new();
+    let app = workspace.add_metadata(app.to_reader(), None).unwrap();
+    assert!(workspace.resolve_type(app, refs.extra).unwrap().is_none());
+    let extra = workspace.add_metadata(extra().to_reader(), None).unwrap();
+    let thing = workspace.resolve_type(app, refs.extra).unwrap().unwrap();
+    assert_eq!(thing.module, extra);
+}
+
+#[test]
+fn unresolved_references_give_the_reason() {
+    let (app, refs) = app();
+    let mut workspace = Workspace::new();
+    workspace.add_metadata(app.to_reader(), None).unwrap();
+    workspace.add_metadata(core(1).to_reader(), None).unwrap();
+    let unresolved: Vec<_> = workspace
+        .unresolved_references()
+        .unwrap()
+        .into_iter()
+        .map(|reference| (reference.token, reference.reason.to_string()))
+        .collect();
+    let core = "Core, Version=1.0.0.0, Culture=neutral, PublicKeyToken=null";
+    assert_eq!(
+        unresolved,
+        [
+            (
+                refs.gone,
+                format!("type Core.Gone is not defined in {core}")
+            ),
+            (
+                refs.elsewhere,
+                "assembly Other, Version=1.0.0.0, Culture=neutral, PublicKeyToken=null \
+                 is not loaded"
+                    .to_owned()
+            ),
+            (
+                refs.extra,
+                "module Extra.netmodule is not loaded".to_owned()
+            ),
+            (refs.stop, "no matching member in Core.Widget".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn exact_version_is_preferred_over_the_highest() {
+    let mut workspace = Workspace::new();
+    let v1 = workspace.add_metadata(core(1).to_reader(), None).unwrap();
+    let v2 = workspace.add_metadata(core(2).to_reader(), None).unwrap();
+    let find = |reference: &str| workspace.find_assembly(&reference.parse().unwrap());
+    assert_eq!(find("Core, Version=1.0.0.0"), Some(v1));
+    assert_eq!(find("Core, Version=3.0.0.0"), Some(v2));
+    assert_eq!(find("Core"), Some(v2));
+    assert_eq!(find("Core, PublicKeyToken=b77a5c561934e089"), None);
+}
+
+#[test]
+fn files_are_loaded_once() {
+    let dir = temp_dir("workspace-files");
+    let path = dir.join("Core.dll");
+    let mut pe = PeBuilder::new(Machine::I386);
+    pe.set_dll(true);
+    pe.set_metadata(core(1).to_bytes());
+    fs::write(&path, pe.to_bytes().unwrap()).unwrap();
+
+    let mut workspace = Workspace::new();
+    let first = workspace.add_file(&path).unwrap();
+    assert_eq!(workspace.add_file(&path).unwrap(), first);
+    assert_eq!(workspace.modules().count(), 1);
+    assert_eq!(
+        workspace.module(first).path.as_deref(),
+        Some(path.as_path())
+    );
+    assert!(workspace.add_file(dir.join("Missing.dll")).is_err());
+}
+
+#[test]
+fn non_type_tokens_are_rejected() {
+    let (app, refs) = app();
+    let mut workspace = Workspace::new();
+    let app = workspace.add_metadata(app.to_reader(), None).unwrap();
+    assert!(workspace.resolve_type(app, refs.run).is_err());
+    assert!(workspace.resolve_member(app, refs.widget).is_err());
+}